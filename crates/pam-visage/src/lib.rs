@@ -6,11 +6,15 @@
 //! # Safety
 //!
 //! All Rust logic is wrapped in `catch_unwind` — a panic unwinding across the
-//! `extern "C"` boundary is undefined behavior.
+//! `extern "C"` boundary is undefined behavior. That includes the
+//! `setcred`, `acct_mgmt`, session and `chauthtok` entry points, which only
+//! return `PAM_IGNORE`: Visage takes part in the `auth` phase alone.
 //!
 //! Every error path returns `PAM_IGNORE` (25), which tells the PAM stack to
-//! skip this module and continue to the next (e.g., password). We never return
-//! `PAM_AUTH_ERR` to avoid locking the user out if the daemon is unavailable.
+//! skip this module and continue to the next (e.g., password), except that a
+//! daemon that cannot be reached at all returns `PAM_AUTHINFO_UNAVAIL` (9) so
+//! stacks can tell the two apart. We never return `PAM_AUTH_ERR` to avoid
+//! locking the user out if the daemon is unavailable.
 
 // Enforce explicit `unsafe {}` blocks inside `unsafe fn` bodies — catches
 // the Rust 2024 edition change before it lands.
@@ -19,21 +23,129 @@
 use std::ffi::{CStr, CString};
 use std::panic;
 use std::ptr;
+use std::time::{Duration, Instant};
 
 // PAM return codes (POSIX / Linux-PAM values)
 const PAM_SUCCESS: libc::c_int = 0;
+const PAM_AUTHINFO_UNAVAIL: libc::c_int = 9;
 const PAM_IGNORE: libc::c_int = 25;
 
 // PAM item types
+const PAM_SERVICE: libc::c_int = 1;
+const PAM_TTY: libc::c_int = 3;
+const PAM_RHOST: libc::c_int = 4;
 const PAM_CONV: libc::c_int = 5;
 
 // PAM message styles
 const PAM_TEXT_INFO: libc::c_int = 4;
 
+// Module option: send non-UTF-8 usernames percent-encoded instead of skipping
+// them. Requires `VISAGE_PERCENT_ENCODED_USERNAMES=1` on the daemon.
+const OPT_PERCENT_ENCODE: &[u8] = b"percent_encode_usernames";
+
+// Daemon capability required before an encoded name is sent; older daemons
+// would look the escaped form up literally.
+const CAP_PERCENT_ENCODED: &str = "percent_encoded_usernames";
+
+/// Module argument `use_token`: keep the one-time token visaged issues with a
+/// match in PAM data, and redeem it instead of capturing when pam_visage runs
+/// again on the same PAM handle.
+const OPT_USE_TOKEN: &[u8] = b"use_token";
+
+// Daemon capability for `VerifyWithToken` / `RedeemToken`.
+const CAP_VERIFY_TOKENS: &str = "verify_tokens";
+
+/// Module argument `grace=N`: accept a match visaged made in this caller's
+/// logind session within the last N seconds instead of capturing again.
+const OPT_GRACE: &[u8] = b"grace=";
+
+// Daemon capability for `VerifyDetailed`, used with `debug` to log the
+// similarity and matched model.
+const CAP_VERIFY_DETAILED: &str = "verify_detailed";
+
+// Daemon capability for `VerifyCached`.
+const CAP_VERIFY_GRACE: &str = "verify_grace";
+
+// PAM data name the token is kept under (NUL-terminated for the C API).
+const TOKEN_DATA: &[u8] = b"pam_visage_token\0";
+
+/// Module argument `allow_remote`: try face auth even for remote logins.
+const OPT_ALLOW_REMOTE: &[u8] = b"allow_remote";
+
+/// Module arguments `service_allow=a,b` / `service_deny=c`: comma-separated
+/// PAM service names, `*` matching any run of characters. Deny wins.
+const OPT_SERVICE_ALLOW: &[u8] = b"service_allow=";
+const OPT_SERVICE_DENY: &[u8] = b"service_deny=";
+
+// PAM_TTY OpenSSH sets while authenticating, before a pty is allocated.
+const SSH_TTY: &[u8] = b"ssh";
+
+/// Module argument `quiet`: send the user no progress messages.
+const OPT_QUIET: &[u8] = b"quiet";
+
+// Progress messages shown while and after visaged looks for a face.
+const MSG_LOOKING: &str = "Looking for your face…";
+const MSG_RECOGNIZED: &str = "Face recognized";
+const MSG_NOT_RECOGNIZED: &str = "Face not recognized, falling back to password";
+const MSG_UNAVAILABLE: &str = "Face authentication unavailable, falling back to password";
+
+/// How long a verify may take before the user is told visaged is looking.
+/// Refusals that never reach the camera (not enrolled, denied) come back
+/// well within it, so those users see nothing.
+const LOOKING_DELAY: Duration = Duration::from_millis(300);
+
+/// Module argument `debug`: also log routine detail at `LOG_DEBUG`.
+const OPT_DEBUG: &[u8] = b"debug";
+
+/// Module argument `timeout=N`: cap each D-Bus call to visaged at N seconds.
+const OPT_TIMEOUT: &[u8] = b"timeout=";
+
+/// Per-call D-Bus timeout when no valid `timeout=` argument is given.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Module argument `max_tries=N`: ask visaged up to N times while it finds
+/// no match.
+const OPT_MAX_TRIES: &[u8] = b"max_tries=";
+
+/// Module argument `retry_delay_ms=N`: pause between tries.
+const OPT_RETRY_DELAY: &[u8] = b"retry_delay_ms=";
+
+/// Module arguments `bus_address=ADDR` / `bus_name=NAME`: reach visaged on
+/// the D-Bus at `ADDR` instead of the system bus, or under `NAME` instead of
+/// its usual name. Whatever answers there can authenticate users, so these
+/// exist for tests against a private bus, not for production stacks.
+const OPT_BUS_ADDRESS: &[u8] = b"bus_address=";
+const OPT_BUS_NAME: &[u8] = b"bus_name=";
+
+/// The bus name visaged owns.
+const DEFAULT_BUS_NAME: &str = "org.freedesktop.Visage1";
+
+const DEFAULT_MAX_TRIES: u32 = 1;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// Error names visaged returns (`org.freedesktop.Visage1.Error.<Variant>`).
+const ERR_POLICY_DENIED: &str = "org.freedesktop.Visage1.Error.PolicyDenied";
+const ERR_ACCESS_DENIED: &str = "org.freedesktop.Visage1.Error.AccessDenied";
+const ERR_NOT_ENROLLED: &str = "org.freedesktop.Visage1.Error.NotEnrolled";
+const ERR_STORE_UNAVAILABLE: &str = "org.freedesktop.Visage1.Error.StoreUnavailable";
+const ERR_DIRECTORY_UNAVAILABLE: &str = "org.freedesktop.Visage1.Error.DirectoryUnavailable";
+const ERR_RATE_LIMITED: &str = "org.freedesktop.Visage1.Error.RateLimited";
+const ERR_CAMERA_UNAVAILABLE: &str = "org.freedesktop.Visage1.Error.CameraUnavailable";
+const ERR_FACE_CHANGED: &str = "org.freedesktop.Visage1.Error.FaceChangedDuringCapture";
+const ERR_TIMEOUT: &str = "org.freedesktop.Visage1.Error.Timeout";
+const ERR_RECOGNIZER_UNAVAILABLE: &str = "org.freedesktop.Visage1.Error.RecognizerUnavailable";
+const ERR_ENGINE_RESTARTED: &str = "org.freedesktop.Visage1.Error.EngineRestarted";
+const ERR_CANCELLED: &str = "org.freedesktop.Visage1.Error.Cancelled";
+
+// Message of the generic `Failed` error older daemons return for a user
+// without templates.
+const LEGACY_NOT_ENROLLED_PREFIX: &str = "no enrolled models";
+
 // syslog constants
-const LOG_PID: libc::c_int = 0x01;
 const LOG_AUTHPRIV: libc::c_int = 10 << 3;
+const LOG_NOTICE: libc::c_int = 5;
 const LOG_INFO: libc::c_int = 6;
+const LOG_DEBUG: libc::c_int = 7;
 const LOG_WARNING: libc::c_int = 4;
 const LOG_ERR: libc::c_int = 3;
 
@@ -49,6 +161,21 @@ extern "C" {
         item_type: libc::c_int,
         item: *mut *const libc::c_void,
     ) -> libc::c_int;
+
+    fn pam_syslog(pamh: *const libc::c_void, priority: libc::c_int, fmt: *const libc::c_char, ...);
+
+    fn pam_set_data(
+        pamh: *mut libc::c_void,
+        module_data_name: *const libc::c_char,
+        data: *mut libc::c_void,
+        cleanup: Option<unsafe extern "C" fn(*mut libc::c_void, *mut libc::c_void, libc::c_int)>,
+    ) -> libc::c_int;
+
+    fn pam_get_data(
+        pamh: *const libc::c_void,
+        module_data_name: *const libc::c_char,
+        data: *mut *const libc::c_void,
+    ) -> libc::c_int;
 }
 
 /// PAM message struct — mirrors `struct pam_message` from <security/pam_appl.h>.
@@ -88,98 +215,839 @@ struct PamConv {
 )]
 trait Visage {
     async fn verify(&self, user: &str) -> zbus::Result<bool>;
+    async fn verify_with_token(&self, user: &str) -> zbus::Result<(bool, String)>;
+    async fn verify_detailed(&self, user: &str) -> zbus::Result<String>;
+    async fn redeem_token(&self, user: &str, token: &str) -> zbus::Result<bool>;
+    async fn verify_cached(&self, user: &str, max_age_secs: u32) -> zbus::Result<bool>;
+    async fn get_capabilities(&self) -> zbus::Result<Vec<String>>;
+}
+
+/// Diagnostics sink for one PAM call.
+///
+/// Messages go through `pam_syslog(3)` at `LOG_AUTHPRIV`, which tags them
+/// with the calling service (`pam_visage(sudo:auth): ...`) and keeps them out
+/// of the user's terminal and the login manager's UI. `LOG_DEBUG` messages
+/// are dropped unless the `debug` module argument is given.
+struct Log {
+    pamh: *mut libc::c_void,
+    debug: bool,
 }
 
-/// Open syslog with `pam_visage` ident and `LOG_AUTHPRIV` facility.
-fn syslog_open() {
-    // The ident string must outlive the openlog call. Using a static ensures this.
-    static IDENT: &[u8] = b"pam_visage\0";
-    // SAFETY: IDENT is a valid NUL-terminated static string.
-    unsafe {
-        libc::openlog(IDENT.as_ptr() as *const libc::c_char, LOG_PID, LOG_AUTHPRIV);
+impl Log {
+    fn msg(&self, priority: libc::c_int, msg: &str) {
+        if priority == LOG_DEBUG && !self.debug {
+            return;
+        }
+        let line = log_line(msg);
+        // pam_syslog interprets % as format specifiers. Use "%s" to avoid injection.
+        let fmt = b"%s\0";
+        // SAFETY: pamh is the handle PAM passed in; fmt and line are valid
+        // NUL-terminated strings matching one "%s" conversion.
+        unsafe {
+            pam_syslog(
+                self.pamh,
+                LOG_AUTHPRIV | priority,
+                fmt.as_ptr() as *const libc::c_char,
+                line.as_ptr(),
+            );
+        }
     }
 }
 
-/// Log a message to syslog at the given priority.
-fn syslog_msg(priority: libc::c_int, msg: &str) {
-    // syslog(3) interprets % as format specifiers. Use "%s" format to avoid injection.
-    let c_msg = match CString::new(msg) {
-        Ok(s) => s,
-        Err(_) => return, // interior NUL — skip logging rather than panic
-    };
-    let fmt = b"%s\0";
-    // SAFETY: fmt is a valid NUL-terminated format string; c_msg is a valid C string.
-    unsafe {
-        libc::syslog(
-            priority,
-            fmt.as_ptr() as *const libc::c_char,
-            c_msg.as_ptr(),
-        );
+/// Build the C string for one log message. Control characters, including
+/// NUL and newlines from a hostile username, are escaped so every message
+/// stays one complete log line.
+fn log_line(msg: &str) -> CString {
+    let mut line = String::with_capacity(msg.len());
+    for c in msg.chars() {
+        if c.is_control() {
+            line.extend(c.escape_default());
+        } else {
+            line.push(c);
+        }
+    }
+    CString::new(line).unwrap_or_default()
+}
+
+/// Progress messages for the user, sent through the PAM conversation
+/// function as `PAM_TEXT_INFO`.
+///
+/// Silent when the application supplied no conversation function (or the
+/// `quiet` module argument is given) — this is non-critical feedback and
+/// must never block authentication.
+struct Feedback<'a> {
+    conv: Option<&'a PamConv>,
+}
+
+impl<'a> Feedback<'a> {
+    /// Look up the conversation function for `pamh`.
+    ///
+    /// # Safety
+    ///
+    /// `pamh` must be a valid PAM handle that outlives `'a`.
+    unsafe fn for_handle(pamh: *mut libc::c_void, quiet: bool) -> Self {
+        if quiet {
+            return Self { conv: None };
+        }
+        let mut conv_ptr: *const libc::c_void = ptr::null();
+        // SAFETY: pamh is a valid PAM handle. pam_get_item reads the conversation struct.
+        let ret = unsafe { pam_get_item(pamh, PAM_CONV, &mut conv_ptr) };
+        if ret != PAM_SUCCESS || conv_ptr.is_null() {
+            return Self { conv: None };
+        }
+        // SAFETY: pam_get_item with PAM_CONV returns a pointer to a pam_conv
+        // struct owned by the handle.
+        Self {
+            conv: Some(unsafe { &*(conv_ptr as *const PamConv) }),
+        }
+    }
+
+    fn info(&self, text: &str) {
+        let Some(conv) = self.conv else {
+            return;
+        };
+        let Some(conv_fn) = conv.conv else {
+            return;
+        };
+        let Ok(c_text) = CString::new(text) else {
+            return;
+        };
+
+        let msg = PamMessage {
+            msg_style: PAM_TEXT_INFO,
+            msg: c_text.as_ptr(),
+        };
+        let msg_ptr: *const PamMessage = &msg;
+        let mut resp_ptr: *mut PamResponse = ptr::null_mut();
+
+        // SAFETY: msg_ptr points to a valid PamMessage, conv_fn is the PAM conversation callback.
+        unsafe {
+            conv_fn(
+                1,
+                &msg_ptr as *const _ as *mut _,
+                &mut resp_ptr,
+                conv.appdata_ptr,
+            );
+            // Free response array if allocated. TEXT_INFO rarely gets a response, but the spec
+            // requires us to free both the response string and the response struct if present.
+            if !resp_ptr.is_null() {
+                if !(*resp_ptr).resp.is_null() {
+                    libc::free((*resp_ptr).resp as *mut libc::c_void);
+                }
+                libc::free(resp_ptr as *mut libc::c_void);
+            }
+        }
+    }
+}
+
+/// What the user is told once the verify call returns. Nothing for a user
+/// who never enrolled or may not use face auth here: for them the module
+/// stays silent.
+fn outcome_message(outcome: &Result<bool, VerifyError>) -> Option<&'static str> {
+    match outcome {
+        Ok(true) => Some(MSG_RECOGNIZED),
+        Ok(false) => Some(MSG_NOT_RECOGNIZED),
+        Err(VerifyError::NotEnrolled | VerifyError::Denied(_)) => None,
+        Err(_) => Some(MSG_UNAVAILABLE),
+    }
+}
+
+/// Run `work` on its own thread and call `announce` if it has not
+/// finished after `after`, then wait for it.
+fn announce_if_slow<T: Send>(
+    work: impl FnOnce() -> T + Send,
+    after: Duration,
+    announce: impl FnOnce(),
+) -> T {
+    std::thread::scope(|scope| {
+        let (done, finished) = std::sync::mpsc::channel();
+        let worker = scope.spawn(move || {
+            let result = work();
+            let _ = done.send(());
+            result
+        });
+        if finished.recv_timeout(after).is_err() {
+            announce();
+        }
+        worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// A string PAM item (`PAM_RHOST`, `PAM_TTY`, …), or `None` when unset.
+///
+/// # Safety
+///
+/// `pamh` must be a valid PAM handle; the returned bytes borrow from it.
+unsafe fn string_item<'a>(pamh: *mut libc::c_void, item_type: libc::c_int) -> Option<&'a [u8]> {
+    let mut item: *const libc::c_void = ptr::null();
+    // SAFETY: pamh is a valid PAM handle; string items are NUL-terminated.
+    let ret = unsafe { pam_get_item(pamh, item_type, &mut item) };
+    if ret != PAM_SUCCESS || item.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(item as *const libc::c_char) }.to_bytes())
+}
+
+/// Whether the login comes from another machine: a non-empty `PAM_RHOST`,
+/// or OpenSSH's `ssh` placeholder TTY. An empty or unset `PAM_RHOST` and
+/// local TTYs (`tty1`, `/dev/pts/0`, an X display such as `:0`) are local.
+fn is_remote_login(rhost: Option<&[u8]>, tty: Option<&[u8]>) -> bool {
+    rhost.is_some_and(|host| !host.is_empty()) || tty == Some(SSH_TTY)
+}
+
+/// Whether `pattern` matches all of `name`, `*` standing for any run of bytes.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| glob_matches(rest, &name[skip..])),
+        Some((&c, rest)) => name
+            .split_first()
+            .is_some_and(|(&n, tail)| n == c && glob_matches(rest, tail)),
+    }
+}
+
+/// Whether a comma-separated `service_allow=`/`service_deny=` list names
+/// `service`. Empty entries are skipped, so an empty list names nothing.
+fn service_listed(list: &[u8], service: &[u8]) -> bool {
+    list.split(|&b| b == b',')
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| glob_matches(entry, service))
+}
+
+/// Whether face auth runs for PAM service `service` (unset counts as the
+/// empty name, which only `*` matches). A service on the deny list is
+/// refused. Given an allow list, only services on it are allowed.
+fn service_allowed(service: Option<&[u8]>, allow: Option<&[u8]>, deny: Option<&[u8]>) -> bool {
+    let service = service.unwrap_or_default();
+    if deny.is_some_and(|deny| service_listed(deny, service)) {
+        return false;
+    }
+    match allow {
+        Some(allow) => service_listed(allow, service),
+        None => true,
     }
 }
 
-/// Send a PAM_TEXT_INFO message to the user via the PAM conversation function.
+/// Whether `name` appears among the module arguments from the PAM config line.
 ///
-/// Fails silently if the conversation function is unavailable — this is non-critical
-/// feedback and must never block authentication.
-fn send_text_info(pamh: *mut libc::c_void, text: &str) {
-    let c_text = match CString::new(text) {
-        Ok(s) => s,
-        Err(_) => return,
+/// # Safety
+///
+/// `argv` must point to `argc` valid NUL-terminated strings (or be null).
+unsafe fn has_option(argc: libc::c_int, argv: *const *const libc::c_char, name: &[u8]) -> bool {
+    if argv.is_null() {
+        return false;
+    }
+    (0..argc.max(0) as usize).any(|i| {
+        // SAFETY: the caller guarantees argv[0..argc] are valid C strings.
+        let arg = unsafe { *argv.add(i) };
+        !arg.is_null() && unsafe { CStr::from_ptr(arg) }.to_bytes() == name
+    })
+}
+
+/// The value of the first `prefix…` module argument (e.g. `timeout=5`), with
+/// the prefix stripped.
+///
+/// # Safety
+///
+/// `argv` must point to `argc` valid NUL-terminated strings (or be null), and
+/// they must outlive the returned slice.
+unsafe fn option_value<'a>(
+    argc: libc::c_int,
+    argv: *const *const libc::c_char,
+    prefix: &[u8],
+) -> Option<&'a [u8]> {
+    if argv.is_null() {
+        return None;
+    }
+    (0..argc.max(0) as usize).find_map(|i| {
+        // SAFETY: the caller guarantees argv[0..argc] are valid C strings.
+        let arg = unsafe { *argv.add(i) };
+        if arg.is_null() {
+            return None;
+        }
+        unsafe { CStr::from_ptr(arg) }
+            .to_bytes()
+            .strip_prefix(prefix)
+    })
+}
+
+/// Parse a `timeout=` value as whole seconds. A missing value means
+/// [`DEFAULT_TIMEOUT`]; a zero or malformed one is an error for the caller
+/// to log before falling back to it.
+fn parse_timeout(value: Option<&[u8]>) -> Result<Duration, String> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_TIMEOUT);
     };
+    match std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!(
+            "invalid timeout={}, using {}s",
+            String::from_utf8_lossy(value),
+            DEFAULT_TIMEOUT.as_secs()
+        )),
+    }
+}
 
-    let mut conv_ptr: *const libc::c_void = ptr::null();
-    // SAFETY: pamh is a valid PAM handle. pam_get_item reads the conversation struct.
-    let ret = unsafe { pam_get_item(pamh, PAM_CONV, &mut conv_ptr) };
-    if ret != PAM_SUCCESS || conv_ptr.is_null() {
-        return;
+/// Percent-encode a raw username the way visaged canonicalizes it: bytes that
+/// form valid UTF-8 stay literal, `%` becomes `%25`, every other byte `%XX`.
+///
+/// Must stay in sync with `visaged::username::encode`.
+fn encode_username(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        let (valid, bad) = match std::str::from_utf8(rest) {
+            Ok(s) => (s, 0),
+            Err(e) => (
+                std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or_default(),
+                e.error_len().unwrap_or(rest.len() - e.valid_up_to()),
+            ),
+        };
+        out.push_str(&valid.replace('%', "%25"));
+        let invalid = &rest[valid.len()..valid.len() + bad];
+        for b in invalid {
+            out.push_str(&format!("%{b:02X}"));
+        }
+        if bad == 0 {
+            return out;
+        }
+        rest = &rest[valid.len() + bad..];
     }
+}
 
-    // SAFETY: pam_get_item with PAM_CONV returns a pointer to a pam_conv struct.
-    let conv = unsafe { &*(conv_ptr as *const PamConv) };
-    let conv_fn = match conv.conv {
-        Some(f) => f,
-        None => return,
+/// Parse a `max_tries=` value. A missing value means [`DEFAULT_MAX_TRIES`];
+/// a zero or malformed one is an error for the caller to log before falling
+/// back to it.
+fn parse_max_tries(value: Option<&[u8]>) -> Result<u32, String> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_MAX_TRIES);
     };
+    match std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+    {
+        Some(tries) if tries > 0 => Ok(tries),
+        _ => Err(format!(
+            "invalid max_tries={}, using {DEFAULT_MAX_TRIES}",
+            String::from_utf8_lossy(value)
+        )),
+    }
+}
 
-    let msg = PamMessage {
-        msg_style: PAM_TEXT_INFO,
-        msg: c_text.as_ptr(),
+/// Parse a `grace=` value as seconds; a missing value or `0` turns the
+/// grace period off, a malformed one is an error for the caller to log.
+fn parse_grace(value: Option<&[u8]>) -> Result<u32, String> {
+    let Some(value) = value else {
+        return Ok(0);
     };
-    let msg_ptr: *const PamMessage = &msg;
-    let mut resp_ptr: *mut PamResponse = ptr::null_mut();
-
-    // SAFETY: msg_ptr points to a valid PamMessage, conv_fn is the PAM conversation callback.
-    unsafe {
-        conv_fn(
-            1,
-            &msg_ptr as *const _ as *mut _,
-            &mut resp_ptr,
-            conv.appdata_ptr,
-        );
-        // Free response array if allocated. TEXT_INFO rarely gets a response, but the spec
-        // requires us to free both the response string and the response struct if present.
-        if !resp_ptr.is_null() {
-            if !(*resp_ptr).resp.is_null() {
-                libc::free((*resp_ptr).resp as *mut libc::c_void);
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| {
+            format!(
+                "invalid grace={}, not using a grace period",
+                String::from_utf8_lossy(value)
+            )
+        })
+}
+
+/// Parse a `retry_delay_ms=` value as milliseconds; `0` retries at once.
+fn parse_retry_delay(value: Option<&[u8]>) -> Result<Duration, String> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_RETRY_DELAY);
+    };
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .ok_or_else(|| {
+            format!(
+                "invalid retry_delay_ms={}, using {}ms",
+                String::from_utf8_lossy(value),
+                DEFAULT_RETRY_DELAY.as_millis()
+            )
+        })
+}
+
+/// Call `attempt` until it matches or errors, or `max_tries` attempts found
+/// no match. Only a clean no-match is retried: an error means the daemon
+/// cannot decide, and asking again would only delay the password prompt.
+///
+/// All attempts and the `delay` between them share a budget of
+/// `per_try * max_tries`. Each attempt gets the budget's remainder, capped
+/// at `per_try`, as its timeout, and no retry starts once the pause would
+/// use up what is left. `on_retry` is told the number of the coming try.
+fn verify_with_retries<E>(
+    max_tries: u32,
+    per_try: Duration,
+    delay: Duration,
+    mut attempt: impl FnMut(Duration) -> Result<bool, E>,
+    mut on_retry: impl FnMut(u32),
+) -> Result<bool, E> {
+    let budget = per_try.saturating_mul(max_tries);
+    let start = Instant::now();
+    let mut tries = 1;
+    loop {
+        let left = budget.saturating_sub(start.elapsed());
+        match attempt(left.min(per_try)) {
+            Ok(false) if tries < max_tries => {}
+            outcome => return outcome,
+        }
+        if budget.saturating_sub(start.elapsed()) <= delay {
+            return Ok(false);
+        }
+        tries += 1;
+        on_retry(tries);
+        std::thread::sleep(delay);
+    }
+}
+
+/// Where visaged is reached: the system bus under [`DEFAULT_BUS_NAME`]
+/// unless the `bus_address=` / `bus_name=` arguments say otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    address: Option<String>,
+    name: String,
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Self {
+            address: None,
+            name: DEFAULT_BUS_NAME.to_string(),
+        }
+    }
+}
+
+impl Endpoint {
+    /// Connect to the bus, capping every D-Bus call at `timeout` so a stuck
+    /// daemon cannot hold up login for longer than that.
+    fn connect(&self, timeout: Duration) -> Result<Daemon, VerifyError> {
+        let builder = match &self.address {
+            Some(address) => zbus::blocking::connection::Builder::address(address.as_str())?,
+            None => zbus::blocking::connection::Builder::system()?,
+        };
+        Ok(Daemon {
+            conn: builder.method_timeout(timeout).build()?,
+            name: self.name.clone(),
+        })
+    }
+}
+
+/// Parse the `bus_address=` / `bus_name=` values. A malformed one is an
+/// error for the caller to log before falling back to the system daemon.
+fn parse_endpoint(address: Option<&[u8]>, name: Option<&[u8]>) -> Result<Endpoint, String> {
+    let mut endpoint = Endpoint::default();
+    if let Some(value) = address {
+        match std::str::from_utf8(value) {
+            Ok(address) if !address.is_empty() => endpoint.address = Some(address.to_string()),
+            _ => {
+                return Err(format!(
+                    "invalid bus_address={}, using the system bus",
+                    String::from_utf8_lossy(value)
+                ))
             }
-            libc::free(resp_ptr as *mut libc::c_void);
         }
     }
+    if let Some(value) = name {
+        match std::str::from_utf8(value)
+            .ok()
+            .filter(|n| zbus::names::WellKnownName::try_from(*n).is_ok())
+        {
+            Some(name) => endpoint.name = name.to_string(),
+            None => {
+                return Err(format!(
+                    "invalid bus_name={}, using {DEFAULT_BUS_NAME} on the system bus",
+                    String::from_utf8_lossy(value)
+                ))
+            }
+        }
+    }
+    Ok(endpoint)
+}
+
+/// A connection to visaged and the bus name it answers under.
+struct Daemon {
+    conn: zbus::blocking::Connection,
+    name: String,
+}
+
+impl Daemon {
+    fn proxy(&self) -> Result<VisageProxyBlocking<'_>, VerifyError> {
+        Ok(VisageProxyBlocking::builder(&self.conn)
+            .destination(self.name.as_str())?
+            .build()?)
+    }
+}
+
+/// What a verify call returned.
+#[derive(Debug, Default, PartialEq)]
+struct Verdict {
+    matched: bool,
+    /// One-time token issued with a match, when asked for.
+    token: Option<String>,
+    /// Score and model, when `VerifyDetailed` was used.
+    detail: Option<Detail>,
+}
+
+/// The parts of a `VerifyDetailed` report the module logs.
+#[derive(Debug, PartialEq)]
+struct Detail {
+    similarity: f64,
+    model_label: Option<String>,
+    failure_reason: Option<String>,
+}
+
+impl std::fmt::Display for Detail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "similarity {:.3}", self.similarity)?;
+        if let Some(label) = &self.model_label {
+            write!(f, ", model '{label}'")?;
+        }
+        if let Some(reason) = &self.failure_reason {
+            write!(f, ", {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the match and the logged fields from a `VerifyDetailed` reply.
+fn parse_verify_report(report: &str) -> Result<(bool, Detail), VerifyError> {
+    let malformed = || VerifyError::Other(format!("malformed VerifyDetailed reply: {report}"));
+    let report: serde_json::Value = serde_json::from_str(report).map_err(|_| malformed())?;
+    let text = |key: &str| report.get(key).and_then(|v| v.as_str()).map(String::from);
+    let matched = report
+        .get("matched")
+        .and_then(|v| v.as_bool())
+        .ok_or_else(malformed)?;
+    Ok((
+        matched,
+        Detail {
+            similarity: report
+                .get("similarity")
+                .and_then(|v| v.as_f64())
+                .unwrap_or_default(),
+            model_label: text("model_label"),
+            failure_reason: text("failure_reason"),
+        },
+    ))
+}
+
+/// Connect to visaged and call `Visage1.Verify(username)`. See
+/// [`verify_face_on`] for the result.
+fn verify_face(
+    endpoint: &Endpoint,
+    username: &str,
+    required_capability: Option<&str>,
+    timeout: Duration,
+    want_token: bool,
+    detailed: bool,
+) -> Result<Verdict, VerifyError> {
+    let daemon = endpoint.connect(timeout)?;
+    verify_face_on(&daemon, username, required_capability, want_token, detailed)
+}
+
+/// Call `Visage1.Verify(username)` over an established connection, or
+/// `VerifyWithToken` when `want_token` is set and the daemon offers it, or
+/// else `VerifyDetailed` when `detailed` is set and the daemon offers it.
+///
+/// Returns an unmatched [`Verdict`] if the daemon responds but finds no
+/// match, and the one-time token, if any, alongside a match.
+/// Returns `Err` if the daemon is not running, the call fails, or times out,
+/// or if `required_capability` is not advertised by the daemon.
+fn verify_face_on(
+    daemon: &Daemon,
+    username: &str,
+    required_capability: Option<&str>,
+    want_token: bool,
+    detailed: bool,
+) -> Result<Verdict, VerifyError> {
+    let proxy = daemon.proxy()?;
+    // Daemons without GetCapabilities advertise nothing.
+    let caps = if required_capability.is_some() || want_token || detailed {
+        proxy.get_capabilities().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    if let Some(capability) = required_capability {
+        if !caps.iter().any(|c| c == capability) {
+            return Err(VerifyError::Other(format!(
+                "visaged does not advertise '{capability}'"
+            )));
+        }
+    }
+    if want_token && caps.iter().any(|c| c == CAP_VERIFY_TOKENS) {
+        let (matched, token) = proxy.verify_with_token(username)?;
+        return Ok(Verdict {
+            matched,
+            token: Some(token).filter(|t| !t.is_empty()),
+            detail: None,
+        });
+    }
+    if detailed && caps.iter().any(|c| c == CAP_VERIFY_DETAILED) {
+        let (matched, detail) = parse_verify_report(&proxy.verify_detailed(username)?)?;
+        return Ok(Verdict {
+            matched,
+            token: None,
+            detail: Some(detail),
+        });
+    }
+    Ok(Verdict {
+        matched: proxy.verify(username)?,
+        ..Verdict::default()
+    })
+}
+
+/// Connect to visaged and redeem a token from an earlier match. See
+/// [`redeem_token_on`].
+fn redeem_token(
+    endpoint: &Endpoint,
+    username: &str,
+    token: &str,
+    timeout: Duration,
+) -> Result<bool, VerifyError> {
+    redeem_token_on(&endpoint.connect(timeout)?, username, token)
+}
+
+/// Call `Visage1.RedeemToken(username, token)`: `Ok(true)` if visaged
+/// accepted it, which it does once, from the logind session it was issued to.
+fn redeem_token_on(daemon: &Daemon, username: &str, token: &str) -> Result<bool, VerifyError> {
+    Ok(daemon.proxy()?.redeem_token(username, token)?)
+}
+
+/// Connect to visaged and ask whether a recent match still counts. See
+/// [`verify_cached_on`].
+fn verify_cached(
+    endpoint: &Endpoint,
+    username: &str,
+    required_capability: Option<&str>,
+    max_age_secs: u32,
+    timeout: Duration,
+) -> Result<bool, VerifyError> {
+    verify_cached_on(
+        &endpoint.connect(timeout)?,
+        username,
+        required_capability,
+        max_age_secs,
+    )
+}
+
+/// Call `Visage1.VerifyCached(username, max_age_secs)`: `Ok(true)` if the
+/// daemon matched `username` for this caller within `max_age_secs`.
+/// `Ok(false)`, meaning run a full verify, when it did not or when it lacks
+/// `verify_grace` or `required_capability`.
+fn verify_cached_on(
+    daemon: &Daemon,
+    username: &str,
+    required_capability: Option<&str>,
+    max_age_secs: u32,
+) -> Result<bool, VerifyError> {
+    let proxy = daemon.proxy()?;
+    let caps = proxy.get_capabilities().unwrap_or_default();
+    let advertised = |capability: &str| caps.iter().any(|c| c == capability);
+    let required = match required_capability {
+        Some(capability) => advertised(capability),
+        None => true,
+    };
+    if !advertised(CAP_VERIFY_GRACE) || !required {
+        return Ok(false);
+    }
+    Ok(proxy.verify_cached(username, max_age_secs)?)
+}
+
+/// `pam_set_data` cleanup for a token stored by [`store_token`].
+unsafe extern "C" fn free_token(
+    _pamh: *mut libc::c_void,
+    data: *mut libc::c_void,
+    _error_status: libc::c_int,
+) {
+    if !data.is_null() {
+        // SAFETY: store_token passes ownership of a CString::into_raw pointer.
+        drop(unsafe { CString::from_raw(data as *mut libc::c_char) });
+    }
+}
+
+/// Keep `token` on the PAM handle for a later pam_visage call in the stack.
+///
+/// # Safety
+///
+/// `pamh` must be a valid PAM handle.
+unsafe fn store_token(pamh: *mut libc::c_void, token: &str) -> bool {
+    let Ok(token) = CString::new(token) else {
+        return false;
+    };
+    let raw = token.into_raw();
+    // SAFETY: pamh is valid; on success PAM owns `raw` and frees it through
+    // free_token when the data is replaced or the handle ends.
+    let ret = unsafe {
+        pam_set_data(
+            pamh,
+            TOKEN_DATA.as_ptr() as *const libc::c_char,
+            raw as *mut libc::c_void,
+            Some(free_token),
+        )
+    };
+    if ret != PAM_SUCCESS {
+        // SAFETY: PAM did not take ownership.
+        drop(unsafe { CString::from_raw(raw) });
+        return false;
+    }
+    true
 }
 
-/// Connect to the system bus and call `Visage1.Verify(username)`.
+/// Remove and return the token a previous call stored on the handle.
 ///
-/// Uses a 3-second method timeout to prevent login hangs if the daemon is stuck.
-/// Returns `Ok(false)` if the daemon responds but finds no match.
-/// Returns `Err` if the daemon is not running, the call fails, or times out.
-fn verify_face(username: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let conn = zbus::blocking::connection::Builder::system()?
-        .method_timeout(std::time::Duration::from_secs(3))
-        .build()?;
-    let proxy = VisageProxyBlocking::new(&conn)?;
-    let matched = proxy.verify(username)?;
-    Ok(matched)
+/// # Safety
+///
+/// `pamh` must be a valid PAM handle.
+unsafe fn take_token(pamh: *mut libc::c_void) -> Option<String> {
+    let name = TOKEN_DATA.as_ptr() as *const libc::c_char;
+    let mut data: *const libc::c_void = ptr::null();
+    // SAFETY: pamh is valid; the data, if any, is a C string from store_token.
+    let ret = unsafe { pam_get_data(pamh, name, &mut data) };
+    if ret != PAM_SUCCESS || data.is_null() {
+        return None;
+    }
+    let token = unsafe { CStr::from_ptr(data as *const libc::c_char) }
+        .to_str()
+        .ok()
+        .map(str::to_string);
+    // A token is good for one try: replacing the data frees the stored copy.
+    unsafe { pam_set_data(pamh, name, ptr::null_mut(), None) };
+    token
+}
+
+/// Why a verify call produced no match decision.
+#[derive(Debug)]
+enum VerifyError {
+    /// The user has never enrolled. Routine for most accounts.
+    NotEnrolled,
+    /// visaged is not on the bus, or the bus itself cannot be reached.
+    DaemonUnreachable(String),
+    /// visaged answered but cannot verify (recognizer missing, camera
+    /// unavailable, engine replaced mid-request).
+    DaemonUnavailable(String),
+    /// The call outlived the `timeout=` budget, or the daemon's own timeout.
+    Timeout(String),
+    /// Site policy, access control or a rate-limit lockout refused the
+    /// request.
+    Denied(String),
+    /// The daemon's face store cannot be read.
+    StoreUnavailable(String),
+    /// The daemon could not look the account up (an NSS or directory
+    /// outage), so it cannot tell whether the caller may verify it.
+    DirectoryUnavailable(String),
+    /// Another client cancelled the verify, e.g. root through `CancelUser`.
+    Cancelled,
+    /// Anything else, e.g. a camera failure or a missing capability.
+    Other(String),
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotEnrolled => f.write_str("user has not enrolled"),
+            Self::DaemonUnreachable(msg) => write!(f, "visaged unreachable: {msg}"),
+            Self::DaemonUnavailable(msg) => write!(f, "visaged unavailable: {msg}"),
+            Self::Timeout(msg) => write!(f, "visaged timed out: {msg}"),
+            Self::Denied(msg) => write!(f, "denied: {msg}"),
+            Self::StoreUnavailable(msg) => write!(f, "visaged face store unavailable: {msg}"),
+            Self::DirectoryUnavailable(msg) => write!(f, "account lookup unavailable: {msg}"),
+            Self::Cancelled => f.write_str("verify cancelled"),
+            Self::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<zbus::Error> for VerifyError {
+    fn from(e: zbus::Error) -> Self {
+        match &e {
+            zbus::Error::MethodError(name, msg, _) => {
+                let msg = msg.clone().unwrap_or_default();
+                match name.as_str() {
+                    ERR_NOT_ENROLLED => Self::NotEnrolled,
+                    "org.freedesktop.Visage1.Error.Failed"
+                        if msg.starts_with(LEGACY_NOT_ENROLLED_PREFIX) =>
+                    {
+                        Self::NotEnrolled
+                    }
+                    ERR_POLICY_DENIED
+                    | ERR_ACCESS_DENIED
+                    | ERR_RATE_LIMITED
+                    | ERR_FACE_CHANGED
+                    | "org.freedesktop.DBus.Error.AccessDenied" => Self::Denied(msg),
+                    ERR_TIMEOUT
+                    | "org.freedesktop.DBus.Error.Timeout"
+                    | "org.freedesktop.DBus.Error.TimedOut"
+                    | "org.freedesktop.DBus.Error.NoReply" => Self::Timeout(msg),
+                    ERR_STORE_UNAVAILABLE => Self::StoreUnavailable(msg),
+                    ERR_DIRECTORY_UNAVAILABLE => Self::DirectoryUnavailable(msg),
+                    ERR_CANCELLED => Self::Cancelled,
+                    ERR_RECOGNIZER_UNAVAILABLE | ERR_ENGINE_RESTARTED | ERR_CAMERA_UNAVAILABLE => {
+                        Self::DaemonUnavailable(format!("{name}: {msg}"))
+                    }
+                    "org.freedesktop.DBus.Error.ServiceUnknown"
+                    | "org.freedesktop.DBus.Error.NameHasNoOwner"
+                    | "org.freedesktop.DBus.Error.NoServer"
+                    | "org.freedesktop.DBus.Error.Disconnected" => {
+                        Self::DaemonUnreachable(format!("{name}: {msg}"))
+                    }
+                    _ => Self::Other(e.to_string()),
+                }
+            }
+            zbus::Error::InputOutput(io) if io.kind() == std::io::ErrorKind::TimedOut => {
+                Self::Timeout(e.to_string())
+            }
+            zbus::Error::InputOutput(_) | zbus::Error::Address(_) | zbus::Error::Handshake(_) => {
+                Self::DaemonUnreachable(e.to_string())
+            }
+            _ => Self::Other(e.to_string()),
+        }
+    }
+}
+
+impl VerifyError {
+    /// Syslog priority and message. A user who never enrolled is logged at
+    /// debug only (dropped without the `debug` option) and policy refusals at
+    /// info; a broken face store is an error.
+    fn log_entry(&self, username: &str) -> (libc::c_int, String) {
+        match self {
+            Self::NotEnrolled => (LOG_DEBUG, format!("user '{username}' has not enrolled")),
+            Self::Denied(msg) => (
+                LOG_INFO,
+                format!("face unlock not permitted now for user '{username}': {msg}"),
+            ),
+            Self::StoreUnavailable(_) => (LOG_ERR, format!("{self} (user '{username}')")),
+            Self::Cancelled => (
+                LOG_INFO,
+                format!("face auth cancelled for user '{username}'"),
+            ),
+            Self::Timeout(_) | Self::DirectoryUnavailable(_) => {
+                (LOG_WARNING, format!("{self} (user '{username}')"))
+            }
+            Self::DaemonUnreachable(_) | Self::DaemonUnavailable(_) => {
+                (LOG_WARNING, self.to_string())
+            }
+            Self::Other(msg) => (LOG_WARNING, format!("D-Bus error: {msg}")),
+        }
+    }
+
+    /// What `pam_sm_authenticate` returns: `PAM_AUTHINFO_UNAVAIL` when visaged
+    /// could not be reached, so stacks can act on an absent daemon, and
+    /// `PAM_IGNORE` for every answer it gave.
+    fn pam_status(&self) -> libc::c_int {
+        match self {
+            Self::DaemonUnreachable(_) => PAM_AUTHINFO_UNAVAIL,
+            _ => PAM_IGNORE,
+        }
+    }
 }
 
 /// PAM authentication entry point.
@@ -189,7 +1057,24 @@ fn verify_face(username: &str) -> Result<bool, Box<dyn std::error::Error>> {
 ///
 /// Returns:
 /// - `PAM_SUCCESS` (0) if face matched
-/// - `PAM_IGNORE` (25) on any failure — daemon down, no match, error, panic
+/// - `PAM_AUTHINFO_UNAVAIL` (9) if visaged is not on the bus or the bus
+///   cannot be reached
+/// - `PAM_IGNORE` (25) on any other failure — no match, outside the daemon's
+///   verify windows, timeout, error, panic
+///
+/// Remote logins (a non-empty `PAM_RHOST`, or OpenSSH's `ssh` TTY) and
+/// services excluded by `service_allow=`/`service_deny=` return `PAM_IGNORE`
+/// at once, without contacting visaged.
+///
+/// Module arguments: `percent_encode_usernames`, `timeout=N` to cap each
+/// call to visaged at N seconds (default 3), `max_tries=N` to ask again
+/// after a no-match (default 1) with `retry_delay_ms=N` between tries
+/// (default 500), `quiet` to send no progress messages, `allow_remote` to
+/// try remote logins too, `service_allow=`/`service_deny=` to pick the PAM
+/// services face auth runs for, `use_token` to redeem a match from earlier on the
+/// same PAM handle, `grace=N` to accept a match visaged made in the last N
+/// seconds, `bus_address=`/`bus_name=` to reach a test daemon on a private
+/// bus, and `debug`.
 ///
 /// # Safety
 ///
@@ -201,11 +1086,82 @@ fn verify_face(username: &str) -> Result<bool, Box<dyn std::error::Error>> {
 pub unsafe extern "C" fn pam_sm_authenticate(
     pamh: *mut libc::c_void,
     _flags: libc::c_int,
-    _argc: libc::c_int,
-    _argv: *const *const libc::c_char,
+    argc: libc::c_int,
+    argv: *const *const libc::c_char,
 ) -> libc::c_int {
     let result = panic::catch_unwind(|| {
-        syslog_open();
+        // SAFETY: PAM passes argc/argv straight from the module's config line.
+        let log = Log {
+            pamh,
+            debug: unsafe { has_option(argc, argv, OPT_DEBUG) },
+        };
+        let percent_encode = unsafe { has_option(argc, argv, OPT_PERCENT_ENCODE) };
+        let timeout = parse_timeout(unsafe { option_value(argc, argv, OPT_TIMEOUT) })
+            .unwrap_or_else(|warning| {
+                log.msg(LOG_WARNING, &warning);
+                DEFAULT_TIMEOUT
+            });
+        let max_tries = parse_max_tries(unsafe { option_value(argc, argv, OPT_MAX_TRIES) })
+            .unwrap_or_else(|warning| {
+                log.msg(LOG_WARNING, &warning);
+                DEFAULT_MAX_TRIES
+            });
+        let retry_delay = parse_retry_delay(unsafe { option_value(argc, argv, OPT_RETRY_DELAY) })
+            .unwrap_or_else(|warning| {
+                log.msg(LOG_WARNING, &warning);
+                DEFAULT_RETRY_DELAY
+            });
+        let grace =
+            parse_grace(unsafe { option_value(argc, argv, OPT_GRACE) }).unwrap_or_else(|warning| {
+                log.msg(LOG_WARNING, &warning);
+                0
+            });
+        let endpoint = parse_endpoint(
+            unsafe { option_value(argc, argv, OPT_BUS_ADDRESS) },
+            unsafe { option_value(argc, argv, OPT_BUS_NAME) },
+        )
+        .unwrap_or_else(|warning| {
+            log.msg(LOG_WARNING, &warning);
+            Endpoint::default()
+        });
+
+        // Services the administrator left out: skip before any D-Bus call.
+        // SAFETY: pamh is a valid PAM handle for the whole call.
+        let service = unsafe { string_item(pamh, PAM_SERVICE) };
+        let (allow, deny) = unsafe {
+            (
+                option_value(argc, argv, OPT_SERVICE_ALLOW),
+                option_value(argc, argv, OPT_SERVICE_DENY),
+            )
+        };
+        if !service_allowed(service, allow, deny) {
+            log.msg(
+                LOG_DEBUG,
+                &format!(
+                    "service '{}' not enabled for face auth, skipping",
+                    String::from_utf8_lossy(service.unwrap_or_default())
+                ),
+            );
+            return PAM_IGNORE;
+        }
+
+        // No camera in front of a remote user: skip before any D-Bus call.
+        if !unsafe { has_option(argc, argv, OPT_ALLOW_REMOTE) } {
+            // SAFETY: pamh is a valid PAM handle for the whole call.
+            let (rhost, tty) =
+                unsafe { (string_item(pamh, PAM_RHOST), string_item(pamh, PAM_TTY)) };
+            if is_remote_login(rhost, tty) {
+                log.msg(
+                    LOG_DEBUG,
+                    &format!(
+                        "remote login (rhost '{}', tty '{}'), skipping",
+                        String::from_utf8_lossy(rhost.unwrap_or_default()),
+                        String::from_utf8_lossy(tty.unwrap_or_default())
+                    ),
+                );
+                return PAM_IGNORE;
+            }
+        }
 
         // Extract username from PAM handle.
         let mut user_ptr: *const libc::c_char = ptr::null();
@@ -213,34 +1169,152 @@ pub unsafe extern "C" fn pam_sm_authenticate(
         // that remains valid for the lifetime of the PAM conversation.
         let ret = unsafe { pam_get_user(pamh, &mut user_ptr, ptr::null()) };
         if ret != PAM_SUCCESS || user_ptr.is_null() {
-            syslog_msg(LOG_ERR, &format!("pam_get_user failed (ret={})", ret));
+            log.msg(LOG_ERR, &format!("pam_get_user failed (ret={})", ret));
             return PAM_IGNORE;
         }
 
         // SAFETY: pam_get_user guarantees the pointer is non-null and points
         // to a NUL-terminated string that lives for the PAM conversation.
-        let username = match unsafe { CStr::from_ptr(user_ptr) }.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                syslog_msg(LOG_WARNING, "username is not valid UTF-8");
-                return PAM_IGNORE;
+        let raw_username = unsafe { CStr::from_ptr(user_ptr) }.to_bytes();
+        let username = if percent_encode {
+            encode_username(raw_username)
+        } else {
+            match std::str::from_utf8(raw_username) {
+                Ok(s) => s.to_string(),
+                Err(_) => {
+                    log.msg(
+                        LOG_WARNING,
+                        "username is not valid UTF-8 (see percent_encode_usernames)",
+                    );
+                    return PAM_IGNORE;
+                }
             }
         };
+        // Only spend a capability round-trip when encoding changed the name.
+        let required_capability =
+            (username.as_bytes() != raw_username).then_some(CAP_PERCENT_ENCODED);
+        let username = username.as_str();
+
+        // SAFETY: pamh stays valid for the whole call.
+        let feedback = unsafe { Feedback::for_handle(pamh, has_option(argc, argv, OPT_QUIET)) };
+
+        // A match earlier on this handle left a token: no second capture.
+        let use_token = unsafe { has_option(argc, argv, OPT_USE_TOKEN) };
+        if let Some(token) = use_token.then(|| unsafe { take_token(pamh) }).flatten() {
+            match redeem_token(&endpoint, username, &token, timeout) {
+                Ok(true) => {
+                    log.msg(
+                        LOG_INFO,
+                        &format!("verification token redeemed for user '{username}'"),
+                    );
+                    feedback.info(MSG_RECOGNIZED);
+                    return PAM_SUCCESS;
+                }
+                Ok(false) => log.msg(
+                    LOG_DEBUG,
+                    &format!("verification token for user '{username}' not accepted"),
+                ),
+                Err(e) => {
+                    let (priority, msg) = e.log_entry(username);
+                    log.msg(priority, &msg);
+                }
+            }
+        }
+
+        // A match moments ago, e.g. the screen unlock before this sudo.
+        if grace > 0 {
+            match verify_cached(&endpoint, username, required_capability, grace, timeout) {
+                Ok(true) => {
+                    log.msg(
+                        LOG_INFO,
+                        &format!("recent match within {grace}s accepted for user '{username}'"),
+                    );
+                    feedback.info(MSG_RECOGNIZED);
+                    return PAM_SUCCESS;
+                }
+                Ok(false) => log.msg(
+                    LOG_DEBUG,
+                    &format!("no match within {grace}s for user '{username}'"),
+                ),
+                Err(e) => {
+                    let (priority, msg) = e.log_entry(username);
+                    log.msg(priority, &msg);
+                }
+            }
+        }
 
         // Call visaged over D-Bus.
-        match verify_face(username) {
+        log.msg(
+            LOG_DEBUG,
+            &format!(
+                "verifying user '{username}' (timeout {}s)",
+                timeout.as_secs()
+            ),
+        );
+        let issued = std::cell::Cell::new(None);
+        let looking = std::cell::Cell::new(true);
+        let debug = log.debug;
+        let outcome = verify_with_retries(
+            max_tries,
+            timeout,
+            retry_delay,
+            |timeout| -> Result<bool, VerifyError> {
+                let verdict = announce_if_slow(
+                    || {
+                        verify_face(
+                            &endpoint,
+                            username,
+                            required_capability,
+                            timeout,
+                            use_token,
+                            debug,
+                        )
+                    },
+                    LOOKING_DELAY,
+                    || {
+                        if looking.replace(false) {
+                            feedback.info(MSG_LOOKING);
+                        }
+                    },
+                )?;
+                if let Some(detail) = &verdict.detail {
+                    log.msg(LOG_DEBUG, &format!("user '{username}': {detail}"));
+                }
+                issued.set(verdict.token);
+                Ok(verdict.matched)
+            },
+            |next| {
+                log.msg(
+                    LOG_DEBUG,
+                    &format!("no match for user '{username}', try {next}/{max_tries}"),
+                );
+                feedback.info(&format!(
+                    "Face not recognized, trying again ({next}/{max_tries})"
+                ));
+            },
+        );
+        if let Some(message) = outcome_message(&outcome) {
+            feedback.info(message);
+        }
+        if let (Ok(true), Some(token)) = (&outcome, issued.take()) {
+            // SAFETY: pamh stays valid for the whole call.
+            if !unsafe { store_token(pamh, &token) } {
+                log.msg(LOG_WARNING, "could not keep the verification token");
+            }
+        }
+        match outcome {
             Ok(true) => {
-                syslog_msg(LOG_INFO, &format!("face matched for user '{}'", username));
-                send_text_info(pamh, "Visage: face recognized");
+                log.msg(LOG_INFO, &format!("face matched for user '{}'", username));
                 PAM_SUCCESS
             }
             Ok(false) => {
-                syslog_msg(LOG_INFO, &format!("no match for user '{}'", username));
+                log.msg(LOG_NOTICE, &format!("no match for user '{}'", username));
                 PAM_IGNORE
             }
             Err(e) => {
-                syslog_msg(LOG_WARNING, &format!("D-Bus error: {}", e));
-                PAM_IGNORE
+                let (priority, msg) = e.log_entry(username);
+                log.msg(priority, &msg);
+                e.pam_status()
             }
         }
     });
@@ -248,6 +1322,13 @@ pub unsafe extern "C" fn pam_sm_authenticate(
     result.unwrap_or(PAM_IGNORE)
 }
 
+/// Result of the entry points for phases Visage takes no part in. Wrapped
+/// in `catch_unwind` like `pam_sm_authenticate`, so a future change here
+/// cannot unwind across the FFI boundary either.
+fn not_participating() -> libc::c_int {
+    panic::catch_unwind(|| PAM_IGNORE).unwrap_or(PAM_IGNORE)
+}
+
 /// PAM credential management entry point (required by the PAM ABI).
 ///
 /// Visage does not manage credentials — always returns `PAM_IGNORE`.
@@ -262,7 +1343,79 @@ pub unsafe extern "C" fn pam_sm_setcred(
     _argc: libc::c_int,
     _argv: *const *const libc::c_char,
 ) -> libc::c_int {
-    PAM_IGNORE
+    not_participating()
+}
+
+/// PAM account management entry point.
+///
+/// Visage only takes part in the `auth` phase; this exists so stacks that
+/// list every module under `account` resolve the symbol. Always returns
+/// `PAM_IGNORE`.
+///
+/// # Safety
+///
+/// `_pamh` must be a valid PAM handle. This function is a no-op stub.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_acct_mgmt(
+    _pamh: *mut libc::c_void,
+    _flags: libc::c_int,
+    _argc: libc::c_int,
+    _argv: *const *const libc::c_char,
+) -> libc::c_int {
+    not_participating()
+}
+
+/// PAM session open entry point.
+///
+/// Visage does not manage sessions — always returns `PAM_IGNORE`.
+///
+/// # Safety
+///
+/// `_pamh` must be a valid PAM handle. This function is a no-op stub.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_open_session(
+    _pamh: *mut libc::c_void,
+    _flags: libc::c_int,
+    _argc: libc::c_int,
+    _argv: *const *const libc::c_char,
+) -> libc::c_int {
+    not_participating()
+}
+
+/// PAM session close entry point.
+///
+/// Visage does not manage sessions — always returns `PAM_IGNORE`.
+///
+/// # Safety
+///
+/// `_pamh` must be a valid PAM handle. This function is a no-op stub.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_close_session(
+    _pamh: *mut libc::c_void,
+    _flags: libc::c_int,
+    _argc: libc::c_int,
+    _argv: *const *const libc::c_char,
+) -> libc::c_int {
+    not_participating()
+}
+
+/// PAM password change entry point.
+///
+/// Face templates are not passwords, so Visage has nothing to change —
+/// always returns `PAM_IGNORE`, leaving the decision to the rest of the
+/// `password` stack.
+///
+/// # Safety
+///
+/// `_pamh` must be a valid PAM handle. This function is a no-op stub.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_chauthtok(
+    _pamh: *mut libc::c_void,
+    _flags: libc::c_int,
+    _argc: libc::c_int,
+    _argv: *const *const libc::c_char,
+) -> libc::c_int {
+    not_participating()
 }
 
 #[cfg(test)]
@@ -275,6 +1428,36 @@ mod tests {
         // These are load-bearing: wrong values silently mis-route the PAM stack.
         assert_eq!(PAM_SUCCESS, 0, "PAM_SUCCESS must be 0");
         assert_eq!(PAM_IGNORE, 25, "PAM_IGNORE must be 25");
+        assert_eq!(PAM_AUTHINFO_UNAVAIL, 9);
+        // <security/_pam_types.h> item types.
+        assert_eq!(PAM_SERVICE, 1);
+        assert_eq!(PAM_TTY, 3);
+        assert_eq!(PAM_RHOST, 4);
+    }
+
+    #[test]
+    fn non_auth_entry_points_exist_and_ignore() {
+        type PamEntry = unsafe extern "C" fn(
+            *mut libc::c_void,
+            libc::c_int,
+            libc::c_int,
+            *const *const libc::c_char,
+        ) -> libc::c_int;
+
+        // Naming each symbol as a `PamEntry` fails the build if one is
+        // missing or has the wrong signature.
+        let entries: [PamEntry; 5] = [
+            pam_sm_setcred,
+            pam_sm_acct_mgmt,
+            pam_sm_open_session,
+            pam_sm_close_session,
+            pam_sm_chauthtok,
+        ];
+        for entry in entries {
+            // SAFETY: the stubs never touch their arguments.
+            let ret = unsafe { entry(ptr::null_mut(), 0, 0, ptr::null()) };
+            assert_eq!(ret, PAM_IGNORE);
+        }
     }
 
     #[test]
@@ -282,6 +1465,23 @@ mod tests {
         assert_eq!(PAM_CONV, 5, "PAM_CONV must be 5");
     }
 
+    #[test]
+    fn remote_logins_are_detected_from_rhost_and_tty() {
+        // SSH: the client's address, and OpenSSH's placeholder TTY.
+        assert!(is_remote_login(Some(b"192.0.2.7"), Some(b"ssh")));
+        assert!(is_remote_login(Some(b"host.example"), None));
+        assert!(is_remote_login(None, Some(b"ssh")));
+        // Unset and empty RHOST are both local.
+        assert!(!is_remote_login(None, None));
+        assert!(!is_remote_login(Some(b""), Some(b"tty1")));
+        // Local X sessions and terminals.
+        assert!(!is_remote_login(None, Some(b":0")));
+        assert!(!is_remote_login(Some(b""), Some(b":1")));
+        assert!(!is_remote_login(None, Some(b"/dev/pts/3")));
+        // Only the exact placeholder counts.
+        assert!(!is_remote_login(None, Some(b"sshd-tty")));
+    }
+
     #[test]
     fn pam_text_info_matches_spec() {
         assert_eq!(PAM_TEXT_INFO, 4, "PAM_TEXT_INFO must be 4");
@@ -290,11 +1490,661 @@ mod tests {
     #[test]
     fn syslog_constants_match_spec() {
         assert_eq!(LOG_AUTHPRIV, 80, "LOG_AUTHPRIV must be 10 << 3 = 80");
+        assert_eq!(LOG_NOTICE, 5, "LOG_NOTICE must be 5");
         assert_eq!(LOG_INFO, 6, "LOG_INFO must be 6");
+        assert_eq!(LOG_DEBUG, 7, "LOG_DEBUG must be 7");
         assert_eq!(LOG_WARNING, 4, "LOG_WARNING must be 4");
         assert_eq!(LOG_ERR, 3, "LOG_ERR must be 3");
     }
 
+    /// Conversation callback that records each message's text in the
+    /// `Vec<String>` behind `appdata_ptr`.
+    unsafe extern "C" fn record_messages(
+        num_msg: libc::c_int,
+        msg: *mut *const PamMessage,
+        _resp: *mut *mut PamResponse,
+        appdata_ptr: *mut libc::c_void,
+    ) -> libc::c_int {
+        // SAFETY: the test passes a live Vec<String> and one valid message.
+        let seen = unsafe { &mut *(appdata_ptr as *mut Vec<String>) };
+        for i in 0..num_msg as usize {
+            let m = unsafe { &**msg.add(i) };
+            assert_eq!(m.msg_style, PAM_TEXT_INFO);
+            seen.push(
+                unsafe { CStr::from_ptr(m.msg) }
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        PAM_SUCCESS
+    }
+
+    #[test]
+    fn feedback_sends_text_info_through_conversation() {
+        let mut seen: Vec<String> = Vec::new();
+        let conv = PamConv {
+            conv: Some(record_messages),
+            appdata_ptr: &mut seen as *mut Vec<String> as *mut libc::c_void,
+        };
+        let feedback = Feedback { conv: Some(&conv) };
+        feedback.info(MSG_LOOKING);
+        feedback.info(outcome_message(&Ok(false)).unwrap());
+        // Quiet, or no conversation function: nothing is sent.
+        Feedback { conv: None }.info(MSG_LOOKING);
+        let no_fn = PamConv {
+            conv: None,
+            appdata_ptr: ptr::null_mut(),
+        };
+        Feedback { conv: Some(&no_fn) }.info(MSG_LOOKING);
+
+        assert_eq!(
+            seen,
+            [
+                "Looking for your face…",
+                "Face not recognized, falling back to password"
+            ]
+        );
+        assert_eq!(outcome_message(&Ok(true)), Some("Face recognized"));
+        let unreachable = VerifyError::DaemonUnreachable("gone".into());
+        assert_eq!(outcome_message(&Err(unreachable)), Some(MSG_UNAVAILABLE));
+        // Users who cannot use face auth here are told nothing.
+        assert_eq!(outcome_message(&Err(VerifyError::NotEnrolled)), None);
+        let denied = VerifyError::Denied("outside hours".into());
+        assert_eq!(outcome_message(&Err(denied)), None);
+    }
+
+    #[test]
+    fn looking_is_announced_only_for_a_slow_verify() {
+        let announced = std::cell::Cell::new(0);
+        let quick = announce_if_slow(|| 1, Duration::from_secs(5), || announced.set(1));
+        assert_eq!((quick, announced.get()), (1, 0));
+
+        let slow = announce_if_slow(
+            || {
+                std::thread::sleep(Duration::from_millis(100));
+                2
+            },
+            Duration::from_millis(10),
+            || announced.set(announced.get() + 1),
+        );
+        assert_eq!((slow, announced.get()), (2, 1));
+    }
+
+    #[test]
+    fn encode_username_matches_daemon_canonical_form() {
+        assert_eq!(encode_username(b"alice"), "alice");
+        assert_eq!(encode_username("jos\u{e9}".as_bytes()), "jos\u{e9}");
+        assert_eq!(encode_username(b"jos\xe9"), "jos%E9");
+        assert_eq!(encode_username(b"a%b\xff\xfe"), "a%25b%FF%FE");
+    }
+
+    #[test]
+    fn service_lists_allow_deny_and_wildcards() {
+        let allowed = |service: Option<&[u8]>, allow: Option<&[u8]>, deny: Option<&[u8]>| {
+            service_allowed(service, allow, deny)
+        };
+        // No lists: every service, named or not.
+        assert!(allowed(Some(b"login"), None, None));
+        assert!(allowed(None, None, None));
+
+        let allow: Option<&[u8]> = Some(b"sudo,gdm-*,kde");
+        assert!(allowed(Some(b"sudo"), allow, None));
+        assert!(allowed(Some(b"gdm-password"), allow, None));
+        assert!(allowed(Some(b"gdm-"), allow, None));
+        assert!(!allowed(Some(b"login"), allow, None));
+        assert!(!allowed(Some(b"sudo-i"), allow, None), "no prefix match");
+        assert!(!allowed(None, allow, None), "unknown service");
+
+        // Deny wins over allow; `*` covers everything, unknown included.
+        assert!(!allowed(Some(b"sudo"), allow, Some(b"sudo")));
+        assert!(!allowed(Some(b"login"), None, Some(b"*")));
+        assert!(!allowed(None, None, Some(b"*")));
+        assert!(allowed(None, Some(b"*"), None));
+        assert!(allowed(Some(b"login"), Some(b"*"), Some(b"su*")));
+        assert!(!allowed(Some(b"su-l"), Some(b"*"), Some(b"su*")));
+
+        // Empty lists and entries name nothing.
+        assert!(!allowed(Some(b"sudo"), Some(b""), None));
+        assert!(!allowed(Some(b""), Some(b",,"), None));
+        assert!(allowed(Some(b"sudo"), None, Some(b"")));
+        assert!(allowed(Some(b"sudo"), Some(b",sudo,"), None));
+    }
+
+    #[test]
+    fn has_option_finds_module_argument() {
+        let owned = [
+            CString::new("debug").unwrap(),
+            CString::new("percent_encode_usernames").unwrap(),
+        ];
+        let args = [owned[0].as_ptr(), owned[1].as_ptr()];
+        // SAFETY: args points at two live, NUL-terminated strings.
+        assert!(unsafe { has_option(2, args.as_ptr(), OPT_PERCENT_ENCODE) });
+        assert!(!unsafe { has_option(1, args.as_ptr(), OPT_PERCENT_ENCODE) });
+        assert!(!unsafe { has_option(0, ptr::null(), OPT_PERCENT_ENCODE) });
+    }
+
+    #[test]
+    fn timeout_option_parses_seconds_and_falls_back() {
+        let owned = [
+            CString::new("percent_encode_usernames").unwrap(),
+            CString::new("timeout=5").unwrap(),
+        ];
+        let args = [owned[0].as_ptr(), owned[1].as_ptr()];
+        // SAFETY: args points at two live, NUL-terminated strings.
+        let value = unsafe { option_value(2, args.as_ptr(), OPT_TIMEOUT) };
+        assert_eq!(value, Some(&b"5"[..]));
+        assert_eq!(parse_timeout(value), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_timeout(None), Ok(DEFAULT_TIMEOUT));
+        assert!(parse_timeout(Some(b"0")).is_err());
+        assert_eq!(
+            parse_timeout(Some(b"soon")),
+            Err("invalid timeout=soon, using 3s".to_string())
+        );
+    }
+
+    #[test]
+    fn bus_options_override_the_system_daemon() {
+        assert_eq!(parse_endpoint(None, None).unwrap(), Endpoint::default());
+        let endpoint = parse_endpoint(
+            Some(b"unix:path=/tmp/visage-test/bus"),
+            Some(b"org.freedesktop.Visage1.Test"),
+        )
+        .unwrap();
+        assert_eq!(
+            endpoint,
+            Endpoint {
+                address: Some("unix:path=/tmp/visage-test/bus".to_string()),
+                name: "org.freedesktop.Visage1.Test".to_string(),
+            }
+        );
+        assert!(parse_endpoint(Some(b""), None).is_err());
+        assert!(parse_endpoint(Some(&[0xff]), None).is_err());
+        assert!(parse_endpoint(None, Some(b":1.42")).is_err());
+        assert!(parse_endpoint(None, Some(b"not a name")).is_err());
+    }
+
+    #[test]
+    fn retry_options_parse_and_fall_back() {
+        assert_eq!(parse_max_tries(Some(b"3")), Ok(3));
+        assert_eq!(parse_max_tries(None), Ok(DEFAULT_MAX_TRIES));
+        assert_eq!(
+            parse_max_tries(Some(b"0")),
+            Err("invalid max_tries=0, using 1".to_string())
+        );
+        assert_eq!(
+            parse_retry_delay(Some(b"0")),
+            Ok(Duration::ZERO),
+            "0 retries at once"
+        );
+        assert_eq!(parse_retry_delay(None), Ok(DEFAULT_RETRY_DELAY));
+        assert!(parse_retry_delay(Some(b"-1")).is_err());
+        assert_eq!(parse_grace(Some(b"10")), Ok(10));
+        assert_eq!(parse_grace(None), Ok(0));
+        assert_eq!(parse_grace(Some(b"0")), Ok(0));
+        assert!(parse_grace(Some(b"soon")).is_err());
+    }
+
+    #[test]
+    fn retries_count_misses_and_stop_on_a_match() {
+        let per_try = Duration::from_secs(3);
+        let run = |results: &[Result<bool, &'static str>]| {
+            let mut calls = 0;
+            let mut retries = Vec::new();
+            let outcome = verify_with_retries(
+                3,
+                per_try,
+                Duration::ZERO,
+                |_| {
+                    calls += 1;
+                    results[calls - 1]
+                },
+                |next| retries.push(next),
+            );
+            (outcome, calls, retries)
+        };
+
+        // Every try misses: three calls, then the no-match stands.
+        assert_eq!(
+            run(&[Ok(false), Ok(false), Ok(false)]),
+            (Ok(false), 3, vec![2, 3])
+        );
+        // A match ends the loop early.
+        assert_eq!(run(&[Ok(false), Ok(true)]), (Ok(true), 2, vec![2]));
+        assert_eq!(run(&[Ok(true)]), (Ok(true), 1, vec![]));
+        // Errors are never retried.
+        assert_eq!(run(&[Err("daemon down")]), (Err("daemon down"), 1, vec![]));
+        assert_eq!(
+            run(&[Ok(false), Err("timed out")]),
+            (Err("timed out"), 2, vec![2])
+        );
+    }
+
+    #[test]
+    fn retries_stay_within_the_overall_budget() {
+        let per_try = Duration::from_millis(40);
+        let delay = Duration::from_millis(15);
+        let mut timeouts = Vec::new();
+        let outcome = verify_with_retries(
+            4,
+            per_try,
+            delay,
+            |timeout| {
+                // A daemon that takes the whole allowance to find no match.
+                timeouts.push(timeout);
+                std::thread::sleep(timeout);
+                Ok::<_, ()>(false)
+            },
+            |_| {},
+        );
+        assert_eq!(outcome, Ok(false));
+        assert!(timeouts.iter().all(|t| *t <= per_try), "{timeouts:?}");
+        let pauses = delay * (timeouts.len() as u32 - 1);
+        // The pauses eat into the budget, so there is no room for a fourth
+        // full try.
+        assert!(timeouts.len() < 4 || timeouts[3] < per_try, "{timeouts:?}");
+        assert!(
+            timeouts.iter().sum::<Duration>() + pauses <= per_try * 4,
+            "{timeouts:?}"
+        );
+    }
+
+    #[test]
+    fn log_line_keeps_one_message_per_line() {
+        assert_eq!(
+            log_line("no match for user 'alice'").as_bytes(),
+            b"no match for user 'alice'"
+        );
+        // A username cannot forge a second auth.log entry or truncate the line.
+        assert_eq!(
+            log_line("no match for user 'x'\nsshd: Accepted\0").as_bytes(),
+            br"no match for user 'x'\nsshd: Accepted\u{0}"
+        );
+        assert_eq!(
+            log_line("100% jos\u{e9}").to_str().unwrap(),
+            "100% jos\u{e9}"
+        );
+    }
+
+    /// Stand-in for visaged whose Verify takes longer than any sane timeout.
+    struct SlowVisage;
+
+    #[zbus::interface(name = "org.freedesktop.Visage1")]
+    impl SlowVisage {
+        fn verify(&self, _user: &str) -> bool {
+            std::thread::sleep(Duration::from_secs(5));
+            true
+        }
+    }
+
+    #[test]
+    fn verify_face_gives_up_after_timeout() {
+        let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            zbus::blocking::connection::Builder::unix_stream(server_sock)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/Visage1", SlowVisage)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let conn = zbus::blocking::connection::Builder::unix_stream(client_sock)
+            .p2p()
+            .method_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+
+        let started = std::time::Instant::now();
+        let result = verify_face_on(&daemon(conn), "alice", None, false, false);
+        assert!(
+            matches!(result, Err(VerifyError::Timeout(_))),
+            "a stalled daemon must not authenticate: {result:?}"
+        );
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "verify took {:?}",
+            started.elapsed()
+        );
+    }
+
+    /// Stand-in for a visaged that issues one-time tokens.
+    struct TokenVisage;
+
+    const ISSUED: &str = "00112233445566778899aabbccddeeff";
+
+    #[zbus::interface(name = "org.freedesktop.Visage1")]
+    impl TokenVisage {
+        fn get_capabilities(&self) -> Vec<String> {
+            vec![CAP_VERIFY_TOKENS.to_string()]
+        }
+
+        fn verify(&self, _user: &str) -> bool {
+            true
+        }
+
+        fn verify_with_token(&self, user: &str) -> (bool, String) {
+            let matched = user == "alice";
+            (
+                matched,
+                if matched {
+                    ISSUED.into()
+                } else {
+                    String::new()
+                },
+            )
+        }
+
+        fn redeem_token(&self, user: &str, token: &str) -> bool {
+            user == "alice" && token == ISSUED
+        }
+    }
+
+    #[test]
+    fn use_token_asks_for_and_redeems_a_token() {
+        let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            zbus::blocking::connection::Builder::unix_stream(server_sock)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/Visage1", TokenVisage)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let conn = zbus::blocking::connection::Builder::unix_stream(client_sock)
+            .p2p()
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+        let conn = daemon(conn);
+
+        let issued = verify_face_on(&conn, "alice", None, true, false).unwrap();
+        assert!(issued.matched);
+        assert_eq!(issued.token.as_deref(), Some(ISSUED));
+        // No match, no token; without use_token plain Verify runs.
+        assert_eq!(
+            verify_face_on(&conn, "bob", None, true, false).unwrap(),
+            Verdict::default()
+        );
+        assert!(
+            verify_face_on(&conn, "bob", None, false, false)
+                .unwrap()
+                .matched
+        );
+
+        assert!(redeem_token_on(&conn, "alice", ISSUED).unwrap());
+        assert!(!redeem_token_on(&conn, "bob", ISSUED).unwrap());
+        assert!(!redeem_token_on(&conn, "alice", "guess").unwrap());
+    }
+
+    /// Stand-in for a visaged that matched alice moments ago.
+    struct GraceVisage;
+
+    #[zbus::interface(name = "org.freedesktop.Visage1")]
+    impl GraceVisage {
+        fn get_capabilities(&self) -> Vec<String> {
+            vec![CAP_VERIFY_GRACE.to_string()]
+        }
+
+        fn verify_cached(&self, user: &str, max_age_secs: u32) -> bool {
+            user == "alice" && max_age_secs >= 5
+        }
+    }
+
+    /// visaged under its usual name at the other end of `conn`.
+    fn daemon(conn: zbus::blocking::Connection) -> Daemon {
+        Daemon {
+            conn,
+            name: DEFAULT_BUS_NAME.to_string(),
+        }
+    }
+
+    /// Serve `iface` over a socket pair; returns the server and client ends.
+    fn serve_p2p<I: zbus::object_server::Interface>(
+        iface: I,
+    ) -> (zbus::blocking::Connection, Daemon) {
+        let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            zbus::blocking::connection::Builder::unix_stream(server_sock)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/Visage1", iface)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let conn = zbus::blocking::connection::Builder::unix_stream(client_sock)
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), daemon(conn))
+    }
+
+    /// Stand-in for a visaged that reports scores through `VerifyDetailed`.
+    struct DetailedVisage;
+
+    #[zbus::interface(name = "org.freedesktop.Visage1")]
+    impl DetailedVisage {
+        fn get_capabilities(&self) -> Vec<String> {
+            vec![CAP_VERIFY_DETAILED.to_string()]
+        }
+
+        fn verify(&self, _user: &str) -> bool {
+            false
+        }
+
+        fn verify_detailed(&self, user: &str) -> String {
+            if user == "alice" {
+                r#"{"matched":true,"similarity":0.8,"threshold":0.4,"model_id":"m1","model_label":"desk","fast_path":false,"failure_reason":null}"#
+            } else {
+                r#"{"matched":false,"similarity":0.25,"threshold":0.4,"model_id":null,"model_label":null,"fast_path":false,"failure_reason":"below_threshold"}"#
+            }
+            .to_string()
+        }
+    }
+
+    #[test]
+    fn debug_logs_the_score_from_verify_detailed() {
+        let (_server, conn) = serve_p2p(DetailedVisage);
+        let verdict = verify_face_on(&conn, "alice", None, false, true).unwrap();
+        assert!(verdict.matched);
+        let detail = verdict.detail.unwrap();
+        assert_eq!(
+            detail,
+            Detail {
+                similarity: 0.8,
+                model_label: Some("desk".to_string()),
+                failure_reason: None,
+            }
+        );
+        assert_eq!(detail.to_string(), "similarity 0.800, model 'desk'");
+
+        let verdict = verify_face_on(&conn, "bob", None, false, true).unwrap();
+        assert!(!verdict.matched);
+        assert_eq!(
+            verdict.detail.unwrap().to_string(),
+            "similarity 0.250, below_threshold"
+        );
+
+        // Without debug, or against a daemon without the method, plain Verify.
+        assert_eq!(
+            verify_face_on(&conn, "alice", None, false, false).unwrap(),
+            Verdict::default()
+        );
+        let (_server, conn) = serve_p2p(TokenVisage);
+        let verdict = verify_face_on(&conn, "bob", None, false, true).unwrap();
+        assert!(verdict.matched && verdict.detail.is_none());
+
+        assert!(matches!(
+            parse_verify_report("{}"),
+            Err(VerifyError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn grace_asks_for_a_recent_match_only_when_advertised() {
+        let (_server, conn) = serve_p2p(GraceVisage);
+        assert!(verify_cached_on(&conn, "alice", None, 10).unwrap());
+        assert!(!verify_cached_on(&conn, "alice", None, 2).unwrap());
+        assert!(!verify_cached_on(&conn, "bob", None, 10).unwrap());
+        // An encoded name needs a capability this daemon lacks: full verify.
+        assert!(!verify_cached_on(&conn, "alice", Some(CAP_PERCENT_ENCODED), 10).unwrap());
+
+        // A daemon without the capability is never asked.
+        let (_server, conn) = serve_p2p(TokenVisage);
+        assert!(!verify_cached_on(&conn, "alice", None, 10).unwrap());
+    }
+
+    fn method_error(name: &str, msg: &str) -> zbus::Error {
+        let reply = zbus::message::Message::method_call("/org/freedesktop/Visage1", "Verify")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        zbus::Error::MethodError(
+            zbus::names::OwnedErrorName::try_from(name).unwrap(),
+            Some(msg.to_string()),
+            reply,
+        )
+    }
+
+    #[test]
+    fn daemon_error_names_map_to_verify_errors() {
+        let classify = |name, msg| VerifyError::from(method_error(name, msg));
+        assert!(matches!(
+            classify(ERR_NOT_ENROLLED, "no enrolled models for user 'alice'"),
+            VerifyError::NotEnrolled
+        ));
+        // Daemons that predate the structured error.
+        assert!(matches!(
+            classify(
+                "org.freedesktop.Visage1.Error.Failed",
+                "no enrolled models for user 'alice'"
+            ),
+            VerifyError::NotEnrolled
+        ));
+        assert!(matches!(
+            classify(ERR_POLICY_DENIED, "outside verify windows"),
+            VerifyError::Denied(m) if m == "outside verify windows"
+        ));
+        assert!(matches!(
+            classify("org.freedesktop.DBus.Error.AccessDenied", "root only"),
+            VerifyError::Denied(_)
+        ));
+        assert!(matches!(
+            classify(ERR_TIMEOUT, "verify timed out"),
+            VerifyError::Timeout(_)
+        ));
+        assert!(matches!(
+            classify("org.freedesktop.DBus.Error.NoReply", "no reply"),
+            VerifyError::Timeout(_)
+        ));
+        assert!(matches!(
+            classify("org.freedesktop.DBus.Error.ServiceUnknown", "not provided"),
+            VerifyError::DaemonUnreachable(_)
+        ));
+        assert!(matches!(
+            classify(ERR_RECOGNIZER_UNAVAILABLE, "checksum mismatch"),
+            VerifyError::DaemonUnavailable(_)
+        ));
+        assert!(matches!(
+            classify(ERR_CANCELLED, "verify cancelled by the client"),
+            VerifyError::Cancelled
+        ));
+        assert!(matches!(
+            classify(ERR_STORE_UNAVAILABLE, "database: no such table: faces"),
+            VerifyError::StoreUnavailable(m) if m.starts_with("database")
+        ));
+        assert!(matches!(
+            classify(ERR_RATE_LIMITED, "retry after 287s: too many failed attempts"),
+            VerifyError::Denied(m) if m.contains("287s")
+        ));
+        assert!(matches!(
+            classify(ERR_CAMERA_UNAVAILABLE, "camera error: device busy"),
+            VerifyError::DaemonUnavailable(_)
+        ));
+        assert!(matches!(
+            classify(ERR_FACE_CHANGED, "face changed during capture"),
+            VerifyError::Denied(_)
+        ));
+        assert!(matches!(
+            classify(ERR_DIRECTORY_UNAVAILABLE, "cannot look up account 'alice': EIO"),
+            VerifyError::DirectoryUnavailable(m) if m.contains("EIO")
+        ));
+        assert!(matches!(
+            classify("org.freedesktop.Visage1.Error.Failed", "camera: device busy"),
+            VerifyError::Other(m) if m.contains("camera: device busy")
+        ));
+
+        let timed_out = zbus::Error::from(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "timed out",
+        ));
+        assert!(matches!(
+            VerifyError::from(timed_out),
+            VerifyError::Timeout(_)
+        ));
+        let no_socket = zbus::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(
+            VerifyError::from(no_socket),
+            VerifyError::DaemonUnreachable(_)
+        ));
+    }
+
+    #[test]
+    fn only_an_unreachable_daemon_reports_authinfo_unavail() {
+        let status = |e: zbus::Error| VerifyError::from(e).pam_status();
+        for name in [
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+            "org.freedesktop.DBus.Error.NameHasNoOwner",
+            "org.freedesktop.DBus.Error.NoServer",
+            "org.freedesktop.DBus.Error.Disconnected",
+        ] {
+            assert_eq!(
+                status(method_error(name, "")),
+                PAM_AUTHINFO_UNAVAIL,
+                "{name}"
+            );
+        }
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert_eq!(status(zbus::Error::from(refused)), PAM_AUTHINFO_UNAVAIL);
+
+        // The daemon answered: a no-match-like outcome, not an outage.
+        for name in [
+            ERR_NOT_ENROLLED,
+            ERR_POLICY_DENIED,
+            ERR_TIMEOUT,
+            ERR_RECOGNIZER_UNAVAILABLE,
+            ERR_ENGINE_RESTARTED,
+            ERR_STORE_UNAVAILABLE,
+            ERR_DIRECTORY_UNAVAILABLE,
+            "org.freedesktop.DBus.Error.NoReply",
+            "org.freedesktop.Visage1.Error.Failed",
+        ] {
+            assert_eq!(status(method_error(name, "")), PAM_IGNORE, "{name}");
+        }
+        let timed_out = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        assert_eq!(status(zbus::Error::from(timed_out)), PAM_IGNORE);
+    }
+
+    #[test]
+    fn store_failures_log_loudly_and_missing_enrollment_quietly() {
+        assert_eq!(VerifyError::NotEnrolled.log_entry("alice").0, LOG_DEBUG);
+
+        let broken = VerifyError::StoreUnavailable("database: no such table: faces".into());
+        let (priority, msg) = broken.log_entry("alice");
+        assert_eq!(priority, LOG_ERR);
+        assert!(msg.contains("database: no such table"), "{msg}");
+
+        let policy = VerifyError::Denied("not now".into());
+        assert_eq!(policy.log_entry("alice").0, LOG_INFO);
+        let other = VerifyError::Other("camera".into());
+        assert_eq!(other.log_entry("alice").0, LOG_WARNING);
+    }
+
     #[test]
     fn verify_face_errors_when_daemon_not_running() {
         // When visaged is not on the system bus, verify_face must return Err,
@@ -303,21 +2153,27 @@ mod tests {
         // This test will pass in any environment where visaged is not running,
         // including CI. If the daemon happens to be running, the test is skipped
         // to avoid a real camera capture during unit testing.
-        let result = verify_face("_pam_visage_unit_test_user_");
+        let result = verify_face(
+            &Endpoint::default(),
+            "_pam_visage_unit_test_user_",
+            None,
+            DEFAULT_TIMEOUT,
+            false,
+            false,
+        );
         // If the daemon is running we get Ok(true/false); that's also fine —
         // the important property is no panic.
         match result {
             Err(e) => {
                 // Expected: daemon not present
-                let msg = e.to_string();
                 assert!(
-                    msg.contains("ServiceUnknown")
-                        || msg.contains("NameHasNoOwner")
-                        || msg.contains("not provided")
-                        || msg.contains("Failed to connect")
-                        || msg.contains("no enrolled models")
-                        || msg.contains("unknown user"),
-                    "unexpected error message: {msg}"
+                    matches!(
+                        &e,
+                        VerifyError::DaemonUnreachable(_)
+                            | VerifyError::DaemonUnavailable(_)
+                            | VerifyError::NotEnrolled
+                    ) || e.to_string().contains("unknown user"),
+                    "unexpected error: {e:?}"
                 );
             }
             Ok(_) => {
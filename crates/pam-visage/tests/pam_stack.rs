@@ -0,0 +1,270 @@
+//! End-to-end test of the built `pam_visage.so` in a real PAM stack.
+//!
+//! Each case writes a one-line pam.d service into a temporary directory and
+//! authenticates through it with `pam_start_confdir(3)` (Linux-PAM 1.4+),
+//! which reads that directory in place of /etc/pam.d — what pam_wrapper's
+//! `PAM_WRAPPER_SERVICE_DIR` does, without the preload. The module talks to a
+//! fake visaged on a private `dbus-daemon`, reached through its
+//! `bus_address=` and `bus_name=` arguments.
+//!
+//! Needs libpam and `dbus-daemon` at run time, so it only builds with the
+//! `pam-integration` feature, and Cargo does not build a `cdylib` for its
+//! integration tests:
+//!
+//! ```sh
+//! cargo build -p pam-visage
+//! cargo test -p pam-visage --features pam-integration --test pam_stack
+//! ```
+//!
+//! Set `PAM_VISAGE_MODULE` to test a module other than the one Cargo built.
+
+use std::ffi::CString;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const PAM_SUCCESS: libc::c_int = 0;
+const PAM_CRED_INSUFFICIENT: libc::c_int = 8;
+const PAM_AUTHINFO_UNAVAIL: libc::c_int = 9;
+
+/// What the stack returns once pam_visage returned `PAM_IGNORE` and the
+/// module after it ran: `pam_debug.so auth=cred_insufficient`.
+const NEXT_MODULE: libc::c_int = PAM_CRED_INSUFFICIENT;
+
+const SERVICE: &str = "visage-test";
+const FAKE_NAME: &str = "org.freedesktop.Visage1.Test";
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: libc::c_int,
+    msg: *const libc::c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut libc::c_char,
+    resp_retcode: libc::c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: unsafe extern "C" fn(
+        libc::c_int,
+        *mut *const PamMessage,
+        *mut *mut PamResponse,
+        *mut libc::c_void,
+    ) -> libc::c_int,
+    appdata_ptr: *mut libc::c_void,
+}
+
+#[link(name = "pam")]
+extern "C" {
+    fn pam_start_confdir(
+        service: *const libc::c_char,
+        user: *const libc::c_char,
+        conv: *const PamConv,
+        confdir: *const libc::c_char,
+        pamh: *mut *mut libc::c_void,
+    ) -> libc::c_int;
+    fn pam_authenticate(pamh: *mut libc::c_void, flags: libc::c_int) -> libc::c_int;
+    fn pam_end(pamh: *mut libc::c_void, status: libc::c_int) -> libc::c_int;
+}
+
+/// Conversation that accepts every message with an empty response, as a
+/// terminal would after printing `PAM_TEXT_INFO`.
+unsafe extern "C" fn accept_all(
+    num_msg: libc::c_int,
+    _msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    _appdata: *mut libc::c_void,
+) -> libc::c_int {
+    // PAM frees the responses with free(3).
+    let responses =
+        unsafe { libc::calloc(num_msg.max(1) as usize, std::mem::size_of::<PamResponse>()) }
+            as *mut PamResponse;
+    if responses.is_null() {
+        return 5; // PAM_BUF_ERR
+    }
+    unsafe { *resp = responses };
+    PAM_SUCCESS
+}
+
+/// Stand-in for visaged that matches alice only.
+struct FakeVisage;
+
+#[zbus::interface(name = "org.freedesktop.Visage1")]
+impl FakeVisage {
+    fn verify(&self, user: &str) -> bool {
+        user == "alice"
+    }
+}
+
+/// A private `dbus-daemon` with the fake visaged on it, in its own
+/// temporary directory; both stop on drop.
+struct PrivateBus {
+    dir: PathBuf,
+    daemon: Child,
+    address: String,
+    _visage: zbus::blocking::Connection,
+}
+
+impl PrivateBus {
+    fn start() -> Self {
+        let dir = std::env::temp_dir().join(format!("pam-visage-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("pam.d")).unwrap();
+        let config = dir.join("bus.conf");
+        std::fs::write(
+            &config,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                dir.join("bus").display()
+            ),
+        )
+        .unwrap();
+        let mut daemon = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon must be installed for the PAM integration test");
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let visage = zbus::blocking::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name(FAKE_NAME)
+            .unwrap()
+            .serve_at("/org/freedesktop/Visage1", FakeVisage)
+            .unwrap()
+            .build()
+            .unwrap();
+        Self {
+            dir,
+            daemon,
+            address,
+            _visage: visage,
+        }
+    }
+
+    /// Authenticate `user` through a service running pam_visage with `args`,
+    /// followed by a stand-in password module that fails with
+    /// [`NEXT_MODULE`]; returns what `pam_authenticate` did.
+    ///
+    /// pam_visage's success ends the stack and any other error but
+    /// `PAM_IGNORE` aborts it with that code, so each outcome is visible.
+    fn authenticate(&self, user: &[u8], args: &str) -> libc::c_int {
+        std::fs::write(
+            self.dir.join("pam.d").join(SERVICE),
+            format!(
+                "auth [success=done ignore=ignore default=die] {} {args}\n\
+                 auth required pam_debug.so auth=cred_insufficient\n",
+                module().display()
+            ),
+        )
+        .unwrap();
+
+        let service = CString::new(SERVICE).unwrap();
+        let user = CString::new(user).unwrap();
+        let confdir = CString::new(self.dir.join("pam.d").to_str().unwrap()).unwrap();
+        let conv = PamConv {
+            conv: accept_all,
+            appdata_ptr: ptr::null_mut(),
+        };
+        let mut pamh = ptr::null_mut();
+        // SAFETY: every pointer is a valid C string or struct that outlives
+        // the handle, which is ended before returning.
+        unsafe {
+            let ret = pam_start_confdir(
+                service.as_ptr(),
+                user.as_ptr(),
+                &conv,
+                confdir.as_ptr(),
+                &mut pamh,
+            );
+            assert_eq!(ret, PAM_SUCCESS, "pam_start_confdir");
+            let ret = pam_authenticate(pamh, 0);
+            pam_end(pamh, ret);
+            ret
+        }
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The module under test: `PAM_VISAGE_MODULE`, or the one Cargo built next
+/// to this test's `deps/` directory.
+fn module() -> PathBuf {
+    if let Some(path) = std::env::var_os("PAM_VISAGE_MODULE") {
+        return path.into();
+    }
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(Path::parent).unwrap();
+    let module = profile_dir.join("libpam_visage.so");
+    assert!(
+        module.exists(),
+        "{} not built; run `cargo build -p pam-visage` first",
+        module.display()
+    );
+    module
+}
+
+// The cases share one bus: the private dbus-daemon is slow to start and the
+// fake daemon keeps no state between calls.
+#[test]
+fn pam_visage_in_a_real_stack() {
+    let bus = PrivateBus::start();
+    let on_fake = format!("bus_address={} bus_name={FAKE_NAME} quiet", bus.address);
+
+    // A match authenticates.
+    assert_eq!(bus.authenticate(b"alice", &on_fake), PAM_SUCCESS);
+
+    // No match: PAM_IGNORE, and the next module decides.
+    assert_eq!(bus.authenticate(b"bob", &on_fake), NEXT_MODULE);
+
+    // Nobody owns the bus name, as when visaged is stopped. The module
+    // reports PAM_AUTHINFO_UNAVAIL rather than PAM_IGNORE here so stacks can
+    // tell an absent daemon from a no-match, and must do so at once rather
+    // than after the call timeout.
+    let started = Instant::now();
+    let absent = format!(
+        "bus_address={} bus_name=org.freedesktop.Visage1.Absent timeout=5",
+        bus.address
+    );
+    assert_eq!(bus.authenticate(b"alice", &absent), PAM_AUTHINFO_UNAVAIL);
+    assert!(
+        started.elapsed() < Duration::from_secs(2),
+        "absent daemon took {:?}",
+        started.elapsed()
+    );
+
+    // No bus at all.
+    let started = Instant::now();
+    let no_bus = "bus_address=unix:path=/nonexistent/bus timeout=5";
+    assert_eq!(bus.authenticate(b"alice", no_bus), PAM_AUTHINFO_UNAVAIL);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // Not UTF-8 and no percent_encode_usernames: never sent to the daemon.
+    assert_eq!(bus.authenticate(b"al\xffice", &on_fake), NEXT_MODULE);
+}
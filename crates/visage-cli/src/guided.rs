@@ -0,0 +1,570 @@
+//! `visage enroll --poses` — guided multi-pose enrollment.
+//!
+//! A gallery holding only frontal templates scores lower when the head is
+//! turned. The guided flow asks for each pose in turn, enrolls it, and checks
+//! the pose the daemon classified before moving on; a capture in the wrong
+//! pose is discarded and retried. Ctrl-C removes the templates the run has
+//! enrolled, so an interrupted run leaves the gallery as it was.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use visage_client::Client;
+
+/// Attempts per pose before moving on without it.
+const MAX_ATTEMPTS: usize = 3;
+
+/// A head pose the daemon can classify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Pose {
+    Frontal,
+    Left,
+    Right,
+}
+
+impl Pose {
+    /// The daemon's `pose_label` for this pose.
+    fn as_str(self) -> &'static str {
+        match self {
+            Pose::Frontal => "frontal",
+            Pose::Left => "left",
+            Pose::Right => "right",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "frontal" => Some(Pose::Frontal),
+            "left" => Some(Pose::Left),
+            "right" => Some(Pose::Right),
+            _ => None,
+        }
+    }
+
+    fn instruction(self) -> &'static str {
+        match self {
+            Pose::Frontal => "Look straight at the camera",
+            Pose::Left => "Turn your head a little to your left",
+            Pose::Right => "Turn your head a little to your right",
+        }
+    }
+}
+
+impl std::fmt::Display for Pose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where the sequence stands when the user is asked for a pose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    pose: Pose,
+    /// 1-based position in the requested sequence.
+    number: usize,
+    total: usize,
+    /// 1-based attempt at this pose.
+    attempt: usize,
+}
+
+/// A template enrolled by one step.
+#[derive(Debug, Clone, PartialEq)]
+struct Enrolled {
+    model_id: String,
+    /// Pose the daemon classified, if it reports one.
+    captured: Option<Pose>,
+}
+
+/// An enroll call that failed.
+#[derive(Debug, Clone, PartialEq)]
+struct Failure {
+    error: String,
+    /// The daemon refused before capturing (the enroll cooldown) and gave
+    /// this wait.
+    retry_after: Option<Duration>,
+}
+
+/// The user pressed Ctrl-C (or closed the input).
+#[derive(Debug, PartialEq)]
+struct Interrupted;
+
+/// What the flow reports as it goes.
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    /// `pose` is enrolled; `covered` lists every pose enrolled so far.
+    Covered { covered: Vec<Pose>, total: usize },
+    /// The capture for `asked` came out as `captured` and was discarded.
+    WrongPose { asked: Pose, captured: Pose },
+    /// The enroll call for `pose` failed.
+    Failed { pose: Pose, error: String },
+}
+
+/// How a run ended.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Every pose was enrolled.
+    Complete,
+    /// These poses ran out of attempts; the other templates were kept.
+    Incomplete { missing: Vec<Pose> },
+    /// Interrupted: the run's templates were removed, except `leftover`.
+    Aborted {
+        removed: usize,
+        leftover: Vec<String>,
+    },
+}
+
+/// The daemon and terminal as the driver sees them, so the sequence logic
+/// can be tested with scripted answers.
+trait PoseSession {
+    /// Ask for `step.pose` and wait until the user is ready.
+    async fn ready(&mut self, step: Step) -> Result<(), Interrupted>;
+    /// Enroll one template in `pose`. A Ctrl-C during the call does not
+    /// abandon it (the daemon finishes the capture anyway); it is reported
+    /// alongside the result instead.
+    async fn enroll(&mut self, pose: Pose) -> (Result<Enrolled, Failure>, Option<Interrupted>);
+    /// Wait `delay` before the next capture.
+    async fn wait(&mut self, delay: Duration) -> Result<(), Interrupted>;
+    async fn remove(&mut self, model_id: &str) -> Result<(), String>;
+    fn report(&mut self, event: Event);
+}
+
+/// Enroll each of `poses` in turn through `session`.
+async fn drive(session: &mut impl PoseSession, poses: &[Pose]) -> Outcome {
+    // Templates this run created and still holds, for a rollback.
+    let mut created: Vec<String> = Vec::new();
+    let mut covered: Vec<Pose> = Vec::new();
+    let mut missing = Vec::new();
+
+    for (i, &pose) in poses.iter().enumerate() {
+        let mut done = false;
+        let mut attempt = 1;
+        while attempt <= MAX_ATTEMPTS {
+            let step = Step {
+                pose,
+                number: i + 1,
+                total: poses.len(),
+                attempt,
+            };
+            if session.ready(step).await.is_err() {
+                return roll_back(session, created).await;
+            }
+            let (result, interrupted) = session.enroll(pose).await;
+            match result {
+                Ok(Enrolled {
+                    model_id,
+                    captured: Some(captured),
+                }) if captured != pose => {
+                    session.report(Event::WrongPose {
+                        asked: pose,
+                        captured,
+                    });
+                    if session.remove(&model_id).await.is_err() {
+                        created.push(model_id);
+                    }
+                }
+                Ok(Enrolled { model_id, .. }) => {
+                    created.push(model_id);
+                    covered.push(pose);
+                    done = true;
+                }
+                Err(Failure { error, retry_after }) => {
+                    session.report(Event::Failed { pose, error });
+                    // Refused before any capture: wait the cooldown out and
+                    // ask again without spending an attempt.
+                    if let (Some(delay), None) = (retry_after, &interrupted) {
+                        if session.wait(delay).await.is_err() {
+                            return roll_back(session, created).await;
+                        }
+                        continue;
+                    }
+                }
+            }
+            if interrupted.is_some() {
+                return roll_back(session, created).await;
+            }
+            if done {
+                session.report(Event::Covered {
+                    covered: covered.clone(),
+                    total: poses.len(),
+                });
+                break;
+            }
+            attempt += 1;
+        }
+        if !done {
+            missing.push(pose);
+        }
+    }
+
+    if missing.is_empty() {
+        Outcome::Complete
+    } else {
+        Outcome::Incomplete { missing }
+    }
+}
+
+async fn roll_back(session: &mut impl PoseSession, created: Vec<String>) -> Outcome {
+    let mut removed = 0;
+    let mut leftover = Vec::new();
+    for model_id in created {
+        match session.remove(&model_id).await {
+            Ok(()) => removed += 1,
+            Err(_) => leftover.push(model_id),
+        }
+    }
+    Outcome::Aborted { removed, leftover }
+}
+
+/// [`PoseSession`] on a real daemon and terminal.
+struct TerminalSession<'a> {
+    client: &'a Client,
+    user: &'a str,
+    label: &'a str,
+    /// Woken once per Ctrl-C; a press while nothing waits is kept for the
+    /// next wait.
+    ctrl_c: Arc<Notify>,
+}
+
+impl PoseSession for TerminalSession<'_> {
+    async fn ready(&mut self, step: Step) -> Result<(), Interrupted> {
+        let retry = if step.attempt > 1 {
+            format!(" (attempt {} of {MAX_ATTEMPTS})", step.attempt)
+        } else {
+            String::new()
+        };
+        println!(
+            "[{}/{}] {}{retry}, then press Enter (Ctrl-C aborts).",
+            step.number,
+            step.total,
+            step.pose.instruction()
+        );
+        let line = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|n| n > 0)
+        });
+        tokio::select! {
+            read = line => match read {
+                Ok(Ok(true)) => Ok(()),
+                _ => Err(Interrupted),
+            },
+            _ = self.ctrl_c.notified() => Err(Interrupted),
+        }
+    }
+
+    async fn enroll(&mut self, pose: Pose) -> (Result<Enrolled, Failure>, Option<Interrupted>) {
+        println!("  capturing — hold still...");
+        let label = format!("{}-{pose}", self.label);
+        let call = self.client.enroll(self.user, &label);
+        tokio::pin!(call);
+        let (result, interrupted) = tokio::select! {
+            result = &mut call => (result, None),
+            _ = self.ctrl_c.notified() => {
+                println!("  interrupted — finishing this capture before undoing the run...");
+                (call.await, Some(Interrupted))
+            }
+        };
+        let result = match result {
+            Ok(model_id) => {
+                // Older daemons do not report poses; take the capture as asked.
+                let captured = self
+                    .client
+                    .list_models(self.user)
+                    .await
+                    .ok()
+                    .and_then(|models| models.into_iter().find(|m| m.id == model_id))
+                    .and_then(|m| Pose::from_label(&m.pose_label));
+                Ok(Enrolled { model_id, captured })
+            }
+            Err(e) => Err(Failure {
+                retry_after: match &e {
+                    visage_client::Error::RateLimited { retry_after, .. } => *retry_after,
+                    _ => None,
+                },
+                error: e.to_string(),
+            }),
+        };
+        (result, interrupted)
+    }
+
+    async fn wait(&mut self, delay: Duration) -> Result<(), Interrupted> {
+        println!(
+            "  waiting {}s before the next capture...",
+            delay.as_secs().max(1)
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => Ok(()),
+            _ = self.ctrl_c.notified() => Err(Interrupted),
+        }
+    }
+
+    async fn remove(&mut self, model_id: &str) -> Result<(), String> {
+        match self.client.remove_model(self.user, model_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("not found".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn report(&mut self, event: Event) {
+        match event {
+            Event::Covered { covered, total } => {
+                let names: Vec<&str> = covered.iter().map(|p| p.as_str()).collect();
+                println!(
+                    "  enrolled — coverage: {} ({} of {total} poses)",
+                    names.join(", "),
+                    covered.len()
+                );
+            }
+            Event::WrongPose { asked, captured } => {
+                println!("  that capture looked {captured}, not {asked}; discarded");
+            }
+            Event::Failed { pose, error } => println!("  enrolling {pose} failed: {error}"),
+        }
+    }
+}
+
+/// Run the guided flow for `user`, labelling templates `<label>-<pose>`.
+/// Returns whether every pose was enrolled. Exit the process on `false`
+/// rather than returning from `main`: a prompt cut short by Ctrl-C leaves a
+/// stdin read behind, which runtime shutdown would wait for.
+pub async fn run(client: &Client, user: &str, label: &str, poses: &[Pose]) -> Result<bool> {
+    let ctrl_c = Arc::new(Notify::new());
+    let listener = {
+        let ctrl_c = ctrl_c.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                ctrl_c.notify_one();
+            }
+        })
+    };
+
+    println!(
+        "Guided enrollment for user '{user}': {} pose(s).",
+        poses.len()
+    );
+    let mut session = TerminalSession {
+        client,
+        user,
+        label,
+        ctrl_c,
+    };
+    let outcome = drive(&mut session, poses).await;
+    listener.abort();
+
+    println!();
+    match outcome {
+        Outcome::Complete => {
+            println!("All {} pose(s) enrolled.", poses.len());
+            Ok(true)
+        }
+        Outcome::Incomplete { missing } => {
+            let missing: Vec<&str> = missing.iter().map(|p| p.as_str()).collect();
+            println!(
+                "Not enrolled: {}. The other poses were kept; run again with `--poses {}` \
+                 to add the rest.",
+                missing.join(", "),
+                missing.join(",")
+            );
+            Ok(false)
+        }
+        Outcome::Aborted { removed, leftover } => {
+            println!("Aborted; removed the {removed} template(s) this run enrolled.");
+            if !leftover.is_empty() {
+                println!(
+                    "Could not remove {}; delete them with `visage remove <id>`.",
+                    leftover.join(", ")
+                );
+            }
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers from a script: `ready` is interrupted at `interrupt_at`
+    /// (pose number, attempt), and each enroll takes the next answer.
+    #[derive(Default)]
+    struct Scripted {
+        interrupt_at: Option<(usize, usize)>,
+        answers: VecDeque<(Result<Option<Pose>, &'static str>, bool)>,
+        next_id: usize,
+        asked: Vec<Step>,
+        waited: Vec<Duration>,
+        removed: Vec<String>,
+        events: Vec<Event>,
+    }
+
+    impl Scripted {
+        fn answering(answers: Vec<(Result<Option<Pose>, &'static str>, bool)>) -> Self {
+            Self {
+                answers: answers.into(),
+                ..Self::default()
+            }
+        }
+    }
+
+    /// An enroll the daemon refused during its cooldown.
+    const COOLDOWN: &str = "rate limited: retry after 5s: enroll cooldown";
+
+    impl PoseSession for Scripted {
+        async fn ready(&mut self, step: Step) -> Result<(), Interrupted> {
+            self.asked.push(step);
+            if self.interrupt_at == Some((step.number, step.attempt)) {
+                return Err(Interrupted);
+            }
+            Ok(())
+        }
+
+        async fn enroll(
+            &mut self,
+            _pose: Pose,
+        ) -> (Result<Enrolled, Failure>, Option<Interrupted>) {
+            let (answer, interrupted) = self.answers.pop_front().expect("unscripted enroll");
+            self.next_id += 1;
+            let result = answer
+                .map(|captured| Enrolled {
+                    model_id: format!("m{}", self.next_id),
+                    captured,
+                })
+                .map_err(|error| Failure {
+                    error: error.to_string(),
+                    retry_after: (error == COOLDOWN).then_some(Duration::from_secs(5)),
+                });
+            (result, interrupted.then_some(Interrupted))
+        }
+
+        async fn wait(&mut self, delay: Duration) -> Result<(), Interrupted> {
+            self.waited.push(delay);
+            Ok(())
+        }
+
+        async fn remove(&mut self, model_id: &str) -> Result<(), String> {
+            self.removed.push(model_id.to_string());
+            Ok(())
+        }
+
+        fn report(&mut self, event: Event) {
+            self.events.push(event);
+        }
+    }
+
+    const ALL: [Pose; 3] = [Pose::Frontal, Pose::Left, Pose::Right];
+
+    #[tokio::test]
+    async fn wrong_poses_are_discarded_and_retried_until_covered() {
+        let mut session = Scripted::answering(vec![
+            (Ok(Some(Pose::Frontal)), false),
+            // Not turned far enough: classified frontal.
+            (Ok(Some(Pose::Frontal)), false),
+            (Ok(Some(Pose::Left)), false),
+            // An older daemon reports no pose; taken as asked.
+            (Ok(None), false),
+        ]);
+        assert_eq!(drive(&mut session, &ALL).await, Outcome::Complete);
+        assert_eq!(session.removed, ["m2"]);
+        assert_eq!(
+            session
+                .asked
+                .iter()
+                .map(|s| (s.number, s.attempt))
+                .collect::<Vec<_>>(),
+            [(1, 1), (2, 1), (2, 2), (3, 1)]
+        );
+        assert_eq!(
+            session.events.last(),
+            Some(&Event::Covered {
+                covered: ALL.to_vec(),
+                total: 3
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn a_pose_that_keeps_failing_is_skipped_and_the_rest_kept() {
+        let mut session = Scripted::answering(vec![
+            (Err("no face detected"), false),
+            (Ok(Some(Pose::Frontal)), false),
+            (Ok(Some(Pose::Frontal)), false),
+            (Err("timed out"), false),
+            (Ok(Some(Pose::Right)), false),
+        ]);
+        let outcome = drive(&mut session, &[Pose::Left, Pose::Right]).await;
+        assert_eq!(
+            outcome,
+            Outcome::Incomplete {
+                missing: vec![Pose::Left]
+            }
+        );
+        assert_eq!(session.removed, ["m2", "m3"]);
+        assert_eq!(
+            session.events.last(),
+            Some(&Event::Covered {
+                covered: vec![Pose::Right],
+                total: 2
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn an_enroll_cooldown_is_waited_out_without_spending_an_attempt() {
+        let mut session = Scripted::answering(vec![
+            (Ok(Some(Pose::Frontal)), false),
+            // The next pose came too soon after the last enroll.
+            (Err(COOLDOWN), false),
+            (Err(COOLDOWN), false),
+            (Err(COOLDOWN), false),
+            (Ok(Some(Pose::Left)), false),
+        ]);
+        let outcome = drive(&mut session, &[Pose::Frontal, Pose::Left]).await;
+        assert_eq!(outcome, Outcome::Complete);
+        assert_eq!(session.waited, [Duration::from_secs(5); 3]);
+        assert_eq!(
+            session
+                .asked
+                .iter()
+                .map(|s| (s.number, s.attempt))
+                .collect::<Vec<_>>(),
+            [(1, 1), (2, 1), (2, 1), (2, 1), (2, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_removes_every_template_the_run_enrolled() {
+        // Interrupted while the second capture runs: it completes, then the
+        // whole run is undone.
+        let mut session = Scripted::answering(vec![
+            (Ok(Some(Pose::Frontal)), false),
+            (Ok(Some(Pose::Left)), true),
+        ]);
+        let outcome = drive(&mut session, &ALL).await;
+        assert_eq!(
+            outcome,
+            Outcome::Aborted {
+                removed: 2,
+                leftover: vec![]
+            }
+        );
+        assert_eq!(session.removed, ["m1", "m2"]);
+
+        // Interrupted at a prompt: nothing more is enrolled.
+        let mut session = Scripted {
+            interrupt_at: Some((2, 1)),
+            ..Scripted::answering(vec![(Ok(Some(Pose::Frontal)), false)])
+        };
+        let outcome = drive(&mut session, &ALL).await;
+        assert_eq!(
+            outcome,
+            Outcome::Aborted {
+                removed: 1,
+                leftover: vec![]
+            }
+        );
+        assert_eq!(session.removed, ["m1"]);
+        assert!(session.answers.is_empty());
+    }
+}
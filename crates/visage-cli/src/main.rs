@@ -1,21 +1,12 @@
+mod guided;
+mod pam_config;
+mod report;
 mod setup;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::time::Duration;
-
-#[zbus::proxy(
-    interface = "org.freedesktop.Visage1",
-    default_service = "org.freedesktop.Visage1",
-    default_path = "/org/freedesktop/Visage1"
-)]
-trait Visage {
-    async fn enroll(&self, user: &str, label: &str) -> zbus::fdo::Result<String>;
-    async fn verify(&self, user: &str) -> zbus::fdo::Result<bool>;
-    async fn status(&self) -> zbus::fdo::Result<String>;
-    async fn list_models(&self, user: &str) -> zbus::fdo::Result<String>;
-    async fn remove_model(&self, user: &str, model_id: &str) -> zbus::fdo::Result<bool>;
-}
+use visage_client::{Bus, Client, Error as ClientError, Progress, VerifyOutcome};
 
 #[derive(Parser)]
 #[command(name = "visage", about = "Visage biometric authentication CLI")]
@@ -35,6 +26,15 @@ enum Commands {
         /// User to enroll for (defaults to $USER)
         #[arg(short, long)]
         user: Option<String>,
+
+        /// Enroll for another user as an administrator, authenticating via polkit
+        #[arg(long)]
+        admin: bool,
+
+        /// Guide through one template per head pose (e.g. frontal,left,right),
+        /// labelled <label>-<pose>
+        #[arg(long, value_delimiter = ',', conflicts_with = "admin")]
+        poses: Option<Vec<guided::Pose>>,
     },
     /// Verify your face against enrolled models
     Verify {
@@ -42,31 +42,157 @@ enum Commands {
         #[arg(short, long)]
         user: Option<String>,
     },
+    /// Print which enrolled user is in front of the camera, if any (root only)
+    Identify,
+    /// Match a stored PNG frame instead of the camera and print detailed scores (root only)
+    VerifyFrame {
+        /// PNG image to run through the pipeline
+        image: std::path::PathBuf,
+
+        /// User whose gallery to match against (defaults to $USER)
+        #[arg(short, long)]
+        user: Option<String>,
+    },
     /// List enrolled face models
     List {
         /// User whose models to list (defaults to $USER)
         #[arg(short, long)]
         user: Option<String>,
     },
-    /// Remove an enrolled face model
+    /// Remove an enrolled face model, or all of them
     Remove {
         /// Model ID to remove
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+
+        /// Remove every model of the user instead
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+
+        /// User who owns the model (defaults to $USER)
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+    /// Change the label of an enrolled face model
+    Rename {
+        /// Model ID to rename
         id: String,
 
+        /// New label (e.g., "with-glasses")
+        label: String,
+
         /// User who owns the model (defaults to $USER)
         #[arg(short, long)]
         user: Option<String>,
     },
+    /// Remove near-duplicate templates, keeping the best of each group
+    Dedupe {
+        /// User whose templates to clean up (defaults to $USER)
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// Cosine similarity at which two templates count as duplicates
+        #[arg(short, long, default_value_t = 0.90)]
+        threshold: f64,
+    },
     /// Download ONNX models required for face detection and recognition
     Setup {
         /// Model directory (default: /var/lib/visage/models when root, ~/.local/share/visage/models otherwise)
         #[arg(short, long)]
         model_dir: Option<String>,
+
+        /// Only check the installed models and report each file's state
+        #[arg(long)]
+        check: bool,
+    },
+    /// Report near-identical enrollments under different users (root only)
+    Duplicates,
+    /// List enrollments of users whose accounts no longer exist (root only)
+    Orphans {
+        /// Remove them instead
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Write the face database, sealed with a passphrase, to a portable file (root only)
+    Backup {
+        /// File to write
+        path: std::path::PathBuf,
+    },
+    /// Replace the face database with a file written by `visage backup` (root only)
+    Restore {
+        /// File to read
+        path: std::path::PathBuf,
+    },
+    /// Capture raw frames and show a brightness histogram to diagnose lighting (root only)
+    Exposure,
+    /// Measure the camera's frame rate, jitter and dropped frames (root only)
+    Benchmark {
+        /// Frames to capture (2–300)
+        #[arg(short, long, default_value_t = 60)]
+        frames: u32,
+    },
+    /// Show the engine's request queue and in-flight operation
+    Engine {
+        /// Abort the in-flight operation instead (root only)
+        #[arg(long)]
+        cancel: bool,
+    },
+    /// Pin a user to a recognizer model version, or clear the pin (root only)
+    PinModel {
+        /// Model version to require (e.g. "w600k_r50"); omit to clear the pin
+        version: Option<String>,
+
+        /// User to pin (defaults to $USER)
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+    /// Change the daemon's log filter without restarting it (root only)
+    LogLevel {
+        /// Filter directive in RUST_LOG syntax (e.g. "visaged=debug")
+        directive: String,
+    },
+    /// Re-verify the model files and load them into the running daemon (root only)
+    ReloadModels,
+    /// Show or change the daemon's verification policy without restarting it (root only)
+    Policy {
+        /// JSON object of the fields to change (e.g. '{"similarity_threshold": 0.6}')
+        #[arg(long)]
+        set: Option<String>,
+    },
+    /// Show or store per-camera settings, kept across restarts (root only)
+    DeviceSettings {
+        /// JSON object of the fields to store (e.g. '{"liveness_min_displacement": 0.8}')
+        #[arg(long)]
+        set: Option<String>,
     },
     /// Show daemon status
-    Status,
+    Status {
+        /// Break enrollments down by recognizer model version and estimate
+        /// how many users an upgrade would force to re-enroll
+        #[arg(long)]
+        models: bool,
+    },
+    /// Print the daemon's D-Bus interface as introspection XML, for client code generation
+    Introspect,
+    /// Inspect the daemon's configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Write an anonymized hardware compatibility report (camera, config, aggregate stats)
+    ReportHardware {
+        /// Output file
+        #[arg(short, long, default_value = "visage-hardware-report.json")]
+        output: String,
+
+        /// Write without showing the preview prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
     /// List cameras and their IR emitter quirk status
     Discover,
+    /// Check that face auth is wired up: the daemon answers and which PAM services use it
+    Doctor,
     /// Run camera diagnostics
     Test {
         /// Camera device path
@@ -79,6 +205,12 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Report renamed, conflicting or unknown VISAGE_* variables the daemon was started with
+    Validate,
+}
+
 fn current_user() -> String {
     std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
 }
@@ -90,23 +222,196 @@ fn verify_timeout_secs() -> u64 {
         .unwrap_or(10)
 }
 
-async fn connect_proxy() -> Result<VisageProxy<'static>> {
-    let use_session = std::env::var("VISAGE_SESSION_BUS").is_ok();
-    let timeout = Duration::from_secs(verify_timeout_secs());
-    let conn = if use_session {
-        zbus::connection::Builder::session()?
-    } else {
-        zbus::connection::Builder::system()?
-    }
-    .method_timeout(timeout)
-    .build()
-    .await
-    .map_err(|e| anyhow::anyhow!("failed to connect to D-Bus: {e}"))?;
+fn enroll_timeout_secs() -> u64 {
+    std::env::var("VISAGE_ENROLL_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(15)
+}
+
+/// Extra reply time for calls that may wait on a polkit prompt: `--admin`,
+/// and enrolling or removing models as a non-root user.
+const POLKIT_PROMPT_GRACE: Duration = Duration::from_secs(120);
 
-    let proxy = VisageProxy::new(&conn)
+async fn connect_client() -> Result<Client> {
+    connect_client_with_grace(Duration::ZERO).await
+}
+
+async fn connect_client_with_grace(grace: Duration) -> Result<Client> {
+    // Outlast the daemon's own operation timeouts so its structured Timeout
+    // error reaches us instead of a generic D-Bus reply timeout.
+    let timeout = Duration::from_secs(verify_timeout_secs().max(enroll_timeout_secs()) + 2) + grace;
+    Client::connect(Bus::from_env(), Some(timeout))
         .await
-        .map_err(|e| anyhow::anyhow!("failed to create proxy: {e} — is visaged running?"))?;
-    Ok(proxy)
+        .map_err(|e| anyhow::anyhow!("failed to connect to visaged: {e} — is visaged running?"))
+}
+
+/// Register a textual polkit agent for this process so `--admin` can prompt
+/// on a bare terminal. A desktop session's own agent takes precedence
+/// (`--fallback`). Returns once the agent is registered, or `None` if
+/// `pkttyagent` could not be started.
+fn spawn_tty_agent() -> Option<std::process::Child> {
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+
+    let mut fds = [0; 2];
+    // SAFETY: `fds` is a valid two-element buffer for pipe() to fill.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: pipe() just returned these descriptors and nothing else owns them.
+    let (mut ready, notify) = unsafe {
+        (
+            std::fs::File::from_raw_fd(fds[0]),
+            std::fs::File::from_raw_fd(fds[1]),
+        )
+    };
+    let child = std::process::Command::new("pkttyagent")
+        .arg("--process")
+        .arg(std::process::id().to_string())
+        .arg("--notify-fd")
+        .arg(fds[1].to_string())
+        .arg("--fallback")
+        .spawn();
+    drop(notify);
+    match child {
+        Ok(child) => {
+            // pkttyagent closes the notify fd once registered (or on exit).
+            let _ = ready.read_to_end(&mut Vec::new());
+            Some(child)
+        }
+        Err(e) => {
+            eprintln!(
+                "warning: could not start pkttyagent ({e}); relying on the session's polkit agent"
+            );
+            None
+        }
+    }
+}
+
+/// A `pkttyagent` started by [`spawn_tty_agent`], stopped when dropped.
+struct TtyAgent(Option<std::process::Child>);
+
+impl TtyAgent {
+    /// Start an agent unless running as root, whom the daemon never checks
+    /// against polkit.
+    fn for_non_root() -> Self {
+        Self((!setup::is_root()).then(spawn_tty_agent).flatten())
+    }
+}
+
+impl Drop for TtyAgent {
+    fn drop(&mut self) {
+        if let Some(agent) = &mut self.0 {
+            let _ = agent.kill();
+            let _ = agent.wait();
+        }
+    }
+}
+
+/// Print an `ExposureReport` as a bar chart with a plain-language verdict.
+fn print_exposure_report(report: &serde_json::Value) {
+    let frames = report["frames"].as_u64().unwrap_or(0);
+    println!("Exposure over {frames} raw frame(s):");
+    if let Some(buckets) = report["histogram_pct"].as_array() {
+        for (i, pct) in buckets.iter().enumerate() {
+            let pct = pct.as_f64().unwrap_or(0.0);
+            let lo = i * 32;
+            println!(
+                "  {lo:>3}–{:<3} {:>5.1}% {}",
+                lo + 31,
+                pct,
+                "#".repeat((pct / 2.0).round() as usize)
+            );
+        }
+    }
+    println!(
+        "  mean brightness: {:.1}",
+        report["mean_brightness"].as_f64().unwrap_or(0.0)
+    );
+    println!(
+        "  underexposed:    {:.1}%",
+        report["underexposed_pct"].as_f64().unwrap_or(0.0)
+    );
+    println!(
+        "  overexposed:     {:.1}%",
+        report["overexposed_pct"].as_f64().unwrap_or(0.0)
+    );
+    let verdict = match report["verdict"].as_str() {
+        Some("ok") => "lighting looks fine",
+        Some("too_dark") => "too dark — check the IR emitter (`visage discover`) or add light",
+        Some("too_bright") => "too bright — move away from direct light or sunlight",
+        Some("backlit") => "backlit — a bright source behind you is darkening your face",
+        Some("no_data") => "no frames captured — check the camera device",
+        _ => "unknown",
+    };
+    println!("  verdict:         {verdict}");
+}
+
+/// Print a `CameraBenchmark` report, flagging a low frame rate or lost frames.
+fn print_camera_benchmark(report: &serde_json::Value) {
+    let number = |key: &str| report[key].as_f64().unwrap_or(0.0);
+    let count = |key: &str| report[key].as_u64().unwrap_or(0);
+    println!(
+        "Camera benchmark over {} of {} frame(s){}:",
+        count("frames"),
+        count("frames_requested"),
+        if report["emitter_active"].as_bool() == Some(true) {
+            ", emitter on"
+        } else {
+            ""
+        }
+    );
+    println!("  first frame:   {:.0} ms", number("first_frame_ms"));
+    println!("  frame rate:    {:.1} fps", number("fps"));
+    println!(
+        "  interval:      {:.1} ms mean, {:.1} ms jitter, {:.1} ms max",
+        number("mean_interval_ms"),
+        number("jitter_ms"),
+        number("max_interval_ms")
+    );
+    println!("  dark frames:   {}", count("dark_frames"));
+    println!("  dropped:       {}", count("dropped_frames"));
+    if count("frames") < count("frames_requested") {
+        println!("  note: the capture hit the verify timeout before every frame arrived");
+    }
+    if number("fps") < 15.0 {
+        println!(
+            "  note: below 15 fps — the camera may have negotiated a low frame rate; \
+             check `v4l2-ctl --list-formats-ext`"
+        );
+    }
+    if count("dropped_frames") > 0 {
+        println!("  note: frames were lost — try another USB port or a shorter cable");
+    }
+}
+
+/// Print `Status`'s per-model-version enrollment counts and upgrade estimate.
+fn print_model_versions(status: &serde_json::Value) {
+    let Some(versions) = status["models_by_version"].as_array() else {
+        println!("  by model:   not reported by this daemon");
+        return;
+    };
+    println!("  by model:");
+    if versions.is_empty() {
+        println!("    (no enrollments)");
+    }
+    for v in versions {
+        println!(
+            "    {:<16} {} template(s), {} user(s)",
+            v["model_version"].as_str().unwrap_or("?"),
+            v["templates"].as_u64().unwrap_or(0),
+            v["users"].as_u64().unwrap_or(0)
+        );
+    }
+    let upgrade = &status["model_upgrade"];
+    if let Some(target) = upgrade["target_version"].as_str() {
+        println!(
+            "  upgrade:    {} of {} user(s) would need re-enrollment if upgrading to {target}",
+            upgrade["users_to_reenroll"].as_u64().unwrap_or(0),
+            upgrade["users_enrolled"].as_u64().unwrap_or(0)
+        );
+    }
 }
 
 #[tokio::main]
@@ -118,12 +423,62 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Enroll { label, user } => {
+        Commands::Enroll {
+            label,
+            user,
+            admin,
+            poses,
+        } => {
             let user = user.unwrap_or_else(current_user);
-            let proxy = connect_proxy().await?;
-            println!("Enrolling face model '{label}' for user '{user}'...");
-            match proxy.enroll(&user, &label).await {
+            if let Some(poses) = poses {
+                let client = connect_client_with_grace(POLKIT_PROMPT_GRACE).await?;
+                let agent = TtyAgent::for_non_root();
+                let completed = guided::run(&client, &user, &label, &poses).await?;
+                drop(agent);
+                if !completed {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            let client = connect_client_with_grace(POLKIT_PROMPT_GRACE).await?;
+            let result = if admin {
+                client.require("enroll_other").await?;
+                let _agent = TtyAgent(spawn_tty_agent());
+                println!("Enrolling face model '{label}' for user '{user}' as administrator...");
+                client.enroll_other(&user, &label).await
+            } else {
+                let _agent = TtyAgent::for_non_root();
+                println!("Enrolling face model '{label}' for user '{user}'...");
+                let mut capturing = false;
+                client
+                    .enroll_with_progress(&user, &label, |progress| match progress {
+                        Progress::Capturing { .. } if !capturing => {
+                            capturing = true;
+                            println!("Capturing — look at the camera...");
+                        }
+                        Progress::Waiting { operation, .. } if !capturing => {
+                            println!("Waiting for the daemon to finish '{operation}'...");
+                        }
+                        _ => {}
+                    })
+                    .await
+            };
+            match result {
                 Ok(model_id) => println!("Enrolled successfully. Model ID: {model_id}"),
+                Err(e @ ClientError::Timeout(_)) => {
+                    eprintln!("Enrollment timed out: {e}");
+                    eprintln!("Face the camera in good lighting and try again.");
+                    std::process::exit(1);
+                }
+                Err(ClientError::DuplicateEnrollment(message)) => {
+                    eprintln!("Not enrolled: this {message}.");
+                    eprintln!(
+                        "Another template this close adds verify time without improving \
+                         recognition. Re-enroll in different conditions (lighting, glasses, \
+                         angle), or replace the existing one with `visage remove <id>` first."
+                    );
+                    std::process::exit(1);
+                }
                 Err(e) => {
                     eprintln!("Enrollment failed: {e}");
                     std::process::exit(1);
@@ -132,40 +487,93 @@ async fn main() -> Result<()> {
         }
         Commands::Verify { user } => {
             let user = user.unwrap_or_else(current_user);
-            let proxy = connect_proxy().await?;
+            let client = connect_client().await?;
             println!("Verifying face for user '{user}'...");
-            match proxy.verify(&user).await {
-                Ok(true) => {
+            match client.verify(&user).await {
+                Ok(VerifyOutcome::Matched) => {
                     println!("Match: verified");
                     // Exit 0 on match (shell-friendly)
                 }
-                Ok(false) => {
+                Ok(VerifyOutcome::NoMatch) => {
                     println!("No match");
                     std::process::exit(1);
                 }
+                Err(e @ ClientError::Timeout(_)) => {
+                    eprintln!("Verification timed out: {e}");
+                    eprintln!("Face the camera and try again.");
+                    std::process::exit(1);
+                }
                 Err(e) => {
                     eprintln!("Verification failed: {e}");
                     std::process::exit(1);
                 }
             }
         }
+        Commands::Identify => {
+            let client = connect_client().await?;
+            println!("Identifying face...");
+            match client.identify().await {
+                Ok(Some(user)) => println!("Identified: {user}"),
+                Ok(None) => {
+                    println!("No enrolled user matched");
+                    std::process::exit(1);
+                }
+                Err(e @ ClientError::Timeout(_)) => {
+                    eprintln!("Identification timed out: {e}");
+                    eprintln!("Face the camera and try again.");
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Identification failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::VerifyFrame { image, user } => {
+            let user = user.unwrap_or_else(current_user);
+            let png = std::fs::read(&image)
+                .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", image.display()))?;
+            let client = connect_client().await?;
+            match client.verify_frame(&user, &png).await {
+                Ok(report) => {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                Err(e) => {
+                    eprintln!("Frame verification failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
         Commands::List { user } => {
             let user = user.unwrap_or_else(current_user);
-            let proxy = connect_proxy().await?;
-            match proxy.list_models(&user).await {
-                Ok(json) => {
-                    let models: Vec<serde_json::Value> = serde_json::from_str(&json)?;
+            let client = connect_client().await?;
+            match client.list_models(&user).await {
+                Ok(models) => {
                     if models.is_empty() {
                         println!("No models enrolled for user '{user}'");
                     } else {
                         println!("Enrolled models for '{user}':");
                         for m in &models {
+                            let pose = if m.pose_label.is_empty() {
+                                String::new()
+                            } else {
+                                format!(", pose: {}", m.pose_label)
+                            };
+                            println!(
+                                "  {} — label: {}{pose}, quality: {:.3}, created: {}",
+                                m.id, m.label, m.quality_score, m.created_at,
+                            );
+                            if let Some(reason) = &m.excluded {
+                                println!("    invalid, excluded from verification: {reason}");
+                            }
+                            if let Some(original) = &m.duplicate_of {
+                                println!("    near-duplicate of {original}");
+                            }
+                        }
+                        let invalid = models.iter().filter(|m| m.excluded.is_some()).count();
+                        if invalid > 0 {
                             println!(
-                                "  {} — label: {}, quality: {:.3}, created: {}",
-                                m["id"].as_str().unwrap_or("?"),
-                                m["label"].as_str().unwrap_or("?"),
-                                m["quality_score"].as_f64().unwrap_or(0.0),
-                                m["created_at"].as_str().unwrap_or("?"),
+                                "{invalid} invalid model(s) excluded; remove them with `visage remove <id>`"
                             );
                         }
                     }
@@ -176,10 +584,22 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Remove { id, user } => {
+        Commands::Remove { id: None, user, .. } => {
             let user = user.unwrap_or_else(current_user);
-            let proxy = connect_proxy().await?;
-            match proxy.remove_model(&user, &id).await {
+            if let Err(e) = cmd_remove_all(&user).await {
+                eprintln!("Failed to remove models: {e}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Remove {
+            id: Some(id), user, ..
+        } => {
+            let user = user.unwrap_or_else(current_user);
+            let client = connect_client_with_grace(POLKIT_PROMPT_GRACE).await?;
+            let agent = TtyAgent::for_non_root();
+            let result = client.remove_model(&user, &id).await;
+            drop(agent);
+            match result {
                 Ok(true) => println!("Model {id} removed"),
                 Ok(false) => {
                     eprintln!("Model {id} not found (or not owned by user '{user}')");
@@ -191,17 +611,289 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Setup { model_dir } => {
-            setup::run(model_dir)?;
+        Commands::Rename { id, label, user } => {
+            let user = user.unwrap_or_else(current_user);
+            let client = connect_client().await?;
+            match client.set_model_label(&user, &id, &label).await {
+                Ok(true) => println!("Model {id} renamed to '{label}'"),
+                Ok(false) => {
+                    eprintln!("Model {id} not found (or not owned by user '{user}')");
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Failed to rename model: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Dedupe { user, threshold } => {
+            let user = user.unwrap_or_else(current_user);
+            let client = connect_client().await?;
+            match client.dedupe(&user, threshold).await {
+                Ok(0) => println!("No near-duplicate templates for '{user}'"),
+                Ok(removed) => {
+                    println!("Removed {removed} near-duplicate template(s) for '{user}'")
+                }
+                Err(e) => {
+                    eprintln!("Failed to dedupe: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Duplicates => {
+            let client = connect_client().await?;
+            match client.find_duplicate_enrollments().await {
+                Ok(pairs) => {
+                    if pairs.is_empty() {
+                        println!("No cross-user duplicate enrollments found");
+                    } else {
+                        println!("Possible duplicate enrollments:");
+                        for p in &pairs {
+                            println!(
+                                "  {}/{} ({}) ↔ {}/{} ({}) — distance: {}",
+                                p["user_a"].as_str().unwrap_or("?"),
+                                p["label_a"].as_str().unwrap_or("?"),
+                                p["model_a"].as_str().unwrap_or("?"),
+                                p["user_b"].as_str().unwrap_or("?"),
+                                p["label_b"].as_str().unwrap_or("?"),
+                                p["model_b"].as_str().unwrap_or("?"),
+                                p["distance"].as_u64().unwrap_or(0),
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to query duplicates: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Orphans { purge: false } => {
+            let client = connect_client().await?;
+            match client.find_orphans().await {
+                Ok(users) if users.is_empty() => println!("No orphaned enrollments found"),
+                Ok(users) => {
+                    println!("Enrolled users with no system account:");
+                    for user in &users {
+                        println!("  {user}");
+                    }
+                    println!("Remove them with `visage orphans --purge`");
+                }
+                Err(e) => {
+                    eprintln!("Failed to query orphans: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Orphans { purge: true } => {
+            let client = connect_client().await?;
+            match client.purge_orphans().await {
+                Ok(0) => println!("No orphaned enrollments found"),
+                Ok(removed) => println!("Removed {removed} orphaned template(s)"),
+                Err(e) => {
+                    eprintln!("Failed to purge orphans: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Backup { path } => {
+            if let Err(e) = cmd_backup(&path).await {
+                eprintln!("Backup failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Restore { path } => {
+            if let Err(e) = cmd_restore(&path).await {
+                eprintln!("Restore failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Exposure => {
+            let client = connect_client().await?;
+            match client.exposure_report().await {
+                Ok(report) => print_exposure_report(&report),
+                Err(e) => {
+                    eprintln!("Exposure report failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Benchmark { frames } => {
+            let client = connect_client().await?;
+            match client.camera_benchmark(frames).await {
+                Ok(report) => print_camera_benchmark(&report),
+                Err(e) => {
+                    eprintln!("Camera benchmark failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Engine { cancel: false } => {
+            let client = connect_client().await?;
+            match client.engine_queue_status().await {
+                Ok(status) => {
+                    println!(
+                        "Queued requests: {}",
+                        status["queued"].as_u64().unwrap_or(0)
+                    );
+                    let op = &status["in_flight"];
+                    if op.is_null() {
+                        println!("In flight:       none (idle)");
+                    } else {
+                        println!(
+                            "In flight:       {} — running {:.1}s, last progress {:.1}s ago{}",
+                            op["operation"].as_str().unwrap_or("?"),
+                            op["elapsed_ms"].as_u64().unwrap_or(0) as f64 / 1000.0,
+                            op["stalled_ms"].as_u64().unwrap_or(0) as f64 / 1000.0,
+                            if op["cancel_requested"].as_bool().unwrap_or(false) {
+                                " (cancel requested)"
+                            } else {
+                                ""
+                            },
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to query engine queue: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Engine { cancel: true } => {
+            let client = connect_client().await?;
+            match client.cancel_current().await {
+                Ok(true) => println!("In-flight operation cancelled"),
+                Ok(false) => println!("Engine is idle; nothing to cancel"),
+                Err(e) => {
+                    eprintln!("Failed to cancel: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::PinModel { version, user } => {
+            let user = user.unwrap_or_else(current_user);
+            let client = connect_client().await?;
+            let version = version.unwrap_or_default();
+            match client.set_model_pin(&user, &version).await {
+                Ok(()) if version.is_empty() => println!("Model pin cleared for '{user}'"),
+                Ok(()) => println!("'{user}' pinned to model '{version}'"),
+                Err(e) => {
+                    eprintln!("Failed to set model pin: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::LogLevel { directive } => {
+            let client = connect_client().await?;
+            match client.set_log_level(&directive).await {
+                Ok(()) => println!("visaged log filter set to '{directive}'"),
+                Err(e) => {
+                    eprintln!("Failed to set log level: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ReloadModels => {
+            let client = connect_client().await?;
+            match client.reload_models().await {
+                Ok(report) => {
+                    println!(
+                        "Models reloaded from {}",
+                        report["model_dir"].as_str().unwrap_or("?")
+                    );
+                    if report["recognizer_restored"] == true {
+                        println!("Recognizer restored: enroll and verify are available again");
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to reload models: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Policy { set } => {
+            let client = connect_client().await?;
+            let action = if set.is_some() { "set" } else { "read" };
+            let result = match set {
+                Some(update) => {
+                    let update: serde_json::Value = serde_json::from_str(&update)
+                        .map_err(|e| anyhow::anyhow!("--set is not valid JSON: {e}"))?;
+                    client.set_policy(&update).await
+                }
+                None => client.policy().await,
+            };
+            match result {
+                Ok(policy) => println!("{}", serde_json::to_string_pretty(&policy)?),
+                Err(e) => {
+                    eprintln!("Failed to {action} policy: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::DeviceSettings { set } => {
+            let client = connect_client().await?;
+            let action = if set.is_some() { "store" } else { "read" };
+            let result = match set {
+                Some(update) => {
+                    let update: serde_json::Value = serde_json::from_str(&update)
+                        .map_err(|e| anyhow::anyhow!("--set is not valid JSON: {e}"))?;
+                    client.set_device_settings(&update).await
+                }
+                None => client.device_settings().await,
+            };
+            match result {
+                Ok(settings) => println!("{}", serde_json::to_string_pretty(&settings)?),
+                Err(e) => {
+                    eprintln!("Failed to {action} camera settings: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Setup { model_dir, check } => {
+            setup::run(model_dir, check)?;
         }
         Commands::Discover => {
             cmd_discover();
         }
-        Commands::Status => {
-            let proxy = connect_proxy().await?;
-            match proxy.status().await {
-                Ok(json) => {
-                    let status: serde_json::Value = serde_json::from_str(&json)?;
+        Commands::Doctor => {
+            cmd_doctor().await;
+        }
+        Commands::Config {
+            action: ConfigAction::Validate,
+        } => {
+            let client = connect_client().await?;
+            let status = client.status().await?;
+            let Some(warnings) = status.get("config_warnings").and_then(|v| v.as_array()) else {
+                eprintln!("visaged does not report configuration warnings; upgrade the daemon");
+                std::process::exit(1);
+            };
+            if let Some(file) = status.get("config_file").and_then(|v| v.as_str()) {
+                println!("Config file: {file}");
+            }
+            if warnings.is_empty() {
+                println!("Configuration OK");
+            } else {
+                println!("Configuration warnings:");
+                for warning in warnings {
+                    println!("  {}", warning.as_str().unwrap_or("?"));
+                }
+                std::process::exit(1);
+            }
+        }
+        Commands::Introspect => {
+            let client = connect_client().await?;
+            match client.introspect().await {
+                Ok(xml) => print!("{xml}"),
+                Err(e) => {
+                    eprintln!("Introspection failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Status { models } => {
+            let client = connect_client().await?;
+            match client.status().await {
+                Ok(status) => {
                     println!("visaged status:");
                     println!(
                         "  version:    {}",
@@ -222,6 +914,24 @@ async fn main() -> Result<()> {
                         "  threshold:  {:.2}",
                         status["similarity_threshold"].as_f64().unwrap_or(0.0)
                     );
+                    if status["degraded"].as_bool() == Some(true) {
+                        println!(
+                            "  WARNING:    degraded — recognizer unavailable ({}); enroll/verify disabled",
+                            status["recognizer_error"].as_str().unwrap_or("unknown error")
+                        );
+                    }
+                    if status["insecure_config"].as_bool() == Some(true) {
+                        println!("  WARNING:    threshold below safe minimum (insecure override)");
+                    }
+                    if let Some(warnings) = status.get("config_warnings").and_then(|v| v.as_array())
+                    {
+                        if !warnings.is_empty() {
+                            println!(
+                                "  WARNING:    {} configuration warning(s) — run `visage config validate`",
+                                warnings.len()
+                            );
+                        }
+                    }
                     if let Some(v) = status.get("verify_timeout_secs").and_then(|v| v.as_u64()) {
                         println!("  timeout:    {v}s");
                     }
@@ -237,6 +947,13 @@ async fn main() -> Result<()> {
                     if let Some(v) = status.get("session_bus").and_then(|v| v.as_bool()) {
                         println!("  bus:        {}", if v { "session" } else { "system" });
                     }
+                    if let Some(caps) = status.get("capabilities").and_then(|v| v.as_array()) {
+                        let names: Vec<&str> = caps.iter().filter_map(|c| c.as_str()).collect();
+                        println!("  features:   {}", names.join(", "));
+                    }
+                    if models {
+                        print_model_versions(&status);
+                    }
                 }
                 Err(e) => {
                     eprintln!("visaged: not reachable — {e}");
@@ -245,14 +962,211 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Commands::ReportHardware { output, yes } => {
+            report_hardware(&output, yes).await?;
+        }
         Commands::Test { device, frames } => {
             run_camera_test(&device, frames)?;
+            print_last_verify_capture(&device).await;
         }
     }
 
     Ok(())
 }
 
+async fn report_hardware(output: &str, yes: bool) -> Result<()> {
+    use std::io::{BufRead, Write};
+
+    let client = connect_client().await?;
+    let status = client
+        .status()
+        .await
+        .map_err(|e| anyhow::anyhow!("visaged: not reachable — {e}"))?;
+    // Older daemons have no `Stats`; the report then carries no statistics.
+    let stats = match client.stats().await {
+        Ok(stats) => stats,
+        Err(ClientError::Unsupported(_)) => serde_json::json!({}),
+        Err(e) => anyhow::bail!("failed to read daemon stats: {e}"),
+    };
+    let camera = report::probe_camera(status["camera"].as_str().unwrap_or_default());
+    let report = report::build_report(&status, &stats, camera);
+    let text = serde_json::to_string_pretty(&report)?;
+
+    if !yes {
+        println!("{text}");
+        println!();
+        print!("Write this report to {output}? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !report::confirmed(&answer) {
+            println!("Aborted; nothing written.");
+            return Ok(());
+        }
+    }
+
+    std::fs::write(output, text + "\n")?;
+    println!("Report written to {output}");
+    Ok(())
+}
+
+async fn cmd_remove_all(user: &str) -> Result<()> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    if std::io::stdin().is_terminal() {
+        print!("Remove every face model of '{user}'? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !report::confirmed(&answer) {
+            println!("Aborted; nothing removed.");
+            return Ok(());
+        }
+    }
+    let client = connect_client_with_grace(POLKIT_PROMPT_GRACE).await?;
+    let agent = TtyAgent::for_non_root();
+    let result = client.remove_all_models(user).await;
+    drop(agent);
+    println!("Removed {} model(s) for '{user}'", result?);
+    Ok(())
+}
+
+async fn cmd_backup(path: &std::path::Path) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let client = connect_client().await?;
+    let passphrase = read_passphrase("Backup passphrase: ")?;
+    if std::io::IsTerminal::is_terminal(&std::io::stdin())
+        && read_passphrase("Repeat passphrase: ")? != passphrase
+    {
+        anyhow::bail!("passphrases do not match");
+    }
+    let bundle = client.backup(&passphrase).await?;
+    // The bundle is encrypted, but keep it as private as the database.
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(&bundle)?;
+    println!("Face database backed up to {}", path.display());
+    Ok(())
+}
+
+async fn cmd_restore(path: &std::path::Path) -> Result<()> {
+    let bundle = std::fs::read(path)?;
+    let client = connect_client().await?;
+    let passphrase = read_passphrase("Backup passphrase: ")?;
+    let templates = client.restore(&bundle, &passphrase).await?;
+    println!("Restored {templates} template(s) from {}", path.display());
+    Ok(())
+}
+
+/// Read a passphrase from the terminal without echoing it, or the first
+/// line of stdin when it is not a terminal (for scripts).
+fn read_passphrase(prompt: &str) -> Result<String> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    if !stdin.is_terminal() {
+        stdin.lock().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    eprint!("{prompt}");
+    std::io::stderr().flush()?;
+    // SAFETY: termios is plain data, filled in by tcgetattr before use.
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: fd 0 is a terminal (checked above) and `saved` is valid.
+    let echo_off = unsafe { libc::tcgetattr(0, &mut saved) } == 0 && {
+        let mut quiet = saved;
+        quiet.c_lflag &= !libc::ECHO;
+        // SAFETY: `quiet` is a copy of the terminal's own settings.
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &quiet) == 0 }
+    };
+    let read = stdin.lock().read_line(&mut line);
+    if echo_off {
+        // SAFETY: restores the settings read above.
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &saved) };
+    }
+    eprintln!();
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn cmd_doctor() {
+    let mut healthy = true;
+
+    println!("Daemon:");
+    match connect_client().await {
+        Ok(client) => match client.status().await {
+            Ok(status) => {
+                println!(
+                    "  visaged {} is running",
+                    status["version"].as_str().unwrap_or("?")
+                );
+                if status["degraded"].as_bool() == Some(true) {
+                    healthy = false;
+                    println!(
+                        "  degraded — recognizer unavailable ({}); run `sudo visage setup`",
+                        status["recognizer_error"]
+                            .as_str()
+                            .unwrap_or("unknown error")
+                    );
+                }
+            }
+            Err(e) => {
+                healthy = false;
+                println!("  visaged is not answering: {e}");
+            }
+        },
+        Err(e) => {
+            healthy = false;
+            println!("  {e}");
+        }
+    }
+
+    println!("PAM services using pam_visage ({}):", pam_config::PAM_DIR);
+    match pam_config::scan(std::path::Path::new(pam_config::PAM_DIR)) {
+        Ok(rules) if rules.is_empty() => {
+            healthy = false;
+            println!("  none — run `sudo pam-auth-update` and enable Visage");
+        }
+        Ok(rules) => {
+            for rule in &rules {
+                let via = rule
+                    .via
+                    .as_deref()
+                    .map(|file| format!(" (via {file})"))
+                    .unwrap_or_default();
+                println!(
+                    "  {:<14} {} {}{via}",
+                    rule.service, rule.module_type, rule.control
+                );
+            }
+            for service in pam_config::EXPECTED_SERVICES {
+                let installed = std::path::Path::new(pam_config::PAM_DIR)
+                    .join(service)
+                    .is_file();
+                if installed && !rules.iter().any(|r| r.service == *service) {
+                    println!("  {service:<14} not configured");
+                }
+            }
+        }
+        Err(e) => {
+            healthy = false;
+            println!("  cannot read {}: {e}", pam_config::PAM_DIR);
+        }
+    }
+
+    if !healthy {
+        std::process::exit(1);
+    }
+}
+
 fn cmd_discover() {
     use visage_hw::quirks::{get_driver, get_usb_ids, is_ipu6_camera, lookup_quirk};
 
@@ -338,6 +1252,8 @@ fn run_camera_test(device_path: &str, frame_count: usize) -> Result<()> {
         "  Format: {:?} {}x{}",
         camera.fourcc, camera.width, camera.height
     );
+    // This test never drives the IR emitter.
+    println!("  Capture: {}", camera.capture_context(false));
 
     // Prepare output directory
     let out_dir = std::path::PathBuf::from("/tmp/visage-test");
@@ -379,6 +1295,31 @@ fn run_camera_test(device_path: &str, frame_count: usize) -> Result<()> {
     Ok(())
 }
 
+/// Show which camera the daemon's most recent verify used, so a verify run on
+/// the wrong device (e.g. a docked RGB webcam) is obvious. Silent when the
+/// daemon is unreachable or has not verified anyone yet.
+async fn print_last_verify_capture(tested_device: &str) {
+    let Ok(client) = connect_client().await else {
+        return;
+    };
+    let Some(capture) = client.stats().await.ok().and_then(|stats| {
+        serde_json::from_value::<visage_hw::CaptureContext>(stats["last_verify_capture"].clone())
+            .ok()
+    }) else {
+        return;
+    };
+    println!("\nLast daemon verify: {capture}");
+    if capture.device != tested_device {
+        println!(
+            "  note: the daemon verified on {}, not {tested_device} — check VISAGE_CAMERA_DEVICE",
+            capture.device
+        );
+    }
+    if !capture.emitter_active {
+        println!("  note: the IR emitter was off for that verify");
+    }
+}
+
 /// Write a grayscale image as PGM (Portable Gray Map) — no extra deps needed.
 fn save_pgm(path: &std::path::Path, data: &[u8], width: u32, height: u32) -> Result<()> {
     use std::io::Write;
@@ -0,0 +1,279 @@
+//! Which PAM services use `pam_visage` (`visage doctor`).
+//!
+//! Read-only: the files under `/etc/pam.d` are parsed, never changed. A
+//! service counts when one of its own rules loads `pam_visage.so`, or when it
+//! pulls in a file that does (`@include common-auth` on Debian,
+//! `auth include system-auth` on Fedora), which is how `pam-auth-update`
+//! wires face auth into `sudo` and `login`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+pub const PAM_DIR: &str = "/etc/pam.d";
+
+/// Services users usually expect face auth on, checked by `visage doctor`
+/// when their files exist.
+pub const EXPECTED_SERVICES: &[&str] = &["login", "sudo", "polkit-1", "gdm-password"];
+
+/// A rule that runs `pam_visage` for a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisageRule {
+    pub service: String,
+    /// `auth`, `account`, … (a leading `-` dropped).
+    pub module_type: String,
+    /// `sufficient`, `required`, `optional`, `requisite`, or a bracketed
+    /// `[value=action …]` list as written.
+    pub control: String,
+    /// The included file the rule is in, e.g. `common-auth`; `None` when it
+    /// is in the service's own file.
+    pub via: Option<String>,
+}
+
+/// One rule of a PAM file, as far as it matters here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    /// `type control module [args…]`.
+    Module {
+        module_type: String,
+        control: String,
+        module: String,
+    },
+    /// `@include file` (every type), or `type include|substack file`.
+    Include {
+        module_type: Option<String>,
+        file: String,
+    },
+}
+
+/// Every rule in `dir` (normally [`PAM_DIR`]) that runs `pam_visage`, by
+/// service name.
+pub fn scan(dir: &Path) -> std::io::Result<Vec<VisageRule>> {
+    let mut files = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        // Unreadable files (e.g. a mode-600 file for a non-root caller) are
+        // skipped rather than failing the whole scan.
+        if let Ok(text) = std::fs::read_to_string(entry.path()) {
+            files.insert(name, parse_rules(&text));
+        }
+    }
+    Ok(find(&files))
+}
+
+/// The rules of `text`, a PAM service file. Comments and malformed lines
+/// are dropped; `\`-continued lines are joined.
+fn parse_rules(text: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut pending = String::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        if let Some(continued) = line.strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        if let Some(rule) = parse_rule(&pending) {
+            rules.push(rule);
+        }
+        pending.clear();
+    }
+    rules
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let mut tokens = line.split_whitespace();
+    let first = tokens.next()?;
+    if first == "@include" {
+        return Some(Rule::Include {
+            module_type: None,
+            file: tokens.next()?.to_string(),
+        });
+    }
+    let module_type = first.trim_start_matches('-').to_string();
+    let mut control = tokens.next()?.to_string();
+    if control.starts_with('[') {
+        while !control.ends_with(']') {
+            control.push(' ');
+            control.push_str(tokens.next()?);
+        }
+    }
+    let module = tokens.next()?.to_string();
+    Some(match control.as_str() {
+        "include" | "substack" => Rule::Include {
+            module_type: Some(module_type),
+            file: module,
+        },
+        _ => Rule::Module {
+            module_type,
+            control,
+            module,
+        },
+    })
+}
+
+/// The `pam_visage` rules each file in `files` runs, following includes.
+fn find(files: &BTreeMap<String, Vec<Rule>>) -> Vec<VisageRule> {
+    let mut found = Vec::new();
+    for service in files.keys() {
+        let mut seen = BTreeSet::new();
+        collect(files, service, service, None, None, &mut seen, &mut found);
+    }
+    found
+}
+
+/// Add the `pam_visage` rules of `file` (restricted to `module_type` when
+/// it was included for one type) to `found` as rules of `service`.
+fn collect(
+    files: &BTreeMap<String, Vec<Rule>>,
+    service: &str,
+    file: &str,
+    module_type: Option<&str>,
+    via: Option<&str>,
+    seen: &mut BTreeSet<String>,
+    found: &mut Vec<VisageRule>,
+) {
+    if !seen.insert(file.to_string()) {
+        return;
+    }
+    let Some(rules) = files.get(file) else {
+        return;
+    };
+    let wanted = |t: &str| module_type.is_none() || module_type == Some(t);
+    for rule in rules {
+        match rule {
+            Rule::Module {
+                module_type: t,
+                control,
+                module,
+            } if wanted(t) && is_pam_visage(module) => found.push(VisageRule {
+                service: service.to_string(),
+                module_type: t.clone(),
+                control: control.clone(),
+                via: via.map(str::to_string),
+            }),
+            Rule::Module { .. } => {}
+            Rule::Include {
+                module_type: t,
+                file: included,
+            } => {
+                let t = t.as_deref().or(module_type);
+                if t.is_none() || t.is_some_and(wanted) {
+                    let via = via.unwrap_or(included);
+                    collect(files, service, included, t, Some(via), seen, found);
+                }
+            }
+        }
+    }
+}
+
+fn is_pam_visage(module: &str) -> bool {
+    Path::new(module)
+        .file_name()
+        .is_some_and(|n| n == "pam_visage.so")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> BTreeMap<String, Vec<Rule>> {
+        entries
+            .iter()
+            .map(|(name, text)| (name.to_string(), parse_rules(text)))
+            .collect()
+    }
+
+    fn rule(service: &str, control: &str, via: Option<&str>) -> VisageRule {
+        VisageRule {
+            service: service.into(),
+            module_type: "auth".into(),
+            control: control.into(),
+            via: via.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn services_and_control_flags_are_extracted() {
+        let found = find(&files(&[
+            // Debian: pam-auth-update writes the rule into common-auth.
+            (
+                "common-auth",
+                "# here are the per-package modules (the \"Primary\" block)\n\
+                 auth\t[success=end default=ignore]\tpam_visage.so\n\
+                 auth\t[success=1 default=ignore]\tpam_unix.so nullok\n",
+            ),
+            (
+                "sudo",
+                "#%PAM-1.0\n@include common-auth\n@include common-account\n",
+            ),
+            (
+                "login",
+                "auth optional pam_faildelay.so\n@include common-auth\n",
+            ),
+            // Fedora style: a typed include, and a direct rule with a path.
+            (
+                "system-auth",
+                "auth sufficient /usr/lib64/security/pam_visage.so timeout=5\n",
+            ),
+            (
+                "polkit-1",
+                "auth include system-auth\naccount include system-auth\n",
+            ),
+            ("gdm-password", "-auth required pam_visage.so \\\n  debug\n"),
+            // Commented out, or only for another type.
+            (
+                "sshd",
+                "#auth sufficient pam_visage.so\n@include common-auth-ssh\n",
+            ),
+            ("su", "session include system-auth\n"),
+            ("passwd", "@include common-password\n"),
+        ]));
+        assert_eq!(
+            found,
+            [
+                rule("common-auth", "[success=end default=ignore]", None),
+                rule("gdm-password", "required", None),
+                rule("login", "[success=end default=ignore]", Some("common-auth")),
+                rule("polkit-1", "sufficient", Some("system-auth")),
+                rule("sudo", "[success=end default=ignore]", Some("common-auth")),
+                rule("system-auth", "sufficient", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn include_cycles_and_missing_files_are_harmless() {
+        let found = find(&files(&[
+            ("a", "@include b\n"),
+            (
+                "b",
+                "@include a\n@include missing\nauth optional pam_visage.so\n",
+            ),
+        ]));
+        assert_eq!(
+            found,
+            [
+                rule("a", "optional", Some("b")),
+                rule("b", "optional", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_reads_a_pam_directory() {
+        let dir = std::env::temp_dir().join(format!("visage-pam-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("subdir")).unwrap();
+        std::fs::write(dir.join("sudo"), "auth sufficient pam_visage.so\n").unwrap();
+        std::fs::write(dir.join("other"), "auth required pam_deny.so\n").unwrap();
+        let found = scan(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, [rule("sudo", "sufficient", None)]);
+    }
+}
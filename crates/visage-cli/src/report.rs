@@ -0,0 +1,197 @@
+//! Anonymized hardware compatibility reports (`visage report-hardware`).
+//!
+//! A report combines the camera's identity and advertised formats, the
+//! daemon's tuning knobs, and its aggregate capture statistics. Everything
+//! passes through [`redact`] before it is shown or written, so a key that
+//! could identify a person or machine never leaves the host even if a future
+//! daemon starts returning it.
+
+use serde_json::{json, Map, Value};
+
+/// Bumped whenever a field is renamed or removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Keys whose values are stripped from a report at any depth.
+const SENSITIVE_KEYS: &[&str] = &[
+    "user",
+    "username",
+    "label",
+    "id",
+    "model_id",
+    "model_label",
+    "embedding",
+    "crop_hash",
+    "similarity",
+    "db_path",
+    "model_dir",
+    "hostname",
+    "home",
+    "serial",
+    "bus",
+];
+
+/// Status fields copied into the report's `config` section.
+const CONFIG_KEYS: &[&str] = &[
+    "similarity_threshold",
+    "insecure_config",
+    "verify_timeout_secs",
+    "enroll_timeout_secs",
+    "frames_per_verify",
+    "frames_per_enroll",
+    "emitter_enabled",
+    "lazy_camera",
+];
+
+/// Identify the camera at `device_path` using sysfs and V4L2 queries only.
+///
+/// Does not open a capture stream, so it works while `visaged` holds the device.
+pub fn probe_camera(device_path: &str) -> Value {
+    use visage_hw::quirks::{get_driver, get_usb_ids, lookup_quirk};
+
+    let info = visage_hw::Camera::list_devices()
+        .into_iter()
+        .find(|d| d.path == device_path);
+    let usb_ids = get_usb_ids(device_path);
+    let quirk = usb_ids.and_then(|(vid, pid)| lookup_quirk(vid, pid));
+    let formats = visage_hw::Camera::list_formats(device_path).unwrap_or_default();
+
+    json!({
+        "name": info.as_ref().map(|d| d.name.clone()),
+        "driver": info
+            .as_ref()
+            .map(|d| d.driver.clone())
+            .or_else(|| get_driver(device_path)),
+        "usb_vendor_id": usb_ids.map(|(vid, _)| format!("{vid:04x}")),
+        "usb_product_id": usb_ids.map(|(_, pid)| format!("{pid:04x}")),
+        "formats": formats,
+        "emitter_quirk": quirk.map(|q| q.device.name.clone()),
+    })
+}
+
+/// Assemble a report from the daemon's `Status` and `Stats` replies and a
+/// camera description, then redact it.
+pub fn build_report(status: &Value, stats: &Value, camera: Value) -> Value {
+    let config: Map<String, Value> = CONFIG_KEYS
+        .iter()
+        .filter_map(|&k| status.get(k).map(|v| (k.to_string(), v.clone())))
+        .collect();
+
+    let mut report = json!({
+        "schema_version": SCHEMA_VERSION,
+        "cli_version": env!("CARGO_PKG_VERSION"),
+        "daemon_version": status.get("version").cloned().unwrap_or(Value::Null),
+        "camera": camera,
+        "config": config,
+        "stats": stats,
+    });
+    redact(&mut report);
+    report
+}
+
+/// Remove every [`SENSITIVE_KEYS`] entry from `value`, recursing into objects
+/// and arrays. Returns the number of entries removed.
+pub fn redact(value: &mut Value) -> usize {
+    match value {
+        Value::Object(map) => {
+            let before = map.len();
+            map.retain(|k, _| !SENSITIVE_KEYS.contains(&k.to_ascii_lowercase().as_str()));
+            let removed = before - map.len();
+            removed + map.values_mut().map(redact).sum::<usize>()
+        }
+        Value::Array(items) => items.iter_mut().map(redact).sum(),
+        _ => 0,
+    }
+}
+
+/// Whether an interactive answer to "write this report?" means yes.
+pub fn confirmed(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_status() -> Value {
+        json!({
+            "version": "0.3.0",
+            "camera": "/dev/video2",
+            "model_dir": "/home/alice/.local/share/visage/models",
+            "db_path": "/var/lib/visage/faces.db",
+            "models_enrolled": 3,
+            "similarity_threshold": 0.4,
+            "verify_timeout_secs": 10,
+            "frames_per_verify": 3,
+            "emitter_enabled": true,
+        })
+    }
+
+    fn sample_stats() -> Value {
+        json!({
+            "verify_count": 12,
+            "median_verify_latency_ms": 420,
+            "confidence_histogram": [0, 0, 0, 0, 0, 0, 1, 3, 6, 2],
+            "frames_captured": 36,
+            "dark_frames": 4,
+            "dark_frame_rate": 0.1,
+        })
+    }
+
+    #[test]
+    fn test_report_schema() {
+        let camera = json!({ "name": "Integrated IR Camera", "formats": ["GREY"] });
+        let report = build_report(&sample_status(), &sample_stats(), camera);
+
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(report["cli_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(report["daemon_version"], "0.3.0");
+        assert_eq!(report["camera"]["formats"][0], "GREY");
+        assert_eq!(report["config"]["similarity_threshold"], 0.4);
+        assert_eq!(report["config"]["frames_per_verify"], 3);
+        assert_eq!(report["stats"]["median_verify_latency_ms"], 420);
+
+        // Only allowlisted status fields are copied.
+        let config = report["config"].as_object().unwrap();
+        assert!(config.keys().all(|k| CONFIG_KEYS.contains(&k.as_str())));
+        assert!(!config.contains_key("models_enrolled"));
+    }
+
+    #[test]
+    fn test_report_never_contains_paths() {
+        let report = build_report(&sample_status(), &sample_stats(), json!({}));
+        let text = report.to_string();
+        assert!(!text.contains("alice"));
+        assert!(!text.contains("faces.db"));
+    }
+
+    #[test]
+    fn test_redact_nested_keys() {
+        let mut value = json!({
+            "stats": { "verify_count": 1, "user": "alice" },
+            "models": [
+                { "id": "abc", "label": "glasses", "quality_score": 0.9 },
+                { "Model_ID": "def", "quality_score": 0.8 },
+            ],
+            "camera": { "name": "IR", "bus": "usb-0000:00:14.0-5", "serial": "XYZ" },
+        });
+
+        assert_eq!(redact(&mut value), 6);
+        assert_eq!(
+            value,
+            json!({
+                "stats": { "verify_count": 1 },
+                "models": [{ "quality_score": 0.9 }, { "quality_score": 0.8 }],
+                "camera": { "name": "IR" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_confirmation_defaults_to_no() {
+        assert!(confirmed("y\n"));
+        assert!(confirmed(" YES "));
+        assert!(!confirmed(""));
+        assert!(!confirmed("n"));
+        assert!(!confirmed("sure"));
+    }
+}
@@ -1,11 +1,16 @@
 //! `visage setup` — downloads ONNX models required for face detection and recognition.
 
 use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use visage_models::{verify_file_sha256, ModelIntegrityError, MODELS};
+use visage_models::{
+    total_download_bytes, validate_model_file, verify_file_sha256, Manifest, ModelIntegrityError,
+    ModelStatus, MODELS,
+};
 
 // libc is a workspace dep (already used by pam-visage)
 extern crate libc;
@@ -26,98 +31,264 @@ fn default_model_dir() -> PathBuf {
     }
 }
 
-fn is_root() -> bool {
+pub fn is_root() -> bool {
     // SAFETY: geteuid is always safe to call.
     unsafe { libc::geteuid() == 0 }
 }
 
-/// Download a single model file with progress output.
-fn download_model(model: &visage_models::ModelFile, dest: &Path) -> Result<()> {
-    let tmp_path = dest.with_extension("onnx.part");
+/// `bytes` in whole MiB, labelled "MB" as in `ModelFile::size_display`.
+fn display_mb(bytes: u64) -> String {
+    format!("{} MB", bytes / (1 << 20))
+}
 
-    println!("  downloading {} ({})...", model.name, model.size_display);
+/// Fetches one URL into a local file. Behind a trait so the order in which
+/// `download_model` tries sources can be tested without a network.
+trait Fetch {
+    fn fetch(&self, url: &str, dest: &Path) -> Result<()>;
+}
 
-    let resp = ureq::get(model.url)
-        .call()
-        .with_context(|| format!("failed to download {}", model.url))?;
+/// Fetch over HTTP(S) with progress output.
+struct HttpFetch;
 
-    let content_length = resp
-        .headers()
-        .get("Content-Length")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.parse::<u64>().ok());
+impl Fetch for HttpFetch {
+    fn fetch(&self, url: &str, dest: &Path) -> Result<()> {
+        let resp = ureq::get(url)
+            .call()
+            .with_context(|| format!("failed to download {url}"))?;
 
-    let mut reader = resp.into_body().into_reader();
-    let mut file = fs::File::create(&tmp_path)
-        .with_context(|| format!("failed to create {}", tmp_path.display()))?;
+        let content_length = resp
+            .headers()
+            .get("Content-Length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
 
-    let mut buf = [0u8; 65536];
-    let mut total: u64 = 0;
-    let mut last_pct: u64 = 0;
+        let mut reader = resp.into_body().into_reader();
+        let mut file = fs::File::create(dest)
+            .with_context(|| format!("failed to create {}", dest.display()))?;
 
-    loop {
-        let n = reader.read(&mut buf)?;
-        if n == 0 {
-            break;
-        }
-        file.write_all(&buf[..n])?;
-        total += n as u64;
-
-        // Print progress every 10%
-        if let Some(len) = content_length {
-            let pct = (total * 100) / len;
-            if pct / 10 > last_pct / 10 {
-                print!("  {pct}%\r");
-                io::stdout().flush().ok();
-                last_pct = pct;
+        let mut buf = [0u8; 65536];
+        let mut total: u64 = 0;
+        let mut last_pct: u64 = 0;
+
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
             }
+            file.write_all(&buf[..n])?;
+            total += n as u64;
+
+            // Print progress every 10%
+            if let Some(len) = content_length {
+                let pct = (total * 100) / len;
+                if pct / 10 > last_pct / 10 {
+                    print!("  {pct}%\r");
+                    io::stdout().flush().ok();
+                    last_pct = pct;
+                }
+            }
+        }
+
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Times one source is fetched while it keeps serving a complete file with
+/// the wrong checksum, which a flaky proxy can do now and then.
+const CHECKSUM_ATTEMPTS: u32 = 3;
+
+/// Pause before the second attempt at a source; doubled for each one after.
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Checksum failures seen during one `visage setup` run, so a host that kept
+/// serving corrupt files for one model is not retried for the next.
+struct DownloadSession {
+    backoff: Duration,
+    /// Checksum mismatches per host.
+    mismatches: HashMap<String, u32>,
+}
+
+impl DownloadSession {
+    fn new() -> Self {
+        Self::with_backoff(RETRY_BACKOFF)
+    }
+
+    fn with_backoff(backoff: Duration) -> Self {
+        Self {
+            backoff,
+            mismatches: HashMap::new(),
+        }
+    }
+
+    /// Attempts left for `url`: one only once its host has used up
+    /// [`CHECKSUM_ATTEMPTS`] on an earlier model.
+    fn attempts_for(&self, url: &str) -> u32 {
+        match self.mismatches.get(host(url)) {
+            Some(&n) if n >= CHECKSUM_ATTEMPTS => 1,
+            _ => CHECKSUM_ATTEMPTS,
         }
     }
 
-    file.flush()?;
-    drop(file);
+    fn record_mismatch(&mut self, url: &str) {
+        *self.mismatches.entry(host(url).to_string()).or_default() += 1;
+    }
+}
+
+/// The `host[:port]` part of `url`, or all of it if it has none.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// Download a single model file, trying each of `sources` in order until one
+/// serves a file with the expected checksum. A source whose file fails the
+/// checksum is fetched again, with backoff, up to [`CHECKSUM_ATTEMPTS`] times.
+/// When no source ever served a good file, the error says so and suggests
+/// another source. No `.part` file is left behind on failure.
+fn download_model(
+    model: &visage_models::ModelFile,
+    dest: &Path,
+    sources: &[String],
+    fetcher: &dyn Fetch,
+    session: &mut DownloadSession,
+) -> Result<()> {
+    let tmp_path = dest.with_extension("onnx.part");
+
+    println!("  downloading {} ({})...", model.name, model.size_display);
+
+    let mut failures = Vec::new();
+    let mut only_mismatches = true;
+    let mut mismatches = 0;
+    for url in sources {
+        let attempts = session.attempts_for(url);
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                let delay = session.backoff * 2u32.pow(attempt - 2);
+                println!(
+                    "  retrying {url} in {}s ({attempt}/{attempts})",
+                    delay.as_secs()
+                );
+                std::thread::sleep(delay);
+            }
+            if let Err(err) = fetcher.fetch(url, &tmp_path) {
+                fs::remove_file(&tmp_path).ok();
+                println!("  {url}: {err:#}");
+                failures.push(format!("{url}: {err:#}"));
+                only_mismatches = false;
+                break;
+            }
+
+            // Verify checksum; whatever the source, a mismatch rejects its file
+            print!("  verifying checksum... ");
+            io::stdout().flush().ok();
+            if let Err(err) = verify_file_sha256(model.name, &tmp_path, model.sha256) {
+                fs::remove_file(&tmp_path).ok();
+                println!("failed");
+                println!("  {url}: {err}");
+                session.record_mismatch(url);
+                mismatches += 1;
+                if attempt == attempts {
+                    failures.push(format!("{url}: {err} ({})", attempt_count(attempts)));
+                }
+                continue;
+            }
+            println!("ok");
+
+            // Atomic rename
+            fs::rename(&tmp_path, dest).with_context(|| {
+                format!(
+                    "failed to rename {} -> {}",
+                    tmp_path.display(),
+                    dest.display()
+                )
+            })?;
 
-    // Verify checksum
-    print!("  verifying checksum... ");
-    io::stdout().flush().ok();
-    if let Err(err) = verify_file_sha256(model.name, &tmp_path, model.sha256) {
-        fs::remove_file(&tmp_path).ok();
-        bail!("{err}");
+            return Ok(());
+        }
     }
-    println!("ok");
 
-    // Atomic rename
-    fs::rename(&tmp_path, dest).with_context(|| {
-        format!(
-            "failed to rename {} -> {}",
-            tmp_path.display(),
-            dest.display()
+    if only_mismatches && !failures.is_empty() {
+        let retried = if mismatches > failures.len() {
+            format!(" on all {}", attempt_count(mismatches as u32))
+        } else {
+            String::new()
+        };
+        bail!(
+            "every source served {} with the wrong checksum{retried}:\n  {}\n\
+             The file arrives complete but altered, so a mirror, proxy or CDN may be serving a \
+             corrupt copy, or the download is being intercepted. Point VISAGE_MODEL_MIRROR at \
+             another source, or fetch the file on a trusted network and check it with \
+             `visage setup --check`.",
+            model.name,
+            failures.join("\n  ")
         )
-    })?;
+    }
+    bail!(
+        "could not download {} from any source:\n  {}",
+        model.name,
+        failures.join("\n  ")
+    )
+}
 
-    Ok(())
+/// "1 attempt", "3 attempts".
+fn attempt_count(n: u32) -> String {
+    if n == 1 {
+        "1 attempt".to_string()
+    } else {
+        format!("{n} attempts")
+    }
 }
 
-/// Run the setup command: download and verify ONNX models.
-pub fn run(model_dir: Option<String>) -> Result<()> {
+/// Run the setup command: download and verify ONNX models, or with `check`
+/// only report the state of each file.
+pub fn run(model_dir: Option<String>, check: bool) -> Result<()> {
     let dir = match model_dir {
         Some(d) => PathBuf::from(d),
         None => default_model_dir(),
     };
+    if check {
+        return check_models(&dir);
+    }
+    // The downloads carry the built-in checksums; a site that pins its own
+    // stages the files itself.
+    if let Some(source) = Manifest::from_env()?.source() {
+        bail!(
+            "models are pinned by {}; stage them in {} and run `visage setup --check`",
+            source.display(),
+            dir.display()
+        );
+    }
 
     println!("Model directory: {}", dir.display());
+    println!(
+        "Models: {} file(s), {} in total",
+        MODELS.len(),
+        display_mb(total_download_bytes())
+    );
+
+    // A site serving the model files by name, tried before the manifest URLs
+    let mirror = std::env::var("VISAGE_MODEL_MIRROR")
+        .ok()
+        .filter(|m| !m.is_empty());
+    if let Some(mirror) = &mirror {
+        println!("Mirror: {mirror}");
+    }
 
     fs::create_dir_all(&dir)
         .with_context(|| format!("failed to create directory {}", dir.display()))?;
 
     let mut downloaded = 0;
     let mut skipped = 0;
+    let mut session = DownloadSession::new();
 
     for model in MODELS {
         let dest = dir.join(model.name);
         if dest.exists() {
-            // Verify existing file
-            match verify_file_sha256(model.name, &dest, model.sha256) {
+            // Verify existing file; the structural check is cheaper than hashing
+            match validate_model_file(&dest)
+                .and_then(|()| verify_file_sha256(model.name, &dest, model.sha256))
+            {
                 Ok(()) => {
                     println!("  {} already present (checksum ok)", model.name);
                     skipped += 1;
@@ -132,20 +303,237 @@ pub fn run(model_dir: Option<String>) -> Result<()> {
                 Err(ModelIntegrityError::Open { .. } | ModelIntegrityError::Read { .. }) => {
                     println!("  {} exists but unreadable — re-downloading", model.name);
                 }
+                Err(
+                    ModelIntegrityError::Empty { .. }
+                    | ModelIntegrityError::NotOnnx { .. }
+                    | ModelIntegrityError::WrongSize { .. },
+                ) => {
+                    println!("  {} exists but is damaged — re-downloading", model.name);
+                }
                 Err(ModelIntegrityError::MissingModel { .. }) => {}
+                Err(
+                    e @ (ModelIntegrityError::ManifestRead { .. }
+                    | ModelIntegrityError::ManifestInvalid { .. }),
+                ) => return Err(e.into()),
             }
         }
 
-        download_model(model, &dest)?;
+        download_model(
+            model,
+            &dest,
+            &model.sources(mirror.as_deref()),
+            &HttpFetch,
+            &mut session,
+        )?;
         downloaded += 1;
     }
 
     println!();
     if downloaded > 0 {
         println!("Setup complete: {downloaded} model(s) downloaded, {skipped} already present.");
+        println!("If visaged is running, load them with `sudo visage reload-models`.");
     } else {
         println!("All models already present. Nothing to download.");
     }
 
     Ok(())
 }
+
+/// Report every model file's state against the checksums visaged uses
+/// (`VISAGE_MODEL_MANIFEST`, if set), failing if any needs downloading.
+fn check_models(dir: &Path) -> Result<()> {
+    let manifest = Manifest::from_env()?;
+    println!("Model directory: {}", dir.display());
+    if let Some(source) = manifest.source() {
+        println!("Checksums from: {}", source.display());
+    }
+    let mut broken = 0;
+    for (model, status) in manifest.audit(dir) {
+        if !matches!(status, ModelStatus::Ok) {
+            broken += 1;
+        }
+        println!("  {}: {status}", model.name);
+    }
+    if broken > 0 {
+        bail!("{broken} model file(s) need repair; run `sudo visage setup`");
+    }
+    println!("All models verified.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_labels_match_the_shared_manifest() {
+        for model in MODELS {
+            assert_eq!(
+                display_mb(model.size_bytes),
+                model.size_display,
+                "{}",
+                model.name
+            );
+        }
+        assert_eq!(display_mb(total_download_bytes()), "182 MB");
+    }
+
+    /// Serves fixed bodies per URL (`None` fails the fetch) and records the
+    /// URLs asked for.
+    struct StubFetch {
+        bodies: Vec<(&'static str, Option<&'static [u8]>)>,
+        asked: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl Fetch for StubFetch {
+        fn fetch(&self, url: &str, dest: &Path) -> Result<()> {
+            self.asked.borrow_mut().push(url.to_string());
+            match self.bodies.iter().find(|(u, _)| *u == url) {
+                Some((_, Some(body))) => Ok(fs::write(dest, body)?),
+                _ => bail!("connection refused"),
+            }
+        }
+    }
+
+    const STUB_MODEL: visage_models::ModelFile = visage_models::ModelFile {
+        name: "stub.onnx",
+        url: "https://primary.example/stub.onnx",
+        mirrors: &["https://a.example/stub.onnx", "https://b.example/stub.onnx"],
+        // sha256("good model")
+        sha256: "386babe658361883a51bad7442f3fcfbc1d46466d360253a72a000b3781adf64",
+        size_bytes: 10,
+        size_display: "0 MB",
+    };
+
+    fn scratch_dir(tag: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("visage-setup-test-{tag}-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn download_falls_through_failed_and_mismatched_sources() {
+        let dir = scratch_dir("fallthrough");
+        let dest = dir.join(STUB_MODEL.name);
+        let fetcher = StubFetch {
+            bodies: vec![
+                ("https://a.example/stub.onnx", Some(b"tampered")),
+                ("https://b.example/stub.onnx", Some(b"good model")),
+            ],
+            asked: Default::default(),
+        };
+
+        let mut session = DownloadSession::with_backoff(Duration::ZERO);
+        download_model(
+            &STUB_MODEL,
+            &dest,
+            &STUB_MODEL.sources(None),
+            &fetcher,
+            &mut session,
+        )
+        .unwrap();
+
+        assert_eq!(
+            *fetcher.asked.borrow(),
+            [
+                "https://primary.example/stub.onnx",
+                "https://a.example/stub.onnx",
+                "https://a.example/stub.onnx",
+                "https://a.example/stub.onnx",
+                "https://b.example/stub.onnx",
+            ]
+        );
+        assert_eq!(fs::read(&dest).unwrap(), b"good model");
+        assert!(!dest.with_extension("onnx.part").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn download_stops_at_the_first_good_source_and_fails_if_none_is() {
+        let dir = scratch_dir("first-good");
+        let dest = dir.join(STUB_MODEL.name);
+        let sources = STUB_MODEL.sources(Some("https://proxy.corp/visage"));
+
+        let fetcher = StubFetch {
+            bodies: vec![("https://proxy.corp/visage/stub.onnx", Some(b"good model"))],
+            asked: Default::default(),
+        };
+        let mut session = DownloadSession::with_backoff(Duration::ZERO);
+        download_model(&STUB_MODEL, &dest, &sources, &fetcher, &mut session).unwrap();
+        assert_eq!(
+            *fetcher.asked.borrow(),
+            ["https://proxy.corp/visage/stub.onnx"]
+        );
+        fs::remove_file(&dest).unwrap();
+
+        let fetcher = StubFetch {
+            bodies: vec![("https://primary.example/stub.onnx", Some(b"tampered"))],
+            asked: Default::default(),
+        };
+        let err = download_model(&STUB_MODEL, &dest, &sources, &fetcher, &mut session).unwrap_err();
+        // Only the primary served anything, and its file was retried.
+        assert_eq!(fetcher.asked.borrow().len(), 3 + CHECKSUM_ATTEMPTS as usize);
+        assert!(err.to_string().contains("could not download"), "{err}");
+        assert!(!dest.exists());
+        assert!(!dest.with_extension("onnx.part").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn persistent_checksum_mismatch_is_retried_a_bounded_number_of_times() {
+        let dir = scratch_dir("corrupt");
+        let dest = dir.join(STUB_MODEL.name);
+        let fetcher = StubFetch {
+            bodies: vec![
+                ("https://primary.example/stub.onnx", Some(b"tampered")),
+                ("https://a.example/stub.onnx", Some(b"tampered")),
+                ("https://b.example/stub.onnx", Some(b"tampered")),
+            ],
+            asked: Default::default(),
+        };
+        let mut session = DownloadSession::with_backoff(Duration::ZERO);
+
+        let sources = STUB_MODEL.sources(None);
+        let err = download_model(&STUB_MODEL, &dest, &sources, &fetcher, &mut session)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            fetcher.asked.borrow().len(),
+            sources.len() * CHECKSUM_ATTEMPTS as usize
+        );
+        assert!(
+            err.contains(&format!(
+                "wrong checksum on all {} attempts:",
+                sources.len() * CHECKSUM_ATTEMPTS as usize
+            )),
+            "{err}"
+        );
+        assert!(
+            err.contains(&format!("({CHECKSUM_ATTEMPTS} attempts)")),
+            "{err}"
+        );
+        assert!(err.contains("VISAGE_MODEL_MIRROR"), "{err}");
+        assert!(!dest.exists());
+        assert!(!dest.with_extension("onnx.part").exists());
+
+        // Later in the same run, hosts that kept failing get one attempt each,
+        // and the error does not claim a retry.
+        fetcher.asked.borrow_mut().clear();
+        let err = download_model(&STUB_MODEL, &dest, &sources, &fetcher, &mut session)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(fetcher.asked.borrow().len(), sources.len());
+        assert!(err.contains("wrong checksum:"), "{err}");
+        assert!(err.contains("(1 attempt)"), "{err}");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn host_is_the_authority_of_a_url() {
+        assert_eq!(host("https://a.example/models/x.onnx"), "a.example");
+        assert_eq!(host("http://proxy.corp:8080/x.onnx"), "proxy.corp:8080");
+        assert_eq!(host("file-without-scheme"), "file-without-scheme");
+    }
+}
@@ -0,0 +1,451 @@
+//! [`Client`] — typed wrappers around the `org.freedesktop.Visage1` proxy.
+
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_lite::StreamExt;
+
+use crate::error::{Error, Result};
+use crate::introspection;
+use crate::proxy::VisageProxy;
+use crate::types::{ModelInfo, Progress, VerificationEvent, VerifyOutcome, VerifyScore};
+
+/// How often [`Client::enroll_with_progress`] polls the engine queue.
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Which message bus to find visaged on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    System,
+    /// For development daemons started with `VISAGE_SESSION_BUS=1`.
+    Session,
+}
+
+impl Bus {
+    /// [`Bus::Session`] when `VISAGE_SESSION_BUS` is set to anything but
+    /// `0`, as the daemon does.
+    pub fn from_env() -> Self {
+        if std::env::var_os("VISAGE_SESSION_BUS").is_some_and(|v| v != "0") {
+            Self::Session
+        } else {
+            Self::System
+        }
+    }
+}
+
+/// Async client for visaged.
+///
+/// Methods backed by an optional daemon feature check `GetCapabilities` first
+/// and fail with [`Error::Unsupported`] on daemons that predate it.
+#[derive(Debug, Clone)]
+pub struct Client {
+    proxy: VisageProxy<'static>,
+}
+
+impl Client {
+    /// Connect to visaged on `bus`. `method_timeout` bounds every call; zbus
+    /// defaults to 25 s when `None`.
+    pub async fn connect(bus: Bus, method_timeout: Option<Duration>) -> Result<Self> {
+        let mut builder = match bus {
+            Bus::System => zbus::connection::Builder::system(),
+            Bus::Session => zbus::connection::Builder::session(),
+        }
+        .map_err(Error::DBus)?;
+        if let Some(timeout) = method_timeout {
+            builder = builder.method_timeout(timeout);
+        }
+        let conn = builder.build().await.map_err(Error::DBus)?;
+        Self::from_connection(&conn).await
+    }
+
+    /// Use an existing connection (e.g. one shared with other proxies).
+    pub async fn from_connection(conn: &zbus::Connection) -> Result<Self> {
+        let proxy = VisageProxy::new(conn).await.map_err(Error::DBus)?;
+        Ok(Self { proxy })
+    }
+
+    /// The underlying proxy, for calls without a typed wrapper.
+    pub fn proxy(&self) -> &VisageProxy<'static> {
+        &self.proxy
+    }
+
+    /// Optional features the daemon advertises. Daemons that predate
+    /// `GetCapabilities` advertise nothing.
+    pub async fn capabilities(&self) -> Vec<String> {
+        self.proxy.get_capabilities().await.unwrap_or_default()
+    }
+
+    /// Whether the daemon advertises `capability`.
+    pub async fn supports(&self, capability: &str) -> bool {
+        self.capabilities().await.iter().any(|c| c == capability)
+    }
+
+    /// Fail with [`Error::Unsupported`] unless the daemon advertises `capability`.
+    pub async fn require(&self, capability: &str) -> Result<()> {
+        if self.supports(capability).await {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(capability.to_string()))
+        }
+    }
+
+    /// Match `user`'s face against their enrolled models.
+    pub async fn verify(&self, user: &str) -> Result<VerifyOutcome> {
+        Ok(self.proxy.verify(user).await?.into())
+    }
+
+    /// Like [`Client::verify`], returning the daemon's JSON report (similarity,
+    /// threshold and, when enabled, timing and frame counts).
+    pub async fn verify_detailed(&self, user: &str) -> Result<serde_json::Value> {
+        self.require("verify_detailed").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.verify_detailed(user).await?,
+        )?)
+    }
+
+    /// Like [`Client::verify`], returning the similarity, the matched model
+    /// and why a face did not match.
+    pub async fn verify_score(&self, user: &str) -> Result<VerifyScore> {
+        self.require("verify_score").await?;
+        let (matched, similarity, model_id, model_label, failure_reason) =
+            self.proxy.verify_score(user).await?;
+        let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+        Ok(VerifyScore {
+            outcome: matched.into(),
+            similarity,
+            model_id: non_empty(model_id),
+            model_label: non_empty(model_label),
+            failure_reason: non_empty(failure_reason),
+        })
+    }
+
+    /// Like [`Client::verify`], also returning the one-time token the daemon
+    /// issues on a match (`None` when it issued none).
+    pub async fn verify_with_token(&self, user: &str) -> Result<(VerifyOutcome, Option<String>)> {
+        self.require("verify_tokens").await?;
+        let (matched, token) = self.proxy.verify_with_token(user).await?;
+        Ok((matched.into(), Some(token).filter(|t| !t.is_empty())))
+    }
+
+    /// Redeem a [`Client::verify_with_token`] token in place of a capture;
+    /// `true` once, from the logind session it was issued to.
+    pub async fn redeem_token(&self, user: &str, token: &str) -> Result<bool> {
+        self.require("verify_tokens").await?;
+        Ok(self.proxy.redeem_token(user, token).await?)
+    }
+
+    /// Whether `user` matched a verify from this caller within the last
+    /// `max_age_secs`, answered without the camera; `false` means run
+    /// [`Client::verify`].
+    pub async fn verify_cached(&self, user: &str, max_age_secs: u32) -> Result<bool> {
+        self.require("verify_grace").await?;
+        Ok(self.proxy.verify_cached(user, max_age_secs).await?)
+    }
+
+    /// [`Client::verify_cached`], falling back to a camera verify when
+    /// `user` has no match younger than `max_age_secs`.
+    pub async fn verify_fresh(&self, user: &str, max_age_secs: u32) -> Result<bool> {
+        self.require("verify_fresh").await?;
+        Ok(self.proxy.verify_fresh(user, max_age_secs).await?)
+    }
+
+    /// Stop the verifies and enrollments this client has waiting on the
+    /// daemon, which then fail with [`Error::Cancelled`]; `false` when there
+    /// were none.
+    pub async fn cancel(&self) -> Result<bool> {
+        self.require("verify_cancel").await?;
+        Ok(self.proxy.cancel().await?)
+    }
+
+    /// Stop every verify and enrollment the daemon has waiting for `user`,
+    /// from any client; returns how many. Non-root callers may only name
+    /// themselves.
+    pub async fn cancel_user(&self, user: &str) -> Result<u32> {
+        self.require("cancel_user").await?;
+        Ok(self.proxy.cancel_user(user).await?)
+    }
+
+    /// The enrolled user whose face is in front of the camera, matched
+    /// against every user's templates (root only); `None` when no one matched.
+    pub async fn identify(&self) -> Result<Option<String>> {
+        self.require("identify").await?;
+        let user = self.proxy.identify().await?;
+        Ok(Some(user).filter(|u| !u.is_empty()))
+    }
+
+    /// Match a PNG frame instead of the camera (root only).
+    pub async fn verify_frame(&self, user: &str, png: &[u8]) -> Result<serde_json::Value> {
+        use base64::Engine as _;
+
+        self.require("verify_frame").await?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(png);
+        Ok(serde_json::from_str(
+            &self.proxy.verify_frame(user, &encoded).await?,
+        )?)
+    }
+
+    /// Enroll a new face model; returns its ID.
+    pub async fn enroll(&self, user: &str, label: &str) -> Result<String> {
+        Ok(self.proxy.enroll(user, label).await?)
+    }
+
+    /// Enroll for another user as an administrator (polkit
+    /// `org.freedesktop.Visage1.enroll-other`). The caller should allow for
+    /// an authentication prompt in its method timeout.
+    pub async fn enroll_other(&self, user: &str, label: &str) -> Result<String> {
+        self.require("enroll_other").await?;
+        Ok(self.proxy.enroll_other(user, label).await?)
+    }
+
+    /// [`Client::enroll`], reporting the engine's progress to `progress`
+    /// while the call is pending. Daemons without `engine_queue_status`
+    /// report nothing.
+    pub async fn enroll_with_progress(
+        &self,
+        user: &str,
+        label: &str,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<String> {
+        let watch_queue = self.supports("engine_queue_status").await;
+        let enroll = async { Ok(self.proxy.enroll(user, label).await?) };
+        let watch = async {
+            if !watch_queue {
+                return std::future::pending().await;
+            }
+            loop {
+                async_io::Timer::after(PROGRESS_POLL_INTERVAL).await;
+                let Ok(json) = self.proxy.engine_queue_status().await else {
+                    continue;
+                };
+                let Ok(status) = serde_json::from_str(&json) else {
+                    continue;
+                };
+                if let Some(update) = Progress::from_queue_status(&status) {
+                    progress(update);
+                }
+            }
+        };
+        futures_lite::future::or(enroll, watch).await
+    }
+
+    /// `user`'s enrolled face models.
+    pub async fn list_models(&self, user: &str) -> Result<Vec<ModelInfo>> {
+        Ok(serde_json::from_str(&self.proxy.list_models(user).await?)?)
+    }
+
+    /// Remove one of `user`'s models; `false` if no such model exists.
+    pub async fn remove_model(&self, user: &str, model_id: &str) -> Result<bool> {
+        Ok(self.proxy.remove_model(user, model_id).await?)
+    }
+
+    /// Remove all of `user`'s models; returns how many went.
+    pub async fn remove_all_models(&self, user: &str) -> Result<u32> {
+        self.require("remove_all_models").await?;
+        Ok(self.proxy.remove_all_models(user).await?)
+    }
+
+    /// Rename one of `user`'s models; `false` if no such model exists.
+    pub async fn set_model_label(&self, user: &str, model_id: &str, label: &str) -> Result<bool> {
+        self.require("set_model_label").await?;
+        Ok(self.proxy.set_model_label(user, model_id, label).await?)
+    }
+
+    /// Remove `user`'s near-duplicate templates; returns how many went.
+    pub async fn dedupe(&self, user: &str, similarity_threshold: f64) -> Result<u64> {
+        self.require("dedupe").await?;
+        Ok(self.proxy.dedupe(user, similarity_threshold).await?)
+    }
+
+    /// Near-identical enrollments under different users (root only).
+    pub async fn find_duplicate_enrollments(&self) -> Result<Vec<serde_json::Value>> {
+        self.require("find_duplicate_enrollments").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.find_duplicate_enrollments().await?,
+        )?)
+    }
+
+    /// Enrolled users whose accounts no longer exist (root only).
+    pub async fn find_orphans(&self) -> Result<Vec<String>> {
+        self.require("orphans").await?;
+        Ok(serde_json::from_str(&self.proxy.find_orphans().await?)?)
+    }
+
+    /// Remove the enrollments of [`Client::find_orphans`]'s users; returns
+    /// how many templates went (root only).
+    pub async fn purge_orphans(&self) -> Result<u64> {
+        self.require("orphans").await?;
+        Ok(self.proxy.purge_orphans().await?)
+    }
+
+    /// The whole face database sealed with `passphrase`, for
+    /// [`Client::restore`] here or on another machine (root only).
+    pub async fn backup(&self, passphrase: &str) -> Result<Vec<u8>> {
+        self.require("backup").await?;
+        Ok(self.proxy.backup(passphrase).await?)
+    }
+
+    /// Replace the face database with a [`Client::backup`] bundle; returns
+    /// how many templates were restored (root only).
+    pub async fn restore(&self, bundle: &[u8], passphrase: &str) -> Result<u64> {
+        self.require("backup").await?;
+        Ok(self.proxy.restore(bundle, passphrase).await?)
+    }
+
+    /// Pin `user` to a recognizer model version; an empty version clears it
+    /// (root only).
+    pub async fn set_model_pin(&self, user: &str, model_version: &str) -> Result<()> {
+        self.require("model_pinning").await?;
+        Ok(self.proxy.set_model_pin(user, model_version).await?)
+    }
+
+    /// Replace the daemon's log filter (root only).
+    pub async fn set_log_level(&self, directive: &str) -> Result<()> {
+        self.require("set_log_level").await?;
+        Ok(self.proxy.set_log_level(directive).await?)
+    }
+
+    /// Re-verify the model files and load them into the running daemon;
+    /// returns what was reloaded (root only).
+    pub async fn reload_models(&self) -> Result<serde_json::Value> {
+        self.require("reload_models").await?;
+        Ok(serde_json::from_str(&self.proxy.reload_models().await?)?)
+    }
+
+    /// The active verification policy (root only).
+    pub async fn policy(&self) -> Result<serde_json::Value> {
+        self.require("verify_policy").await?;
+        Ok(serde_json::from_str(&self.proxy.get_policy().await?)?)
+    }
+
+    /// Change the fields of the verification policy named in `update`, a
+    /// JSON object; returns the new policy (root only).
+    pub async fn set_policy(&self, update: &serde_json::Value) -> Result<serde_json::Value> {
+        self.require("verify_policy").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.set_policy(&update.to_string()).await?,
+        )?)
+    }
+
+    /// Settings stored for the daemon's camera and the values in effect
+    /// (root only).
+    pub async fn device_settings(&self) -> Result<serde_json::Value> {
+        self.require("device_settings").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.get_device_settings().await?,
+        )?)
+    }
+
+    /// Store the camera settings named in `update`, a JSON object, for the
+    /// daemon's camera and apply them (root only).
+    pub async fn set_device_settings(
+        &self,
+        update: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.require("device_settings").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.set_device_settings(&update.to_string()).await?,
+        )?)
+    }
+
+    /// Brightness histogram of a few raw frames (root only).
+    pub async fn exposure_report(&self) -> Result<serde_json::Value> {
+        self.require("exposure_report").await?;
+        Ok(serde_json::from_str(&self.proxy.exposure_report().await?)?)
+    }
+
+    /// Frame rate, jitter and dropped frames over `frames` captured frames
+    /// (root only).
+    pub async fn camera_benchmark(&self, frames: u32) -> Result<serde_json::Value> {
+        self.require("camera_benchmark").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.camera_benchmark(frames).await?,
+        )?)
+    }
+
+    /// The engine's request queue and in-flight operation.
+    pub async fn engine_queue_status(&self) -> Result<serde_json::Value> {
+        self.require("engine_queue_status").await?;
+        Ok(serde_json::from_str(
+            &self.proxy.engine_queue_status().await?,
+        )?)
+    }
+
+    /// Abort the in-flight engine operation; `false` when idle (root only).
+    pub async fn cancel_current(&self) -> Result<bool> {
+        self.require("cancel_current").await?;
+        Ok(self.proxy.cancel_current().await?)
+    }
+
+    /// The daemon's `Status` JSON.
+    pub async fn status(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::from_str(&self.proxy.status().await?)?)
+    }
+
+    /// The daemon's aggregate capture statistics.
+    pub async fn stats(&self) -> Result<serde_json::Value> {
+        self.require("stats").await?;
+        Ok(serde_json::from_str(&self.proxy.stats().await?)?)
+    }
+
+    /// The daemon's `org.freedesktop.Visage1` introspection XML as a
+    /// standalone document (see [`crate::introspection`]).
+    pub async fn introspect(&self) -> Result<String> {
+        let inner = self.proxy.inner();
+        let introspectable = zbus::fdo::IntrospectableProxy::builder(inner.connection())
+            .destination(inner.destination().to_owned())?
+            .path(inner.path().to_owned())?
+            .build()
+            .await?;
+        let xml = introspectable
+            .introspect()
+            .await
+            .map_err(zbus::Error::from)?;
+        introspection::interface_document(&xml).ok_or_else(|| {
+            Error::Failed(format!(
+                "introspection data lacks {}",
+                introspection::INTERFACE
+            ))
+        })
+    }
+
+    /// Stream of `camera_degraded` values from `HealthChanged` signals.
+    pub async fn health_changes(&self) -> Result<impl Stream<Item = bool>> {
+        let signals = self.proxy.receive_health_changed().await?;
+        Ok(signals.filter_map(|signal| signal.args().ok().map(|args| args.camera_degraded)))
+    }
+
+    /// Stream of `VerificationStarted` and `VerificationCompleted` signals,
+    /// for every verify the daemon runs, in the order they were sent.
+    pub async fn verifications(&self) -> Result<impl Stream<Item = VerificationEvent>> {
+        let inner = self.proxy.inner();
+        let mut rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(introspection::INTERFACE)?
+            .path(inner.path().to_owned())?;
+        // Peer-to-peer messages carry no sender to match.
+        if inner.connection().unique_name().is_some() {
+            rule = rule.sender(inner.destination().to_owned())?;
+        }
+        let messages =
+            zbus::MessageStream::for_match_rule(rule.build(), inner.connection(), None).await?;
+        Ok(messages.filter_map(|message| {
+            let message = message.ok()?;
+            let header = message.header();
+            let body = message.body();
+            match header.member()?.as_str() {
+                "VerificationStarted" => Some(VerificationEvent::Started {
+                    user: body.deserialize().ok()?,
+                }),
+                "VerificationCompleted" => {
+                    let (user, matched, reason) = body.deserialize().ok()?;
+                    Some(VerificationEvent::Completed {
+                        user,
+                        matched,
+                        reason,
+                    })
+                }
+                _ => None,
+            }
+        }))
+    }
+}
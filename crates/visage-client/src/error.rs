@@ -0,0 +1,216 @@
+//! Errors returned by [`crate::Client`].
+//!
+//! Structured `org.freedesktop.Visage1.Error.<Name>` replies map to the
+//! variant of the same name, so callers match on variants instead of parsing
+//! message text. Each variant carries the daemon's message.
+
+const VISAGE_PREFIX: &str = "org.freedesktop.Visage1.Error.";
+
+/// How visaged starts a `RateLimited` message: `retry after <seconds>s: `.
+const RETRY_AFTER_PREFIX: &str = "retry after ";
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// visaged is not on the bus (not installed, not started, or crashed).
+    #[error("visaged is not running: {0}")]
+    NotRunning(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgs(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("recognizer unavailable: {0}")]
+    RecognizerUnavailable(String),
+    /// The watchdog replaced a stuck engine; the request may be retried.
+    #[error("engine restarted: {0}")]
+    EngineRestarted(String),
+    #[error("model mismatch: {0}")]
+    ModelMismatch(String),
+    /// Site policy forbids the operation right now; fall back silently.
+    #[error("not permitted now: {0}")]
+    PolicyDenied(String),
+    /// The user never enrolled; fall back silently.
+    #[error("not enrolled: {0}")]
+    NotEnrolled(String),
+    /// The daemon's face store could not be read; worth alerting on.
+    #[error("face store unavailable: {0}")]
+    StoreUnavailable(String),
+    /// An administrator cancelled the operation, in which case it may be
+    /// retried, or the client did with `cancel`.
+    #[error("cancelled: {0}")]
+    Cancelled(String),
+    /// This connection exceeded the daemon's per-client request limits; the
+    /// message says when to retry.
+    #[error("too many requests: {0}")]
+    TooManyRequests(String),
+    /// The daemon could not look the account up through NSS (an sssd or
+    /// LDAP outage); fall back rather than wait.
+    #[error("account lookup unavailable: {0}")]
+    DirectoryUnavailable(String),
+    /// The user is locked out after failed verifies, or enrolled too
+    /// recently. `retry_after` is the wait the daemon gave, if it gave one.
+    #[error("rate limited: {message}")]
+    RateLimited {
+        retry_after: Option<std::time::Duration>,
+        message: String,
+    },
+    #[error("no face detected: {0}")]
+    NoFaceDetected(String),
+    #[error("camera unavailable: {0}")]
+    CameraUnavailable(String),
+    /// Enrollment did not pass the liveness check.
+    #[error("liveness check failed: {0}")]
+    LivenessFailed(String),
+    /// The enrollment nearly repeats one of the user's templates, which the
+    /// message names; nothing was stored.
+    #[error("duplicate enrollment: {0}")]
+    DuplicateEnrollment(String),
+    /// The verify saw different faces across its frames and was aborted.
+    #[error("face changed during capture: {0}")]
+    FaceChangedDuringCapture(String),
+    /// The running daemon does not advertise a capability the call needs.
+    #[error("the running visaged does not support '{0}' — upgrade the daemon")]
+    Unsupported(String),
+    /// The daemon replied with JSON this client cannot decode.
+    #[error("unexpected reply from visaged: {0}")]
+    Decode(#[from] serde_json::Error),
+    /// Any other D-Bus failure (connection, unknown error name, reply timeout).
+    #[error("D-Bus error: {0}")]
+    DBus(zbus::Error),
+}
+
+impl From<zbus::Error> for Error {
+    fn from(e: zbus::Error) -> Self {
+        let zbus::Error::MethodError(name, message, _) = &e else {
+            return Self::DBus(e);
+        };
+        let message = message.clone().unwrap_or_default();
+        let name = name.as_str();
+        match name.strip_prefix(VISAGE_PREFIX) {
+            Some("Failed") => Self::Failed(message),
+            Some("AccessDenied") => Self::AccessDenied(message),
+            Some("InvalidArgs") => Self::InvalidArgs(message),
+            Some("Timeout") => Self::Timeout(message),
+            Some("RecognizerUnavailable") => Self::RecognizerUnavailable(message),
+            Some("EngineRestarted") => Self::EngineRestarted(message),
+            Some("ModelMismatch") => Self::ModelMismatch(message),
+            Some("PolicyDenied") => Self::PolicyDenied(message),
+            Some("NotEnrolled") => Self::NotEnrolled(message),
+            Some("StoreUnavailable") => Self::StoreUnavailable(message),
+            Some("Cancelled") => Self::Cancelled(message),
+            Some("TooManyRequests") => Self::TooManyRequests(message),
+            Some("DirectoryUnavailable") => Self::DirectoryUnavailable(message),
+            Some("RateLimited") => Self::RateLimited {
+                retry_after: retry_after(&message),
+                message,
+            },
+            Some("NoFaceDetected") => Self::NoFaceDetected(message),
+            Some("CameraUnavailable") => Self::CameraUnavailable(message),
+            Some("LivenessFailed") => Self::LivenessFailed(message),
+            Some("DuplicateEnrollment") => Self::DuplicateEnrollment(message),
+            Some("FaceChangedDuringCapture") => Self::FaceChangedDuringCapture(message),
+            // Older handlers still return the generic fdo errors.
+            _ => match name {
+                "org.freedesktop.DBus.Error.Failed" => Self::Failed(message),
+                "org.freedesktop.DBus.Error.AccessDenied" => Self::AccessDenied(message),
+                "org.freedesktop.DBus.Error.InvalidArgs" => Self::InvalidArgs(message),
+                "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner" => Self::NotRunning(message),
+                _ => Self::DBus(e),
+            },
+        }
+    }
+}
+
+/// The wait a `RateLimited` message leads with.
+fn retry_after(message: &str) -> Option<std::time::Duration> {
+    let (secs, _) = message.strip_prefix(RETRY_AFTER_PREFIX)?.split_once("s:")?;
+    secs.parse().ok().map(std::time::Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn method_error(name: &str, msg: &str) -> zbus::Error {
+        let reply = zbus::message::Message::method_call("/org/freedesktop/Visage1", "Verify")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        zbus::Error::MethodError(
+            zbus::names::OwnedErrorName::try_from(name).unwrap(),
+            Some(msg.to_string()),
+            reply,
+        )
+    }
+
+    #[test]
+    fn test_structured_names_map_to_variants() {
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.NotEnrolled",
+            "no enrolled models for user 'alice'",
+        ));
+        assert!(
+            matches!(&e, Error::NotEnrolled(m) if m.contains("alice")),
+            "{e:?}"
+        );
+        let e = Error::from(method_error("org.freedesktop.Visage1.Error.Timeout", "10s"));
+        assert!(matches!(e, Error::Timeout(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.TooManyRequests",
+            "retry in 20 ms",
+        ));
+        assert!(matches!(e, Error::TooManyRequests(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.DirectoryUnavailable",
+            "cannot look up account 'alice'",
+        ));
+        assert!(matches!(e, Error::DirectoryUnavailable(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.RateLimited",
+            "retry after 287s: too many failed attempts",
+        ));
+        assert!(
+            matches!(&e, Error::RateLimited { retry_after: Some(d), .. } if d.as_secs() == 287),
+            "{e:?}"
+        );
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.RateLimited",
+            "slow down",
+        ));
+        assert!(matches!(
+            e,
+            Error::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.CameraUnavailable",
+            "device busy",
+        ));
+        assert!(matches!(e, Error::CameraUnavailable(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.Visage1.Error.DuplicateEnrollment",
+            "face matches existing model 'desk'",
+        ));
+        assert!(matches!(e, Error::DuplicateEnrollment(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.DBus.Error.AccessDenied",
+            "root",
+        ));
+        assert!(matches!(e, Error::AccessDenied(_)));
+        let e = Error::from(method_error(
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+            "gone",
+        ));
+        assert!(matches!(e, Error::NotRunning(_)));
+        let e = Error::from(method_error("org.example.Other", "?"));
+        assert!(matches!(e, Error::DBus(_)));
+    }
+}
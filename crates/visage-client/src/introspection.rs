@@ -0,0 +1,68 @@
+//! The `org.freedesktop.Visage1` interface contract as introspection XML.
+//!
+//! [`interface_document`] cuts the interface out of the object's full
+//! introspection (which also lists the standard `org.freedesktop.DBus.*`
+//! interfaces) into a standalone document. The result is byte-for-byte the
+//! format of `packaging/dbus/org.freedesktop.Visage1.xml`, the golden copy
+//! the daemon's tests compare against and packages install under
+//! `/usr/share/dbus-1/interfaces/`.
+
+/// The interface clients generate code against.
+pub const INTERFACE: &str = "org.freedesktop.Visage1";
+
+const HEADER: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+"#;
+
+/// Wrap an already-extracted `<interface>` block in a node document.
+pub fn wrap_interface(interface_xml: &str) -> String {
+    format!("{HEADER}{interface_xml}</node>\n")
+}
+
+/// Extract [`INTERFACE`] from `node_xml`, as returned by
+/// `org.freedesktop.DBus.Introspectable.Introspect`; `None` if it is absent.
+pub fn interface_document(node_xml: &str) -> Option<String> {
+    let open = format!(r#"<interface name="{INTERFACE}">"#);
+    let mut lines = node_xml.lines().skip_while(|line| line.trim() != open);
+    let mut block = String::new();
+    for line in lines.by_ref() {
+        block.push_str(line);
+        block.push('\n');
+        if line.trim() == "</interface>" {
+            return Some(wrap_interface(&block));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_document_keeps_only_visage1() {
+        let node = r#"
+<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.freedesktop.DBus.Peer">
+    <method name="Ping">
+    </method>
+  </interface>
+  <interface name="org.freedesktop.Visage1">
+    <method name="Status">
+      <arg type="s" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+        let doc = interface_document(node).unwrap();
+        assert!(doc.starts_with("<!DOCTYPE node"));
+        assert!(doc.contains(r#"<method name="Status">"#));
+        assert!(!doc.contains("Peer"));
+        assert!(doc.ends_with("  </interface>\n</node>\n"));
+
+        assert_eq!(interface_document("<node>\n</node>\n"), None);
+    }
+}
@@ -0,0 +1,17 @@
+//! visage-client — Async client library for the `visaged` D-Bus service.
+//!
+//! Wraps the `org.freedesktop.Visage1` proxy with typed results and errors so
+//! desktop integrations (shell extensions, applets, the `visage` CLI) do not
+//! reimplement the proxy and JSON parsing. The raw proxy stays available as
+//! [`VisageProxy`] for calls without a wrapper.
+
+pub mod client;
+pub mod error;
+pub mod introspection;
+pub mod proxy;
+pub mod types;
+
+pub use client::{Bus, Client};
+pub use error::{Error, Result};
+pub use proxy::VisageProxy;
+pub use types::{ModelInfo, Progress, VerificationEvent, VerifyOutcome, VerifyScore};
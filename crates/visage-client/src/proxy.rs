@@ -0,0 +1,74 @@
+//! The `org.freedesktop.Visage1` D-Bus proxy.
+//!
+//! Methods that return JSON strings are decoded by [`crate::Client`]; see
+//! `docs/architecture.md` for each method's access rules.
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Visage1",
+    default_service = "org.freedesktop.Visage1",
+    default_path = "/org/freedesktop/Visage1"
+)]
+pub trait Visage {
+    async fn enroll(&self, user: &str, label: &str) -> zbus::Result<String>;
+    async fn enroll_other(&self, user: &str, label: &str) -> zbus::Result<String>;
+    async fn preview(&self, user: &str) -> zbus::Result<(String, u32, u32, Vec<u8>)>;
+    async fn enroll_now(
+        &self,
+        user: &str,
+        label: &str,
+        preview_token: &str,
+    ) -> zbus::Result<String>;
+    async fn verify(&self, user: &str) -> zbus::Result<bool>;
+    async fn verify_detailed(&self, user: &str) -> zbus::Result<String>;
+    async fn verify_score(&self, user: &str) -> zbus::Result<(bool, f64, String, String, String)>;
+    async fn verify_with_token(&self, user: &str) -> zbus::Result<(bool, String)>;
+    async fn redeem_token(&self, user: &str, token: &str) -> zbus::Result<bool>;
+    async fn verify_cached(&self, user: &str, max_age_secs: u32) -> zbus::Result<bool>;
+    async fn verify_fresh(&self, user: &str, max_age_secs: u32) -> zbus::Result<bool>;
+    async fn cancel(&self) -> zbus::Result<bool>;
+    async fn cancel_user(&self, user: &str) -> zbus::Result<u32>;
+    async fn identify(&self) -> zbus::Result<String>;
+    async fn status(&self) -> zbus::Result<String>;
+    async fn stats(&self) -> zbus::Result<String>;
+    async fn get_capabilities(&self) -> zbus::Result<Vec<String>>;
+    async fn list_models(&self, user: &str) -> zbus::Result<String>;
+    async fn remove_model(&self, user: &str, model_id: &str) -> zbus::Result<bool>;
+    async fn remove_all_models(&self, user: &str) -> zbus::Result<u32>;
+    async fn set_model_label(
+        &self,
+        user: &str,
+        model_id: &str,
+        new_label: &str,
+    ) -> zbus::Result<bool>;
+    async fn dedupe(&self, user: &str, similarity_threshold: f64) -> zbus::Result<u64>;
+    async fn find_duplicate_enrollments(&self) -> zbus::Result<String>;
+    async fn find_orphans(&self) -> zbus::Result<String>;
+    async fn purge_orphans(&self) -> zbus::Result<u64>;
+    async fn backup(&self, passphrase: &str) -> zbus::Result<Vec<u8>>;
+    async fn restore(&self, bundle: &[u8], passphrase: &str) -> zbus::Result<u64>;
+    async fn verify_frame(&self, user: &str, frame_png_base64: &str) -> zbus::Result<String>;
+    async fn set_log_level(&self, directive: &str) -> zbus::Result<()>;
+    async fn reload_models(&self) -> zbus::Result<String>;
+    async fn get_policy(&self) -> zbus::Result<String>;
+    async fn set_policy(&self, policy_json: &str) -> zbus::Result<String>;
+    async fn get_device_settings(&self) -> zbus::Result<String>;
+    async fn set_device_settings(&self, settings_json: &str) -> zbus::Result<String>;
+    async fn set_model_pin(&self, user: &str, model_version: &str) -> zbus::Result<()>;
+    async fn exposure_report(&self) -> zbus::Result<String>;
+    async fn camera_benchmark(&self, frames: u32) -> zbus::Result<String>;
+    async fn engine_queue_status(&self) -> zbus::Result<String>;
+    async fn cancel_current(&self) -> zbus::Result<bool>;
+
+    /// A verify for `user` passed the rate limit and is using the camera.
+    #[zbus(signal)]
+    fn verification_started(&self, user: &str) -> zbus::Result<()>;
+
+    /// The verify announced by `VerificationStarted` ended; `reason` is
+    /// empty on a match, else `no-face`, `no-match`, `timeout` or `error`.
+    #[zbus(signal)]
+    fn verification_completed(&self, user: &str, matched: bool, reason: &str) -> zbus::Result<()>;
+
+    /// Scheduled self-tests flagged or cleared `camera_degraded`.
+    #[zbus(signal)]
+    fn health_changed(&self, camera_degraded: bool) -> zbus::Result<()>;
+}
@@ -0,0 +1,141 @@
+//! Typed replies decoded from the daemon's JSON.
+
+use std::time::Duration;
+
+/// Result of a `Verify` call that reached the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    Matched,
+    NoMatch,
+}
+
+impl VerifyOutcome {
+    pub fn matched(self) -> bool {
+        self == Self::Matched
+    }
+}
+
+impl From<bool> for VerifyOutcome {
+    fn from(matched: bool) -> Self {
+        if matched {
+            Self::Matched
+        } else {
+            Self::NoMatch
+        }
+    }
+}
+
+/// The match a `VerifyScore` call reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyScore {
+    pub outcome: VerifyOutcome,
+    /// Cosine similarity of the best-scoring template.
+    pub similarity: f64,
+    /// The matched model; `None` without a match.
+    pub model_id: Option<String>,
+    pub model_label: Option<String>,
+    /// Why the face did not match (`below_threshold`, `liveness`,
+    /// `pose_mismatch` or `foreign_device`); `None` on a match.
+    pub failure_reason: Option<String>,
+}
+
+/// A `VerificationStarted` or `VerificationCompleted` signal, for showing
+/// that face verification is running (see [`crate::Client::verifications`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationEvent {
+    Started {
+        user: String,
+    },
+    /// `reason` is empty on a match, else `no-face`, `no-match`, `timeout`
+    /// or `error`.
+    Completed {
+        user: String,
+        matched: bool,
+        reason: String,
+    },
+}
+
+/// One enrolled face model, as listed by `ListModels`.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub label: String,
+    /// Recognizer model version the template was computed with.
+    #[serde(default)]
+    pub model_version: String,
+    pub quality_score: f64,
+    /// Head pose the template was enrolled in (`frontal`, `left`, `right`);
+    /// empty from daemons that do not report it.
+    #[serde(default)]
+    pub pose_label: String,
+    pub created_at: String,
+    /// Why verification ignores this model (e.g. `near-constant values`);
+    /// `None` for usable models.
+    #[serde(default)]
+    pub excluded: Option<String>,
+    /// ID of the template this one was stored as a near-duplicate of.
+    #[serde(default)]
+    pub duplicate_of: Option<String>,
+}
+
+/// Progress of an enrollment, read from the daemon's engine queue while the
+/// `Enroll` call is pending (see [`crate::Client::enroll_with_progress`]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Progress {
+    /// The engine is busy with another operation; `queued` requests
+    /// (including ours) wait behind it.
+    Waiting { operation: String, queued: u64 },
+    /// The engine is capturing enrollment frames.
+    Capturing { elapsed: Duration },
+}
+
+impl Progress {
+    /// Decode an `EngineQueueStatus` reply. `None` when the engine is idle.
+    pub(crate) fn from_queue_status(status: &serde_json::Value) -> Option<Self> {
+        let op = status.get("in_flight").filter(|op| !op.is_null())?;
+        let operation = op["operation"].as_str().unwrap_or_default();
+        if operation == "enroll" {
+            return Some(Self::Capturing {
+                elapsed: Duration::from_millis(op["elapsed_ms"].as_u64().unwrap_or(0)),
+            });
+        }
+        Some(Self::Waiting {
+            operation: operation.to_string(),
+            queued: status["queued"].as_u64().unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_progress_from_queue_status() {
+        assert_eq!(
+            Progress::from_queue_status(&json!({"queued": 0, "in_flight": null})),
+            None
+        );
+        assert_eq!(
+            Progress::from_queue_status(&json!({
+                "queued": 0,
+                "in_flight": {"operation": "enroll", "elapsed_ms": 1500, "stalled_ms": 10,
+                              "cancel_requested": false}
+            })),
+            Some(Progress::Capturing {
+                elapsed: Duration::from_millis(1500)
+            })
+        );
+        assert_eq!(
+            Progress::from_queue_status(&json!({
+                "queued": 1,
+                "in_flight": {"operation": "verify", "elapsed_ms": 20}
+            })),
+            Some(Progress::Waiting {
+                operation: "verify".into(),
+                queued: 1
+            })
+        );
+    }
+}
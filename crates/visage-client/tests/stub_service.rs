@@ -0,0 +1,208 @@
+//! Runs [`visage_client::Client`] against a stub `org.freedesktop.Visage1`
+//! served over a peer-to-peer socket, so no bus or daemon is needed.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_lite::StreamExt;
+use visage_client::{Client, Error, Progress, VerificationEvent, VerifyOutcome};
+use zbus::object_server::SignalEmitter;
+
+const PATH: &str = "/org/freedesktop/Visage1";
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.freedesktop.Visage1.Error")]
+enum StubError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotEnrolled(String),
+}
+
+/// Stand-in for visaged with canned replies.
+struct StubVisage {
+    /// Whether an `Enroll` is in progress, as `EngineQueueStatus` reports it.
+    enrolling: Arc<Mutex<bool>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.Visage1")]
+impl StubVisage {
+    async fn verify(&self, user: &str) -> Result<bool, StubError> {
+        match user {
+            "alice" => Ok(true),
+            "bob" => Ok(false),
+            _ => Err(StubError::NotEnrolled(format!(
+                "no enrolled models for user '{user}'"
+            ))),
+        }
+    }
+
+    async fn enroll(&self, _user: &str, label: &str) -> String {
+        *self.enrolling.lock().unwrap() = true;
+        async_io::Timer::after(Duration::from_millis(800)).await;
+        *self.enrolling.lock().unwrap() = false;
+        format!("model-{label}")
+    }
+
+    async fn engine_queue_status(&self) -> String {
+        if *self.enrolling.lock().unwrap() {
+            r#"{"queued":0,"in_flight":{"operation":"enroll","elapsed_ms":400,"stalled_ms":0,"cancel_requested":false}}"#
+                .to_string()
+        } else {
+            r#"{"queued":0,"in_flight":null}"#.to_string()
+        }
+    }
+
+    async fn list_models(&self, user: &str) -> String {
+        format!(
+            r#"[{{"id":"m1","label":"{user}-normal","model_version":"w600k_r50","quality_score":0.91,"created_at":"2026-03-01T10:00:00Z"}}]"#
+        )
+    }
+
+    async fn get_capabilities(&self) -> Vec<String> {
+        vec!["engine_queue_status".to_string()]
+    }
+
+    #[zbus(signal)]
+    async fn health_changed(emitter: &SignalEmitter<'_>, camera_degraded: bool)
+        -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn verification_started(emitter: &SignalEmitter<'_>, user: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn verification_completed(
+        emitter: &SignalEmitter<'_>,
+        user: &str,
+        matched: bool,
+        reason: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// A client connected to a fresh stub, plus the stub's side of the socket.
+async fn connect() -> (Client, zbus::Connection) {
+    let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+    let stub = StubVisage {
+        enrolling: Arc::new(Mutex::new(false)),
+    };
+    let server = async {
+        zbus::connection::Builder::unix_stream(server_sock)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(PATH, stub)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    };
+    let client = async {
+        zbus::connection::Builder::unix_stream(client_sock)
+            .p2p()
+            .build()
+            .await
+            .unwrap()
+    };
+    let (server, conn) = futures_lite::future::zip(server, client).await;
+    (Client::from_connection(&conn).await.unwrap(), server)
+}
+
+#[tokio::test]
+async fn verify_maps_replies_and_structured_errors() {
+    let (client, _server) = connect().await;
+    assert_eq!(
+        client.verify("alice").await.unwrap(),
+        VerifyOutcome::Matched
+    );
+    assert_eq!(client.verify("bob").await.unwrap(), VerifyOutcome::NoMatch);
+    let err = client.verify("carol").await.unwrap_err();
+    assert!(
+        matches!(&err, Error::NotEnrolled(m) if m.contains("carol")),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn list_models_decodes_json() {
+    let (client, _server) = connect().await;
+    let models = client.list_models("alice").await.unwrap();
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].id, "m1");
+    assert_eq!(models[0].label, "alice-normal");
+    assert!((models[0].quality_score - 0.91).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn introspect_returns_only_the_visage_interface() {
+    let (client, _server) = connect().await;
+    let xml = client.introspect().await.unwrap();
+    assert!(xml.contains(r#"<interface name="org.freedesktop.Visage1">"#));
+    assert!(xml.contains(r#"<method name="ListModels">"#));
+    assert!(xml.contains(r#"<signal name="HealthChanged">"#));
+    assert!(!xml.contains("org.freedesktop.DBus.Introspectable"));
+}
+
+#[tokio::test]
+async fn unadvertised_features_fail_before_calling() {
+    let (client, _server) = connect().await;
+    let err = client.dedupe("alice", 0.9).await.unwrap_err();
+    assert!(
+        matches!(&err, Error::Unsupported(c) if c == "dedupe"),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn enroll_reports_progress_while_pending() {
+    let (client, _server) = connect().await;
+    let mut updates = Vec::new();
+    let id = client
+        .enroll_with_progress("alice", "glasses", |p| updates.push(p))
+        .await
+        .unwrap();
+    assert_eq!(id, "model-glasses");
+    assert!(
+        updates
+            .iter()
+            .any(|p| matches!(p, Progress::Capturing { .. })),
+        "{updates:?}"
+    );
+}
+
+#[tokio::test]
+async fn health_changes_streams_signal_values() {
+    let (client, server) = connect().await;
+    let mut changes = std::pin::pin!(client.health_changes().await.unwrap());
+    let emitter = SignalEmitter::new(&server, PATH).unwrap();
+    StubVisage::health_changed(&emitter, true).await.unwrap();
+    StubVisage::health_changed(&emitter, false).await.unwrap();
+    assert_eq!(changes.next().await, Some(true));
+    assert_eq!(changes.next().await, Some(false));
+}
+
+#[tokio::test]
+async fn verifications_stream_started_and_completed_in_order() {
+    let (client, server) = connect().await;
+    let mut events = std::pin::pin!(client.verifications().await.unwrap());
+    let emitter = SignalEmitter::new(&server, PATH).unwrap();
+    StubVisage::verification_started(&emitter, "alice")
+        .await
+        .unwrap();
+    StubVisage::health_changed(&emitter, true).await.unwrap();
+    StubVisage::verification_completed(&emitter, "alice", false, "no-face")
+        .await
+        .unwrap();
+    assert_eq!(
+        events.next().await,
+        Some(VerificationEvent::Started {
+            user: "alice".to_string()
+        })
+    );
+    assert_eq!(
+        events.next().await,
+        Some(VerificationEvent::Completed {
+            user: "alice".to_string(),
+            matched: false,
+            reason: "no-face".to_string(),
+        })
+    );
+}
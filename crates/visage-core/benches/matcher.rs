@@ -0,0 +1,72 @@
+//! Gallery matching throughput with and without pre-normalized embeddings.
+//!
+//! Run with `cargo bench -p visage-core --bench matcher`. Uses plain timing
+//! rather than a benchmark framework; compare the two lines against each other.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use visage_core::{CosineMatcher, Embedding, FaceModel, Matcher};
+
+const DIM: usize = 512;
+const GALLERY: usize = 10;
+/// Frames per verify times a generous number of verify attempts.
+const COMPARES: usize = 20_000;
+
+fn vector(seed: u64) -> Vec<f32> {
+    let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+    (0..DIM)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        })
+        .collect()
+}
+
+fn gallery(make: impl Fn(Vec<f32>) -> Embedding) -> Vec<FaceModel> {
+    (0..GALLERY as u64)
+        .map(|i| FaceModel {
+            id: i.to_string(),
+            user: "bench".into(),
+            label: "bench".into(),
+            embedding: make(vector(i)),
+            pose_label: "frontal".into(),
+            device_id: None,
+            enroll_brightness: None,
+            created_at: String::new(),
+        })
+        .collect()
+}
+
+fn run(name: &str, probe: &Embedding, gallery: &[FaceModel]) -> Duration {
+    let started = Instant::now();
+    for _ in 0..COMPARES {
+        black_box(CosineMatcher.compare(black_box(probe), black_box(gallery), 0.4));
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{name:<12} {:>8.2} µs per {GALLERY}-model gallery compare",
+        elapsed.as_secs_f64() * 1e6 / COMPARES as f64
+    );
+    elapsed
+}
+
+fn main() {
+    let probe = vector(GALLERY as u64 + 1);
+    let raw = run(
+        "raw",
+        &Embedding::new(probe.clone(), None),
+        &gallery(|v| Embedding::new(v, None)),
+    );
+    let unit = run(
+        "normalized",
+        &Embedding::normalized(probe, None),
+        &gallery(|v| Embedding::normalized(v, None)),
+    );
+    println!(
+        "speedup      {:>8.2}x",
+        raw.as_secs_f64() / unit.as_secs_f64()
+    );
+}
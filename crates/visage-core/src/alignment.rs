@@ -12,7 +12,14 @@ const REFERENCE_LANDMARKS_112: [(f32, f32); 5] = [
     (70.7299, 92.2041), // right mouth
 ];
 
-const ALIGNED_SIZE: usize = 112;
+/// Side length of the aligned face crop produced by [`align_face`].
+pub const ALIGNED_SIZE: usize = 112;
+
+/// RMS landmark residual (template pixels) at which [`alignment_quality`] reaches 0.
+///
+/// About a quarter of the reference inter-ocular distance (35 px): beyond this
+/// the landmark geometry no longer resembles a frontal face.
+const MAX_ALIGNMENT_RESIDUAL: f32 = 10.0;
 
 /// Estimate a 2×3 similarity transform (4-DOF: scale, rotation, translation)
 /// from `src` landmarks to `dst` landmarks using least-squares.
@@ -165,6 +172,120 @@ fn warp_affine(
     output
 }
 
+/// RMS distance (in 112×112 template pixels) between the reference landmarks
+/// and `landmarks` after the best-fit similarity transform.
+///
+/// In-plane rotation, scale and translation are absorbed by the transform, so
+/// this measures only geometry a similarity cannot explain: head yaw/pitch,
+/// clipped or mislocated landmarks. Returns `f32::INFINITY` for degenerate input.
+pub fn alignment_residual(landmarks: &[(f32, f32); 5]) -> f32 {
+    // A collapsed point set has no geometry to fit; treat it as unusable.
+    let (cx, cy) = landmarks
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x / 5.0, sy + y / 5.0));
+    let spread: f32 = landmarks
+        .iter()
+        .map(|&(x, y)| (x - cx).powi(2) + (y - cy).powi(2))
+        .sum();
+    if spread.is_nan() || spread <= 1e-6 {
+        return f32::INFINITY;
+    }
+
+    let m = estimate_similarity_transform(landmarks, &REFERENCE_LANDMARKS_112);
+    let sum_sq: f32 = landmarks
+        .iter()
+        .zip(REFERENCE_LANDMARKS_112.iter())
+        .map(|(&(x, y), &(rx, ry))| {
+            let tx = m[0] * x + m[1] * y + m[2];
+            let ty = m[3] * x + m[4] * y + m[5];
+            (tx - rx).powi(2) + (ty - ry).powi(2)
+        })
+        .sum();
+    let rms = (sum_sq / 5.0).sqrt();
+    if rms.is_finite() {
+        rms
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Alignment quality in [0, 1]: 1.0 for landmarks that match the canonical
+/// template exactly, falling linearly to 0.0 at a 10 px RMS residual.
+pub fn alignment_quality(landmarks: &[(f32, f32); 5]) -> f32 {
+    (1.0 - alignment_residual(landmarks) / MAX_ALIGNMENT_RESIDUAL).clamp(0.0, 1.0)
+}
+
+/// Nose offset (in inter-ocular distances) beyond which a face counts as
+/// turned rather than [`Pose::Frontal`]. Roughly 20° of head yaw.
+const POSE_YAW_RATIO: f32 = 0.2;
+
+/// Coarse head pose classified from the five landmarks.
+///
+/// `Left` and `Right` are the subject's own sides: a subject turning to their
+/// left moves the nose towards the right of an unmirrored image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pose {
+    Frontal,
+    Left,
+    Right,
+}
+
+impl Pose {
+    /// Label stored with an enrolled template.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Pose::Frontal => "frontal",
+            Pose::Left => "left",
+            Pose::Right => "right",
+        }
+    }
+
+    /// Parse a stored pose label; `None` for labels this version does not know.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "frontal" => Some(Pose::Frontal),
+            "left" => Some(Pose::Left),
+            "right" => Some(Pose::Right),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Pose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Signed horizontal offset of the nose from the eye midpoint, measured along
+/// the eye axis in inter-ocular distances. Near 0 for a frontal face,
+/// positive when the nose sits towards the image-right eye. `None` when the
+/// eyes coincide.
+pub fn yaw_ratio(landmarks: &[(f32, f32); 5]) -> Option<f32> {
+    let [left_eye, right_eye, nose, _, _] = *landmarks;
+    let axis = (right_eye.0 - left_eye.0, right_eye.1 - left_eye.1);
+    let eye_distance = (axis.0 * axis.0 + axis.1 * axis.1).sqrt();
+    if !eye_distance.is_finite() || eye_distance <= 1e-3 {
+        return None;
+    }
+    let mid = (
+        (left_eye.0 + right_eye.0) / 2.0,
+        (left_eye.1 + right_eye.1) / 2.0,
+    );
+    let along = (nose.0 - mid.0) * axis.0 + (nose.1 - mid.1) * axis.1;
+    Some(along / (eye_distance * eye_distance))
+}
+
+/// Classify the head pose of a detected face. Degenerate landmarks count as
+/// frontal.
+pub fn classify_pose(landmarks: &[(f32, f32); 5]) -> Pose {
+    match yaw_ratio(landmarks) {
+        Some(r) if r > POSE_YAW_RATIO => Pose::Left,
+        Some(r) if r < -POSE_YAW_RATIO => Pose::Right,
+        _ => Pose::Frontal,
+    }
+}
+
 /// Align a detected face to a canonical 112×112 crop.
 ///
 /// Takes a grayscale frame and five detected facial landmarks, computes the
@@ -237,6 +358,59 @@ mod tests {
         assert_eq!(aligned.len(), 112 * 112);
     }
 
+    /// Reference landmarks rotated by `deg`, scaled by `s`, then shifted.
+    fn transformed_reference(deg: f32, s: f32, dx: f32, dy: f32) -> [(f32, f32); 5] {
+        let (sin, cos) = deg.to_radians().sin_cos();
+        REFERENCE_LANDMARKS_112
+            .map(|(x, y)| (s * (cos * x - sin * y) + dx, s * (sin * x + cos * y) + dy))
+    }
+
+    #[test]
+    fn test_alignment_quality_ignores_similarity_transforms() {
+        assert!(alignment_quality(&REFERENCE_LANDMARKS_112) > 0.999);
+        // Rotated, scaled, shifted faces are perfectly alignable.
+        let moved = transformed_reference(25.0, 3.0, 200.0, 90.0);
+        assert!(alignment_quality(&moved) > 0.99);
+    }
+
+    #[test]
+    fn test_alignment_residual_ordering() {
+        let base = transformed_reference(10.0, 2.0, 100.0, 50.0);
+
+        // Mild detector jitter on one landmark.
+        let mut jitter = base;
+        jitter[2].0 += 2.0;
+
+        // Strong yaw: nose and mouth pushed far towards one eye.
+        let mut yawed = base;
+        for p in &mut yawed[2..] {
+            p.0 += 25.0;
+        }
+
+        // Landmarks clipped against the frame edge (mouth clamped up to the eyes' row).
+        let mut clipped = base;
+        clipped[3].1 = base[0].1;
+        clipped[4].1 = base[1].1;
+
+        let r_base = alignment_residual(&base);
+        let r_jitter = alignment_residual(&jitter);
+        let r_yawed = alignment_residual(&yawed);
+        let r_clipped = alignment_residual(&clipped);
+        assert!(r_base < r_jitter, "{r_base} !< {r_jitter}");
+        assert!(r_jitter < r_yawed, "{r_jitter} !< {r_yawed}");
+        assert!(r_jitter < r_clipped, "{r_jitter} !< {r_clipped}");
+
+        assert!(alignment_quality(&jitter) > 0.8);
+        assert!(alignment_quality(&clipped) < 0.5);
+    }
+
+    #[test]
+    fn test_collapsed_landmarks_score_zero() {
+        let collapsed = [(50.0, 50.0); 5];
+        assert_eq!(alignment_residual(&collapsed), f32::INFINITY);
+        assert_eq!(alignment_quality(&collapsed), 0.0);
+    }
+
     #[test]
     fn test_landmark_roundtrip() {
         // Place a bright patch at a landmark position, verify it lands near the
@@ -288,4 +462,26 @@ mod tests {
             "Expected bright patch near reference left eye ({ref_x}, {ref_y}), max={max_val}"
         );
     }
+
+    #[test]
+    fn test_classify_pose_from_nose_offset() {
+        let base = transformed_reference(10.0, 2.0, 100.0, 50.0);
+        assert_eq!(classify_pose(&REFERENCE_LANDMARKS_112), Pose::Frontal);
+        assert_eq!(classify_pose(&base), Pose::Frontal);
+
+        // Nose pushed towards the image-right eye: subject turned to their left.
+        let mut left = REFERENCE_LANDMARKS_112;
+        left[2].0 += 12.0;
+        assert_eq!(classify_pose(&left), Pose::Left);
+        let mut right = REFERENCE_LANDMARKS_112;
+        right[2].0 -= 12.0;
+        assert_eq!(classify_pose(&right), Pose::Right);
+
+        // Collapsed eyes carry no pose information.
+        assert_eq!(classify_pose(&[(5.0, 5.0); 5]), Pose::Frontal);
+        for pose in [Pose::Frontal, Pose::Left, Pose::Right] {
+            assert_eq!(Pose::from_label(pose.as_str()), Some(pose));
+        }
+        assert_eq!(Pose::from_label("up"), None);
+    }
 }
@@ -6,13 +6,22 @@
 pub mod alignment;
 pub mod detector;
 pub mod liveness;
+pub mod phash;
+pub mod protected;
 pub mod recognizer;
 pub mod types;
 
 pub use detector::FaceDetector;
-pub use liveness::{check_landmark_stability, LivenessResult};
+pub use liveness::{
+    check_landmark_liveness, check_landmark_stability, check_timed_landmark_liveness,
+    LivenessPolicy, LivenessResult,
+};
+pub use protected::ProtectError;
 pub use recognizer::FaceRecognizer;
-pub use types::{BoundingBox, CosineMatcher, Embedding, FaceModel, MatchResult, Matcher};
+pub use types::{
+    BoundingBox, CentroidMatcher, CosineMatcher, Embedding, EmbeddingVector, FaceModel,
+    MatchResult, Matcher,
+};
 
 /// Default model directory (XDG data home).
 pub fn default_model_dir() -> std::path::PathBuf {
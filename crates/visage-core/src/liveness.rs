@@ -14,6 +14,13 @@
 //! - **Blocks:** Printed photographs, static IR images held in front of camera.
 //! - **Does not block:** Video replay attacks (landmarks move in video),
 //!   high-quality 3D masks, or adversarial displays.
+//!
+//! With [`LivenessPolicy::min_frame_gap`] set, [`check_timed_landmark_liveness`]
+//! only compares frames captured at least that far apart, so a photo flashed
+//! for a few milliseconds cannot supply a pair whose sensor noise happens to
+//! clear the threshold.
+
+use std::time::{Duration, Instant};
 
 /// Result of a landmark stability liveness check.
 #[derive(Debug, Clone)]
@@ -24,6 +31,36 @@ pub struct LivenessResult {
     pub mean_eye_displacement: f32,
     /// Number of frame pairs analysed.
     pub frame_pairs_analysed: usize,
+    /// Mean eye displacement of each consecutive frame pair, in order.
+    pub pair_displacements: Vec<f32>,
+    /// Number of pairs whose own displacement met the threshold.
+    pub live_pairs: usize,
+    /// Frames left out for following the previous kept frame by less than
+    /// `min_frame_gap`.
+    pub frames_too_close: usize,
+}
+
+/// Tunable liveness requirements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LivenessPolicy {
+    /// Minimum mean eye displacement (pixels) across all frame pairs.
+    pub min_displacement: f32,
+    /// Minimum number of frame pairs that must individually reach
+    /// `min_displacement`. `0` keeps the mean-only decision.
+    pub min_live_pairs: usize,
+    /// Minimum capture-time gap between frames compared by
+    /// [`check_timed_landmark_liveness`]. Zero compares every frame.
+    pub min_frame_gap: Duration,
+}
+
+impl Default for LivenessPolicy {
+    fn default() -> Self {
+        Self {
+            min_displacement: DEFAULT_MIN_EYE_DISPLACEMENT,
+            min_live_pairs: 0,
+            min_frame_gap: Duration::ZERO,
+        }
+    }
 }
 
 /// Default minimum eye displacement (in pixels) below which frames are
@@ -53,19 +90,44 @@ pub fn check_landmark_stability(
     landmark_sequence: &[[(f32, f32); 5]],
     min_displacement: Option<f32>,
 ) -> LivenessResult {
-    let threshold = min_displacement.unwrap_or(DEFAULT_MIN_EYE_DISPLACEMENT);
+    check_landmark_liveness(
+        landmark_sequence,
+        &LivenessPolicy {
+            min_displacement: min_displacement.unwrap_or(DEFAULT_MIN_EYE_DISPLACEMENT),
+            ..LivenessPolicy::default()
+        },
+    )
+}
+
+/// Like [`check_landmark_stability`], but additionally requires at least
+/// `policy.min_live_pairs` frame pairs to individually reach the displacement
+/// threshold, so one moving pair among many static ones cannot carry the mean.
+///
+/// With `min_live_pairs > 0` a sequence too short to contain that many pairs
+/// fails instead of passing through.
+///
+/// Frames carry no capture times here, so `policy.min_frame_gap` is not
+/// applied; see [`check_timed_landmark_liveness`].
+pub fn check_landmark_liveness(
+    landmark_sequence: &[[(f32, f32); 5]],
+    policy: &LivenessPolicy,
+) -> LivenessResult {
+    let threshold = policy.min_displacement;
 
     // Need at least 2 frames to compare
     if landmark_sequence.len() < 2 {
         return LivenessResult {
-            is_live: true, // cannot determine — pass through
+            // Cannot determine — pass through unless pairs are explicitly required
+            is_live: policy.min_live_pairs == 0,
             mean_eye_displacement: 0.0,
             frame_pairs_analysed: 0,
+            pair_displacements: Vec::new(),
+            live_pairs: 0,
+            frames_too_close: 0,
         };
     }
 
-    let mut total_displacement = 0.0f32;
-    let mut pair_count = 0usize;
+    let mut pair_displacements = Vec::with_capacity(landmark_sequence.len() - 1);
 
     for pair in landmark_sequence.windows(2) {
         let prev = &pair[0];
@@ -82,20 +144,48 @@ pub fn check_landmark_stability(
         let right_disp = (right_dx * right_dx + right_dy * right_dy).sqrt();
 
         // Average displacement of both eyes for this frame pair
-        total_displacement += (left_disp + right_disp) / 2.0;
-        pair_count += 1;
+        pair_displacements.push((left_disp + right_disp) / 2.0);
     }
 
-    let mean_displacement = if pair_count > 0 {
-        total_displacement / pair_count as f32
-    } else {
-        0.0
-    };
+    let pair_count = pair_displacements.len();
+    let mean_displacement = pair_displacements.iter().sum::<f32>() / pair_count as f32;
+    let live_pairs = pair_displacements
+        .iter()
+        .filter(|&&d| d >= threshold)
+        .count();
 
     LivenessResult {
-        is_live: mean_displacement >= threshold,
+        is_live: mean_displacement >= threshold && live_pairs >= policy.min_live_pairs,
         mean_eye_displacement: mean_displacement,
         frame_pairs_analysed: pair_count,
+        pair_displacements,
+        live_pairs,
+        frames_too_close: 0,
+    }
+}
+
+/// Like [`check_landmark_liveness`] for landmarks paired with their frame's
+/// capture time. A frame captured less than `policy.min_frame_gap` after the
+/// last frame kept is dropped before pairs are formed.
+pub fn check_timed_landmark_liveness(
+    track: &[(Instant, [(f32, f32); 5])],
+    policy: &LivenessPolicy,
+) -> LivenessResult {
+    let mut kept: Vec<[(f32, f32); 5]> = Vec::with_capacity(track.len());
+    let mut last_kept: Option<Instant> = None;
+    for &(captured, landmarks) in track {
+        let spaced = match last_kept {
+            Some(last) => captured.saturating_duration_since(last) >= policy.min_frame_gap,
+            None => true,
+        };
+        if spaced {
+            kept.push(landmarks);
+            last_kept = Some(captured);
+        }
+    }
+    LivenessResult {
+        frames_too_close: track.len() - kept.len(),
+        ..check_landmark_liveness(&kept, policy)
     }
 }
 
@@ -243,4 +333,100 @@ mod tests {
         assert_eq!(result.frame_pairs_analysed, 2);
         assert!((result.mean_eye_displacement - 0.5).abs() < 1e-6);
     }
+
+    fn policy(min_live_pairs: usize) -> LivenessPolicy {
+        LivenessPolicy {
+            min_live_pairs,
+            ..LivenessPolicy::default()
+        }
+    }
+
+    /// Five frames: one large jump, then static — the mean alone passes.
+    fn single_jump_sequence() -> Vec<[(f32, f32); 5]> {
+        let still = landmarks_with_eyes((105.0, 50.0), (145.0, 50.0));
+        vec![
+            landmarks_with_eyes((100.0, 50.0), (140.0, 50.0)),
+            still,
+            still,
+            still,
+            still,
+        ]
+    }
+
+    #[test]
+    fn test_per_pair_displacements_reported() {
+        let result = check_landmark_stability(&single_jump_sequence(), None);
+        assert_eq!(result.pair_displacements, vec![5.0, 0.0, 0.0, 0.0]);
+        assert_eq!(result.live_pairs, 1);
+        // Mean 1.25 px clears the default threshold on its own.
+        assert!(result.is_live);
+    }
+
+    #[test]
+    fn test_min_live_pairs_rejects_single_moving_pair() {
+        let result = check_landmark_liveness(&single_jump_sequence(), &policy(2));
+        assert!(!result.is_live);
+        assert!(result.mean_eye_displacement >= DEFAULT_MIN_EYE_DISPLACEMENT);
+        assert_eq!(result.live_pairs, 1);
+    }
+
+    #[test]
+    fn test_min_live_pairs_accepts_sustained_movement() {
+        let seq = vec![
+            landmarks_with_eyes((100.0, 50.0), (140.0, 50.0)),
+            landmarks_with_eyes((101.2, 50.8), (141.0, 50.6)),
+            landmarks_with_eyes((100.0, 50.0), (140.0, 50.0)),
+            landmarks_with_eyes((101.0, 51.0), (141.0, 51.0)),
+        ];
+        let result = check_landmark_liveness(&seq, &policy(3));
+        assert_eq!(result.live_pairs, 3);
+        assert!(result.is_live);
+    }
+
+    #[test]
+    fn test_frames_closer_than_min_gap_are_not_paired() {
+        // A photo flashed twice within 5 ms: noise puts the burst pair over
+        // the threshold, the frames around it are static.
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        let still = landmarks_with_eyes((100.0, 50.0), (140.0, 50.0));
+        let jitter = landmarks_with_eyes((101.0, 50.0), (141.0, 50.0));
+        let track = vec![(ms(0), still), (ms(5), jitter), (ms(100), still)];
+        let strict = LivenessPolicy {
+            min_frame_gap: Duration::from_millis(50),
+            ..LivenessPolicy::default()
+        };
+
+        let lax = check_timed_landmark_liveness(&track, &LivenessPolicy::default());
+        assert!(lax.is_live);
+        assert_eq!(lax.frames_too_close, 0);
+
+        let result = check_timed_landmark_liveness(&track, &strict);
+        assert_eq!(result.frames_too_close, 1);
+        assert_eq!(result.pair_displacements, vec![0.0]);
+        assert!(!result.is_live);
+    }
+
+    #[test]
+    fn test_min_gap_is_measured_from_the_last_kept_frame() {
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        let lm = landmarks_with_eyes((100.0, 50.0), (140.0, 50.0));
+        // 30 ms steps against a 50 ms gap: 0, 60 and 120 survive.
+        let track: Vec<_> = (0..5).map(|i| (ms(i * 30), lm)).collect();
+        let policy = LivenessPolicy {
+            min_frame_gap: Duration::from_millis(50),
+            ..LivenessPolicy::default()
+        };
+        let result = check_timed_landmark_liveness(&track, &policy);
+        assert_eq!(result.frames_too_close, 2);
+        assert_eq!(result.frame_pairs_analysed, 2);
+    }
+
+    #[test]
+    fn test_min_live_pairs_fails_short_sequences() {
+        let lm = landmarks_with_eyes((100.0, 50.0), (140.0, 50.0));
+        assert!(check_landmark_liveness(&[lm], &policy(0)).is_live);
+        assert!(!check_landmark_liveness(&[lm], &policy(1)).is_live);
+    }
 }
@@ -0,0 +1,108 @@
+//! Perceptual hashing of aligned face crops.
+//!
+//! A 64-bit difference hash (dHash) summarises the coarse luminance structure
+//! of a 112×112 aligned crop. Two crops of the same face under similar
+//! conditions produce hashes a few bits apart; unrelated faces differ in
+//! roughly half the bits. Only the hash is ever stored — never the crop.
+
+use crate::alignment::{align_face, ALIGNED_SIZE};
+
+const HASH_COLS: usize = 9;
+const HASH_ROWS: usize = 8;
+
+/// Compute a 64-bit difference hash of a square grayscale crop.
+///
+/// The crop is box-averaged down to a 9×8 grid; each bit records whether a
+/// cell is darker than its right-hand neighbour. Returns 0 for an empty or
+/// undersized input.
+pub fn dhash(crop: &[u8], size: usize) -> u64 {
+    if size < HASH_COLS || crop.len() < size * size {
+        return 0;
+    }
+
+    let mut grid = [[0.0f32; HASH_COLS]; HASH_ROWS];
+    for (row, cells) in grid.iter_mut().enumerate() {
+        let y0 = row * size / HASH_ROWS;
+        let y1 = ((row + 1) * size / HASH_ROWS).max(y0 + 1);
+        for (col, cell) in cells.iter_mut().enumerate() {
+            let x0 = col * size / HASH_COLS;
+            let x1 = ((col + 1) * size / HASH_COLS).max(x0 + 1);
+            let mut sum = 0u32;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += crop[y * size + x] as u32;
+                }
+            }
+            *cell = sum as f32 / ((y1 - y0) * (x1 - x0)) as f32;
+        }
+    }
+
+    let mut hash = 0u64;
+    for cells in &grid {
+        for pair in cells.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] < pair[1]);
+        }
+    }
+    hash
+}
+
+/// Align the face described by `landmarks` and return the dHash of the crop.
+pub fn crop_hash(frame: &[u8], width: u32, height: u32, landmarks: &[(f32, f32); 5]) -> u64 {
+    dhash(&align_face(frame, width, height, landmarks), ALIGNED_SIZE)
+}
+
+/// Number of differing bits between two hashes (0 = identical, 64 = inverse).
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 112;
+
+    fn gradient_crop() -> Vec<u8> {
+        (0..SIZE * SIZE)
+            .map(|i| ((i % SIZE) * 255 / SIZE) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_uniform_crop_hashes_to_zero() {
+        let crop = vec![128u8; SIZE * SIZE];
+        assert_eq!(dhash(&crop, SIZE), 0);
+    }
+
+    #[test]
+    fn test_horizontal_gradient_sets_every_bit() {
+        // Brightness increases left→right, so every cell is darker than its neighbour.
+        assert_eq!(dhash(&gradient_crop(), SIZE), u64::MAX);
+    }
+
+    #[test]
+    fn test_small_perturbation_keeps_hash_close() {
+        let crop = gradient_crop();
+        let mut noisy = crop.clone();
+        for (i, p) in noisy.iter_mut().enumerate() {
+            if i % 7 == 0 {
+                *p = p.saturating_add(3);
+            }
+        }
+        let d = hamming_distance(dhash(&crop, SIZE), dhash(&noisy, SIZE));
+        assert!(d <= 4, "distance {d} too large for mild noise");
+    }
+
+    #[test]
+    fn test_undersized_input_returns_zero() {
+        assert_eq!(dhash(&[1, 2, 3], SIZE), 0);
+        assert_eq!(dhash(&[], 0), 0);
+    }
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(0, u64::MAX), 64);
+        assert_eq!(hamming_distance(0b1010, 0b0110), 2);
+    }
+}
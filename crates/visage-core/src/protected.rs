@@ -0,0 +1,296 @@
+//! Passphrase-encrypted bundles.
+//!
+//! [`Embedding`] is not serializable; [`Embedding::into_protected_bytes`]
+//! seals one for export, and store backups seal their whole payload. A
+//! bundle ([`seal`]) is AES-256-GCM under a key derived from the passphrase
+//! with PBKDF2-HMAC-SHA256:
+//!
+//! ```text
+//! "VSGE" | version u8 | rounds u32 LE | salt [16] | nonce [12] | ciphertext || tag [16]
+//! ```
+//!
+//! The header is authenticated as associated data, but the round count in it
+//! is chosen by whoever wrote the bundle, so [`unseal`] refuses more than
+//! [`MAX_PBKDF2_ROUNDS`] rather than spin on a hostile one. For an embedding the
+//! plaintext is the model version (u16 LE length, UTF-8) followed by the
+//! little-endian `f32` values; store backups seal their own payload.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::types::{Embedding, UNIT_NORM_EPSILON};
+
+/// Leading bytes of every bundle.
+pub const PROTECTED_MAGIC: &[u8; 4] = b"VSGE";
+
+/// PBKDF2 iterations for new bundles.
+pub const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Most PBKDF2 iterations [`unseal`] will run for a bundle.
+pub const MAX_PBKDF2_ROUNDS: u32 = 10 * PBKDF2_ROUNDS;
+
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = PROTECTED_MAGIC.len() + 1 + 4 + SALT_LEN + NONCE_LEN;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtectError {
+    #[error("passphrase must not be empty")]
+    EmptyPassphrase,
+    #[error("embedding encryption failed")]
+    EncryptionFailed,
+    #[error("wrong passphrase or corrupted bundle")]
+    DecryptionFailed,
+    #[error("malformed bundle: {0}")]
+    Malformed(&'static str),
+}
+
+impl Embedding {
+    /// Encrypt this embedding under `passphrase` for export.
+    ///
+    /// Neither [`Embedding`] nor [`crate::types::EmbeddingVector`] can be
+    /// serialized onto the bus; this is how a single template leaves.
+    pub fn into_protected_bytes(self, passphrase: &str) -> Result<Vec<u8>, ProtectError> {
+        self.protect(passphrase, PBKDF2_ROUNDS)
+    }
+
+    /// Decrypt a bundle written by [`into_protected_bytes`](Self::into_protected_bytes).
+    ///
+    /// A unit-length vector comes back [`normalized`](Self::normalized),
+    /// bit-for-bit.
+    pub fn from_protected_bytes(bytes: &[u8], passphrase: &str) -> Result<Self, ProtectError> {
+        decode(&unseal(bytes, passphrase)?)
+    }
+
+    fn protect(self, passphrase: &str, rounds: u32) -> Result<Vec<u8>, ProtectError> {
+        seal(&encode(&self)?, passphrase, rounds)
+    }
+}
+
+/// Encrypt `plaintext` under `passphrase`, deriving the key with `rounds`
+/// PBKDF2 iterations ([`PBKDF2_ROUNDS`] outside tests).
+pub fn seal(plaintext: &[u8], passphrase: &str, rounds: u32) -> Result<Vec<u8>, ProtectError> {
+    if passphrase.is_empty() {
+        return Err(ProtectError::EmptyPassphrase);
+    }
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut bundle = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    bundle.extend_from_slice(PROTECTED_MAGIC);
+    bundle.push(FORMAT_VERSION);
+    bundle.extend_from_slice(&rounds.to_le_bytes());
+    bundle.extend_from_slice(&salt);
+    bundle.extend_from_slice(&nonce);
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, &salt, rounds));
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &bundle,
+            },
+        )
+        .map_err(|_| ProtectError::EncryptionFailed)?;
+    bundle.extend_from_slice(&ciphertext);
+    Ok(bundle)
+}
+
+/// Decrypt a bundle written by [`seal`]. The round count is read from the
+/// (authenticated) header and must not exceed [`MAX_PBKDF2_ROUNDS`].
+pub fn unseal(bundle: &[u8], passphrase: &str) -> Result<Vec<u8>, ProtectError> {
+    if passphrase.is_empty() {
+        return Err(ProtectError::EmptyPassphrase);
+    }
+    if bundle.len() < HEADER_LEN || &bundle[..PROTECTED_MAGIC.len()] != PROTECTED_MAGIC {
+        return Err(ProtectError::Malformed("not a sealed bundle"));
+    }
+    let (header, ciphertext) = bundle.split_at(HEADER_LEN);
+    if header[4] != FORMAT_VERSION {
+        return Err(ProtectError::Malformed("unsupported version"));
+    }
+    let rounds = u32::from_le_bytes(header[5..9].try_into().expect("4 bytes"));
+    if rounds == 0 {
+        return Err(ProtectError::Malformed("zero key-derivation rounds"));
+    }
+    if rounds > MAX_PBKDF2_ROUNDS {
+        return Err(ProtectError::Malformed("too many key-derivation rounds"));
+    }
+    let salt = &header[9..9 + SALT_LEN];
+    let nonce = &header[9 + SALT_LEN..];
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, salt, rounds));
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| ProtectError::DecryptionFailed)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+    key
+}
+
+fn encode(embedding: &Embedding) -> Result<Vec<u8>, ProtectError> {
+    let version = embedding.model_version.as_deref().unwrap_or("");
+    let version_len = u16::try_from(version.len()).map_err(|_| ProtectError::EncryptionFailed)?;
+    let mut plaintext = Vec::with_capacity(2 + version.len() + embedding.values.len() * 4);
+    plaintext.extend_from_slice(&version_len.to_le_bytes());
+    plaintext.extend_from_slice(version.as_bytes());
+    for v in embedding.values.iter() {
+        plaintext.extend_from_slice(&v.to_le_bytes());
+    }
+    Ok(plaintext)
+}
+
+fn decode(plaintext: &[u8]) -> Result<Embedding, ProtectError> {
+    if plaintext.len() < 2 {
+        return Err(ProtectError::Malformed("truncated model version"));
+    }
+    let (len, rest) = plaintext.split_at(2);
+    let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+    if rest.len() < len {
+        return Err(ProtectError::Malformed("truncated model version"));
+    }
+    let (version, values) = rest.split_at(len);
+    let version = std::str::from_utf8(version)
+        .map_err(|_| ProtectError::Malformed("model version is not UTF-8"))?;
+    if values.len() % 4 != 0 {
+        return Err(ProtectError::Malformed("truncated values"));
+    }
+    let values: Vec<f32> = values
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes(c.try_into().expect("4 bytes")))
+        .collect();
+    if values.iter().any(|v| !v.is_finite()) {
+        return Err(ProtectError::Malformed("non-finite value"));
+    }
+
+    let model_version = (!version.is_empty()).then(|| version.to_string());
+    let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+    Ok(if (norm - 1.0).abs() <= UNIT_NORM_EPSILON {
+        Embedding::normalized(values, model_version)
+    } else {
+        Embedding::new(values, model_version)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps the tests fast; the format records the round count.
+    const TEST_ROUNDS: u32 = 1_000;
+
+    fn sample() -> Embedding {
+        let values = (0..512).map(|i| ((i * 37 % 101) as f32) - 50.0).collect();
+        Embedding::normalized(values, Some("w600k_r50".to_string()))
+    }
+
+    #[test]
+    fn test_protected_bytes_round_trip() {
+        let original = sample();
+        let bundle = original
+            .clone()
+            .protect("correct horse", TEST_ROUNDS)
+            .unwrap();
+        assert_eq!(&bundle[..4], PROTECTED_MAGIC);
+
+        let restored = Embedding::from_protected_bytes(&bundle, "correct horse").unwrap();
+        assert_eq!(restored.values, original.values);
+        assert_eq!(restored.model_version.as_deref(), Some("w600k_r50"));
+        assert!(restored.is_unit_length());
+    }
+
+    #[test]
+    fn test_protected_bytes_do_not_contain_the_values() {
+        let original = sample();
+        let raw: Vec<u8> = original
+            .values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let bundle = original.protect("pw", TEST_ROUNDS).unwrap();
+        assert!(!bundle.windows(16).any(|w| w == &raw[..16]));
+    }
+
+    #[test]
+    fn test_protected_bytes_reject_wrong_passphrase() {
+        let bundle = sample().protect("right", TEST_ROUNDS).unwrap();
+        assert!(matches!(
+            Embedding::from_protected_bytes(&bundle, "wrong"),
+            Err(ProtectError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_protected_bytes_detect_header_tampering() {
+        let mut bundle = sample().protect("pw", TEST_ROUNDS).unwrap();
+        // Lower the round count: the header is authenticated, so this fails
+        // decryption rather than silently weakening the key derivation.
+        bundle[5] ^= 1;
+        assert!(matches!(
+            Embedding::from_protected_bytes(&bundle, "pw"),
+            Err(ProtectError::DecryptionFailed)
+        ));
+        assert!(matches!(
+            Embedding::from_protected_bytes(b"VSGE", "pw"),
+            Err(ProtectError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_unseal_refuses_an_excessive_round_count() {
+        let mut bundle = seal(b"payload", "pw", TEST_ROUNDS).unwrap();
+        // Refused before any key derivation, so this returns at once.
+        bundle[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            unseal(&bundle, "pw"),
+            Err(ProtectError::Malformed("too many key-derivation rounds"))
+        ));
+        bundle[5..9].copy_from_slice(&(MAX_PBKDF2_ROUNDS + 1).to_le_bytes());
+        assert!(matches!(
+            unseal(&bundle, "pw"),
+            Err(ProtectError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_protected_bytes_require_a_passphrase() {
+        assert!(matches!(
+            sample().into_protected_bytes(""),
+            Err(ProtectError::EmptyPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_seal_round_trips_arbitrary_payloads() {
+        let payload = b"not an embedding at all".repeat(100);
+        let bundle = seal(&payload, "pw", TEST_ROUNDS).unwrap();
+        assert_eq!(unseal(&bundle, "pw").unwrap(), payload);
+        assert!(matches!(
+            Embedding::from_protected_bytes(&bundle, "pw"),
+            Err(ProtectError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_embedding_debug_omits_values() {
+        let shown = format!("{:?}", sample());
+        assert!(shown.contains("[512 values]"), "{shown}");
+        assert!(!shown.contains("0.0"), "{shown}");
+    }
+}
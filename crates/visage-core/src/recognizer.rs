@@ -15,8 +15,10 @@ use thiserror::Error;
 const ARCFACE_INPUT_SIZE: usize = 112;
 const ARCFACE_MEAN: f32 = 127.5;
 const ARCFACE_STD: f32 = 127.5; // NOT 128.0 — ArcFace uses symmetric normalization
-const ARCFACE_EMBEDDING_DIM: usize = 512;
-const ARCFACE_MODEL_VERSION: &str = "w600k_r50";
+/// Length of the embeddings this recognizer produces.
+pub const ARCFACE_EMBEDDING_DIM: usize = 512;
+/// Version tag stamped on every embedding this recognizer produces.
+pub const ARCFACE_MODEL_VERSION: &str = "w600k_r50";
 
 #[derive(Error, Debug)]
 pub enum RecognizerError {
@@ -96,18 +98,10 @@ impl FaceRecognizer {
             )));
         }
 
-        // L2-normalize the embedding
-        let norm: f32 = raw.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let values = if norm > 0.0 {
-            raw.iter().map(|x| x / norm).collect()
-        } else {
-            raw
-        };
-
-        Ok(Embedding {
-            values,
-            model_version: Some(ARCFACE_MODEL_VERSION.to_string()),
-        })
+        Ok(Embedding::normalized(
+            raw,
+            Some(ARCFACE_MODEL_VERSION.to_string()),
+        ))
     }
 
     /// Preprocess a 112x112 grayscale aligned face crop into a NCHW float tensor.
@@ -12,20 +12,129 @@ pub struct BoundingBox {
     pub landmarks: Option<[(f32, f32); 5]>,
 }
 
+/// Tolerance on the L2 norm within which an embedding counts as unit length.
+pub const UNIT_NORM_EPSILON: f32 = 1e-4;
+
+/// Raw components of an [`Embedding`], readable as a `[f32]` slice.
+///
+/// Deliberately implements neither `serde::Serialize` nor `zvariant::Type`,
+/// and its `Debug` output omits the values: a reply type that embeds one
+/// does not build, and a log line that formats one shows only its length.
+/// To export values, seal them: [`Embedding::into_protected_bytes`] for one
+/// template, [`crate::protected::seal`] for a whole payload such as a store
+/// backup.
+#[derive(Clone, PartialEq)]
+pub struct EmbeddingVector(Vec<f32>);
+
+impl std::ops::Deref for EmbeddingVector {
+    type Target = [f32];
+
+    fn deref(&self) -> &[f32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for EmbeddingVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EmbeddingVector([{} values])", self.0.len())
+    }
+}
+
 /// Face embedding vector (typically 512-dimensional for ArcFace).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Not serializable; see [`EmbeddingVector`].
+#[derive(Debug, Clone)]
 pub struct Embedding {
-    pub values: Vec<f32>,
+    pub values: EmbeddingVector,
     /// Model version that produced this embedding (e.g., "w600k_r50").
     pub model_version: Option<String>,
+    /// `values` has unit L2 norm, so similarity is a plain dot product.
+    /// Only set by [`Embedding::normalized`].
+    unit_length: bool,
 }
 
 impl Embedding {
+    /// Wrap a vector as is. Comparisons compute both norms every time.
+    pub fn new(values: Vec<f32>, model_version: Option<String>) -> Self {
+        Self {
+            values: EmbeddingVector(values),
+            model_version,
+            unit_length: false,
+        }
+    }
+
+    /// Scale `values` to unit L2 norm and mark the embedding as normalized.
+    ///
+    /// Vectors already within [`UNIT_NORM_EPSILON`] of unit length are kept
+    /// bit-for-bit, so normalizing twice is a no-op. A zero vector is left
+    /// unchanged and unmarked.
+    pub fn normalized(mut values: Vec<f32>, model_version: Option<String>) -> Self {
+        let norm = l2_norm(&values);
+        if norm > 0.0 && (norm - 1.0).abs() > UNIT_NORM_EPSILON {
+            for v in &mut values {
+                *v /= norm;
+            }
+        }
+        Self {
+            values: EmbeddingVector(values),
+            model_version,
+            unit_length: norm > 0.0,
+        }
+    }
+
+    /// Normalized mean of the normalized `templates`: a single vector that
+    /// stands for a user's whole gallery.
+    ///
+    /// Returns `None` for an empty slice, templates of different lengths or
+    /// model versions (their spaces are not comparable), or a zero mean.
+    pub fn centroid(templates: &[Embedding]) -> Option<Embedding> {
+        let first = templates.first()?;
+        let dim = first.values.len();
+        if templates
+            .iter()
+            .any(|t| t.values.len() != dim || t.model_version != first.model_version)
+        {
+            return None;
+        }
+        let mut sum = vec![0.0f32; dim];
+        for template in templates {
+            let norm = l2_norm(&template.values);
+            if norm == 0.0 {
+                continue;
+            }
+            for (s, v) in sum.iter_mut().zip(template.values.iter()) {
+                *s += v / norm;
+            }
+        }
+        let centroid = Embedding::normalized(sum, first.model_version.clone());
+        centroid.is_unit_length().then_some(centroid)
+    }
+
+    /// Whether this embedding was built by [`normalized`](Self::normalized).
+    pub fn is_unit_length(&self) -> bool {
+        self.unit_length
+    }
+
     /// Compute cosine similarity between two embeddings.
     ///
     /// Returns a value in [-1, 1]. Higher = more similar.
     /// Uses constant-time computation: always processes all dimensions.
+    /// When both sides are normalized the norms are skipped.
     pub fn similarity(&self, other: &Embedding) -> f32 {
+        if self.unit_length && other.unit_length {
+            debug_assert!(
+                (l2_norm(&self.values) - 1.0).abs() <= UNIT_NORM_EPSILON
+                    && (l2_norm(&other.values) - 1.0).abs() <= UNIT_NORM_EPSILON,
+                "normalized embedding modified after construction"
+            );
+            return self
+                .values
+                .iter()
+                .zip(other.values.iter())
+                .map(|(a, b)| a * b)
+                .sum();
+        }
+
         let mut dot = 0.0f32;
         let mut norm_a = 0.0f32;
         let mut norm_b = 0.0f32;
@@ -63,13 +172,28 @@ impl Embedding {
     }
 }
 
+fn l2_norm(values: &[f32]) -> f32 {
+    values.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
 /// A stored face model with metadata.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Not serializable, since it carries an [`Embedding`].
+#[derive(Debug, Clone)]
 pub struct FaceModel {
     pub id: String,
     pub user: String,
     pub label: String,
     pub embedding: Embedding,
+    /// Head pose at enrollment (see [`crate::alignment::Pose`]); empty when
+    /// unknown.
+    pub pose_label: String,
+    /// Camera the template was enrolled on (`CaptureContext::device_id` in
+    /// visage-hw); `None` for templates enrolled before it was recorded.
+    pub device_id: Option<String>,
+    /// Average raw brightness of the enrollment frames (0.0–255.0); `None`
+    /// for templates enrolled before it was recorded.
+    pub enroll_brightness: Option<f32>,
     pub created_at: String,
 }
 
@@ -131,98 +255,118 @@ impl Matcher for CosineMatcher {
     }
 }
 
+/// Fast path that compares a probe against a user's centroid template
+/// (see [`Embedding::centroid`]) before the full gallery.
+///
+/// Unlike [`CosineMatcher`] this is not constant-time: a probe it cannot
+/// reject costs a full gallery pass, a clear impostor does not.
+///
+/// It only ever rejects. The centroid is renormalized, so a probe between
+/// two dissimilar templates can sit closer to it than to either of them;
+/// accepting on the centroid would admit a face no template matches.
+///
+/// The reject bound widens with the gallery's `spread`. A probe that
+/// matches some template lies within `acos(threshold)` of it, and that
+/// template within `acos(spread)` of the centroid, so a genuine probe is
+/// never further than their sum from the centroid. A divergent gallery
+/// (say, one template per pose) pushes the bound down until the fast path
+/// rejects nothing.
+pub struct CentroidMatcher {
+    /// Extra distance below the reject bound before the centroid is trusted.
+    pub margin: f32,
+    /// Smallest similarity between any of the user's templates and their
+    /// centroid; `1.0` for a single template.
+    pub spread: f32,
+}
+
+impl CentroidMatcher {
+    /// Reject from the centroid alone when the probe is at least `margin`
+    /// below the lowest similarity a probe matching any template at
+    /// `threshold` could have. Returns `None` for any other probe, which
+    /// must be compared against the individual templates.
+    pub fn decide(
+        &self,
+        probe: &Embedding,
+        centroid: &Embedding,
+        threshold: f32,
+    ) -> Option<MatchResult> {
+        let reach = threshold.clamp(-1.0, 1.0).acos() + self.spread.clamp(-1.0, 1.0).acos();
+        let bound = reach.min(std::f32::consts::PI).cos();
+        let similarity = probe.similarity(centroid);
+        (similarity < bound - self.margin).then_some(MatchResult {
+            matched: false,
+            similarity,
+            model_id: None,
+            model_label: None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_cosine_similarity_identical() {
-        let a = Embedding {
-            values: vec![1.0, 0.0, 0.0],
-            model_version: None,
-        };
-        let b = Embedding {
-            values: vec![1.0, 0.0, 0.0],
-            model_version: None,
-        };
+        let a = Embedding::new(vec![1.0, 0.0, 0.0], None);
+        let b = Embedding::new(vec![1.0, 0.0, 0.0], None);
         assert!((a.similarity(&b) - 1.0).abs() < 1e-6);
     }
 
     #[test]
     fn test_cosine_similarity_orthogonal() {
-        let a = Embedding {
-            values: vec![1.0, 0.0],
-            model_version: None,
-        };
-        let b = Embedding {
-            values: vec![0.0, 1.0],
-            model_version: None,
-        };
+        let a = Embedding::new(vec![1.0, 0.0], None);
+        let b = Embedding::new(vec![0.0, 1.0], None);
         assert!(a.similarity(&b).abs() < 1e-6);
     }
 
     #[test]
     fn test_cosine_similarity_opposite() {
-        let a = Embedding {
-            values: vec![1.0, 0.0],
-            model_version: None,
-        };
-        let b = Embedding {
-            values: vec![-1.0, 0.0],
-            model_version: None,
-        };
+        let a = Embedding::new(vec![1.0, 0.0], None);
+        let b = Embedding::new(vec![-1.0, 0.0], None);
         assert!((a.similarity(&b) + 1.0).abs() < 1e-6);
     }
 
     #[test]
     fn test_cosine_similarity_zero_vector() {
-        let a = Embedding {
-            values: vec![0.0, 0.0],
-            model_version: None,
-        };
-        let b = Embedding {
-            values: vec![1.0, 0.0],
-            model_version: None,
-        };
+        let a = Embedding::new(vec![0.0, 0.0], None);
+        let b = Embedding::new(vec![1.0, 0.0], None);
         assert_eq!(a.similarity(&b), 0.0);
     }
 
     #[test]
     fn test_cosine_matcher_constant_time() {
         // Verify all gallery entries are compared (best match is last entry)
-        let probe = Embedding {
-            values: vec![1.0, 0.0, 0.0],
-            model_version: None,
-        };
+        let probe = Embedding::new(vec![1.0, 0.0, 0.0], None);
         let gallery = vec![
             FaceModel {
                 id: "1".into(),
                 user: "u".into(),
                 label: "decoy1".into(),
-                embedding: Embedding {
-                    values: vec![0.0, 1.0, 0.0],
-                    model_version: None,
-                },
+                embedding: Embedding::new(vec![0.0, 1.0, 0.0], None),
+                pose_label: "frontal".into(),
+                device_id: None,
+                enroll_brightness: None,
                 created_at: "".into(),
             },
             FaceModel {
                 id: "2".into(),
                 user: "u".into(),
                 label: "decoy2".into(),
-                embedding: Embedding {
-                    values: vec![0.0, 0.0, 1.0],
-                    model_version: None,
-                },
+                embedding: Embedding::new(vec![0.0, 0.0, 1.0], None),
+                pose_label: "frontal".into(),
+                device_id: None,
+                enroll_brightness: None,
                 created_at: "".into(),
             },
             FaceModel {
                 id: "3".into(),
                 user: "u".into(),
                 label: "match".into(),
-                embedding: Embedding {
-                    values: vec![1.0, 0.0, 0.0],
-                    model_version: None,
-                },
+                embedding: Embedding::new(vec![1.0, 0.0, 0.0], None),
+                pose_label: "frontal".into(),
+                device_id: None,
+                enroll_brightness: None,
                 created_at: "".into(),
             },
         ];
@@ -236,18 +380,15 @@ mod tests {
 
     #[test]
     fn test_cosine_matcher_no_match() {
-        let probe = Embedding {
-            values: vec![1.0, 0.0, 0.0],
-            model_version: None,
-        };
+        let probe = Embedding::new(vec![1.0, 0.0, 0.0], None);
         let gallery = vec![FaceModel {
             id: "1".into(),
             user: "u".into(),
             label: "other".into(),
-            embedding: Embedding {
-                values: vec![0.0, 1.0, 0.0],
-                model_version: None,
-            },
+            embedding: Embedding::new(vec![0.0, 1.0, 0.0], None),
+            pose_label: "frontal".into(),
+            device_id: None,
+            enroll_brightness: None,
             created_at: "".into(),
         }];
 
@@ -256,14 +397,182 @@ mod tests {
         assert!(result.similarity.abs() < 1e-6);
     }
 
+    /// Deterministic pseudo-random vector with components in [-1, 1).
+    fn vector(seed: u64, dim: usize) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (0..dim)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
     #[test]
-    fn test_cosine_matcher_empty_gallery() {
-        let probe = Embedding {
-            values: vec![1.0, 0.0],
-            model_version: None,
+    fn test_normalized_similarity_matches_raw() {
+        let raw: Vec<Embedding> = (0..8)
+            .map(|i| Embedding::new(vector(i, 512), None))
+            .collect();
+        let unit: Vec<Embedding> = raw
+            .iter()
+            .map(|e| Embedding::normalized(e.values.to_vec(), None))
+            .collect();
+        for i in 0..raw.len() {
+            for j in 0..raw.len() {
+                let before = raw[i].similarity(&raw[j]);
+                let after = unit[i].similarity(&unit[j]);
+                assert!(
+                    (before - after).abs() < 1e-6,
+                    "{i},{j}: {before} vs {after}"
+                );
+                // Mixed pairs take the full cosine path and agree too.
+                assert!((raw[i].similarity(&unit[j]) - before).abs() < 1e-6);
+            }
+        }
+
+        let gallery = |embeddings: &[Embedding]| -> Vec<FaceModel> {
+            embeddings
+                .iter()
+                .enumerate()
+                .map(|(i, e)| FaceModel {
+                    id: i.to_string(),
+                    user: "u".into(),
+                    label: "l".into(),
+                    embedding: e.clone(),
+                    pose_label: "frontal".into(),
+                    device_id: None,
+                    enroll_brightness: None,
+                    created_at: "".into(),
+                })
+                .collect()
         };
+        let probe = Embedding::new(vector(3, 512).iter().map(|v| v * 7.0).collect(), None);
+        let before = CosineMatcher.compare(&probe, &gallery(&raw), 0.5);
+        let unit_probe = Embedding::normalized(probe.values.to_vec(), None);
+        let after = CosineMatcher.compare(&unit_probe, &gallery(&unit), 0.5);
+        assert_eq!(before.model_id.as_deref(), Some("3"));
+        assert_eq!(after.model_id, before.model_id);
+        assert!((after.similarity - before.similarity).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_normalized_is_idempotent() {
+        let once = Embedding::normalized(vector(1, 512), None);
+        assert!(once.is_unit_length());
+        let twice = Embedding::normalized(once.values.to_vec(), None);
+        assert_eq!(
+            once.values, twice.values,
+            "unit vectors are kept bit-for-bit"
+        );
+
+        let zero = Embedding::normalized(vec![0.0; 4], None);
+        assert!(!zero.is_unit_length());
+        assert_eq!(*zero.values, [0.0; 4]);
+        assert!(!Embedding::new(vec![1.0, 0.0], None).is_unit_length());
+    }
+
+    #[test]
+    fn test_cosine_matcher_empty_gallery() {
+        let probe = Embedding::new(vec![1.0, 0.0], None);
         let result = CosineMatcher.compare(&probe, &[], 0.5);
         assert!(!result.matched);
         assert_eq!(result.similarity, 0.0);
     }
+
+    #[test]
+    fn test_centroid_is_normalized_mean() {
+        let v = |x: f32, y: f32| Embedding::normalized(vec![x, y], Some("m".to_string()));
+        let centroid = Embedding::centroid(&[v(1.0, 0.0), v(0.0, 2.0)]).unwrap();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((centroid.values[0] - half).abs() < 1e-6);
+        assert!((centroid.values[1] - half).abs() < 1e-6);
+        assert!(centroid.is_unit_length());
+        assert_eq!(centroid.model_version.as_deref(), Some("m"));
+
+        assert!(Embedding::centroid(&[]).is_none());
+        assert!(Embedding::centroid(&[v(1.0, 0.0), v(-1.0, 0.0)]).is_none());
+        let other = Embedding::normalized(vec![0.0, 1.0], Some("other".to_string()));
+        assert!(Embedding::centroid(&[v(1.0, 0.0), other]).is_none());
+    }
+
+    #[test]
+    fn test_centroid_matcher_defers_borderline_probes() {
+        let centroid = Embedding::normalized(vec![1.0, 0.0], None);
+        let matcher = CentroidMatcher {
+            margin: 0.1,
+            spread: 1.0,
+        };
+        let at = |angle: f32| Embedding::normalized(vec![angle.cos(), angle.sin()], None);
+
+        // cos 1.5 ≈ 0.07: clearly below 0.5 - 0.1.
+        let clear = matcher.decide(&at(1.5), &centroid, 0.5).unwrap();
+        assert!(!clear.matched);
+        assert!(clear.model_id.is_none());
+        // cos 1.0 ≈ 0.54: within the margin.
+        assert!(matcher.decide(&at(1.0), &centroid, 0.5).is_none());
+        // cos 0.3 ≈ 0.955: clearly above, but only the templates may accept.
+        assert!(matcher.decide(&at(0.3), &centroid, 0.5).is_none());
+    }
+
+    #[test]
+    fn test_centroid_never_accepts_what_no_template_matches() {
+        // Two orthogonal templates: their centroid lies halfway between.
+        let template = |id: &str, values: Vec<f32>| FaceModel {
+            id: id.into(),
+            user: "u".into(),
+            label: id.into(),
+            embedding: Embedding::normalized(values, None),
+            pose_label: "frontal".into(),
+            device_id: None,
+            enroll_brightness: None,
+            created_at: "".into(),
+        };
+        let gallery = [template("a", vec![1.0, 0.0]), template("b", vec![0.0, 1.0])];
+        let embeddings: Vec<Embedding> = gallery.iter().map(|m| m.embedding.clone()).collect();
+        let centroid = Embedding::centroid(&embeddings).unwrap();
+
+        // The probe is the centroid itself: similarity 1.0 to it, but only
+        // cos 45° ≈ 0.71 to either template, below the 0.8 threshold.
+        let probe = centroid.clone();
+        let matcher = CentroidMatcher {
+            margin: 0.1,
+            spread: std::f32::consts::FRAC_1_SQRT_2,
+        };
+        assert!(matcher.decide(&probe, &centroid, 0.8).is_none());
+        assert!(!CosineMatcher.compare(&probe, &gallery, 0.8).matched);
+    }
+
+    #[test]
+    fn test_centroid_never_rejects_what_a_template_matches() {
+        // Three orthogonal templates, one per pose.
+        let templates: Vec<Embedding> = (0..3)
+            .map(|i| {
+                let mut values = vec![0.0; 3];
+                values[i] = 1.0;
+                Embedding::normalized(values, None)
+            })
+            .collect();
+        let centroid = Embedding::centroid(&templates).unwrap();
+        let spread = templates
+            .iter()
+            .map(|t| t.similarity(&centroid))
+            .fold(1.0f32, f32::min);
+        assert!((spread - 1.0 / 3f32.sqrt()).abs() < 1e-6);
+
+        // The probe is one template: cos ≈ 0.58 to the centroid, well below
+        // 0.8 - 0.1, yet a perfect match for that template.
+        let probe = templates[0].clone();
+        assert!(probe.similarity(&centroid) < 0.7);
+        let matcher = CentroidMatcher {
+            margin: 0.1,
+            spread,
+        };
+        assert!(matcher.decide(&probe, &centroid, 0.8).is_none());
+
+        // Only a probe unlike every template is still rejected.
+        let impostor = Embedding::normalized(vec![-1.0, -1.0, -1.0], None);
+        assert!(matcher.decide(&impostor, &centroid, 0.8).is_some());
+    }
 }
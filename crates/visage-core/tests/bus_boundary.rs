@@ -0,0 +1,8 @@
+//! Embeddings must not be serializable onto the bus: a D-Bus reply type
+//! (serde JSON or a zvariant structure) that carries one does not build.
+
+#[test]
+fn reply_types_cannot_carry_embeddings() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
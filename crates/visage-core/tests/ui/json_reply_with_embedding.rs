@@ -0,0 +1,9 @@
+use visage_core::Embedding;
+
+#[derive(serde::Serialize)]
+struct ModelReply {
+    id: String,
+    embedding: Embedding,
+}
+
+fn main() {}
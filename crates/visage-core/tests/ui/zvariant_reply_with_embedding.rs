@@ -0,0 +1,9 @@
+use visage_core::EmbeddingVector;
+
+#[derive(zvariant::Type)]
+struct ModelReply {
+    id: String,
+    values: EmbeddingVector,
+}
+
+fn main() {}
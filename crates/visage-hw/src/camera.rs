@@ -1,6 +1,7 @@
 //! V4L2 camera capture via the `v4l` crate.
 
 use crate::frame::{self, Frame};
+use std::ops::ControlFlow;
 use std::path::Path;
 use thiserror::Error;
 use v4l::buffer::Type as BufType;
@@ -32,6 +33,45 @@ pub struct DeviceInfo {
     pub bus: String,
 }
 
+/// Which camera produced a capture and how its frames were processed, so
+/// results from different sessions (e.g. a docked RGB webcam versus the
+/// built-in IR sensor) can be told apart.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CaptureContext {
+    /// Device path, e.g. `/dev/video2`.
+    pub device: String,
+    /// Stable device identity (see [`Camera::device_id`]); empty when
+    /// deserialized from a record that predates it.
+    #[serde(default)]
+    pub device_id: String,
+    /// Negotiated pixel format, e.g. `GREY` or `YUYV`.
+    pub fourcc: String,
+    pub width: u32,
+    pub height: u32,
+    /// Whether an IR emitter was switched on for the capture.
+    pub emitter_active: bool,
+    /// Processing applied to each frame after capture, in order.
+    pub preprocessing: Vec<String>,
+}
+
+impl std::fmt::Display for CaptureContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}x{}, emitter {}",
+            self.device,
+            self.fourcc,
+            self.width,
+            self.height,
+            if self.emitter_active { "on" } else { "off" }
+        )?;
+        if !self.preprocessing.is_empty() {
+            write!(f, ", {}", self.preprocessing.join(" → "))?;
+        }
+        Ok(())
+    }
+}
+
 /// Negotiated pixel format for the camera.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PixelFormat {
@@ -49,6 +89,10 @@ pub struct Camera {
     pub width: u32,
     pub height: u32,
     pub device_path: String,
+    /// Identity that stays the same across reboots and re-plugging:
+    /// `usb:VVVV:PPPP.I` for USB cameras (see [`crate::quirks::get_device_id`]),
+    /// otherwise `v4l2:` followed by the driver's card name.
+    pub device_id: String,
     pub fourcc: FourCC,
     /// Negotiated pixel format.
     pixel_format: PixelFormat,
@@ -126,11 +170,39 @@ impl Camera {
             width: negotiated.width,
             height: negotiated.height,
             device_path: device_path.to_string(),
+            device_id: crate::quirks::get_device_id(device_path)
+                .unwrap_or_else(|| format!("v4l2:{}", caps.card)),
             fourcc,
             pixel_format,
         })
     }
 
+    /// Describe frames from [`for_each_frame_until`](Self::for_each_frame_until)
+    /// and the other enhanced captures. The camera does not drive the
+    /// emitter, so the caller says whether it was lit.
+    pub fn capture_context(&self, emitter_active: bool) -> CaptureContext {
+        let conversion = match self.pixel_format {
+            PixelFormat::Yuyv => Some("yuyv_luma"),
+            PixelFormat::Y16 => Some("y16_to_8bit"),
+            PixelFormat::Grey => None,
+        };
+        CaptureContext {
+            device: self.device_path.clone(),
+            device_id: self.device_id.clone(),
+            fourcc: String::from_utf8_lossy(&self.fourcc.repr)
+                .trim_end_matches(['\0', ' '])
+                .to_string(),
+            width: self.width,
+            height: self.height,
+            emitter_active,
+            preprocessing: conversion
+                .into_iter()
+                .chain(["dark_frame_filter", "clahe"])
+                .map(String::from)
+                .collect(),
+        }
+    }
+
     /// Capture a single frame, converting to grayscale if needed.
     pub fn capture_frame(&self) -> Result<Frame, CameraError> {
         let mut stream =
@@ -144,6 +216,7 @@ impl Camera {
 
         let gray = self.buf_to_grayscale(buf)?;
         let is_dark = frame::is_dark_frame(&gray, 0.95);
+        let raw_brightness = frame::mean_brightness(&gray);
 
         Ok(Frame {
             data: gray,
@@ -152,6 +225,7 @@ impl Camera {
             timestamp: std::time::Instant::now(),
             sequence: meta.sequence,
             is_dark,
+            raw_brightness,
         })
     }
 
@@ -197,8 +271,74 @@ impl Camera {
     /// Attempts up to `count * 3` raw captures to find `count` non-dark frames.
     /// Each non-dark frame gets CLAHE contrast enhancement applied.
     pub fn capture_frames(&self, count: usize) -> Result<(Vec<Frame>, usize), CameraError> {
-        let max_attempts = count * 3;
-        let mut good_frames = Vec::with_capacity(count);
+        let mut frames = Vec::with_capacity(count);
+        let dark = self.capture_frames_inner(count, None, true, &mut |frame| {
+            frames.push(frame);
+            ControlFlow::Continue(())
+        })?;
+        Ok((frames, dark))
+    }
+
+    /// Like [`capture_frames`](Self::capture_frames), but stops early once
+    /// `deadline` has passed.
+    ///
+    /// The deadline is checked between raw captures and also bounds each
+    /// buffer dequeue, so a stalled device yields a `CaptureFailed` error
+    /// rather than blocking. Callers should compare the returned frame count
+    /// against `count` to detect early exit.
+    pub fn capture_frames_until(
+        &self,
+        count: usize,
+        deadline: std::time::Instant,
+    ) -> Result<(Vec<Frame>, usize), CameraError> {
+        let mut frames = Vec::with_capacity(count);
+        let dark = self.for_each_frame_until(count, deadline, &mut |frame| {
+            frames.push(frame);
+            ControlFlow::Continue(())
+        })?;
+        Ok((frames, dark))
+    }
+
+    /// Like [`capture_frames_until`](Self::capture_frames_until), but hands
+    /// each usable frame to `on_frame` as soon as it is ready instead of
+    /// collecting the batch, so the caller decides which frames stay alive.
+    /// `on_frame` returning [`ControlFlow::Break`] ends the capture early.
+    ///
+    /// Returns the number of dark frames skipped.
+    pub fn for_each_frame_until(
+        &self,
+        count: usize,
+        deadline: std::time::Instant,
+        on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+    ) -> Result<usize, CameraError> {
+        self.capture_frames_inner(count, Some(deadline), true, on_frame)
+    }
+
+    /// Capture up to `count` frames as the sensor delivers them — dark frames
+    /// included, no CLAHE — stopping early once `deadline` has passed. Used
+    /// for exposure diagnostics, where enhancement would hide the problem.
+    pub fn capture_raw_frames_until(
+        &self,
+        count: usize,
+        deadline: std::time::Instant,
+    ) -> Result<Vec<Frame>, CameraError> {
+        let mut frames = Vec::with_capacity(count);
+        self.capture_frames_inner(count, Some(deadline), false, &mut |frame| {
+            frames.push(frame);
+            ControlFlow::Continue(())
+        })?;
+        Ok(frames)
+    }
+
+    fn capture_frames_inner(
+        &self,
+        count: usize,
+        deadline: Option<std::time::Instant>,
+        enhance: bool,
+        on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+    ) -> Result<usize, CameraError> {
+        let max_attempts = if enhance { count * 3 } else { count };
+        let mut delivered = 0usize;
         let mut dark_count = 0usize;
 
         let mut stream =
@@ -207,36 +347,74 @@ impl Camera {
             })?;
 
         for _ in 0..max_attempts {
-            if good_frames.len() >= count {
+            if delivered >= count {
                 break;
             }
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                if remaining.is_zero() {
+                    tracing::debug!(captured = delivered, "capture deadline reached");
+                    break;
+                }
+                // Bound each dequeue so a stalled device cannot block past the deadline.
+                stream.set_timeout(remaining.max(std::time::Duration::from_millis(1)));
+            }
 
             let (buf, meta) = stream.next().map_err(|e| {
                 CameraError::CaptureFailed(format!("failed to dequeue buffer: {e}"))
             })?;
 
             let mut gray = self.buf_to_grayscale(buf)?;
+            let is_dark = frame::is_dark_frame(&gray, 0.95);
+            let raw_brightness = frame::mean_brightness(&gray);
+
+            if enhance {
+                if is_dark {
+                    dark_count += 1;
+                    tracing::debug!(seq = meta.sequence, "skipping dark frame");
+                    continue;
+                }
 
-            if frame::is_dark_frame(&gray, 0.95) {
-                dark_count += 1;
-                tracing::debug!(seq = meta.sequence, "skipping dark frame");
-                continue;
+                // Apply CLAHE contrast enhancement
+                frame::clahe_enhance(&mut gray, self.width, self.height, 8, 0.02);
             }
 
-            // Apply CLAHE contrast enhancement
-            frame::clahe_enhance(&mut gray, self.width, self.height, 8, 0.02);
-
-            good_frames.push(Frame {
+            delivered += 1;
+            let frame = Frame {
                 data: gray,
                 width: self.width,
                 height: self.height,
                 timestamp: std::time::Instant::now(),
                 sequence: meta.sequence,
-                is_dark: false,
-            });
+                is_dark,
+                raw_brightness,
+            };
+            if on_frame(frame).is_break() {
+                break;
+            }
         }
 
-        Ok((good_frames, dark_count))
+        Ok(dark_count)
+    }
+
+    /// List the pixel formats a device advertises (e.g. "GREY", "YUYV").
+    ///
+    /// Only queries the device, so it works while another process is streaming.
+    pub fn list_formats(device_path: &str) -> Result<Vec<String>, CameraError> {
+        let dev = Device::with_path(device_path)
+            .map_err(|e| CameraError::DeviceNotFound(format!("{device_path}: {e}")))?;
+        let formats = dev
+            .enum_formats()
+            .map_err(|e| CameraError::FormatNegotiationFailed(e.to_string()))?;
+        Ok(formats
+            .iter()
+            .map(|f| {
+                f.fourcc
+                    .str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|_| f.fourcc.to_string())
+            })
+            .collect())
     }
 
     /// List available V4L2 video capture devices.
@@ -10,16 +10,25 @@ pub struct Frame {
     pub timestamp: std::time::Instant,
     pub sequence: u32,
     pub is_dark: bool,
+    /// Average pixel brightness as captured, before contrast enhancement
+    /// (0.0–255.0). CLAHE pulls every frame toward mid-grey, so this is what
+    /// tells a dim room from a bright one.
+    pub raw_brightness: f32,
 }
 
 impl Frame {
-    /// Average pixel brightness (0.0–255.0).
+    /// Average pixel brightness of `data` (0.0–255.0).
     pub fn avg_brightness(&self) -> f32 {
-        if self.data.is_empty() {
-            return 0.0;
-        }
-        self.data.iter().map(|&b| b as f32).sum::<f32>() / self.data.len() as f32
+        mean_brightness(&self.data)
+    }
+}
+
+/// Average brightness of a grayscale buffer (0.0–255.0); 0 when empty.
+pub fn mean_brightness(gray: &[u8]) -> f32 {
+    if gray.is_empty() {
+        return 0.0;
     }
+    gray.iter().map(|&b| b as u64).sum::<u64>() as f32 / gray.len() as f32
 }
 
 /// Convert packed YUYV (4:2:2) to grayscale by extracting the Y channel.
@@ -37,6 +46,19 @@ pub fn yuyv_to_grayscale(yuyv: &[u8], width: u32, height: u32) -> Result<Vec<u8>
     Ok(yuyv[..expected].iter().step_by(2).copied().collect())
 }
 
+/// Number of equal-width buckets in [`brightness_histogram`].
+pub const HISTOGRAM_BUCKETS: usize = 8;
+
+/// Count pixels in [`HISTOGRAM_BUCKETS`] equal brightness buckets
+/// (0–31, 32–63, …, 224–255).
+pub fn brightness_histogram(gray: &[u8]) -> [usize; HISTOGRAM_BUCKETS] {
+    let mut buckets = [0usize; HISTOGRAM_BUCKETS];
+    for &p in gray {
+        buckets[(p >> 5) as usize] += 1;
+    }
+    buckets
+}
+
 /// Check if a frame is dark using an 8-bucket histogram.
 ///
 /// Returns true if >95% of pixels fall in the darkest bucket (0–31).
@@ -44,10 +66,96 @@ pub fn is_dark_frame(gray: &[u8], threshold_pct: f32) -> bool {
     if gray.is_empty() {
         return true;
     }
-    let dark_count = gray.iter().filter(|&&p| p < 32).count();
+    let dark_count = brightness_histogram(gray)[0];
     (dark_count as f32 / gray.len() as f32) > threshold_pct
 }
 
+/// Overall lighting judgement from [`exposure_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Exposure {
+    Ok,
+    /// Most of the image sits in the darkest bucket — the emitter is off or
+    /// the room is too dark for the sensor.
+    TooDark,
+    /// A large share of the image is near saturation.
+    TooBright,
+    /// Bright and dark regions both dominate, typically a window or lamp
+    /// behind the user.
+    Backlit,
+    /// No pixels were captured.
+    NoData,
+}
+
+/// Brightness distribution over a set of raw (not contrast-enhanced) frames.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExposureStats {
+    pub frames: usize,
+    /// Share of pixels in each [`brightness_histogram`] bucket, in percent.
+    pub histogram_pct: [f32; HISTOGRAM_BUCKETS],
+    /// Average pixel brightness (0.0–255.0).
+    pub mean_brightness: f32,
+    /// Pixels in the darkest bucket (the one dark-frame skipping counts), in percent.
+    pub underexposed_pct: f32,
+    /// Pixels in the brightest bucket (224–255), in percent.
+    pub overexposed_pct: f32,
+    pub verdict: Exposure,
+}
+
+/// Summarize the brightness of `frames` for lighting diagnostics.
+///
+/// Pass raw frames: CLAHE stretches every frame toward a flat histogram and
+/// hides the exposure problem being diagnosed.
+pub fn exposure_stats(frames: &[Frame]) -> ExposureStats {
+    let mut counts = [0usize; HISTOGRAM_BUCKETS];
+    let mut sum = 0u64;
+    let mut pixels = 0usize;
+    for frame in frames {
+        for (total, n) in counts.iter_mut().zip(brightness_histogram(&frame.data)) {
+            *total += n;
+        }
+        sum += frame.data.iter().map(|&p| p as u64).sum::<u64>();
+        pixels += frame.data.len();
+    }
+
+    let pct = |n: usize| {
+        if pixels == 0 {
+            0.0
+        } else {
+            n as f32 * 100.0 / pixels as f32
+        }
+    };
+    let histogram_pct = counts.map(pct);
+    let underexposed_pct = histogram_pct[0];
+    let overexposed_pct = histogram_pct[HISTOGRAM_BUCKETS - 1];
+    let mean_brightness = if pixels == 0 {
+        0.0
+    } else {
+        sum as f32 / pixels as f32
+    };
+
+    let verdict = if pixels == 0 {
+        Exposure::NoData
+    } else if underexposed_pct >= 25.0 && overexposed_pct >= 10.0 {
+        Exposure::Backlit
+    } else if underexposed_pct > 60.0 {
+        Exposure::TooDark
+    } else if overexposed_pct > 25.0 {
+        Exposure::TooBright
+    } else {
+        Exposure::Ok
+    };
+
+    ExposureStats {
+        frames: frames.len(),
+        histogram_pct,
+        mean_brightness,
+        underexposed_pct,
+        overexposed_pct,
+        verdict,
+    }
+}
+
 /// Apply Contrast-Limited Adaptive Histogram Equalization (CLAHE) in-place.
 ///
 /// Divides the image into a grid of tiles, computes a clipped histogram
@@ -223,6 +331,79 @@ mod tests {
         assert!(!is_dark_frame(&gray, 0.95));
     }
 
+    fn frame_of(data: Vec<u8>) -> Frame {
+        Frame {
+            width: data.len() as u32,
+            height: 1,
+            raw_brightness: mean_brightness(&data),
+            data,
+            timestamp: std::time::Instant::now(),
+            sequence: 0,
+            is_dark: false,
+        }
+    }
+
+    #[test]
+    fn test_brightness_histogram_bucket_edges() {
+        let gray = [0, 31, 32, 63, 128, 223, 224, 255];
+        assert_eq!(brightness_histogram(&gray), [2, 2, 0, 0, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_exposure_stats_uniform_midtone() {
+        let frames = vec![frame_of(vec![128; 500]), frame_of(vec![128; 500])];
+        let stats = exposure_stats(&frames);
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.histogram_pct[4], 100.0);
+        assert!((stats.mean_brightness - 128.0).abs() < 1e-3);
+        assert_eq!(stats.underexposed_pct, 0.0);
+        assert_eq!(stats.overexposed_pct, 0.0);
+        assert_eq!(stats.verdict, Exposure::Ok);
+    }
+
+    #[test]
+    fn test_exposure_stats_classifies_lighting() {
+        // 80% black: too dark.
+        let mut dark = vec![5u8; 800];
+        dark.extend(vec![100u8; 200]);
+        let stats = exposure_stats(&[frame_of(dark)]);
+        assert!((stats.underexposed_pct - 80.0).abs() < 1e-3);
+        assert_eq!(stats.verdict, Exposure::TooDark);
+
+        // 40% saturated: too bright.
+        let mut bright = vec![250u8; 400];
+        bright.extend(vec![120u8; 600]);
+        let stats = exposure_stats(&[frame_of(bright)]);
+        assert!((stats.overexposed_pct - 40.0).abs() < 1e-3);
+        assert_eq!(stats.verdict, Exposure::TooBright);
+
+        // Bright window behind a dark subject: backlit.
+        let mut backlit = vec![10u8; 500];
+        backlit.extend(vec![240u8; 300]);
+        backlit.extend(vec![128u8; 200]);
+        let stats = exposure_stats(&[frame_of(backlit)]);
+        assert!((stats.underexposed_pct - 50.0).abs() < 1e-3);
+        assert!((stats.overexposed_pct - 30.0).abs() < 1e-3);
+        assert_eq!(stats.verdict, Exposure::Backlit);
+    }
+
+    #[test]
+    fn test_exposure_stats_pools_frames_by_pixel() {
+        // One black and one white frame of equal size average to mid-grey.
+        let stats = exposure_stats(&[frame_of(vec![0; 100]), frame_of(vec![255; 100])]);
+        assert_eq!(stats.histogram_pct[0], 50.0);
+        assert_eq!(stats.histogram_pct[7], 50.0);
+        assert!((stats.mean_brightness - 127.5).abs() < 1e-3);
+        assert_eq!(stats.verdict, Exposure::Backlit);
+    }
+
+    #[test]
+    fn test_exposure_stats_empty() {
+        let stats = exposure_stats(&[]);
+        assert_eq!(stats.frames, 0);
+        assert_eq!(stats.verdict, Exposure::NoData);
+    }
+
     #[test]
     fn test_clahe_increases_contrast() {
         // Low-contrast 16x16 image: all pixels between 100–110
@@ -8,7 +8,7 @@ pub mod frame;
 pub mod ir_emitter;
 pub mod quirks;
 
-pub use camera::{Camera, CameraError, PixelFormat};
-pub use frame::Frame;
+pub use camera::{Camera, CameraError, CaptureContext, PixelFormat};
+pub use frame::{exposure_stats, Exposure, ExposureStats, Frame};
 pub use ir_emitter::{EmitterError, IrEmitter};
 pub use quirks::{get_driver, is_ipu6_camera, CameraQuirk};
@@ -89,16 +89,21 @@ pub fn is_ipu6_camera(device_path: &str) -> bool {
         .unwrap_or(false)
 }
 
-/// Read USB VID:PID from sysfs for a `/dev/videoN` device.
-///
-/// Returns `None` if the device is not USB or sysfs is unavailable.
-pub fn get_usb_ids(device_path: &str) -> Option<(u16, u16)> {
+/// Resolve the sysfs USB interface directory behind a `/dev/videoN` device.
+fn usb_interface_dir(device_path: &str) -> Option<std::path::PathBuf> {
     // /dev/video2 → "video2"
     let dev_name = std::path::Path::new(device_path).file_name()?.to_str()?;
     // /sys/class/video4linux/video2/device is a symlink to the USB interface dir
     let device_link = format!("/sys/class/video4linux/{dev_name}/device");
+    std::fs::canonicalize(device_link).ok()
+}
+
+/// Read USB VID:PID from sysfs for a `/dev/videoN` device.
+///
+/// Returns `None` if the device is not USB or sysfs is unavailable.
+pub fn get_usb_ids(device_path: &str) -> Option<(u16, u16)> {
     // Resolve: interface dir → parent = USB device dir
-    let interface_dir = std::fs::canonicalize(&device_link).ok()?;
+    let interface_dir = usb_interface_dir(device_path)?;
     let usb_device_dir = interface_dir.parent()?;
 
     let vid_str = std::fs::read_to_string(usb_device_dir.join("idVendor")).ok()?;
@@ -108,3 +113,18 @@ pub fn get_usb_ids(device_path: &str) -> Option<(u16, u16)> {
     let pid = u16::from_str_radix(pid_str.trim(), 16).ok()?;
     Some((vid, pid))
 }
+
+/// Stable identity of a USB camera function: `usb:VVVV:PPPP.I` (vendor,
+/// product, interface number).
+///
+/// Unlike the device path it survives re-enumeration as another
+/// `/dev/videoN`, and the interface number tells apart the RGB and IR
+/// functions of a combined camera module that share one VID:PID. Returns
+/// `None` if the device is not USB or sysfs is unavailable.
+pub fn get_device_id(device_path: &str) -> Option<String> {
+    let (vid, pid) = get_usb_ids(device_path)?;
+    let interface =
+        std::fs::read_to_string(usb_interface_dir(device_path)?.join("bInterfaceNumber")).ok()?;
+    let interface = u8::from_str_radix(interface.trim(), 16).ok()?;
+    Some(format!("usb:{vid:04x}:{pid:04x}.{interface}"))
+}
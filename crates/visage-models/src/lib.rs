@@ -4,31 +4,75 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
-/// Model file descriptor: URL, expected filename, SHA-256 checksum, human-readable size.
+/// Model file descriptor: URL, expected filename, SHA-256 checksum, size.
+#[derive(Debug)]
 pub struct ModelFile {
     pub name: &'static str,
     pub url: &'static str,
+    /// Other URLs serving the same file, tried in order after `url`. Every
+    /// source is checked against `sha256`, so a mirror need not be trusted.
+    pub mirrors: &'static [&'static str],
     pub sha256: &'static str,
+    pub size_bytes: u64,
     pub size_display: &'static str,
 }
 
+impl ModelFile {
+    /// URLs to download this file from, in the order to try them.
+    ///
+    /// `mirror_base` is a site the user picked (e.g. a company proxy) that
+    /// serves the files as `<mirror_base>/<name>`. It goes first, followed
+    /// by `url` and then `mirrors`.
+    pub fn sources(&self, mirror_base: Option<&str>) -> Vec<String> {
+        mirror_base
+            .map(|base| format!("{}/{}", base.trim_end_matches('/'), self.name))
+            .into_iter()
+            .chain(std::iter::once(self.url.to_string()))
+            .chain(self.mirrors.iter().map(|url| url.to_string()))
+            .collect()
+    }
+}
+
 // Checksums verified from HuggingFace Git LFS pointer files (oid sha256: field).
 // Source: https://huggingface.co/public-data/insightface/raw/main/models/buffalo_l/
 pub const MODELS: &[ModelFile] = &[
     ModelFile {
         name: "det_10g.onnx",
         url: "https://huggingface.co/public-data/insightface/resolve/main/models/buffalo_l/det_10g.onnx",
+        mirrors: &[],
         sha256: "5838f7fe053675b1c7a08b633df49e7af5495cee0493c7dcf6697200b85b5b91",
+        size_bytes: 16_923_827,
         size_display: "16 MB",
     },
     ModelFile {
         name: "w600k_r50.onnx",
         url: "https://huggingface.co/public-data/insightface/resolve/main/models/buffalo_l/w600k_r50.onnx",
+        mirrors: &[],
         sha256: "4c06341c33c2ca1f86781dab0e829f88ad5b64be9fba56e56bc9ebdefc619e43",
+        size_bytes: 174_383_860,
         size_display: "166 MB",
     },
 ];
 
+/// The [`MODELS`] entry for the file called `name`, e.g. `"det_10g.onnx"`.
+pub fn model_by_name(name: &str) -> Option<&'static ModelFile> {
+    MODELS.iter().find(|m| m.name == name)
+}
+
+/// Bytes `visage setup` downloads when no model is present yet.
+pub fn total_download_bytes() -> u64 {
+    MODELS.iter().map(|m| m.size_bytes).sum()
+}
+
+/// Version tag of the recognizer in [`MODELS`] (its file stem). Templates
+/// enrolled under any other version need re-enrolling once these files are
+/// installed.
+pub const RECOGNIZER_VERSION: &str = "w600k_r50";
+
+/// First byte of a serialized ONNX `ModelProto`: the varint tag of field 1,
+/// `ir_version`, which protobuf writers emit first.
+const ONNX_IR_VERSION_TAG: u8 = 0x08;
+
 #[derive(Error, Debug)]
 pub enum ModelIntegrityError {
     #[error("model file not found: {name} ({path})")]
@@ -57,17 +101,68 @@ pub enum ModelIntegrityError {
         expected: String,
         got: String,
     },
+
+    #[error("model file is empty: {path} — re-run `sudo visage setup`")]
+    Empty { path: PathBuf },
+
+    #[error("model file is not an ONNX model: {path} — re-run `sudo visage setup`")]
+    NotOnnx { path: PathBuf },
+
+    #[error(
+        "model file {path} is {got} bytes, expected {expected} (interrupted copy?) — re-run \
+         `sudo visage setup`"
+    )]
+    WrongSize {
+        path: PathBuf,
+        expected: u64,
+        got: u64,
+    },
+
+    #[error("failed to read model manifest {path}: {source}")]
+    ManifestRead {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid model manifest {path}: {reason}")]
+    ManifestInvalid { path: PathBuf, reason: String },
 }
 
 /// Compute SHA-256 hex digest of a file.
 pub fn sha256_file_hex(path: &Path) -> Result<String, ModelIntegrityError> {
+    sha256_file_hex_with_progress(path, &mut |_, _| {})
+}
+
+/// Most calls [`sha256_file_hex_with_progress`] makes for a file of known size.
+const PROGRESS_REPORTS: u64 = 100;
+
+/// Report interval when the file size is unknown.
+const PROGRESS_UNSIZED_STEP: u64 = 1 << 20;
+
+/// Like [`sha256_file_hex`], calling `progress(bytes_read, total)` as it goes
+/// so a frontend can show how far hashing a large model has got.
+///
+/// `total` is the size from `fs::metadata`, `None` if that is unavailable.
+/// For a file of known size the callback fires at most 100 times, once per
+/// hundredth of the file; the last call always reports the full length read.
+pub fn sha256_file_hex_with_progress(
+    path: &Path,
+    progress: &mut dyn FnMut(u64, Option<u64>),
+) -> Result<String, ModelIntegrityError> {
     let mut file = fs::File::open(path).map_err(|source| ModelIntegrityError::Open {
         path: path.to_path_buf(),
         source,
     })?;
+    let total = file.metadata().ok().map(|m| m.len());
+    let step = total.map_or(PROGRESS_UNSIZED_STEP, |len| {
+        len.div_ceil(PROGRESS_REPORTS).max(1)
+    });
 
     let mut hasher = Sha256::new();
     let mut buf = [0u8; 8192];
+    let mut read = 0u64;
+    let mut reported = None;
 
     loop {
         let n = file
@@ -80,11 +175,73 @@ pub fn sha256_file_hex(path: &Path) -> Result<String, ModelIntegrityError> {
             break;
         }
         hasher.update(&buf[..n]);
+        read += n as u64;
+        if read - reported.unwrap_or(0) >= step {
+            progress(read, total);
+            reported = Some(read);
+        }
+    }
+    if reported != Some(read) {
+        progress(read, total);
     }
 
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+/// Cheap structural check of a model file before ONNX Runtime parses it.
+///
+/// Catches what a half-finished copy leaves behind: an empty file, one that
+/// does not start with an ONNX protobuf header, or, for files named in
+/// [`MODELS`], one whose size differs from the manifest's. Unlike
+/// [`verify_file_sha256`] this reads only the first two bytes.
+pub fn validate_model_file(path: &Path) -> Result<(), ModelIntegrityError> {
+    let manifest = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(model_by_name);
+    let mut file = fs::File::open(path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::NotFound => ModelIntegrityError::MissingModel {
+            name: manifest.map_or("model", |m| m.name),
+            path: path.to_path_buf(),
+        },
+        _ => ModelIntegrityError::Open {
+            path: path.to_path_buf(),
+            source,
+        },
+    })?;
+    let read_err = |source| ModelIntegrityError::Read {
+        path: path.to_path_buf(),
+        source,
+    };
+    let len = file.metadata().map_err(read_err)?.len();
+    if len == 0 {
+        return Err(ModelIntegrityError::Empty {
+            path: path.to_path_buf(),
+        });
+    }
+
+    // The tag, then a small non-zero ir_version in a single varint byte.
+    let mut header = [0u8; 2];
+    let n = file.read(&mut header).map_err(read_err)?;
+    if n < header.len() || header[0] != ONNX_IR_VERSION_TAG || !(1..0x80).contains(&header[1]) {
+        return Err(ModelIntegrityError::NotOnnx {
+            path: path.to_path_buf(),
+        });
+    }
+
+    if let Some(model) = manifest {
+        if len != model.size_bytes {
+            return Err(ModelIntegrityError::WrongSize {
+                path: path.to_path_buf(),
+                expected: model.size_bytes,
+                got: len,
+            });
+        }
+    }
+
+    Ok(())
+}
+
 pub fn verify_file_sha256(
     name: &'static str,
     path: &Path,
@@ -110,19 +267,567 @@ pub fn verify_file_sha256(
     Ok(())
 }
 
-pub fn verify_models_dir(model_dir: &Path) -> Result<(), ModelIntegrityError> {
-    for model in MODELS {
-        let path = model_dir.join(model.name);
-        verify_file_sha256(model.name, &path, model.sha256)?;
+/// Check `path` against `model`'s checksum. A mismatch caused by an empty,
+/// non-ONNX or wrongly sized file is reported as that instead, since it
+/// usually means an interrupted copy.
+pub fn verify_model(model: &ModelFile, path: &Path) -> Result<(), ModelIntegrityError> {
+    verify_file_sha256(model.name, path, model.sha256).map_err(|e| match e {
+        ModelIntegrityError::ChecksumMismatch { .. } => {
+            validate_model_file(path).err().unwrap_or(e)
+        }
+        e => e,
+    })
+}
+
+/// State of one manifest file in a model directory; see [`audit_models_dir`].
+#[derive(Debug)]
+pub enum ModelStatus {
+    Ok,
+    Missing,
+    ChecksumMismatch {
+        expected: String,
+        got: String,
+    },
+    /// Present but unusable for another reason: unreadable, empty, not ONNX
+    /// or the wrong size.
+    Damaged(ModelIntegrityError),
+}
+
+impl std::fmt::Display for ModelStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => f.write_str("ok"),
+            Self::Missing => f.write_str("missing"),
+            Self::ChecksumMismatch { expected, got } => {
+                write!(f, "checksum mismatch (expected {expected}, got {got})")
+            }
+            Self::Damaged(e) => write!(f, "damaged: {e}"),
+        }
     }
+}
 
-    Ok(())
+impl From<Result<(), ModelIntegrityError>> for ModelStatus {
+    fn from(result: Result<(), ModelIntegrityError>) -> Self {
+        match result {
+            Ok(()) => Self::Ok,
+            Err(ModelIntegrityError::MissingModel { .. }) => Self::Missing,
+            Err(ModelIntegrityError::ChecksumMismatch { expected, got, .. }) => {
+                Self::ChecksumMismatch { expected, got }
+            }
+            Err(e) => Self::Damaged(e),
+        }
+    }
+}
+
+/// Environment variable naming a site checksum manifest; see [`Manifest`].
+pub const MANIFEST_ENV: &str = "VISAGE_MODEL_MANIFEST";
+
+/// The checksums model files are verified against.
+///
+/// By default these are the built-in [`MODELS`] checksums. Air-gapped sites
+/// that stage their own exports of the models can pin them instead with a
+/// manifest file in `sha256sum` format (`<hex digest>  <file name>` per
+/// line, `#` comments allowed). It must list every file in [`MODELS`], so
+/// no model ever loads unchecked, and must not be writable by group or
+/// others. Files are still verified, against the site's digests.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// The site manifest, `None` for the built-in checksums.
+    source: Option<PathBuf>,
+    entries: Vec<(&'static ModelFile, String)>,
+}
+
+impl Manifest {
+    /// The checksums built into this release.
+    pub fn builtin() -> Self {
+        Self::of(MODELS)
+    }
+
+    fn of(models: &'static [ModelFile]) -> Self {
+        Self {
+            source: None,
+            entries: models.iter().map(|m| (m, m.sha256.to_string())).collect(),
+        }
+    }
+
+    /// The site manifest at `path`, or the built-in checksums for `None`.
+    pub fn load_or_builtin(path: Option<&Path>) -> Result<Self, ModelIntegrityError> {
+        path.map_or_else(|| Ok(Self::builtin()), Self::load)
+    }
+
+    /// The manifest [`MANIFEST_ENV`] names, or the built-in checksums when it
+    /// is unset.
+    pub fn from_env() -> Result<Self, ModelIntegrityError> {
+        match std::env::var_os(MANIFEST_ENV) {
+            Some(path) => Self::load(Path::new(&path)),
+            None => Ok(Self::builtin()),
+        }
+    }
+
+    /// Read a site manifest covering [`MODELS`]. The file must be owned by
+    /// root and not writable by group or others.
+    pub fn load(path: &Path) -> Result<Self, ModelIntegrityError> {
+        Self::load_for(path, MODELS, 0)
+    }
+
+    /// [`Manifest::load`] for `models`, trusting only files owned by `owner`.
+    fn load_for(
+        path: &Path,
+        models: &'static [ModelFile],
+        owner: u32,
+    ) -> Result<Self, ModelIntegrityError> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let invalid = |reason: String| ModelIntegrityError::ManifestInvalid {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let read_err = |source| ModelIntegrityError::ManifestRead {
+            path: path.to_path_buf(),
+            source,
+        };
+        let meta = fs::metadata(path).map_err(read_err)?;
+        if meta.uid() != owner {
+            return Err(invalid(format!(
+                "owned by uid {}, not uid {owner}",
+                meta.uid()
+            )));
+        }
+        let mode = meta.permissions().mode();
+        if mode & 0o022 != 0 {
+            return Err(invalid(format!(
+                "writable by group or others (mode {:o})",
+                mode & 0o777
+            )));
+        }
+        let text = fs::read_to_string(path).map_err(read_err)?;
+
+        let mut listed: Vec<(&str, &str)> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (digest, name) = line
+                .split_once(char::is_whitespace)
+                .map(|(d, n)| (d, n.trim_start().trim_start_matches('*')))
+                .ok_or_else(|| invalid(format!("line {}: expected `<sha256>  <file>`", n + 1)))?;
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid(format!(
+                    "line {}: '{digest}' is not a SHA-256 digest",
+                    n + 1
+                )));
+            }
+            if !models.iter().any(|m| m.name == name) {
+                return Err(invalid(format!(
+                    "line {}: unknown model file '{name}'",
+                    n + 1
+                )));
+            }
+            if listed.iter().any(|&(_, seen)| seen == name) {
+                return Err(invalid(format!("line {}: '{name}' listed twice", n + 1)));
+            }
+            listed.push((digest, name));
+        }
+
+        let entries = models
+            .iter()
+            .map(|model| {
+                listed
+                    .iter()
+                    .find(|&&(_, name)| name == model.name)
+                    .map(|&(digest, _)| (model, digest.to_ascii_lowercase()))
+                    .ok_or_else(|| invalid(format!("no checksum for {}", model.name)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            source: Some(path.to_path_buf()),
+            entries,
+        })
+    }
+
+    /// The site manifest in use, `None` for the built-in checksums.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Expected digest of `model`, `None` if this manifest does not cover it.
+    pub fn sha256(&self, model: &ModelFile) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(m, _)| m.name == model.name)
+            .map(|(_, digest)| digest.as_str())
+    }
+
+    /// Check `path` against this manifest's digest for `model`, as
+    /// [`verify_model`] does against the built-in one. A site's export may
+    /// differ in size from the built-in file, so only the built-in
+    /// checksums report a size mismatch.
+    pub fn verify(&self, model: &ModelFile, path: &Path) -> Result<(), ModelIntegrityError> {
+        let Some(expected) = self.sha256(model) else {
+            return Err(ModelIntegrityError::ManifestInvalid {
+                path: self.source.clone().unwrap_or_default(),
+                reason: format!("no checksum for {}", model.name),
+            });
+        };
+        verify_file_sha256(model.name, path, expected).map_err(|e| match e {
+            ModelIntegrityError::ChecksumMismatch { .. } => match validate_model_file(path) {
+                Err(ModelIntegrityError::WrongSize { .. }) if self.source.is_some() => e,
+                Err(problem) => problem,
+                Ok(()) => e,
+            },
+            e => e,
+        })
+    }
+
+    /// Check each file in `model_dir`, lazily and in manifest order.
+    fn check<'a>(
+        &'a self,
+        model_dir: &'a Path,
+    ) -> impl Iterator<Item = (&'static ModelFile, Result<(), ModelIntegrityError>)> + 'a {
+        self.entries
+            .iter()
+            .map(move |&(model, _)| (model, self.verify(model, &model_dir.join(model.name))))
+    }
+
+    /// Every file's status, instead of stopping at the first problem.
+    pub fn audit(&self, model_dir: &Path) -> Vec<(&'static ModelFile, ModelStatus)> {
+        self.check(model_dir)
+            .map(|(model, result)| (model, result.into()))
+            .collect()
+    }
+
+    /// The first problem with the files, if any. Stops there, so a missing
+    /// detector does not cost hashing the recognizer.
+    pub fn verify_dir(&self, model_dir: &Path) -> Result<(), ModelIntegrityError> {
+        self.check(model_dir)
+            .map(|(_, result)| result)
+            .find(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+}
+
+/// Check every file in [`MODELS`] against the manifest [`MANIFEST_ENV`]
+/// names, or the built-in checksums, reporting each one's status instead of
+/// stopping at the first problem.
+pub fn audit_models_dir(
+    model_dir: &Path,
+) -> Result<Vec<(&'static ModelFile, ModelStatus)>, ModelIntegrityError> {
+    Ok(Manifest::from_env()?.audit(model_dir))
+}
+
+/// The first problem with the files in [`MODELS`], checked against the
+/// manifest [`MANIFEST_ENV`] names or the built-in checksums, if any. Stops
+/// there; use [`audit_models_dir`] for the full picture.
+pub fn verify_models_dir(model_dir: &Path) -> Result<(), ModelIntegrityError> {
+    Manifest::from_env()?.verify_dir(model_dir)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn recognizer_version_names_a_manifest_file() {
+        let file = format!("{RECOGNIZER_VERSION}.onnx");
+        assert!(model_by_name(&file).is_some());
+    }
+
+    #[test]
+    fn model_lookup_and_download_total() {
+        let detector = model_by_name("det_10g.onnx").unwrap();
+        assert_eq!(detector.size_bytes, 16_923_827);
+        assert!(model_by_name("det_10g").is_none());
+        assert!(model_by_name("").is_none());
+        assert_eq!(total_download_bytes(), 16_923_827 + 174_383_860);
+    }
+
+    #[test]
+    fn sources_put_the_chosen_mirror_first_then_the_manifest_order() {
+        let model = ModelFile {
+            name: "det_10g.onnx",
+            url: "https://primary.example/det_10g.onnx",
+            mirrors: &[
+                "https://a.example/det_10g.onnx",
+                "https://b.example/det_10g.onnx",
+            ],
+            sha256: "",
+            size_bytes: 0,
+            size_display: "",
+        };
+        assert_eq!(
+            model.sources(None),
+            [
+                "https://primary.example/det_10g.onnx",
+                "https://a.example/det_10g.onnx",
+                "https://b.example/det_10g.onnx",
+            ]
+        );
+        assert_eq!(
+            model.sources(Some("https://proxy.corp/visage/")),
+            [
+                "https://proxy.corp/visage/det_10g.onnx",
+                "https://primary.example/det_10g.onnx",
+                "https://a.example/det_10g.onnx",
+                "https://b.example/det_10g.onnx",
+            ]
+        );
+    }
+
+    fn scratch_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "visage-models-test-{tag}-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn validate_model_file_rejects_empty_and_non_onnx() {
+        let dir = scratch_dir("validate");
+        let path = dir.join("custom.onnx");
+        let err = validate_model_file(&path).unwrap_err();
+        assert!(
+            matches!(err, ModelIntegrityError::MissingModel { .. }),
+            "{err}"
+        );
+
+        fs::write(&path, b"").unwrap();
+        let err = validate_model_file(&path).unwrap_err();
+        assert!(matches!(err, ModelIntegrityError::Empty { .. }), "{err}");
+        assert!(err.to_string().contains("visage setup"), "{err}");
+
+        // An HTML error page saved in place of the model.
+        fs::write(&path, b"<!DOCTYPE html>").unwrap();
+        let err = validate_model_file(&path).unwrap_err();
+        assert!(matches!(err, ModelIntegrityError::NotOnnx { .. }), "{err}");
+        fs::write(&path, [ONNX_IR_VERSION_TAG]).unwrap();
+        let err = validate_model_file(&path).unwrap_err();
+        assert!(matches!(err, ModelIntegrityError::NotOnnx { .. }), "{err}");
+
+        // Files outside the manifest only need a plausible header.
+        fs::write(&path, [ONNX_IR_VERSION_TAG, 8, 0x12, 0x07]).unwrap();
+        validate_model_file(&path).unwrap();
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn validate_model_file_rejects_truncated_manifest_model() {
+        let dir = scratch_dir("truncated");
+        let path = dir.join(MODELS[0].name);
+        let mut partial = vec![0u8; 4096];
+        partial[..2].copy_from_slice(&[ONNX_IR_VERSION_TAG, 8]);
+        fs::write(&path, &partial).unwrap();
+
+        let err = validate_model_file(&path).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ModelIntegrityError::WrongSize { expected, got: 4096, .. }
+                    if expected == MODELS[0].size_bytes
+            ),
+            "{err}"
+        );
+        assert!(err.to_string().contains(MODELS[0].name), "{err}");
+        // The checksum check names the truncation rather than the digests.
+        let err = verify_model(&MODELS[0], &path).unwrap_err();
+        assert!(
+            matches!(err, ModelIntegrityError::WrongSize { got: 4096, .. }),
+            "{err}"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// A manifest whose first two files hold "good model".
+    static AUDIT_MODELS: [ModelFile; 3] = {
+        const GOOD: &str = "386babe658361883a51bad7442f3fcfbc1d46466d360253a72a000b3781adf64";
+        const fn entry(name: &'static str) -> ModelFile {
+            ModelFile {
+                name,
+                url: "",
+                mirrors: &[],
+                sha256: GOOD,
+                size_bytes: 10,
+                size_display: "",
+            }
+        }
+        [
+            entry("good.onnx"),
+            entry("missing.onnx"),
+            entry("corrupt.onnx"),
+        ]
+    };
+
+    #[test]
+    fn audit_reports_every_file_not_just_the_first_problem() {
+        let dir = scratch_dir("audit");
+        fs::write(dir.join("good.onnx"), b"good model").unwrap();
+        fs::write(
+            dir.join("corrupt.onnx"),
+            [ONNX_IR_VERSION_TAG, 8, 0x12, 0x07],
+        )
+        .unwrap();
+
+        let manifest = Manifest::of(&AUDIT_MODELS);
+        let report = manifest.audit(&dir);
+        let names: Vec<&str> = report.iter().map(|(m, _)| m.name).collect();
+        assert_eq!(names, ["good.onnx", "missing.onnx", "corrupt.onnx"]);
+        assert!(matches!(report[0].1, ModelStatus::Ok), "{}", report[0].1);
+        assert!(
+            matches!(report[1].1, ModelStatus::Missing),
+            "{}",
+            report[1].1
+        );
+        assert!(
+            matches!(
+                &report[2].1,
+                ModelStatus::ChecksumMismatch { expected, got }
+                    if expected == AUDIT_MODELS[2].sha256 && got != expected
+            ),
+            "{}",
+            report[2].1
+        );
+
+        // The first-error check stops at the missing file.
+        assert!(matches!(
+            manifest.verify_dir(&dir),
+            Err(ModelIntegrityError::MissingModel {
+                name: "missing.onnx",
+                ..
+            })
+        ));
+
+        // The real manifest in an empty directory: everything missing.
+        let report = Manifest::builtin().audit(&dir);
+        assert_eq!(report.len(), MODELS.len());
+        assert!(report
+            .iter()
+            .all(|(_, status)| matches!(status, ModelStatus::Missing)));
+        assert!(matches!(
+            Manifest::builtin().verify_dir(&dir),
+            Err(ModelIntegrityError::MissingModel { .. })
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Write a site manifest with owner-only write permission.
+    fn write_manifest(path: &Path, text: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, text).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o644)).unwrap();
+    }
+
+    /// The owner of `path`, standing in for root in tests.
+    fn owner_of(path: &Path) -> u32 {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).unwrap().uid()
+    }
+
+    #[test]
+    fn site_manifest_pins_its_own_checksums() {
+        let dir = scratch_dir("site-manifest");
+        let export = [ONNX_IR_VERSION_TAG, 8, b's', b'i', b't', b'e'];
+        for model in &AUDIT_MODELS {
+            fs::write(dir.join(model.name), export).unwrap();
+        }
+        let digest = sha256_file_hex(&dir.join("good.onnx")).unwrap();
+        assert_ne!(digest, AUDIT_MODELS[0].sha256);
+        // The built-in checksums reject the site's export.
+        assert!(matches!(
+            Manifest::of(&AUDIT_MODELS).verify_dir(&dir),
+            Err(ModelIntegrityError::ChecksumMismatch { .. })
+        ));
+
+        let path = dir.join("manifest.sha256");
+        write_manifest(
+            &path,
+            &format!(
+                "# approved exports\n{digest}  good.onnx\n{digest} *missing.onnx\n\n{}  corrupt.onnx\n",
+                digest.to_uppercase()
+            ),
+        );
+        let manifest = Manifest::load_for(&path, &AUDIT_MODELS, owner_of(&path)).unwrap();
+        assert_eq!(manifest.source(), Some(path.as_path()));
+        manifest.verify_dir(&dir).unwrap();
+
+        // Tampering with a file is still caught, and not excused as a
+        // size difference from the built-in file.
+        fs::write(dir.join("corrupt.onnx"), [ONNX_IR_VERSION_TAG, 8, b'x']).unwrap();
+        let report = manifest.audit(&dir);
+        assert!(matches!(report[0].1, ModelStatus::Ok), "{}", report[0].1);
+        assert!(
+            matches!(
+                &report[2].1,
+                ModelStatus::ChecksumMismatch { expected, .. } if *expected == digest
+            ),
+            "{}",
+            report[2].1
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn site_manifest_must_cover_every_model_and_be_pinned() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("bad-manifest");
+        let path = dir.join("manifest.sha256");
+        let digest = "ab".repeat(32);
+        let load = |text: &str| {
+            write_manifest(&path, text);
+            Manifest::load_for(&path, &AUDIT_MODELS, owner_of(&path))
+                .unwrap_err()
+                .to_string()
+        };
+
+        // No checksums at all.
+        assert!(load("").contains("no checksum for good.onnx"));
+        assert!(
+            load(&format!("{digest}  good.onnx\n{digest}  missing.onnx\n"))
+                .contains("no checksum for corrupt.onnx")
+        );
+        assert!(load(&format!("{digest}  other.onnx\n")).contains("unknown model file"));
+        assert!(
+            load(&format!("{digest}  good.onnx\n{digest}  good.onnx\n")).contains("listed twice")
+        );
+        assert!(load("abc  good.onnx\n").contains("not a SHA-256 digest"));
+        assert!(load(&digest).contains("line 1"));
+
+        let complete =
+            format!("{digest}  good.onnx\n{digest}  missing.onnx\n{digest}  corrupt.onnx\n");
+        write_manifest(&path, &complete);
+        let owner = owner_of(&path);
+        Manifest::load_for(&path, &AUDIT_MODELS, owner).unwrap();
+        // A file owned by anyone else is refused however it is written.
+        let err = Manifest::load_for(&path, &AUDIT_MODELS, owner + 1).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("owned by uid {owner}, not uid {}", owner + 1)),
+            "{err}"
+        );
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+        let err = Manifest::load_for(&path, &AUDIT_MODELS, owner).unwrap_err();
+        assert!(
+            err.to_string().contains("writable by group or others"),
+            "{err}"
+        );
+
+        assert!(matches!(
+            Manifest::load(&dir.join("absent")),
+            Err(ModelIntegrityError::ManifestRead { .. })
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn verify_file_sha256_rejects_missing() {
         let tmp = std::env::temp_dir().join(format!(
@@ -176,6 +881,36 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn sha256_progress_reports_at_most_a_hundred_times() {
+        let dir = scratch_dir("progress");
+        let path = dir.join("model.onnx");
+        let len = 3 * 1024 * 1024 + 17;
+        fs::write(&path, vec![0x5au8; len]).unwrap();
+
+        let mut calls = Vec::new();
+        let digest =
+            sha256_file_hex_with_progress(&path, &mut |read, total| calls.push((read, total)))
+                .unwrap();
+        assert_eq!(digest, sha256_file_hex(&path).unwrap());
+        assert!(
+            (90..=100).contains(&calls.len()),
+            "{} progress calls",
+            calls.len()
+        );
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(calls.iter().all(|&(_, total)| total == Some(len as u64)));
+        assert_eq!(calls.last(), Some(&(len as u64, Some(len as u64))));
+
+        // A file smaller than one chunk gets a single, final report.
+        fs::write(&path, b"hello").unwrap();
+        let mut count = 0;
+        sha256_file_hex_with_progress(&path, &mut |_, _| count += 1).unwrap();
+        assert_eq!(count, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn verify_models_dir_reports_missing() {
         let dir = std::env::temp_dir().join(format!(
@@ -0,0 +1,196 @@
+//! Optional daemon features advertised to clients.
+//!
+//! Clients read `capabilities` from `Status` (or call `GetCapabilities`) to
+//! decide whether a newer call is available instead of probing for
+//! `UnknownMethod`. Each entry names the D-Bus member that provides it; a
+//! feature is listed here in the same change that adds its handler. A
+//! feature that depends on configuration is advertised only while enabled.
+
+use crate::config::Config;
+
+/// Version of the `Status` JSON layout. Bumped when a field is renamed,
+/// removed or changes meaning — adding fields does not bump it.
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// An optional feature and the D-Bus member clients call to use it.
+pub struct Capability {
+    /// Stable identifier clients test for.
+    pub name: &'static str,
+    /// Method on `org.freedesktop.Visage1` that implements the feature.
+    pub member: &'static str,
+}
+
+/// Every optional feature this build implements.
+pub const CAPABILITIES: &[Capability] = &[
+    Capability {
+        name: "stats",
+        member: "Stats",
+    },
+    Capability {
+        name: "verify_frame",
+        member: "VerifyFrame",
+    },
+    Capability {
+        name: "find_duplicate_enrollments",
+        member: "FindDuplicateEnrollments",
+    },
+    Capability {
+        name: "set_log_level",
+        member: "SetLogLevel",
+    },
+    Capability {
+        name: "model_pinning",
+        member: "SetModelPin",
+    },
+    Capability {
+        name: "enroll_other",
+        member: "EnrollOther",
+    },
+    Capability {
+        name: "exposure_report",
+        member: "ExposureReport",
+    },
+    Capability {
+        name: "camera_benchmark",
+        member: "CameraBenchmark",
+    },
+    Capability {
+        name: "engine_queue_status",
+        member: "EngineQueueStatus",
+    },
+    Capability {
+        name: "cancel_current",
+        member: "CancelCurrent",
+    },
+    // `Preview` issues the token `EnrollNow` redeems.
+    Capability {
+        name: "enroll_now",
+        member: "EnrollNow",
+    },
+    // `Verify`/`Enroll` accept `%XX`-encoded names; advertised only while
+    // `VISAGE_PERCENT_ENCODED_USERNAMES=1` (see `Capability::enabled`).
+    Capability {
+        name: "percent_encoded_usernames",
+        member: "Verify",
+    },
+    Capability {
+        name: "verify_detailed",
+        member: "VerifyDetailed",
+    },
+    Capability {
+        name: "verify_score",
+        member: "VerifyScore",
+    },
+    Capability {
+        name: "remove_all_models",
+        member: "RemoveAllModels",
+    },
+    Capability {
+        name: "dedupe",
+        member: "Dedupe",
+    },
+    // `PurgeOrphans` removes what `FindOrphans` reports.
+    Capability {
+        name: "orphans",
+        member: "FindOrphans",
+    },
+    // `RedeemToken` accepts the tokens `VerifyWithToken` issues.
+    Capability {
+        name: "verify_tokens",
+        member: "VerifyWithToken",
+    },
+    Capability {
+        name: "verify_grace",
+        member: "VerifyCached",
+    },
+    Capability {
+        name: "reload_models",
+        member: "ReloadModels",
+    },
+    // `SetPolicy` changes what `GetPolicy` reports.
+    Capability {
+        name: "verify_policy",
+        member: "GetPolicy",
+    },
+    Capability {
+        name: "verify_cancel",
+        member: "Cancel",
+    },
+    Capability {
+        name: "cancel_user",
+        member: "CancelUser",
+    },
+    Capability {
+        name: "typed_status",
+        member: "GetStatus",
+    },
+    // `VerifyFresh` answers from the same record as `VerifyCached`.
+    Capability {
+        name: "verify_fresh",
+        member: "VerifyFresh",
+    },
+    // `SetDeviceSettings` stores what `GetDeviceSettings` reports.
+    Capability {
+        name: "device_settings",
+        member: "GetDeviceSettings",
+    },
+    // `Restore` reads the bundles `Backup` writes.
+    Capability {
+        name: "backup",
+        member: "Backup",
+    },
+    Capability {
+        name: "identify",
+        member: "Identify",
+    },
+    Capability {
+        name: "set_model_label",
+        member: "SetModelLabel",
+    },
+];
+
+impl Capability {
+    /// Whether the daemon offers this feature under `config`.
+    pub fn enabled(&self, config: &Config) -> bool {
+        match self.name {
+            "percent_encoded_usernames" => config.percent_encoded_usernames,
+            _ => true,
+        }
+    }
+}
+
+/// Names of the capabilities enabled under `config`, in registration order.
+pub fn names(config: &Config) -> Vec<String> {
+    CAPABILITIES
+        .iter()
+        .filter(|c| c.enabled(config))
+        .map(|c| c.name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_capability_names_are_unique() {
+        let unique: HashSet<_> = CAPABILITIES.iter().map(|c| c.name).collect();
+        assert_eq!(unique.len(), CAPABILITIES.len());
+    }
+
+    #[test]
+    fn test_percent_encoded_usernames_follows_the_config() {
+        let advertised = |value| {
+            let config =
+                Config::from_pairs(&[("VISAGE_PERCENT_ENCODED_USERNAMES", value)]).unwrap();
+            names(&config).contains(&"percent_encoded_usernames".to_string())
+        };
+        assert!(!advertised("0"));
+        assert!(advertised("1"));
+
+        // Everything else is advertised either way.
+        let config = Config::from_pairs(&[]).unwrap();
+        assert_eq!(names(&config).len(), CAPABILITIES.len() - 1);
+    }
+}
@@ -0,0 +1,169 @@
+//! Time source for lockouts, grace periods and expiry.
+//!
+//! Wall-clock time jumps: NTP steps it, an administrator sets it, a laptop
+//! resumes with a stale RTC. A deadline stored as a wall-clock instant would
+//! then last hours too long or lapse at once. A [`Span`] therefore measures
+//! its length on the monotonic clock and keeps the wall-clock start only as
+//! an anchor for persistence and display.
+//!
+//! When a span is rebuilt from a persisted wall-clock start, the elapsed wall
+//! time is clamped to `[0, length]`. A backward jump can at most restart the
+//! span, never stretch it past its configured length. A forward jump can end
+//! it early, since nothing outlives a restart to tell the difference.
+//!
+//! The monotonic clock stops during suspend, so a span does not run down
+//! while the machine sleeps.
+
+use chrono::{DateTime, Utc};
+use std::time::{Duration, Instant};
+
+/// A simultaneous reading of the monotonic and wall clocks.
+#[derive(Debug, Clone, Copy)]
+pub struct Now {
+    pub mono: Instant,
+    pub wall: DateTime<Utc>,
+}
+
+/// Where time-based features read the time from.
+pub trait Clock: Send + Sync {
+    fn read(&self) -> Now;
+}
+
+/// The system's monotonic and real-time clocks.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn read(&self) -> Now {
+        Now {
+            mono: Instant::now(),
+            wall: Utc::now(),
+        }
+    }
+}
+
+/// A period of fixed length (a lockout, grace period or validity window)
+/// that runs on the monotonic clock.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    started_wall: DateTime<Utc>,
+    length: Duration,
+    deadline: Instant,
+}
+
+impl Span {
+    /// Start a span of `length` now.
+    pub fn start(now: Now, length: Duration) -> Self {
+        Self::restore(now.wall, length, now)
+    }
+
+    /// Rebuild a span that started at wall-clock `started_wall`, e.g. one
+    /// read back from storage. Elapsed time is clamped to `[0, length]`.
+    pub fn restore(started_wall: DateTime<Utc>, length: Duration, now: Now) -> Self {
+        let elapsed = (now.wall - started_wall)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+            .min(length);
+        Self {
+            started_wall,
+            length,
+            deadline: now.mono + (length - elapsed),
+        }
+    }
+
+    /// Time left, never more than the span's length.
+    pub fn remaining(&self, now: Now) -> Duration {
+        self.deadline
+            .saturating_duration_since(now.mono)
+            .min(self.length)
+    }
+
+    pub fn is_over(&self, now: Now) -> bool {
+        self.remaining(now).is_zero()
+    }
+
+    /// Wall-clock time the span ends, for messages. Derived from the
+    /// monotonic remainder, so it follows the current wall clock.
+    pub fn ends_at(&self, now: Now) -> DateTime<Utc> {
+        now.wall + chrono::Duration::from_std(self.remaining(now)).unwrap_or(chrono::Duration::MAX)
+    }
+
+    /// Wall-clock start, for persisting the span.
+    pub fn started_wall(&self) -> DateTime<Utc> {
+        self.started_wall
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A reading `mono` seconds after `base` on the monotonic clock and `wall`
+    /// seconds after it on the wall clock; the two differ after a jump.
+    pub(crate) fn after(base: Now, mono: u64, wall: i64) -> Now {
+        Now {
+            mono: base.mono + Duration::from_secs(mono),
+            wall: base.wall + chrono::Duration::seconds(wall),
+        }
+    }
+
+    const LOCKOUT: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn test_lockout_ignores_wall_clock_steps() {
+        let t0 = SystemClock.read();
+        let lockout = Span::start(t0, LOCKOUT);
+
+        // An NTP step back by a day and one forward by a day, 60 s in.
+        for wall in [-86_400 + 60, 86_400 + 60] {
+            assert_eq!(
+                lockout.remaining(after(t0, 60, wall)),
+                LOCKOUT - Duration::from_secs(60)
+            );
+        }
+        assert!(!lockout.is_over(after(t0, 299, -86_400)));
+        assert!(lockout.is_over(after(t0, 300, -86_400)));
+        // The displayed end follows the stepped wall clock.
+        let stepped = after(t0, 100, -3_600 + 100);
+        assert_eq!(
+            lockout.ends_at(stepped),
+            t0.wall + chrono::Duration::seconds(-3_600 + 300)
+        );
+    }
+
+    #[test]
+    fn test_restored_lockout_never_exceeds_its_length() {
+        let t0 = SystemClock.read();
+        // Persisted by a daemon whose clock was an hour ahead, or a start
+        // stamped in the future: at most a fresh lockout.
+        let future = Span::restore(t0.wall + chrono::Duration::hours(1), LOCKOUT, t0);
+        assert_eq!(future.remaining(t0), LOCKOUT);
+        // Halfway through when the daemon restarted.
+        let half = Span::restore(t0.wall - chrono::Duration::seconds(150), LOCKOUT, t0);
+        assert_eq!(half.remaining(t0), Duration::from_secs(150));
+        assert_eq!(
+            half.started_wall(),
+            t0.wall - chrono::Duration::seconds(150)
+        );
+        // A forward jump while stopped ends it; it cannot go negative.
+        let past = Span::restore(t0.wall - chrono::Duration::days(2), LOCKOUT, t0);
+        assert!(past.is_over(t0));
+    }
+
+    #[test]
+    fn test_grace_period_and_expiry_survive_resume_with_stale_clock() {
+        let t0 = SystemClock.read();
+        // A 15-minute grace period: the RTC comes back a week behind after
+        // resume; the grace period neither restarts nor grows.
+        let grace = Span::start(t0, Duration::from_secs(900));
+        let resumed = after(t0, 600, -7 * 86_400);
+        assert_eq!(grace.remaining(resumed), Duration::from_secs(300));
+
+        // A 90-day validity restored after the clock was set back by a year
+        // is capped at 90 days rather than growing by the year.
+        let validity = Duration::from_secs(90 * 86_400);
+        let issued = t0.wall;
+        let set_back = after(t0, 0, -365 * 86_400);
+        let restored = Span::restore(issued, validity, set_back);
+        assert_eq!(restored.remaining(set_back), validity);
+    }
+}
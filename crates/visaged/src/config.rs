@@ -1,76 +1,796 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 
-/// Daemon configuration, loaded from environment variables.
+use crate::logging::LogFormat;
+use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+use crate::request_limit::RequestLimits;
+use crate::schedule::{self, Schedule, ScheduleError};
+use crate::verify_grace::VerifyGrace;
+use crate::verify_token::VerifyTokens;
+
+/// Daemon configuration, loaded from `VISAGE_*` environment variables layered
+/// over an optional TOML file (see [`Config::load`]).
+#[derive(Debug, Clone)]
 pub struct Config {
     /// V4L2 device path (default: /dev/video2).
     pub camera_device: String,
     /// Directory containing ONNX model files.
     pub model_dir: PathBuf,
+    /// Site checksum manifest the model files are verified against instead
+    /// of the built-in checksums (`VISAGE_MODEL_MANIFEST`).
+    pub model_manifest: Option<PathBuf>,
     /// Path to the SQLite database file.
     pub db_path: PathBuf,
+    /// Read-only SQLite connections used for gallery fetches and listings.
+    pub db_read_connections: usize,
+    /// Encrypt each template's label and pose in the database as well as its
+    /// embedding (`VISAGE_ENCRYPT_METADATA=1`).
+    pub encrypt_metadata: bool,
     /// Cosine similarity threshold for a positive match.
     pub similarity_threshold: f32,
+    /// Accept a similarity threshold below [`MIN_SAFE_THRESHOLD`].
+    /// Set via `VISAGE_ALLOW_INSECURE_THRESHOLD=1`; reported as `insecure_config` in Status.
+    pub allow_insecure_threshold: bool,
     /// Timeout in seconds for a verify operation.
     pub verify_timeout_secs: u64,
+    /// Timeout in seconds for an enroll operation.
+    pub enroll_timeout_secs: u64,
+    /// Seconds a user must wait after an enrollment before the next one;
+    /// `0` disables the cooldown.
+    pub enroll_cooldown_secs: u64,
+    /// Seconds a `Preview` token stays valid for `EnrollNow`.
+    pub preview_ttl_secs: u64,
+    /// Seconds a `VerifyWithToken` token stays redeemable; `0` issues none.
+    pub verify_token_ttl_secs: u64,
+    /// Longest `VerifyCached` may reach back to a verify success, in
+    /// seconds; `0` disables the cache.
+    pub verify_grace_max_secs: u64,
     /// Number of warmup frames to discard at startup (camera AGC/AE stabilization).
     pub warmup_frames: usize,
+    /// Keep per-user activity out of the logs (`VISAGE_PRIVACY_MODE=1`):
+    /// only audit records (enroll, remove, authorization) may name a user.
+    pub privacy_mode: bool,
+    /// Run a synthetic frame through both models at startup and run degraded
+    /// if the recognizer's output is malformed (`VISAGE_STARTUP_SELFTEST=1`).
+    pub startup_selftest: bool,
     /// Number of frames to capture per verify attempt.
     pub frames_per_verify: usize,
     /// Number of frames to capture per enroll attempt.
     pub frames_per_enroll: usize,
+    /// Number of raw frames `ExposureReport` captures for its histogram.
+    pub exposure_report_frames: usize,
     /// Whether to activate the IR emitter around each capture sequence.
     pub emitter_enabled: bool,
+    /// Keep the camera closed between requests (`VISAGE_LAZY_CAMERA=1`).
+    /// Frees the device for other applications at the cost of first-request latency.
+    pub lazy_camera: bool,
+    /// Seconds a lazily opened camera stays open after a request, so bursts
+    /// of requests share one open/warmup cycle.
+    pub lazy_camera_keep_open_secs: u64,
+    /// Seconds an in-flight engine request may go without progress before the
+    /// watchdog abandons the engine thread and starts a replacement; `0`
+    /// disables the watchdog. Must exceed both operation timeouts.
+    pub engine_watchdog_secs: u64,
+    /// Seconds per failed verify the rate limiter forgives, one at a time
+    /// (`VISAGE_RATE_LIMIT_DECAY_SECS`); `0` keeps the fixed 60 s window that
+    /// clears every failure at once.
+    pub rate_limit_decay_secs: u64,
+    /// Requests per second each D-Bus client may make to methods that do
+    /// not reach the camera; `0` disables the limit.
+    pub query_requests_per_sec: u32,
+    /// Requests per minute each D-Bus client may make to methods that queue
+    /// on the engine (captures, enrolls, frame verifies); `0` disables it.
+    pub capture_requests_per_min: u32,
+    /// Handlers each D-Bus client may have running at once; `0` disables
+    /// the cap.
+    pub client_max_in_flight: u32,
+    /// Compare verify frames against each user's centroid template first and
+    /// fall back to the individual templates for any frame it cannot reject
+    /// (`VISAGE_CENTROID_MATCHING=1`).
+    pub centroid_matching: bool,
+    /// How far (in similarity) below the lowest score a genuine face could
+    /// reach a centroid comparison must land to reject a frame on its own;
+    /// between 0 and 1. The centroid never accepts.
+    pub centroid_margin: f32,
+    /// Include elapsed time and frame counts in the `VerifyDetailed` reply
+    /// (`VISAGE_VERIFY_REPORT_TIMING=1`), for SLA monitoring.
+    pub verify_report_timing: bool,
+    /// Reject a verify whose best-matching template was enrolled in a
+    /// different head pose than the live face shows
+    /// (`VISAGE_VERIFY_REQUIRE_POSE_MATCH=1`), against coached attempts on
+    /// multi-pose galleries. Disables the centroid fast path, which decides
+    /// without a template.
+    pub verify_require_pose_match: bool,
+    /// Reject a verify run on a camera none of the user's templates were
+    /// enrolled on (`VISAGE_VERIFY_REQUIRE_ENROLLED_DEVICE=1`), e.g. the RGB
+    /// webcam of a convertible enrolled with its IR camera. The mismatch is
+    /// logged either way.
+    pub verify_require_enrolled_device: bool,
+    /// Lowest cosine similarity allowed between the embeddings of any two
+    /// frames of one verify before it is aborted as a face change mid-capture;
+    /// `0.0` disables the check.
+    pub verify_consistency_floor: f32,
+    /// Match each verify frame only against templates enrolled within this
+    /// much raw brightness of it (0–255), falling back to the whole gallery
+    /// when none is; `0.0` matches the whole gallery every time.
+    pub verify_lighting_tolerance: f32,
     /// Whether passive liveness detection (landmark stability) is enabled.
     pub liveness_enabled: bool,
     /// Minimum mean eye landmark displacement (pixels) for liveness check.
     /// Lower values are more permissive; higher values reject more aggressively.
     /// Only used when `liveness_enabled` is true.
     pub liveness_min_displacement: f32,
+    /// Minimum number of frame pairs that must each reach
+    /// `liveness_min_displacement` on their own; `0` uses the mean alone.
+    pub liveness_min_live_pairs: usize,
+    /// Minimum milliseconds between the capture times of frames the liveness
+    /// check compares; closer frames are left out. `0` compares every frame.
+    pub liveness_min_frame_gap_ms: u64,
+    /// Minimum landmark alignment quality (0–1) for a frame to count toward
+    /// an enrollment; `0.0` disables the gate.
+    pub enroll_min_alignment: f32,
+    /// Refuse to enroll a subject that fails the passive liveness check
+    /// (`VISAGE_ENROLL_REQUIRE_LIVENESS=1`), so a photo cannot become a
+    /// template. Uses the liveness thresholds above, independent of
+    /// `liveness_enabled`.
+    pub enroll_require_liveness: bool,
+    /// Whether to store a perceptual hash of each enrollment crop (opt-in).
+    /// Enables the cross-user duplicate report (`FindDuplicateEnrollments`).
+    pub enroll_crop_hash: bool,
+    /// Maximum crop-hash Hamming distance (bits) at which two enrollments
+    /// belonging to different users are reported as likely duplicates.
+    pub duplicate_hash_max_distance: u32,
+    /// Cosine similarity to one of the user's own templates at or above
+    /// which a new enrollment is a near-duplicate; `0.0` disables the check.
+    pub enroll_duplicate_threshold: f32,
+    /// Store near-duplicate enrollments marked with the template they repeat
+    /// (`VISAGE_ENROLL_ALLOW_DUPLICATES=1`) instead of refusing them.
+    pub enroll_allow_duplicates: bool,
+    /// Accept percent-encoded usernames so accounts whose names are not valid
+    /// UTF-8 can enroll and verify (`VISAGE_PERCENT_ENCODED_USERNAMES=1`).
+    /// See [`crate::username`] for the canonical form.
+    pub percent_encoded_usernames: bool,
+    /// When `Verify` is permitted, from `VISAGE_VERIFY_ALLOWED_WINDOWS` and
+    /// `VISAGE_VERIFY_BLOCKED_WINDOWS` (see [`crate::schedule`]). Unrestricted
+    /// by default; enrollment is never restricted.
+    pub verify_schedule: Schedule,
+    /// Hours between scheduled camera self-tests (see [`crate::selftest`]);
+    /// `0` disables them.
+    pub selftest_interval_hours: u64,
+    /// When self-tests run without the IR emitter, from
+    /// `VISAGE_SELFTEST_QUIET_HOURS` (default `22:00-07:00`): the quiet
+    /// hours are the schedule's blocked windows. An empty value lights the
+    /// emitter at any time.
+    pub selftest_emitter_schedule: Schedule,
+    /// Log line format (`VISAGE_LOG_FORMAT=text|json`, default `text`).
+    pub log_format: LogFormat,
     /// Whether the daemon is running on the session bus (development mode).
     /// UID validation is skipped on the session bus — all callers share the same user.
     pub session_bus: bool,
+    /// Check callers on the session bus as on the system bus, with the
+    /// daemon's own user in root's place (`VISAGE_SESSION_BUS_HARDENED`,
+    /// default off). For session buses other users can reach.
+    pub session_bus_hardened: bool,
+    /// The TOML file whose settings were applied, if any.
+    pub config_file: Option<PathBuf>,
+    /// Problems found while loading that did not prevent startup: renamed
+    /// or unknown variables, unknown file keys. Logged at startup and
+    /// reported in Status.
+    pub warnings: Vec<ConfigWarning>,
+}
+
+/// A variable that has been renamed. The old name keeps working, with a
+/// startup warning, until the next major release drops it.
+pub struct RenamedVar {
+    pub old: &'static str,
+    pub new: &'static str,
+}
+
+/// Renamed variables, oldest first.
+pub const RENAMED_VARS: &[RenamedVar] = &[];
+
+/// A configuration problem that is reported but does not stop the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// Only the old name of a renamed variable was set; its value was used.
+    Renamed {
+        old: &'static str,
+        new: &'static str,
+    },
+    /// Both names of a renamed variable were set; the new one wins.
+    Conflict {
+        old: &'static str,
+        new: &'static str,
+    },
+    /// A `VISAGE_*` variable the daemon does not read, probably a typo.
+    Unknown {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// A key in the config file the daemon does not read.
+    UnknownKey {
+        file: PathBuf,
+        key: String,
+        suggestion: Option<String>,
+    },
+    /// A setting whose value does not parse; the default was used instead.
+    Invalid { name: String, value: String },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Renamed { old, new } => {
+                write!(f, "{old} is deprecated and was applied as {new}; rename it")
+            }
+            Self::Conflict { old, new } => {
+                write!(
+                    f,
+                    "{old} and {new} are both set; using {new} and ignoring {old}"
+                )
+            }
+            Self::Unknown {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "unknown variable {name} is ignored (did you mean {suggestion}?)"
+            ),
+            Self::Unknown {
+                name,
+                suggestion: None,
+            } => write!(f, "unknown variable {name} is ignored"),
+            Self::UnknownKey {
+                file,
+                key,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "unknown key '{key}' in {} is ignored (did you mean '{suggestion}'?)",
+                file.display()
+            ),
+            Self::UnknownKey {
+                file,
+                key,
+                suggestion: None,
+            } => write!(f, "unknown key '{key}' in {} is ignored", file.display()),
+            Self::Invalid { name, value } => {
+                write!(
+                    f,
+                    "{name}={value:?} is not a valid value; using the default"
+                )
+            }
+        }
+    }
+}
+
+/// Where [`Config::load`] looks for the config file unless
+/// `VISAGE_CONFIG_FILE` names another.
+pub const DEFAULT_CONFIG_FILE: &str = "/etc/visage/visaged.toml";
+
+/// Variable naming the config file; read by [`Config::load`] itself, not
+/// from the file.
+const CONFIG_FILE_VAR: &str = "VISAGE_CONFIG_FILE";
+
+/// Settings from a TOML config file, as the values of the variables they
+/// stand for: key `similarity_threshold` is `VISAGE_SIMILARITY_THRESHOLD`.
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    /// Variable name → (key as written, value as the variable would hold it).
+    values: BTreeMap<String, (String, String)>,
+}
+
+impl ConfigFile {
+    /// Read and parse the file at `path`. The file must be owned by root (or
+    /// by the user the daemon runs as) and not writable by group or others,
+    /// as it sets the threshold and who may do what.
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        Self::read_owned_by(path, nix::unistd::geteuid().as_raw())
+    }
+
+    /// [`read`](Self::read), trusting files owned by root or `owner`.
+    fn read_owned_by(path: &Path, owner: u32) -> Result<Self, ConfigError> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let read_err = |source| ConfigError::FileRead {
+            path: path.to_path_buf(),
+            source,
+        };
+        let invalid = |reason: String| ConfigError::FileInvalid {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let meta = std::fs::metadata(path).map_err(read_err)?;
+        if meta.uid() != 0 && meta.uid() != owner {
+            return Err(invalid(format!("owned by uid {}, not root", meta.uid())));
+        }
+        let mode = meta.permissions().mode();
+        if mode & 0o022 != 0 {
+            return Err(invalid(format!(
+                "writable by group or others (mode {:o})",
+                mode & 0o777
+            )));
+        }
+        let text = std::fs::read_to_string(path).map_err(read_err)?;
+        Self::parse(path, &text)
+    }
+
+    fn parse(path: &Path, text: &str) -> Result<Self, ConfigError> {
+        let invalid = |reason: String| ConfigError::FileInvalid {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        let mut values = BTreeMap::new();
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(v) => v,
+                toml::Value::Integer(v) => v.to_string(),
+                toml::Value::Float(v) => v.to_string(),
+                toml::Value::Boolean(v) => if v { "1" } else { "0" }.to_string(),
+                other => {
+                    return Err(invalid(format!(
+                        "{key}: expected a string, number or boolean, found {}",
+                        other.type_str()
+                    )))
+                }
+            };
+            values.insert(format!("VISAGE_{}", key.to_ascii_uppercase()), (key, value));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            values,
+        })
+    }
+
+    fn get(&self, var: &str) -> Option<String> {
+        self.values.get(var).map(|(_, value)| value.clone())
+    }
+
+    /// Set `key` to `value` in the file at `path`, for a setting changed at
+    /// runtime. The line assigning `key` is replaced, or one is appended, so
+    /// comments and the other settings stay as written. The new file is
+    /// checked to parse and then renamed over the old one with its mode.
+    pub fn store(path: &Path, key: &str, value: toml::Value) -> Result<(), ConfigError> {
+        let write_err = |source| ConfigError::FileWrite {
+            path: path.to_path_buf(),
+            source,
+        };
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::FileRead {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let assignment = format!("{key} = {value}");
+        let mut replaced = false;
+        let mut lines: Vec<String> = text
+            .lines()
+            .map(|line| {
+                let assigns_key = line
+                    .trim_start()
+                    .split_once('=')
+                    .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case(key));
+                if assigns_key && !replaced {
+                    replaced = true;
+                    assignment.clone()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        if !replaced {
+            lines.push(assignment);
+        }
+        let text = lines.join("\n") + "\n";
+        Self::parse(path, &text)?;
+
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, &text).map_err(write_err)?;
+        let renamed = std::fs::metadata(path)
+            .and_then(|meta| std::fs::set_permissions(&tmp, meta.permissions()))
+            .and_then(|()| std::fs::rename(&tmp, path));
+        if let Err(e) = renamed {
+            std::fs::remove_file(&tmp).ok();
+            return Err(write_err(e));
+        }
+        Ok(())
+    }
 }
 
 impl Config {
     /// Load configuration from `VISAGE_*` environment variables with defaults.
-    pub fn from_env() -> Self {
-        let model_dir = std::env::var("VISAGE_MODEL_DIR")
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let names = env_names();
+        Self::from_sources(|key| std::env::var(key).ok(), &names, RENAMED_VARS, None)
+    }
+
+    /// Load the config file (`VISAGE_CONFIG_FILE`, else
+    /// [`DEFAULT_CONFIG_FILE`] if it exists) with `VISAGE_*` environment
+    /// variables overriding its values. Without a file this is
+    /// [`from_env`](Self::from_env).
+    pub fn load() -> Result<Self, ConfigError> {
+        if let Some(path) = std::env::var_os(CONFIG_FILE_VAR) {
+            return Self::from_file(Path::new(&path));
+        }
+        match Self::from_file(Path::new(DEFAULT_CONFIG_FILE)) {
+            Err(ConfigError::FileRead { source, .. })
+                if source.kind() == std::io::ErrorKind::NotFound =>
+            {
+                Self::from_env()
+            }
+            other => other,
+        }
+    }
+
+    /// The settings in the TOML file at `path` over the defaults, with
+    /// `VISAGE_*` environment variables overriding them. The file is
+    /// [`read`](ConfigFile::read) only if root owns it.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let file = ConfigFile::read(path)?;
+        let names = env_names();
+        Self::from_sources(
+            |key| std::env::var(key).ok(),
+            &names,
+            RENAMED_VARS,
+            Some(&file),
+        )
+    }
+
+    /// [`from_vars`](Self::from_vars) over `var`, falling back to `file`
+    /// for variables `var` does not set, with old names in `renamed` mapped
+    /// to their replacements and every `VISAGE_*` entry in `names` (the
+    /// variables that are set) checked against the ones actually read.
+    /// Findings, including file keys nothing read, are collected in
+    /// [`Config::warnings`].
+    pub fn from_sources(
+        var: impl Fn(&str) -> Option<String>,
+        names: &[String],
+        renamed: &[RenamedVar],
+        file: Option<&ConfigFile>,
+    ) -> Result<Self, ConfigError> {
+        let read = RefCell::new(BTreeSet::new());
+        let warnings = RefCell::new(Vec::new());
+        let mut config = Self::from_vars(|key| {
+            read.borrow_mut().insert(key.to_string());
+            let old = renamed
+                .iter()
+                .find(|r| r.new == key)
+                .and_then(|r| var(r.old).map(|value| (r, value)));
+            match (var(key), old) {
+                (Some(value), Some((r, _))) => {
+                    warnings.borrow_mut().push(ConfigWarning::Conflict {
+                        old: r.old,
+                        new: r.new,
+                    });
+                    Some(value)
+                }
+                (None, Some((r, value))) => {
+                    warnings.borrow_mut().push(ConfigWarning::Renamed {
+                        old: r.old,
+                        new: r.new,
+                    });
+                    Some(value)
+                }
+                (value, None) => value.or_else(|| file.and_then(|file| file.get(key))),
+            }
+        })?;
+
+        let read = read.into_inner();
+        let mut warnings = warnings.into_inner();
+        for name in names {
+            if name.starts_with("VISAGE_")
+                && name != CONFIG_FILE_VAR
+                && !read.contains(name)
+                && !renamed.iter().any(|r| r.old == name)
+            {
+                warnings.push(ConfigWarning::Unknown {
+                    name: name.clone(),
+                    suggestion: closest_name(name, &read),
+                });
+            }
+        }
+        if let Some(file) = file {
+            for (name, (key, _)) in &file.values {
+                if !read.contains(name) {
+                    warnings.push(ConfigWarning::UnknownKey {
+                        file: file.path.clone(),
+                        key: key.clone(),
+                        suggestion: closest_name(name, &read)
+                            .map(|name| name.trim_start_matches("VISAGE_").to_ascii_lowercase()),
+                    });
+                }
+            }
+            config.config_file = Some(file.path.clone());
+        }
+        config.warnings.extend(warnings);
+        Ok(config)
+    }
+
+    /// Load configuration from an arbitrary variable lookup (the environment
+    /// in production, a map in tests), then validate it.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let model_dir = var("VISAGE_MODEL_DIR")
             .map(PathBuf::from)
-            .unwrap_or_else(|_| visage_core::default_model_dir());
+            .unwrap_or_else(visage_core::default_model_dir);
 
-        let data_dir = std::env::var("XDG_DATA_HOME")
+        let data_dir = var("XDG_DATA_HOME")
             .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            .unwrap_or_else(|| {
+                let home = var("HOME").unwrap_or_else(|| "/tmp".to_string());
                 PathBuf::from(home).join(".local/share")
             })
             .join("visage");
 
-        let db_path = std::env::var("VISAGE_DB_PATH")
+        let db_path = var("VISAGE_DB_PATH")
             .map(PathBuf::from)
-            .unwrap_or_else(|_| data_dir.join("faces.db"));
+            .unwrap_or_else(|| data_dir.join("faces.db"));
+
+        let flag = |key: &str, default: bool| var(key).map(|v| v != "0").unwrap_or(default);
+        let invalid = RefCell::new(Vec::new());
+        let windows = |key: &'static str| {
+            var(key)
+                .map(|v| schedule::parse_windows(&v))
+                .transpose()
+                .map(Option::unwrap_or_default)
+                .map_err(|source| ConfigError::InvalidSchedule { var: key, source })
+        };
 
-        Self {
-            camera_device: std::env::var("VISAGE_CAMERA_DEVICE")
-                .unwrap_or_else(|_| "/dev/video2".to_string()),
+        let config = Self {
+            camera_device: var("VISAGE_CAMERA_DEVICE").unwrap_or_else(|| "/dev/video2".to_string()),
             model_dir,
+            model_manifest: var("VISAGE_MODEL_MANIFEST").map(PathBuf::from),
             db_path,
-            similarity_threshold: env_f32("VISAGE_SIMILARITY_THRESHOLD", 0.40),
-            verify_timeout_secs: env_u64("VISAGE_VERIFY_TIMEOUT_SECS", 10),
-            warmup_frames: env_usize("VISAGE_WARMUP_FRAMES", 4),
-            frames_per_verify: env_usize("VISAGE_FRAMES_PER_VERIFY", 3),
-            frames_per_enroll: env_usize("VISAGE_FRAMES_PER_ENROLL", 5),
-            emitter_enabled: std::env::var("VISAGE_EMITTER_ENABLED")
-                .map(|v| v != "0")
-                .unwrap_or(true),
-            liveness_enabled: std::env::var("VISAGE_LIVENESS_ENABLED")
-                .map(|v| v != "0")
-                .unwrap_or(true),
-            liveness_min_displacement: env_f32("VISAGE_LIVENESS_MIN_DISPLACEMENT", 0.8),
-            session_bus: std::env::var("VISAGE_SESSION_BUS").is_ok(),
+            db_read_connections: parse_var(&var, &invalid, "VISAGE_DB_READ_CONNECTIONS")
+                .unwrap_or(2),
+            encrypt_metadata: flag("VISAGE_ENCRYPT_METADATA", false),
+            similarity_threshold: parse_var(&var, &invalid, "VISAGE_SIMILARITY_THRESHOLD")
+                .unwrap_or(0.40),
+            allow_insecure_threshold: flag("VISAGE_ALLOW_INSECURE_THRESHOLD", false),
+            verify_timeout_secs: parse_var(&var, &invalid, "VISAGE_VERIFY_TIMEOUT_SECS")
+                .unwrap_or(10),
+            enroll_timeout_secs: parse_var(&var, &invalid, "VISAGE_ENROLL_TIMEOUT_SECS")
+                .unwrap_or(15),
+            enroll_cooldown_secs: parse_var(&var, &invalid, "VISAGE_ENROLL_COOLDOWN_SECS")
+                .unwrap_or(5),
+            preview_ttl_secs: parse_var(&var, &invalid, "VISAGE_PREVIEW_TTL_SECS").unwrap_or(30),
+            verify_token_ttl_secs: parse_var(&var, &invalid, "VISAGE_VERIFY_TOKEN_TTL_SECS")
+                .unwrap_or(30),
+            verify_grace_max_secs: parse_var(&var, &invalid, "VISAGE_VERIFY_GRACE_MAX_SECS")
+                .unwrap_or(60),
+            warmup_frames: parse_var(&var, &invalid, "VISAGE_WARMUP_FRAMES").unwrap_or(4),
+            startup_selftest: flag("VISAGE_STARTUP_SELFTEST", false),
+            privacy_mode: flag("VISAGE_PRIVACY_MODE", false),
+            frames_per_verify: parse_var(&var, &invalid, "VISAGE_FRAMES_PER_VERIFY").unwrap_or(3),
+            frames_per_enroll: parse_var(&var, &invalid, "VISAGE_FRAMES_PER_ENROLL").unwrap_or(5),
+            exposure_report_frames: parse_var(&var, &invalid, "VISAGE_EXPOSURE_REPORT_FRAMES")
+                .unwrap_or(5),
+            emitter_enabled: flag("VISAGE_EMITTER_ENABLED", true),
+            lazy_camera: flag("VISAGE_LAZY_CAMERA", false),
+            lazy_camera_keep_open_secs: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_LAZY_CAMERA_KEEP_OPEN_SECS",
+            )
+            .unwrap_or(5),
+            engine_watchdog_secs: parse_var(&var, &invalid, "VISAGE_ENGINE_WATCHDOG_SECS")
+                .unwrap_or(0),
+            rate_limit_decay_secs: parse_var(&var, &invalid, "VISAGE_RATE_LIMIT_DECAY_SECS")
+                .unwrap_or(0),
+            query_requests_per_sec: parse_var(&var, &invalid, "VISAGE_QUERY_REQUESTS_PER_SEC")
+                .unwrap_or(50),
+            capture_requests_per_min: parse_var(&var, &invalid, "VISAGE_CAPTURE_REQUESTS_PER_MIN")
+                .unwrap_or(30),
+            client_max_in_flight: parse_var(&var, &invalid, "VISAGE_CLIENT_MAX_IN_FLIGHT")
+                .unwrap_or(8),
+            centroid_matching: flag("VISAGE_CENTROID_MATCHING", false),
+            centroid_margin: parse_var(&var, &invalid, "VISAGE_CENTROID_MARGIN").unwrap_or(0.10),
+            verify_report_timing: flag("VISAGE_VERIFY_REPORT_TIMING", false),
+            verify_require_pose_match: flag("VISAGE_VERIFY_REQUIRE_POSE_MATCH", false),
+            verify_require_enrolled_device: flag("VISAGE_VERIFY_REQUIRE_ENROLLED_DEVICE", false),
+            verify_consistency_floor: parse_var(&var, &invalid, "VISAGE_VERIFY_CONSISTENCY_FLOOR")
+                .unwrap_or(0.0),
+            verify_lighting_tolerance: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_VERIFY_LIGHTING_TOLERANCE",
+            )
+            .unwrap_or(0.0),
+            liveness_enabled: flag("VISAGE_LIVENESS_ENABLED", true),
+            liveness_min_displacement: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_LIVENESS_MIN_DISPLACEMENT",
+            )
+            .unwrap_or(0.8),
+            liveness_min_live_pairs: parse_var(&var, &invalid, "VISAGE_LIVENESS_MIN_LIVE_PAIRS")
+                .unwrap_or(0),
+            liveness_min_frame_gap_ms: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_LIVENESS_MIN_FRAME_GAP_MS",
+            )
+            .unwrap_or(0),
+            enroll_min_alignment: parse_var(&var, &invalid, "VISAGE_ENROLL_MIN_ALIGNMENT")
+                .unwrap_or(0.0),
+            enroll_require_liveness: flag("VISAGE_ENROLL_REQUIRE_LIVENESS", false),
+            enroll_crop_hash: flag("VISAGE_ENROLL_CROP_HASH", false),
+            duplicate_hash_max_distance: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_DUPLICATE_HASH_MAX_DISTANCE",
+            )
+            .unwrap_or(6),
+            enroll_duplicate_threshold: parse_var(
+                &var,
+                &invalid,
+                "VISAGE_ENROLL_DUPLICATE_THRESHOLD",
+            )
+            .unwrap_or(0.92),
+            enroll_allow_duplicates: flag("VISAGE_ENROLL_ALLOW_DUPLICATES", false),
+            percent_encoded_usernames: flag("VISAGE_PERCENT_ENCODED_USERNAMES", false),
+            verify_schedule: Schedule::new(
+                windows("VISAGE_VERIFY_ALLOWED_WINDOWS")?,
+                windows("VISAGE_VERIFY_BLOCKED_WINDOWS")?,
+            ),
+            selftest_interval_hours: parse_var(&var, &invalid, "VISAGE_SELFTEST_INTERVAL_HOURS")
+                .unwrap_or(0),
+            selftest_emitter_schedule: Schedule::new(
+                Vec::new(),
+                schedule::parse_windows(
+                    &var("VISAGE_SELFTEST_QUIET_HOURS")
+                        .unwrap_or_else(|| DEFAULT_SELFTEST_QUIET_HOURS.to_string()),
+                )
+                .map_err(|source| ConfigError::InvalidSchedule {
+                    var: "VISAGE_SELFTEST_QUIET_HOURS",
+                    source,
+                })?,
+            ),
+            log_format: var("VISAGE_LOG_FORMAT")
+                .map(|v| v.parse().map_err(ConfigError::InvalidLogFormat))
+                .transpose()?
+                .unwrap_or_default(),
+            session_bus: flag("VISAGE_SESSION_BUS", false),
+            session_bus_hardened: flag("VISAGE_SESSION_BUS_HARDENED", false),
+            config_file: None,
+            // Last, so it holds every value the fields above failed to parse.
+            warnings: invalid.take(),
+        };
+
+        check_threshold(config.similarity_threshold, config.allow_insecure_threshold)?;
+        if !(0.0..=1.0).contains(&config.centroid_margin) {
+            return Err(ConfigError::InvalidCentroidMargin(config.centroid_margin));
+        }
+        if !(0.0..=1.0).contains(&config.verify_consistency_floor) {
+            return Err(ConfigError::InvalidConsistencyFloor(
+                config.verify_consistency_floor,
+            ));
+        }
+        if !(0.0..=255.0).contains(&config.verify_lighting_tolerance) {
+            return Err(ConfigError::InvalidLightingTolerance(
+                config.verify_lighting_tolerance,
+            ));
+        }
+        if !(0.0..=1.0).contains(&config.enroll_duplicate_threshold) {
+            return Err(ConfigError::InvalidDuplicateThreshold(
+                config.enroll_duplicate_threshold,
+            ));
+        }
+        let longest_timeout = config.verify_timeout_secs.max(config.enroll_timeout_secs);
+        if config.engine_watchdog_secs != 0 && config.engine_watchdog_secs <= longest_timeout {
+            return Err(ConfigError::WatchdogTooShort {
+                watchdog: config.engine_watchdog_secs,
+                longest_timeout,
+            });
+        }
+        Ok(config)
+    }
+
+    /// Reject settings the daemon cannot run with. Unlike the checks made
+    /// while loading, this also looks at the system (the camera device must
+    /// exist), so `visaged` runs it once at startup.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&self.similarity_threshold) {
+            return Err(ConfigError::InvalidThreshold(self.similarity_threshold));
+        }
+        if self.frames_per_verify == 0 {
+            return Err(ConfigError::Zero("VISAGE_FRAMES_PER_VERIFY"));
+        }
+        if self.verify_timeout_secs == 0 {
+            return Err(ConfigError::Zero("VISAGE_VERIFY_TIMEOUT_SECS"));
+        }
+        if !Path::new(&self.camera_device).exists() {
+            return Err(ConfigError::CameraNotFound(self.camera_device.clone()));
+        }
+        Ok(())
+    }
+
+    /// Thresholds for the passive liveness check, shared by verify and
+    /// `enroll_require_liveness`.
+    pub fn liveness_policy(&self) -> visage_core::LivenessPolicy {
+        visage_core::LivenessPolicy {
+            min_displacement: self.liveness_min_displacement,
+            min_live_pairs: self.liveness_min_live_pairs,
+            min_frame_gap: std::time::Duration::from_millis(self.liveness_min_frame_gap_ms),
+        }
+    }
+
+    /// Load configuration from explicit `(name, value)` pairs, ignoring the environment.
+    #[cfg(test)]
+    pub fn from_pairs(vars: &[(&str, &str)]) -> Result<Self, ConfigError> {
+        Self::from_pairs_renamed(vars, RENAMED_VARS)
+    }
+
+    /// [`from_pairs`](Self::from_pairs) with a custom rename table.
+    #[cfg(test)]
+    pub fn from_pairs_renamed(
+        vars: &[(&str, &str)],
+        renamed: &[RenamedVar],
+    ) -> Result<Self, ConfigError> {
+        let names: Vec<String> = vars.iter().map(|(k, _)| k.to_string()).collect();
+        let lookup = |key: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        };
+        Self::from_sources(lookup, &names, renamed, None)
+    }
+
+    /// [`from_pairs`](Self::from_pairs) layered over the config file at
+    /// `path`, as [`load`](Self::load) layers the environment.
+    #[cfg(test)]
+    pub fn from_pairs_and_file(vars: &[(&str, &str)], path: &Path) -> Result<Self, ConfigError> {
+        let names: Vec<String> = vars.iter().map(|(k, _)| k.to_string()).collect();
+        let lookup = |key: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        };
+        Self::from_sources(lookup, &names, RENAMED_VARS, Some(&ConfigFile::read(path)?))
+    }
+
+    /// Whether the configuration runs with a threshold below
+    /// [`MIN_SAFE_THRESHOLD`] (only possible with the explicit override).
+    pub fn insecure_config(&self) -> bool {
+        self.similarity_threshold < MIN_SAFE_THRESHOLD
+    }
+
+    /// Verify rate limiter in the configured forgiveness mode.
+    pub fn rate_limiter(&self) -> RateLimiter {
+        match self.rate_limit_decay_secs {
+            0 => RateLimiter::new(),
+            secs => RateLimiter::decaying(std::time::Duration::from_secs(secs)),
+        }
+    }
+
+    /// Per-client D-Bus request limits.
+    pub fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            query_per_sec: self.query_requests_per_sec,
+            capture_per_min: self.capture_requests_per_min,
+            max_in_flight: self.client_max_in_flight,
         }
     }
 
+    /// Per-user wait between enrollments.
+    pub fn enroll_cooldown(&self) -> EnrollCooldown {
+        EnrollCooldown::new(std::time::Duration::from_secs(self.enroll_cooldown_secs))
+    }
+
+    /// One-time tokens issued by `VerifyWithToken`.
+    pub fn verify_tokens(&self) -> VerifyTokens {
+        VerifyTokens::new(std::time::Duration::from_secs(self.verify_token_ttl_secs))
+    }
+
+    /// Recent verify successes `VerifyCached` answers from.
+    pub fn verify_grace(&self) -> VerifyGrace {
+        VerifyGrace::new(std::time::Duration::from_secs(self.verify_grace_max_secs))
+    }
+
     /// Path to the SCRFD detection model.
     pub fn scrfd_model_path(&self) -> String {
         self.model_dir
@@ -88,23 +808,610 @@ impl Config {
     }
 }
 
-fn env_f32(key: &str, default: f32) -> f32 {
-    std::env::var(key)
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(default)
+/// Names of the variables set in the environment.
+fn env_names() -> Vec<String> {
+    std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .collect()
+}
+
+/// The known variable within edit distance 2 of `name`, if any.
+fn closest_name(name: &str, known: &BTreeSet<String>) -> Option<String> {
+    known
+        .iter()
+        .filter(|k| k.starts_with("VISAGE_"))
+        .map(|k| (edit_distance(name, k), k))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k.clone())
+}
+
+/// Levenshtein distance between two ASCII-ish strings, by bytes.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            let next = (row[j] + 1).min(row[j + 1] + 1).min(prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// The value of `key` parsed as `T`; `None` when unset, or when set to
+/// something that does not parse, which is recorded in `invalid` so the
+/// fallback to the default is not silent.
+fn parse_var<T: std::str::FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    invalid: &RefCell<Vec<ConfigWarning>>,
+    key: &str,
+) -> Option<T> {
+    let value = var(key)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        invalid.borrow_mut().push(ConfigWarning::Invalid {
+            name: key.to_string(),
+            value,
+        });
+    }
+    parsed
+}
+
+/// Lowest similarity threshold accepted without `VISAGE_ALLOW_INSECURE_THRESHOLD=1`.
+///
+/// Below this, unrelated faces start to match (a typo such as `0.05` for `0.5`
+/// would otherwise silently accept almost anyone).
+pub const MIN_SAFE_THRESHOLD: f32 = 0.25;
+
+/// Quiet hours for self-tests when `VISAGE_SELFTEST_QUIET_HOURS` is unset.
+pub const DEFAULT_SELFTEST_QUIET_HOURS: &str = "22:00-07:00";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error(
+        "similarity threshold {0} is below the safe minimum {MIN_SAFE_THRESHOLD}; \
+         set VISAGE_ALLOW_INSECURE_THRESHOLD=1 to override"
+    )]
+    InsecureThreshold(f32),
+    #[error("similarity threshold {0} is out of range (expected 0.0–1.0)")]
+    InvalidThreshold(f32),
+    #[error(
+        "engine watchdog of {watchdog}s must exceed the longest operation timeout \
+         ({longest_timeout}s), or it would restart healthy engines"
+    )]
+    WatchdogTooShort { watchdog: u64, longest_timeout: u64 },
+    #[error("centroid margin {0} is out of range (expected 0.0–1.0)")]
+    InvalidCentroidMargin(f32),
+    #[error("verify consistency floor {0} is out of range (expected 0.0–1.0)")]
+    InvalidConsistencyFloor(f32),
+    #[error("verify lighting tolerance {0} is out of range (expected 0–255)")]
+    InvalidLightingTolerance(f32),
+    #[error("enroll duplicate threshold {0} is out of range (expected 0.0–1.0)")]
+    InvalidDuplicateThreshold(f32),
+    #[error("{0} must be greater than 0")]
+    Zero(&'static str),
+    #[error(
+        "camera device {0} does not exist; set VISAGE_CAMERA_DEVICE to one `visage discover` lists"
+    )]
+    CameraNotFound(String),
+    #[error("unknown log format '{0}' (expected 'text' or 'json')")]
+    InvalidLogFormat(String),
+    #[error("{var}: {source}")]
+    InvalidSchedule {
+        var: &'static str,
+        source: ScheduleError,
+    },
+    #[error("cannot read config file {}: {source}", path.display())]
+    FileRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {}: {reason}", path.display())]
+    FileInvalid { path: PathBuf, reason: String },
+    #[error("cannot write config file {}: {source}", path.display())]
+    FileWrite {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 }
 
-fn env_u64(key: &str, default: u64) -> u64 {
-    std::env::var(key)
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(default)
+/// Validate a similarity threshold from any source (daemon config, runtime
+/// changes, per-user or per-call overrides).
+///
+/// Values below [`MIN_SAFE_THRESHOLD`] are rejected unless `allow_insecure`
+/// is set, in which case a prominent warning is logged.
+pub fn check_threshold(threshold: f32, allow_insecure: bool) -> Result<(), ConfigError> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(ConfigError::InvalidThreshold(threshold));
+    }
+    if threshold < MIN_SAFE_THRESHOLD {
+        if !allow_insecure {
+            return Err(ConfigError::InsecureThreshold(threshold));
+        }
+        tracing::warn!(
+            threshold,
+            minimum = MIN_SAFE_THRESHOLD,
+            "INSECURE CONFIGURATION: similarity threshold below safe minimum — \
+             unrelated faces may be accepted"
+        );
+    }
+    Ok(())
 }
 
-fn env_usize(key: &str, default: usize) -> usize {
-    std::env::var(key)
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(default)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        Config::from_pairs(vars)
+    }
+
+    #[test]
+    fn test_defaults_are_secure() {
+        let config = load(&[]).unwrap();
+        assert!((config.similarity_threshold - 0.40).abs() < f32::EPSILON);
+        assert!(!config.allow_insecure_threshold);
+        assert!(!config.insecure_config());
+    }
+
+    #[test]
+    fn test_low_threshold_rejected() {
+        let err = load(&[("VISAGE_SIMILARITY_THRESHOLD", "0.05")]).unwrap_err();
+        assert!(matches!(err, ConfigError::InsecureThreshold(t) if (t - 0.05).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_low_threshold_allowed_with_override() {
+        let config = load(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "0.05"),
+            ("VISAGE_ALLOW_INSECURE_THRESHOLD", "1"),
+        ])
+        .unwrap();
+        assert!(config.insecure_config());
+    }
+
+    #[test]
+    fn test_out_of_range_threshold_rejected_even_with_override() {
+        let err = load(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "1.5"),
+            ("VISAGE_ALLOW_INSECURE_THRESHOLD", "1"),
+        ])
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidThreshold(_)));
+    }
+
+    #[test]
+    fn test_check_threshold_boundary() {
+        assert!(check_threshold(MIN_SAFE_THRESHOLD, false).is_ok());
+        assert!(check_threshold(MIN_SAFE_THRESHOLD - 0.01, false).is_err());
+        assert!(check_threshold(MIN_SAFE_THRESHOLD - 0.01, true).is_ok());
+    }
+
+    #[test]
+    fn test_watchdog_must_outlast_operation_timeouts() {
+        assert_eq!(load(&[]).unwrap().engine_watchdog_secs, 0);
+        let err = load(&[("VISAGE_ENGINE_WATCHDOG_SECS", "15")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::WatchdogTooShort {
+                watchdog: 15,
+                longest_timeout: 15
+            }
+        ));
+        let config = load(&[("VISAGE_ENGINE_WATCHDOG_SECS", "30")]).unwrap();
+        assert_eq!(config.engine_watchdog_secs, 30);
+    }
+
+    #[test]
+    fn test_centroid_margin_validated() {
+        let config = load(&[]).unwrap();
+        assert!(!config.centroid_matching);
+        assert_eq!(config.centroid_margin, 0.10);
+        let config = load(&[
+            ("VISAGE_CENTROID_MATCHING", "1"),
+            ("VISAGE_CENTROID_MARGIN", "0.2"),
+        ])
+        .unwrap();
+        assert!(config.centroid_matching);
+        assert_eq!(config.centroid_margin, 0.2);
+        assert!(matches!(
+            load(&[("VISAGE_CENTROID_MARGIN", "1.5")]),
+            Err(ConfigError::InvalidCentroidMargin(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_consistency_floor_validated() {
+        assert_eq!(load(&[]).unwrap().verify_consistency_floor, 0.0);
+        let config = load(&[("VISAGE_VERIFY_CONSISTENCY_FLOOR", "0.6")]).unwrap();
+        assert_eq!(config.verify_consistency_floor, 0.6);
+        assert!(matches!(
+            load(&[("VISAGE_VERIFY_CONSISTENCY_FLOOR", "-0.1")]),
+            Err(ConfigError::InvalidConsistencyFloor(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_lighting_tolerance_validated() {
+        assert_eq!(load(&[]).unwrap().verify_lighting_tolerance, 0.0);
+        let config = load(&[("VISAGE_VERIFY_LIGHTING_TOLERANCE", "40")]).unwrap();
+        assert_eq!(config.verify_lighting_tolerance, 40.0);
+        assert!(matches!(
+            load(&[("VISAGE_VERIFY_LIGHTING_TOLERANCE", "300")]),
+            Err(ConfigError::InvalidLightingTolerance(_))
+        ));
+    }
+
+    #[test]
+    fn test_enroll_duplicate_threshold_validated() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.enroll_duplicate_threshold, 0.92);
+        assert!(!config.enroll_allow_duplicates);
+        let config = load(&[
+            ("VISAGE_ENROLL_DUPLICATE_THRESHOLD", "0"),
+            ("VISAGE_ENROLL_ALLOW_DUPLICATES", "1"),
+        ])
+        .unwrap();
+        assert_eq!(config.enroll_duplicate_threshold, 0.0);
+        assert!(config.enroll_allow_duplicates);
+        assert!(matches!(
+            load(&[("VISAGE_ENROLL_DUPLICATE_THRESHOLD", "1.2")]),
+            Err(ConfigError::InvalidDuplicateThreshold(_))
+        ));
+    }
+
+    #[test]
+    fn test_log_format_parsed_and_validated() {
+        assert_eq!(load(&[]).unwrap().log_format, LogFormat::Text);
+        let config = load(&[("VISAGE_LOG_FORMAT", "json")]).unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(matches!(
+            load(&[("VISAGE_LOG_FORMAT", "yaml")]),
+            Err(ConfigError::InvalidLogFormat(f)) if f == "yaml"
+        ));
+    }
+
+    #[test]
+    fn test_verify_windows_parsed_and_validated() {
+        assert!(load(&[]).unwrap().verify_schedule.is_unrestricted());
+        let config = load(&[
+            ("VISAGE_VERIFY_ALLOWED_WINDOWS", "mon-fri 08:00-18:00"),
+            ("VISAGE_VERIFY_BLOCKED_WINDOWS", "12:00-13:00"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.verify_schedule.allowed(),
+            ["mon,tue,wed,thu,fri 08:00-18:00"]
+        );
+        assert_eq!(config.verify_schedule.blocked(), ["12:00-13:00"]);
+        let err = load(&[("VISAGE_VERIFY_BLOCKED_WINDOWS", "22:00-6:00pm")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidSchedule {
+                var: "VISAGE_VERIFY_BLOCKED_WINDOWS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_selftest_quiet_hours_default_and_override() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.selftest_interval_hours, 0);
+        assert_eq!(
+            config.selftest_emitter_schedule.blocked(),
+            [DEFAULT_SELFTEST_QUIET_HOURS]
+        );
+        let config = load(&[
+            ("VISAGE_SELFTEST_INTERVAL_HOURS", "6"),
+            ("VISAGE_SELFTEST_QUIET_HOURS", ""),
+        ])
+        .unwrap();
+        assert_eq!(config.selftest_interval_hours, 6);
+        assert!(config.selftest_emitter_schedule.is_unrestricted());
+        let err = load(&[("VISAGE_SELFTEST_QUIET_HOURS", "late")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidSchedule {
+                var: "VISAGE_SELFTEST_QUIET_HOURS",
+                ..
+            }
+        ));
+    }
+
+    const TEST_RENAMES: &[RenamedVar] = &[RenamedVar {
+        old: "VISAGE_DEVICE",
+        new: "VISAGE_CAMERA_DEVICE",
+    }];
+
+    #[test]
+    fn test_renamed_var_is_mapped_with_warning() {
+        let config =
+            Config::from_pairs_renamed(&[("VISAGE_DEVICE", "/dev/video0")], TEST_RENAMES).unwrap();
+        assert_eq!(config.camera_device, "/dev/video0");
+        assert_eq!(
+            config.warnings,
+            [ConfigWarning::Renamed {
+                old: "VISAGE_DEVICE",
+                new: "VISAGE_CAMERA_DEVICE"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_new_name_wins_over_old() {
+        let config = Config::from_pairs_renamed(
+            &[
+                ("VISAGE_DEVICE", "/dev/video0"),
+                ("VISAGE_CAMERA_DEVICE", "/dev/video4"),
+            ],
+            TEST_RENAMES,
+        )
+        .unwrap();
+        assert_eq!(config.camera_device, "/dev/video4");
+        assert_eq!(
+            config.warnings,
+            [ConfigWarning::Conflict {
+                old: "VISAGE_DEVICE",
+                new: "VISAGE_CAMERA_DEVICE"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_vars_warn_with_suggestion() {
+        let config = load(&[
+            ("VISAGE_SIMILARITY_TRESHOLD", "0.5"),
+            ("VISAGE_NOT_A_SETTING", "1"),
+            ("VISAGE_LAZY_CAMERA", "1"),
+            ("HOME", "/root"),
+        ])
+        .unwrap();
+        assert!((config.similarity_threshold - 0.40).abs() < f32::EPSILON);
+        assert_eq!(
+            config.warnings,
+            [
+                ConfigWarning::Unknown {
+                    name: "VISAGE_SIMILARITY_TRESHOLD".into(),
+                    suggestion: Some("VISAGE_SIMILARITY_THRESHOLD".into()),
+                },
+                ConfigWarning::Unknown {
+                    name: "VISAGE_NOT_A_SETTING".into(),
+                    suggestion: None,
+                },
+            ]
+        );
+        assert!(load(&[("VISAGE_LAZY_CAMERA", "1")])
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn test_unparseable_values_warn_and_fall_back() {
+        let config = load(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "0,4"),
+            ("VISAGE_FRAMES_PER_VERIFY", "five"),
+            ("VISAGE_VERIFY_TIMEOUT_SECS", "12"),
+        ])
+        .unwrap();
+        assert!((config.similarity_threshold - 0.40).abs() < f32::EPSILON);
+        assert_eq!(config.frames_per_verify, 3);
+        assert_eq!(config.verify_timeout_secs, 12);
+        assert_eq!(
+            config.warnings,
+            [
+                ConfigWarning::Invalid {
+                    name: "VISAGE_SIMILARITY_THRESHOLD".into(),
+                    value: "0,4".into(),
+                },
+                ConfigWarning::Invalid {
+                    name: "VISAGE_FRAMES_PER_VERIFY".into(),
+                    value: "five".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            config.warnings[0].to_string(),
+            "VISAGE_SIMILARITY_THRESHOLD=\"0,4\" is not a valid value; using the default"
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_unusable_settings() {
+        let camera = std::env::temp_dir();
+        let camera = camera.to_str().unwrap();
+        let valid = || load(&[("VISAGE_CAMERA_DEVICE", camera)]).unwrap();
+        valid().validate().unwrap();
+
+        let mut config = valid();
+        config.similarity_threshold = 1.5;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidThreshold(_))
+        ));
+        let mut config = valid();
+        config.similarity_threshold = -0.1;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidThreshold(_))
+        ));
+
+        let config = load(&[
+            ("VISAGE_CAMERA_DEVICE", camera),
+            ("VISAGE_FRAMES_PER_VERIFY", "0"),
+        ])
+        .unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Zero("VISAGE_FRAMES_PER_VERIFY"))
+        ));
+
+        let config = load(&[
+            ("VISAGE_CAMERA_DEVICE", camera),
+            ("VISAGE_VERIFY_TIMEOUT_SECS", "0"),
+        ])
+        .unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Zero("VISAGE_VERIFY_TIMEOUT_SECS"))
+        ));
+
+        let config = load(&[("VISAGE_CAMERA_DEVICE", "/dev/video-missing")]).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::CameraNotFound(path) if path == "/dev/video-missing"),
+            "{err:?}"
+        );
+    }
+
+    /// Write `contents` to a fresh temp file and return its path.
+    fn config_file(contents: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("visaged-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_store_rewrites_one_key_and_keeps_the_rest() {
+        let path = config_file(
+            "# /etc/visage/visaged.toml\n\
+             similarity_threshold = 0.5 # site default\n\
+             verify_timeout_secs = 7\n",
+        );
+
+        ConfigFile::store(&path, "similarity_threshold", toml::Value::Float(0.6)).unwrap();
+        ConfigFile::store(&path, "liveness_enabled", toml::Value::Boolean(false)).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# /etc/visage/visaged.toml\n\
+             similarity_threshold = 0.6\n\
+             verify_timeout_secs = 7\n\
+             liveness_enabled = false\n"
+        );
+        let config = Config::from_file(&path).unwrap();
+        assert!((config.similarity_threshold - 0.6).abs() < f32::EPSILON);
+        assert!(!config.liveness_enabled);
+        assert_eq!(config.verify_timeout_secs, 7);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_environment_overrides_file_overrides_defaults() {
+        let path = config_file(
+            "# /etc/visage/visaged.toml\n\
+             camera_device = \"/dev/video0\"\n\
+             similarity_threshold = 0.5\n\
+             verify_timeout_secs = 7\n\
+             liveness_enabled = false\n\
+             session_bus = false\n",
+        );
+
+        let file = Config::from_file(&path).unwrap();
+        assert_eq!(file.camera_device, "/dev/video0");
+        assert!((file.similarity_threshold - 0.5).abs() < f32::EPSILON);
+        assert_eq!(file.verify_timeout_secs, 7);
+        assert!(!file.liveness_enabled);
+        assert!(!file.session_bus);
+        assert_eq!(file.config_file.as_deref(), Some(path.as_path()));
+
+        let merged = Config::from_pairs_and_file(
+            &[
+                ("VISAGE_SIMILARITY_THRESHOLD", "0.6"),
+                ("VISAGE_LIVENESS_ENABLED", "1"),
+            ],
+            &path,
+        )
+        .unwrap();
+        // The environment wins, the file fills the rest, defaults the remainder.
+        assert!((merged.similarity_threshold - 0.6).abs() < f32::EPSILON);
+        assert!(merged.liveness_enabled);
+        assert_eq!(merged.camera_device, "/dev/video0");
+        assert_eq!(merged.verify_timeout_secs, 7);
+        let defaults = load(&[]).unwrap();
+        assert_eq!(merged.enroll_timeout_secs, defaults.enroll_timeout_secs);
+        assert!(merged.warnings.is_empty(), "{:?}", merged.warnings);
+
+        // File values are validated like variables.
+        std::fs::write(&path, "similarity_threshold = 0.05\n").unwrap();
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::InsecureThreshold(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_file_keys_warn_and_bad_files_fail() {
+        let path = config_file("similarity_treshold = 0.5\nnot_a_setting = \"x\"\n");
+        let config = Config::from_file(&path).unwrap();
+        assert!((config.similarity_threshold - 0.40).abs() < f32::EPSILON);
+        assert_eq!(
+            config.warnings,
+            [
+                ConfigWarning::UnknownKey {
+                    file: path.clone(),
+                    key: "not_a_setting".into(),
+                    suggestion: None,
+                },
+                ConfigWarning::UnknownKey {
+                    file: path.clone(),
+                    key: "similarity_treshold".into(),
+                    suggestion: Some("similarity_threshold".into()),
+                },
+            ]
+        );
+
+        std::fs::write(&path, "camera_device = [\"/dev/video0\"]\n").unwrap();
+        let err = Config::from_file(&path).unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::FileInvalid { reason, .. } if reason.contains("array")),
+            "{err}"
+        );
+        std::fs::write(&path, "camera_device = \n").unwrap();
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::FileInvalid { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::FileRead { .. })
+        ));
+    }
+
+    #[test]
+    fn test_config_file_must_be_owned_by_root_and_not_shared_writable() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let path = config_file("similarity_threshold = 0.5\n");
+        let owner = std::fs::metadata(&path).unwrap().uid();
+        ConfigFile::read_owned_by(&path, owner).unwrap();
+        if owner != 0 {
+            // Someone else's file is refused, however it is written.
+            let err = ConfigFile::read_owned_by(&path, owner + 1).unwrap_err();
+            assert!(
+                matches!(&err, ConfigError::FileInvalid { reason, .. }
+                    if *reason == format!("owned by uid {owner}, not root")),
+                "{err}"
+            );
+        }
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o664)).unwrap();
+        let err = Config::from_file(&path).unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::FileInvalid { reason, .. }
+                if reason.contains("writable by group or others")),
+            "{err}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
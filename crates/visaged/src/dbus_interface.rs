@@ -1,12 +1,34 @@
 use nix::unistd::User;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use zbus::interface;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedValue;
 
-use crate::config::Config;
-use crate::engine::{EngineError, EngineHandle};
-use crate::rate_limiter::RateLimiter;
-use crate::store::FaceModelStore;
+use crate::capabilities;
+use crate::config::{Config, ConfigFile};
+use crate::device_settings::DeviceSettings;
+use crate::engine::{
+    CentroidGate, EngineError, EngineHandle, EnrollProgress, PreviewCapture, VerifyProgress,
+};
+use crate::error::VisageError;
+use crate::polkit::{self, Authority};
+use crate::preview::PreviewSessions;
+use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+use crate::request_limit::{InFlight, RequestClass, RequestLimiter};
+use crate::selftest::SelfTestState;
+use crate::stats::QualityStats;
+use crate::status::StatusInfo;
+use crate::store::{EnrollMeta, FaceModelStore};
+use crate::username;
+use crate::verify_cancel::PendingVerifies;
+use crate::verify_grace::VerifyGrace;
+use crate::verify_policy::VerifyPolicy;
+use crate::verify_token::VerifyTokens;
+use visage_core::recognizer::ARCFACE_MODEL_VERSION;
+use visage_core::FaceModel;
+use visage_hw::CaptureContext;
 
 /// Shared state accessible by D-Bus method handlers.
 pub struct AppState {
@@ -14,6 +36,310 @@ pub struct AppState {
     pub engine: EngineHandle,
     pub store: FaceModelStore,
     pub rate_limiter: RateLimiter,
+    pub enroll_cooldown: EnrollCooldown,
+    /// Frames held between `Preview` and `EnrollNow`.
+    pub previews: PreviewSessions,
+    /// Tokens held between `VerifyWithToken` and `RedeemToken`.
+    pub verify_tokens: VerifyTokens,
+    /// Recent verify successes `VerifyCached` answers from.
+    pub verify_grace: VerifyGrace,
+    /// Cancel tokens of running verifies and enrollments, per D-Bus
+    /// connection and user (`Cancel`, `CancelUser`).
+    pub pending_verifies: PendingVerifies,
+    pub stats: QualityStats,
+    /// Scheduled camera self-test results (see [`crate::selftest`]).
+    pub selftest: SelfTestState,
+    /// Handle for swapping the tracing filter at runtime (`None` in tests).
+    pub log_filter: Option<crate::logging::FilterHandle>,
+    /// Answers polkit checks for methods that act on another user's behalf.
+    pub authority: Arc<dyn Authority>,
+}
+
+/// Longest accepted model version tag for `SetModelPin`.
+const MAX_MODEL_VERSION_LEN: usize = 64;
+
+/// Longest accepted label for `SetModelLabel`, in characters.
+const MAX_LABEL_LEN: usize = 64;
+
+/// Most frames the `FramesPerVerify` property may be set to.
+const MAX_PROPERTY_FRAMES: u32 = 30;
+
+/// Most frames one `CameraBenchmark` may capture.
+const MAX_BENCHMARK_FRAMES: u32 = 300;
+
+/// JSON reply of `VerifyDetailed`. `elapsed` runs from request arrival to
+/// reply; the frame counts and timings are only included with `timing`.
+fn verify_report(
+    result: &crate::engine::VerifyResult,
+    elapsed: std::time::Duration,
+    threshold: f32,
+    timing: bool,
+) -> serde_json::Value {
+    let mut report = serde_json::json!({
+        "matched": result.result.matched,
+        "similarity": result.result.similarity,
+        "threshold": threshold,
+        "model_id": result.result.model_id,
+        "model_label": result.result.model_label,
+        "fast_path": result.fast_path,
+        "failure_reason": failure_reason(result),
+    });
+    if timing {
+        report["elapsed_ms"] = (elapsed.as_millis() as u64).into();
+        report["engine_ms"] = (result.elapsed.as_millis() as u64).into();
+        report["frames_captured"] = result.frames_captured.into();
+        report["frames_with_face"] = result.frames_with_face.into();
+        report["dark_skipped"] = result.dark_skipped.into();
+    }
+    report
+}
+
+/// Why a verify did not match, for the `VerifyDetailed` report: the check
+/// that turned the face down, else `below_threshold`; `None` on a match.
+fn failure_reason(result: &crate::engine::VerifyResult) -> Option<&'static str> {
+    if result.result.matched {
+        return None;
+    }
+    Some(result.rejection.map_or("below_threshold", |r| r.as_str()))
+}
+
+/// The camera a verify ran on, when none of the gallery's templates were
+/// enrolled on it. Templates enrolled before device ids were recorded are
+/// ignored, so such galleries never mismatch.
+fn foreign_device<'a>(
+    enrolled: &BTreeSet<String>,
+    capture: Option<&'a CaptureContext>,
+) -> Option<&'a str> {
+    let current = capture?.device_id.as_str();
+    (!enrolled.is_empty() && !current.is_empty() && !enrolled.contains(current)).then_some(current)
+}
+
+/// Fetch the gallery `user` is verified against. A user without templates
+/// fails with `NotEnrolled`, which clients may treat as routine; a store that
+/// cannot be read fails with `StoreUnavailable`, naming the error class.
+async fn fetch_verify_gallery(
+    store: &FaceModelStore,
+    user: &str,
+) -> Result<Vec<FaceModel>, VisageError> {
+    let gallery = store.get_gallery_for_user(user).await.map_err(|e| {
+        tracing::error!(user, class = e.class(), error = %e, "gallery fetch failed");
+        VisageError::from(e)
+    })?;
+    if gallery.is_empty() {
+        tracing::info!(user, "no enrolled models");
+        return Err(VisageError::NotEnrolled(format!(
+            "no enrolled models for user '{user}'"
+        )));
+    }
+    Ok(gallery)
+}
+
+/// Refuse to enroll or verify `user` with a recognizer other than the one
+/// they are pinned to, rather than silently matching across model versions.
+async fn check_model_pin(
+    store: &FaceModelStore,
+    user: &str,
+    active_version: &str,
+) -> Result<(), VisageError> {
+    let settings = store
+        .get_user_settings(user)
+        .await
+        .map_err(|e| VisageError::Failed(e.to_string()))?;
+    match settings.model_version {
+        Some(pinned) if pinned != active_version => {
+            tracing::warn!(user, pinned, active_version, "user pinned to another model");
+            Err(VisageError::ModelMismatch(format!(
+                "user '{user}' is pinned to model '{pinned}', but this daemon runs \
+                 '{active_version}'"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Refuse a face unlock outside `VISAGE_VERIFY_SCHEDULE`'s time windows.
+fn check_verify_schedule(config: &Config, user: &str) -> Result<(), VisageError> {
+    if config.verify_schedule.permits_at(&chrono::Local::now()) {
+        return Ok(());
+    }
+    tracing::info!(user, "verify: outside permitted time windows");
+    Err(VisageError::PolicyDenied(
+        "face unlock is not permitted at this time".to_string(),
+    ))
+}
+
+/// Refuse a new enrollment whose `embedding` has cosine similarity of at
+/// least `threshold` to one of `user`'s templates, since it would add verify
+/// cost without improving accuracy. With `allow` the enrollment goes ahead
+/// and the template it repeats is returned for the store to record. A
+/// `threshold` of `0.0` disables the check.
+async fn check_duplicate(
+    store: &FaceModelStore,
+    user: &str,
+    embedding: &visage_core::Embedding,
+    threshold: f32,
+    allow: bool,
+) -> Result<Option<String>, VisageError> {
+    if threshold <= 0.0 {
+        return Ok(None);
+    }
+    let Some((model, similarity)) = store.nearest_template(user, embedding).await? else {
+        return Ok(None);
+    };
+    if similarity < threshold {
+        return Ok(None);
+    }
+    if allow {
+        tracing::warn!(
+            user,
+            duplicate_of = %model.id,
+            similarity,
+            "enroll: storing near-duplicate template"
+        );
+        return Ok(Some(model.id));
+    }
+    tracing::warn!(
+        user,
+        duplicate_of = %model.id,
+        similarity,
+        "enroll: near-duplicate refused"
+    );
+    Err(VisageError::DuplicateEnrollment(format!(
+        "face matches existing model '{}' ({}) with similarity {similarity:.3}; \
+         keep using it, or remove it first to replace it",
+        model.label, model.id
+    )))
+}
+
+/// Address signals about a capture (`EnrollProgress` and the `Verify*`
+/// signals) to `client`, the caller's unique name, only, so other peers on
+/// the bus do not learn who is enrolling or how well a face matched.
+/// Peer-to-peer connections have no sender and get the signals undirected.
+fn caller_emitter<'a>(emitter: SignalEmitter<'a>, client: &str) -> SignalEmitter<'a> {
+    match zbus::names::UniqueName::try_from(client) {
+        Ok(client) => emitter.set_destination(client.into_owned().into()),
+        Err(_) => emitter,
+    }
+}
+
+/// The caller's unique bus name, under which its captures are registered
+/// for `Cancel`. Empty for a peer-to-peer message, which has no sender.
+fn sender_name(header: &zbus::message::Header<'_>) -> String {
+    header.sender().map(|s| s.to_string()).unwrap_or_default()
+}
+
+/// Emit one `EnrollProgress` signal. A caller that went away does not fail
+/// the enrollment.
+async fn emit_enroll_progress(emitter: &SignalEmitter<'_>, user: &str, update: EnrollProgress) {
+    let sent = VisageService::enroll_progress(
+        emitter,
+        user,
+        update.frames_captured as u32,
+        update.frames_total as u32,
+        f64::from(update.quality),
+    )
+    .await;
+    if let Err(e) = sent {
+        tracing::debug!(user, error = %e, "enroll: progress signal not sent");
+    }
+}
+
+/// Emit `VerificationStarted` for `user`. A failed emit does not fail the
+/// verify.
+async fn emit_verification_started(emitter: &SignalEmitter<'_>, user: &str) {
+    if let Err(e) = VisageService::verification_started(emitter, user).await {
+        tracing::debug!(user, error = %e, "verify: started signal not sent");
+    }
+}
+
+/// Emit `VerificationCompleted` for `user` with the result of `outcome`.
+async fn emit_verification_completed(
+    emitter: &SignalEmitter<'_>,
+    user: &str,
+    outcome: &Result<(crate::engine::VerifyResult, std::time::Duration), VisageError>,
+) {
+    let matched = matches!(outcome, Ok((result, _)) if result.result.matched);
+    let reason = completion_reason(outcome);
+    if let Err(e) = VisageService::verification_completed(emitter, user, matched, reason).await {
+        tracing::debug!(user, error = %e, "verify: completed signal not sent");
+    }
+}
+
+/// Emit `VerifyStarted` for `user` to the caller. A failed emit does not
+/// fail the verify.
+async fn emit_verify_started(caller: &SignalEmitter<'_>, user: &str) {
+    if let Err(e) = VisageService::verify_started(caller, user).await {
+        tracing::debug!(user, error = %e, "verify: caller's started signal not sent");
+    }
+}
+
+/// Emit one `VerifyProgress` signal to the caller.
+async fn emit_verify_progress(caller: &SignalEmitter<'_>, user: &str, update: VerifyProgress) {
+    let sent = VisageService::verify_progress(
+        caller,
+        user,
+        update.frames_done as u32,
+        update.frames_total as u32,
+    )
+    .await;
+    if let Err(e) = sent {
+        tracing::debug!(user, error = %e, "verify: progress signal not sent");
+    }
+}
+
+/// Emit `VerifyCompleted` for `user` to the caller with the result of
+/// `outcome`; a verify that failed reports a similarity of 0.
+async fn emit_verify_completed(
+    caller: &SignalEmitter<'_>,
+    user: &str,
+    outcome: &Result<(crate::engine::VerifyResult, std::time::Duration), VisageError>,
+) {
+    let (matched, similarity) = match outcome {
+        Ok((result, _)) => (result.result.matched, f64::from(result.result.similarity)),
+        Err(_) => (false, 0.0),
+    };
+    if let Err(e) = VisageService::verify_completed(caller, user, matched, similarity).await {
+        tracing::debug!(user, error = %e, "verify: caller's completed signal not sent");
+    }
+}
+
+/// Await `work` while passing each update it sends on `updates` to `relay`.
+/// The reply never waits for the sender to be dropped: an engine the
+/// watchdog abandoned, or a request still queued when its caller gave up,
+/// holds on to it. Updates already queued when `work` ends are relayed.
+async fn relaying<T, U, Fut>(
+    work: impl std::future::Future<Output = T>,
+    updates: &mut tokio::sync::mpsc::UnboundedReceiver<U>,
+    relay: impl Fn(U) -> Fut,
+) -> T
+where
+    Fut: std::future::Future<Output = ()>,
+{
+    tokio::pin!(work);
+    let result = loop {
+        tokio::select! {
+            result = &mut work => break result,
+            Some(update) = updates.recv() => relay(update).await,
+        }
+    };
+    while let Ok(update) = updates.try_recv() {
+        relay(update).await;
+    }
+    result
+}
+
+/// `reason` of a `VerificationCompleted` signal: empty on a match, else
+/// `no-face`, `no-match`, `timeout` or `error`.
+fn completion_reason(
+    outcome: &Result<(crate::engine::VerifyResult, std::time::Duration), VisageError>,
+) -> &'static str {
+    match outcome {
+        Ok((result, _)) if result.result.matched => "",
+        Ok((result, _)) if result.frames_with_face == 0 => "no-face",
+        Ok(_) => "no-match",
+        Err(VisageError::Timeout(_)) => "timeout",
+        Err(_) => "error",
+    }
 }
 
 /// D-Bus interface for the Visage biometric daemon.
@@ -22,6 +348,8 @@ pub struct AppState {
 /// Object path: /org/freedesktop/Visage1
 pub struct VisageService {
     pub state: Arc<Mutex<AppState>>,
+    /// Per-client request limits, checked before a handler takes `state`.
+    pub limiter: RequestLimiter,
 }
 
 /// Retrieve the UID of the D-Bus peer identified by `sender_str` (a unique bus name).
@@ -37,159 +365,634 @@ async fn get_caller_uid(sender_str: &str, conn: &zbus::Connection) -> zbus::fdo:
         .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
 }
 
-/// Look up the numeric UID for a username via NSS.
-fn uid_for_name(name: &str) -> Option<u32> {
-    match User::from_name(name) {
-        Ok(Some(user)) => Some(user.uid.as_raw()),
-        Ok(None) => None,
-        Err(_) => None,
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn get_session_by_pid(&self, pid: u32) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+}
+
+/// How callers are checked, from the bus the daemon serves on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BusAccess {
+    /// System bus: root may do anything, other users act on their own account.
+    System,
+    /// Session bus (development mode): every caller is the same user, so
+    /// caller checks are skipped.
+    Session,
+    /// Session bus with `VISAGE_SESSION_BUS_HARDENED`: checked as on the
+    /// system bus, with the daemon's own user in root's place. Other users
+    /// on a shared session bus act only on their own account.
+    HardenedSession { owner_uid: u32 },
+}
+
+impl BusAccess {
+    fn of(config: &Config) -> Self {
+        match (config.session_bus, config.session_bus_hardened) {
+            (false, _) => Self::System,
+            (true, false) => Self::Session,
+            (true, true) => Self::HardenedSession {
+                owner_uid: nix::unistd::getuid().as_raw(),
+            },
+        }
+    }
+
+    /// Whether callers go unchecked.
+    fn unchecked(self) -> bool {
+        self == Self::Session
+    }
+
+    /// Whether `caller_uid` may do what root may.
+    fn privileged(self, caller_uid: u32) -> bool {
+        match self {
+            Self::HardenedSession { owner_uid } => caller_uid == 0 || caller_uid == owner_uid,
+            _ => caller_uid == 0,
+        }
     }
 }
 
-#[interface(name = "org.freedesktop.Visage1")]
-impl VisageService {
-    /// Enroll a new face model for the given user.
-    ///
-    /// Returns the UUID of the newly created model.
-    async fn enroll(&self, user: &str, label: &str) -> zbus::fdo::Result<String> {
-        tracing::info!(user, label, "enroll requested");
+/// The logind session of the peer `sender_str`, as its session object path.
+/// logind lives on the system bus, so a development daemon on the session
+/// bus opens a system connection to ask it.
+async fn get_caller_session(
+    sender_str: &str,
+    access: BusAccess,
+    conn: &zbus::Connection,
+) -> zbus::fdo::Result<String> {
+    let failed = |e: zbus::Error| zbus::fdo::Error::Failed(e.to_string());
+    let dbus_proxy = zbus::fdo::DBusProxy::new(conn).await.map_err(failed)?;
+    let bus_name = zbus::names::BusName::try_from(sender_str)
+        .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    let pid = dbus_proxy.get_connection_unix_process_id(bus_name).await?;
+    let system;
+    let logind_conn = if access != BusAccess::System {
+        system = zbus::Connection::system().await.map_err(failed)?;
+        &system
+    } else {
+        conn
+    };
+    let manager = LoginManagerProxy::new(logind_conn).await.map_err(failed)?;
+    let session = manager.get_session_by_pid(pid).await.map_err(failed)?;
+    Ok(session.to_string())
+}
 
-        // Copy values while holding lock, then release
-        let (engine, frames_count) = {
-            let state = self.state.lock().await;
-            (state.engine.clone(), state.config.frames_per_enroll)
-        };
+/// Look up the numeric UID for a username via NSS: `Ok(None)` when there is
+/// no such account, `Err` when the lookup itself failed.
+fn uid_for_name(name: &str) -> nix::Result<Option<u32>> {
+    User::from_name(name).map(|user| user.map(|user| user.uid.as_raw()))
+}
 
-        // Run engine (no lock held)
-        let result = engine.enroll(frames_count).await.map_err(|e| {
-            tracing::error!(error = %e, "enroll failed");
-            zbus::fdo::Error::Failed(e.to_string())
-        })?;
+/// How long [`resolve_uid`] keeps retrying a transient NSS failure, and the
+/// pause between tries. Short: a PAM conversation is waiting on the answer.
+const NSS_RETRY_DEADLINE: std::time::Duration = std::time::Duration::from_millis(500);
+const NSS_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
 
-        tracing::info!(
-            quality = result.quality_score,
-            "enroll: embedding extracted"
+/// Whether a failed NSS lookup is worth repeating: the errors a directory
+/// backend (sssd, nslcd) reports while it reconnects.
+fn nss_error_is_transient(e: nix::Error) -> bool {
+    matches!(
+        e,
+        nix::Error::EAGAIN
+            | nix::Error::EINTR
+            | nix::Error::EBUSY
+            | nix::Error::ETIMEDOUT
+            | nix::Error::ECONNREFUSED
+            | nix::Error::ECONNRESET
+    )
+}
+
+/// Resolve `name` to a UID with `lookup` (normally [`uid_for_name`]),
+/// retrying transient failures until [`NSS_RETRY_DEADLINE`]. `Ok(None)` means
+/// the account does not exist; a lookup that keeps failing is
+/// `DirectoryUnavailable`, never "unknown user".
+async fn resolve_uid(
+    name: &str,
+    lookup: impl Fn(&str) -> nix::Result<Option<u32>>,
+) -> Result<Option<u32>, VisageError> {
+    let deadline = std::time::Instant::now() + NSS_RETRY_DEADLINE;
+    loop {
+        match lookup(name) {
+            Ok(uid) => return Ok(uid),
+            Err(e)
+                if nss_error_is_transient(e)
+                    && std::time::Instant::now() + NSS_RETRY_DELAY < deadline =>
+            {
+                tracing::debug!(user = name, error = %e, "account lookup failed, retrying");
+                // Not tokio's timer: handlers run on zbus's executor.
+                async_io::Timer::after(NSS_RETRY_DELAY).await;
+            }
+            Err(e) => {
+                tracing::warn!(user = name, error = %e, "account lookup failed");
+                return Err(VisageError::DirectoryUnavailable(format!(
+                    "cannot look up account '{name}': {e}"
+                )));
+            }
+        }
+    }
+}
+
+/// Whether `name` is a known account. Unlike [`resolve_uid`], a failed NSS
+/// lookup (an unreachable directory server, say) is an error rather than "no
+/// such user".
+fn account_exists(name: &str) -> nix::Result<bool> {
+    User::from_name(name).map(|user| user.is_some())
+}
+
+/// Enrolled users whose accounts no longer exist, judged by `exists`.
+///
+/// Users whose lookup fails, and non-UTF-8 names NSS cannot be asked about,
+/// are never reported: a purge must not follow from a lookup outage. The
+/// lookups can block on a directory server, so they run on the blocking
+/// pool with the state lock released.
+async fn find_orphans(
+    state: &Mutex<AppState>,
+    exists: impl Fn(&str) -> nix::Result<bool> + Send + 'static,
+) -> Result<Vec<String>, crate::store::StoreError> {
+    let (users, percent_encoded) = {
+        let state = state.lock().await;
+        (
+            state.store.list_users().await?,
+            state.config.percent_encoded_usernames,
+        )
+    };
+    let orphans = blocking::unblock(move || {
+        let mut orphans = Vec::new();
+        for user in users {
+            let Some(name) = username::nss_name(&user, percent_encoded) else {
+                continue;
+            };
+            match exists(&name) {
+                Ok(true) => {}
+                Ok(false) => orphans.push(user),
+                Err(e) => tracing::warn!(user, error = %e, "orphan check: account lookup failed"),
+            }
+        }
+        orphans
+    })
+    .await;
+    Ok(orphans)
+}
+
+/// Look up the account name for a UID via NSS, for audit records.
+fn name_for_uid(uid: u32) -> Option<String> {
+    match User::from_uid(nix::unistd::Uid::from_raw(uid)) {
+        Ok(Some(user)) => Some(user.name),
+        _ => None,
+    }
+}
+
+/// Reject callers other than root (see [`BusAccess::privileged`]).
+///
+/// On the session bus (development mode) every caller is the same user, so the
+/// check is skipped unless hardened.
+async fn require_root(
+    method: &str,
+    access: BusAccess,
+    header: &zbus::message::Header<'_>,
+    conn: &zbus::Connection,
+) -> zbus::fdo::Result<()> {
+    if access.unchecked() {
+        return Ok(());
+    }
+    let sender = header
+        .sender()
+        .ok_or_else(|| zbus::fdo::Error::Failed("no sender in message".to_string()))?;
+    let caller_uid = get_caller_uid(sender.as_str(), conn).await?;
+    check_root(method, access, caller_uid)
+}
+
+/// The caller-UID half of [`require_root`].
+fn check_root(method: &str, access: BusAccess, caller_uid: u32) -> zbus::fdo::Result<()> {
+    if !access.privileged(caller_uid) {
+        tracing::warn!(method, caller_uid, "non-root caller rejected");
+        return Err(zbus::fdo::Error::AccessDenied(format!(
+            "{method} is restricted to root"
+        )));
+    }
+    Ok(())
+}
+
+/// Reject callers other than root or the account `user` (already canonical),
+/// unless `access` skips caller checks; see [`check_caller`].
+async fn require_root_or_self(
+    method: &str,
+    access: BusAccess,
+    user: &str,
+    percent_encoded: bool,
+    header: &zbus::message::Header<'_>,
+    conn: &zbus::Connection,
+) -> Result<(), VisageError> {
+    if access.unchecked() {
+        return Ok(());
+    }
+    let sender = header
+        .sender()
+        .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+    let caller_uid = get_caller_uid(sender.as_str(), conn).await?;
+    check_caller(
+        method,
+        access,
+        user,
+        percent_encoded,
+        caller_uid,
+        uid_for_name,
+    )
+    .await
+}
+
+/// Whether `caller_uid` may call `method` on the canonical `user`: root (see
+/// [`BusAccess::privileged`]) always, anyone else only as that account,
+/// resolved with `lookup` (see [`resolve_uid`]). Non-UTF-8 accounts have no
+/// NSS name to compare, so only root may act on them.
+async fn check_caller(
+    method: &str,
+    access: BusAccess,
+    user: &str,
+    percent_encoded: bool,
+    caller_uid: u32,
+    lookup: impl Fn(&str) -> nix::Result<Option<u32>>,
+) -> Result<(), VisageError> {
+    if access.unchecked() || access.privileged(caller_uid) {
+        return Ok(());
+    }
+    let Some(nss_name) = username::nss_name(user, percent_encoded) else {
+        tracing::warn!(
+            method,
+            user,
+            caller_uid,
+            "non-UTF-8 account needs root caller"
         );
+        return Err(VisageError::AccessDenied(format!(
+            "only root may call {method} for non-UTF-8 account '{user}'"
+        )));
+    };
+    match resolve_uid(&nss_name, lookup).await? {
+        Some(expected_uid) if caller_uid == expected_uid => Ok(()),
+        Some(_) => {
+            tracing::warn!(
+                method,
+                user,
+                caller_uid,
+                "caller UID does not match target user UID"
+            );
+            Err(VisageError::AccessDenied(format!(
+                "caller is not permitted to call {method} for user '{user}'"
+            )))
+        }
+        // Denied like any other account the caller is not: a non-root
+        // caller needs no way to tell a missing account from someone else's.
+        None => {
+            tracing::warn!(method, user, caller_uid, "unknown user");
+            Err(VisageError::AccessDenied(format!(
+                "caller is not permitted to call {method} for user '{user}'"
+            )))
+        }
+    }
+}
 
-        // Store result (re-acquire lock)
-        let state = self.state.lock().await;
-        let model_id = state
-            .store
-            .insert(user, label, &result.embedding, result.quality_score)
+impl VisageService {
+    /// Admit a request from the message's sender, or refuse it with
+    /// `TooManyRequests` (see [`crate::request_limit`]). Hold the guard for
+    /// the rest of the handler.
+    fn admit(
+        &self,
+        header: &zbus::message::Header<'_>,
+        class: RequestClass,
+    ) -> Result<InFlight<'_>, VisageError> {
+        let client = header.sender().map(|s| s.as_str()).unwrap_or_default();
+        self.limiter.admit(client, class)
+    }
+
+    /// Run a camera verify for `user` with every check `Verify` applies:
+    /// time windows, caller UID, rate limit and model pin. Returns the engine
+    /// result and the time since the request arrived. Once the rate limit
+    /// passes, the verify is announced through `emitter` (see
+    /// [`verify_capture`](Self::verify_capture)).
+    async fn run_verify(
+        &self,
+        user: &str,
+        header: &zbus::message::Header<'_>,
+        conn: &zbus::Connection,
+        emitter: &SignalEmitter<'_>,
+    ) -> Result<(crate::engine::VerifyResult, std::time::Duration), VisageError> {
+        tracing::info!(user, "verify requested");
+        let started = std::time::Instant::now();
+        let (user, _) = self.authorize_verify(user, header, conn).await?;
+        let session = self.grace_session(header, conn).await;
+        // Only a permissive session bus lets a message without a sender
+        // through; only `CancelUser` can reach its verify.
+        let client = sender_name(header);
+        self.verify_canonical(&user, session.as_deref(), &client, started, Some(emitter))
             .await
-            .map_err(|e| {
-                tracing::error!(error = %e, "enroll: store insert failed");
-                zbus::fdo::Error::Failed(e.to_string())
-            })?;
+    }
 
-        tracing::info!(model_id = %model_id, user, label, "enrolled successfully");
-        Ok(model_id)
+    /// `VerifyFresh` for an authorized, canonical `user`: true from a match
+    /// in `session` younger than `max_age`, else the result of a capture.
+    async fn fresh_or_capture(
+        &self,
+        user: &str,
+        session: Option<&str>,
+        max_age: std::time::Duration,
+        client: &str,
+        started: std::time::Instant,
+        emitter: &SignalEmitter<'_>,
+    ) -> Result<bool, VisageError> {
+        let cached = match session {
+            Some(session) => self
+                .state
+                .lock()
+                .await
+                .verify_grace
+                .is_fresh(user, session, max_age),
+            None => false,
+        };
+        if cached {
+            tracing::info!(user, session, "verify_fresh answered from a recent match");
+            return Ok(true);
+        }
+        tracing::info!(user, session, "verify_fresh: no recent match, capturing");
+        let (result, _) = self
+            .verify_canonical(user, session, client, started, Some(emitter))
+            .await?;
+        Ok(result.result.matched)
     }
 
-    /// Verify the current face against enrolled models for the given user.
-    ///
-    /// Returns true if the face matches any enrolled model above the threshold.
-    ///
-    /// Security: on the system bus the caller UID is validated against the target
-    /// username before any camera access or rate-limit check.  Root (UID 0) is always
-    /// permitted.  On the session bus (development mode) UID validation is skipped.
-    async fn verify(
+    /// `RedeemToken` for a canonical `user` the caller may act for, from
+    /// logind `session`. The lockout and model pin are checked before the
+    /// token is touched, so a refusal does not use it up.
+    async fn redeem_in_session(
         &self,
         user: &str,
-        #[zbus(header)] header: zbus::message::Header<'_>,
-        #[zbus(connection)] conn: &zbus::Connection,
-    ) -> zbus::fdo::Result<bool> {
-        tracing::info!(user, "verify requested");
+        token: &str,
+        session: &str,
+    ) -> Result<bool, VisageError> {
+        let mut state = self.state.lock().await;
+        state.rate_limiter.check(user).map_err(|remaining| {
+            tracing::warn!(user, "redeem_token: rate limited");
+            VisageError::rate_limited(remaining, "too many failed attempts")
+        })?;
+        check_model_pin(&state.store, user, ARCFACE_MODEL_VERSION).await?;
+        match state.verify_tokens.redeem(token, user, session) {
+            Ok(()) => {
+                tracing::info!(user, "verification token redeemed");
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::warn!(user, error = %e, "verification token rejected");
+                Ok(false)
+            }
+        }
+    }
 
-        // Read session_bus flag without holding lock across the async UID lookup
-        let session_bus = self.state.lock().await.config.session_bus;
+    /// The caller's logind session, which keys its `VerifyCached` successes.
+    /// `None` when the grace period is off or the caller has no session, in
+    /// which case nothing is remembered for it or answered from memory.
+    async fn grace_session(
+        &self,
+        header: &zbus::message::Header<'_>,
+        conn: &zbus::Connection,
+    ) -> Option<String> {
+        let access = {
+            let state = self.state.lock().await;
+            if !state.verify_grace.enabled() {
+                return None;
+            }
+            BusAccess::of(&state.config)
+        };
+        let sender = header.sender()?;
+        match get_caller_session(sender.as_str(), access, conn).await {
+            Ok(session) => Some(session),
+            Err(e) => {
+                tracing::info!(error = %e, "verify: caller has no logind session, no grace");
+                None
+            }
+        }
+    }
 
-        // --- UID validation (system bus only) ---
-        if !session_bus {
-            let sender = header
-                .sender()
-                .ok_or_else(|| zbus::fdo::Error::Failed("no sender in message".to_string()))?;
-            let caller_uid = get_caller_uid(sender.as_str(), conn).await?;
-            if caller_uid != 0 {
-                match uid_for_name(user) {
-                    Some(expected_uid) if caller_uid == expected_uid => {}
-                    Some(_) => {
-                        tracing::warn!(
-                            user,
-                            caller_uid,
-                            "verify: caller UID does not match target user UID"
-                        );
-                        return Err(zbus::fdo::Error::AccessDenied(format!(
-                            "caller is not permitted to verify user '{user}'"
-                        )));
-                    }
-                    None => {
-                        tracing::warn!(user, "verify: unknown user");
-                        return Err(zbus::fdo::Error::Failed(format!("unknown user '{user}'")));
-                    }
+    /// The checks `Verify` makes before any capture: time windows, then that
+    /// the caller may verify `user` (root, or `user` themselves on the system
+    /// bus). Returns the canonical name and the caller's UID.
+    async fn authorize_verify(
+        &self,
+        user: &str,
+        header: &zbus::message::Header<'_>,
+        conn: &zbus::Connection,
+    ) -> Result<(String, u32), VisageError> {
+        // Read flags without holding lock across the async UID lookup
+        let (access, percent_encoded, permitted) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+                check_verify_schedule(&state.config, user),
+            )
+        };
+
+        // --- Time-window policy (before any other check or camera access) ---
+        permitted?;
+
+        let user = username::canonicalize(user, percent_encoded)?;
+        let caller_uid = match header.sender() {
+            Some(sender) => get_caller_uid(sender.as_str(), conn).await?,
+            // Only a peer-to-peer connection delivers no sender. On the
+            // permissive session bus every caller is the daemon's own user.
+            None if access.unchecked() => nix::unistd::getuid().as_raw(),
+            None => return Err(VisageError::Failed("no sender in message".to_string())),
+        };
+
+        // --- UID validation (skipped on a permissive session bus) ---
+        // Root callers (the PAM stack) may verify anyone, so only other
+        // callers depend on NSS answering.
+        check_caller(
+            "Verify",
+            access,
+            &user,
+            percent_encoded,
+            caller_uid,
+            uid_for_name,
+        )
+        .await?;
+
+        Ok((user, caller_uid))
+    }
+
+    /// Verify an already canonical `user` once
+    /// [`authorize_verify`](Self::authorize_verify) has passed. `started` is
+    /// when the request arrived, and `client` the caller's unique bus name,
+    /// which `Cancel` matches. A match is remembered for `VerifyCached` in
+    /// the caller's logind `session`, if it has one; any other outcome
+    /// forgets the user's earlier matches.
+    async fn verify_canonical(
+        &self,
+        user: &str,
+        session: Option<&str>,
+        client: &str,
+        started: std::time::Instant,
+        signals: Option<&SignalEmitter<'_>>,
+    ) -> Result<(crate::engine::VerifyResult, std::time::Duration), VisageError> {
+        let outcome = self.verify_capture(user, client, started, signals).await;
+        let mut state = self.state.lock().await;
+        match &outcome {
+            Ok((result, _)) if result.result.matched => {
+                if let Some(session) = session {
+                    state.verify_grace.record_success(user, session);
                 }
             }
+            _ => state.verify_grace.forget_user(user),
         }
+        outcome
+    }
 
+    /// Capture and match for [`verify_canonical`](Self::verify_canonical).
+    /// A verify that passes the rate limit is bracketed by the
+    /// `VerificationStarted` and `VerificationCompleted` signals, whatever
+    /// its outcome. `client` gets `VerifyStarted`, `VerifyProgress` for each
+    /// frame and `VerifyCompleted` as well.
+    async fn verify_capture(
+        &self,
+        user: &str,
+        client: &str,
+        started: std::time::Instant,
+        signals: Option<&SignalEmitter<'_>>,
+    ) -> Result<(crate::engine::VerifyResult, std::time::Duration), VisageError> {
         // --- Rate limit check ---
         {
             let mut state = self.state.lock().await;
-            state.rate_limiter.check(user).map_err(|msg| {
+            state.rate_limiter.check(user).map_err(|remaining| {
                 tracing::warn!(user, "verify: rate limited");
-                zbus::fdo::Error::Failed(msg)
+                VisageError::rate_limited(remaining, "too many failed attempts")
             })?;
         }
 
+        let caller = signals.map(|emitter| caller_emitter(emitter.clone(), client));
+        if let (Some(emitter), Some(caller)) = (signals, &caller) {
+            emit_verification_started(emitter, user).await;
+            emit_verify_started(caller, user).await;
+        }
+        let outcome = self
+            .verify_match(user, client, started, caller.as_ref())
+            .await;
+        if let (Some(emitter), Some(caller)) = (signals, &caller) {
+            emit_verification_completed(emitter, user, &outcome).await;
+            emit_verify_completed(caller, user, &outcome).await;
+        }
+        outcome
+    }
+
+    /// The part of [`verify_capture`](Self::verify_capture) after the rate
+    /// limit: gallery fetch, engine capture and the post-match checks. Each
+    /// frame the engine matches is announced as `VerifyProgress` through
+    /// `progress`.
+    async fn verify_match(
+        &self,
+        user: &str,
+        client: &str,
+        started: std::time::Instant,
+        progress: Option<&SignalEmitter<'_>>,
+    ) -> Result<(crate::engine::VerifyResult, std::time::Duration), VisageError> {
         // --- Fetch gallery and config (release lock before engine call) ---
-        let (engine, gallery, threshold, frames_count, timeout_secs, liveness_enabled, liveness_min_displacement) = {
-            let state = self.state.lock().await;
-            let gallery = state.store.get_gallery_for_user(user).await.map_err(|e| {
-                tracing::error!(error = %e, "verify: gallery fetch failed");
-                zbus::fdo::Error::Failed(e.to_string())
-            })?;
+        let (
+            engine,
+            gallery,
+            centroid,
+            threshold,
+            frames_count,
+            timeout_secs,
+            liveness,
+            require_pose_match,
+            require_enrolled_device,
+            consistency_floor,
+            lighting_tolerance,
+            (ticket, cancel),
+        ) = {
+            let mut state = self.state.lock().await;
+            check_model_pin(&state.store, user, ARCFACE_MODEL_VERSION).await?;
+            let gallery = fetch_verify_gallery(&state.store, user).await?;
+            let require_pose_match = state.config.verify_require_pose_match;
+            // A missing or unreadable centroid only costs the fast path. The
+            // pose check needs a template, so it rules the fast path out.
+            let centroid = if state.config.centroid_matching && !require_pose_match {
+                match state.store.get_centroid(user).await {
+                    Ok(centroid) => centroid.map(|centroid| CentroidGate {
+                        centroid: centroid.embedding,
+                        margin: state.config.centroid_margin,
+                        spread: centroid.spread,
+                    }),
+                    Err(e) => {
+                        tracing::warn!(user, error = %e, "verify: centroid fetch failed");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
             (
                 state.engine.clone(),
                 gallery,
+                centroid,
                 state.config.similarity_threshold,
                 state.config.frames_per_verify,
                 state.config.verify_timeout_secs,
-                state.config.liveness_enabled,
-                state.config.liveness_min_displacement,
+                state
+                    .config
+                    .liveness_enabled
+                    .then(|| state.config.liveness_policy()),
+                require_pose_match,
+                state.config.verify_require_enrolled_device,
+                Some(state.config.verify_consistency_floor).filter(|floor| *floor > 0.0),
+                Some(state.config.verify_lighting_tolerance).filter(|tolerance| *tolerance > 0.0),
+                state.pending_verifies.register(client, Some(user)),
             )
         };
-
-        if gallery.is_empty() {
-            tracing::warn!(user, "verify: no enrolled models");
-            return Err(zbus::fdo::Error::Failed(format!(
-                "no enrolled models for user '{user}'"
-            )));
-        }
+        let enrolled_devices: BTreeSet<String> =
+            gallery.iter().filter_map(|m| m.device_id.clone()).collect();
 
         // --- Run engine with timeout (no lock held) ---
         // Runtime errors (camera failure, timeout) are returned as Err and do NOT count
         // as rate-limit failures. Liveness failures are treated as deliberate auth failures
-        // and converted to non-match so they are rate-limited like other failed attempts.
+        // and converted to non-match so they are rate-limited like other failed attempts;
+        // a face change mid-capture stays an error but is rate-limited the same way.
+        // Progress is relayed only while the request runs; the reply does
+        // not wait for the engine to drop its sender.
         let timeout = std::time::Duration::from_secs(timeout_secs);
-        let result = match engine
-            .verify(
-                gallery,
-                threshold,
-                frames_count,
-                timeout,
-                liveness_enabled,
-                liveness_min_displacement,
-            )
-            .await
-        {
+        let engine_started = std::time::Instant::now();
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+        let progress_tx = progress.map(|_| progress_tx);
+        let verify = engine.verify(
+            gallery,
+            centroid,
+            threshold,
+            frames_count,
+            timeout,
+            liveness,
+            consistency_floor,
+            lighting_tolerance,
+            progress_tx,
+            cancel,
+        );
+        let outcome = relaying(verify, &mut progress_rx, |update| async move {
+            if let Some(caller) = progress {
+                emit_verify_progress(caller, user, update).await;
+            }
+        })
+        .await;
+        self.state.lock().await.pending_verifies.finish(ticket);
+        let mut result = match outcome {
             Ok(result) => result,
             Err(EngineError::LivenessCheckFailed {
                 displacement,
                 threshold,
+                live_pairs,
+                min_live_pairs,
             }) => {
                 tracing::warn!(
                     user,
                     displacement,
                     threshold,
+                    live_pairs,
+                    min_live_pairs,
                     "verify: liveness check failed — treating as non-match"
                 );
                 crate::engine::VerifyResult {
@@ -200,17 +1003,86 @@ impl VisageService {
                         model_label: None,
                     },
                     best_quality: 0.0,
+                    best_alignment: 0.0,
+                    frames_captured: 0,
+                    frames_with_face: 0,
+                    dark_skipped: 0,
+                    elapsed: engine_started.elapsed(),
+                    frame_memory: Default::default(),
+                    capture: None,
+                    fast_path: false,
+                    pose_mismatch: None,
+                    rejection: Some(crate::engine::Rejection::Liveness),
                 }
             }
+            Err(EngineError::VerifyTimeout) => {
+                tracing::error!(user, timeout_secs, "verify timed out");
+                return Err(VisageError::Timeout(format!(
+                    "verification did not complete within {timeout_secs}s"
+                )));
+            }
+            Err(e @ EngineError::ClientCancelled) => {
+                tracing::info!(user, client, "verify cancelled by client");
+                return Err(e.into());
+            }
+            Err(e @ EngineError::FaceChangedDuringCapture { .. }) => {
+                tracing::warn!(user, error = %e, "verify: face changed during capture");
+                self.state.lock().await.rate_limiter.record_failure(user);
+                return Err(e.into());
+            }
             Err(e) => {
                 tracing::error!(error = %e, "verify failed");
-                return Err(zbus::fdo::Error::Failed(e.to_string()));
+                return Err(e.into());
             }
         };
 
-        // --- Record rate-limit outcome ---
+        if let Some(mismatch) = result.pose_mismatch {
+            tracing::warn!(
+                user,
+                live_pose = %mismatch.live,
+                template_pose = %mismatch.template,
+                model_id = ?result.result.model_id,
+                rejected = require_pose_match && result.result.matched,
+                "verify: live pose differs from the matched template's pose"
+            );
+            if require_pose_match && result.result.matched {
+                result.result.matched = false;
+                result.rejection = Some(crate::engine::Rejection::PoseMismatch);
+            }
+        }
+
+        if let Some(current) = foreign_device(&enrolled_devices, result.capture.as_ref()) {
+            tracing::warn!(
+                user,
+                current_device = current,
+                enrolled_devices = ?enrolled_devices,
+                rejected = require_enrolled_device && result.result.matched,
+                "verify: camera differs from the one the face was enrolled on; \
+                 check VISAGE_CAMERA_DEVICE or re-enroll on this camera"
+            );
+            if require_enrolled_device && result.result.matched {
+                result.result.matched = false;
+                result.rejection = Some(crate::engine::Rejection::ForeignDevice);
+            }
+        }
+
+        // --- Record rate-limit outcome and aggregate stats ---
         {
             let mut state = self.state.lock().await;
+            if result.frames_captured > 0 {
+                state
+                    .stats
+                    .record_verify(engine_started.elapsed(), result.best_quality);
+                state
+                    .stats
+                    .record_capture(result.frames_captured, result.dark_skipped);
+                state
+                    .stats
+                    .record_frame_memory(result.frame_memory.frames, result.frame_memory.bytes);
+            }
+            if let Some(capture) = &result.capture {
+                state.stats.record_verify_capture(capture.clone());
+            }
             if result.result.matched {
                 state.rate_limiter.record_success(user);
             } else {
@@ -222,63 +1094,3716 @@ impl VisageService {
             user,
             matched = result.result.matched,
             similarity = result.result.similarity,
+            confidence = result.best_quality,
+            alignment = result.best_alignment,
             model_id = ?result.result.model_id,
+            fast_path = result.fast_path,
+            capture = result.capture.as_ref().map(tracing::field::display),
             "verify complete"
         );
 
-        Ok(result.result.matched)
+        Ok((result, started.elapsed()))
     }
 
-    /// Return daemon status information as JSON.
-    async fn status(&self) -> zbus::fdo::Result<String> {
-        let state = self.state.lock().await;
-        let model_count = state.store.count_all().await.unwrap_or(0);
-
-        Ok(serde_json::json!({
-            "version": env!("CARGO_PKG_VERSION"),
-            "camera": state.config.camera_device,
-            "model_dir": state.config.model_dir.display().to_string(),
-            "db_path": state.config.db_path.display().to_string(),
-            "models_enrolled": model_count,
-            "similarity_threshold": state.config.similarity_threshold,
-            "verify_timeout_secs": state.config.verify_timeout_secs,
-            "warmup_frames": state.config.warmup_frames,
-            "frames_per_verify": state.config.frames_per_verify,
-            "frames_per_enroll": state.config.frames_per_enroll,
-            "emitter_enabled": state.config.emitter_enabled,
-            "liveness_enabled": state.config.liveness_enabled,
-            "liveness_min_displacement": state.config.liveness_min_displacement,
-            "session_bus": state.config.session_bus,
-        })
-        .to_string())
+    /// Let the caller change `user`'s (canonical) templates under polkit
+    /// `action_id`: root always, anyone else only on their own account and
+    /// once polkit authorizes it, which may prompt them to authenticate.
+    /// Skipped on the session bus. Decisions go to the audit log.
+    async fn authorize_own_account(
+        &self,
+        method: &str,
+        action_id: &'static str,
+        user: &str,
+        header: &zbus::message::Header<'_>,
+        conn: &zbus::Connection,
+    ) -> Result<(), VisageError> {
+        let (access, percent_encoded, authority) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+                state.authority.clone(),
+            )
+        };
+        if access.unchecked() {
+            return Ok(());
+        }
+        let sender = header
+            .sender()
+            .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+        let caller_uid = get_caller_uid(sender.as_str(), conn).await?;
+        let caller = name_for_uid(caller_uid);
+        if caller_uid != 0 && access.privileged(caller_uid) {
+            tracing::info!(
+                target: polkit::AUDIT_TARGET,
+                action = action_id,
+                caller_uid,
+                caller = caller.as_deref(),
+                user,
+                grant = "session_owner",
+                "authorized"
+            );
+            return Ok(());
+        }
+        let decision = match check_caller(
+            method,
+            access,
+            user,
+            percent_encoded,
+            caller_uid,
+            uid_for_name,
+        )
+        .await
+        {
+            Ok(()) => {
+                polkit::authorize(
+                    authority.as_ref(),
+                    sender.as_str(),
+                    caller_uid,
+                    action_id,
+                    &[("user", user)],
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match decision {
+            Ok(grant) => {
+                tracing::info!(
+                    target: polkit::AUDIT_TARGET,
+                    action = action_id,
+                    caller_uid,
+                    caller = caller.as_deref(),
+                    user,
+                    grant = grant.as_str(),
+                    "authorized"
+                );
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!(
+                    target: polkit::AUDIT_TARGET,
+                    action = action_id,
+                    caller_uid,
+                    caller = caller.as_deref(),
+                    user,
+                    error = ?e,
+                    "denied"
+                );
+                Err(e)
+            }
+        }
     }
 
-    /// List enrolled face models for the given user as JSON.
-    async fn list_models(&self, user: &str) -> zbus::fdo::Result<String> {
-        tracing::info!(user, "list_models requested");
-        let state = self.state.lock().await;
-        let models = state
+    /// Extract and store a face model for an already canonical `user`, from
+    /// the frames of a redeemed preview or else from a fresh capture. Each
+    /// frame the engine processes is announced as an `EnrollProgress` signal
+    /// through `progress`. The capture can be cancelled by `client`'s
+    /// `Cancel` or by `CancelUser`.
+    async fn enroll_canonical(
+        &self,
+        user: &str,
+        client: &str,
+        label: &str,
+        preview: Option<PreviewCapture>,
+        progress: Option<&SignalEmitter<'_>>,
+    ) -> Result<String, VisageError> {
+        // Copy values while holding lock, then release
+        let (
+            engine,
+            frames_count,
+            crop_hash_enabled,
+            min_alignment,
+            timeout_secs,
+            liveness,
+            (ticket, cancel),
+        ) = {
+            let mut state = self.state.lock().await;
+            state.enroll_cooldown.check(user).map_err(|remaining| {
+                tracing::warn!(user, "enroll: cooldown running");
+                VisageError::rate_limited(remaining, "enrolled too recently")
+            })?;
+            check_model_pin(&state.store, user, ARCFACE_MODEL_VERSION).await?;
+            (
+                state.engine.clone(),
+                state.config.frames_per_enroll,
+                state.config.enroll_crop_hash,
+                state.config.enroll_min_alignment,
+                state.config.enroll_timeout_secs,
+                state
+                    .config
+                    .enroll_require_liveness
+                    .then(|| state.config.liveness_policy()),
+                state.pending_verifies.register(client, Some(user)),
+            )
+        };
+
+        // Run engine (no lock held), relaying its progress as it goes. The
+        // reply does not wait for the engine to drop its sender.
+        let timeout = std::time::Duration::from_secs(timeout_secs);
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+        let progress_tx = progress.map(|_| progress_tx);
+        let enroll = async {
+            match preview {
+                Some(preview) => {
+                    engine
+                        .enroll_frames(
+                            preview,
+                            crop_hash_enabled,
+                            min_alignment,
+                            timeout,
+                            liveness,
+                            progress_tx,
+                            cancel,
+                        )
+                        .await
+                }
+                None => {
+                    engine
+                        .enroll(
+                            frames_count,
+                            crop_hash_enabled,
+                            min_alignment,
+                            timeout,
+                            liveness,
+                            progress_tx,
+                            cancel,
+                        )
+                        .await
+                }
+            }
+        };
+        let result = relaying(enroll, &mut progress_rx, |update| async move {
+            if let Some(emitter) = progress {
+                emit_enroll_progress(emitter, user, update).await;
+            }
+        })
+        .await;
+        self.state.lock().await.pending_verifies.finish(ticket);
+        let result = result.map_err(|e| {
+            if matches!(e, EngineError::ClientCancelled) {
+                tracing::info!(user, client, "enroll cancelled by client");
+                return e.into();
+            }
+            tracing::error!(error = %e, "enroll failed");
+            match e {
+                EngineError::EnrollTimeout => VisageError::Timeout(format!(
+                    "enrollment did not complete within {timeout_secs}s"
+                )),
+                EngineError::LivenessCheckFailed { .. } => VisageError::LivenessFailed(format!(
+                    "enrollment refused, no live face detected: {e} — enroll in person, \
+                         facing the camera, not from a photo or screen"
+                )),
+                e => e.into(),
+            }
+        })?;
+
+        tracing::info!(
+            quality = result.quality_score,
+            alignment = result.alignment_score,
+            "enroll: embedding extracted"
+        );
+
+        // Store result (re-acquire lock)
+        let mut state = self.state.lock().await;
+        state
+            .stats
+            .record_capture(result.frames_captured, result.dark_skipped);
+        state
+            .stats
+            .record_frame_memory(result.frame_memory.frames, result.frame_memory.bytes);
+        let duplicate_of = check_duplicate(
+            &state.store,
+            user,
+            &result.embedding,
+            state.config.enroll_duplicate_threshold,
+            state.config.enroll_allow_duplicates,
+        )
+        .await?;
+        let model_id = state
             .store
-            .list_by_user(user)
+            .insert(
+                user,
+                label,
+                &result.embedding,
+                result.quality_score,
+                EnrollMeta {
+                    crop_hash: result.crop_hash,
+                    pose: result.pose,
+                    device_id: result.device_id,
+                    brightness: Some(result.brightness),
+                    duplicate_of,
+                },
+            )
             .await
-            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
-        serde_json::to_string(&models).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+            .map_err(|e| {
+                tracing::error!(error = %e, "enroll: store insert failed");
+                VisageError::Failed(e.to_string())
+            })?;
+        state.enroll_cooldown.record(user);
+
+        tracing::info!(
+            target: polkit::AUDIT_TARGET,
+            model_id = %model_id,
+            user,
+            label,
+            "enrolled successfully"
+        );
+        Ok(model_id)
     }
 
-    /// Remove an enrolled face model by ID (scoped to user).
-    async fn remove_model(&self, user: &str, model_id: &str) -> zbus::fdo::Result<bool> {
-        tracing::info!(user, model_id, "remove_model requested");
-        let state = self.state.lock().await;
-        let removed = state
-            .store
-            .remove(user, model_id)
+    /// Merge the JSON object `update` over the active verification policy,
+    /// validate the result and apply it. Returns the new policy.
+    async fn update_policy(&self, update: &str) -> Result<VerifyPolicy, VisageError> {
+        let mut state = self.state.lock().await;
+        let current = VerifyPolicy::from_config(&state.config);
+        let policy = current
+            .merged(update)
+            .and_then(|policy| policy.validate(&state.config).map(|()| policy))
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        policy.apply(&mut state.config);
+        // Successes under the old policy must not vouch for the new one.
+        state.verify_grace.clear();
+        tracing::warn!(
+            target: polkit::AUDIT_TARGET,
+            from = %serde_json::json!(current),
+            to = %serde_json::json!(policy),
+            "verification policy changed at runtime"
+        );
+        Ok(policy)
+    }
+
+    /// The root check of a property setter. `header` is `None` when the
+    /// daemon sets the property itself, which needs no check.
+    async fn require_root_to_set(
+        &self,
+        property: &str,
+        header: Option<&zbus::message::Header<'_>>,
+        conn: &zbus::Connection,
+    ) -> zbus::fdo::Result<()> {
+        let Some(header) = header else {
+            return Ok(());
+        };
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root(&format!("Setting {property}"), access, header, conn).await
+    }
+
+    /// Apply the `SetPolicy` update `update` for a property setter, then
+    /// save `key = value` to the config file.
+    async fn set_policy_property(
+        &self,
+        update: serde_json::Value,
+        key: &str,
+        value: toml::Value,
+    ) -> zbus::fdo::Result<()> {
+        self.update_policy(&update.to_string())
             .await
-            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
-        if removed {
-            tracing::info!(model_id, "model removed");
+            .map_err(|e| match e {
+                VisageError::InvalidArgs(msg) => zbus::fdo::Error::InvalidArgs(msg),
+                e => zbus::fdo::Error::Failed(e.to_string()),
+            })?;
+        self.persist_setting(key, value).await;
+        Ok(())
+    }
+
+    /// Save a setting changed through a property to the config file, if the
+    /// daemon read one. A file that cannot be written is logged and the
+    /// change still applies until the daemon restarts.
+    async fn persist_setting(&self, key: &str, value: toml::Value) {
+        let Some(path) = self.state.lock().await.config.config_file.clone() else {
+            return;
+        };
+        let (file, name) = (path.clone(), key.to_string());
+        match blocking::unblock(move || ConfigFile::store(&file, &name, value)).await {
+            Ok(()) => tracing::info!(key, path = %path.display(), "setting saved to config file"),
+            Err(e) => tracing::warn!(key, error = %e, "setting applies until restart only"),
+        }
+    }
+
+    /// Emit `PropertiesChanged` for the properties among the settings a
+    /// configuration reload `applied` (see [`crate::reload`]).
+    pub async fn announce_reload(conn: &zbus::Connection, applied: &[&str]) -> zbus::Result<()> {
+        let iface = conn
+            .object_server()
+            .interface::<_, VisageService>("/org/freedesktop/Visage1")
+            .await?;
+        let emitter = iface.signal_emitter();
+        let service = iface.get().await;
+        for setting in applied {
+            match *setting {
+                "similarity_threshold" => service.similarity_threshold_changed(emitter).await?,
+                "liveness_enabled" => service.liveness_enabled_changed(emitter).await?,
+                "frames_per_verify" => service.frames_per_verify_changed(emitter).await?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the JSON object `update` into the settings stored for the
+    /// camera `device_id`, store the result and apply it to the running
+    /// daemon. A cleared field keeps its current value until restart.
+    async fn update_device_settings(
+        &self,
+        device_id: &str,
+        update: &str,
+    ) -> Result<DeviceSettings, VisageError> {
+        let mut state = self.state.lock().await;
+        let current = state.store.get_device_settings(device_id).await?;
+        let settings = current
+            .merged(update)
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        state
+            .store
+            .set_device_settings(device_id, &settings)
+            .await?;
+        settings.apply(&mut state.config);
+        state.verify_grace.clear();
+        tracing::warn!(
+            target: polkit::AUDIT_TARGET,
+            device_id,
+            from = %serde_json::json!(current),
+            to = %serde_json::json!(settings),
+            "camera settings changed"
+        );
+        Ok(settings)
+    }
+}
+
+/// JSON reply of `GetDeviceSettings` / `SetDeviceSettings`.
+fn device_settings_report(
+    device_id: Option<&str>,
+    stored: &DeviceSettings,
+    config: &Config,
+) -> String {
+    serde_json::json!({
+        "device_id": device_id,
+        "stored": stored,
+        "liveness_min_displacement": config.liveness_min_displacement,
+    })
+    .to_string()
+}
+
+#[interface(name = "org.freedesktop.Visage1")]
+impl VisageService {
+    /// Enroll a new face model for the given user.
+    ///
+    /// Returns the UUID of the newly created model. `EnrollProgress` is
+    /// signalled to the caller after each frame.
+    ///
+    /// Security: on the system bus a non-root caller may only enroll their
+    /// own account, and must be authorized for the polkit action
+    /// `org.freedesktop.Visage1.enroll`, which prompts for authentication by
+    /// default.
+    async fn enroll(
+        &self,
+        user: &str,
+        label: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(user, label, "enroll requested");
+        let percent_encoded = self.state.lock().await.config.percent_encoded_usernames;
+        let user = username::canonicalize(user, percent_encoded)?;
+        self.authorize_own_account("Enroll", polkit::ACTION_ENROLL, &user, &header, conn)
+            .await?;
+        self.enroll_canonical(
+            &user,
+            &sender_name(&header),
+            label,
+            None,
+            Some(&caller_emitter(emitter, &sender_name(&header))),
+        )
+        .await
+    }
+
+    /// Enroll a new face model for another user on an administrator's behalf.
+    ///
+    /// Returns the UUID of the newly created model. `EnrollProgress` is
+    /// signalled to the caller after each frame.
+    ///
+    /// Security: on the system bus non-root callers must be authorized for the
+    /// polkit action `org.freedesktop.Visage1.enroll-other`, which may prompt
+    /// them to authenticate. The target account must exist. Both identities
+    /// are recorded under the `visaged::audit` log target.
+    async fn enroll_other(
+        &self,
+        user: &str,
+        label: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(user, label, "enroll-other requested");
+
+        let (access, percent_encoded, authority) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+                state.authority.clone(),
+            )
+        };
+        let user = username::canonicalize(user, percent_encoded)?;
+        let known = match username::nss_name(&user, percent_encoded) {
+            Some(name) => resolve_uid(&name, uid_for_name).await?.is_some(),
+            None => false,
+        };
+        if !known {
+            return Err(VisageError::InvalidArgs(format!("unknown user '{user}'")));
+        }
+
+        // On the session bus (development mode) every caller is the same user.
+        let (caller_uid, grant) = if access.unchecked() {
+            (None, "session_bus")
         } else {
-            tracing::warn!(model_id, user, "model not found or not owned by user");
+            let sender = header
+                .sender()
+                .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+            let caller_uid = get_caller_uid(sender.as_str(), conn).await?;
+            if caller_uid != 0 && access.privileged(caller_uid) {
+                (Some(caller_uid), "session_owner")
+            } else {
+                let details = [("user", user.as_str())];
+                let grant = polkit::authorize(
+                    authority.as_ref(),
+                    sender.as_str(),
+                    caller_uid,
+                    polkit::ACTION_ENROLL_OTHER,
+                    &details,
+                )
+                .await
+                .map_err(|e| {
+                    tracing::warn!(
+                        target: polkit::AUDIT_TARGET,
+                        action = polkit::ACTION_ENROLL_OTHER,
+                        caller_uid,
+                        caller = name_for_uid(caller_uid).as_deref(),
+                        user,
+                        label,
+                        error = ?e,
+                        "denied"
+                    );
+                    e
+                })?;
+                (Some(caller_uid), grant.as_str())
+            }
+        };
+        let caller = caller_uid.and_then(name_for_uid);
+        tracing::info!(
+            target: polkit::AUDIT_TARGET,
+            action = polkit::ACTION_ENROLL_OTHER,
+            caller_uid,
+            caller = caller.as_deref(),
+            user,
+            label,
+            grant,
+            "authorized"
+        );
+
+        let client = sender_name(&header);
+        let progress = caller_emitter(emitter, &client);
+        let model_id = self
+            .enroll_canonical(&user, &client, label, None, Some(&progress))
+            .await?;
+        tracing::info!(
+            target: polkit::AUDIT_TARGET,
+            action = polkit::ACTION_ENROLL_OTHER,
+            caller_uid,
+            caller = caller.as_deref(),
+            user,
+            label,
+            model_id = %model_id,
+            "enrolled"
+        );
+        Ok(model_id)
+    }
+
+    /// Capture a burst of frames for `user` to look at before enrolling.
+    ///
+    /// Returns a single-use token for `EnrollNow` together with the last
+    /// frame (width, height and 8-bit grayscale pixels, row-major) for the
+    /// client to display. The frames stay in memory until the token is
+    /// redeemed, expires (`VISAGE_PREVIEW_TTL_SECS`) or is replaced by the
+    /// user's next preview.
+    ///
+    /// Security: as for `Enroll`, a non-root caller on the system bus may
+    /// only preview their own account, once polkit authorizes
+    /// `org.freedesktop.Visage1.enroll`.
+    async fn preview(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<(String, u32, u32, Vec<u8>), VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(user, "preview requested");
+        let (percent_encoded, engine, frames_count, timeout_secs) = {
+            let state = self.state.lock().await;
+            (
+                state.config.percent_encoded_usernames,
+                state.engine.clone(),
+                state.config.frames_per_enroll,
+                state.config.enroll_timeout_secs,
+            )
+        };
+        let user = username::canonicalize(user, percent_encoded)?;
+        self.authorize_own_account("Preview", polkit::ACTION_ENROLL, &user, &header, conn)
+            .await?;
+
+        let capture = engine
+            .preview(frames_count, std::time::Duration::from_secs(timeout_secs))
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "preview failed");
+                VisageError::from(e)
+            })?;
+        let shown = capture
+            .frames
+            .last()
+            .map(|f| (f.width, f.height, f.data.clone()))
+            .unwrap_or_default();
+        let frames = capture.frames.len();
+        let token = self.state.lock().await.previews.insert(&user, capture);
+        tracing::info!(user, frames, "preview captured");
+        Ok((token, shown.0, shown.1, shown.2))
+    }
+
+    /// Enroll `user` from the frames captured by `Preview`, so the stored
+    /// template matches what they were shown.
+    ///
+    /// `preview_token` must come from a `Preview` call for the same user and
+    /// is consumed by the attempt. An unknown, used, expired or mismatched
+    /// token fails with `InvalidArgs` before the engine is involved.
+    ///
+    /// Returns the UUID of the newly created model. `EnrollProgress` is
+    /// signalled to the caller after each frame.
+    ///
+    /// Security: the same as `Preview`, checked again here.
+    async fn enroll_now(
+        &self,
+        user: &str,
+        label: &str,
+        preview_token: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(user, label, "enroll-now requested");
+        let percent_encoded = self.state.lock().await.config.percent_encoded_usernames;
+        let user = username::canonicalize(user, percent_encoded)?;
+        self.authorize_own_account("EnrollNow", polkit::ACTION_ENROLL, &user, &header, conn)
+            .await?;
+        let capture = self
+            .state
+            .lock()
+            .await
+            .previews
+            .take(preview_token, &user)
+            .map_err(|e| {
+                tracing::warn!(user, error = %e, "enroll-now: preview token rejected");
+                VisageError::InvalidArgs(e.to_string())
+            })?;
+        self.enroll_canonical(
+            &user,
+            &sender_name(&header),
+            label,
+            Some(capture),
+            Some(&caller_emitter(emitter, &sender_name(&header))),
+        )
+        .await
+    }
+
+    /// Verify the current face against enrolled models for the given user.
+    ///
+    /// Returns true if the face matches any enrolled model above the threshold.
+    ///
+    /// Security: on the system bus the caller UID is validated against the target
+    /// username before any camera access or rate-limit check.  Root (UID 0) is always
+    /// permitted.  On the session bus (development mode) UID validation is skipped.
+    ///
+    /// Outside the configured verify windows this fails with `PolicyDenied`
+    /// before any other check.
+    async fn verify(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let (result, _) = self.run_verify(user, &header, conn, &emitter).await?;
+        Ok(result.result.matched)
+    }
+
+    /// [`Verify`](Self::verify), returning the outcome as JSON: match,
+    /// similarity, threshold, matched model, whether the centroid fast path
+    /// decided it and, without a match, `failure_reason` (`below_threshold`,
+    /// `liveness`, `pose_mismatch` or `foreign_device`). With `VISAGE_VERIFY_REPORT_TIMING=1` it also carries
+    /// `elapsed_ms` (request to reply, including time queued behind other
+    /// engine work), `engine_ms`, `frames_captured`, `frames_with_face` and
+    /// `dark_skipped`. Same access rules and rate limit as `Verify`.
+    async fn verify_detailed(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let (result, elapsed) = self.run_verify(user, &header, conn, &emitter).await?;
+        let state = self.state.lock().await;
+        let report = verify_report(
+            &result,
+            elapsed,
+            state.config.similarity_threshold,
+            state.config.verify_report_timing,
+        );
+        Ok(report.to_string())
+    }
+
+    /// [`Verify`](Self::verify), replying with typed values instead of
+    /// `VerifyDetailed`'s JSON: whether it matched, the similarity, the
+    /// matched model's ID and label (empty without one) and the
+    /// `failure_reason` (empty on a match). Same access rules and rate limit
+    /// as `Verify`.
+    async fn verify_score(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(bool, f64, String, String, String), VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let (result, _) = self.run_verify(user, &header, conn, &emitter).await?;
+        let reason = failure_reason(&result).unwrap_or_default().to_string();
+        let matched = result.result;
+        Ok((
+            matched.matched,
+            f64::from(matched.similarity),
+            matched.model_id.unwrap_or_default(),
+            matched.model_label.unwrap_or_default(),
+            reason,
+        ))
+    }
+
+    /// [`Verify`](Self::verify), also returning a one-time token on a match.
+    ///
+    /// The token lets a later `RedeemToken` from the same logind session
+    /// succeed once without a capture, within `VISAGE_VERIFY_TOKEN_TTL_SECS`.
+    /// It is empty when there was no match, tokens are disabled, or the
+    /// caller is not in a logind session. Same access rules and rate limit
+    /// as `Verify`.
+    async fn verify_with_token(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(bool, String), VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let (result, _) = self.run_verify(user, &header, conn, &emitter).await?;
+        let (access, percent_encoded, enabled) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+                state.config.verify_token_ttl_secs > 0,
+            )
+        };
+        if !result.result.matched || !enabled {
+            return Ok((result.result.matched, String::new()));
         }
-        Ok(removed)
+
+        let user = username::canonicalize(user, percent_encoded)?;
+        let sender = header
+            .sender()
+            .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+        let token = match get_caller_session(sender.as_str(), access, conn).await {
+            Ok(session) => self
+                .state
+                .lock()
+                .await
+                .verify_tokens
+                .issue(&user, &session)
+                .unwrap_or_default(),
+            Err(e) => {
+                tracing::info!(user, error = %e, "verify: caller has no logind session, no token");
+                String::new()
+            }
+        };
+        Ok((true, token))
+    }
+
+    /// Redeem a token from `VerifyWithToken` in place of a capture.
+    ///
+    /// Returns `true` once per token, for its user, within its TTL and only
+    /// to a caller in the logind session it was issued to; `false` for any
+    /// other token. Callers other than root may only redeem their own. Like
+    /// `Verify`, it is refused outside the permitted time windows, while the
+    /// user is locked out and when they are pinned to another model; a
+    /// refused token stays redeemable.
+    async fn redeem_token(
+        &self,
+        user: &str,
+        token: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, "redeem_token requested");
+        let (access, percent_encoded, permitted) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+                check_verify_schedule(&state.config, user),
+            )
+        };
+        permitted?;
+        let user = username::canonicalize(user, percent_encoded)?;
+        require_root_or_self("RedeemToken", access, &user, percent_encoded, &header, conn).await?;
+        let sender = header
+            .sender()
+            .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+        let session = get_caller_session(sender.as_str(), access, conn).await?;
+        self.redeem_in_session(&user, token, &session).await
+    }
+
+    /// Whether `user` matched a `Verify` from the caller's logind session
+    /// within the last `max_age_secs`, without touching the camera.
+    ///
+    /// The age is capped at `VISAGE_VERIFY_GRACE_MAX_SECS`. A verify for the
+    /// user that does not match forgets their earlier successes, and none
+    /// survive a daemon restart. `false` means the caller should run a full
+    /// `Verify`. Same access rules as `Verify`.
+    async fn verify_cached(
+        &self,
+        user: &str,
+        max_age_secs: u32,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, max_age_secs, "verify_cached requested");
+        let (user, caller_uid) = self.authorize_verify(user, &header, conn).await?;
+        let max_age = std::time::Duration::from_secs(max_age_secs.into());
+        let fresh = match self.grace_session(&header, conn).await {
+            Some(session) => self
+                .state
+                .lock()
+                .await
+                .verify_grace
+                .is_fresh(&user, &session, max_age),
+            None => false,
+        };
+        tracing::info!(user, caller_uid, fresh, "verify_cached answered");
+        Ok(fresh)
+    }
+
+    /// Whether `user` is at the camera now or matched a `Verify` from the
+    /// caller's logind session within the last `max_age_secs`: the answer of
+    /// `VerifyCached` when that is true, else that of a fresh `Verify`.
+    ///
+    /// Lets a client gate e.g. a sensitive dialog on a recent match without
+    /// tracking timestamps itself. The age is capped as for `VerifyCached`,
+    /// and a fresh capture runs under `Verify`'s rate limit and signals and
+    /// refreshes or forgets the cached match. Same access rules as `Verify`.
+    async fn verify_fresh(
+        &self,
+        user: &str,
+        max_age_secs: u32,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(user, max_age_secs, "verify_fresh requested");
+        let started = std::time::Instant::now();
+        let (user, _) = self.authorize_verify(user, &header, conn).await?;
+        let max_age = std::time::Duration::from_secs(max_age_secs.into());
+        let session = self.grace_session(&header, conn).await;
+        let client = sender_name(&header);
+        self.fresh_or_capture(
+            &user,
+            session.as_deref(),
+            max_age,
+            &client,
+            started,
+            &emitter,
+        )
+        .await
+    }
+
+    /// Cancel the verifies and enrollments this D-Bus connection has waiting
+    /// on the engine, e.g. because the user started typing a password instead.
+    ///
+    /// A running capture stops at its next frame and a queued one never
+    /// starts; their callers receive `org.freedesktop.Visage1.Error.Cancelled`.
+    /// Other connections' captures are unaffected, so any caller may use it.
+    /// Returns false when the connection had no capture to cancel.
+    async fn cancel(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<bool, VisageError> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| VisageError::Failed("no sender in message".to_string()))?;
+        let cancelled = self
+            .state
+            .lock()
+            .await
+            .pending_verifies
+            .cancel(sender.as_str());
+        tracing::info!(client = %sender, cancelled, "cancel requested by client");
+        Ok(cancelled > 0)
+    }
+
+    /// Cancel every verify and enrollment waiting on the engine for `user`,
+    /// whichever connection asked for it, e.g. from a PAM conversation that
+    /// does not own the connection the capture runs on.
+    ///
+    /// Cancelled captures fail as for `Cancel` and do not count as failed
+    /// verifies. Returns how many were cancelled.
+    ///
+    /// Security: on the system bus a non-root caller may only cancel their
+    /// own account's captures.
+    async fn cancel_user(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<u32, VisageError> {
+        let (access, percent_encoded) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+            )
+        };
+        let user = &username::canonicalize(user, percent_encoded)?;
+        require_root_or_self("CancelUser", access, user, percent_encoded, &header, conn).await?;
+        let cancelled = self.state.lock().await.pending_verifies.cancel_user(user);
+        tracing::info!(user, cancelled, "cancel requested for user");
+        Ok(u32::try_from(cancelled).unwrap_or(u32::MAX))
+    }
+
+    /// Emitted to the caller of `Enroll`, `EnrollOther` or `EnrollNow` after
+    /// each frame the engine processes for `user`. `frames_captured` rises
+    /// to `frames_total` on a complete capture; `current_quality` is the
+    /// detector confidence of the best usable face so far (0 until one is
+    /// found).
+    #[zbus(signal)]
+    pub async fn enroll_progress(
+        emitter: &SignalEmitter<'_>,
+        user: &str,
+        frames_captured: u32,
+        frames_total: u32,
+        current_quality: f64,
+    ) -> zbus::Result<()>;
+
+    /// Emitted to every listener once a `Verify`, `VerifyDetailed` or
+    /// `VerifyWithToken` for `user` (the user being verified, not the
+    /// caller) has passed the rate limit and is about to use the camera.
+    #[zbus(signal)]
+    pub async fn verification_started(emitter: &SignalEmitter<'_>, user: &str) -> zbus::Result<()>;
+
+    /// Emitted to every listener when a verify announced by
+    /// `VerificationStarted` ends, before its reply, also when it fails.
+    /// `reason` is empty on a match, else `no-face`, `no-match`, `timeout`
+    /// or `error`.
+    #[zbus(signal)]
+    pub async fn verification_completed(
+        emitter: &SignalEmitter<'_>,
+        user: &str,
+        matched: bool,
+        reason: &str,
+    ) -> zbus::Result<()>;
+
+    /// Emitted to the caller of a verify announced by `VerificationStarted`,
+    /// right after that signal.
+    #[zbus(signal)]
+    pub async fn verify_started(emitter: &SignalEmitter<'_>, user: &str) -> zbus::Result<()>;
+
+    /// Emitted to the caller of a verify after each frame the engine matches
+    /// for `user`. `frames_done` rises to `frames_total` on a complete
+    /// capture; a verify that fails early stops short of it.
+    #[zbus(signal)]
+    pub async fn verify_progress(
+        emitter: &SignalEmitter<'_>,
+        user: &str,
+        frames_done: u32,
+        frames_total: u32,
+    ) -> zbus::Result<()>;
+
+    /// Emitted to the caller of a verify right after `VerificationCompleted`.
+    /// `similarity` is the best cosine similarity of the capture, 0 when the
+    /// verify failed.
+    #[zbus(signal)]
+    pub async fn verify_completed(
+        emitter: &SignalEmitter<'_>,
+        user: &str,
+        matched: bool,
+        similarity: f64,
+    ) -> zbus::Result<()>;
+
+    /// Emitted when scheduled self-tests mark the camera degraded, or a
+    /// passing test clears the flag (see `camera_degraded` in Status).
+    #[zbus(signal)]
+    pub async fn health_changed(
+        emitter: &SignalEmitter<'_>,
+        camera_degraded: bool,
+    ) -> zbus::Result<()>;
+
+    /// Return daemon status information as JSON.
+    async fn status(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let state = self.state.lock().await;
+        Ok(StatusInfo::collect(&state).await.to_json().to_string())
+    }
+
+    /// Return the same status as `Status`, as a dictionary of typed values
+    /// instead of a JSON string. Keys whose JSON value is null are absent;
+    /// nested objects are `a{sv}` and lists `av`.
+    async fn get_status(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<HashMap<String, OwnedValue>, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let state = self.state.lock().await;
+        Ok(StatusInfo::collect(&state).await.into())
+    }
+
+    /// List the optional features this daemon implements (see `capabilities`).
+    async fn get_capabilities(&self) -> Vec<String> {
+        capabilities::names(&self.state.lock().await.config)
+    }
+
+    /// Return aggregate capture/verify statistics since daemon start, and
+    /// enrollment counts per model version, as JSON.
+    ///
+    /// Contains only counters and latencies — no user names, model IDs, or
+    /// similarity scores. `requests` counts handlers in flight and requests
+    /// refused by the per-client limits.
+    async fn stats(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let state = self.state.lock().await;
+        let mut stats = serde_json::to_value(state.stats.summary())
+            .map_err(|e| VisageError::Failed(e.to_string()))?;
+        stats["models_by_version"] = serde_json::json!(state
+            .store
+            .count_by_model_version()
+            .await
+            .unwrap_or_default());
+        stats["requests"] = serde_json::json!(self.limiter.stats());
+        Ok(stats.to_string())
+    }
+
+    /// List enrolled face models for the given user as JSON.
+    ///
+    /// Security: a non-root caller on the system bus may only list their own
+    /// models.
+    async fn list_models(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, "list_models requested");
+        let (access, percent_encoded) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+            )
+        };
+        let user = &username::canonicalize(user, percent_encoded)
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        require_root_or_self("ListModels", access, user, percent_encoded, &header, conn).await?;
+        let state = self.state.lock().await;
+        let models = state.store.list_by_user(user).await?;
+        serde_json::to_string(&models).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Remove an enrolled face model by ID (scoped to user).
+    ///
+    /// Security: like `Enroll`, a non-root caller on the system bus may only
+    /// remove their own models, under the polkit action
+    /// `org.freedesktop.Visage1.remove-model`.
+    async fn remove_model(
+        &self,
+        user: &str,
+        model_id: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, model_id, "remove_model requested");
+        let percent_encoded = self.state.lock().await.config.percent_encoded_usernames;
+        let user = &username::canonicalize(user, percent_encoded)
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        self.authorize_own_account(
+            "RemoveModel",
+            polkit::ACTION_REMOVE_MODEL,
+            user,
+            &header,
+            conn,
+        )
+        .await?;
+        let state = self.state.lock().await;
+        let removed = state.store.remove(user, model_id).await?;
+        if removed {
+            tracing::info!(target: polkit::AUDIT_TARGET, model_id, user, "model removed");
+        } else {
+            tracing::warn!(model_id, user, "model not found or not owned by user");
+        }
+        Ok(removed)
+    }
+
+    /// Remove every enrolled face model of the given user and return how
+    /// many were removed; none is not an error. The user's failed-verify
+    /// count, lockout and cached matches are reset with them.
+    ///
+    /// Security: as for `RemoveModel`, a non-root caller on the system bus
+    /// may only remove their own models, under the polkit action
+    /// `org.freedesktop.Visage1.remove-model`.
+    async fn remove_all_models(
+        &self,
+        user: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<u32, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, "remove_all_models requested");
+        let percent_encoded = self.state.lock().await.config.percent_encoded_usernames;
+        let user = &username::canonicalize(user, percent_encoded)
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        self.authorize_own_account(
+            "RemoveAllModels",
+            polkit::ACTION_REMOVE_MODEL,
+            user,
+            &header,
+            conn,
+        )
+        .await?;
+        let mut state = self.state.lock().await;
+        let removed = state.store.remove_all_for_user(user).await?;
+        state.rate_limiter.forget_user(user);
+        state.verify_grace.forget_user(user);
+        tracing::info!(target: polkit::AUDIT_TARGET, user, removed, "all models removed");
+        Ok(u32::try_from(removed).unwrap_or(u32::MAX))
+    }
+
+    /// Rename one of `user`'s face models to `new_label` (1–64 characters,
+    /// unique among the user's models). Returns whether a model changed;
+    /// `false` when `model_id` is not one of the user's. Root or the user.
+    async fn set_model_label(
+        &self,
+        user: &str,
+        model_id: &str,
+        new_label: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, model_id, "set_model_label requested");
+        let new_label = new_label.trim();
+        if new_label.is_empty() {
+            return Err(VisageError::InvalidArgs(
+                "label must not be empty".to_string(),
+            ));
+        }
+        if new_label.chars().count() > MAX_LABEL_LEN || new_label.chars().any(char::is_control) {
+            return Err(VisageError::InvalidArgs(format!(
+                "label must be at most {MAX_LABEL_LEN} printable characters"
+            )));
+        }
+        let (access, percent_encoded) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+            )
+        };
+        let user = &username::canonicalize(user, percent_encoded)?;
+        require_root_or_self(
+            "SetModelLabel",
+            access,
+            user,
+            percent_encoded,
+            &header,
+            conn,
+        )
+        .await?;
+
+        // Held across the check and the update so no enroll slips a clashing
+        // label in between.
+        let state = self.state.lock().await;
+        let models = state.store.list_by_user(user).await?;
+        if models
+            .iter()
+            .any(|m| m.label == new_label && m.id != model_id)
+        {
+            return Err(VisageError::InvalidArgs(format!(
+                "user '{user}' already has a model labelled '{new_label}'"
+            )));
+        }
+        let renamed = state.store.update_label(user, model_id, new_label).await?;
+        if renamed {
+            tracing::info!(
+                target: polkit::AUDIT_TARGET,
+                model_id,
+                user,
+                label = new_label,
+                "model relabelled"
+            );
+        } else {
+            tracing::warn!(model_id, user, "model not found or not owned by user");
+        }
+        Ok(renamed)
+    }
+
+    /// Remove `user`'s near-duplicate templates, keeping the highest-quality
+    /// template of each group whose cosine similarity reaches
+    /// `similarity_threshold` (0–1, exclusive of 0). Returns the number
+    /// removed; at least one template always remains. Root or the user.
+    async fn dedupe(
+        &self,
+        user: &str,
+        similarity_threshold: f64,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<u64, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!(user, similarity_threshold, "dedupe requested");
+        if !(similarity_threshold > 0.0 && similarity_threshold <= 1.0) {
+            return Err(VisageError::InvalidArgs(format!(
+                "similarity threshold {similarity_threshold} must be in (0, 1]"
+            )));
+        }
+        let (access, percent_encoded) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.percent_encoded_usernames,
+            )
+        };
+        let user = &username::canonicalize(user, percent_encoded)?;
+        require_root_or_self("Dedupe", access, user, percent_encoded, &header, conn).await?;
+
+        let store = self.state.lock().await.store.clone();
+        let removed = store
+            .dedupe_user(user, similarity_threshold as f32)
+            .await
+            .map_err(|e| {
+                tracing::error!(user, error = %e, "dedupe failed");
+                VisageError::from(e)
+            })?;
+        tracing::info!(user, removed, "dedupe complete");
+        Ok(removed)
+    }
+
+    /// Pin `user` to a recognizer model version (e.g. `w600k_r50`), or clear
+    /// the pin with an empty string. While pinned, enroll and verify fail with
+    /// `ModelMismatch` unless the daemon runs that version. Root only.
+    async fn set_model_pin(
+        &self,
+        user: &str,
+        model_version: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<(), VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("SetModelPin", access, &header, conn).await?;
+
+        let model_version = model_version.trim();
+        if model_version.len() > MAX_MODEL_VERSION_LEN
+            || model_version.chars().any(char::is_control)
+        {
+            return Err(VisageError::InvalidArgs(format!(
+                "invalid model version '{model_version}'"
+            )));
+        }
+        let pin = Some(model_version).filter(|v| !v.is_empty());
+
+        let state = self.state.lock().await;
+        let user = username::canonicalize(user, state.config.percent_encoded_usernames)?;
+        state
+            .store
+            .set_model_pin(&user, pin)
+            .await
+            .map_err(|e| VisageError::Failed(e.to_string()))?;
+        tracing::info!(user, pin, "model pin updated");
+        Ok(())
+    }
+
+    /// Replace the daemon's log filter at runtime, e.g. `visaged=debug`.
+    ///
+    /// Accepts the same directive syntax as `RUST_LOG`; malformed directives
+    /// are rejected and the current filter is kept. Root only.
+    async fn set_log_level(
+        &self,
+        directive: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<(), VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("SetLogLevel", access, &header, conn).await?;
+
+        let state = self.state.lock().await;
+        let handle = state
+            .log_filter
+            .as_ref()
+            .ok_or_else(|| VisageError::Failed("log filter reload unavailable".to_string()))?;
+        crate::logging::set_directive(handle, directive).map_err(|e| match e {
+            crate::logging::LogLevelError::InvalidDirective { .. } => {
+                VisageError::InvalidArgs(e.to_string())
+            }
+            e => VisageError::Failed(e.to_string()),
+        })?;
+        tracing::warn!(directive, "log filter changed at runtime");
+        Ok(())
+    }
+
+    /// The active verification policy as JSON: similarity threshold,
+    /// liveness settings, matcher choice and the pose and camera
+    /// requirements. Root only.
+    async fn get_policy(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("GetPolicy", access, &header, conn).await?;
+
+        let policy = VerifyPolicy::from_config(&self.state.lock().await.config);
+        serde_json::to_string(&policy).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Change the verification policy without a restart. `policy_json` is a
+    /// JSON object naming the `GetPolicy` fields to change; the merged policy
+    /// is validated as a whole and applies to the next verify, or an invalid
+    /// update is rejected and nothing changes. Returns the new policy. The
+    /// change lasts until the daemon restarts. Root only.
+    async fn set_policy(
+        &self,
+        policy_json: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("SetPolicy", access, &header, conn).await?;
+
+        let before = VerifyPolicy::from_config(&self.state.lock().await.config);
+        let policy = self.update_policy(policy_json).await?;
+        // The policy shares two settings with the properties below.
+        if policy.similarity_threshold != before.similarity_threshold {
+            self.similarity_threshold_changed(&emitter).await?;
+        }
+        if policy.liveness_enabled != before.liveness_enabled {
+            self.liveness_enabled_changed(&emitter).await?;
+        }
+        serde_json::to_string(&policy).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// The similarity threshold verifies match against, as in `GetPolicy`.
+    /// Root may set it within 0.0–1.0, under the same checks as `SetPolicy`.
+    /// A change applies to the next verify and is saved to the config file,
+    /// if the daemon read one.
+    //
+    // The setters take `&mut self` because zbus only keeps the error name of
+    // a setter that does; it waits for calls in flight, which is fine for an
+    // administrator's occasional change.
+    #[zbus(property)]
+    async fn similarity_threshold(&self) -> f64 {
+        f64::from(self.state.lock().await.config.similarity_threshold)
+    }
+
+    #[zbus(property)]
+    async fn set_similarity_threshold(
+        &mut self,
+        value: f64,
+        #[zbus(header)] header: Option<zbus::message::Header<'_>>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> zbus::fdo::Result<()> {
+        self.require_root_to_set("SimilarityThreshold", header.as_ref(), conn)
+            .await?;
+        if !(0.0..=1.0).contains(&value) {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "SimilarityThreshold {value} is outside 0.0–1.0"
+            )));
+        }
+        self.set_policy_property(
+            serde_json::json!({ "similarity_threshold": value }),
+            "similarity_threshold",
+            toml::Value::Float(value),
+        )
+        .await
+    }
+
+    /// Whether verifies run the liveness check, as in `GetPolicy`. Root may
+    /// set it; a change applies and is saved as for `SimilarityThreshold`.
+    #[zbus(property)]
+    async fn liveness_enabled(&self) -> bool {
+        self.state.lock().await.config.liveness_enabled
+    }
+
+    #[zbus(property)]
+    async fn set_liveness_enabled(
+        &mut self,
+        value: bool,
+        #[zbus(header)] header: Option<zbus::message::Header<'_>>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> zbus::fdo::Result<()> {
+        self.require_root_to_set("LivenessEnabled", header.as_ref(), conn)
+            .await?;
+        self.set_policy_property(
+            serde_json::json!({ "liveness_enabled": value }),
+            "liveness_enabled",
+            toml::Value::Boolean(value),
+        )
+        .await
+    }
+
+    /// Frames each verify captures. Root may set it within 1–30, as long as
+    /// the liveness check still gets the frames it needs; a change applies
+    /// and is saved as for `SimilarityThreshold`.
+    #[zbus(property)]
+    async fn frames_per_verify(&self) -> u32 {
+        let frames = self.state.lock().await.config.frames_per_verify;
+        u32::try_from(frames).unwrap_or(u32::MAX)
+    }
+
+    #[zbus(property)]
+    async fn set_frames_per_verify(
+        &mut self,
+        value: u32,
+        #[zbus(header)] header: Option<zbus::message::Header<'_>>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> zbus::fdo::Result<()> {
+        self.require_root_to_set("FramesPerVerify", header.as_ref(), conn)
+            .await?;
+        if !(1..=MAX_PROPERTY_FRAMES).contains(&value) {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "FramesPerVerify {value} is outside 1–{MAX_PROPERTY_FRAMES}"
+            )));
+        }
+        {
+            let mut state = self.state.lock().await;
+            let mut candidate = state.config.clone();
+            candidate.frames_per_verify = value as usize;
+            VerifyPolicy::from_config(&candidate)
+                .validate(&candidate)
+                .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+            tracing::warn!(
+                target: polkit::AUDIT_TARGET,
+                from = state.config.frames_per_verify,
+                to = value,
+                "frames per verify changed at runtime"
+            );
+            state.config.frames_per_verify = candidate.frames_per_verify;
+        }
+        self.persist_setting("frames_per_verify", toml::Value::Integer(value.into()))
+            .await;
+        Ok(())
+    }
+
+    /// Settings stored for the configured camera, as JSON: its `device_id`,
+    /// the `stored` overrides and the `liveness_min_displacement` in effect.
+    /// `device_id` is null for a camera without a stable identity. Root only.
+    async fn get_device_settings(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("GetDeviceSettings", access, &header, conn).await?;
+
+        let state = self.state.lock().await;
+        let device_id = visage_hw::quirks::get_device_id(&state.config.camera_device);
+        let stored = match &device_id {
+            Some(device_id) => state.store.get_device_settings(device_id).await?,
+            None => DeviceSettings::default(),
+        };
+        Ok(device_settings_report(
+            device_id.as_deref(),
+            &stored,
+            &state.config,
+        ))
+    }
+
+    /// Store settings for the configured camera, e.g. a calibrated
+    /// `liveness_min_displacement`, and apply them at once. `settings_json`
+    /// names the fields to change; `null` clears one, which takes effect at
+    /// the next restart. Stored settings replace the configured defaults
+    /// whenever the daemon starts on this camera. Returns the same JSON as
+    /// `GetDeviceSettings`. Root only.
+    async fn set_device_settings(
+        &self,
+        settings_json: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let (access, camera_device) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.camera_device.clone(),
+            )
+        };
+        require_root("SetDeviceSettings", access, &header, conn).await?;
+
+        let device_id = visage_hw::quirks::get_device_id(&camera_device).ok_or_else(|| {
+            VisageError::Failed(format!(
+                "camera {camera_device} has no stable device id; per-camera settings need a USB camera"
+            ))
+        })?;
+        let stored = self
+            .update_device_settings(&device_id, settings_json)
+            .await?;
+        let state = self.state.lock().await;
+        Ok(device_settings_report(
+            Some(&device_id),
+            &stored,
+            &state.config,
+        ))
+    }
+
+    /// Re-verify the model files against the manifest and load them into the
+    /// engine without a restart, e.g. after `visage setup` repaired them.
+    ///
+    /// The new sessions must load and pass the pipeline self-test; they are
+    /// swapped in between engine operations, so the camera stays open.
+    /// Returns JSON naming the reloaded files and whether the reload ended a
+    /// degraded state. Any failure keeps the current models. Root only.
+    async fn reload_models(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let (access, model_dir, model_manifest, engine) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.config.model_dir.clone(),
+                state.config.model_manifest.clone(),
+                state.engine.clone(),
+            )
+        };
+        require_root("ReloadModels", access, &header, conn).await?;
+        tracing::info!(model_dir = %model_dir.display(), "reload_models requested");
+
+        let checked_dir = model_dir.clone();
+        // The manifest is reread, so an updated one takes effect with the
+        // models. Handlers run on zbus's executor rather than tokio's, so the
+        // hashing goes to the `blocking` pool, not `spawn_blocking`.
+        blocking::unblock(move || {
+            visage_models::Manifest::load_or_builtin(model_manifest.as_deref())?
+                .verify_dir(&checked_dir)
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "reload_models: model verification failed");
+            VisageError::Failed(format!(
+                "model verification failed, previous models kept: {e}"
+            ))
+        })?;
+
+        let previous_fault = engine.recognizer_fault();
+        engine.reload_models().await?;
+        tracing::warn!(
+            target: polkit::AUDIT_TARGET,
+            model_dir = %model_dir.display(),
+            recognizer_restored = previous_fault.is_some(),
+            "models reloaded at runtime"
+        );
+        Ok(serde_json::json!({
+            "model_dir": model_dir.display().to_string(),
+            "reloaded": visage_models::MODELS.iter().map(|m| m.name).collect::<Vec<_>>(),
+            "recognizer_restored": previous_fault.is_some(),
+            "previous_recognizer_error": previous_fault.as_deref(),
+        })
+        .to_string())
+    }
+
+    /// Capture frames and match them against every enrolled user's
+    /// templates, returning the user whose template matched best at or
+    /// above the similarity threshold, or an empty string when none did.
+    ///
+    /// Users pinned to another model or locked out after failed verifies are
+    /// left out, and the outcome counts toward no one's lockout. Root only on
+    /// the system bus, since the reply names an account.
+    async fn identify(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!("identify requested");
+        let (access, permitted) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state
+                    .config
+                    .verify_schedule
+                    .permits_at(&chrono::Local::now()),
+            )
+        };
+        require_root("Identify", access, &header, conn).await?;
+        if !permitted {
+            tracing::info!("identify: outside permitted time windows");
+            return Err(VisageError::PolicyDenied(
+                "face unlock is not permitted at this time".to_string(),
+            ));
+        }
+
+        let client = sender_name(&header);
+        let (engine, gallery, threshold, frames_count, timeout_secs, liveness, (ticket, cancel)) = {
+            let mut state = self.state.lock().await;
+            let galleries = state.store.all_galleries().await.map_err(|e| {
+                tracing::error!(class = e.class(), error = %e, "identify: gallery fetch failed");
+                VisageError::from(e)
+            })?;
+            let mut gallery = Vec::new();
+            for (user, models) in galleries {
+                if state.rate_limiter.check(&user).is_err() {
+                    tracing::info!(user, "identify: skipping locked-out user");
+                    continue;
+                }
+                if check_model_pin(&state.store, &user, ARCFACE_MODEL_VERSION)
+                    .await
+                    .is_err()
+                {
+                    continue;
+                }
+                gallery.extend(models);
+            }
+            if gallery.is_empty() {
+                tracing::info!("identify: no enrolled user to match against");
+                return Ok(String::new());
+            }
+            (
+                state.engine.clone(),
+                gallery,
+                state.config.similarity_threshold,
+                state.config.frames_per_verify,
+                state.config.verify_timeout_secs,
+                state
+                    .config
+                    .liveness_enabled
+                    .then(|| state.config.liveness_policy()),
+                state.pending_verifies.register(&client, None),
+            )
+        };
+
+        let timeout = std::time::Duration::from_secs(timeout_secs);
+        let outcome = engine
+            .identify(gallery, threshold, frames_count, timeout, liveness, cancel)
+            .await;
+        self.state.lock().await.pending_verifies.finish(ticket);
+        let identified = match outcome {
+            Ok(identified) => identified,
+            Err(EngineError::LivenessCheckFailed { displacement, .. }) => {
+                tracing::warn!(
+                    displacement,
+                    "identify: liveness check failed — treating as no match"
+                );
+                return Ok(String::new());
+            }
+            Err(EngineError::VerifyTimeout) => {
+                tracing::error!(timeout_secs, "identify timed out");
+                return Err(VisageError::Timeout(format!(
+                    "identification did not complete within {timeout_secs}s"
+                )));
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "identify failed");
+                return Err(e.into());
+            }
+        };
+        tracing::info!(
+            user = identified.user.as_deref(),
+            similarity = identified.similarity,
+            frames = identified.frames_captured,
+            elapsed_ms = identified.elapsed.as_millis() as u64,
+            "identify complete"
+        );
+        Ok(identified.user.unwrap_or_default())
+    }
+
+    /// Run detection, extraction and matching on a supplied PNG frame instead
+    /// of the camera, returning the detailed result as JSON. Root only.
+    ///
+    /// Intended for regression testing: replaying a stored "golden" frame
+    /// yields the same scores until models or preprocessing change.
+    async fn verify_frame(
+        &self,
+        user: &str,
+        frame_png_base64: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("VerifyFrame", access, &header, conn).await?;
+
+        let frame = crate::frame_input::decode_png_base64(frame_png_base64)
+            .map_err(|e| VisageError::InvalidArgs(e.to_string()))?;
+        tracing::info!(
+            user,
+            width = frame.width,
+            height = frame.height,
+            "verify_frame requested"
+        );
+
+        let (engine, gallery, threshold) = {
+            let state = self.state.lock().await;
+            let user = username::canonicalize(user, state.config.percent_encoded_usernames)?;
+            let gallery = fetch_verify_gallery(&state.store, &user).await?;
+            (
+                state.engine.clone(),
+                gallery,
+                state.config.similarity_threshold,
+            )
+        };
+
+        let report = engine
+            .verify_frame(frame, gallery, threshold)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "verify_frame failed");
+                VisageError::from(e)
+            })?;
+        serde_json::to_string(&report).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Capture raw frames and return a JSON brightness histogram with
+    /// over/under-exposed pixel percentages and a lighting verdict, for
+    /// diagnosing rooms that are too dark, too bright or backlit.
+    ///
+    /// Root only on the system bus. Works while the daemon runs degraded.
+    async fn exposure_report(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!("exposure_report requested");
+        let (access, engine, frames_count, timeout_secs) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.engine.clone(),
+                state.config.exposure_report_frames,
+                state.config.verify_timeout_secs,
+            )
+        };
+        require_root("ExposureReport", access, &header, conn).await?;
+
+        let stats = engine
+            .exposure_report(frames_count, std::time::Duration::from_secs(timeout_secs))
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "exposure report failed");
+                VisageError::from(e)
+            })?;
+        tracing::info!(
+            frames = stats.frames,
+            mean = stats.mean_brightness,
+            under_pct = stats.underexposed_pct,
+            over_pct = stats.overexposed_pct,
+            verdict = ?stats.verdict,
+            "exposure report"
+        );
+        serde_json::to_string(&stats).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Capture `frames` frames (2–300) through the verify capture path and
+    /// return JSON with the achieved frame rate, inter-frame jitter and the
+    /// dark and dropped frames, for diagnosing slow or flaky cameras. The
+    /// capture stops at the verify timeout.
+    ///
+    /// Root only on the system bus. Works while the daemon runs degraded.
+    async fn camera_benchmark(
+        &self,
+        frames: u32,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Capture)?;
+        tracing::info!(frames, "camera_benchmark requested");
+        let (access, engine, timeout_secs) = {
+            let state = self.state.lock().await;
+            (
+                BusAccess::of(&state.config),
+                state.engine.clone(),
+                state.config.verify_timeout_secs,
+            )
+        };
+        require_root("CameraBenchmark", access, &header, conn).await?;
+        if !(2..=MAX_BENCHMARK_FRAMES).contains(&frames) {
+            return Err(VisageError::InvalidArgs(format!(
+                "frames must be between 2 and {MAX_BENCHMARK_FRAMES}, got {frames}"
+            )));
+        }
+
+        let report = engine
+            .camera_benchmark(
+                frames as usize,
+                std::time::Duration::from_secs(timeout_secs),
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "camera benchmark failed");
+                VisageError::from(e)
+            })?;
+        tracing::info!(
+            frames = report.frames,
+            fps = report.fps,
+            jitter_ms = report.jitter_ms,
+            dark = report.dark_frames,
+            dropped = report.dropped_frames,
+            "camera benchmark"
+        );
+        serde_json::to_string(&report).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Report the engine's request queue as JSON: how many requests are
+    /// waiting, and which operation is in flight, for how long and how long
+    /// since it last made progress.
+    async fn engine_queue_status(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let engine = self.state.lock().await.engine.clone();
+        serde_json::to_string(&engine.queue_status())
+            .map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Abort the engine's in-flight operation; its caller receives
+    /// `org.freedesktop.Visage1.Error.Cancelled`. Returns false when the
+    /// engine is idle. Queued requests are left alone.
+    ///
+    /// Root only.
+    async fn cancel_current(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<bool, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let (access, engine) = {
+            let state = self.state.lock().await;
+            (BusAccess::of(&state.config), state.engine.clone())
+        };
+        require_root("CancelCurrent", access, &header, conn).await?;
+        match engine.cancel_current() {
+            Some(operation) => {
+                tracing::warn!(operation, "in-flight engine operation cancelled");
+                Ok(true)
+            }
+            None => {
+                tracing::info!("cancel requested with no operation in flight");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Report enrollments under different users whose crop hashes nearly match.
+    ///
+    /// Returns a JSON array of pairs ordered by ascending Hamming distance.
+    /// Only enrollments made with `VISAGE_ENROLL_CROP_HASH=1` carry a hash.
+    /// Root only.
+    async fn find_duplicate_enrollments(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!("find_duplicate_enrollments requested");
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("FindDuplicateEnrollments", access, &header, conn).await?;
+
+        let state = self.state.lock().await;
+        let duplicates = state
+            .store
+            .find_cross_user_duplicates(state.config.duplicate_hash_max_distance)
+            .await
+            .map_err(|e| VisageError::Failed(e.to_string()))?;
+        if !duplicates.is_empty() {
+            tracing::warn!(
+                count = duplicates.len(),
+                "cross-user duplicate enrollments found"
+            );
+        }
+        serde_json::to_string(&duplicates).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Report enrolled users whose accounts no longer resolve via NSS.
+    ///
+    /// Returns a JSON array of usernames. Users whose lookup fails are left
+    /// out rather than reported. Root only.
+    async fn find_orphans(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<String, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!("find_orphans requested");
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("FindOrphans", access, &header, conn).await?;
+
+        let orphans = find_orphans(&self.state, account_exists)
+            .await
+            .map_err(|e| VisageError::Failed(e.to_string()))?;
+        serde_json::to_string(&orphans).map_err(|e| VisageError::Failed(e.to_string()))
+    }
+
+    /// Remove every template, centroid and setting of the users
+    /// `FindOrphans` reports. Returns how many templates were removed.
+    /// Root only.
+    async fn purge_orphans(
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<u64, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        tracing::info!("purge_orphans requested");
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("PurgeOrphans", access, &header, conn).await?;
+
+        let orphans = find_orphans(&self.state, account_exists)
+            .await
+            .map_err(|e| VisageError::Failed(e.to_string()))?;
+        let state = self.state.lock().await;
+        let mut removed = 0;
+        for user in &orphans {
+            let templates = state
+                .store
+                .remove_user(user)
+                .await
+                .map_err(|e| VisageError::Failed(e.to_string()))?;
+            tracing::info!(
+                target: polkit::AUDIT_TARGET,
+                user,
+                templates,
+                "orphaned user purged"
+            );
+            removed += templates;
+        }
+        Ok(removed)
+    }
+
+    /// The whole face database as one bundle sealed with `passphrase`,
+    /// including the embedding key, for `Restore` here or on another
+    /// machine. Root only.
+    async fn backup(
+        &self,
+        passphrase: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<Vec<u8>, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("Backup", access, &header, conn).await?;
+
+        let store = self.state.lock().await.store.clone();
+        let (bundle, templates) = store.backup(passphrase).await.map_err(backup_error)?;
+        tracing::warn!(
+            target: polkit::AUDIT_TARGET,
+            templates,
+            bytes = bundle.len(),
+            "face database backed up"
+        );
+        Ok(bundle)
+    }
+
+    /// Replace the whole face database with a `Backup` bundle. Returns how
+    /// many templates were restored; a wrong passphrase leaves the database
+    /// as it was. Root only.
+    async fn restore(
+        &self,
+        bundle: Vec<u8>,
+        passphrase: &str,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+    ) -> Result<u64, VisageError> {
+        let _request = self.admit(&header, RequestClass::Query)?;
+        let access = BusAccess::of(&self.state.lock().await.config);
+        require_root("Restore", access, &header, conn).await?;
+
+        let store = self.state.lock().await.store.clone();
+        let templates = store
+            .restore(bundle, passphrase)
+            .await
+            .map_err(backup_error)?;
+        // Remembered successes vouched for the galleries just replaced.
+        self.state.lock().await.verify_grace.clear();
+        tracing::warn!(
+            target: polkit::AUDIT_TARGET,
+            templates,
+            "face database restored from backup"
+        );
+        Ok(templates)
+    }
+}
+
+/// A bundle the caller got wrong (passphrase, format) is their error; the
+/// rest means the store could not be read or written.
+fn backup_error(e: crate::store::StoreError) -> VisageError {
+    match e {
+        crate::store::StoreError::Bundle(_) => VisageError::InvalidArgs(e.to_string()),
+        e => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use zbus::object_server::Interface;
+
+    async fn service(config: Config) -> VisageService {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let enroll_cooldown = config.enroll_cooldown();
+        let verify_tokens = config.verify_tokens();
+        let verify_grace = config.verify_grace();
+        let request_limits = config.request_limits();
+        VisageService {
+            state: Arc::new(Mutex::new(AppState {
+                config,
+                engine: EngineHandle::disconnected(),
+                store,
+                rate_limiter: RateLimiter::new(),
+                enroll_cooldown,
+                previews: PreviewSessions::new(std::time::Duration::from_secs(30)),
+                verify_tokens,
+                verify_grace,
+                pending_verifies: PendingVerifies::default(),
+                stats: QualityStats::new(),
+                selftest: SelfTestState::default(),
+                log_filter: None,
+                authority: Arc::new(polkit::MockAuthority::answering(
+                    polkit::Authorization::NotAuthorized,
+                )),
+            })),
+            limiter: RequestLimiter::new(request_limits),
+        }
+    }
+
+    /// A method call as the client with unique name `sender` sends it.
+    fn call_from(sender: &str) -> zbus::message::Message {
+        zbus::message::Message::method_call("/org/freedesktop/Visage1", "Status")
+            .unwrap()
+            .sender(sender)
+            .unwrap()
+            .build(&())
+            .unwrap()
+    }
+
+    async fn status_json(service: &VisageService) -> serde_json::Value {
+        let call = call_from(":1.1");
+        serde_json::from_str(&service.status(call.header()).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_secure_config_by_default() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        assert_eq!(status_json(&service).await["insecure_config"], false);
+    }
+
+    #[tokio::test]
+    async fn test_status_advertises_privacy_mode() {
+        let default = service(Config::from_pairs(&[]).unwrap()).await;
+        assert_eq!(status_json(&default).await["privacy_mode"], false);
+        let config = Config::from_pairs(&[("VISAGE_PRIVACY_MODE", "1")]).unwrap();
+        let private = service(config).await;
+        assert_eq!(status_json(&private).await["privacy_mode"], true);
+    }
+
+    #[tokio::test]
+    async fn test_status_flags_insecure_threshold_override() {
+        let config = Config::from_pairs(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "0.10"),
+            ("VISAGE_ALLOW_INSECURE_THRESHOLD", "1"),
+        ])
+        .unwrap();
+        let service = service(config).await;
+        assert_eq!(status_json(&service).await["insecure_config"], true);
+    }
+
+    #[tokio::test]
+    async fn test_status_advertises_capabilities() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let status = status_json(&service).await;
+        assert_eq!(
+            status["status_schema_version"],
+            capabilities::STATUS_SCHEMA_VERSION
+        );
+        let names = capabilities::names(&service.state.lock().await.config);
+        assert_eq!(status["capabilities"], serde_json::json!(names));
+        assert_eq!(service.get_capabilities().await, names);
+    }
+
+    #[tokio::test]
+    async fn test_get_status_sends_the_status_as_a_typed_dictionary() {
+        let service = service_verifying_alice_at_half().await;
+        let (_server, client) = serve_p2p(service).await;
+        let call = |method: &'static str| {
+            let client = client.clone();
+            async move {
+                client
+                    .call_method(
+                        None::<()>,
+                        "/org/freedesktop/Visage1",
+                        Some("org.freedesktop.Visage1"),
+                        method,
+                        &(),
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let reply = call("GetStatus").await;
+        assert_eq!(reply.body().signature().to_string(), "a{sv}");
+        let status: HashMap<String, OwnedValue> = reply.body().deserialize().unwrap();
+        let json: String = call("Status").await.body().deserialize().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            String::try_from(status["version"].try_clone().unwrap()).unwrap(),
+            json["version"]
+        );
+        assert_eq!(
+            u64::try_from(&status["models_enrolled"]).unwrap(),
+            json["models_enrolled"]
+        );
+        assert_eq!(
+            bool::try_from(&status["liveness_enabled"]).unwrap(),
+            json["liveness_enabled"]
+        );
+        assert!(status.contains_key("uptime_secs"));
+        assert_eq!(status["models_by_version"].value_signature(), "av");
+    }
+
+    #[tokio::test]
+    async fn test_status_and_stats_count_models_by_version() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let store = service.state.lock().await.store.clone();
+        for (user, version) in [("alice", "w600k_r50"), ("bob", "r34_legacy")] {
+            let emb = visage_core::Embedding::new(vec![1.0; 512], Some(version.into()));
+            store
+                .insert(user, "default", &emb, 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+
+        let status = status_json(&service).await;
+        assert_eq!(
+            status["models_by_version"],
+            serde_json::json!([
+                {"model_version": "r34_legacy", "templates": 1, "users": 1},
+                {"model_version": "w600k_r50", "templates": 1, "users": 1},
+            ])
+        );
+        assert_eq!(
+            status["model_upgrade"],
+            serde_json::json!({
+                "target_version": visage_models::RECOGNIZER_VERSION,
+                "users_enrolled": 2,
+                "users_to_reenroll": 1,
+            })
+        );
+        let stats: serde_json::Value =
+            serde_json::from_str(&service.stats(call_from(":1.1").header()).await.unwrap())
+                .unwrap();
+        assert_eq!(stats["models_by_version"], status["models_by_version"]);
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_resolve_to_introspected_methods() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let mut xml = String::new();
+        service.introspect_to_writer(&mut xml, 0);
+
+        assert!(xml.contains(r#"<method name="GetCapabilities">"#));
+        for capability in capabilities::CAPABILITIES {
+            let method = format!(r#"<method name="{}">"#, capability.member);
+            assert!(
+                xml.contains(&method),
+                "capability '{}' names missing method '{}'",
+                capability.name,
+                capability.member
+            );
+        }
+    }
+
+    /// Golden copy of the interface contract, installed for client codegen.
+    /// Regenerate after an intended interface change with
+    /// `VISAGE_UPDATE_GOLDEN=1 cargo test -p visaged introspection`.
+    const GOLDEN_INTROSPECTION: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../packaging/dbus/org.freedesktop.Visage1.xml"
+    );
+
+    #[tokio::test]
+    async fn test_introspection_matches_golden_snapshot() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let mut xml = String::from(
+            "<!DOCTYPE node PUBLIC \"-//freedesktop//DTD D-BUS Object Introspection 1.0//EN\"\n \
+             \"http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd\">\n<node>\n",
+        );
+        service.introspect_to_writer(&mut xml, 2);
+        xml.push_str("</node>\n");
+
+        if std::env::var_os("VISAGE_UPDATE_GOLDEN").is_some() {
+            std::fs::write(GOLDEN_INTROSPECTION, &xml).unwrap();
+        }
+        let golden = std::fs::read_to_string(GOLDEN_INTROSPECTION).unwrap();
+        assert!(
+            xml == golden,
+            "org.freedesktop.Visage1 no longer matches {GOLDEN_INTROSPECTION}; \
+             if the change is intended, rerun with VISAGE_UPDATE_GOLDEN=1\n{xml}"
+        );
+        // Every member is documented for codegen consumers.
+        let members = xml.matches("<method ").count()
+            + xml.matches("<signal ").count()
+            + xml.matches("<property ").count();
+        assert_eq!(xml.matches("<!--").count(), members);
+    }
+
+    #[test]
+    fn test_verify_report_includes_timing_only_when_enabled() {
+        let result = crate::engine::VerifyResult {
+            result: visage_core::MatchResult {
+                matched: true,
+                similarity: 0.82,
+                model_id: Some("m1".to_string()),
+                model_label: Some("desk".to_string()),
+            },
+            best_quality: 0.9,
+            best_alignment: 0.8,
+            frames_captured: 3,
+            frames_with_face: 2,
+            dark_skipped: 1,
+            elapsed: std::time::Duration::from_millis(640),
+            frame_memory: Default::default(),
+            capture: None,
+            fast_path: false,
+            pose_mismatch: None,
+            rejection: None,
+        };
+        let elapsed = std::time::Duration::from_millis(910);
+
+        let report = verify_report(&result, elapsed, 0.4, false);
+        assert_eq!(report["matched"], true);
+        assert_eq!(report["model_label"], "desk");
+        assert!(report.get("elapsed_ms").is_none());
+        assert!(report.get("frames_captured").is_none());
+
+        let report = verify_report(&result, elapsed, 0.4, true);
+        assert_eq!(report["elapsed_ms"], 910);
+        assert_eq!(report["engine_ms"], 640);
+        assert_eq!(report["frames_captured"], 3);
+        assert_eq!(report["frames_with_face"], 2);
+        assert_eq!(report["dark_skipped"], 1);
+        assert!(report["failure_reason"].is_null());
+
+        let mut result = result;
+        result.result.matched = false;
+        let report = verify_report(&result, elapsed, 0.4, false);
+        assert_eq!(report["failure_reason"], "below_threshold");
+        assert_eq!(report["similarity"], 0.82f32);
+        result.rejection = Some(crate::engine::Rejection::PoseMismatch);
+        let report = verify_report(&result, elapsed, 0.4, false);
+        assert_eq!(report["failure_reason"], "pose_mismatch");
+    }
+
+    #[test]
+    fn test_foreign_device_only_when_no_template_matches_camera() {
+        let capture = |device_id: &str| CaptureContext {
+            device: "/dev/video0".to_string(),
+            device_id: device_id.to_string(),
+            fourcc: "YUYV".to_string(),
+            width: 640,
+            height: 360,
+            emitter_active: false,
+            preprocessing: Vec::new(),
+        };
+        let ir = "usb:04f2:b6d9.2".to_string();
+        let rgb = capture("usb:04f2:b6d9.0");
+        let enrolled = BTreeSet::from([ir.clone()]);
+
+        // Enrolled on the IR function, verifying on the RGB one.
+        assert_eq!(
+            foreign_device(&enrolled, Some(&rgb)),
+            Some("usb:04f2:b6d9.0")
+        );
+        assert_eq!(foreign_device(&enrolled, Some(&capture(&ir))), None);
+        let both = BTreeSet::from([ir, rgb.device_id.clone()]);
+        assert_eq!(foreign_device(&both, Some(&rgb)), None);
+        // Legacy templates, or a source that cannot identify itself.
+        assert_eq!(foreign_device(&BTreeSet::new(), Some(&rgb)), None);
+        assert_eq!(foreign_device(&enrolled, Some(&capture(""))), None);
+        assert_eq!(foreign_device(&enrolled, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_degraded_engine() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let status = status_json(&service).await;
+        assert_eq!(status["degraded"], false);
+        assert!(status["recognizer_error"].is_null());
+
+        service.state.lock().await.engine = EngineHandle::degraded("w600k_r50.onnx missing");
+        let status = status_json(&service).await;
+        assert_eq!(status["degraded"], true);
+        assert_eq!(status["recognizer_error"], "w600k_r50.onnx missing");
+
+        let err = VisageError::from(EngineError::RecognizerUnavailable("missing".into()));
+        assert!(matches!(err, VisageError::RecognizerUnavailable(msg) if msg.contains("missing")));
+    }
+
+    #[tokio::test]
+    async fn test_gallery_fetch_separates_not_enrolled_from_store_failure() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let store = service.state.lock().await.store.clone();
+
+        let err = fetch_verify_gallery(&store, "alice").await.unwrap_err();
+        assert!(matches!(&err, VisageError::NotEnrolled(_)), "{err:?}");
+
+        store.break_gallery_reads().await;
+        let err = fetch_verify_gallery(&store, "alice").await.unwrap_err();
+        assert!(
+            matches!(&err, VisageError::StoreUnavailable(msg) if msg.starts_with("database: ")),
+            "{err:?}"
+        );
+        let name = zbus::DBusError::name(&err);
+        assert_eq!(
+            name.as_str(),
+            "org.freedesktop.Visage1.Error.StoreUnavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enroll_cooldown_refuses_quick_reenrollment() {
+        let config = Config::from_pairs(&[("VISAGE_ENROLL_COOLDOWN_SECS", "60")]).unwrap();
+        let service = service(config).await;
+        service.state.lock().await.engine = EngineHandle::enrolling();
+
+        service
+            .enroll_canonical("alice", "", "desk", None, None)
+            .await
+            .unwrap();
+        let err = service
+            .enroll_canonical("alice", "", "desk", None, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, VisageError::RateLimited(msg) if msg.starts_with("retry after 60s: ")),
+            "{err:?}"
+        );
+        // The cooldown is per user.
+        service
+            .enroll_canonical("bob", "", "desk", None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_enroll_refuses_or_marks_near_duplicates() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let face = |wobble: f32| {
+            let mut values = vec![0.0; 512];
+            values[0] = 1.0;
+            values[1] = wobble;
+            visage_core::Embedding::normalized(values, Some(ARCFACE_MODEL_VERSION.into()))
+        };
+        let desk = store
+            .insert("alice", "desk", &face(0.0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        let mut values = vec![0.0; 512];
+        values[2] = 1.0;
+        let far = visage_core::Embedding::normalized(values, Some(ARCFACE_MODEL_VERSION.into()));
+        store
+            .insert("alice", "glasses", &far, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        // cos = 1/sqrt(1.01) ≈ 0.995: a near-duplicate of "desk".
+        let near = face(0.1);
+        let err = check_duplicate(&store, "alice", &near, 0.92, false)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, VisageError::DuplicateEnrollment(msg)
+                if msg.contains(&format!("'desk' ({desk})"))),
+            "{err:?}"
+        );
+        assert_eq!(
+            check_duplicate(&store, "alice", &near, 0.92, true)
+                .await
+                .unwrap(),
+            Some(desk)
+        );
+
+        // cos ≈ 0.71 to "desk" and 0 to "glasses": a new angle, kept under
+        // either policy.
+        let angled = face(1.0);
+        for allow in [false, true] {
+            assert_eq!(
+                check_duplicate(&store, "alice", &angled, 0.92, allow)
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+        // Other users' templates and a disabled check never count.
+        assert_eq!(
+            check_duplicate(&store, "bob", &near, 0.92, false)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            check_duplicate(&store, "alice", &near, 0.0, false)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    /// Serve `service` over a peer-to-peer connection, as visaged serves it
+    /// on the bus, and return the client's end.
+    async fn serve_p2p(service: VisageService) -> (zbus::Connection, zbus::Connection) {
+        let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server = async {
+            zbus::connection::Builder::unix_stream(server_sock)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/Visage1", service)
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        };
+        let client = async {
+            zbus::connection::Builder::unix_stream(client_sock)
+                .p2p()
+                .build()
+                .await
+                .unwrap()
+        };
+        tokio::join!(server, client)
+    }
+
+    /// The D-Bus error name a client receives from calling `method`.
+    async fn error_name<B>(client: &zbus::Connection, method: &str, body: &B) -> (String, String)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let err = client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                method,
+                body,
+            )
+            .await
+            .unwrap_err();
+        match err {
+            zbus::Error::MethodError(name, message, _) => {
+                (name.to_string(), message.unwrap_or_default())
+            }
+            other => panic!("{method}: not a method error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enroll_signals_progress_to_the_caller() {
+        use futures_lite::StreamExt;
+
+        let config = Config::from_pairs(&[
+            ("VISAGE_SESSION_BUS", "1"),
+            ("VISAGE_FRAMES_PER_ENROLL", "4"),
+        ])
+        .unwrap();
+        let service = service(config).await;
+        service.state.lock().await.engine = EngineHandle::enrolling();
+        let (_server, client) = serve_p2p(service).await;
+        let mut messages = zbus::MessageStream::from(&client);
+
+        client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "Enroll",
+                &("alice", "desk"),
+            )
+            .await
+            .unwrap();
+
+        // The signals precede the reply on the connection, so they are all
+        // queued by now.
+        let mut counts = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.unwrap();
+            let header = message.header();
+            if header.member().map(|m| m.as_str()) != Some("EnrollProgress") {
+                continue;
+            }
+            let (user, captured, total, quality): (String, u32, u32, f64) =
+                message.body().deserialize().unwrap();
+            assert_eq!((user.as_str(), total), ("alice", 4));
+            assert!(quality > 0.0, "{quality}");
+            counts.push(captured);
+            if captured == total {
+                break;
+            }
+        }
+        assert_eq!(counts, [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_enroll_replies_when_the_engine_is_abandoned_mid_request() {
+        let config = Config::from_pairs(&[("VISAGE_SESSION_BUS", "1")]).unwrap();
+        let service = service(config).await;
+        let engine = EngineHandle::stuck();
+        service.state.lock().await.engine = engine.clone();
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+
+        // The stuck engine keeps the progress sender; abandoning it must
+        // still end the call.
+        let abandon = async {
+            async_io::Timer::after(std::time::Duration::from_millis(100)).await;
+            engine.abandon();
+        };
+        let ((name, _), ()) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            tokio::join!(error_name(&client, "Enroll", &("alice", "desk")), abandon)
+        })
+        .await
+        .expect("Enroll must reply once the engine is abandoned");
+        assert_eq!(name, "org.freedesktop.Visage1.Error.EngineRestarted");
+        assert_eq!(state.lock().await.pending_verifies.cancel_user("alice"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_started_progress_and_completed_to_the_caller() {
+        use futures_lite::StreamExt;
+
+        let service = service_verifying_alice_at_half().await;
+        service.state.lock().await.config.session_bus = true;
+        let (_server, client) = serve_p2p(service).await;
+        let mut messages = zbus::MessageStream::from(&client);
+
+        let matched: bool = client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "Verify",
+                &("alice",),
+            )
+            .await
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap();
+        assert!(matched);
+
+        // The signals precede the reply on the connection, so they are all
+        // queued by now.
+        let mut signals = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.unwrap();
+            let header = message.header();
+            match header.member().map(|m| m.as_str()) {
+                Some("VerifyStarted") => {
+                    let user: String = message.body().deserialize().unwrap();
+                    signals.push(format!("started {user}"));
+                }
+                Some("VerifyProgress") => {
+                    let (user, done, total): (String, u32, u32) =
+                        message.body().deserialize().unwrap();
+                    signals.push(format!("progress {user} {done}/{total}"));
+                }
+                Some("VerifyCompleted") => {
+                    let (user, matched, similarity): (String, bool, f64) =
+                        message.body().deserialize().unwrap();
+                    assert!(similarity >= 0.5, "{similarity}");
+                    signals.push(format!("completed {user} {matched}"));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            signals,
+            [
+                "started alice",
+                "progress alice 1/3",
+                "progress alice 2/3",
+                "progress alice 3/3",
+                "completed alice true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_replies_when_the_engine_is_abandoned_mid_request() {
+        let service = service_verifying_alice_at_half().await;
+        let engine = EngineHandle::stuck();
+        service.state.lock().await.config.session_bus = true;
+        service.state.lock().await.engine = engine.clone();
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+
+        // The stuck engine never answers and never drops the progress
+        // sender; the watchdog abandoning it must still end the call.
+        let abandon = async {
+            async_io::Timer::after(std::time::Duration::from_millis(100)).await;
+            engine.abandon();
+        };
+        let ((name, _), ()) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            tokio::join!(error_name(&client, "Verify", &("alice",)), abandon)
+        })
+        .await
+        .expect("Verify must reply once the engine is abandoned");
+        assert_eq!(name, "org.freedesktop.Visage1.Error.EngineRestarted");
+        assert_eq!(state.lock().await.pending_verifies.cancel_user("alice"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_verify_is_bracketed_by_started_and_completed_signals() {
+        use futures_lite::StreamExt;
+
+        let service = service_verifying_alice_at_half().await;
+        let state = service.state.clone();
+        let (server, client) = serve_p2p(service).await;
+        let mut messages = zbus::MessageStream::from(&client);
+        // A peer connection carries no sender for the UID checks `Verify`
+        // needs, so drive the served object past them directly.
+        let iface = server
+            .object_server()
+            .interface::<_, VisageService>("/org/freedesktop/Visage1")
+            .await
+            .unwrap();
+        let emitter = iface.signal_emitter().clone();
+        let verify = || async {
+            let service = iface.get().await;
+            let started = std::time::Instant::now();
+            service
+                .verify_canonical("alice", None, ":1.1", started, Some(&emitter))
+                .await
+        };
+
+        assert!(verify().await.unwrap().0.result.matched);
+        state.lock().await.config.similarity_threshold = 0.6;
+        assert!(!verify().await.unwrap().0.result.matched);
+        state.lock().await.engine =
+            EngineHandle::flaky_camera(Arc::new(std::sync::atomic::AtomicBool::new(true)));
+        assert!(verify().await.is_err());
+
+        // Each verify's signals were sent before it returned.
+        let mut signals = Vec::new();
+        while signals.len() < 6 {
+            let message = messages.next().await.unwrap().unwrap();
+            let header = message.header();
+            match header.member().map(|m| m.as_str()) {
+                Some("VerificationStarted") => {
+                    let user: String = message.body().deserialize().unwrap();
+                    signals.push(format!("started {user}"));
+                }
+                Some("VerificationCompleted") => {
+                    let (user, matched, reason): (String, bool, String) =
+                        message.body().deserialize().unwrap();
+                    signals.push(format!("completed {user} {matched} {reason:?}"));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            signals,
+            [
+                "started alice",
+                r#"completed alice true """#,
+                "started alice",
+                r#"completed alice false "no-match""#,
+                "started alice",
+                r#"completed alice false "error""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completion_reason_names_the_outcome() {
+        let result = |matched: bool, frames_with_face: usize| crate::engine::VerifyResult {
+            result: visage_core::MatchResult {
+                matched,
+                similarity: 0.0,
+                model_id: None,
+                model_label: None,
+            },
+            best_quality: 0.0,
+            best_alignment: 0.0,
+            frames_captured: 3,
+            frames_with_face,
+            dark_skipped: 0,
+            elapsed: std::time::Duration::ZERO,
+            frame_memory: Default::default(),
+            capture: None,
+            fast_path: false,
+            pose_mismatch: None,
+            rejection: None,
+        };
+        let ok = |r| Ok((r, std::time::Duration::ZERO));
+        assert_eq!(completion_reason(&ok(result(true, 3))), "");
+        assert_eq!(completion_reason(&ok(result(false, 0))), "no-face");
+        assert_eq!(completion_reason(&ok(result(false, 2))), "no-match");
+        assert_eq!(
+            completion_reason(&Err(VisageError::Timeout("10s".into()))),
+            "timeout"
+        );
+        assert_eq!(
+            completion_reason(&Err(VisageError::Failed("camera".into()))),
+            "error"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clients_receive_structured_error_names() {
+        let config = Config::from_pairs(&[
+            ("VISAGE_SESSION_BUS", "1"),
+            ("VISAGE_ENROLL_COOLDOWN_SECS", "60"),
+        ])
+        .unwrap();
+        let service = service(config).await;
+        let state = service.state.clone();
+        let broken = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        state.lock().await.engine = EngineHandle::flaky_camera(broken.clone());
+        let (_server, client) = serve_p2p(service).await;
+        let name = |short: &str| format!("org.freedesktop.Visage1.Error.{short}");
+
+        let (err, _) = error_name(&client, "Enroll", &("alice", "desk")).await;
+        assert_eq!(err, name("NoFaceDetected"));
+        broken.store(true, std::sync::atomic::Ordering::SeqCst);
+        let (err, _) = error_name(&client, "Enroll", &("alice", "desk")).await;
+        assert_eq!(err, name("CameraUnavailable"));
+
+        state.lock().await.engine = EngineHandle::enrolling();
+        client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "Enroll",
+                &("alice", "desk"),
+            )
+            .await
+            .unwrap();
+        let (err, message) = error_name(&client, "Enroll", &("alice", "desk")).await;
+        assert_eq!(err, name("RateLimited"));
+        assert!(message.starts_with("retry after 60s: "), "{message}");
+
+        state.lock().await.store.break_gallery_reads().await;
+        let (err, _) = error_name(&client, "ListModels", &("alice",)).await;
+        assert_eq!(err, name("StoreUnavailable"));
+        let (err, _) = error_name(&client, "RemoveModel", &("alice", "m1")).await;
+        assert_eq!(err, name("StoreUnavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_models_reports_a_bad_model_dir_over_the_bus() {
+        let config = Config::from_pairs(&[
+            ("VISAGE_SESSION_BUS", "1"),
+            ("VISAGE_MODEL_DIR", "/nonexistent/visage-models"),
+        ])
+        .unwrap();
+        let (_server, client) = serve_p2p(service(config).await).await;
+        let (_, message) = error_name(&client, "ReloadModels", &()).await;
+        assert!(
+            message.starts_with("model verification failed, previous models kept"),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hardened_session_bus_rejects_senderless_calls() {
+        // A peer connection carries no sender, as a replayed or forged
+        // message would not.
+        let hardened = Config::from_pairs(&[
+            ("VISAGE_SESSION_BUS", "1"),
+            ("VISAGE_SESSION_BUS_HARDENED", "1"),
+        ])
+        .unwrap();
+        let service = service(hardened).await;
+        service.state.lock().await.engine = EngineHandle::enrolling();
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+        for (method, body) in [
+            ("Enroll", ("alice", "desk")),
+            ("RemoveModel", ("alice", "m1")),
+        ] {
+            let (_, message) = error_name(&client, method, &body).await;
+            assert!(message.contains("no sender"), "{method}: {message}");
+        }
+        let (_, message) = error_name(&client, "SetLogLevel", &("debug",)).await;
+        assert!(message.contains("no sender"), "{message}");
+
+        // The permissive default lets the same call through.
+        state.lock().await.config.session_bus_hardened = false;
+        client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "Enroll",
+                &("alice", "desk"),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_permissive_session_bus_verifies_a_senderless_caller() {
+        let service = service_verifying_alice_at_half().await;
+        service.state.lock().await.config.session_bus = true;
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+        let verify = || async {
+            client
+                .call_method(
+                    None::<()>,
+                    "/org/freedesktop/Visage1",
+                    Some("org.freedesktop.Visage1"),
+                    "Verify",
+                    &("alice",),
+                )
+                .await
+        };
+
+        // A peer connection carries no sender; the caller is the daemon's
+        // own user, as every peer on a private session bus is.
+        let matched: bool = verify().await.unwrap().body().deserialize().unwrap();
+        assert!(matched);
+
+        // Hardened, the same call is refused.
+        state.lock().await.config.session_bus_hardened = true;
+        let err = verify().await.unwrap_err();
+        assert!(err.to_string().contains("no sender"), "{err}");
+    }
+
+    #[test]
+    fn test_hardened_session_bus_privileges_only_root_and_its_owner() {
+        let owner = BusAccess::HardenedSession { owner_uid: 1000 };
+        assert!(owner.privileged(0));
+        assert!(owner.privileged(1000));
+        assert!(!owner.privileged(1001));
+        assert!(!owner.unchecked());
+        assert!(!BusAccess::System.privileged(1000));
+        assert!(BusAccess::Session.unchecked());
+    }
+
+    #[test]
+    fn test_engine_and_limiter_failures_map_to_their_error_names() {
+        use zbus::DBusError;
+        let liveness = VisageError::from(EngineError::LivenessCheckFailed {
+            displacement: 0.1,
+            threshold: 0.5,
+            live_pairs: 0,
+            min_live_pairs: 2,
+        });
+        assert_eq!(
+            liveness.name().as_str(),
+            "org.freedesktop.Visage1.Error.LivenessFailed"
+        );
+        let locked = VisageError::rate_limited(
+            std::time::Duration::from_millis(286_400),
+            "too many failed attempts",
+        );
+        assert_eq!(
+            locked.name().as_str(),
+            "org.freedesktop.Visage1.Error.RateLimited"
+        );
+        assert_eq!(
+            locked.description(),
+            Some("retry after 287s: too many failed attempts")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_orphans_reports_users_nss_no_longer_knows() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let store = service.state.lock().await.store.clone();
+        let emb = visage_core::Embedding::new(vec![1.0; 512], Some("w600k_r50".into()));
+        for user in ["alice", "ghost", "ldap-user", "j%E9"] {
+            store
+                .insert(user, "default", &emb, 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+        let nss = |state: Arc<Mutex<AppState>>| {
+            move |name: &str| {
+                // A slow directory must not stall every other request.
+                assert!(state.try_lock().is_ok(), "state locked during lookup");
+                match name {
+                    "alice" => Ok(true),
+                    "ldap-user" => Err(nix::Error::EIO),
+                    _ => Ok(false),
+                }
+            }
+        };
+        service.state.lock().await.config.percent_encoded_usernames = true;
+
+        // The directory outage and the name NSS cannot look up are skipped.
+        let orphans = find_orphans(&service.state, nss(service.state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(orphans, ["ghost"]);
+
+        store.remove_user("ghost").await.unwrap();
+        assert!(find_orphans(&service.state, nss(service.state.clone()))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_caller_check_allows_root_and_the_account_itself() {
+        let nss = |name: &str| match name {
+            "alice" => Ok(Some(1000)),
+            "bob" => Ok(Some(1001)),
+            _ => Ok(None),
+        };
+        let system = BusAccess::System;
+        for method in ["Enroll", "ListModels", "RemoveModel", "Verify"] {
+            let check =
+                |user, caller_uid| check_caller(method, system, user, false, caller_uid, nss);
+            assert!(check("alice", 0).await.is_ok(), "{method}");
+            assert!(check("ghost", 0).await.is_ok(), "{method}");
+            assert!(check("alice", 1000).await.is_ok(), "{method}");
+            let err = check("bob", 1000).await.unwrap_err();
+            assert!(
+                matches!(&err, VisageError::AccessDenied(m) if m.contains(method)),
+                "{err:?}"
+            );
+            let err = check("ghost", 1000).await.unwrap_err();
+            assert!(
+                matches!(&err, VisageError::AccessDenied(m) if m.contains(method)),
+                "{err:?}"
+            );
+        }
+
+        // Only root may act on an account NSS cannot name.
+        let raw = "caf%E9";
+        assert!(check_caller("Enroll", system, raw, true, 0, nss)
+            .await
+            .is_ok());
+        let err = check_caller("Enroll", system, raw, true, 1000, nss)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisageError::AccessDenied(_)), "{err:?}");
+
+        // The permissive session bus checks no one.
+        let session = BusAccess::Session;
+        assert!(check_caller("Enroll", session, "bob", false, 1000, nss)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_verify_caller_check_tells_missing_accounts_from_lookup_failures() {
+        let calls = std::cell::Cell::new(0);
+        let nss = |name: &str| {
+            calls.set(calls.get() + 1);
+            match name {
+                "alice" => Ok(Some(1000)),
+                "flaky" if calls.get() < 3 => Err(nix::Error::EAGAIN),
+                "flaky" => Ok(Some(1000)),
+                "ldap-user" => Err(nix::Error::EIO),
+                "sssd-down" => Err(nix::Error::ETIMEDOUT),
+                _ => Ok(None),
+            }
+        };
+        let check = |user: &'static str, caller_uid| {
+            calls.set(0);
+            check_caller("Verify", BusAccess::System, user, false, caller_uid, nss)
+        };
+
+        assert!(check("alice", 1000).await.is_ok());
+        let err = check("alice", 1001).await.unwrap_err();
+        assert!(matches!(err, VisageError::AccessDenied(_)), "{err:?}");
+        let err = check("ghost", 1000).await.unwrap_err();
+        assert!(matches!(err, VisageError::AccessDenied(_)), "{err:?}");
+
+        // A transient failure is retried until the directory answers.
+        assert!(check("flaky", 1000).await.is_ok());
+        assert_eq!(calls.get(), 3);
+
+        // A failed lookup is never "unknown user": a hard error at once, a
+        // transient one once the retry deadline has passed.
+        let err = check("ldap-user", 1000).await.unwrap_err();
+        assert!(
+            matches!(err, VisageError::DirectoryUnavailable(_)),
+            "{err:?}"
+        );
+        assert_eq!(calls.get(), 1);
+        let started = std::time::Instant::now();
+        let err = check("sssd-down", 1000).await.unwrap_err();
+        assert!(
+            matches!(err, VisageError::DirectoryUnavailable(_)),
+            "{err:?}"
+        );
+        assert!(calls.get() > 1);
+        assert!(started.elapsed() < NSS_RETRY_DEADLINE + NSS_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_model_pin_gates_enroll_and_verify() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let store = service.state.lock().await.store.clone();
+
+        // Unpinned users run on whatever the daemon has.
+        assert!(check_model_pin(&store, "alice", "w600k_r50").await.is_ok());
+
+        store
+            .set_model_pin("alice", Some("w600k_r50"))
+            .await
+            .unwrap();
+        assert!(check_model_pin(&store, "alice", "w600k_r50").await.is_ok());
+        let err = check_model_pin(&store, "alice", "antelopev2")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, VisageError::ModelMismatch(msg) if msg.contains("w600k_r50")),
+            "{err:?}"
+        );
+
+        // Other users are unaffected.
+        assert!(check_model_pin(&store, "bob", "antelopev2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_redeem_token_is_refused_while_locked_out_or_pinned() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let session = "/org/freedesktop/login1/session/_31";
+        let (store, token) = {
+            let mut state = service.state.lock().await;
+            let token = state.verify_tokens.issue("alice", session).unwrap();
+            for _ in 0..5 {
+                state.rate_limiter.record_failure("alice");
+            }
+            (state.store.clone(), token)
+        };
+
+        let err = service
+            .redeem_in_session("alice", &token, session)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisageError::RateLimited(_)), "{err:?}");
+        service.state.lock().await.rate_limiter.forget_user("alice");
+
+        store
+            .set_model_pin("alice", Some("antelopev2"))
+            .await
+            .unwrap();
+        let err = service
+            .redeem_in_session("alice", &token, session)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisageError::ModelMismatch(_)), "{err:?}");
+        store.set_model_pin("alice", None).await.unwrap();
+
+        // Neither refusal used the token up.
+        assert!(service
+            .redeem_in_session("alice", &token, session)
+            .await
+            .unwrap());
+        assert!(!service
+            .redeem_in_session("alice", &token, session)
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn test_verify_schedule_check_is_shared_by_verify_and_redeem() {
+        let config = Config::from_pairs(&[]).unwrap();
+        assert!(check_verify_schedule(&config, "alice").is_ok());
+        let config =
+            Config::from_pairs(&[("VISAGE_VERIFY_BLOCKED_WINDOWS", "00:00-12:00; 12:00-00:00")])
+                .unwrap();
+        let err = check_verify_schedule(&config, "alice").unwrap_err();
+        assert!(matches!(err, VisageError::PolicyDenied(_)), "{err:?}");
+    }
+
+    /// A service whose camera verifies of alice score 0.5: the engine
+    /// extracts a constant probe and her one template sits at that similarity.
+    async fn service_verifying_alice_at_half() -> VisageService {
+        let config = Config::from_pairs(&[("VISAGE_LIVENESS_ENABLED", "0")]).unwrap();
+        let service = service(config).await;
+        service.state.lock().await.engine = EngineHandle::enrolling();
+        let values: Vec<f32> = (0..512)
+            .map(|i| (1.0 + 3f32.sqrt() * if i % 2 == 0 { 1.0 } else { -1.0 }) / 2048f32.sqrt())
+            .collect();
+        let emb = visage_core::Embedding::new(values, Some(ARCFACE_MODEL_VERSION.into()));
+        let store = service.state.lock().await.store.clone();
+        store
+            .insert("alice", "default", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        service
+    }
+
+    #[tokio::test]
+    async fn test_identify_names_the_matching_user_to_root_only() {
+        let service = service_verifying_alice_at_half().await;
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+
+        // On the system bus a peer connection has no sender to check, and
+        // any caller but root is turned away.
+        let (_, message) = error_name(&client, "Identify", &()).await;
+        assert!(message.contains("no sender"), "{message}");
+        let err = check_root("Identify", BusAccess::System, 1000).unwrap_err();
+        assert!(
+            matches!(&err, zbus::fdo::Error::AccessDenied(m) if m.contains("Identify")),
+            "{err:?}"
+        );
+        assert!(check_root("Identify", BusAccess::System, 0).is_ok());
+
+        state.lock().await.config.session_bus = true;
+        let identify = || async {
+            client
+                .call_method(
+                    None::<()>,
+                    "/org/freedesktop/Visage1",
+                    Some("org.freedesktop.Visage1"),
+                    "Identify",
+                    &(),
+                )
+                .await
+                .unwrap()
+                .body()
+                .deserialize::<String>()
+                .unwrap()
+        };
+        assert_eq!(identify().await, "alice");
+
+        // A locked-out user is not matched against.
+        {
+            let mut state = state.lock().await;
+            while state.rate_limiter.check("alice").is_ok() {
+                state.rate_limiter.record_failure("alice");
+            }
+        }
+        assert_eq!(identify().await, "");
+    }
+
+    #[tokio::test]
+    async fn test_remove_all_models_clears_one_user_and_their_lockout() {
+        let config = Config::from_pairs(&[("VISAGE_SESSION_BUS", "1")]).unwrap();
+        let service = service(config).await;
+        let state = service.state.clone();
+        let store = state.lock().await.store.clone();
+        let emb = visage_core::Embedding::new(vec![0.5; 512], Some(ARCFACE_MODEL_VERSION.into()));
+        for (user, label) in [("alice", "desk"), ("alice", "glasses"), ("bob", "desk")] {
+            store
+                .insert(user, label, &emb, 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+        {
+            let mut state = state.lock().await;
+            while state.rate_limiter.check("alice").is_ok() {
+                state.rate_limiter.record_failure("alice");
+            }
+        }
+        let (_server, client) = serve_p2p(service).await;
+        let remove_all = |user: &'static str| {
+            let client = client.clone();
+            async move {
+                let reply = client
+                    .call_method(
+                        None::<()>,
+                        "/org/freedesktop/Visage1",
+                        Some("org.freedesktop.Visage1"),
+                        "RemoveAllModels",
+                        &(user,),
+                    )
+                    .await
+                    .unwrap();
+                reply.body().deserialize::<u32>().unwrap()
+            }
+        };
+
+        assert_eq!(remove_all("alice").await, 2);
+        assert!(store.list_by_user("alice").await.unwrap().is_empty());
+        assert_eq!(store.list_by_user("bob").await.unwrap().len(), 1);
+        assert!(state.lock().await.rate_limiter.check("alice").is_ok());
+        // A user with nothing enrolled is not an error.
+        assert_eq!(remove_all("alice").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_set_model_label_renames_only_the_users_own_model() {
+        let config = Config::from_pairs(&[("VISAGE_SESSION_BUS", "1")]).unwrap();
+        let service = service(config).await;
+        let store = service.state.lock().await.store.clone();
+        let emb = visage_core::Embedding::new(vec![0.5; 512], Some(ARCFACE_MODEL_VERSION.into()));
+        let mut ids = Vec::new();
+        for (user, label) in [("alice", "default"), ("alice", "desk"), ("bob", "default")] {
+            ids.push(
+                store
+                    .insert(user, label, &emb, 0.9, EnrollMeta::default())
+                    .await
+                    .unwrap(),
+            );
+        }
+        let (_server, client) = serve_p2p(service).await;
+
+        let invalid = "org.freedesktop.Visage1.Error.InvalidArgs";
+        let too_long = "x".repeat(MAX_LABEL_LEN + 1);
+        for label in ["", "  ", too_long.as_str(), "desk"] {
+            let (name, message) =
+                error_name(&client, "SetModelLabel", &("alice", ids[0].as_str(), label)).await;
+            assert_eq!(name, invalid, "{label:?}: {message}");
+        }
+
+        let rename = |user: &'static str, id: String| {
+            let client = client.clone();
+            async move {
+                client
+                    .call_method(
+                        None::<()>,
+                        "/org/freedesktop/Visage1",
+                        Some("org.freedesktop.Visage1"),
+                        "SetModelLabel",
+                        &(user, id.as_str(), "with-glasses"),
+                    )
+                    .await
+                    .unwrap()
+                    .body()
+                    .deserialize::<bool>()
+                    .unwrap()
+            }
+        };
+        // Bob's model is out of reach under Alice's name.
+        assert!(!rename("alice", ids[2].clone()).await);
+        assert_eq!(store.list_by_user("bob").await.unwrap()[0].label, "default");
+
+        assert!(rename("alice", ids[0].clone()).await);
+        let labels: Vec<_> = store
+            .list_by_user("alice")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.label)
+            .collect();
+        assert!(labels.contains(&"with-glasses".to_string()), "{labels:?}");
+        // Renaming a model to its own label is not a clash.
+        assert!(rename("alice", ids[0].clone()).await);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_user_stops_captures_without_counting_a_failure() {
+        let service = service_verifying_alice_at_half().await;
+        let state = service.state.clone();
+        {
+            let mut state = state.lock().await;
+            state.config.session_bus = true;
+            state.config.frames_per_verify = 10_000;
+            state.config.frames_per_enroll = 10_000;
+            state.engine = EngineHandle::stalling(20);
+            // One more failure would lock Alice out.
+            for _ in 0..4 {
+                state.rate_limiter.record_failure("alice");
+            }
+        }
+        let (_server, client) = serve_p2p(service).await;
+        // Cancel once the capture has registered, answering how long it took.
+        let cancel_alice = || async {
+            loop {
+                let reply = client
+                    .call_method(
+                        None::<()>,
+                        "/org/freedesktop/Visage1",
+                        Some("org.freedesktop.Visage1"),
+                        "CancelUser",
+                        &("alice",),
+                    )
+                    .await
+                    .unwrap();
+                if reply.body().deserialize::<u32>().unwrap() > 0 {
+                    return std::time::Instant::now();
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+
+        let ((name, _), cancelled_at) =
+            tokio::join!(error_name(&client, "Verify", &("alice",)), cancel_alice());
+        assert_eq!(name, "org.freedesktop.Visage1.Error.Cancelled");
+        assert!(cancelled_at.elapsed() < std::time::Duration::from_millis(500));
+        assert!(state.lock().await.rate_limiter.check("alice").is_ok());
+
+        let ((name, _), _) = tokio::join!(
+            error_name(&client, "Enroll", &("alice", "desk")),
+            cancel_alice()
+        );
+        assert_eq!(name, "org.freedesktop.Visage1.Error.Cancelled");
+        let store = state.lock().await.store.clone();
+        assert_eq!(store.list_by_user("alice").await.unwrap().len(), 1);
+
+        // With nothing in flight there is nothing to cancel.
+        let reply = client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "CancelUser",
+                &("alice",),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.body().deserialize::<u32>().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_verify_fresh_captures_only_without_a_recent_match() {
+        let service = service_verifying_alice_at_half().await;
+        let state = service.state.clone();
+        let (server, _client) = serve_p2p(service).await;
+        // A peer connection has no logind session to look up, so drive the
+        // served object with one directly.
+        let iface = server
+            .object_server()
+            .interface::<_, VisageService>("/org/freedesktop/Visage1")
+            .await
+            .unwrap();
+        let emitter = iface.signal_emitter().clone();
+        let verify_fresh = |session: &'static str, max_age_secs: u64| {
+            let iface = iface.clone();
+            let emitter = emitter.clone();
+            async move {
+                let max_age = std::time::Duration::from_secs(max_age_secs);
+                let started = std::time::Instant::now();
+                iface
+                    .get()
+                    .await
+                    .fresh_or_capture("alice", Some(session), max_age, ":1.1", started, &emitter)
+                    .await
+            }
+        };
+        let tty1 = "/org/freedesktop/login1/session/_31";
+        let tty2 = "/org/freedesktop/login1/session/_32";
+
+        // Nothing cached yet, so the camera decides.
+        assert!(verify_fresh(tty1, 60).await.unwrap());
+
+        // With the engine gone only the cached match can answer, and only
+        // in the session that made it.
+        state.lock().await.engine = EngineHandle::disconnected();
+        assert!(verify_fresh(tty1, 60).await.unwrap());
+        assert!(verify_fresh(tty2, 60).await.is_err());
+        // That failed capture forgot the match for every session.
+        assert!(verify_fresh(tty1, 60).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_detailed_replies_with_the_match_as_json() {
+        let service = service_verifying_alice_at_half().await;
+        service.state.lock().await.config.session_bus = true;
+        let (_server, client) = serve_p2p(service).await;
+        let call = |method: &'static str| {
+            let client = client.clone();
+            async move {
+                client
+                    .call_method(
+                        None::<()>,
+                        "/org/freedesktop/Visage1",
+                        Some("org.freedesktop.Visage1"),
+                        method,
+                        &("alice",),
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let reply = call("VerifyDetailed").await;
+        let json: String = reply.body().deserialize().unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["matched"], true);
+        let similarity = report["similarity"].as_f64().unwrap();
+        assert!((similarity - 0.5).abs() < 1e-3, "{report}");
+        assert!(report["model_id"].as_str().is_some_and(|id| !id.is_empty()));
+        assert_eq!(report["model_label"], "default");
+        assert!(report["failure_reason"].is_null());
+
+        // The same match as typed values.
+        let reply = call("VerifyScore").await;
+        assert_eq!(reply.body().signature().to_string(), "(bdsss)");
+        let (matched, similarity, model_id, label, reason): (bool, f64, String, String, String) =
+            reply.body().deserialize().unwrap();
+        assert!(matched);
+        assert!((similarity - 0.5).abs() < 1e-3, "{similarity}");
+        assert_eq!(model_id, report["model_id"].as_str().unwrap());
+        assert_eq!(label, "default");
+        assert_eq!(reason, "");
+
+        // The plain method keeps its boolean reply for pam_visage.
+        let matched: bool = call("Verify").await.body().deserialize().unwrap();
+        assert!(matched);
+    }
+
+    #[tokio::test]
+    async fn test_set_policy_applies_to_the_next_verify() {
+        let service = service_verifying_alice_at_half().await;
+        let verify =
+            || service.verify_canonical("alice", None, ":1.1", std::time::Instant::now(), None);
+        assert!(verify().await.unwrap().0.result.matched);
+
+        let policy = service
+            .update_policy(r#"{"similarity_threshold": 0.6}"#)
+            .await
+            .unwrap();
+        assert_eq!(policy.similarity_threshold, 0.6);
+        assert!(!policy.liveness_enabled);
+        assert!(!verify().await.unwrap().0.result.matched);
+
+        // A rejected update leaves the policy as it was.
+        let err = service
+            .update_policy(r#"{"similarity_threshold": 0.3, "centroid_margin": 2.0}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisageError::InvalidArgs(_)), "{err:?}");
+        assert_eq!(
+            VerifyPolicy::from_config(&service.state.lock().await.config),
+            policy
+        );
+    }
+
+    /// Call `method` of `org.freedesktop.DBus.Properties` on the service.
+    async fn properties_call<B>(
+        client: &zbus::Connection,
+        method: &str,
+        body: &B,
+    ) -> zbus::Result<zbus::Message>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.DBus.Properties"),
+                method,
+                body,
+            )
+            .await
+    }
+
+    async fn get_property<T>(client: &zbus::Connection, name: &str) -> T
+    where
+        T: TryFrom<zbus::zvariant::OwnedValue>,
+        T::Error: std::fmt::Debug,
+    {
+        let reply = properties_call(client, "Get", &("org.freedesktop.Visage1", name))
+            .await
+            .unwrap();
+        let value: zbus::zvariant::OwnedValue = reply.body().deserialize().unwrap();
+        T::try_from(value).unwrap()
+    }
+
+    async fn set_property<'v>(
+        client: &zbus::Connection,
+        name: &str,
+        value: impl Into<zbus::zvariant::Value<'v>>,
+    ) -> zbus::Result<zbus::Message> {
+        let value = value.into();
+        properties_call(client, "Set", &("org.freedesktop.Visage1", name, &value)).await
+    }
+
+    #[tokio::test]
+    async fn test_policy_properties_round_trip_and_are_range_checked() {
+        let path = std::env::temp_dir().join(format!("visaged-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "# site settings\nsimilarity_threshold = 0.5\n").unwrap();
+        let service = service(Config::from_pairs(&[("VISAGE_SESSION_BUS", "1")]).unwrap()).await;
+        service.state.lock().await.config.config_file = Some(path.clone());
+        let state = service.state.clone();
+        let (_server, client) = serve_p2p(service).await;
+
+        set_property(&client, "SimilarityThreshold", 0.6f64)
+            .await
+            .unwrap();
+        set_property(&client, "LivenessEnabled", false)
+            .await
+            .unwrap();
+        set_property(&client, "FramesPerVerify", 12u32)
+            .await
+            .unwrap();
+        let threshold: f64 = get_property(&client, "SimilarityThreshold").await;
+        assert!((threshold - 0.6).abs() < 1e-6, "{threshold}");
+        assert!(!get_property::<bool>(&client, "LivenessEnabled").await);
+        assert_eq!(get_property::<u32>(&client, "FramesPerVerify").await, 12);
+        assert_eq!(state.lock().await.config.frames_per_verify, 12);
+
+        // Out-of-range values are refused and change nothing.
+        for (name, value) in [
+            ("SimilarityThreshold", zbus::zvariant::Value::from(1.5f64)),
+            ("FramesPerVerify", zbus::zvariant::Value::from(0u32)),
+            ("FramesPerVerify", zbus::zvariant::Value::from(31u32)),
+        ] {
+            let err = set_property(&client, name, value).await.unwrap_err();
+            assert!(
+                matches!(&err, zbus::Error::MethodError(n, _, _)
+                    if n.as_str() == "org.freedesktop.DBus.Error.InvalidArgs"),
+                "{name}: {err:?}"
+            );
+        }
+        assert_eq!(get_property::<u32>(&client, "FramesPerVerify").await, 12);
+
+        // The changes survive a restart through the config file.
+        let saved = Config::from_file(&path).unwrap();
+        assert!((saved.similarity_threshold - 0.6).abs() < 1e-6);
+        assert!(!saved.liveness_enabled);
+        assert_eq!(saved.frames_per_verify, 12);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# site settings\n"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_policy_properties_are_readable_by_all_but_set_by_root() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let (_server, client) = serve_p2p(service).await;
+
+        assert_eq!(get_property::<u32>(&client, "FramesPerVerify").await, 3);
+        // Peer-to-peer messages have no sender to resolve to root.
+        let err = set_property(&client, "FramesPerVerify", 8u32)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, zbus::Error::MethodError(_, Some(m), _) if m.contains("no sender")),
+            "{err:?}"
+        );
+        let err = check_root("Setting FramesPerVerify", BusAccess::System, 1000).unwrap_err();
+        assert!(
+            matches!(&err, zbus::fdo::Error::AccessDenied(m) if m.contains("FramesPerVerify")),
+            "{err:?}"
+        );
+        assert_eq!(get_property::<u32>(&client, "FramesPerVerify").await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_policy_changes_emit_properties_changed() {
+        use futures_lite::StreamExt;
+
+        let service = service(Config::from_pairs(&[("VISAGE_SESSION_BUS", "1")]).unwrap()).await;
+        let (_server, client) = serve_p2p(service).await;
+        let mut messages = zbus::MessageStream::from(&client);
+
+        set_property(&client, "FramesPerVerify", 9u32)
+            .await
+            .unwrap();
+        client
+            .call_method(
+                None::<()>,
+                "/org/freedesktop/Visage1",
+                Some("org.freedesktop.Visage1"),
+                "SetPolicy",
+                &r#"{"similarity_threshold": 0.55}"#,
+            )
+            .await
+            .unwrap();
+
+        let mut changed = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.unwrap();
+            if message.header().member().map(|m| m.as_str()) != Some("PropertiesChanged") {
+                continue;
+            }
+            let (_, properties, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize().unwrap();
+            changed.extend(properties.into_keys());
+            if changed.len() == 2 {
+                break;
+            }
+        }
+        assert_eq!(changed, ["FramesPerVerify", "SimilarityThreshold"]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_emits_properties_changed() {
+        use futures_lite::StreamExt;
+
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let (server, client) = serve_p2p(service).await;
+        let mut messages = zbus::MessageStream::from(&client);
+
+        let applied = [
+            "verify_timeout_secs",
+            "similarity_threshold",
+            "frames_per_verify",
+            "liveness_enabled",
+        ];
+        VisageService::announce_reload(&server, &applied)
+            .await
+            .unwrap();
+
+        let mut changed = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.unwrap();
+            if message.header().member().map(|m| m.as_str()) != Some("PropertiesChanged") {
+                continue;
+            }
+            let (_, properties, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize().unwrap();
+            changed.extend(properties.into_keys());
+            if changed.len() == 3 {
+                break;
+            }
+        }
+        assert_eq!(
+            changed,
+            ["SimilarityThreshold", "FramesPerVerify", "LivenessEnabled"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_settings_are_stored_and_applied() {
+        let service = service(Config::from_pairs(&[]).unwrap()).await;
+        let docked = "usb:046d:085e.2";
+        let settings = service
+            .update_device_settings(docked, r#"{"liveness_min_displacement": 0.9}"#)
+            .await
+            .unwrap();
+        assert_eq!(settings.liveness_min_displacement, Some(0.9));
+        let state = service.state.lock().await;
+        assert_eq!(state.config.liveness_min_displacement, 0.9);
+        assert_eq!(
+            state.store.get_device_settings(docked).await.unwrap(),
+            settings
+        );
+        drop(state);
+
+        let err = service
+            .update_device_settings(docked, r#"{"liveness_min_displacement": -1}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisageError::InvalidArgs(_)), "{err:?}");
+        assert_eq!(
+            service.state.lock().await.config.liveness_min_displacement,
+            0.9
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_request_limits_refuse_a_flood_but_not_a_concurrent_verifier() {
+        let service = Arc::new(service_verifying_alice_at_half().await);
+        let flood = {
+            let service = service.clone();
+            tokio::spawn(async move {
+                let call = call_from(":1.66");
+                let mut refused = 0u64;
+                for _ in 0..2000 {
+                    match service.status(call.header()).await {
+                        Err(VisageError::TooManyRequests(_)) => refused += 1,
+                        other => drop(other.unwrap()),
+                    }
+                }
+                refused
+            })
+        };
+
+        let call = call_from(":1.7");
+        for _ in 0..3 {
+            let _request = service
+                .admit(&call.header(), RequestClass::Capture)
+                .unwrap();
+            let (result, _) = service
+                .verify_canonical("alice", None, ":1.7", std::time::Instant::now(), None)
+                .await
+                .unwrap();
+            assert!(result.result.matched);
+        }
+
+        let refused = flood.await.unwrap();
+        assert!(refused > 1000, "only {refused} of 2000 refused");
+        service.status(call.header()).await.unwrap();
+        let stats: serde_json::Value =
+            serde_json::from_str(&service.stats(call.header()).await.unwrap()).unwrap();
+        assert_eq!(stats["requests"]["refused_query_rate"], refused);
+        assert_eq!(stats["requests"]["refused_capture_rate"], 0);
+        assert_eq!(stats["requests"]["in_flight"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_outcome_feeds_the_grace_cache() {
+        let service = service_verifying_alice_at_half().await;
+        let minute = std::time::Duration::from_secs(60);
+        let state = service.state.clone();
+        let tty1 = "/org/freedesktop/login1/session/_31";
+        let tty2 = "/org/freedesktop/login1/session/_32";
+        let fresh = |session| {
+            let state = state.clone();
+            async move {
+                state
+                    .lock()
+                    .await
+                    .verify_grace
+                    .is_fresh("alice", session, minute)
+            }
+        };
+
+        // Root callers in two terminals: the match in one does not answer
+        // for the other.
+        let (result, _) = service
+            .verify_canonical("alice", Some(tty1), ":1.1", std::time::Instant::now(), None)
+            .await
+            .unwrap();
+        assert!(result.result.matched);
+        assert!(fresh(tty1).await);
+        assert!(!fresh(tty2).await);
+
+        // The next verify fails: the earlier match no longer counts.
+        service.state.lock().await.config.similarity_threshold = 0.6;
+        let (result, _) = service
+            .verify_canonical("alice", Some(tty2), ":1.2", std::time::Instant::now(), None)
+            .await
+            .unwrap();
+        assert!(!result.result.matched);
+        assert!(!fresh(tty1).await);
     }
 }
@@ -0,0 +1,136 @@
+//! Per-camera settings behind `GetDeviceSettings` / `SetDeviceSettings`.
+//!
+//! Sensors differ enough that a liveness threshold calibrated on a docked
+//! webcam is wrong for the built-in IR camera. Settings are stored per
+//! camera identity (`usb:VVVV:PPPP.I`) in the face store, and the daemon
+//! applies the ones for `VISAGE_CAMERA_DEVICE` at startup, over the
+//! configured defaults. A field left `null` keeps the configured value.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::store::{FaceModelStore, StoreError};
+
+#[derive(Error, Debug)]
+pub enum DeviceSettingsError {
+    #[error("invalid device settings: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("liveness_min_displacement {0} must be a non-negative number of pixels")]
+    InvalidDisplacement(f32),
+}
+
+/// Overrides stored for one camera.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceSettings {
+    /// Calibrated liveness landmark displacement threshold, in pixels.
+    pub liveness_min_displacement: Option<f32>,
+}
+
+impl DeviceSettings {
+    /// These settings with the fields of the JSON object `update` replaced;
+    /// `null` clears a field. Unknown fields and invalid values are rejected.
+    pub fn merged(&self, update: &str) -> Result<Self, DeviceSettingsError> {
+        let update: serde_json::Map<String, serde_json::Value> = serde_json::from_str(update)?;
+        let mut merged = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(fields) = &mut merged {
+            fields.extend(update);
+        }
+        let merged: Self = serde_json::from_value(merged)?;
+        if let Some(px) = merged.liveness_min_displacement {
+            if px < 0.0 || !px.is_finite() {
+                return Err(DeviceSettingsError::InvalidDisplacement(px));
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Override the fields of `config` these settings name.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(px) = self.liveness_min_displacement {
+            config.liveness_min_displacement = px;
+        }
+    }
+}
+
+/// Apply the settings stored for the camera `device_id` to `config`, as the
+/// daemon does at startup. Returns what was stored.
+pub async fn apply_stored(
+    config: &mut Config,
+    store: &FaceModelStore,
+    device_id: &str,
+) -> Result<DeviceSettings, StoreError> {
+    let settings = store.get_device_settings(device_id).await?;
+    if let Some(px) = settings.liveness_min_displacement {
+        tracing::info!(
+            device_id,
+            configured = config.liveness_min_displacement,
+            stored = px,
+            "using liveness displacement stored for this camera"
+        );
+    }
+    settings.apply(config);
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_stored_settings_override_config_for_matching_device() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let stored = DeviceSettings {
+            liveness_min_displacement: Some(1.7),
+        };
+        store
+            .set_device_settings("usb:046d:085e.2", &stored)
+            .await
+            .unwrap();
+        let defaults = Config::from_pairs(&[]).unwrap();
+        assert_ne!(defaults.liveness_min_displacement, 1.7);
+
+        let mut config = defaults.clone();
+        let applied = apply_stored(&mut config, &store, "usb:046d:085e.2")
+            .await
+            .unwrap();
+        assert_eq!(applied, stored);
+        assert_eq!(config.liveness_min_displacement, 1.7);
+
+        // Another camera keeps the configured value.
+        let mut config = defaults.clone();
+        apply_stored(&mut config, &store, "usb:04f2:b6d9.2")
+            .await
+            .unwrap();
+        assert_eq!(
+            config.liveness_min_displacement,
+            defaults.liveness_min_displacement
+        );
+    }
+
+    #[test]
+    fn test_update_merges_and_validates() {
+        let current = DeviceSettings {
+            liveness_min_displacement: Some(0.8),
+        };
+        assert_eq!(current.merged("{}").unwrap(), current);
+        assert_eq!(
+            current
+                .merged(r#"{"liveness_min_displacement": null}"#)
+                .unwrap(),
+            DeviceSettings::default()
+        );
+        assert!(matches!(
+            current.merged(r#"{"liveness_min_displacement": -0.5}"#),
+            Err(DeviceSettingsError::InvalidDisplacement(_))
+        ));
+        assert!(matches!(
+            current.merged(r#"{"warmup_frames": 3}"#),
+            Err(DeviceSettingsError::Parse(_))
+        ));
+    }
+}
@@ -1,455 +1,4528 @@
+use std::cell::Cell;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::{mpsc, oneshot};
-use visage_core::{check_landmark_stability, CosineMatcher, Embedding, FaceModel, MatchResult, Matcher};
-use visage_hw::{Camera, IrEmitter};
+use tokio::sync::{mpsc, oneshot, watch};
+use visage_core::alignment::{alignment_quality, classify_pose, Pose};
+use visage_core::recognizer::{RecognizerError, ARCFACE_EMBEDDING_DIM};
+use visage_core::{
+    check_timed_landmark_liveness, BoundingBox, CentroidMatcher, CosineMatcher, Embedding,
+    FaceModel, LivenessPolicy, MatchResult, Matcher,
+};
+use visage_hw::{Camera, CameraError, CaptureContext, ExposureStats, Frame, IrEmitter};
 
 #[derive(Error, Debug)]
 pub enum EngineError {
     #[error("camera error: {0}")]
     Camera(#[from] visage_hw::CameraError),
+    #[error("{0}")]
+    ModelFile(#[from] visage_models::ModelIntegrityError),
     #[error("detector error: {0}")]
     Detector(#[from] visage_core::detector::DetectorError),
     #[error("recognizer error: {0}")]
     Recognizer(#[from] visage_core::recognizer::RecognizerError),
     #[error("no face detected in any captured frame")]
     NoFaceDetected,
-    #[error("liveness check failed: landmark displacement {displacement:.3} px < threshold {threshold:.3} px")]
+    #[error("recognizer unavailable ({0}) — run `sudo visage setup` to restore models")]
+    RecognizerUnavailable(String),
+    #[error("face alignment too poor (best {best:.2}, need {min:.2}) — face the camera squarely")]
+    PoorAlignment { best: f32, min: f32 },
+    #[error(
+        "liveness check failed: landmark displacement {displacement:.3} px (threshold \
+         {threshold:.3} px), {live_pairs} live frame pairs (need {min_live_pairs})"
+    )]
     LivenessCheckFailed {
         displacement: f32,
         threshold: f32,
+        live_pairs: usize,
+        min_live_pairs: usize,
     },
+    #[error(
+        "face changed during capture (similarity {similarity:.2} between frames, need \
+         {floor:.2}) — only one person should face the camera"
+    )]
+    FaceChangedDuringCapture { similarity: f32, floor: f32 },
     #[error("verification timed out")]
     VerifyTimeout,
+    #[error("enrollment timed out")]
+    EnrollTimeout,
+    #[error("engine stopped responding and was restarted; retry the request")]
+    EngineRestarted,
+    #[error("operation cancelled by an administrator")]
+    Cancelled,
+    #[error("cancelled by the client")]
+    ClientCancelled,
+    #[error("model reload failed, previous models kept: {0}")]
+    ReloadFailed(String),
     #[error("engine thread exited")]
     ChannelClosed,
 }
 
+/// How far an enrollment has got, reported after each frame the engine
+/// processes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnrollProgress {
+    pub frames_captured: usize,
+    pub frames_total: usize,
+    /// Detector confidence of the best usable face so far; 0 until one is
+    /// found.
+    pub quality: f32,
+}
+
+/// Where the engine sends [`EnrollProgress`] updates.
+pub type ProgressSender = mpsc::UnboundedSender<EnrollProgress>;
+
+/// How far a verify has got, reported after each frame the engine matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyProgress {
+    pub frames_done: usize,
+    pub frames_total: usize,
+}
+
+/// Where the engine sends [`VerifyProgress`] updates.
+pub type VerifyProgressSender = mpsc::UnboundedSender<VerifyProgress>;
+
 /// Result of an enrollment operation.
 pub struct EnrollResult {
     pub embedding: Embedding,
     pub quality_score: f32,
+    /// Landmark alignment quality of the best frame (see `alignment_quality`).
+    pub alignment_score: f32,
+    /// Perceptual hash of the best frame's aligned crop, when requested.
+    pub crop_hash: Option<u64>,
+    /// Head pose of the best frame, when it had landmarks.
+    pub pose: Option<Pose>,
+    /// Camera the frames came from, when the source reports it.
+    pub device_id: Option<String>,
+    /// Average raw brightness of the frames that yielded an embedding.
+    pub brightness: f32,
+    /// Usable frames captured.
+    pub frames_captured: usize,
+    /// Frames rejected as dark during capture.
+    pub dark_skipped: usize,
+    /// Peak frame buffers held while processing.
+    pub frame_memory: FrameMemory,
+}
+
+/// Frames captured for a preview, kept so a later enroll sees exactly them.
+pub struct PreviewCapture {
+    /// Usable frames, in capture order.
+    pub frames: Vec<Frame>,
+    /// Frames rejected as dark during capture.
+    pub dark_skipped: usize,
+    /// Camera the frames came from, when the source reports it.
+    pub device_id: Option<String>,
+}
+
+/// Outcome of a detection-only camera check (see [`EngineHandle::self_test`]).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SelfTestReport {
+    /// Raw frames the camera delivered.
+    pub frames: usize,
+    /// Faces the detector found across all frames; zero is not a failure.
+    pub faces_detected: usize,
+    /// Average pixel brightness of the raw frames (0.0–255.0).
+    pub mean_brightness: f32,
+    /// Whether the IR emitter was lit for the capture.
+    pub emitter_used: bool,
+}
+
+/// Frame delivery measured by [`EngineHandle::camera_benchmark`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct CameraBenchmark {
+    /// Frames asked for.
+    pub frames_requested: usize,
+    /// Usable frames delivered before the deadline.
+    pub frames: usize,
+    /// Frames the dark filter discarded.
+    pub dark_frames: usize,
+    /// Frames missing from the sequence numbers that the dark filter does
+    /// not account for: lost by the driver or the USB link.
+    pub dropped_frames: u64,
+    /// Time from starting the capture to the first usable frame, which
+    /// includes opening and starting the stream.
+    pub first_frame_ms: f64,
+    /// Usable frames per second from the first frame to the last.
+    pub fps: f64,
+    /// Mean interval between consecutive usable frames.
+    pub mean_interval_ms: f64,
+    /// Standard deviation of those intervals.
+    pub jitter_ms: f64,
+    /// Longest interval between consecutive usable frames.
+    pub max_interval_ms: f64,
+    /// Whether the IR emitter was lit for the capture.
+    pub emitter_active: bool,
+}
+
+impl CameraBenchmark {
+    /// Summarize the arrival time and sequence number of each usable frame
+    /// of a capture that began at `started`.
+    fn measure(
+        frames_requested: usize,
+        started: Instant,
+        arrivals: &[(Instant, u32)],
+        dark_frames: usize,
+        emitter_active: bool,
+    ) -> Self {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let intervals: Vec<f64> = arrivals
+            .windows(2)
+            .map(|pair| ms(pair[1].0.saturating_duration_since(pair[0].0)))
+            .collect();
+        let gaps: u64 = arrivals
+            .windows(2)
+            .map(|pair| u64::from(pair[1].1.wrapping_sub(pair[0].1).saturating_sub(1)))
+            .sum();
+        let (mean, jitter, max) = if intervals.is_empty() {
+            (0.0, 0.0, 0.0)
+        } else {
+            let n = intervals.len() as f64;
+            let mean = intervals.iter().sum::<f64>() / n;
+            let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+            let max = intervals.iter().copied().fold(0.0, f64::max);
+            (mean, variance.sqrt(), max)
+        };
+        Self {
+            frames_requested,
+            frames: arrivals.len(),
+            dark_frames,
+            dropped_frames: gaps.saturating_sub(dark_frames as u64),
+            first_frame_ms: arrivals
+                .first()
+                .map_or(0.0, |(at, _)| ms(at.saturating_duration_since(started))),
+            fps: if mean > 0.0 { 1000.0 / mean } else { 0.0 },
+            mean_interval_ms: mean,
+            jitter_ms: jitter,
+            max_interval_ms: max,
+            emitter_active,
+        }
+    }
 }
 
 /// Result of a verification operation.
 pub struct VerifyResult {
     pub result: MatchResult,
-    /// Reserved for v3: surface capture quality metadata to callers without a schema change.
-    #[allow(dead_code)]
+    /// Detection confidence of the face that produced the best match.
     pub best_quality: f32,
+    /// Landmark alignment quality of the face that produced the best match.
+    pub best_alignment: f32,
+    /// Usable frames captured.
+    pub frames_captured: usize,
+    /// Captured frames in which the detector found a face.
+    pub frames_with_face: usize,
+    /// Frames rejected as dark during capture.
+    pub dark_skipped: usize,
+    /// Time from the engine picking up the request to its result.
+    pub elapsed: Duration,
+    /// Peak frame buffers held while processing.
+    pub frame_memory: FrameMemory,
+    /// Camera and processing the frames came from, when the source reports it.
+    pub capture: Option<CaptureContext>,
+    /// The result was rejected by the user's centroid alone, without
+    /// comparing against individual templates.
+    pub fast_path: bool,
+    /// The best-matching template was enrolled in a different pose than the
+    /// live face shows.
+    pub pose_mismatch: Option<PoseMismatch>,
+    /// Why a face the matcher accepted was turned down; set by the D-Bus
+    /// layer, which applies those checks.
+    pub rejection: Option<Rejection>,
+}
+
+/// A check that overrode the matcher's verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The face matched but did not pass the liveness check.
+    Liveness,
+    /// `VISAGE_VERIFY_REQUIRE_POSE_MATCH`: the template's pose differs.
+    PoseMismatch,
+    /// `VISAGE_VERIFY_REQUIRE_ENROLLED_DEVICE`: no template from this camera.
+    ForeignDevice,
+}
+
+impl Rejection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Liveness => "liveness",
+            Self::PoseMismatch => "pose_mismatch",
+            Self::ForeignDevice => "foreign_device",
+        }
+    }
+}
+
+/// Result of a 1:N identification (see [`EngineHandle::identify`]).
+#[derive(Debug)]
+pub struct IdentifyResult {
+    /// Owner of the best-matching template, when it cleared the threshold.
+    pub user: Option<String>,
+    /// Cosine similarity of the best match across all users.
+    pub similarity: f32,
+    /// Usable frames captured.
+    pub frames_captured: usize,
+    /// Time from the engine picking up the request to its result.
+    pub elapsed: Duration,
+}
+
+/// Live head pose versus the pose label of the best-matching template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoseMismatch {
+    pub live: Pose,
+    pub template: Pose,
+}
+
+/// Centroid fast path for verify: frames whose similarity to the user's
+/// centroid template is at least `margin` below what any template match
+/// allows (see [`CentroidMatcher`]) are rejected without a full gallery pass.
+#[derive(Debug, Clone)]
+pub struct CentroidGate {
+    pub centroid: Embedding,
+    pub margin: f32,
+    /// Smallest template-to-centroid similarity (`UserCentroid::spread`).
+    pub spread: f32,
+}
+
+/// Detailed outcome of matching one supplied frame (see `VerifyFrame`).
+#[derive(Debug, serde::Serialize)]
+pub struct FrameVerifyReport {
+    pub faces_detected: usize,
+    /// The face that was matched (the detector's top candidate).
+    pub face: Option<BoundingBox>,
+    pub alignment_score: Option<f32>,
+    pub matched: bool,
+    /// Best similarity across the gallery (0.0 when no face was found).
+    pub similarity: f32,
+    pub threshold: f32,
+    pub model_id: Option<String>,
+    pub model_label: Option<String>,
+    /// Similarity against every gallery model, in gallery order.
+    pub similarities: Vec<ModelSimilarity>,
+}
+
+/// Similarity of a probe against one gallery model.
+#[derive(Debug, serde::Serialize)]
+pub struct ModelSimilarity {
+    pub model_id: String,
+    pub label: String,
+    pub similarity: f32,
 }
 
 /// Messages sent from D-Bus handlers to the engine thread.
 enum EngineRequest {
     Enroll {
         frames_count: usize,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: CancelToken,
+        reply: oneshot::Sender<Result<EnrollResult, EngineError>>,
+    },
+    EnrollFrames {
+        preview: PreviewCapture,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: CancelToken,
         reply: oneshot::Sender<Result<EnrollResult, EngineError>>,
     },
+    Preview {
+        frames_count: usize,
+        timeout: Duration,
+        reply: oneshot::Sender<Result<PreviewCapture, EngineError>>,
+    },
     Verify {
         gallery: Vec<FaceModel>,
+        centroid: Option<CentroidGate>,
         threshold: f32,
         frames_count: usize,
-        timeout: std::time::Duration,
-        liveness_enabled: bool,
-        liveness_min_displacement: f32,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        consistency_floor: Option<f32>,
+        lighting_tolerance: Option<f32>,
+        progress: Option<VerifyProgressSender>,
+        cancel: CancelToken,
         reply: oneshot::Sender<Result<VerifyResult, EngineError>>,
     },
+    Identify {
+        gallery: Vec<FaceModel>,
+        threshold: f32,
+        frames_count: usize,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        cancel: CancelToken,
+        reply: oneshot::Sender<Result<IdentifyResult, EngineError>>,
+    },
+    VerifyFrame {
+        frame: Frame,
+        gallery: Vec<FaceModel>,
+        threshold: f32,
+        reply: oneshot::Sender<Result<FrameVerifyReport, EngineError>>,
+    },
+    ExposureReport {
+        frames_count: usize,
+        timeout: Duration,
+        reply: oneshot::Sender<Result<ExposureStats, EngineError>>,
+    },
+    CameraBenchmark {
+        frames_count: usize,
+        timeout: Duration,
+        reply: oneshot::Sender<Result<CameraBenchmark, EngineError>>,
+    },
+    SelfTest {
+        frames_count: usize,
+        timeout: Duration,
+        use_emitter: bool,
+        reply: oneshot::Sender<Result<SelfTestReport, EngineError>>,
+    },
+    ReloadModels {
+        reply: oneshot::Sender<Result<(), EngineError>>,
+    },
 }
 
-/// Clone-safe handle to the engine thread.
-#[derive(Clone)]
-pub struct EngineHandle {
-    tx: mpsc::Sender<EngineRequest>,
+impl EngineRequest {
+    /// Short name of the operation, as shown by `EngineQueueStatus`.
+    fn operation(&self) -> &'static str {
+        match self {
+            EngineRequest::Enroll { .. } => "enroll",
+            EngineRequest::EnrollFrames { .. } => "enroll_frames",
+            EngineRequest::Preview { .. } => "preview",
+            EngineRequest::Verify { .. } => "verify",
+            EngineRequest::Identify { .. } => "identify",
+            EngineRequest::VerifyFrame { .. } => "verify_frame",
+            EngineRequest::ExposureReport { .. } => "exposure_report",
+            EngineRequest::CameraBenchmark { .. } => "camera_benchmark",
+            EngineRequest::SelfTest { .. } => "self_test",
+            EngineRequest::ReloadModels { .. } => "reload_models",
+        }
+    }
 }
 
-impl EngineHandle {
-    /// Request enrollment: capture frames, detect best face, extract embedding.
-    pub async fn enroll(&self, frames_count: usize) -> Result<EnrollResult, EngineError> {
-        let (reply_tx, reply_rx) = oneshot::channel();
-        self.tx
-            .send(EngineRequest::Enroll {
-                frames_count,
-                reply: reply_tx,
-            })
-            .await
-            .map_err(|_| EngineError::ChannelClosed)?;
-        reply_rx.await.map_err(|_| EngineError::ChannelClosed)?
-    }
+/// Snapshot of the engine's request queue.
+#[derive(Debug, serde::Serialize)]
+pub struct QueueStatus {
+    /// Requests waiting in the engine channel behind the in-flight one.
+    pub queued: usize,
+    pub in_flight: Option<InFlightStatus>,
+}
 
-    /// Request verification: capture frames, detect, extract, compare against gallery.
-    pub async fn verify(
-        &self,
-        gallery: Vec<FaceModel>,
-        threshold: f32,
-        frames_count: usize,
-        timeout: std::time::Duration,
-        liveness_enabled: bool,
-        liveness_min_displacement: f32,
-    ) -> Result<VerifyResult, EngineError> {
-        let (reply_tx, reply_rx) = oneshot::channel();
-        self.tx
-            .send(EngineRequest::Verify {
-                gallery,
-                threshold,
-                frames_count,
-                timeout,
-                liveness_enabled,
-                liveness_min_displacement,
-                reply: reply_tx,
-            })
-            .await
-            .map_err(|_| EngineError::ChannelClosed)?;
-        reply_rx.await.map_err(|_| EngineError::ChannelClosed)?
-    }
+/// The request the engine thread is currently working on.
+#[derive(Debug, serde::Serialize)]
+pub struct InFlightStatus {
+    pub operation: &'static str,
+    /// Time since the engine picked the request up.
+    pub elapsed_ms: u64,
+    /// Time since the request last made progress (see [`Heartbeat`]).
+    pub stalled_ms: u64,
+    /// Whether [`EngineHandle::cancel_current`] has been called for it.
+    pub cancel_requested: bool,
 }
 
-/// Spawn the engine on a dedicated OS thread.
+/// The in-flight operation and its cancellation flag, shared between the
+/// engine thread and its handles.
 ///
-/// Opens the camera, loads both ONNX models, discards warmup frames,
-/// then enters a request loop. Fails fast at startup if any resource
-/// is unavailable.
-pub fn spawn_engine(
-    camera_device: &str,
-    scrfd_path: &str,
-    arcface_path: &str,
-    warmup_frames: usize,
-    emitter_enabled: bool,
-) -> Result<EngineHandle, EngineError> {
-    // Open camera and load models synchronously (fail-fast)
-    let camera = Camera::open(camera_device)?;
-    tracing::info!(
-        device = camera_device,
-        width = camera.width,
-        height = camera.height,
-        fourcc = ?camera.fourcc,
-        "camera opened"
-    );
+/// `begin`, `finish` and `cancel` hold the same lock, so a cancel can only
+/// ever reach the operation that was in flight when it was requested.
+#[derive(Default)]
+struct Activity {
+    current: Mutex<Option<(&'static str, Instant)>>,
+    cancelled: AtomicBool,
+}
 
-    let mut detector = visage_core::FaceDetector::load(scrfd_path)?;
-    tracing::info!(path = scrfd_path, "SCRFD detector loaded");
+impl Activity {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<(&'static str, Instant)>> {
+        self.current.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 
-    let mut recognizer = visage_core::FaceRecognizer::load(arcface_path)?;
-    tracing::info!(path = arcface_path, "ArcFace recognizer loaded");
+    fn begin(&self, operation: &'static str) {
+        let mut current = self.lock();
+        self.cancelled.store(false, Ordering::SeqCst);
+        *current = Some((operation, Instant::now()));
+    }
 
-    // Probe for IR emitter quirk
-    let emitter: Option<IrEmitter> = if emitter_enabled {
-        match IrEmitter::for_device(camera_device) {
-            Some(e) => {
-                tracing::info!(name = %e.name(), device = %e.device_path(), "IR emitter found");
-                Some(e)
-            }
-            None => {
-                tracing::warn!(
-                    device = camera_device,
-                    "no IR emitter quirk for device; proceeding without illumination"
-                );
-                None
-            }
-        }
-    } else {
-        tracing::info!("IR emitter disabled via VISAGE_EMITTER_ENABLED=0");
-        None
-    };
+    fn finish(&self) {
+        let mut current = self.lock();
+        self.cancelled.store(false, Ordering::SeqCst);
+        *current = None;
+    }
 
-    // Discard warmup frames for camera AGC/AE stabilization
-    if warmup_frames > 0 {
-        tracing::info!(count = warmup_frames, "discarding warmup frames");
-        for _ in 0..warmup_frames {
-            let _ = camera.capture_frame();
-        }
+    /// Flag the in-flight operation for cancellation and return its name.
+    fn cancel(&self) -> Option<&'static str> {
+        let current = self.lock();
+        let (operation, _) = (*current)?;
+        self.cancelled.store(true, Ordering::SeqCst);
+        Some(operation)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
     }
+}
 
-    let (tx, mut rx) = mpsc::channel::<EngineRequest>(4);
+/// Per-request cancellation flag for a verify, held by the client that
+/// asked for it.
+///
+/// Unlike [`EngineHandle::cancel_current`] it reaches its own request only,
+/// whether that is in flight or still queued.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
 
-    std::thread::Builder::new()
-        .name("visage-engine".into())
-        .spawn(move || {
-            tracing::info!("engine thread started");
-            while let Some(req) = rx.blocking_recv() {
-                match req {
-                    EngineRequest::Enroll {
-                        frames_count,
-                        reply,
-                    } => {
-                        let result = run_enroll(
-                            &camera,
-                            &emitter,
-                            &mut detector,
-                            &mut recognizer,
-                            frames_count,
-                        );
-                        let _ = reply.send(result);
-                    }
-                    EngineRequest::Verify {
-                        gallery,
-                        threshold,
-                        frames_count,
-                        timeout,
-                        liveness_enabled,
-                        liveness_min_displacement,
-                        reply,
-                    } => {
-                        let deadline = std::time::Instant::now() + timeout;
-                        let result = run_verify(
-                            &camera,
-                            &emitter,
-                            &mut detector,
-                            &mut recognizer,
-                            &gallery,
-                            threshold,
-                            frames_count,
-                            deadline,
-                            liveness_enabled,
-                            liveness_min_displacement,
-                        );
-                        let _ = reply.send(result);
-                    }
-                }
-            }
-            tracing::info!("engine thread exiting");
-        })
-        .expect("failed to spawn engine thread");
+impl CancelToken {
+    /// Ask the request to stop at its next frame, or not to start at all.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
 
-    Ok(EngineHandle { tx })
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
 }
 
-/// Activate the IR emitter and sleep briefly for AGC stabilisation.
-/// Logs a warning on failure but never propagates the error — capture
-/// continues with ambient light.
-fn activate_emitter(emitter: &Option<IrEmitter>) {
-    if let Some(e) = emitter {
-        if let Err(err) = e.activate() {
-            tracing::warn!(error = %err, "IR emitter activate failed; continuing without illumination");
-        } else {
-            // Allow AGC (auto gain control) to stabilise before capture.
-            std::thread::sleep(std::time::Duration::from_millis(100));
+/// Progress signal from the engine thread, read by the watchdog.
+///
+/// The engine beats when it picks up a request and between frames, and goes
+/// idle once it has replied. Staleness only counts while a request is in
+/// flight, so an engine waiting for work never looks wedged.
+pub struct Heartbeat {
+    epoch: Instant,
+    /// Milliseconds after `epoch` of the last beat, or [`Heartbeat::IDLE`].
+    last_beat_ms: AtomicU64,
+}
+
+impl Heartbeat {
+    const IDLE: u64 = u64::MAX;
+
+    /// Record progress on the current request.
+    pub fn beat(&self) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        self.last_beat_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// Mark the engine as waiting for work.
+    pub fn idle(&self) {
+        self.last_beat_ms.store(Self::IDLE, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat of an in-flight request; `None` while idle.
+    pub fn stalled_for(&self) -> Option<Duration> {
+        let last = self.last_beat_ms.load(Ordering::Relaxed);
+        if last == Self::IDLE {
+            return None;
         }
+        let now = self.epoch.elapsed().as_millis() as u64;
+        Some(Duration::from_millis(now.saturating_sub(last)))
     }
 }
 
-/// Deactivate the IR emitter. Logs a warning on failure.
-fn deactivate_emitter(emitter: &Option<IrEmitter>) {
-    if let Some(e) = emitter {
-        if let Err(err) = e.deactivate() {
-            tracing::warn!(error = %err, "IR emitter deactivate failed");
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_beat_ms: AtomicU64::new(Self::IDLE),
         }
     }
 }
 
-/// Capture frames, extract embeddings from all detected faces, and return
-/// a confidence-weighted average embedding (L2-normalized).
-fn run_enroll(
-    camera: &Camera,
-    emitter: &Option<IrEmitter>,
-    detector: &mut visage_core::FaceDetector,
-    recognizer: &mut visage_core::FaceRecognizer,
-    frames_count: usize,
-) -> Result<EnrollResult, EngineError> {
-    activate_emitter(emitter);
-    let capture_result = camera.capture_frames(frames_count);
-    deactivate_emitter(emitter);
+/// Clone-safe handle to the engine thread.
+#[derive(Clone)]
+pub struct EngineHandle {
+    tx: mpsc::Sender<EngineRequest>,
+    /// Shared by every clone so a model reload that restores the recognizer
+    /// clears it for all of them.
+    recognizer_fault: Arc<Mutex<Option<Arc<str>>>>,
+    heartbeat: Arc<Heartbeat>,
+    activity: Arc<Activity>,
+    /// Set once the watchdog abandons this engine; wakes every waiting caller.
+    abandoned: Arc<watch::Sender<bool>>,
+}
 
-    let (frames, dark_skipped) = capture_result?;
-    tracing::debug!(
-        captured = frames.len(),
-        dark_skipped,
-        "enroll: captured frames"
-    );
+impl EngineHandle {
+    fn new(
+        tx: mpsc::Sender<EngineRequest>,
+        recognizer_fault: Option<Arc<str>>,
+        heartbeat: Arc<Heartbeat>,
+        activity: Arc<Activity>,
+    ) -> Self {
+        Self {
+            tx,
+            recognizer_fault: Arc::new(Mutex::new(recognizer_fault)),
+            heartbeat,
+            activity,
+            abandoned: Arc::new(watch::channel(false).0),
+        }
+    }
 
-    if frames.is_empty() {
-        return Err(EngineError::NoFaceDetected);
+    /// Why the recognizer failed to load, when the engine runs degraded.
+    ///
+    /// A degraded engine still detects faces but rejects enroll and verify
+    /// requests with [`EngineError::RecognizerUnavailable`].
+    pub fn recognizer_fault(&self) -> Option<Arc<str>> {
+        self.recognizer_fault
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 
-    let mut embeddings: Vec<(Embedding, f32)> = Vec::new();
-    let mut best_confidence = 0.0f32;
-    let mut best_frame_idx = 0usize;
+    /// How long the in-flight request has gone without progress, if any.
+    pub fn stalled_for(&self) -> Option<Duration> {
+        self.heartbeat.stalled_for()
+    }
 
-    for (i, frame) in frames.iter().enumerate() {
-        let faces = detector.detect(&frame.data, frame.width, frame.height)?;
-        let Some(face) = faces.first() else {
-            continue;
-        };
+    /// How many requests are waiting, and which one is running and for how long.
+    ///
+    /// Requests stay queued even after their D-Bus caller has given up, so a
+    /// growing `queued` count behind a long-running operation points at a
+    /// wedged engine.
+    pub fn queue_status(&self) -> QueueStatus {
+        let in_flight = (*self.activity.lock()).map(|(operation, started)| InFlightStatus {
+            operation,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            stalled_ms: self
+                .stalled_for()
+                .map_or(0, |stalled| stalled.as_millis() as u64),
+            cancel_requested: self.activity.is_cancelled(),
+        });
+        QueueStatus {
+            queued: self.tx.max_capacity() - self.tx.capacity(),
+            in_flight,
+        }
+    }
 
-        let embedding = match recognizer.extract(&frame.data, frame.width, frame.height, face) {
-            Ok(embedding) => embedding,
-            Err(visage_core::recognizer::RecognizerError::NoLandmarks) => continue,
-            Err(e) => return Err(e.into()),
-        };
+    /// Ask the engine to abort its in-flight operation, whose caller then
+    /// receives [`EngineError::Cancelled`]. Returns the operation's name, or
+    /// `None` when the engine is idle.
+    ///
+    /// Enroll and verify stop at the next frame; the other operations finish
+    /// their capture first. Queued requests are unaffected.
+    pub fn cancel_current(&self) -> Option<&'static str> {
+        self.activity.cancel()
+    }
 
-        let weight = face.confidence.max(0.0);
-        if weight > best_confidence {
-            best_confidence = weight;
-            best_frame_idx = i;
+    /// Give up on this engine: pending and future requests through any clone
+    /// of this handle fail with [`EngineError::EngineRestarted`].
+    ///
+    /// The engine thread itself is not stopped — it may be blocked in the
+    /// kernel — and is simply left behind.
+    pub fn abandon(&self) {
+        self.abandoned.send_replace(true);
+    }
+
+    /// Send a request and wait for its reply, or for the engine to be abandoned.
+    async fn request<T>(
+        &self,
+        build: impl FnOnce(oneshot::Sender<Result<T, EngineError>>) -> EngineRequest,
+    ) -> Result<T, EngineError> {
+        let mut abandoned = self.abandoned.subscribe();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let call = async {
+            self.tx
+                .send(build(reply_tx))
+                .await
+                .map_err(|_| EngineError::ChannelClosed)?;
+            reply_rx.await.map_err(|_| EngineError::ChannelClosed)?
+        };
+        tokio::select! {
+            result = call => result,
+            _ = abandoned.wait_for(|&gone| gone) => Err(EngineError::EngineRestarted),
         }
+    }
 
-        embeddings.push((embedding, weight));
+    /// Request enrollment: capture frames, detect best face, extract embedding.
+    ///
+    /// When `crop_hash_enabled` is set, a perceptual hash of the best aligned
+    /// crop is returned alongside the embedding. Frames whose landmark
+    /// alignment quality is below `min_alignment` are ignored. With a
+    /// `liveness` policy, a subject that fails the landmark stability check
+    /// is refused with [`EngineError::LivenessCheckFailed`]. Fails with
+    /// [`EngineError::EnrollTimeout`] if `timeout` elapses first, and with
+    /// [`EngineError::ClientCancelled`] once `cancel` is set.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn enroll(
+        &self,
+        frames_count: usize,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: CancelToken,
+    ) -> Result<EnrollResult, EngineError> {
+        self.request(|reply| EngineRequest::Enroll {
+            frames_count,
+            crop_hash_enabled,
+            min_alignment,
+            timeout,
+            liveness,
+            progress,
+            cancel,
+            reply,
+        })
+        .await
     }
 
-    if embeddings.is_empty() {
-        return Err(EngineError::NoFaceDetected);
+    /// Request enrollment from frames captured earlier by [`preview`](Self::preview)
+    /// instead of a fresh capture. Otherwise behaves like [`enroll`](Self::enroll).
+    #[allow(clippy::too_many_arguments)]
+    pub async fn enroll_frames(
+        &self,
+        preview: PreviewCapture,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: CancelToken,
+    ) -> Result<EnrollResult, EngineError> {
+        self.request(|reply| EngineRequest::EnrollFrames {
+            preview,
+            crop_hash_enabled,
+            min_alignment,
+            timeout,
+            liveness,
+            progress,
+            cancel,
+            reply,
+        })
+        .await
     }
 
-    tracing::info!(
-        confidence = best_confidence,
-        frame = best_frame_idx,
-        "enroll: best face selected"
-    );
+    /// Capture frames with the emitter active and return them unprocessed,
+    /// for a client to show before the user confirms an enrollment.
+    pub async fn preview(
+        &self,
+        frames_count: usize,
+        timeout: Duration,
+    ) -> Result<PreviewCapture, EngineError> {
+        self.request(|reply| EngineRequest::Preview {
+            frames_count,
+            timeout,
+            reply,
+        })
+        .await
+    }
 
-    let dim = embeddings[0].0.values.len();
+    /// Request verification: capture frames, detect, extract, compare against gallery.
+    ///
+    /// With a `centroid` gate, each frame is first compared against the
+    /// centroid and only frames it cannot reject are matched against `gallery`.
+    /// With a `consistency_floor`, the verify fails with
+    /// [`EngineError::FaceChangedDuringCapture`] as soon as two frames'
+    /// embeddings are less similar than that. With a `lighting_tolerance`,
+    /// each frame is matched against the templates enrolled in similar light
+    /// (see [`lighting_subset`]).
+    /// Each frame matched is reported to `progress` out of `frames_count`.
+    /// Once `cancel` is set the verify stops at the next frame, or is
+    /// skipped if still queued, and fails with [`EngineError::ClientCancelled`].
+    ///
+    /// Fails with [`EngineError::VerifyTimeout`] once `timeout` has passed
+    /// since the call, queueing included. The engine checks its deadline
+    /// between frames, but a frame read blocked in the driver never gets
+    /// there; the caller then stops waiting and the request is cancelled, so
+    /// the engine drops it as soon as the read returns.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn verify(
+        &self,
+        gallery: Vec<FaceModel>,
+        centroid: Option<CentroidGate>,
+        threshold: f32,
+        frames_count: usize,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        consistency_floor: Option<f32>,
+        lighting_tolerance: Option<f32>,
+        progress: Option<VerifyProgressSender>,
+        cancel: CancelToken,
+    ) -> Result<VerifyResult, EngineError> {
+        let on_timeout = cancel.clone();
+        let request = self.request(|reply| EngineRequest::Verify {
+            gallery,
+            centroid,
+            threshold,
+            frames_count,
+            timeout,
+            liveness,
+            consistency_floor,
+            lighting_tolerance,
+            progress,
+            cancel,
+            reply,
+        });
+        within_timeout(request, timeout, on_timeout).await
+    }
 
-    let total_weight: f32 = embeddings.iter().map(|(_, w)| *w).sum();
-    let (denom, use_weighted) = if total_weight > 0.0 {
-        (total_weight, true)
-    } else {
-        (embeddings.len() as f32, false)
-    };
+    /// Match captured frames against `gallery`, which may span many users,
+    /// and name the owner of the best template at or above `threshold`.
+    ///
+    /// Liveness, cancellation and the timeout work as for
+    /// [`verify`](Self::verify); there is no centroid fast path, since each
+    /// centroid belongs to one user.
+    pub async fn identify(
+        &self,
+        gallery: Vec<FaceModel>,
+        threshold: f32,
+        frames_count: usize,
+        timeout: Duration,
+        liveness: Option<LivenessPolicy>,
+        cancel: CancelToken,
+    ) -> Result<IdentifyResult, EngineError> {
+        let on_timeout = cancel.clone();
+        let request = self.request(|reply| EngineRequest::Identify {
+            gallery,
+            threshold,
+            frames_count,
+            timeout,
+            liveness,
+            cancel,
+            reply,
+        });
+        within_timeout(request, timeout, on_timeout).await
+    }
 
-    let mut avg = vec![0.0f32; dim];
-    for (emb, w) in &embeddings {
-        let w = if use_weighted { *w } else { 1.0 };
-        for (a, v) in avg.iter_mut().zip(emb.values.iter()) {
-            *a += v * w;
-        }
+    /// Run detect, extract and match on a supplied frame, bypassing the camera.
+    pub async fn verify_frame(
+        &self,
+        frame: Frame,
+        gallery: Vec<FaceModel>,
+        threshold: f32,
+    ) -> Result<FrameVerifyReport, EngineError> {
+        self.request(|reply| EngineRequest::VerifyFrame {
+            frame,
+            gallery,
+            threshold,
+            reply,
+        })
+        .await
     }
-    for v in &mut avg {
-        *v /= denom;
+
+    /// Capture raw frames with the emitter active and summarize their
+    /// brightness. Works while degraded; no face processing is done.
+    pub async fn exposure_report(
+        &self,
+        frames_count: usize,
+        timeout: Duration,
+    ) -> Result<ExposureStats, EngineError> {
+        self.request(|reply| EngineRequest::ExposureReport {
+            frames_count,
+            timeout,
+            reply,
+        })
+        .await
     }
 
-    // L2-normalize the averaged embedding
-    let norm: f32 = avg.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if norm > 0.0 {
-        for v in &mut avg {
-            *v /= norm;
-        }
+    /// Capture frames through the same path verify uses and time their
+    /// arrival. Works while degraded; no face processing is done.
+    pub async fn camera_benchmark(
+        &self,
+        frames_count: usize,
+        timeout: Duration,
+    ) -> Result<CameraBenchmark, EngineError> {
+        self.request(|reply| EngineRequest::CameraBenchmark {
+            frames_count,
+            timeout,
+            reply,
+        })
+        .await
     }
 
-    let embedding = Embedding {
-        values: avg,
-        model_version: embeddings[0].0.model_version.clone(),
-    };
+    /// Capture raw frames and run the detector on them, to confirm the
+    /// camera still delivers frames. The emitter is only lit when
+    /// `use_emitter` is set. Works while degraded; no embeddings are made.
+    pub async fn self_test(
+        &self,
+        frames_count: usize,
+        timeout: Duration,
+        use_emitter: bool,
+    ) -> Result<SelfTestReport, EngineError> {
+        self.request(|reply| EngineRequest::SelfTest {
+            frames_count,
+            timeout,
+            use_emitter,
+            reply,
+        })
+        .await
+    }
 
-    Ok(EnrollResult {
-        embedding,
-        quality_score: best_confidence,
-    })
+    /// Load fresh detector and recognizer sessions from the model files and
+    /// swap them in between operations. On failure the engine keeps the
+    /// sessions it had; on success a recognizer fault is cleared.
+    pub async fn reload_models(&self) -> Result<(), EngineError> {
+        self.request(|reply| EngineRequest::ReloadModels { reply })
+            .await?;
+        *self
+            .recognizer_fault
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+        Ok(())
+    }
 }
 
-/// Capture frames, detect faces, extract embeddings, compare against gallery.
-/// Uses the best match across all captured frames.
-///
-/// When `liveness_enabled` is true, collects eye landmarks across all frames
-/// and runs a passive stability check before accepting a match. Static images
-/// (photographs) produce near-identical landmarks and are rejected.
-#[allow(clippy::too_many_arguments)]
-fn run_verify(
-    camera: &Camera,
-    emitter: &Option<IrEmitter>,
-    detector: &mut visage_core::FaceDetector,
-    recognizer: &mut visage_core::FaceRecognizer,
-    gallery: &[FaceModel],
-    threshold: f32,
-    frames_count: usize,
-    deadline: std::time::Instant,
-    liveness_enabled: bool,
-    liveness_min_displacement: f32,
-) -> Result<VerifyResult, EngineError> {
-    if std::time::Instant::now() > deadline {
-        return Err(EngineError::VerifyTimeout);
+#[cfg(test)]
+impl EngineHandle {
+    /// Handle whose engine has already exited: every request fails with
+    /// [`EngineError::ChannelClosed`]. For handler tests that never reach the engine.
+    pub fn disconnected() -> Self {
+        let (tx, _rx) = mpsc::channel(1);
+        Self::new(tx, None, Arc::default(), Arc::default())
     }
 
-    activate_emitter(emitter);
-    let capture_result = camera.capture_frames(frames_count);
-    deactivate_emitter(emitter);
+    /// Disconnected handle that reports a recognizer load failure.
+    pub fn degraded(reason: &str) -> Self {
+        let (tx, _rx) = mpsc::channel(1);
+        Self::new(tx, Some(Arc::from(reason)), Arc::default(), Arc::default())
+    }
 
-    if std::time::Instant::now() > deadline {
-        return Err(EngineError::VerifyTimeout);
+    /// Handle to an engine that accepts requests but never answers them.
+    /// Beat [`heartbeat`](Self::heartbeat) to simulate a request that started
+    /// and then stopped making progress.
+    pub fn stuck() -> Self {
+        let (tx, rx) = mpsc::channel(4);
+        // Leaked so the channel stays open and queued requests wait forever.
+        std::mem::forget(rx);
+        Self::new(tx, None, Arc::default(), Arc::default())
     }
 
-    let (frames, dark_skipped) = capture_result?;
-    tracing::debug!(
-        captured = frames.len(),
-        dark_skipped,
-        "verify: captured frames"
-    );
+    /// The heartbeat the watchdog reads, for driving it by hand.
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
 
-    if frames.is_empty() {
-        return Err(EngineError::NoFaceDetected);
+    /// Running engine whose camera always shows the same face, so enrollments
+    /// succeed.
+    pub fn enrolling() -> Self {
+        spawn_engine_with(
+            tests::FlakySource {
+                broken: Arc::default(),
+            },
+            None,
+            tests::EnrollableAnalyzer,
+        )
     }
 
-    let matcher = CosineMatcher;
-    let mut best_result: Option<MatchResult> = None;
-    let mut best_quality = 0.0f32;
-    let mut any_face_detected = false;
-    let mut landmark_sequence: Vec<[(f32, f32); 5]> = Vec::new();
+    /// Running engine whose camera delivers a face every `delay_ms`, so a
+    /// long capture stays in flight for as long as a test needs.
+    pub fn stalling(delay_ms: u64) -> Self {
+        spawn_engine_with(
+            tests::StallingSource {
+                delay_ms: Arc::new(delay_ms.into()),
+            },
+            None,
+            tests::EnrollableAnalyzer,
+        )
+    }
 
-    for frame in &frames {
-        let faces = detector.detect(&frame.data, frame.width, frame.height)?;
-        let Some(face) = faces.first() else {
-            continue;
-        };
-        any_face_detected = true;
+    /// Running engine over a scripted camera that delivers frames without a
+    /// face, or fails every capture while `broken` is set.
+    pub fn flaky_camera(broken: Arc<AtomicBool>) -> Self {
+        spawn_engine_with(tests::FlakySource { broken }, None, tests::EmptyAnalyzer)
+    }
+}
 
-        // Collect landmarks for liveness check
-        if let Some(landmarks) = face.landmarks {
-            landmark_sequence.push(landmarks);
-        }
+/// Source of preprocessed (dark-filtered, contrast-enhanced) frames.
+///
+/// Implemented by [`Camera`]; tests substitute scripted sources.
+pub trait FrameSource: Send + 'static {
+    /// Capture up to `count` usable frames, handing each to `on_frame` as
+    /// soon as it is ready, and stop early once `deadline` passes or
+    /// `on_frame` breaks. Returns the number of dark frames skipped.
+    ///
+    /// Sources must not buffer the batch: the engine relies on dropping
+    /// frames it no longer needs to bound memory.
+    fn capture(
+        &mut self,
+        count: usize,
+        deadline: Instant,
+        on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+    ) -> Result<usize, CameraError>;
 
-        let embedding = recognizer.extract(&frame.data, frame.width, frame.height, face)?;
-        let result = matcher.compare(&embedding, gallery, threshold);
+    /// Capture up to `count` frames without dark filtering or contrast
+    /// enhancement, stopping early once `deadline` passes.
+    fn capture_raw(&mut self, count: usize, deadline: Instant) -> Result<Vec<Frame>, CameraError>;
 
-        let is_better = match &best_result {
-            None => true,
-            Some(prev) => result.similarity > prev.similarity,
+    /// When the source holds a device open while idle, the instant after
+    /// which the engine should call [`release`](Self::release).
+    fn release_deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Release any device handles held while idle.
+    fn release(&mut self) {}
+
+    /// Describe the device behind the most recent capture. The emitter state
+    /// is filled in by the engine.
+    fn describe(&self) -> Option<CaptureContext> {
+        None
+    }
+}
+
+impl FrameSource for Camera {
+    fn capture(
+        &mut self,
+        count: usize,
+        deadline: Instant,
+        on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+    ) -> Result<usize, CameraError> {
+        self.for_each_frame_until(count, deadline, on_frame)
+    }
+
+    fn capture_raw(&mut self, count: usize, deadline: Instant) -> Result<Vec<Frame>, CameraError> {
+        self.capture_raw_frames_until(count, deadline)
+    }
+
+    fn describe(&self) -> Option<CaptureContext> {
+        Some(self.capture_context(false))
+    }
+}
+
+/// Frame source that opens its device on demand and closes it again once
+/// idle for `keep_open`, so other applications can use the camera between
+/// requests. Rapid consecutive requests reuse the open handle.
+pub struct LazySource<S> {
+    open: Box<dyn FnMut() -> Result<S, CameraError> + Send>,
+    keep_open: Duration,
+    current: Option<S>,
+    last_used: Instant,
+}
+
+impl<S: FrameSource> LazySource<S> {
+    pub fn new(
+        keep_open: Duration,
+        open: impl FnMut() -> Result<S, CameraError> + Send + 'static,
+    ) -> Self {
+        Self {
+            open: Box::new(open),
+            keep_open,
+            current: None,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+impl<S: FrameSource> LazySource<S> {
+    /// Run `f` against the device, opening it first if it is closed.
+    fn with_source<T>(
+        &mut self,
+        f: impl FnOnce(&mut S) -> Result<T, CameraError>,
+    ) -> Result<T, CameraError> {
+        let source = match &mut self.current {
+            Some(source) => source,
+            None => {
+                tracing::debug!("lazy camera: opening device");
+                self.current.insert((self.open)()?)
+            }
         };
-        if is_better {
-            best_quality = face.confidence;
-            best_result = Some(result);
+        let result = f(source);
+        self.last_used = Instant::now();
+        result
+    }
+}
+
+impl<S: FrameSource> FrameSource for LazySource<S> {
+    fn capture(
+        &mut self,
+        count: usize,
+        deadline: Instant,
+        on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+    ) -> Result<usize, CameraError> {
+        self.with_source(|source| source.capture(count, deadline, on_frame))
+    }
+
+    fn capture_raw(&mut self, count: usize, deadline: Instant) -> Result<Vec<Frame>, CameraError> {
+        self.with_source(|source| source.capture_raw(count, deadline))
+    }
+
+    fn release_deadline(&self) -> Option<Instant> {
+        self.current
+            .as_ref()
+            .map(|_| self.last_used + self.keep_open)
+    }
+
+    fn release(&mut self) {
+        if self.current.take().is_some() {
+            tracing::debug!("lazy camera: idle window elapsed, device released");
+        }
+    }
+
+    fn describe(&self) -> Option<CaptureContext> {
+        self.current.as_ref().and_then(|source| source.describe())
+    }
+}
+
+/// Face detection and embedding extraction for a single frame.
+pub trait FaceAnalyzer: Send + 'static {
+    /// Detect faces, sorted by descending confidence.
+    fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError>;
+    /// Extract an embedding for a face detected in `frame`.
+    fn extract(&mut self, frame: &Frame, face: &BoundingBox) -> Result<Embedding, RecognizerError>;
+    /// Why [`extract`](Self::extract) cannot work, if the recognizer is missing.
+    fn recognizer_unavailable(&self) -> Option<&str> {
+        None
+    }
+
+    /// Replace the models with freshly loaded ones, keeping the current ones
+    /// if loading or checking the new ones fails.
+    fn reload(&mut self) -> Result<(), String> {
+        Err("this engine cannot reload its models".to_string())
+    }
+}
+
+/// SCRFD detector paired with the ArcFace recognizer.
+///
+/// The recognizer holds the load error instead when it could not be loaded.
+pub struct OnnxAnalyzer {
+    detector: visage_core::FaceDetector,
+    recognizer: Result<visage_core::FaceRecognizer, String>,
+    /// Where [`reload`](FaceAnalyzer::reload) reads the models from.
+    scrfd_path: String,
+    arcface_path: String,
+}
+
+impl OnnxAnalyzer {
+    /// Load both models, passing `pipeline_selftest` before they may be used.
+    fn load(scrfd_path: &str, arcface_path: &str) -> Result<Self, String> {
+        visage_models::validate_model_file(Path::new(scrfd_path)).map_err(|e| e.to_string())?;
+        let detector =
+            visage_core::FaceDetector::load(scrfd_path).map_err(|e| format!("detector: {e}"))?;
+        let recognizer = load_recognizer(arcface_path, None)?;
+        let mut analyzer = Self {
+            detector,
+            recognizer: Ok(recognizer),
+            scrfd_path: scrfd_path.to_string(),
+            arcface_path: arcface_path.to_string(),
+        };
+        pipeline_selftest(&mut analyzer)?;
+        Ok(analyzer)
+    }
+}
+
+impl FaceAnalyzer for OnnxAnalyzer {
+    fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+        Ok(self
+            .detector
+            .detect(&frame.data, frame.width, frame.height)?)
+    }
+
+    fn extract(&mut self, frame: &Frame, face: &BoundingBox) -> Result<Embedding, RecognizerError> {
+        match &mut self.recognizer {
+            Ok(recognizer) => recognizer.extract(&frame.data, frame.width, frame.height, face),
+            Err(reason) => Err(RecognizerError::InferenceFailed(reason.clone())),
+        }
+    }
+
+    fn recognizer_unavailable(&self) -> Option<&str> {
+        self.recognizer.as_ref().err().map(String::as_str)
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        *self = Self::load(&self.scrfd_path, &self.arcface_path)?;
+        Ok(())
+    }
+}
+
+/// Load the ArcFace recognizer, or describe why it is unavailable.
+///
+/// `integrity_fault` is a checksum failure already found by the caller; the
+/// model is not loaded in that case. An empty or truncated file is reported
+/// as such rather than as an ONNX Runtime parse error.
+fn load_recognizer(
+    arcface_path: &str,
+    integrity_fault: Option<String>,
+) -> Result<visage_core::FaceRecognizer, String> {
+    if let Some(fault) = integrity_fault {
+        return Err(fault);
+    }
+    visage_models::validate_model_file(Path::new(arcface_path)).map_err(|e| e.to_string())?;
+    visage_core::FaceRecognizer::load(arcface_path).map_err(|e| e.to_string())
+}
+
+/// Side length of the blank frame [`pipeline_selftest`] runs the models on.
+const SELFTEST_FRAME_SIZE: u32 = 224;
+
+/// Run a blank synthetic frame through detect and extract and check that the
+/// recognizer returns a finite [`ARCFACE_EMBEDDING_DIM`]-dim embedding.
+///
+/// Unlike camera warmup, this validates what the models produce. A blank
+/// frame has no face for the detector to find, so extraction runs on a fixed
+/// face box whose landmarks match the ArcFace template at twice its scale.
+/// A panic in either model is reported as a failure.
+fn pipeline_selftest<A: FaceAnalyzer>(analyzer: &mut A) -> Result<(), String> {
+    let frame = Frame {
+        data: vec![128; (SELFTEST_FRAME_SIZE * SELFTEST_FRAME_SIZE) as usize],
+        width: SELFTEST_FRAME_SIZE,
+        height: SELFTEST_FRAME_SIZE,
+        timestamp: Instant::now(),
+        sequence: 0,
+        is_dark: false,
+        raw_brightness: 128.0,
+    };
+    let face = BoundingBox {
+        x: 48.0,
+        y: 48.0,
+        width: 128.0,
+        height: 160.0,
+        confidence: 1.0,
+        landmarks: Some([
+            (76.6, 103.4),
+            (147.1, 103.0),
+            (112.1, 143.5),
+            (83.1, 184.7),
+            (141.5, 184.4),
+        ]),
+    };
+    let run = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        analyzer
+            .detect(&frame)
+            .map_err(|e| format!("detector failed: {e}"))?;
+        analyzer
+            .extract(&frame, &face)
+            .map_err(|e| format!("recognizer failed: {e}"))
+    }));
+    let embedding = match run {
+        Ok(result) => result?,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            return Err(format!("pipeline panicked: {message}"));
+        }
+    };
+    if embedding.values.len() != ARCFACE_EMBEDDING_DIM {
+        return Err(format!(
+            "recognizer returned a {}-dim embedding, expected {ARCFACE_EMBEDDING_DIM}",
+            embedding.values.len()
+        ));
+    }
+    if embedding.values.iter().any(|v| !v.is_finite()) {
+        return Err("recognizer returned non-finite embedding values".to_string());
+    }
+    Ok(())
+}
+
+/// Await a verify-like `request`, cancelling it and failing with
+/// [`EngineError::VerifyTimeout`] once `timeout` has passed.
+async fn within_timeout<T>(
+    request: impl std::future::Future<Output = Result<T, EngineError>>,
+    timeout: Duration,
+    cancel: CancelToken,
+) -> Result<T, EngineError> {
+    // D-Bus handlers run on zbus's executor, where tokio's timers have no
+    // reactor to register with; async-io's timer works on either.
+    tokio::select! {
+        result = request => result,
+        _ = async_io::Timer::after(timeout) => {
+            cancel.cancel();
+            Err(EngineError::VerifyTimeout)
+        }
+    }
+}
+
+/// Spawn the engine on a dedicated OS thread.
+///
+/// Opens the camera, loads both ONNX models, discards warmup frames,
+/// then enters a request loop. Fails fast at startup if the camera or the
+/// detector is unavailable. A recognizer that is corrupt (`recognizer_fault`)
+/// or fails to load leaves the engine degraded instead — see
+/// [`EngineHandle::recognizer_fault`].
+///
+/// With `lazy_keep_open` set, the camera is only probed at startup and then
+/// closed; each request reopens it (discarding warmup frames again) and it is
+/// released once idle for the given window. Models stay loaded either way.
+///
+/// With `startup_selftest` set, [`pipeline_selftest`] runs once the models
+/// are loaded; a failure drops the recognizer and leaves the engine degraded.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_engine(
+    camera_device: &str,
+    scrfd_path: &str,
+    arcface_path: &str,
+    recognizer_fault: Option<String>,
+    startup_selftest: bool,
+    warmup_frames: usize,
+    emitter_enabled: bool,
+    lazy_keep_open: Option<Duration>,
+) -> Result<EngineHandle, EngineError> {
+    // Open camera and load models synchronously (fail-fast)
+    let camera = Camera::open(camera_device)?;
+    tracing::info!(
+        device = camera_device,
+        width = camera.width,
+        height = camera.height,
+        fourcc = ?camera.fourcc,
+        "camera opened"
+    );
+
+    visage_models::validate_model_file(Path::new(scrfd_path))?;
+    let detector = visage_core::FaceDetector::load(scrfd_path)?;
+    tracing::info!(path = scrfd_path, "SCRFD detector loaded");
+
+    let recognizer = load_recognizer(arcface_path, recognizer_fault);
+    match &recognizer {
+        Ok(_) => tracing::info!(path = arcface_path, "ArcFace recognizer loaded"),
+        Err(reason) => tracing::error!(
+            path = arcface_path,
+            reason = %reason,
+            "ArcFace recognizer unavailable — running degraded, enroll and verify disabled"
+        ),
+    }
+
+    // Probe for IR emitter quirk
+    let emitter: Option<IrEmitter> = if emitter_enabled {
+        match IrEmitter::for_device(camera_device) {
+            Some(e) => {
+                tracing::info!(name = %e.name(), device = %e.device_path(), "IR emitter found");
+                Some(e)
+            }
+            None => {
+                tracing::warn!(
+                    device = camera_device,
+                    "no IR emitter quirk for device; proceeding without illumination"
+                );
+                None
+            }
+        }
+    } else {
+        tracing::info!("IR emitter disabled via VISAGE_EMITTER_ENABLED=0");
+        None
+    };
+
+    let mut analyzer = OnnxAnalyzer {
+        detector,
+        recognizer,
+        scrfd_path: scrfd_path.to_string(),
+        arcface_path: arcface_path.to_string(),
+    };
+    if startup_selftest && analyzer.recognizer.is_ok() {
+        match pipeline_selftest(&mut analyzer) {
+            Ok(()) => tracing::info!("startup self-test passed"),
+            Err(reason) => {
+                tracing::error!(
+                    reason = %reason,
+                    "startup self-test failed — running degraded, enroll and verify disabled"
+                );
+                analyzer.recognizer = Err(format!("startup self-test failed: {reason}"));
+            }
+        }
+    }
+
+    if let Some(keep_open) = lazy_keep_open {
+        drop(camera);
+        tracing::info!(
+            keep_open_secs = keep_open.as_secs_f32(),
+            "lazy camera mode: device closed until first request"
+        );
+        let device = camera_device.to_string();
+        let source = LazySource::new(keep_open, move || {
+            let camera = Camera::open(&device)?;
+            discard_warmup_frames(&camera, warmup_frames);
+            Ok(camera)
+        });
+        return Ok(spawn_engine_with(source, emitter, analyzer));
+    }
+
+    discard_warmup_frames(&camera, warmup_frames);
+    Ok(spawn_engine_with(camera, emitter, analyzer))
+}
+
+/// Discard warmup frames for camera AGC/AE stabilization.
+fn discard_warmup_frames(camera: &Camera, warmup_frames: usize) {
+    if warmup_frames > 0 {
+        tracing::info!(count = warmup_frames, "discarding warmup frames");
+        for _ in 0..warmup_frames {
+            let _ = camera.capture_frame();
+        }
+    }
+}
+
+/// Start the engine request loop over an already-opened frame source and analyzer.
+fn spawn_engine_with<S: FrameSource, A: FaceAnalyzer>(
+    source: S,
+    emitter: Option<IrEmitter>,
+    analyzer: A,
+) -> EngineHandle {
+    let (tx, rx) = mpsc::channel::<EngineRequest>(4);
+    let recognizer_fault = analyzer.recognizer_unavailable().map(Arc::from);
+    let heartbeat = Arc::new(Heartbeat::default());
+    let activity = Arc::new(Activity::default());
+    let engine = Engine {
+        source,
+        emitter,
+        analyzer,
+        heartbeat: heartbeat.clone(),
+        activity: activity.clone(),
+    };
+
+    std::thread::Builder::new()
+        .name("visage-engine".into())
+        .spawn(move || engine.run(rx))
+        .expect("failed to spawn engine thread");
+
+    EngineHandle::new(tx, recognizer_fault, heartbeat, activity)
+}
+
+/// State owned by the engine thread.
+struct Engine<S, A> {
+    source: S,
+    emitter: Option<IrEmitter>,
+    analyzer: A,
+    heartbeat: Arc<Heartbeat>,
+    activity: Arc<Activity>,
+}
+
+impl<S: FrameSource, A: FaceAnalyzer> Engine<S, A> {
+    fn run(mut self, mut rx: mpsc::Receiver<EngineRequest>) {
+        tracing::info!("engine thread started");
+        // Only used to wait for the next request with a timeout while the
+        // frame source holds an idle device open.
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("failed to build engine timer runtime");
+        loop {
+            let req = match self.source.release_deadline() {
+                Some(at) => {
+                    let at = tokio::time::Instant::from_std(at);
+                    match rt.block_on(async { tokio::time::timeout_at(at, rx.recv()).await }) {
+                        Ok(req) => req,
+                        Err(_) => {
+                            self.source.release();
+                            continue;
+                        }
+                    }
+                }
+                None => rx.blocking_recv(),
+            };
+            let Some(req) = req else {
+                break;
+            };
+            self.heartbeat.beat();
+            self.activity.begin(req.operation());
+            match req {
+                EngineRequest::Enroll {
+                    frames_count,
+                    crop_hash_enabled,
+                    min_alignment,
+                    timeout,
+                    liveness,
+                    progress,
+                    cancel,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.enroll(
+                        frames_count,
+                        crop_hash_enabled,
+                        min_alignment,
+                        deadline,
+                        liveness,
+                        progress,
+                        &cancel,
+                    );
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::EnrollFrames {
+                    preview,
+                    crop_hash_enabled,
+                    min_alignment,
+                    timeout,
+                    liveness,
+                    progress,
+                    cancel,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.enroll_frames(
+                        preview,
+                        crop_hash_enabled,
+                        min_alignment,
+                        deadline,
+                        liveness,
+                        progress,
+                        &cancel,
+                    );
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::Preview {
+                    frames_count,
+                    timeout,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.preview(frames_count, deadline);
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::Verify {
+                    gallery,
+                    centroid,
+                    threshold,
+                    frames_count,
+                    timeout,
+                    liveness,
+                    consistency_floor,
+                    lighting_tolerance,
+                    progress,
+                    cancel,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.verify(
+                        &gallery,
+                        centroid.as_ref(),
+                        threshold,
+                        frames_count,
+                        deadline,
+                        liveness,
+                        consistency_floor,
+                        lighting_tolerance,
+                        progress.as_ref(),
+                        &cancel,
+                    );
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::Identify {
+                    gallery,
+                    threshold,
+                    frames_count,
+                    timeout,
+                    liveness,
+                    cancel,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.identify(
+                        &gallery,
+                        threshold,
+                        frames_count,
+                        deadline,
+                        liveness,
+                        &cancel,
+                    );
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::VerifyFrame {
+                    frame,
+                    gallery,
+                    threshold,
+                    reply,
+                } => {
+                    let result = self.verify_frame(&frame, &gallery, threshold);
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::ExposureReport {
+                    frames_count,
+                    timeout,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.exposure_report(frames_count, deadline);
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::CameraBenchmark {
+                    frames_count,
+                    timeout,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.camera_benchmark(frames_count, deadline);
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::SelfTest {
+                    frames_count,
+                    timeout,
+                    use_emitter,
+                    reply,
+                } => {
+                    let deadline = Instant::now() + timeout;
+                    let result = self.self_test(frames_count, deadline, use_emitter);
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+                EngineRequest::ReloadModels { reply } => {
+                    let result = self.analyzer.reload().map_err(EngineError::ReloadFailed);
+                    match &result {
+                        Ok(()) => tracing::info!("models reloaded"),
+                        Err(e) => tracing::error!(error = %e, "model reload failed"),
+                    }
+                    self.heartbeat.idle();
+                    self.activity.finish();
+                    let _ = reply.send(result);
+                }
+            }
+        }
+        tracing::info!("engine thread exiting");
+    }
+
+    /// Capture raw frames under the emitter, as authentication would see the
+    /// scene before enhancement, and summarize their brightness.
+    fn exposure_report(
+        &mut self,
+        frames_count: usize,
+        deadline: Instant,
+    ) -> Result<ExposureStats, EngineError> {
+        activate_emitter(&self.emitter);
+        let result = self.source.capture_raw(frames_count, deadline);
+        self.heartbeat.beat();
+        deactivate_emitter(&self.emitter);
+        if self.activity.is_cancelled() {
+            return Err(EngineError::Cancelled);
         }
+        let frames = result?;
+        tracing::debug!(captured = frames.len(), "exposure report: captured frames");
+        Ok(visage_hw::exposure_stats(&frames))
     }
 
-    if !any_face_detected {
-        return Err(EngineError::NoFaceDetected);
+    /// Capture frames under the emitter as verify does — dark filtering and
+    /// contrast enhancement included, since both cost time — and record
+    /// when each one arrives.
+    fn camera_benchmark(
+        &mut self,
+        frames_count: usize,
+        deadline: Instant,
+    ) -> Result<CameraBenchmark, EngineError> {
+        let Engine {
+            source,
+            emitter,
+            heartbeat,
+            activity,
+            ..
+        } = self;
+        let mut arrivals = Vec::with_capacity(frames_count);
+        let emitter_active = activate_emitter(emitter);
+        let started = Instant::now();
+        let result = source.capture(frames_count, deadline, &mut |frame| {
+            arrivals.push((frame.timestamp, frame.sequence));
+            heartbeat.beat();
+            if activity.is_cancelled() {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        deactivate_emitter(emitter);
+        heartbeat.beat();
+        if activity.is_cancelled() {
+            return Err(EngineError::Cancelled);
+        }
+        let dark_frames = result?;
+        if arrivals.is_empty() {
+            return Err(CameraError::CaptureFailed("no frames delivered".into()).into());
+        }
+        let report = CameraBenchmark::measure(
+            frames_count,
+            started,
+            &arrivals,
+            dark_frames,
+            emitter_active,
+        );
+        tracing::debug!(
+            frames = report.frames,
+            fps = report.fps,
+            jitter_ms = report.jitter_ms,
+            "camera benchmark: captured frames"
+        );
+        Ok(report)
     }
 
-    // If no match result at all, return a non-match
-    let result = best_result.unwrap_or(MatchResult {
-        matched: false,
-        similarity: 0.0,
-        model_id: None,
-        model_label: None,
-    });
+    /// Capture raw frames — so a dark room is not mistaken for a broken
+    /// camera — and count the faces the detector finds in them.
+    fn self_test(
+        &mut self,
+        frames_count: usize,
+        deadline: Instant,
+        use_emitter: bool,
+    ) -> Result<SelfTestReport, EngineError> {
+        let emitter_used = use_emitter && activate_emitter(&self.emitter);
+        let result = self.source.capture_raw(frames_count, deadline);
+        self.heartbeat.beat();
+        if emitter_used {
+            deactivate_emitter(&self.emitter);
+        }
+        if self.activity.is_cancelled() {
+            return Err(EngineError::Cancelled);
+        }
+        let frames = result?;
+        if frames.is_empty() {
+            return Err(CameraError::CaptureFailed("no frames delivered".into()).into());
+        }
+        let mut faces_detected = 0;
+        for frame in &frames {
+            faces_detected += self.analyzer.detect(frame)?.len();
+            self.heartbeat.beat();
+        }
+        Ok(SelfTestReport {
+            frames: frames.len(),
+            faces_detected,
+            mean_brightness: visage_hw::exposure_stats(&frames).mean_brightness,
+            emitter_used,
+        })
+    }
 
-    // --- Passive liveness check ---
-    // Run after detection loop so we always have full landmark data.
-    // Only gates the result when a match would otherwise succeed.
-    if liveness_enabled && result.matched {
-        let liveness = check_landmark_stability(
-            &landmark_sequence,
-            Some(liveness_min_displacement),
+    /// Capture frames under the emitter and keep them all for a later
+    /// [`enroll_frames`](Self::enroll_frames).
+    fn preview(
+        &mut self,
+        frames_count: usize,
+        deadline: Instant,
+    ) -> Result<PreviewCapture, EngineError> {
+        let Engine {
+            source,
+            emitter,
+            heartbeat,
+            activity,
+            ..
+        } = self;
+        let mut frames = Vec::with_capacity(frames_count);
+        let result = capture_with_emitter(source, emitter, frames_count, deadline, &mut |frame| {
+            heartbeat.beat();
+            if activity.is_cancelled() {
+                return ControlFlow::Break(());
+            }
+            frames.push(frame);
+            ControlFlow::Continue(())
+        });
+        heartbeat.beat();
+        if activity.is_cancelled() {
+            return Err(EngineError::Cancelled);
+        }
+        let dark_skipped = result?;
+        tracing::debug!(
+            captured = frames.len(),
+            dark_skipped,
+            "preview: captured frames"
         );
+        if frames.is_empty() {
+            return Err(EngineError::NoFaceDetected);
+        }
+        Ok(PreviewCapture {
+            frames,
+            dark_skipped,
+            device_id: source.describe().map(|context| context.device_id),
+        })
+    }
+
+    /// Reject requests that need embeddings while running degraded.
+    fn require_recognizer(&self) -> Result<(), EngineError> {
+        match self.analyzer.recognizer_unavailable() {
+            Some(reason) => Err(EngineError::RecognizerUnavailable(reason.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Capture frames, extract embeddings from all detected faces, and return
+    /// a confidence-weighted average embedding (L2-normalized).
+    ///
+    /// Frames are processed as they arrive: embeddings are summed
+    /// incrementally and only the best frame so far is kept (for the crop
+    /// hash), so at most two frames are alive at once. Frames whose landmark
+    /// alignment quality falls below `min_alignment` are skipped. The deadline
+    /// is checked before capture and before each frame is processed.
+    ///
+    /// When a `liveness` policy is given, eye landmarks from every frame with
+    /// a face feed the same passive stability check verify uses, and a static
+    /// subject (e.g. a photograph) is refused before any template is built.
+    #[allow(clippy::too_many_arguments)]
+    fn enroll(
+        &mut self,
+        frames_count: usize,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        deadline: Instant,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: &CancelToken,
+    ) -> Result<EnrollResult, EngineError> {
+        let result = self.enroll_with(
+            crop_hash_enabled,
+            min_alignment,
+            deadline,
+            liveness,
+            frames_count,
+            progress,
+            cancel,
+            |source, emitter, on_frame| {
+                capture_with_emitter(source, emitter, frames_count, deadline, on_frame)
+            },
+        )?;
+        Ok(EnrollResult {
+            device_id: self.source.describe().map(|context| context.device_id),
+            ..result
+        })
+    }
+
+    /// Enroll from the frames of an earlier [`preview`](Self::preview), in
+    /// the order they were captured. The camera is not touched.
+    #[allow(clippy::too_many_arguments)]
+    fn enroll_frames(
+        &mut self,
+        preview: PreviewCapture,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        deadline: Instant,
+        liveness: Option<LivenessPolicy>,
+        progress: Option<ProgressSender>,
+        cancel: &CancelToken,
+    ) -> Result<EnrollResult, EngineError> {
+        let PreviewCapture {
+            frames,
+            dark_skipped,
+            device_id,
+        } = preview;
+        let frames_total = frames.len();
+        let result = self.enroll_with(
+            crop_hash_enabled,
+            min_alignment,
+            deadline,
+            liveness,
+            frames_total,
+            progress,
+            cancel,
+            |_, _, on_frame| {
+                for frame in frames {
+                    if on_frame(frame).is_break() {
+                        break;
+                    }
+                }
+                Ok(dark_skipped)
+            },
+        )?;
+        Ok(EnrollResult {
+            device_id,
+            ..result
+        })
+    }
+
+    /// Shared body of [`enroll`](Self::enroll) and
+    /// [`enroll_frames`](Self::enroll_frames): `feed` hands frames to the
+    /// accumulator and returns the number of dark frames skipped. Each
+    /// processed frame is reported to `progress` out of `frames_total`.
+    /// Setting `cancel` stops the enrollment at the next frame, or before
+    /// the first one if it is still queued.
+    #[allow(clippy::too_many_arguments)]
+    fn enroll_with(
+        &mut self,
+        crop_hash_enabled: bool,
+        min_alignment: f32,
+        deadline: Instant,
+        liveness: Option<LivenessPolicy>,
+        frames_total: usize,
+        progress: Option<ProgressSender>,
+        cancel: &CancelToken,
+        feed: impl FnOnce(
+            &mut S,
+            &Option<IrEmitter>,
+            &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError>,
+    ) -> Result<EnrollResult, EngineError> {
+        if cancel.is_cancelled() {
+            return Err(EngineError::ClientCancelled);
+        }
+        self.require_recognizer()?;
+        let timed_out = || Instant::now() > deadline;
+        if timed_out() {
+            return Err(EngineError::EnrollTimeout);
+        }
+
+        let Engine {
+            source,
+            emitter,
+            analyzer,
+            heartbeat,
+            activity,
+        } = self;
+        let ledger = FrameLedger::default();
+        let mut acc = EnrollAccumulator::new(min_alignment, crop_hash_enabled);
+        let mut frames_captured = 0usize;
+        let mut failure = None;
+
+        let capture_result = feed(source, emitter, &mut |frame| {
+            let frame = ledger.hold(frame);
+            let index = frames_captured;
+            frames_captured += 1;
+            heartbeat.beat();
+            let step = if timed_out() {
+                Err(EngineError::EnrollTimeout)
+            } else if activity.is_cancelled() {
+                Err(EngineError::Cancelled)
+            } else if cancel.is_cancelled() {
+                Err(EngineError::ClientCancelled)
+            } else {
+                acc.add_frame(analyzer, frame, index)
+            };
+            match step {
+                Ok(()) => {
+                    if let Some(progress) = &progress {
+                        // A client that stopped listening does not stop the enrollment.
+                        let _ = progress.send(EnrollProgress {
+                            frames_captured,
+                            frames_total,
+                            quality: acc.best.confidence,
+                        });
+                    }
+                    ControlFlow::Continue(())
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    ControlFlow::Break(())
+                }
+            }
+        });
+        heartbeat.beat();
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        if timed_out() {
+            return Err(EngineError::EnrollTimeout);
+        }
 
+        let dark_skipped = capture_result?;
         tracing::debug!(
-            is_live = liveness.is_live,
-            mean_eye_displacement = liveness.mean_eye_displacement,
-            frame_pairs = liveness.frame_pairs_analysed,
-            threshold = liveness_min_displacement,
-            "liveness check"
+            captured = frames_captured,
+            dark_skipped,
+            "enroll: captured frames"
         );
 
-        if !liveness.is_live {
-            tracing::warn!(
-                similarity = result.similarity,
-                displacement = liveness.mean_eye_displacement,
-                "liveness rejected a face that matched identity — possible spoof attempt"
+        if frames_captured == 0 {
+            return Err(EngineError::NoFaceDetected);
+        }
+
+        if let Some(policy) = liveness {
+            let liveness = check_timed_landmark_liveness(&acc.landmark_sequence, &policy);
+            tracing::debug!(
+                is_live = liveness.is_live,
+                mean_eye_displacement = liveness.mean_eye_displacement,
+                frame_pairs = liveness.frame_pairs_analysed,
+                live_pairs = liveness.live_pairs,
+                frames_too_close = liveness.frames_too_close,
+                threshold = policy.min_displacement,
+                min_live_pairs = policy.min_live_pairs,
+                "enroll: liveness check"
             );
-            return Err(EngineError::LivenessCheckFailed {
-                displacement: liveness.mean_eye_displacement,
-                threshold: liveness_min_displacement,
-            });
+            // A single usable frame passes the check by default; enrollment
+            // that requires liveness fails closed instead.
+            if !liveness.is_live || liveness.frame_pairs_analysed == 0 {
+                tracing::warn!(
+                    displacement = liveness.mean_eye_displacement,
+                    live_pairs = liveness.live_pairs,
+                    "enroll: liveness not established — refusing to enroll a static subject"
+                );
+                return Err(EngineError::LivenessCheckFailed {
+                    displacement: liveness.mean_eye_displacement,
+                    threshold: policy.min_displacement,
+                    live_pairs: liveness.live_pairs,
+                    min_live_pairs: policy.min_live_pairs,
+                });
+            }
         }
+
+        let frame_memory = ledger.peak();
+        let best = acc.finish()?;
+
+        tracing::info!(
+            confidence = best.confidence,
+            alignment = best.alignment,
+            frame = best.index,
+            peak_frames_held = frame_memory.frames,
+            "enroll: best face selected"
+        );
+
+        // Only the 64-bit hash leaves this function; the crop itself is discarded.
+        let crop_hash = match (&best.frame, best.landmarks) {
+            (Some(frame), Some(landmarks)) => Some(visage_core::phash::crop_hash(
+                &frame.data,
+                frame.width,
+                frame.height,
+                &landmarks,
+            )),
+            _ => None,
+        };
+
+        Ok(EnrollResult {
+            embedding: best.embedding,
+            quality_score: best.confidence,
+            alignment_score: best.alignment,
+            crop_hash,
+            pose: best.landmarks.as_ref().map(classify_pose),
+            device_id: None,
+            brightness: best.brightness,
+            frames_captured,
+            dark_skipped,
+            frame_memory,
+        })
     }
 
-    Ok(VerifyResult {
-        result,
-        best_quality,
-    })
+    /// Capture frames, detect faces, extract embeddings, compare against gallery.
+    /// Uses the best match across all captured frames.
+    ///
+    /// Each frame is matched and dropped as soon as it arrives; only its
+    /// score and landmarks are kept. When a `liveness` policy is given, eye
+    /// landmarks from all frames feed a passive stability check before a
+    /// match is accepted. Static images (photographs) produce near-identical
+    /// landmarks and are rejected. With a `consistency_floor` each frame's
+    /// embedding is also compared with every earlier one, so a capture in
+    /// which one person steps in for another is aborted instead of matching
+    /// on whichever frame looks most like the target. With a
+    /// `lighting_tolerance` each frame is matched against the templates
+    /// enrolled in light like its own. Each matched frame is reported to
+    /// `progress`.
+    #[allow(clippy::too_many_arguments)]
+    fn verify(
+        &mut self,
+        gallery: &[FaceModel],
+        centroid: Option<&CentroidGate>,
+        threshold: f32,
+        frames_count: usize,
+        deadline: Instant,
+        liveness: Option<LivenessPolicy>,
+        consistency_floor: Option<f32>,
+        lighting_tolerance: Option<f32>,
+        progress: Option<&VerifyProgressSender>,
+        cancel: &CancelToken,
+    ) -> Result<VerifyResult, EngineError> {
+        let started = Instant::now();
+        // Cancelled while queued: the camera is never opened.
+        if cancel.is_cancelled() {
+            return Err(EngineError::ClientCancelled);
+        }
+        self.require_recognizer()?;
+        let timed_out = || Instant::now() > deadline;
+        if timed_out() {
+            return Err(EngineError::VerifyTimeout);
+        }
+
+        let Engine {
+            source,
+            emitter,
+            analyzer,
+            heartbeat,
+            activity,
+        } = self;
+        let ledger = FrameLedger::default();
+        let mut acc = VerifyAccumulator {
+            consistency_floor,
+            lighting_tolerance,
+            ..Default::default()
+        };
+        let mut frames_captured = 0usize;
+        let mut failure = None;
+
+        let emitter_active = activate_emitter(emitter);
+        let capture_result = source.capture(frames_count, deadline, &mut |frame| {
+            let frame = ledger.hold(frame);
+            frames_captured += 1;
+            heartbeat.beat();
+            let step = if timed_out() {
+                Err(EngineError::VerifyTimeout)
+            } else if activity.is_cancelled() {
+                Err(EngineError::Cancelled)
+            } else if cancel.is_cancelled() {
+                Err(EngineError::ClientCancelled)
+            } else {
+                acc.add_frame(analyzer, &frame, gallery, centroid, threshold)
+            };
+            match step {
+                Ok(()) => {
+                    if let Some(progress) = progress {
+                        // A caller that stopped listening does not stop the verify.
+                        let _ = progress.send(VerifyProgress {
+                            frames_done: frames_captured,
+                            frames_total: frames_count,
+                        });
+                    }
+                    ControlFlow::Continue(())
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    ControlFlow::Break(())
+                }
+            }
+        });
+        deactivate_emitter(emitter);
+        heartbeat.beat();
+        let capture = source.describe().map(|context| CaptureContext {
+            emitter_active,
+            ..context
+        });
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        if timed_out() {
+            return Err(EngineError::VerifyTimeout);
+        }
+
+        let dark_skipped = capture_result?;
+        tracing::debug!(
+            captured = frames_captured,
+            dark_skipped,
+            "verify: captured frames"
+        );
+
+        if frames_captured == 0 || acc.frames_with_face == 0 {
+            return Err(EngineError::NoFaceDetected);
+        }
+
+        // If no match result at all, return a non-match
+        let result = acc.best_result.unwrap_or(MatchResult {
+            matched: false,
+            similarity: 0.0,
+            model_id: None,
+            model_label: None,
+        });
+
+        // Templates with an unknown pose label are never flagged.
+        let pose_mismatch = acc.best_pose.and_then(|live| {
+            let model_id = result.model_id.as_deref()?;
+            let template = gallery
+                .iter()
+                .find(|m| m.id == model_id)
+                .and_then(|m| Pose::from_label(&m.pose_label))?;
+            (live != template).then_some(PoseMismatch { live, template })
+        });
+
+        // --- Passive liveness check ---
+        // Run after detection loop so we always have full landmark data.
+        // Only gates the result when a match would otherwise succeed.
+        if let Some(policy) = liveness.filter(|_| result.matched) {
+            let liveness = check_timed_landmark_liveness(&acc.landmark_sequence, &policy);
+
+            tracing::debug!(
+                is_live = liveness.is_live,
+                mean_eye_displacement = liveness.mean_eye_displacement,
+                frame_pairs = liveness.frame_pairs_analysed,
+                live_pairs = liveness.live_pairs,
+                pair_displacements = ?liveness.pair_displacements,
+                frames_too_close = liveness.frames_too_close,
+                threshold = policy.min_displacement,
+                min_live_pairs = policy.min_live_pairs,
+                "liveness check"
+            );
+
+            if !liveness.is_live {
+                tracing::warn!(
+                    similarity = result.similarity,
+                    displacement = liveness.mean_eye_displacement,
+                    live_pairs = liveness.live_pairs,
+                    "liveness rejected a face that matched identity — possible spoof attempt"
+                );
+                return Err(EngineError::LivenessCheckFailed {
+                    displacement: liveness.mean_eye_displacement,
+                    threshold: policy.min_displacement,
+                    live_pairs: liveness.live_pairs,
+                    min_live_pairs: policy.min_live_pairs,
+                });
+            }
+        }
+
+        Ok(VerifyResult {
+            result,
+            best_quality: acc.best_quality,
+            best_alignment: acc.best_alignment,
+            frames_captured,
+            frames_with_face: acc.frames_with_face,
+            dark_skipped,
+            elapsed: started.elapsed(),
+            frame_memory: ledger.peak(),
+            capture,
+            fast_path: acc.best_via_centroid,
+            pose_mismatch,
+            rejection: None,
+        })
+    }
+
+    /// Verify against a gallery spanning several users and name the owner of
+    /// the matched template.
+    fn identify(
+        &mut self,
+        gallery: &[FaceModel],
+        threshold: f32,
+        frames_count: usize,
+        deadline: Instant,
+        liveness: Option<LivenessPolicy>,
+        cancel: &CancelToken,
+    ) -> Result<IdentifyResult, EngineError> {
+        let verified = self.verify(
+            gallery,
+            None,
+            threshold,
+            frames_count,
+            deadline,
+            liveness,
+            None,
+            None,
+            None,
+            cancel,
+        )?;
+        let user = verified
+            .result
+            .model_id
+            .as_deref()
+            .filter(|_| verified.result.matched)
+            .and_then(|id| gallery.iter().find(|model| model.id == id))
+            .map(|model| model.user.clone());
+        Ok(IdentifyResult {
+            user,
+            similarity: verified.result.similarity,
+            frames_captured: verified.frames_captured,
+            elapsed: verified.elapsed,
+        })
+    }
+
+    /// Match a single supplied frame against `gallery` and report every score.
+    ///
+    /// No camera, emitter or liveness check is involved, so the result is
+    /// deterministic for a given frame, gallery and model set.
+    fn verify_frame(
+        &mut self,
+        frame: &Frame,
+        gallery: &[FaceModel],
+        threshold: f32,
+    ) -> Result<FrameVerifyReport, EngineError> {
+        self.require_recognizer()?;
+        let faces = self.analyzer.detect(frame)?;
+        let mut report = FrameVerifyReport {
+            faces_detected: faces.len(),
+            face: faces.first().cloned(),
+            alignment_score: None,
+            matched: false,
+            similarity: 0.0,
+            threshold,
+            model_id: None,
+            model_label: None,
+            similarities: Vec::new(),
+        };
+        let Some(face) = faces.first() else {
+            return Ok(report);
+        };
+        report.alignment_score = face.landmarks.as_ref().map(alignment_quality);
+
+        let embedding = self.analyzer.extract(frame, face)?;
+        let result = CosineMatcher.compare(&embedding, gallery, threshold);
+        report.matched = result.matched;
+        report.similarity = result.similarity;
+        report.model_id = result.model_id;
+        report.model_label = result.model_label;
+        report.similarities = gallery
+            .iter()
+            .map(|m| ModelSimilarity {
+                model_id: m.id.clone(),
+                label: m.label.clone(),
+                similarity: embedding.similarity(&m.embedding),
+            })
+            .collect();
+        Ok(report)
+    }
+}
+
+/// Peak frame-buffer usage of one enroll or verify request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameMemory {
+    /// Most frames alive at the same time.
+    pub frames: usize,
+    /// Most frame pixel bytes alive at the same time.
+    pub bytes: usize,
+}
+
+/// Counts the frame buffers a request holds. Every captured frame is wrapped
+/// in a [`HeldFrame`] that stays counted until it is dropped.
+#[derive(Default)]
+struct FrameLedger {
+    live: Cell<FrameMemory>,
+    peak: Cell<FrameMemory>,
+}
+
+impl FrameLedger {
+    fn hold(&self, frame: Frame) -> HeldFrame<'_> {
+        let mut live = self.live.get();
+        live.frames += 1;
+        live.bytes += frame.data.len();
+        self.live.set(live);
+        let peak = self.peak.get();
+        self.peak.set(FrameMemory {
+            frames: peak.frames.max(live.frames),
+            bytes: peak.bytes.max(live.bytes),
+        });
+        HeldFrame {
+            frame,
+            ledger: self,
+        }
+    }
+
+    fn peak(&self) -> FrameMemory {
+        self.peak.get()
+    }
+}
+
+/// A frame counted by a [`FrameLedger`] for as long as it is alive.
+struct HeldFrame<'a> {
+    frame: Frame,
+    ledger: &'a FrameLedger,
+}
+
+impl std::ops::Deref for HeldFrame<'_> {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl Drop for HeldFrame<'_> {
+    fn drop(&mut self) {
+        let mut live = self.ledger.live.get();
+        live.frames -= 1;
+        live.bytes -= self.frame.data.len();
+        self.ledger.live.set(live);
+    }
+}
+
+/// Running state of an enrollment. Embeddings are summed as frames arrive
+/// (both confidence-weighted and plain, since the weighting is only known to
+/// be usable once every frame is in) and only the best frame is retained.
+struct EnrollAccumulator<'a> {
+    min_alignment: f32,
+    keep_best_frame: bool,
+    weighted_sum: Vec<f32>,
+    plain_sum: Vec<f32>,
+    total_weight: f32,
+    embeddings: usize,
+    /// Sum of `Frame::raw_brightness` over the embedded frames.
+    brightness_sum: f32,
+    model_version: Option<String>,
+    best: BestEnrollFrame<'a>,
+    /// Best alignment among frames rejected by `min_alignment`.
+    rejected_alignment: Option<f32>,
+    /// Landmarks of every detected face with the frame's capture time, for
+    /// the optional liveness check.
+    landmark_sequence: Vec<(Instant, [(f32, f32); 5])>,
+}
+
+/// The highest-confidence face of an enrollment so far.
+struct BestEnrollFrame<'a> {
+    confidence: f32,
+    alignment: f32,
+    index: usize,
+    landmarks: Option<[(f32, f32); 5]>,
+    /// Kept only when a crop hash was requested.
+    frame: Option<HeldFrame<'a>>,
+}
+
+/// Outcome of a completed enrollment accumulation.
+struct EnrollSelection<'a> {
+    embedding: Embedding,
+    brightness: f32,
+    confidence: f32,
+    alignment: f32,
+    index: usize,
+    landmarks: Option<[(f32, f32); 5]>,
+    frame: Option<HeldFrame<'a>>,
+}
+
+impl<'a> EnrollAccumulator<'a> {
+    fn new(min_alignment: f32, keep_best_frame: bool) -> Self {
+        Self {
+            min_alignment,
+            keep_best_frame,
+            weighted_sum: Vec::new(),
+            plain_sum: Vec::new(),
+            total_weight: 0.0,
+            embeddings: 0,
+            brightness_sum: 0.0,
+            model_version: None,
+            best: BestEnrollFrame {
+                confidence: 0.0,
+                alignment: 0.0,
+                index: 0,
+                landmarks: None,
+                frame: None,
+            },
+            rejected_alignment: None,
+            landmark_sequence: Vec::new(),
+        }
+    }
+
+    /// Detect, gate and embed one frame, folding it into the running sums.
+    fn add_frame(
+        &mut self,
+        analyzer: &mut impl FaceAnalyzer,
+        frame: HeldFrame<'a>,
+        index: usize,
+    ) -> Result<(), EngineError> {
+        let faces = analyzer.detect(&frame)?;
+        let Some(face) = faces.first() else {
+            return Ok(());
+        };
+        if let Some(landmarks) = face.landmarks {
+            self.landmark_sequence.push((frame.timestamp, landmarks));
+        }
+
+        let alignment = face.landmarks.as_ref().map_or(0.0, alignment_quality);
+        if alignment < self.min_alignment {
+            tracing::debug!(
+                frame = index,
+                alignment,
+                min_alignment = self.min_alignment,
+                "enroll: frame poorly aligned"
+            );
+            self.rejected_alignment = Some(self.rejected_alignment.unwrap_or(0.0).max(alignment));
+            return Ok(());
+        }
+
+        let embedding = match analyzer.extract(&frame, face) {
+            Ok(embedding) => embedding,
+            Err(RecognizerError::NoLandmarks) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let weight = face.confidence.max(0.0);
+        if self.embeddings == 0 {
+            self.weighted_sum = vec![0.0; embedding.values.len()];
+            self.plain_sum = vec![0.0; embedding.values.len()];
+            self.model_version = embedding.model_version.clone();
+        }
+        for ((ws, ps), v) in self
+            .weighted_sum
+            .iter_mut()
+            .zip(self.plain_sum.iter_mut())
+            .zip(embedding.values.iter())
+        {
+            *ws += v * weight;
+            *ps += v;
+        }
+        self.total_weight += weight;
+        self.embeddings += 1;
+        self.brightness_sum += frame.raw_brightness;
+
+        if weight > self.best.confidence {
+            self.best = BestEnrollFrame {
+                confidence: weight,
+                alignment,
+                index,
+                landmarks: face.landmarks,
+                frame: self.keep_best_frame.then_some(frame),
+            };
+        }
+        Ok(())
+    }
+
+    /// Average the accumulated embeddings (L2-normalized) and hand back the
+    /// best frame.
+    fn finish(self) -> Result<EnrollSelection<'a>, EngineError> {
+        if self.embeddings == 0 {
+            return Err(match self.rejected_alignment {
+                Some(best) => EngineError::PoorAlignment {
+                    best,
+                    min: self.min_alignment,
+                },
+                None => EngineError::NoFaceDetected,
+            });
+        }
+
+        let (mut avg, denom) = if self.total_weight > 0.0 {
+            (self.weighted_sum, self.total_weight)
+        } else {
+            (self.plain_sum, self.embeddings as f32)
+        };
+        for v in &mut avg {
+            *v /= denom;
+        }
+
+        Ok(EnrollSelection {
+            embedding: Embedding::normalized(avg, self.model_version),
+            brightness: self.brightness_sum / self.embeddings as f32,
+            confidence: self.best.confidence,
+            alignment: self.best.alignment,
+            index: self.best.index,
+            landmarks: self.best.landmarks,
+            frame: self.best.frame,
+        })
+    }
+}
+
+/// Running state of a verification: the best match so far and the landmark
+/// track for liveness. Frames themselves are never retained.
+#[derive(Default)]
+struct VerifyAccumulator {
+    best_result: Option<MatchResult>,
+    /// `best_result` came from the centroid fast path.
+    best_via_centroid: bool,
+    best_quality: f32,
+    best_alignment: f32,
+    /// Head pose of the face that produced `best_result`.
+    best_pose: Option<Pose>,
+    frames_with_face: usize,
+    landmark_sequence: Vec<(Instant, [(f32, f32); 5])>,
+    /// Lowest similarity allowed between any two frames' embeddings.
+    consistency_floor: Option<f32>,
+    /// Embeddings of the frames so far, kept only with a `consistency_floor`.
+    embeddings: Vec<Embedding>,
+    /// Match each frame only against templates enrolled within this much
+    /// brightness of it (see [`lighting_subset`]).
+    lighting_tolerance: Option<f32>,
+}
+
+impl VerifyAccumulator {
+    /// Detect, embed and match one frame: rejected by `centroid` when it is
+    /// clearly an impostor, else matched against `gallery` or its lighting
+    /// subset.
+    fn add_frame(
+        &mut self,
+        analyzer: &mut impl FaceAnalyzer,
+        frame: &Frame,
+        gallery: &[FaceModel],
+        centroid: Option<&CentroidGate>,
+        threshold: f32,
+    ) -> Result<(), EngineError> {
+        let faces = analyzer.detect(frame)?;
+        let Some(face) = faces.first() else {
+            return Ok(());
+        };
+        self.frames_with_face += 1;
+
+        // Collect landmarks for liveness check
+        if let Some(landmarks) = face.landmarks {
+            self.landmark_sequence.push((frame.timestamp, landmarks));
+        }
+
+        let embedding = analyzer.extract(frame, face)?;
+        if let Some(floor) = self.consistency_floor {
+            let lowest = self
+                .embeddings
+                .iter()
+                .map(|earlier| earlier.similarity(&embedding))
+                .fold(f32::INFINITY, f32::min);
+            if lowest < floor {
+                tracing::warn!(
+                    similarity = lowest,
+                    floor,
+                    frames = self.embeddings.len() + 1,
+                    "verify: face changed during capture"
+                );
+                return Err(EngineError::FaceChangedDuringCapture {
+                    similarity: lowest,
+                    floor,
+                });
+            }
+            self.embeddings.push(embedding.clone());
+        }
+        let decided = centroid.and_then(|gate| {
+            CentroidMatcher {
+                margin: gate.margin,
+                spread: gate.spread,
+            }
+            .decide(&embedding, &gate.centroid, threshold)
+        });
+        let via_centroid = decided.is_some();
+        let result = decided.unwrap_or_else(|| {
+            let subset = self
+                .lighting_tolerance
+                .and_then(|tolerance| lighting_subset(gallery, frame.raw_brightness, tolerance));
+            let gallery = subset.as_deref().unwrap_or(gallery);
+            CosineMatcher.compare(&embedding, gallery, threshold)
+        });
+
+        let is_better = match &self.best_result {
+            None => true,
+            Some(prev) => result.similarity > prev.similarity,
+        };
+        if is_better {
+            self.best_quality = face.confidence;
+            self.best_alignment = face.landmarks.as_ref().map_or(0.0, alignment_quality);
+            self.best_pose = face.landmarks.as_ref().map(classify_pose);
+            self.best_result = Some(result);
+            self.best_via_centroid = via_centroid;
+        }
+        Ok(())
+    }
+}
+
+/// The templates of `gallery` enrolled in light like a frame of `brightness`:
+/// those within `tolerance` of it, plus any enrolled before brightness was
+/// recorded. `None` when no template is that close, and the whole gallery
+/// should be matched rather than only the unknowns.
+fn lighting_subset(
+    gallery: &[FaceModel],
+    brightness: f32,
+    tolerance: f32,
+) -> Option<Vec<FaceModel>> {
+    let close = |model: &FaceModel| {
+        model
+            .enroll_brightness
+            .map(|enrolled| (enrolled - brightness).abs() <= tolerance)
+    };
+    if !gallery.iter().any(|model| close(model) == Some(true)) {
+        return None;
+    }
+    Some(
+        gallery
+            .iter()
+            .filter(|model| close(model) != Some(false))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Stream a frame sequence from `source` with the IR emitter active.
+fn capture_with_emitter<S: FrameSource>(
+    source: &mut S,
+    emitter: &Option<IrEmitter>,
+    count: usize,
+    deadline: Instant,
+    on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+) -> Result<usize, CameraError> {
+    activate_emitter(emitter);
+    let result = source.capture(count, deadline, on_frame);
+    deactivate_emitter(emitter);
+    result
+}
+
+/// Activate the IR emitter and sleep briefly for AGC stabilisation.
+/// Logs a warning on failure but never propagates the error — capture
+/// continues with ambient light. Returns whether the emitter is lit.
+fn activate_emitter(emitter: &Option<IrEmitter>) -> bool {
+    let Some(e) = emitter else {
+        return false;
+    };
+    if let Err(err) = e.activate() {
+        tracing::warn!(error = %err, "IR emitter activate failed; continuing without illumination");
+        return false;
+    }
+    // Allow AGC (auto gain control) to stabilise before capture.
+    std::thread::sleep(Duration::from_millis(100));
+    true
+}
+
+/// Deactivate the IR emitter. Logs a warning on failure.
+fn deactivate_emitter(emitter: &Option<IrEmitter>) {
+    if let Some(e) = emitter {
+        if let Err(err) = e.deactivate() {
+            tracing::warn!(error = %err, "IR emitter deactivate failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    fn test_frame(sequence: u32) -> Frame {
+        Frame {
+            data: vec![128; (WIDTH * HEIGHT) as usize],
+            width: WIDTH,
+            height: HEIGHT,
+            timestamp: Instant::now(),
+            sequence,
+            is_dark: false,
+            raw_brightness: 128.0,
+        }
+    }
+
+    /// Frame source that sleeps `delay_ms` before each frame, honouring the
+    /// deadline between frames the way `Camera::capture_frames_until` does.
+    pub(super) struct StallingSource {
+        pub(super) delay_ms: Arc<AtomicU64>,
+    }
+
+    impl FrameSource for StallingSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count {
+                if Instant::now() >= deadline {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(self.delay_ms.load(Ordering::SeqCst)));
+                if on_frame(test_frame(seq as u32)).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            count: usize,
+            deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            let mut frames = Vec::new();
+            self.capture(count, deadline, &mut |frame| {
+                frames.push(frame);
+                ControlFlow::Continue(())
+            })?;
+            Ok(frames)
+        }
+    }
+
+    /// Analyzer that finds one centred face per frame and returns a fixed embedding.
+    struct FixedAnalyzer;
+
+    impl FaceAnalyzer for FixedAnalyzer {
+        fn detect(&mut self, _frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            Ok(vec![BoundingBox {
+                x: 16.0,
+                y: 8.0,
+                width: 32.0,
+                height: 32.0,
+                confidence: 0.9,
+                landmarks: Some([
+                    (26.0, 20.0),
+                    (38.0, 20.0),
+                    (32.0, 26.0),
+                    (27.0, 32.0),
+                    (37.0, 32.0),
+                ]),
+            }])
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            Ok(Embedding::new(vec![1.0; 8], Some("test".to_string())))
+        }
+    }
+
+    /// [`FixedAnalyzer`]'s face with an embedding the store accepts.
+    pub(super) struct EnrollableAnalyzer;
+
+    impl FaceAnalyzer for EnrollableAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            Ok(Embedding::new(
+                vec![0.5; ARCFACE_EMBEDDING_DIM],
+                Some(visage_core::recognizer::ARCFACE_MODEL_VERSION.to_string()),
+            ))
+        }
+    }
+
+    /// Analyzer whose recognizer failed to load: detection works, extraction never runs.
+    struct DetectorOnlyAnalyzer(String);
+
+    impl FaceAnalyzer for DetectorOnlyAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            panic!("extract called on a degraded engine");
+        }
+
+        fn recognizer_unavailable(&self) -> Option<&str> {
+            Some(&self.0)
+        }
+    }
+
+    /// Analyzer whose "models" are an embedding, or the reason they failed
+    /// to load. [`reload`](FaceAnalyzer::reload) installs whatever the test
+    /// staged, keeping the current embedding when that is an error.
+    struct ReloadableAnalyzer {
+        embedding: Result<Vec<f32>, String>,
+        staged: Arc<Mutex<Result<Vec<f32>, String>>>,
+    }
+
+    impl FaceAnalyzer for ReloadableAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            let values = self
+                .embedding
+                .clone()
+                .expect("extract on a degraded engine");
+            Ok(Embedding::new(values, Some("test".to_string())))
+        }
+
+        fn recognizer_unavailable(&self) -> Option<&str> {
+            self.embedding.as_ref().err().map(String::as_str)
+        }
+
+        fn reload(&mut self) -> Result<(), String> {
+            self.embedding = Ok(self.staged.lock().unwrap().clone()?);
+            Ok(())
+        }
+    }
+
+    /// Camera that delivers plain frames, or fails every capture while
+    /// `broken` is set.
+    pub(super) struct FlakySource {
+        pub(super) broken: Arc<AtomicBool>,
+    }
+
+    impl FrameSource for FlakySource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for frame in self.capture_raw(count, Instant::now())? {
+                if on_frame(frame).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            if self.broken.load(Ordering::SeqCst) {
+                return Err(CameraError::CaptureFailed("VIDIOC_DQBUF: EIO".into()));
+            }
+            Ok((0..count as u32).map(test_frame).collect())
+        }
+    }
+
+    /// Analyzer that never finds a face.
+    pub(super) struct EmptyAnalyzer;
+
+    impl FaceAnalyzer for EmptyAnalyzer {
+        fn detect(&mut self, _frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            Ok(Vec::new())
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            panic!("extract called during a detection-only test");
+        }
+    }
+
+    /// Analyzer that reuses [`FixedAnalyzer`]'s face but derives the embedding
+    /// from content: the mean brightness of eight vertical strips.
+    struct StripAnalyzer;
+
+    impl FaceAnalyzer for StripAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            let (w, h) = (frame.width as usize, frame.height as usize);
+            let strip = w / 8;
+            let values = (0..8)
+                .map(|s| {
+                    let sum: u32 = (0..h)
+                        .flat_map(|y| (s * strip..(s + 1) * strip).map(move |x| (x, y)))
+                        .map(|(x, y)| frame.data[y * w + x] as u32)
+                        .sum();
+                    sum as f32 / (strip * h) as f32
+                })
+                .collect();
+            Ok(Embedding::new(values, Some("test".to_string())))
+        }
+    }
+
+    /// Analyzer whose face drifts a few pixels every frame, like a live head.
+    struct DriftingAnalyzer;
+
+    impl FaceAnalyzer for DriftingAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            let mut faces = FixedAnalyzer.detect(frame)?;
+            let shift = (frame.sequence % 2) as f32 * 2.0;
+            if let Some(landmarks) = faces[0].landmarks.as_mut() {
+                for (x, _) in landmarks.iter_mut() {
+                    *x += shift;
+                }
+            }
+            Ok(faces)
+        }
+
+        fn extract(
+            &mut self,
+            frame: &Frame,
+            face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            FixedAnalyzer.extract(frame, face)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enroll_requiring_liveness_rejects_static_subject() {
+        let timeout = Duration::from_secs(5);
+        let policy = Some(LivenessPolicy::default());
+        let source = || StallingSource {
+            delay_ms: Arc::new(AtomicU64::new(0)),
+        };
+
+        // Identical synthetic frames: landmarks never move, as with a photo.
+        let engine = spawn_engine_with(source(), None, FixedAnalyzer);
+        let result = engine
+            .enroll(4, false, 0.0, timeout, policy, None, CancelToken::default())
+            .await;
+        let Err(EngineError::LivenessCheckFailed { displacement, .. }) = result else {
+            panic!("static enrollment should fail liveness");
+        };
+        assert_eq!(displacement, 0.0);
+        // The option is opt-in: the same frames enroll without a policy.
+        assert!(engine
+            .enroll(4, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .is_ok());
+
+        let engine = spawn_engine_with(source(), None, DriftingAnalyzer);
+        assert!(engine
+            .enroll(4, false, 0.0, timeout, policy, None, CancelToken::default())
+            .await
+            .is_ok());
+        // One face frame cannot show movement: refused rather than waved through.
+        let result = engine
+            .enroll(1, false, 0.0, timeout, policy, None, CancelToken::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(EngineError::LivenessCheckFailed { live_pairs: 0, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_with_liveness_rejects_static_subject() {
+        let policy = Some(LivenessPolicy::default());
+        let source = || StallingSource {
+            delay_ms: Arc::new(AtomicU64::new(0)),
+        };
+        let verify = |engine: EngineHandle, liveness| async move {
+            engine
+                .verify(
+                    vec![model("flat", vec![1.0; 8])],
+                    None,
+                    0.4,
+                    4,
+                    Duration::from_secs(5),
+                    liveness,
+                    None,
+                    None,
+                    None,
+                    CancelToken::default(),
+                )
+                .await
+        };
+
+        // Landmarks that never move, as with a photo, match but are refused.
+        let engine = spawn_engine_with(source(), None, FixedAnalyzer);
+        let Err(EngineError::LivenessCheckFailed { displacement, .. }) =
+            verify(engine.clone(), policy).await
+        else {
+            panic!("a static subject should fail liveness");
+        };
+        assert_eq!(displacement, 0.0);
+        assert!(verify(engine, None).await.unwrap().result.matched);
+
+        let engine = spawn_engine_with(source(), None, DriftingAnalyzer);
+        assert!(verify(engine, policy).await.unwrap().result.matched);
+    }
+
+    fn model(id: &str, values: Vec<f32>) -> FaceModel {
+        FaceModel {
+            id: id.to_string(),
+            user: "alice".to_string(),
+            label: id.to_string(),
+            embedding: Embedding::new(values, Some("test".to_string())),
+            pose_label: "frontal".to_string(),
+            device_id: None,
+            enroll_brightness: None,
+            created_at: String::new(),
+        }
+    }
+
+    fn stalling_engine(delay_ms: u64) -> (EngineHandle, Arc<AtomicU64>) {
+        let delay = Arc::new(AtomicU64::new(delay_ms));
+        let source = StallingSource {
+            delay_ms: delay.clone(),
+        };
+        (spawn_engine_with(source, None, FixedAnalyzer), delay)
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_idle_between_requests() {
+        let (engine, _) = stalling_engine(0);
+        assert_eq!(engine.stalled_for(), None);
+        engine
+            .enroll(
+                2,
+                false,
+                0.0,
+                Duration::from_secs(5),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(engine.stalled_for(), None);
+    }
+
+    #[tokio::test]
+    async fn test_enroll_times_out_on_stalling_camera() {
+        let (engine, _) = stalling_engine(100);
+
+        let started = Instant::now();
+        let result = engine
+            .enroll(
+                50,
+                false,
+                0.0,
+                Duration::from_millis(300),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        let elapsed = started.elapsed();
+
+        assert!(matches!(result, Err(EngineError::EnrollTimeout)));
+        // Bounded by the timeout plus at most one stalled frame.
+        assert!(elapsed < Duration::from_millis(800), "took {elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_engine_usable_after_enroll_timeout() {
+        let (engine, delay) = stalling_engine(100);
+
+        let result = engine
+            .enroll(
+                50,
+                false,
+                0.0,
+                Duration::from_millis(200),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EngineError::EnrollTimeout)));
+
+        delay.store(0, Ordering::SeqCst);
+        let result = engine
+            .enroll(
+                3,
+                false,
+                0.0,
+                Duration::from_secs(5),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .expect("engine should serve the next request");
+        assert!((result.quality_score - 0.9).abs() < 1e-6);
+        let norm: f32 = result.embedding.values.iter().map(|v| v * v).sum();
+        assert!((norm - 1.0).abs() < 1e-5);
+    }
+
+    /// Source that synthesizes full-HD frames one at a time, as a camera would.
+    struct LargeFrameSource;
+
+    const LARGE_WIDTH: u32 = 1920;
+    const LARGE_HEIGHT: u32 = 1080;
+
+    impl FrameSource for LargeFrameSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count as u32 {
+                let frame = Frame {
+                    data: vec![128; (LARGE_WIDTH * LARGE_HEIGHT) as usize],
+                    width: LARGE_WIDTH,
+                    height: LARGE_HEIGHT,
+                    timestamp: Instant::now(),
+                    sequence: seq,
+                    is_dark: false,
+                    raw_brightness: 128.0,
+                };
+                if on_frame(frame).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            _count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Analyzer whose detection confidence rises with every frame, so each
+    /// new frame replaces the best one.
+    struct RisingAnalyzer;
+
+    impl FaceAnalyzer for RisingAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            let mut faces = FixedAnalyzer.detect(frame)?;
+            faces[0].confidence = 0.5 + frame.sequence as f32 * 0.05;
+            Ok(faces)
+        }
+
+        fn extract(
+            &mut self,
+            frame: &Frame,
+            face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            FixedAnalyzer.extract(frame, face)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_frames_hold_at_most_best_and_current() {
+        let frame_bytes = (LARGE_WIDTH * LARGE_HEIGHT) as usize;
+        let engine = spawn_engine_with(LargeFrameSource, None, RisingAnalyzer);
+        let timeout = Duration::from_secs(30);
+
+        let result = engine
+            .enroll(8, true, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert_eq!(result.frames_captured, 8);
+        assert_eq!(
+            result.frame_memory,
+            FrameMemory {
+                frames: 2,
+                bytes: 2 * frame_bytes
+            }
+        );
+        assert!((result.quality_score - 0.85).abs() < 1e-6);
+        assert!(result.crop_hash.is_some());
+
+        // Without a crop hash nothing outlives the frame being evaluated.
+        let result = engine
+            .enroll(8, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert_eq!(result.frame_memory.frames, 1);
+
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                None,
+                0.4,
+                8,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.frames_captured, 8);
+        assert_eq!(
+            result.frame_memory,
+            FrameMemory {
+                frames: 1,
+                bytes: frame_bytes
+            }
+        );
+    }
+
+    /// Source whose frames were all captured at one raw brightness.
+    struct LitSource(f32);
+
+    impl FrameSource for LitSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count as u32 {
+                let frame = Frame {
+                    raw_brightness: self.0,
+                    ..test_frame(seq)
+                };
+                if on_frame(frame).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            _count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lighting_tolerance_matches_templates_enrolled_in_similar_light() {
+        let timeout = Duration::from_secs(5);
+        let dark_room = spawn_engine_with(LitSource(40.0), None, FixedAnalyzer);
+        let enrolled = dark_room
+            .enroll(3, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert!((enrolled.brightness - 40.0).abs() < 1e-3);
+
+        // The bright template is the closer match, but both clear the threshold.
+        let lit = |id: &str, values: Vec<f32>, brightness| FaceModel {
+            enroll_brightness: Some(brightness),
+            ..model(id, values)
+        };
+        let mut dark_values = vec![1.0; 8];
+        dark_values[0] = 0.5;
+        let gallery = vec![
+            lit("bright", vec![1.0; 8], 200.0),
+            lit("dark", dark_values, 45.0),
+        ];
+        let best = |engine: EngineHandle, tolerance| {
+            let gallery = gallery.clone();
+            async move {
+                let result = engine
+                    .verify(
+                        gallery,
+                        None,
+                        0.4,
+                        3,
+                        timeout,
+                        None,
+                        None,
+                        tolerance,
+                        None,
+                        CancelToken::default(),
+                    )
+                    .await
+                    .unwrap();
+                assert!(result.result.matched);
+                result.result.model_id.unwrap()
+            }
+        };
+
+        assert_eq!(best(dark_room.clone(), None).await, "bright");
+        assert_eq!(best(dark_room.clone(), Some(30.0)).await, "dark");
+
+        // Nothing was enrolled in light like this, so the whole gallery counts.
+        let dim_room = spawn_engine_with(LitSource(120.0), None, FixedAnalyzer);
+        assert_eq!(best(dim_room, Some(30.0)).await, "bright");
+
+        // Templates from before brightness was recorded are never left out.
+        let legacy = model("legacy", vec![1.0; 8]);
+        let subset = lighting_subset(
+            &[gallery[0].clone(), gallery[1].clone(), legacy],
+            40.0,
+            30.0,
+        );
+        let ids: Vec<_> = subset.unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["dark", "legacy"]);
+    }
+
+    #[tokio::test]
+    async fn test_identify_names_the_owner_of_the_best_template() {
+        let (engine, _) = stalling_engine(0);
+        let owned = |user: &str, values: Vec<f32>| FaceModel {
+            user: user.to_string(),
+            ..model(&format!("{user}-default"), values)
+        };
+        let mut alice = vec![1.0; 8];
+        alice[0] = 0.5;
+        let alice = owned("alice", alice);
+        let identify = |gallery, threshold| {
+            engine.identify(
+                gallery,
+                threshold,
+                3,
+                Duration::from_secs(5),
+                None,
+                CancelToken::default(),
+            )
+        };
+
+        let gallery = vec![alice.clone(), owned("bob", vec![1.0; 8])];
+        let identified = identify(gallery, 0.4).await.unwrap();
+        assert_eq!(identified.user.as_deref(), Some("bob"));
+        assert!((identified.similarity - 1.0).abs() < 1e-5);
+        assert_eq!(identified.frames_captured, 3);
+
+        // A best match below the threshold names no one.
+        let unknown = identify(vec![alice], 0.99).await.unwrap();
+        assert_eq!(unknown.user, None);
+        assert!(unknown.similarity > 0.9);
+    }
+
+    /// Source that counts how many instances are alive (i.e. "open").
+    struct CountedSource {
+        open: Arc<AtomicU64>,
+    }
+
+    impl FrameSource for CountedSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count as u32 {
+                if on_frame(test_frame(seq)).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok((0..count as u32).map(test_frame).collect())
+        }
+    }
+
+    impl Drop for CountedSource {
+        fn drop(&mut self) {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lazy_camera_opens_per_request_and_releases_when_idle() {
+        let opens = Arc::new(AtomicU64::new(0));
+        let open_now = Arc::new(AtomicU64::new(0));
+        let source = {
+            let (opens, open_now) = (opens.clone(), open_now.clone());
+            LazySource::new(Duration::from_millis(150), move || {
+                opens.fetch_add(1, Ordering::SeqCst);
+                open_now.fetch_add(1, Ordering::SeqCst);
+                Ok(CountedSource {
+                    open: open_now.clone(),
+                })
+            })
+        };
+        let engine = spawn_engine_with(source, None, FixedAnalyzer);
+        let timeout = Duration::from_secs(5);
+
+        // Closed until the first request
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(opens.load(Ordering::SeqCst), 0);
+
+        engine
+            .enroll(2, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 1);
+        assert_eq!(open_now.load(Ordering::SeqCst), 1);
+
+        // A rapid follow-up reuses the open handle
+        engine
+            .enroll(2, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 1);
+
+        // Released once the keep-open window passes
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(open_now.load(Ordering::SeqCst), 0);
+
+        // Reopened for the next request
+        engine
+            .enroll(2, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+        assert_eq!(open_now.load(Ordering::SeqCst), 1);
+    }
+
+    /// Source whose frame sequence numbers keep counting across requests, so
+    /// every capture is distinguishable from the ones before it.
+    struct SequenceSource {
+        next: u32,
+    }
+
+    impl FrameSource for SequenceSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for _ in 0..count {
+                self.next += 1;
+                if on_frame(test_frame(self.next)).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            _count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Source that reports fixed device properties, like a camera would.
+    struct DescribedSource(CaptureContext);
+
+    impl FrameSource for DescribedSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count as u32 {
+                if on_frame(test_frame(seq)).is_break() {
+                    break;
+                }
+            }
+            Ok(0)
+        }
+
+        fn capture_raw(
+            &mut self,
+            _count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok(Vec::new())
+        }
+
+        fn describe(&self) -> Option<CaptureContext> {
+            Some(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_capture_context() {
+        let webcam = CaptureContext {
+            device: "/dev/video0".to_string(),
+            device_id: "usb:046d:085c.0".to_string(),
+            fourcc: "YUYV".to_string(),
+            width: 1280,
+            height: 720,
+            emitter_active: true,
+            preprocessing: vec!["yuyv_luma".to_string(), "clahe".to_string()],
+        };
+        let engine = spawn_engine_with(DescribedSource(webcam.clone()), None, FixedAnalyzer);
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                None,
+                0.4,
+                2,
+                Duration::from_secs(5),
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        // No emitter was configured, so the engine overrides the source's claim.
+        let expected = CaptureContext {
+            emitter_active: false,
+            ..webcam
+        };
+        assert_eq!(result.capture, Some(expected));
+        assert_eq!(
+            result.capture.unwrap().to_string(),
+            "/dev/video0 YUYV 1280x720, emitter off, yuyv_luma → clahe"
+        );
+
+        // Enrollment records the same device.
+        let enrolled = engine
+            .enroll(
+                2,
+                false,
+                0.0,
+                Duration::from_secs(5),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(enrolled.device_id.as_deref(), Some("usb:046d:085c.0"));
+
+        // Sources that cannot describe themselves report nothing.
+        let (engine, _) = stalling_engine(0);
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                None,
+                0.4,
+                2,
+                Duration::from_secs(5),
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.capture.is_none());
+    }
+
+    /// Source that delivers `count` frames a millisecond apart and reports
+    /// `dark` frames skipped along the way.
+    struct DarkSkippingSource {
+        dark: usize,
+    }
+
+    impl FrameSource for DarkSkippingSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for seq in 0..count as u32 {
+                std::thread::sleep(Duration::from_millis(1));
+                if on_frame(test_frame(seq)).is_break() {
+                    break;
+                }
+            }
+            Ok(self.dark)
+        }
+
+        fn capture_raw(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            Ok((0..count as u32).map(test_frame).collect())
+        }
+    }
+
+    /// Analyzer that only finds [`FixedAnalyzer`]'s face in even-numbered frames.
+    struct EvenFramesAnalyzer;
+
+    impl FaceAnalyzer for EvenFramesAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            if frame.sequence % 2 == 1 {
+                return Ok(Vec::new());
+            }
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            frame: &Frame,
+            face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            FixedAnalyzer.extract(frame, face)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_frame_counts_and_elapsed() {
+        let engine = spawn_engine_with(DarkSkippingSource { dark: 2 }, None, EvenFramesAnalyzer);
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                None,
+                0.4,
+                5,
+                Duration::from_secs(5),
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.result.matched);
+        assert_eq!(result.frames_captured, 5);
+        assert_eq!(result.frames_with_face, 3);
+        assert_eq!(result.dark_skipped, 2);
+        assert!(result.elapsed >= Duration::from_millis(5));
+    }
+
+    /// Analyzer that sees one person in the first two frames of a
+    /// [`SequenceSource`] capture and a different one after that.
+    struct SwappingAnalyzer;
+
+    impl FaceAnalyzer for SwappingAnalyzer {
+        fn detect(&mut self, frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            FixedAnalyzer.detect(frame)
+        }
+
+        fn extract(
+            &mut self,
+            frame: &Frame,
+            _face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            let mut values = vec![0.0; 8];
+            values[usize::from(frame.sequence > 2)] = 1.0;
+            Ok(Embedding::new(values, Some("test".to_string())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_aborts_when_the_face_changes_mid_capture() {
+        // The target looks like the second person, who steps in at frame 3.
+        let mut target = vec![0.0; 8];
+        target[1] = 1.0;
+        let verify = |floor| {
+            let engine = spawn_engine_with(SequenceSource { next: 0 }, None, SwappingAnalyzer);
+            let gallery = vec![model("target", target.clone())];
+            async move {
+                engine
+                    .verify(
+                        gallery,
+                        None,
+                        0.4,
+                        4,
+                        Duration::from_secs(5),
+                        None,
+                        floor,
+                        None,
+                        None,
+                        CancelToken::default(),
+                    )
+                    .await
+            }
+        };
+
+        // Without the check the best frame wins, whoever it shows.
+        assert!(verify(None).await.unwrap().result.matched);
+
+        match verify(Some(0.5)).await {
+            Err(EngineError::FaceChangedDuringCapture { similarity, floor }) => {
+                assert!(similarity.abs() < 1e-6, "{similarity}");
+                assert_eq!(floor, 0.5);
+            }
+            other => panic!("expected FaceChangedDuringCapture, got {:?}", other.err()),
+        }
+
+        // One face throughout passes the check.
+        let engine = spawn_engine_with(SequenceSource { next: 0 }, None, FixedAnalyzer);
+        let result = engine
+            .verify(
+                vec![model("flat", vec![1.0; 8])],
+                None,
+                0.4,
+                4,
+                Duration::from_secs(5),
+                None,
+                Some(0.9),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.result.matched);
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_pose_mismatch_with_best_template() {
+        let (engine, _) = stalling_engine(0);
+        let timeout = Duration::from_secs(5);
+        // FixedAnalyzer's face is frontal; its best match was enrolled turned left.
+        let mut turned = model("turned", vec![1.0; 8]);
+        turned.pose_label = "left".to_string();
+        let weaker = model("frontal", [1.0, 0.5].repeat(4));
+        let result = engine
+            .verify(
+                vec![weaker, turned.clone()],
+                None,
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.result.matched);
+        assert_eq!(result.result.model_id.as_deref(), Some("turned"));
+        assert_eq!(
+            result.pose_mismatch,
+            Some(PoseMismatch {
+                live: Pose::Frontal,
+                template: Pose::Left,
+            })
+        );
+
+        // Same pose, or a template whose pose was never recorded: no flag.
+        let frontal = model("frontal", vec![1.0; 8]);
+        let result = engine
+            .verify(
+                vec![frontal],
+                None,
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.pose_mismatch, None);
+        turned.pose_label = String::new();
+        let result = engine
+            .verify(
+                vec![turned],
+                None,
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.pose_mismatch, None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_centroid_fast_path() {
+        let (engine, _) = stalling_engine(0);
+        let timeout = Duration::from_secs(5);
+        let gate = |values: Vec<f32>| {
+            Some(CentroidGate {
+                centroid: Embedding::normalized(values, Some("test".to_string())),
+                margin: 0.1,
+                spread: 1.0,
+            })
+        };
+        // A template the probe does not resemble: reaching it means no match.
+        let stranger = || {
+            vec![model(
+                "stranger",
+                vec![1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0],
+            )]
+        };
+
+        // FixedAnalyzer's probe equals this centroid, yet only a template
+        // may accept it: the stranger gallery turns it down.
+        let result = engine
+            .verify(
+                stranger(),
+                gate(vec![1.0; 8]),
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.fast_path);
+        assert!(!result.result.matched);
+
+        // Similarity 0.45 sits within the margin around 0.4, so the gallery decides.
+        let borderline = [2.984, -0.984].repeat(4);
+        let result = engine
+            .verify(
+                stranger(),
+                gate(borderline.clone()),
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.fast_path);
+        assert!(!result.result.matched);
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                gate(borderline),
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.fast_path);
+        assert_eq!(result.result.model_id.as_deref(), Some("flat"));
+
+        // A clear impostor is rejected by the centroid alone.
+        let gallery = vec![model("flat", vec![1.0; 8])];
+        let result = engine
+            .verify(
+                gallery,
+                gate([1.0, -1.0].repeat(4)),
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.fast_path);
+        assert!(!result.result.matched);
+    }
+
+    #[tokio::test]
+    async fn test_enroll_frames_uses_preview_not_camera() {
+        let engine = spawn_engine_with(SequenceSource { next: 0 }, None, RisingAnalyzer);
+        let timeout = Duration::from_secs(5);
+
+        let preview = engine.preview(3, timeout).await.unwrap();
+        let sequences: Vec<u32> = preview.frames.iter().map(|f| f.sequence).collect();
+        assert_eq!(sequences, [1, 2, 3]);
+
+        // The camera moves on; a fresh enroll would see frames 4..=6.
+        let fresh = engine
+            .enroll(3, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert!((fresh.quality_score - (0.5 + 6.0 * 0.05)).abs() < 1e-6);
+
+        // RisingAnalyzer's confidence tracks the sequence number, so the best
+        // frame identifies which frames were enrolled from.
+        let result = engine
+            .enroll_frames(
+                preview,
+                false,
+                0.0,
+                timeout,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.frames_captured, 3);
+        assert!((result.quality_score - (0.5 + 3.0 * 0.05)).abs() < 1e-6);
+        let next = engine.preview(1, timeout).await.unwrap();
+        assert_eq!(next.frames[0].sequence, 7, "enroll_frames must not capture");
+    }
+
+    #[tokio::test]
+    async fn test_enroll_reports_progress_per_frame() {
+        let engine = spawn_engine_with(SequenceSource { next: 0 }, None, RisingAnalyzer);
+        let timeout = Duration::from_secs(5);
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        engine
+            .enroll(
+                3,
+                false,
+                0.0,
+                timeout,
+                None,
+                Some(tx),
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        let mut updates = Vec::new();
+        while let Some(update) = rx.recv().await {
+            updates.push(update);
+        }
+        let counts: Vec<_> = updates.iter().map(|u| u.frames_captured).collect();
+        assert_eq!(counts, [1, 2, 3]);
+        assert!(updates.iter().all(|u| u.frames_total == 3));
+        assert!((updates[2].quality - (0.5 + 3.0 * 0.05)).abs() < 1e-6);
+
+        let preview = engine.preview(2, timeout).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        engine
+            .enroll_frames(
+                preview,
+                false,
+                0.0,
+                timeout,
+                None,
+                Some(tx),
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        let mut last = None;
+        while let Some(update) = rx.recv().await {
+            last = Some(update);
+        }
+        let last = last.unwrap();
+        assert_eq!((last.frames_captured, last.frames_total), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_progress_per_frame() {
+        let engine = spawn_engine_with(SequenceSource { next: 0 }, None, FixedAnalyzer);
+        let gallery = vec![model("flat", vec![1.0; 8])];
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result = engine
+            .verify(
+                gallery,
+                None,
+                0.4,
+                3,
+                Duration::from_secs(5),
+                None,
+                None,
+                None,
+                Some(tx),
+                CancelToken::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.result.matched);
+        let mut updates = Vec::new();
+        while let Some(update) = rx.recv().await {
+            updates.push((update.frames_done, update.frames_total));
+        }
+        assert_eq!(updates, [(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[tokio::test]
+    async fn test_enroll_rejects_poorly_aligned_faces() {
+        let (engine, _) = stalling_engine(0);
+        let timeout = Duration::from_secs(5);
+
+        let result = engine
+            .enroll(3, false, 0.0, timeout, None, None, CancelToken::default())
+            .await
+            .unwrap();
+        assert!(result.alignment_score > 0.5, "{}", result.alignment_score);
+
+        // The fixed landmarks are near-frontal but not a perfect template fit.
+        let result = engine
+            .enroll(3, false, 0.999, timeout, None, None, CancelToken::default())
+            .await;
+        match result {
+            Err(EngineError::PoorAlignment { best, min }) => {
+                assert!(best > 0.5 && best < 0.999, "{best}");
+                assert_eq!(min, 0.999);
+            }
+            _ => panic!("expected PoorAlignment"),
+        }
+    }
+
+    /// Poll `condition` until it holds, failing the test after five seconds.
+    async fn wait_until(what: &str, condition: impl Fn() -> bool) {
+        let started = Instant::now();
+        while !condition() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "timed out: {what}"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_status_and_cancel_current() {
+        let (engine, _) = stalling_engine(50);
+        let status = engine.queue_status();
+        assert_eq!(status.queued, 0);
+        assert!(status.in_flight.is_none());
+        assert_eq!(
+            engine.cancel_current(),
+            None,
+            "nothing to cancel while idle"
+        );
+
+        let long = Duration::from_secs(60);
+        let verify = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                engine
+                    .verify(
+                        vec![],
+                        None,
+                        0.4,
+                        10_000,
+                        long,
+                        None,
+                        None,
+                        None,
+                        None,
+                        CancelToken::default(),
+                    )
+                    .await
+            }
+        });
+        wait_until("verify in flight", || {
+            engine.queue_status().in_flight.is_some()
+        })
+        .await;
+        let enroll = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                engine
+                    .enroll(10_000, false, 0.0, long, None, None, CancelToken::default())
+                    .await
+            }
+        });
+        wait_until("enroll queued", || engine.queue_status().queued == 1).await;
+
+        let in_flight = engine.queue_status().in_flight.unwrap();
+        assert_eq!(in_flight.operation, "verify");
+        assert!(!in_flight.cancel_requested);
+
+        assert_eq!(engine.cancel_current(), Some("verify"));
+        assert!(matches!(verify.await.unwrap(), Err(EngineError::Cancelled)));
+
+        // The cancel reached only the in-flight request; the queued one runs next.
+        wait_until("enroll in flight", || {
+            let status = engine.queue_status();
+            status.queued == 0 && status.in_flight.is_some_and(|op| op.operation == "enroll")
+        })
+        .await;
+        assert_eq!(engine.cancel_current(), Some("enroll"));
+        assert!(matches!(enroll.await.unwrap(), Err(EngineError::Cancelled)));
+        wait_until("engine idle", || engine.queue_status().in_flight.is_none()).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_token_reaches_only_its_own_verify() {
+        let (engine, _) = stalling_engine(50);
+        let long = Duration::from_secs(60);
+        let spawn_verify = |cancel: CancelToken| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                engine
+                    .verify(
+                        vec![],
+                        None,
+                        0.4,
+                        10_000,
+                        long,
+                        None,
+                        None,
+                        None,
+                        None,
+                        cancel,
+                    )
+                    .await
+            })
+        };
+        let (first_token, queued_token) = (CancelToken::default(), CancelToken::default());
+        let first = spawn_verify(first_token.clone());
+        wait_until("verify in flight", || {
+            engine.queue_status().in_flight.is_some()
+        })
+        .await;
+        let queued = spawn_verify(queued_token.clone());
+        wait_until("verify queued", || engine.queue_status().queued == 1).await;
+
+        // A queued verify that is cancelled never opens the camera.
+        queued_token.cancel();
+        let in_flight = engine.queue_status().in_flight.unwrap();
+        assert!(!in_flight.cancel_requested);
+
+        let started = Instant::now();
+        first_token.cancel();
+        assert!(matches!(
+            first.await.unwrap(),
+            Err(EngineError::ClientCancelled)
+        ));
+        assert!(
+            started.elapsed() < Duration::from_millis(500),
+            "cancel took {:?}",
+            started.elapsed()
+        );
+        assert!(matches!(
+            queued.await.unwrap(),
+            Err(EngineError::ClientCancelled)
+        ));
+        wait_until("engine idle", || engine.queue_status().in_flight.is_none()).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_token_stops_an_enrollment_mid_capture() {
+        let (engine, _) = stalling_engine(50);
+        let cancel = CancelToken::default();
+        let enroll = {
+            let (engine, cancel) = (engine.clone(), cancel.clone());
+            tokio::spawn(async move {
+                engine
+                    .enroll(
+                        10_000,
+                        false,
+                        0.0,
+                        Duration::from_secs(60),
+                        None,
+                        None,
+                        cancel,
+                    )
+                    .await
+            })
+        };
+        wait_until("enroll in flight", || {
+            engine.queue_status().in_flight.is_some()
+        })
+        .await;
+
+        let started = Instant::now();
+        cancel.cancel();
+        assert!(matches!(
+            enroll.await.unwrap(),
+            Err(EngineError::ClientCancelled)
+        ));
+        assert!(
+            started.elapsed() < Duration::from_millis(500),
+            "cancel took {:?}",
+            started.elapsed()
+        );
+        wait_until("engine idle", || engine.queue_status().in_flight.is_none()).await;
+    }
+
+    #[tokio::test]
+    async fn test_verify_times_out_while_a_frame_read_blocks() {
+        // The first frame alone takes far longer than the timeout, so the
+        // engine's own between-frame check cannot fire in time.
+        let (engine, _) = stalling_engine(1_000);
+
+        let started = Instant::now();
+        let result = engine
+            .verify(
+                vec![],
+                None,
+                0.4,
+                50,
+                Duration::from_millis(150),
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EngineError::VerifyTimeout)));
+        assert!(
+            started.elapsed() < Duration::from_millis(600),
+            "timeout took {:?}",
+            started.elapsed()
+        );
+        // The abandoned request ends once the blocked read returns.
+        wait_until("engine idle", || engine.queue_status().in_flight.is_none()).await;
+    }
+
+    #[tokio::test]
+    async fn test_verify_times_out_on_stalling_camera() {
+        let (engine, _) = stalling_engine(100);
+
+        let result = engine
+            .verify(
+                vec![],
+                None,
+                0.4,
+                50,
+                Duration::from_millis(200),
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EngineError::VerifyTimeout)));
+    }
+
+    /// Analyzer with a working detector whose recognizer returns `output`,
+    /// or panics when it is `None`.
+    struct SyntheticAnalyzer {
+        output: Option<Vec<f32>>,
+    }
+
+    impl FaceAnalyzer for SyntheticAnalyzer {
+        fn detect(&mut self, _frame: &Frame) -> Result<Vec<BoundingBox>, EngineError> {
+            Ok(Vec::new())
+        }
+
+        fn extract(
+            &mut self,
+            _frame: &Frame,
+            face: &BoundingBox,
+        ) -> Result<Embedding, RecognizerError> {
+            assert!(face.landmarks.is_some());
+            let values = self.output.clone().expect("recognizer blew up");
+            Ok(Embedding::new(values, Some("test".to_string())))
+        }
+    }
+
+    #[test]
+    fn test_pipeline_selftest_rejects_malformed_recognizer_output() {
+        let mut healthy = SyntheticAnalyzer {
+            output: Some(vec![0.01; ARCFACE_EMBEDDING_DIM]),
+        };
+        assert_eq!(pipeline_selftest(&mut healthy), Ok(()));
+
+        let mut short = SyntheticAnalyzer {
+            output: Some(vec![0.01; 128]),
+        };
+        let reason = pipeline_selftest(&mut short).unwrap_err();
+        assert!(reason.contains("128-dim"), "{reason}");
+
+        let mut values = vec![0.01; ARCFACE_EMBEDDING_DIM];
+        values[7] = f32::NAN;
+        let mut nan = SyntheticAnalyzer {
+            output: Some(values),
+        };
+        let reason = pipeline_selftest(&mut nan).unwrap_err();
+        assert!(reason.contains("non-finite"), "{reason}");
+
+        let mut panicking = SyntheticAnalyzer { output: None };
+        let reason = pipeline_selftest(&mut panicking).unwrap_err();
+        assert!(reason.contains("recognizer blew up"), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn test_recognizer_load_failure_starts_degraded() {
+        let missing =
+            std::env::temp_dir().join(format!("visage-missing-{}.onnx", uuid::Uuid::new_v4()));
+        let Err(reason) = load_recognizer(missing.to_str().unwrap(), None) else {
+            panic!("missing recognizer should fail to load");
+        };
+        assert!(reason.contains("not found"), "{reason}");
+        // A half-copied file is named as such, not as an ONNX parse error.
+        std::fs::write(&missing, b"").unwrap();
+        let Err(empty) = load_recognizer(missing.to_str().unwrap(), None) else {
+            panic!("empty recognizer should fail to load");
+        };
+        std::fs::remove_file(&missing).unwrap();
+        assert!(
+            empty.contains("is empty") && empty.contains("visage setup"),
+            "{empty}"
+        );
+        assert!(matches!(
+            load_recognizer("unused", Some("checksum mismatch".into())),
+            Err(r) if r == "checksum mismatch"
+        ));
+
+        let source = StallingSource {
+            delay_ms: Arc::new(AtomicU64::new(0)),
+        };
+        let engine = spawn_engine_with(source, None, DetectorOnlyAnalyzer(reason.clone()));
+        assert_eq!(engine.recognizer_fault().as_deref(), Some(reason.as_str()));
+
+        let timeout = Duration::from_secs(5);
+        let result = engine
+            .verify(
+                vec![model("flat", vec![1.0; 8])],
+                None,
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EngineError::RecognizerUnavailable(r)) if r == reason));
+        let result = engine
+            .enroll(3, false, 0.0, timeout, None, None, CancelToken::default())
+            .await;
+        assert!(matches!(result, Err(EngineError::RecognizerUnavailable(_))));
+
+        // Exposure diagnostics need only the camera.
+        let stats = engine.exposure_report(2, timeout).await.unwrap();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.verdict, visage_hw::Exposure::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_models_or_keeps_the_old_ones() {
+        let staged = Arc::new(Mutex::new(Err("detector: truncated file".to_string())));
+        let analyzer = ReloadableAnalyzer {
+            embedding: Err("w600k_r50.onnx checksum mismatch".to_string()),
+            staged: staged.clone(),
+        };
+        let source = StallingSource {
+            delay_ms: Arc::new(AtomicU64::new(0)),
+        };
+        let engine = spawn_engine_with(source, None, analyzer);
+        let observer = engine.clone();
+        let timeout = Duration::from_secs(5);
+        let verify = || {
+            engine.verify(
+                vec![model("flat", vec![1.0; 8])],
+                None,
+                0.4,
+                3,
+                timeout,
+                None,
+                None,
+                None,
+                None,
+                CancelToken::default(),
+            )
+        };
+
+        // A failed reload leaves the engine degraded as it was.
+        let err = engine.reload_models().await.unwrap_err();
+        assert!(
+            matches!(&err, EngineError::ReloadFailed(r) if r.contains("truncated")),
+            "{err:?}"
+        );
+        assert!(observer.recognizer_fault().is_some());
+        assert!(matches!(
+            verify().await,
+            Err(EngineError::RecognizerUnavailable(_))
+        ));
+
+        // Good models swap in and clear the fault for every clone.
+        *staged.lock().unwrap() = Ok(vec![1.0; 8]);
+        engine.reload_models().await.unwrap();
+        assert_eq!(observer.recognizer_fault(), None);
+        assert!(verify().await.unwrap().result.matched);
+
+        // A later bad reload keeps the working models.
+        *staged.lock().unwrap() = Err("recognizer: self-test failed".to_string());
+        assert!(engine.reload_models().await.is_err());
+        assert_eq!(observer.recognizer_fault(), None);
+        assert!(verify().await.unwrap().result.matched);
+
+        // Engines that cannot reload say so.
+        let (fixed, _) = stalling_engine(0);
+        assert!(matches!(
+            fixed.reload_models().await,
+            Err(EngineError::ReloadFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_frame_scores_fixture_deterministically() {
+        let fixture = crate::frame_input::tests::fixture_png_base64(64, 48);
+        let source = StallingSource {
+            delay_ms: Arc::new(AtomicU64::new(0)),
+        };
+        let engine = spawn_engine_with(source, None, StripAnalyzer);
+        let gallery = vec![
+            model("flat", vec![1.0; 8]),
+            model(
+                "alternating",
+                vec![1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0],
+            ),
+        ];
+
+        let mut scores = Vec::new();
+        for _ in 0..2 {
+            let frame = crate::frame_input::decode_png_base64(&fixture).unwrap();
+            let report = engine
+                .verify_frame(frame, gallery.clone(), 0.9)
+                .await
+                .unwrap();
+            assert_eq!(report.faces_detected, 1);
+            assert!(report.matched);
+            assert_eq!(report.model_id.as_deref(), Some("flat"));
+            assert_eq!(report.similarities.len(), 2);
+            assert!(report.similarities[1].similarity < 0.0);
+            scores.push(report.similarity);
+        }
+
+        // Strip means of the fixture gradient against a flat vector.
+        assert!((scores[0] - 0.975_797_6).abs() < 1e-5, "{}", scores[0]);
+        assert_eq!(scores[0], scores[1]);
+    }
+
+    #[tokio::test]
+    async fn test_self_test_runs_detector_on_raw_frames() {
+        let engine = spawn_engine_with(
+            StallingSource {
+                delay_ms: Arc::new(AtomicU64::new(0)),
+            },
+            None,
+            DetectorOnlyAnalyzer("recognizer missing".into()),
+        );
+        let report = engine
+            .self_test(3, Duration::from_secs(5), true)
+            .await
+            .unwrap();
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.faces_detected, 3);
+        assert!((report.mean_brightness - 128.0).abs() < 1.0);
+        // No emitter is configured, so none was lit.
+        assert!(!report.emitter_used);
+
+        let broken = Arc::new(AtomicBool::new(true));
+        let engine = EngineHandle::flaky_camera(broken.clone());
+        let result = engine.self_test(3, Duration::from_secs(5), false).await;
+        assert!(matches!(result, Err(EngineError::Camera(_))));
+        broken.store(false, Ordering::SeqCst);
+        let report = engine
+            .self_test(3, Duration::from_secs(5), false)
+            .await
+            .unwrap();
+        assert_eq!((report.frames, report.faces_detected), (3, 0));
+    }
+
+    /// Camera that waits `delays_ms[i]` before frame `i` and numbers its
+    /// frames `sequences[i]`, and reports `dark` skipped frames.
+    struct PacedSource {
+        delays_ms: Vec<u64>,
+        sequences: Vec<u32>,
+        dark: usize,
+    }
+
+    impl FrameSource for PacedSource {
+        fn capture(
+            &mut self,
+            count: usize,
+            _deadline: Instant,
+            on_frame: &mut dyn FnMut(Frame) -> ControlFlow<()>,
+        ) -> Result<usize, CameraError> {
+            for (delay, sequence) in self.delays_ms.iter().zip(&self.sequences).take(count) {
+                std::thread::sleep(Duration::from_millis(*delay));
+                if on_frame(test_frame(*sequence)).is_break() {
+                    break;
+                }
+            }
+            Ok(self.dark)
+        }
+
+        fn capture_raw(
+            &mut self,
+            _count: usize,
+            _deadline: Instant,
+        ) -> Result<Vec<Frame>, CameraError> {
+            unreachable!("the benchmark uses the verify capture path")
+        }
+    }
+
+    #[test]
+    fn test_camera_benchmark_summarizes_frame_arrivals() {
+        let started = Instant::now();
+        let at = |ms| started + Duration::from_millis(ms);
+        // Intervals of 10, 30, 10 and 30 ms; frames 3 and 6 never arrive,
+        // and one of the two was a dark frame.
+        let arrivals = [
+            (at(50), 0),
+            (at(60), 1),
+            (at(90), 2),
+            (at(100), 4),
+            (at(130), 5),
+        ];
+        let report = CameraBenchmark::measure(6, started, &arrivals, 1, false);
+        assert_eq!((report.frames_requested, report.frames), (6, 5));
+        assert_eq!((report.dark_frames, report.dropped_frames), (1, 0));
+        assert!((report.first_frame_ms - 50.0).abs() < 1e-9);
+        assert!((report.mean_interval_ms - 20.0).abs() < 1e-9);
+        assert!((report.fps - 50.0).abs() < 1e-9);
+        assert!((report.jitter_ms - 10.0).abs() < 1e-9);
+        assert!((report.max_interval_ms - 30.0).abs() < 1e-9);
+
+        let report = CameraBenchmark::measure(6, started, &arrivals, 0, false);
+        assert_eq!(report.dropped_frames, 1);
+        let single = CameraBenchmark::measure(6, started, &arrivals[..1], 0, false);
+        assert_eq!((single.fps, single.jitter_ms), (0.0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_camera_benchmark_times_a_paced_camera() {
+        let engine = spawn_engine_with(
+            PacedSource {
+                delays_ms: vec![5, 20, 60, 20, 60, 20, 60],
+                sequences: vec![0, 1, 2, 3, 4, 7, 8],
+                dark: 1,
+            },
+            None,
+            DetectorOnlyAnalyzer("recognizer missing".into()),
+        );
+        let report = engine
+            .camera_benchmark(7, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(report.frames, 7);
+        assert_eq!((report.dark_frames, report.dropped_frames), (1, 1));
+        assert!(!report.emitter_active);
+        // Intervals alternate 20 and 60 ms: 40 ms apart on average, at 25
+        // fps. Sleeps only overshoot, by however much a loaded machine
+        // adds, so only the floors and the ordering are fixed.
+        assert!(report.first_frame_ms >= 5.0, "{report:?}");
+        assert!(report.mean_interval_ms >= 40.0, "{report:?}");
+        assert!(report.max_interval_ms >= 60.0, "{report:?}");
+        assert!(
+            report.max_interval_ms > report.mean_interval_ms,
+            "{report:?}"
+        );
+        assert!(report.fps > 0.0 && report.fps <= 25.0, "{report:?}");
+        assert!(report.jitter_ms > 0.0, "{report:?}");
+    }
 }
@@ -0,0 +1,122 @@
+//! Structured D-Bus errors returned by `org.freedesktop.Visage1`.
+//!
+//! Each variant maps to the error name `org.freedesktop.Visage1.Error.<Variant>`
+//! so clients can match on the name instead of parsing message text.
+
+/// Errors returned by Visage D-Bus methods.
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.Visage1.Error")]
+pub enum VisageError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// Generic failure; the message describes the cause.
+    Failed(String),
+    /// The caller is not permitted to perform this operation.
+    AccessDenied(String),
+    /// An argument was malformed or out of range.
+    InvalidArgs(String),
+    /// The operation did not complete within its configured time limit.
+    Timeout(String),
+    /// The recognizer model failed to load; the daemon is running degraded
+    /// and cannot enroll or verify until models are restored.
+    RecognizerUnavailable(String),
+    /// The engine stopped responding and was replaced by the watchdog; the
+    /// request was not completed and may be retried.
+    EngineRestarted(String),
+    /// The user is pinned to a recognizer model version other than the one
+    /// this daemon runs.
+    ModelMismatch(String),
+    /// Site policy does not permit the operation right now (e.g. outside the
+    /// configured verify windows). Clients should fall back silently.
+    PolicyDenied(String),
+    /// The user has no enrolled face models. Routine for accounts that never
+    /// enrolled; clients should fall back silently.
+    NotEnrolled(String),
+    /// The face store could not be read. The message starts with the error
+    /// class (e.g. `database`, `decryption`); worth alerting on.
+    StoreUnavailable(String),
+    /// The request was cancelled: by an administrator (`CancelCurrent`), in
+    /// which case it may be retried, or by the client itself (`Cancel`).
+    Cancelled(String),
+    /// The calling connection exceeded its request limits (see
+    /// [`crate::request_limit`]). The message says when to retry.
+    TooManyRequests(String),
+    /// The account database (NSS: sssd, LDAP, …) could not be queried, so the
+    /// daemon cannot tell whether the caller may act for the user. Transient;
+    /// clients should fall back at once rather than wait.
+    DirectoryUnavailable(String),
+    /// The user is locked out after repeated failed verifies, or enrolled
+    /// too recently. Built by [`VisageError::rate_limited`], so the message
+    /// always starts with `retry after <seconds>s: `.
+    RateLimited(String),
+    /// No face was found in any captured frame.
+    NoFaceDetected(String),
+    /// The camera could not be opened or stopped delivering frames.
+    CameraUnavailable(String),
+    /// The capture did not pass the liveness check (enrollment; a verify
+    /// that fails liveness is a non-match instead, see `Verify`).
+    LivenessFailed(String),
+    /// The new enrollment nearly repeats one of the user's templates; the
+    /// message names it. Nothing was stored.
+    DuplicateEnrollment(String),
+    /// The frames of one verify showed different people
+    /// (`VISAGE_VERIFY_CONSISTENCY_FLOOR`). Counts as a failed attempt.
+    FaceChangedDuringCapture(String),
+}
+
+impl VisageError {
+    /// `RateLimited` for a wait of `retry_after` (rounded up to whole
+    /// seconds). The error body carries one string, so the seconds lead the
+    /// message where clients can parse them.
+    pub fn rate_limited(retry_after: std::time::Duration, reason: &str) -> Self {
+        let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        Self::RateLimited(format!("retry after {secs}s: {reason}"))
+    }
+}
+
+impl From<zbus::fdo::Error> for VisageError {
+    fn from(e: zbus::fdo::Error) -> Self {
+        match e {
+            zbus::fdo::Error::AccessDenied(msg) => Self::AccessDenied(msg),
+            zbus::fdo::Error::Failed(msg) => Self::Failed(msg),
+            zbus::fdo::Error::InvalidArgs(msg) => Self::InvalidArgs(msg),
+            zbus::fdo::Error::ZBus(e) => Self::ZBus(e),
+            other => Self::Failed(other.to_string()),
+        }
+    }
+}
+
+impl From<crate::engine::EngineError> for VisageError {
+    fn from(e: crate::engine::EngineError) -> Self {
+        match e {
+            crate::engine::EngineError::RecognizerUnavailable(_) => {
+                Self::RecognizerUnavailable(e.to_string())
+            }
+            crate::engine::EngineError::EngineRestarted => Self::EngineRestarted(e.to_string()),
+            crate::engine::EngineError::NoFaceDetected => Self::NoFaceDetected(e.to_string()),
+            crate::engine::EngineError::Camera(_) => Self::CameraUnavailable(e.to_string()),
+            crate::engine::EngineError::LivenessCheckFailed { .. } => {
+                Self::LivenessFailed(e.to_string())
+            }
+            crate::engine::EngineError::FaceChangedDuringCapture { .. } => {
+                Self::FaceChangedDuringCapture(e.to_string())
+            }
+            crate::engine::EngineError::Cancelled | crate::engine::EngineError::ClientCancelled => {
+                Self::Cancelled(e.to_string())
+            }
+            e => Self::Failed(e.to_string()),
+        }
+    }
+}
+
+impl From<crate::store::StoreError> for VisageError {
+    fn from(e: crate::store::StoreError) -> Self {
+        Self::StoreUnavailable(format!("{}: {e}", e.class()))
+    }
+}
+
+impl From<crate::username::UsernameError> for VisageError {
+    fn from(e: crate::username::UsernameError) -> Self {
+        Self::InvalidArgs(e.to_string())
+    }
+}
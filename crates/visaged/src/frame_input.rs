@@ -0,0 +1,113 @@
+//! Decoding of caller-supplied frames for camera-less verification.
+//!
+//! `VerifyFrame` accepts a base64-encoded PNG so a "golden" frame captured
+//! once can be replayed through detection, extraction and matching whenever
+//! models or preprocessing change. The image is converted to 8-bit grayscale
+//! and used as-is — no CLAHE or dark-frame filtering is applied.
+
+use base64::Engine as _;
+use std::io::Cursor;
+use std::time::Instant;
+use thiserror::Error;
+use visage_hw::Frame;
+
+/// Smallest accepted side length; SCRFD needs room for a detectable face.
+pub const MIN_FRAME_DIM: u32 = 32;
+/// Largest accepted side length (covers 4K sensors with margin).
+pub const MAX_FRAME_DIM: u32 = 4096;
+/// Upper bound on the base64 payload, checked before decoding.
+const MAX_ENCODED_LEN: usize = 32 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum FrameInputError {
+    #[error("frame payload exceeds {MAX_ENCODED_LEN} bytes")]
+    TooLarge,
+    #[error("invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("invalid PNG: {0}")]
+    Image(#[from] image::ImageError),
+    #[error(
+        "frame is {width}x{height}; each side must be between \
+         {MIN_FRAME_DIM} and {MAX_FRAME_DIM} pixels"
+    )]
+    Dimensions { width: u32, height: u32 },
+}
+
+/// Decode a base64 PNG into a grayscale [`Frame`], validating its dimensions.
+///
+/// Dimensions are checked from the PNG header before pixel data is decoded,
+/// so an oversized image is rejected without allocating its buffer.
+pub fn decode_png_base64(encoded: &str) -> Result<Frame, FrameInputError> {
+    if encoded.len() > MAX_ENCODED_LEN {
+        return Err(FrameInputError::TooLarge);
+    }
+    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded.trim())?;
+
+    let reader = image::ImageReader::with_format(Cursor::new(&bytes), image::ImageFormat::Png);
+    let (width, height) = reader.into_dimensions()?;
+    let in_range = |d: u32| (MIN_FRAME_DIM..=MAX_FRAME_DIM).contains(&d);
+    if !in_range(width) || !in_range(height) {
+        return Err(FrameInputError::Dimensions { width, height });
+    }
+
+    let gray = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)?.to_luma8();
+    let data = gray.into_raw();
+    Ok(Frame {
+        raw_brightness: visage_hw::frame::mean_brightness(&data),
+        data,
+        width,
+        height,
+        timestamp: Instant::now(),
+        sequence: 0,
+        is_dark: false,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Encode a deterministic grayscale gradient as a base64 PNG fixture.
+    pub(crate) fn fixture_png_base64(width: u32, height: u32) -> String {
+        let img = image::GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([((x * 7 + y * 3) % 256) as u8])
+        });
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        base64::engine::general_purpose::STANDARD.encode(png)
+    }
+
+    #[test]
+    fn test_decodes_fixture() {
+        let frame = decode_png_base64(&fixture_png_base64(64, 48)).unwrap();
+        assert_eq!((frame.width, frame.height), (64, 48));
+        assert_eq!(frame.data.len(), 64 * 48);
+        assert_eq!(frame.data[1], 7);
+        assert_eq!(frame.data[64], 3);
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_dimensions() {
+        assert!(matches!(
+            decode_png_base64(&fixture_png_base64(16, 48)),
+            Err(FrameInputError::Dimensions {
+                width: 16,
+                height: 48
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!(matches!(
+            decode_png_base64("not base64!"),
+            Err(FrameInputError::Base64(_))
+        ));
+        let not_png = base64::engine::general_purpose::STANDARD.encode(b"GIF89a....");
+        assert!(matches!(
+            decode_png_base64(&not_png),
+            Err(FrameInputError::Image(_))
+        ));
+    }
+}
@@ -0,0 +1,503 @@
+//! Tracing subscriber setup with a runtime-reloadable filter.
+//!
+//! Log lines are human-readable text by default. `VISAGE_LOG_FORMAT=json`
+//! writes one JSON object per line instead, for log pipelines: the keys are
+//! always `timestamp`, `level`, `target`, `op_id`, `user`, `message` and
+//! `fields` (any other event fields), with `null` for absent values. String
+//! values are cut at [`MAX_FIELD_BYTES`] so one pathological error message
+//! cannot produce a megabyte log line.
+//!
+//! In privacy mode (`VISAGE_PRIVACY_MODE=1`) a [`PrivacyLayer`] sits in front
+//! of every output and drops each event that names a user or one of their
+//! face models, except audit records, so the logs keep no history of who used
+//! face unlock or when. The audit records it keeps have their model IDs and
+//! labels replaced with [`REDACTED`].
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use thiserror::Error;
+use tracing::field::{Field, Visit};
+use tracing::span;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Handle for replacing the daemon's active `EnvFilter`.
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+#[derive(Error, Debug)]
+pub enum LogLevelError {
+    #[error("invalid log directive '{directive}': {reason}")]
+    InvalidDirective { directive: String, reason: String },
+    #[error("failed to apply log directive: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Log line format (`VISAGE_LOG_FORMAT`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(other.to_string()),
+        }
+    }
+}
+
+/// Longest string value, in bytes, a JSON log line carries before it is cut.
+pub const MAX_FIELD_BYTES: usize = 4096;
+
+/// Fields naming one of a user's face models. A label is often the user's
+/// own wording and a model ID leads back to them through the store.
+const MODEL_FIELDS: &[&str] = &[
+    "model_id",
+    "label",
+    "model_label",
+    "new_label",
+    "duplicate_of",
+];
+
+/// What privacy mode writes in place of a [`MODEL_FIELDS`] value.
+pub const REDACTED: &str = "[redacted]";
+
+/// Install the global subscriber (filter from `RUST_LOG`, lines in `format`)
+/// and return a handle that can swap the filter without restarting the daemon.
+/// `privacy_mode` adds a [`PrivacyLayer`], which the handle cannot remove.
+pub fn init(format: LogFormat, privacy_mode: bool) -> FilterHandle {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(privacy_mode.then_some(PrivacyLayer));
+    let text = tracing_subscriber::fmt::layer();
+    match (format, privacy_mode) {
+        (LogFormat::Text, false) => registry.with(text).init(),
+        (LogFormat::Text, true) => registry.with(text.fmt_fields(redacted_fields())).init(),
+        (LogFormat::Json, _) => registry
+            .with(JsonLayer::new(std::io::stdout).redacting(privacy_mode))
+            .init(),
+    }
+    handle
+}
+
+/// Disables every event with a `user` field, or a field naming a face model,
+/// unless it is on the audit target.
+///
+/// Filtering on the field rather than on call sites means a new log line
+/// about a user is covered without anyone remembering to check the flag.
+pub struct PrivacyLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for PrivacyLayer {
+    fn event_enabled(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let meta = event.metadata();
+        let fields = meta.fields();
+        meta.target() == crate::polkit::AUDIT_TARGET
+            || !std::iter::once("user")
+                .chain(MODEL_FIELDS.iter().copied())
+                .any(|name| fields.field(name).is_some())
+    }
+}
+
+/// Text field formatting that writes [`REDACTED`] for [`MODEL_FIELDS`].
+fn redacted_fields() -> impl for<'w> tracing_subscriber::fmt::FormatFields<'w> + 'static {
+    use tracing_subscriber::field::MakeExt;
+
+    tracing_subscriber::fmt::format::debug_fn(|writer, field, value| match field.name() {
+        "message" => write!(writer, "{value:?}"),
+        name if MODEL_FIELDS.contains(&name) => write!(writer, "{name}={REDACTED}"),
+        name => write!(writer, "{name}={value:?}"),
+    })
+    .delimited(" ")
+}
+
+/// Writes each event as one JSON object per line (see the module docs).
+pub struct JsonLayer<W> {
+    make_writer: W,
+    redact: bool,
+}
+
+impl<W> JsonLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self {
+            make_writer,
+            redact: false,
+        }
+    }
+
+    /// Write [`REDACTED`] for [`MODEL_FIELDS`] values when `redact` is set.
+    pub fn redacting(mut self, redact: bool) -> Self {
+        self.redact = redact;
+        self
+    }
+}
+
+/// `op_id` of a span, kept so events inside it can report it.
+struct OpId(String);
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        attrs.record(&mut fields);
+        if let (Some(op_id), Some(span)) = (fields.op_id, ctx.span(id)) {
+            span.extensions_mut().insert(OpId(op_id));
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        if self.redact {
+            for name in MODEL_FIELDS {
+                if let Some(value) = fields.rest.get_mut(*name) {
+                    *value = REDACTED.into();
+                }
+            }
+        }
+        let op_id = fields.op_id.or_else(|| {
+            ctx.event_scope(event)?
+                .find_map(|span| span.extensions().get::<OpId>().map(|op| op.0.clone()))
+        });
+        let meta = event.metadata();
+        let line = serde_json::json!({
+            "timestamp": chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            "level": meta.level().as_str(),
+            "target": meta.target(),
+            "op_id": op_id,
+            "user": fields.user,
+            "message": fields.message,
+            "fields": fields.rest,
+        });
+        let mut bytes = line.to_string().into_bytes();
+        bytes.push(b'\n');
+        // Nowhere to report a failed log write.
+        let _ = self.make_writer.make_writer().write_all(&bytes);
+    }
+}
+
+/// Event or span fields, sorted into the fixed JSON keys.
+#[derive(Default)]
+struct JsonFields {
+    message: Option<String>,
+    op_id: Option<String>,
+    user: Option<String>,
+    rest: serde_json::Map<String, serde_json::Value>,
+}
+
+impl JsonFields {
+    fn insert(&mut self, field: &Field, value: serde_json::Value) {
+        let slot = match field.name() {
+            "message" => &mut self.message,
+            "op_id" => &mut self.op_id,
+            "user" => &mut self.user,
+            name => {
+                self.rest.insert(name.to_string(), value);
+                return;
+            }
+        };
+        *slot = Some(match value {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        });
+    }
+}
+
+impl Visit for JsonFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, truncate(value.to_string()).into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, truncate(format!("{value:?}")).into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+}
+
+/// Cut `value` to at most [`MAX_FIELD_BYTES`] (on a character boundary),
+/// noting how much was dropped.
+fn truncate(mut value: String) -> String {
+    if value.len() <= MAX_FIELD_BYTES {
+        return value;
+    }
+    let mut cut = MAX_FIELD_BYTES;
+    while !value.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let dropped = value.len() - cut;
+    value.truncate(cut);
+    value.push_str(&format!("…[{dropped} bytes truncated]"));
+    value
+}
+
+/// Parse `directive` (same syntax as `RUST_LOG`) and make it the active filter.
+///
+/// Malformed directives are rejected and leave the current filter untouched.
+pub fn set_directive<S>(
+    handle: &reload::Handle<EnvFilter, S>,
+    directive: &str,
+) -> Result<(), LogLevelError> {
+    let filter = EnvFilter::try_new(directive).map_err(|e| LogLevelError::InvalidDirective {
+        directive: directive.to_string(),
+        reason: e.to_string(),
+    })?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the target of every event that passes the filter.
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Capture {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(event.metadata().target().to_string());
+        }
+    }
+
+    #[test]
+    fn test_reload_enables_suppressed_target() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (filter, handle) = reload::Layer::new(EnvFilter::new("warn"));
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(Capture(seen.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(target: "visaged::engine", "before reload");
+            assert!(seen.lock().unwrap().is_empty());
+
+            set_directive(&handle, "warn,visaged::engine=debug").unwrap();
+            tracing::debug!(target: "visaged::engine", "after reload");
+            tracing::debug!(target: "visaged::store", "still suppressed");
+        });
+
+        assert_eq!(*seen.lock().unwrap(), vec!["visaged::engine".to_string()]);
+    }
+
+    #[test]
+    fn test_malformed_directive_rejected() {
+        let (_filter, handle) = reload::Layer::<_, Registry>::new(EnvFilter::new("warn"));
+        let err = set_directive(&handle, "visaged=loud").unwrap_err();
+        assert!(matches!(err, LogLevelError::InvalidDirective { .. }));
+    }
+
+    /// Test writer collecting everything a layer writes.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn json_lines(emit: impl FnOnce()) -> Vec<serde_json::Value> {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber =
+            tracing_subscriber::registry().with(JsonLayer::new(move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, emit);
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line is one JSON object"))
+            .collect()
+    }
+
+    #[test]
+    fn test_privacy_mode_keeps_only_audit_records_about_users() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(Some(PrivacyLayer))
+            .with(JsonLayer::new(move || writer.clone()).redacting(true));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user = "alice", "verify requested");
+            // SetLogLevel cannot bring the dropped events back.
+            set_directive(&handle, "trace").unwrap();
+            let mut limiter = crate::rate_limiter::RateLimiter::new();
+            for _ in 0..5 {
+                limiter.record_failure("alice");
+            }
+            tracing::info!(
+                target: crate::polkit::AUDIT_TARGET,
+                user = "alice",
+                model_id = "m1",
+                "model removed"
+            );
+            // A model without a user still leads back to one.
+            tracing::info!(
+                model_id = "m2",
+                label = "alice-glasses",
+                "template excluded"
+            );
+            tracing::info!(frames = 3u64, "camera opened");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let messages: Vec<&str> = lines
+            .iter()
+            .map(|line| line["message"].as_str().unwrap())
+            .collect();
+        assert_eq!(messages, ["model removed", "camera opened"]);
+        assert!(!output.contains("lockout") && !output.contains("verify"));
+        // The audit record keeps the user but not which model.
+        assert_eq!(lines[0]["user"], "alice");
+        assert_eq!(lines[0]["fields"]["model_id"], REDACTED);
+        assert!(!output.contains("m1") && !output.contains("glasses"));
+    }
+
+    #[test]
+    fn test_privacy_mode_redacts_models_in_text_audit_lines() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(PrivacyLayer).with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(move || writer.clone())
+                .with_ansi(false)
+                .fmt_fields(redacted_fields()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                target: crate::polkit::AUDIT_TARGET,
+                user = "alice",
+                model_id = "m1",
+                label = "alice-glasses",
+                "enroll complete"
+            );
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            output.contains(r#"enroll complete user="alice" model_id=[redacted] label=[redacted]"#),
+            "{output}"
+        );
+        assert!(
+            !output.contains("m1") && !output.contains("glasses"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn test_json_lines_have_fixed_keys() {
+        let lines = json_lines(|| {
+            tracing::warn!(target: "visaged::dbus_interface", user = "alice", "verify: rate limited");
+            let span = tracing::info_span!("verify", op_id = "op-7");
+            span.in_scope(|| tracing::info!(frames = 3u64, matched = false, "verify done"));
+        });
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            let mut keys: Vec<&str> = line
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|k| k.as_str())
+                .collect();
+            keys.sort_unstable();
+            assert_eq!(
+                keys,
+                [
+                    "fields",
+                    "level",
+                    "message",
+                    "op_id",
+                    "target",
+                    "timestamp",
+                    "user"
+                ]
+            );
+            assert!(
+                chrono::DateTime::parse_from_rfc3339(line["timestamp"].as_str().unwrap()).is_ok()
+            );
+        }
+        assert_eq!(lines[0]["level"], "WARN");
+        assert_eq!(lines[0]["target"], "visaged::dbus_interface");
+        assert_eq!(lines[0]["user"], "alice");
+        assert_eq!(lines[0]["message"], "verify: rate limited");
+        assert_eq!(lines[0]["op_id"], serde_json::Value::Null);
+
+        assert_eq!(lines[1]["op_id"], "op-7");
+        assert_eq!(lines[1]["user"], serde_json::Value::Null);
+        assert_eq!(
+            lines[1]["fields"],
+            serde_json::json!({"frames": 3, "matched": false})
+        );
+    }
+
+    #[test]
+    fn test_json_truncates_oversized_fields() {
+        let huge = "é".repeat(MAX_FIELD_BYTES);
+        let lines = json_lines(|| tracing::error!(error = %huge, "store failed"));
+        let error = lines[0]["fields"]["error"].as_str().unwrap();
+        assert!(error.len() < MAX_FIELD_BYTES + 64, "{} bytes", error.len());
+        assert!(error.ends_with(&format!("…[{} bytes truncated]", MAX_FIELD_BYTES)));
+        assert_eq!(lines[0]["message"], "store failed");
+    }
+
+    #[test]
+    fn test_log_format_parses() {
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!("text".parse(), Ok(LogFormat::Text));
+        assert_eq!("xml".parse::<LogFormat>(), Err("xml".to_string()));
+    }
+}
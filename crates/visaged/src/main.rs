@@ -1,77 +1,200 @@
+// The Status reply is one `serde_json::json!` literal with more fields than
+// the default macro recursion limit allows.
+#![recursion_limit = "256"]
+
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use anyhow::{Context, Result};
-use tracing_subscriber::EnvFilter;
 
+mod capabilities;
+mod clock;
 mod config;
 mod dbus_interface;
+mod device_settings;
 mod engine;
+mod error;
+mod frame_input;
+mod logging;
+mod polkit;
+mod preview;
 mod rate_limiter;
+mod reload;
+mod request_limit;
+mod schedule;
+mod selftest;
+mod stats;
+mod status;
 mod store;
+mod username;
+mod verify_cancel;
+mod verify_grace;
+mod verify_policy;
+mod verify_token;
+mod watchdog;
 
 use config::Config;
 use dbus_interface::{AppState, VisageService};
 use engine::spawn_engine;
-use rate_limiter::RateLimiter;
+use preview::PreviewSessions;
+use stats::QualityStats;
 use store::FaceModelStore;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-
-    tracing::info!("visaged starting");
+    // 1. Load configuration (before logging, which it configures)
+    let mut config = Config::load().context("invalid configuration")?;
+    let log_filter = logging::init(config.log_format, config.privacy_mode);
 
-    // 1. Load configuration
-    let config = Config::from_env();
+    tracing::info!(log_format = config.log_format.as_str(), "visaged starting");
     tracing::info!(
         camera = %config.camera_device,
         model_dir = %config.model_dir.display(),
         db_path = %config.db_path.display(),
         threshold = config.similarity_threshold,
         session_bus = config.session_bus,
+        privacy_mode = config.privacy_mode,
+        config_file = ?config.config_file,
         "configuration loaded"
     );
+    for warning in &config.warnings {
+        tracing::warn!(%warning, "configuration warning");
+    }
+    config.validate().context("invalid configuration")?;
+    if !config.verify_schedule.is_unrestricted() {
+        tracing::info!(
+            allowed = ?config.verify_schedule.allowed(),
+            blocked = ?config.verify_schedule.blocked(),
+            "verify restricted to time windows (daemon local time)"
+        );
+    }
 
-    visage_models::verify_models_dir(&config.model_dir)
-        .map_err(anyhow::Error::from)
-        .with_context(|| {
-            format!(
-                "model integrity verification failed for {}; run `sudo visage setup` to download verified ONNX models",
-                config.model_dir.display()
+    // Only the detector is mandatory. A missing or corrupt recognizer starts the
+    // daemon degraded: Status and model management stay up, enroll/verify fail.
+    let manifest = visage_models::Manifest::load_or_builtin(config.model_manifest.as_deref())
+        .context("cannot load the model checksum manifest")?;
+    if let Some(source) = manifest.source() {
+        tracing::warn!(
+            manifest = %source.display(),
+            "verifying models against a site manifest instead of the built-in checksums"
+        );
+    }
+    let arcface_path = config.arcface_model_path();
+    for model in visage_models::MODELS {
+        let path = config.model_dir.join(model.name);
+        // The recognizer is checked by every engine spawn below.
+        if path.as_os_str() == arcface_path.as_str() {
+            continue;
+        }
+        manifest
+            .verify(model, &path)
+            .map_err(anyhow::Error::from)
+            .with_context(|| {
+                format!(
+                    "model integrity verification failed for {}; run `sudo visage setup` to download verified ONNX models",
+                    config.model_dir.display()
+                )
+            })?;
+    }
+
+    // 2. Spawn engine (opens camera, loads models — fail-fast except the recognizer).
+    //    The watchdog reuses this to replace a wedged engine.
+    let respawn: watchdog::Respawn = {
+        let camera_device = config.camera_device.clone();
+        let scrfd_path = config.scrfd_model_path();
+        let model_dir = config.model_dir.clone();
+        let arcface_path = arcface_path.clone();
+        let manifest = manifest.clone();
+        let startup_selftest = config.startup_selftest;
+        let warmup_frames = config.warmup_frames;
+        let emitter_enabled = config.emitter_enabled;
+        let lazy_keep_open = config
+            .lazy_camera
+            .then(|| std::time::Duration::from_secs(config.lazy_camera_keep_open_secs));
+        Arc::new(move || {
+            // Rechecked on each spawn: a watchdog restart after `ReloadModels`
+            // must load what is on disk now, not what was there at startup.
+            spawn_engine(
+                &camera_device,
+                &scrfd_path,
+                &arcface_path,
+                recognizer_fault(&manifest, &model_dir, &arcface_path),
+                startup_selftest,
+                warmup_frames,
+                emitter_enabled,
+                lazy_keep_open,
             )
-        })?;
-
-    // 2. Spawn engine (opens camera, loads models — fail-fast)
-    let engine = spawn_engine(
-        &config.camera_device,
-        &config.scrfd_model_path(),
-        &config.arcface_model_path(),
-        config.warmup_frames,
-        config.emitter_enabled,
-    )?;
-    tracing::info!("engine started");
+        })
+    };
+    let engine = respawn()?;
+    if engine.recognizer_fault().is_some() {
+        tracing::warn!("engine started degraded — enroll and verify unavailable");
+    } else {
+        tracing::info!("engine started");
+    }
 
     // 3. Open face model store (creates DB if needed)
-    let store = FaceModelStore::open(&config.db_path).await?;
+    let store = FaceModelStore::open(
+        &config.db_path,
+        config.db_read_connections,
+        config.encrypt_metadata,
+    )
+    .await?;
     let model_count = store.count_all().await.unwrap_or(0);
     tracing::info!(db = %config.db_path.display(), models = model_count, "store opened");
+    if config.encrypt_metadata {
+        tracing::info!("template labels and poses are stored encrypted");
+    }
+
+    // Settings calibrated for this camera replace the configured defaults.
+    if let Some(device_id) = visage_hw::quirks::get_device_id(&config.camera_device) {
+        if let Err(e) = device_settings::apply_stored(&mut config, &store, &device_id).await {
+            tracing::warn!(device_id, error = %e, "cannot read stored camera settings");
+        }
+    }
 
     // 4. Register D-Bus service on system bus (or session bus in development mode).
     //    Set VISAGE_SESSION_BUS=1 to use the session bus without elevated privileges.
     let session_bus = config.session_bus;
+    let watchdog_secs = config.engine_watchdog_secs;
+    let selftest_hours = config.selftest_interval_hours;
+    let rate_limiter = config.rate_limiter();
+    let request_limits = config.request_limits();
+    let enroll_cooldown = config.enroll_cooldown();
+    let verify_tokens = config.verify_tokens();
+    let verify_grace = config.verify_grace();
+    let previews = PreviewSessions::new(std::time::Duration::from_secs(config.preview_ttl_secs));
     let state = Arc::new(Mutex::new(AppState {
         config,
         engine,
         store,
-        rate_limiter: RateLimiter::new(),
+        rate_limiter,
+        enroll_cooldown,
+        previews,
+        verify_tokens,
+        verify_grace,
+        pending_verifies: verify_cancel::PendingVerifies::default(),
+        stats: QualityStats::new(),
+        selftest: selftest::SelfTestState::default(),
+        log_filter: Some(log_filter),
+        authority: Arc::new(polkit::PolkitAuthority::default()),
     }));
 
-    let service = VisageService { state };
+    if watchdog_secs > 0 {
+        tracing::info!(stale_after_secs = watchdog_secs, "engine watchdog enabled");
+        tokio::spawn(watchdog::run(
+            state.clone(),
+            std::time::Duration::from_secs(watchdog_secs),
+            respawn,
+        ));
+    }
 
-    let _conn = if session_bus {
+    let service = VisageService {
+        state: state.clone(),
+        limiter: request_limit::RequestLimiter::new(request_limits),
+    };
+
+    let conn = if session_bus {
         zbus::connection::Builder::session()?
     } else {
         zbus::connection::Builder::system()?
@@ -81,11 +204,50 @@ async fn main() -> Result<()> {
     .build()
     .await?;
 
+    tokio::spawn(reload::run(state.clone(), conn.clone()));
+
+    if selftest_hours > 0 {
+        tracing::info!(
+            interval_hours = selftest_hours,
+            "scheduled camera self-test enabled"
+        );
+        let bus = conn.clone();
+        let notify: selftest::Notify = Arc::new(move |camera_degraded| {
+            let bus = bus.clone();
+            tokio::spawn(async move {
+                let emitter =
+                    match zbus::object_server::SignalEmitter::new(&bus, "/org/freedesktop/Visage1")
+                    {
+                        Ok(emitter) => emitter,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "cannot emit HealthChanged");
+                            return;
+                        }
+                    };
+                if let Err(e) = VisageService::health_changed(&emitter, camera_degraded).await {
+                    tracing::warn!(error = %e, "failed to emit HealthChanged");
+                }
+            });
+        });
+        tokio::spawn(selftest::run(
+            state,
+            std::time::Duration::from_secs(selftest_hours * 3600),
+            notify,
+        ));
+    }
+
     let bus_name = if session_bus { "session" } else { "system" };
     tracing::info!(
         bus = bus_name,
         "visaged ready — listening on org.freedesktop.Visage1"
     );
+    for capability in capabilities::CAPABILITIES {
+        tracing::debug!(
+            capability = capability.name,
+            method = capability.member,
+            "capability advertised"
+        );
+    }
 
     // 5. Wait for shutdown signal
     tokio::signal::ctrl_c().await?;
@@ -93,3 +255,19 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Why the recognizer at `arcface_path` fails its `manifest` checksum, if
+/// it does. Files the manifest does not name are not checked.
+fn recognizer_fault(
+    manifest: &visage_models::Manifest,
+    model_dir: &std::path::Path,
+    arcface_path: &str,
+) -> Option<String> {
+    let model = visage_models::MODELS
+        .iter()
+        .find(|m| model_dir.join(m.name).as_os_str() == arcface_path)?;
+    manifest
+        .verify(model, std::path::Path::new(arcface_path))
+        .err()
+        .map(|e| e.to_string())
+}
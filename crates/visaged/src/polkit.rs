@@ -0,0 +1,293 @@
+//! Polkit authorization for actions non-root callers may be granted.
+//!
+//! The D-Bus policy decides who may *call* a method; for methods that change
+//! a user's templates the daemon additionally asks polkit whether the caller
+//! holds the matching action. Policy and rules files live in
+//! `packaging/polkit/`. Polkit may prompt the caller to authenticate through
+//! their session's authentication agent before answering.
+//!
+//! Every decision is written to the [`AUDIT_TARGET`] log target.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::sync::OnceCell;
+use zbus::zvariant::Value;
+
+use crate::error::VisageError;
+
+/// Enroll a face model for a user other than the caller.
+pub const ACTION_ENROLL_OTHER: &str = "org.freedesktop.Visage1.enroll-other";
+
+/// Enroll a face model for the caller's own account.
+pub const ACTION_ENROLL: &str = "org.freedesktop.Visage1.enroll";
+
+/// Remove one of the caller's own face models.
+pub const ACTION_REMOVE_MODEL: &str = "org.freedesktop.Visage1.remove-model";
+
+/// Tracing target for authorization decisions and template changes
+/// (`RUST_LOG=visaged::audit=info`). Kept in privacy mode.
+pub const AUDIT_TARGET: &str = "visaged::audit";
+
+/// `CheckAuthorization` flag letting polkit prompt the caller to authenticate.
+const ALLOW_USER_INTERACTION: u32 = 0x1;
+
+/// Polkit's answer for one subject and action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Authorization {
+    Authorized,
+    /// Authentication was required but did not succeed — no agent answered,
+    /// or the caller dismissed the prompt.
+    ChallengeRequired,
+    NotAuthorized,
+}
+
+/// How a caller came to be allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grant {
+    /// UID 0 is always allowed; polkit is not consulted.
+    Root,
+    /// Polkit authorized the action, possibly after authentication.
+    Polkit,
+}
+
+impl Grant {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Grant::Root => "root",
+            Grant::Polkit => "polkit",
+        }
+    }
+}
+
+pub type CheckFuture<'a> = Pin<Box<dyn Future<Output = zbus::Result<Authorization>> + Send + 'a>>;
+
+/// Source of authorization decisions; polkit in production, a canned
+/// responder in tests.
+pub trait Authority: Send + Sync {
+    /// Ask whether the peer `sender` (a unique bus name) may perform
+    /// `action_id`. `details` are shown in the authentication prompt.
+    fn check<'a>(
+        &'a self,
+        sender: &'a str,
+        action_id: &'a str,
+        details: &'a [(&'a str, &'a str)],
+    ) -> CheckFuture<'a>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait PolicyKitAuthority {
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// The system polkit daemon, reached over a lazily opened system bus
+/// connection (the daemon itself may be serving on the session bus).
+#[derive(Default)]
+pub struct PolkitAuthority {
+    conn: OnceCell<zbus::Connection>,
+}
+
+impl PolkitAuthority {
+    async fn check_authorization(
+        &self,
+        sender: &str,
+        action_id: &str,
+        details: &[(&str, &str)],
+    ) -> zbus::Result<Authorization> {
+        let conn = self.conn.get_or_try_init(zbus::Connection::system).await?;
+        let proxy = PolicyKitAuthorityProxy::new(conn).await?;
+        let subject = (
+            "system-bus-name",
+            HashMap::from([("name", Value::from(sender))]),
+        );
+        let details: HashMap<&str, &str> = details.iter().copied().collect();
+        let (authorized, challenge, _) = proxy
+            .check_authorization(&subject, action_id, &details, ALLOW_USER_INTERACTION, "")
+            .await?;
+        Ok(if authorized {
+            Authorization::Authorized
+        } else if challenge {
+            Authorization::ChallengeRequired
+        } else {
+            Authorization::NotAuthorized
+        })
+    }
+}
+
+impl Authority for PolkitAuthority {
+    fn check<'a>(
+        &'a self,
+        sender: &'a str,
+        action_id: &'a str,
+        details: &'a [(&'a str, &'a str)],
+    ) -> CheckFuture<'a> {
+        Box::pin(self.check_authorization(sender, action_id, details))
+    }
+}
+
+/// Decide whether `caller_uid` (peer `sender`) may perform `action_id`.
+///
+/// Root is allowed outright. Anyone else needs a positive polkit answer; a
+/// polkit failure denies rather than allows.
+pub async fn authorize(
+    authority: &dyn Authority,
+    sender: &str,
+    caller_uid: u32,
+    action_id: &str,
+    details: &[(&str, &str)],
+) -> Result<Grant, VisageError> {
+    if caller_uid == 0 {
+        return Ok(Grant::Root);
+    }
+    match authority.check(sender, action_id, details).await {
+        Ok(Authorization::Authorized) => Ok(Grant::Polkit),
+        Ok(Authorization::ChallengeRequired) => Err(VisageError::AccessDenied(format!(
+            "{action_id} requires authentication, which did not succeed"
+        ))),
+        Ok(Authorization::NotAuthorized) => Err(VisageError::AccessDenied(format!(
+            "caller is not authorized for {action_id}"
+        ))),
+        Err(e) => {
+            tracing::error!(error = %e, action_id, "polkit check failed");
+            Err(VisageError::Failed(format!(
+                "could not check authorization for {action_id}: {e}"
+            )))
+        }
+    }
+}
+
+/// Responder that gives the same answer to every check and counts calls.
+#[cfg(test)]
+pub struct MockAuthority {
+    answer: Result<Authorization, ()>,
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl MockAuthority {
+    pub fn answering(answer: Authorization) -> Self {
+        Self {
+            answer: Ok(answer),
+            calls: Default::default(),
+        }
+    }
+
+    /// Responder standing in for an unreachable polkit daemon.
+    pub fn unreachable() -> Self {
+        Self {
+            answer: Err(()),
+            calls: Default::default(),
+        }
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+impl Authority for MockAuthority {
+    fn check<'a>(
+        &'a self,
+        _sender: &'a str,
+        _action_id: &'a str,
+        _details: &'a [(&'a str, &'a str)],
+    ) -> CheckFuture<'a> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let answer = self
+            .answer
+            .map_err(|()| zbus::Error::Failure("polkit not running".into()));
+        Box::pin(async move { answer })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_enroll_other_decision_matrix() {
+        use Authorization::*;
+
+        // (caller uid, polkit answer, expected grant, polkit consulted)
+        let cases = [
+            (0, Some(NotAuthorized), Some(Grant::Root), false),
+            (0, None, Some(Grant::Root), false),
+            (1000, Some(Authorized), Some(Grant::Polkit), true),
+            (1000, Some(ChallengeRequired), None, true),
+            (1000, Some(NotAuthorized), None, true),
+            (1000, None, None, true),
+        ];
+        for (caller_uid, answer, expected, consulted) in cases {
+            let authority = match answer {
+                Some(answer) => MockAuthority::answering(answer),
+                None => MockAuthority::unreachable(),
+            };
+            let result = authorize(
+                &authority,
+                ":1.42",
+                caller_uid,
+                ACTION_ENROLL_OTHER,
+                &[("user", "newhire")],
+            )
+            .await;
+            let case = format!("uid {caller_uid}, polkit {answer:?}");
+            assert_eq!(
+                result.as_ref().ok().copied(),
+                expected,
+                "{case}: {result:?}"
+            );
+            assert_eq!(authority.calls() == 1, consulted, "{case}");
+            match (answer, &result) {
+                (None, Err(e)) => assert!(matches!(e, VisageError::Failed(_)), "{case}"),
+                (_, Err(e)) => assert!(matches!(e, VisageError::AccessDenied(_)), "{case}"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_own_account_actions_need_polkit_for_non_root_callers() {
+        use Authorization::*;
+
+        // (caller uid, polkit answer, allowed, polkit consulted); the daemon
+        // has already checked that the account is the caller's.
+        let cases = [
+            (0, NotAuthorized, true, false),
+            (1000, Authorized, true, true),
+            (1000, ChallengeRequired, false, true),
+            (1000, NotAuthorized, false, true),
+        ];
+        for action in [ACTION_ENROLL, ACTION_REMOVE_MODEL] {
+            for (caller_uid, answer, allowed, consulted) in cases {
+                let authority = MockAuthority::answering(answer);
+                let result = authorize(
+                    &authority,
+                    ":1.42",
+                    caller_uid,
+                    action,
+                    &[("user", "alice")],
+                )
+                .await;
+                let case = format!("{action}: uid {caller_uid}, polkit {answer:?}");
+                assert_eq!(result.is_ok(), allowed, "{case}: {result:?}");
+                assert_eq!(authority.calls() == 1, consulted, "{case}");
+                if let Err(e) = &result {
+                    assert!(matches!(e, VisageError::AccessDenied(_)), "{case}");
+                }
+            }
+        }
+    }
+}
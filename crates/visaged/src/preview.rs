@@ -0,0 +1,177 @@
+//! Preview sessions backing `Preview` / `EnrollNow`.
+//!
+//! `Preview` captures a burst of frames and parks them here under a random
+//! token. `EnrollNow` redeems the token to enroll from exactly those frames,
+//! so the stored template matches what the user was shown. Tokens are
+//! single-use, bound to the user they were issued for, and expire after the
+//! configured TTL. Each user holds at most one session: a new preview
+//! replaces the previous one.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::engine::PreviewCapture;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PreviewError {
+    #[error("unknown or already used preview token")]
+    UnknownToken,
+    #[error("preview expired — start a new preview")]
+    Expired,
+    #[error("preview token was issued for another user")]
+    WrongUser,
+}
+
+struct Session {
+    user: String,
+    capture: PreviewCapture,
+    created: Instant,
+}
+
+/// Live preview sessions keyed by token.
+pub struct PreviewSessions {
+    ttl: Duration,
+    sessions: HashMap<String, Session>,
+}
+
+impl PreviewSessions {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Store `capture` for `user` and return the token that redeems it.
+    pub fn insert(&mut self, user: &str, capture: PreviewCapture) -> String {
+        self.insert_at(user, capture, Instant::now())
+    }
+
+    fn insert_at(&mut self, user: &str, capture: PreviewCapture, now: Instant) -> String {
+        let ttl = self.ttl;
+        self.sessions
+            .retain(|_, s| s.user != user && now.duration_since(s.created) <= ttl);
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        self.sessions.insert(
+            token.clone(),
+            Session {
+                user: user.to_string(),
+                capture,
+                created: now,
+            },
+        );
+        token
+    }
+
+    /// Redeem `token` for `user`, removing the session.
+    ///
+    /// A token presented for the wrong user is left in place so the rightful
+    /// user can still redeem it.
+    pub fn take(&mut self, token: &str, user: &str) -> Result<PreviewCapture, PreviewError> {
+        self.take_at(token, user, Instant::now())
+    }
+
+    fn take_at(
+        &mut self,
+        token: &str,
+        user: &str,
+        now: Instant,
+    ) -> Result<PreviewCapture, PreviewError> {
+        let session = self.sessions.get(token).ok_or(PreviewError::UnknownToken)?;
+        if now.duration_since(session.created) > self.ttl {
+            self.sessions.remove(token);
+            return Err(PreviewError::Expired);
+        }
+        if session.user != user {
+            return Err(PreviewError::WrongUser);
+        }
+        let session = self
+            .sessions
+            .remove(token)
+            .ok_or(PreviewError::UnknownToken)?;
+        Ok(session.capture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use visage_hw::Frame;
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    fn capture(sequence: u32) -> PreviewCapture {
+        PreviewCapture {
+            frames: vec![Frame {
+                data: vec![0; 4],
+                width: 2,
+                height: 2,
+                timestamp: Instant::now(),
+                sequence,
+                is_dark: false,
+                raw_brightness: 0.0,
+            }],
+            dark_skipped: 0,
+            device_id: None,
+        }
+    }
+
+    #[test]
+    fn test_token_redeems_its_frames_once() {
+        let mut sessions = PreviewSessions::new(TTL);
+        let t0 = Instant::now();
+        let token = sessions.insert_at("alice", capture(7), t0);
+
+        assert_eq!(
+            sessions.take_at(&token, "bob", t0).err(),
+            Some(PreviewError::WrongUser)
+        );
+        let taken = sessions.take_at(&token, "alice", t0 + TTL).unwrap();
+        assert_eq!(taken.frames[0].sequence, 7);
+        assert_eq!(
+            sessions.take_at(&token, "alice", t0).err(),
+            Some(PreviewError::UnknownToken)
+        );
+        assert_eq!(
+            sessions.take_at("bogus", "alice", t0).err(),
+            Some(PreviewError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let mut sessions = PreviewSessions::new(TTL);
+        let t0 = Instant::now();
+        let token = sessions.insert_at("alice", capture(1), t0);
+        let late = t0 + TTL + Duration::from_secs(1);
+        assert_eq!(
+            sessions.take_at(&token, "alice", late).err(),
+            Some(PreviewError::Expired)
+        );
+        assert_eq!(sessions.sessions.len(), 0);
+    }
+
+    #[test]
+    fn test_new_preview_replaces_previous_for_same_user() {
+        let mut sessions = PreviewSessions::new(TTL);
+        let t0 = Instant::now();
+        let first = sessions.insert_at("alice", capture(1), t0);
+        let bob = sessions.insert_at("bob", capture(2), t0);
+        let second = sessions.insert_at("alice", capture(3), t0);
+        assert_ne!(first, second);
+        assert_eq!(sessions.sessions.len(), 2);
+        assert_eq!(
+            sessions.take_at(&first, "alice", t0).err(),
+            Some(PreviewError::UnknownToken)
+        );
+        assert_eq!(
+            sessions.take_at(&second, "alice", t0).unwrap().frames[0].sequence,
+            3
+        );
+        assert_eq!(
+            sessions.take_at(&bob, "bob", t0).unwrap().frames[0].sequence,
+            2
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+use crate::clock::{Clock, Now, Span, SystemClock};
+
 /// Maximum consecutive failures before lockout.
 const MAX_FAILURES: u32 = 5;
 /// Sliding window over which failures are counted.
@@ -8,10 +10,31 @@ const WINDOW: Duration = Duration::from_secs(60);
 /// Lockout duration after exceeding MAX_FAILURES.
 const LOCKOUT: Duration = Duration::from_secs(300);
 
+/// How accumulated failures are forgotten.
+enum Forgiveness {
+    /// All failures are cleared once WINDOW has passed since the first one.
+    Window,
+    /// One failure is forgiven per elapsed interval.
+    Decay(Duration),
+}
+
 struct UserRecord {
     failures: u32,
+    /// Start of the current window, or the last decay step.
     window_start: Instant,
-    locked_until: Option<Instant>,
+    /// Runs on the monotonic clock, so wall-clock steps cannot stretch or
+    /// cut it short (see [`crate::clock`]).
+    lockout: Option<Span>,
+}
+
+impl UserRecord {
+    fn new(now: Instant) -> Self {
+        Self {
+            failures: 0,
+            window_start: now,
+            lockout: None,
+        }
+    }
 }
 
 /// Per-user rate limiter for verification attempts.
@@ -20,44 +43,56 @@ struct UserRecord {
 /// locked out for LOCKOUT seconds.  Engine errors (camera failure, timeout)
 /// are not counted as failures — only a deliberate face-not-matched response
 /// increments the counter.
+///
+/// The window resets all at once, so an attacker who pauses for WINDOW after
+/// every MAX_FAILURES - 1 attempts is never locked. A [`decaying`](Self::decaying)
+/// limiter instead forgives failures one at a time: any sustained rate above
+/// one failure per decay interval eventually trips the lockout.
 pub struct RateLimiter {
     records: HashMap<String, UserRecord>,
+    forgiveness: Forgiveness,
+    clock: Box<dyn Clock>,
 }
 
 impl RateLimiter {
     pub fn new() -> Self {
         Self {
             records: HashMap::new(),
+            forgiveness: Forgiveness::Window,
+            clock: Box::new(SystemClock),
         }
     }
 
-    /// Return `Ok(())` if the user is allowed to attempt verification.
-    /// Return `Err(message)` if the user is currently rate-limited.
-    pub fn check(&mut self, user: &str) -> Result<(), String> {
-        let now = Instant::now();
-        let record = self.records.entry(user.to_string()).or_insert(UserRecord {
-            failures: 0,
-            window_start: now,
-            locked_until: None,
-        });
-
-        if let Some(locked_until) = record.locked_until {
-            if now < locked_until {
-                let remaining = locked_until.duration_since(now).as_secs();
-                return Err(format!(
-                    "too many failed attempts; try again in {remaining}s"
-                ));
+    /// Limiter that forgives one failure per `interval` instead of resetting
+    /// the whole count when the window elapses.
+    pub fn decaying(interval: Duration) -> Self {
+        Self {
+            forgiveness: Forgiveness::Decay(interval),
+            ..Self::new()
+        }
+    }
+
+    /// Return `Ok(())` if the user is allowed to attempt verification, or
+    /// the time left on the user's lockout.
+    pub fn check(&mut self, user: &str) -> Result<(), Duration> {
+        let now = self.clock.read();
+        self.check_at(user, now)
+    }
+
+    fn check_at(&mut self, user: &str, now: Now) -> Result<(), Duration> {
+        let record = self
+            .records
+            .entry(user.to_string())
+            .or_insert_with(|| UserRecord::new(now.mono));
+
+        if let Some(lockout) = record.lockout {
+            if !lockout.is_over(now) {
+                return Err(lockout.remaining(now));
             }
             // Lockout expired — reset
-            *record = UserRecord {
-                failures: 0,
-                window_start: now,
-                locked_until: None,
-            };
-        } else if now.duration_since(record.window_start) >= WINDOW {
-            // Sliding window expired — reset failure counter
-            record.failures = 0;
-            record.window_start = now;
+            *record = UserRecord::new(now.mono);
+        } else {
+            forget(&self.forgiveness, record, now.mono);
         }
 
         Ok(())
@@ -65,25 +100,28 @@ impl RateLimiter {
 
     /// Record a failed verification attempt. May trigger a lockout.
     pub fn record_failure(&mut self, user: &str) {
-        let now = Instant::now();
-        let record = self.records.entry(user.to_string()).or_insert(UserRecord {
-            failures: 0,
-            window_start: now,
-            locked_until: None,
-        });
+        let now = self.clock.read();
+        self.record_failure_at(user, now);
+    }
 
-        if now.duration_since(record.window_start) >= WINDOW {
-            record.failures = 0;
-            record.window_start = now;
-        }
+    fn record_failure_at(&mut self, user: &str, now: Now) {
+        let record = self
+            .records
+            .entry(user.to_string())
+            .or_insert_with(|| UserRecord::new(now.mono));
+
+        forget(&self.forgiveness, record, now.mono);
 
         record.failures += 1;
         if record.failures >= MAX_FAILURES {
-            record.locked_until = Some(now + LOCKOUT);
+            let lockout = Span::start(now, LOCKOUT);
+            record.lockout = Some(lockout);
             tracing::warn!(
                 user,
                 failures = record.failures,
                 lockout_secs = LOCKOUT.as_secs(),
+                since = %lockout.started_wall(),
+                until = %lockout.ends_at(now),
                 "rate limit triggered — locking user"
             );
         } else {
@@ -100,11 +138,89 @@ impl RateLimiter {
     pub fn record_success(&mut self, user: &str) {
         self.records.remove(user);
     }
+
+    /// Drop `user`'s failures and any lockout, as when their templates are
+    /// all removed.
+    pub fn forget_user(&mut self, user: &str) {
+        self.records.remove(user);
+    }
+}
+
+/// Drop the failures `forgiveness` has forgotten by `now`.
+fn forget(forgiveness: &Forgiveness, record: &mut UserRecord, now: Instant) {
+    let elapsed = now.saturating_duration_since(record.window_start);
+    match *forgiveness {
+        Forgiveness::Window => {
+            if elapsed >= WINDOW {
+                // Sliding window expired — reset failure counter
+                record.failures = 0;
+                record.window_start = now;
+            }
+        }
+        Forgiveness::Decay(interval) => {
+            let steps = (elapsed.as_millis() / interval.as_millis().max(1)) as u32;
+            if record.failures == 0 {
+                record.window_start = now;
+            } else if steps > 0 {
+                record.failures = record.failures.saturating_sub(steps);
+                // Keep the remainder so partial intervals still count.
+                record.window_start += interval * steps;
+            }
+        }
+    }
+}
+
+/// Minimum time between two enrollments for the same user.
+///
+/// Targets accidental template churn (a script in a loop, a frustrated user)
+/// rather than attacks: there is no failure count, and only a completed
+/// enrollment starts the cooldown, so a failed capture can be retried at once.
+pub struct EnrollCooldown {
+    length: Duration,
+    last: HashMap<String, Span>,
+    clock: Box<dyn Clock>,
+}
+
+impl EnrollCooldown {
+    /// A `length` of zero disables the cooldown.
+    pub fn new(length: Duration) -> Self {
+        Self {
+            length,
+            last: HashMap::new(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// `Err` with the time left while `user`'s cooldown is running.
+    pub fn check(&self, user: &str) -> Result<(), Duration> {
+        self.check_at(user, self.clock.read())
+    }
+
+    fn check_at(&self, user: &str, now: Now) -> Result<(), Duration> {
+        match self.last.get(user) {
+            Some(cooldown) if !cooldown.is_over(now) => Err(cooldown.remaining(now)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start `user`'s cooldown after a completed enrollment.
+    pub fn record(&mut self, user: &str) {
+        let now = self.clock.read();
+        self.record_at(user, now);
+    }
+
+    fn record_at(&mut self, user: &str, now: Now) {
+        if !self.length.is_zero() {
+            self.last
+                .insert(user.to_string(), Span::start(now, self.length));
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::tests::after;
 
     #[test]
     fn test_allows_under_limit() {
@@ -146,4 +262,96 @@ mod tests {
         assert!(rl.check("bob").is_ok());
         assert!(rl.check("alice").is_err());
     }
+
+    /// Run `cycles` bursts of MAX_FAILURES - 1 failures, pausing for WINDOW
+    /// after each, and return the cycle in which the user was locked out.
+    fn four_wait_four(rl: &mut RateLimiter, cycles: u32) -> Option<u32> {
+        let start = SystemClock.read();
+        for cycle in 0..cycles {
+            let burst_at = (WINDOW * cycle).as_secs();
+            for attempt in 0..MAX_FAILURES - 1 {
+                let secs = burst_at + attempt as u64;
+                let now = after(start, secs, secs as i64);
+                if rl.check_at("alice", now).is_err() {
+                    return Some(cycle);
+                }
+                rl.record_failure_at("alice", now);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_window_reset_allows_paced_attack() {
+        let mut rl = RateLimiter::new();
+        assert_eq!(four_wait_four(&mut rl, 20), None);
+    }
+
+    #[test]
+    fn test_decay_eventually_locks_paced_attack() {
+        // 4 failures per minute outpaces forgiving 2 per minute.
+        let mut rl = RateLimiter::decaying(Duration::from_secs(30));
+        let locked_in = four_wait_four(&mut rl, 20).expect("decay mode should lock");
+        assert!(locked_in <= 2, "locked in cycle {locked_in}");
+    }
+
+    #[test]
+    fn test_decay_forgives_one_failure_per_interval() {
+        let interval = Duration::from_secs(15);
+        let mut rl = RateLimiter::decaying(interval);
+        let start = SystemClock.read();
+        let at = |secs: u64| after(start, secs, secs as i64);
+        for _ in 0..4 {
+            rl.record_failure_at("alice", start);
+        }
+        let failures = |rl: &mut RateLimiter, at: Now| {
+            rl.check_at("alice", at).unwrap();
+            rl.records["alice"].failures
+        };
+        assert_eq!(failures(&mut rl, at(14)), 4);
+        assert_eq!(failures(&mut rl, at(15)), 3);
+        // Partial intervals carry over between calls.
+        assert_eq!(failures(&mut rl, at(29)), 3);
+        assert_eq!(failures(&mut rl, at(30)), 2);
+        assert_eq!(failures(&mut rl, at(120)), 0);
+
+        // Once idle, decay restarts from the next failure.
+        rl.record_failure_at("alice", at(500));
+        assert_eq!(failures(&mut rl, at(514)), 1);
+        assert_eq!(failures(&mut rl, at(515)), 0);
+    }
+
+    #[test]
+    fn test_lockout_bounded_across_wall_clock_jumps() {
+        let mut rl = RateLimiter::new();
+        let start = SystemClock.read();
+        for _ in 0..MAX_FAILURES {
+            rl.record_failure_at("alice", start);
+        }
+        // The wall clock steps forward a day: still locked.
+        let err = rl.check_at("alice", after(start, 10, 86_400)).unwrap_err();
+        assert_eq!(err, Duration::from_secs(290));
+        // It steps back a day: the lockout still ends on time.
+        assert!(rl.check_at("alice", after(start, 299, -86_400)).is_err());
+        assert!(rl.check_at("alice", after(start, 300, -86_400)).is_ok());
+    }
+
+    #[test]
+    fn test_enroll_cooldown_runs_per_user_after_completed_enrollment() {
+        let mut cooldown = EnrollCooldown::new(Duration::from_secs(5));
+        let start = SystemClock.read();
+        assert!(cooldown.check_at("alice", start).is_ok());
+
+        cooldown.record_at("alice", start);
+        assert_eq!(
+            cooldown.check_at("alice", after(start, 2, 2)),
+            Err(Duration::from_secs(3))
+        );
+        assert!(cooldown.check_at("bob", after(start, 2, 2)).is_ok());
+        assert!(cooldown.check_at("alice", after(start, 5, 5)).is_ok());
+
+        let mut off = EnrollCooldown::new(Duration::ZERO);
+        off.record_at("alice", start);
+        assert!(off.check_at("alice", start).is_ok());
+    }
 }
@@ -0,0 +1,346 @@
+//! SIGHUP configuration reload.
+//!
+//! Tuning the threshold should not cost a restart, which reopens the camera
+//! and loads the models again. On SIGHUP the daemon reads its configuration
+//! again ([`Config::load`]) and swaps the settings verify and enroll read on
+//! every request into the running [`AppState`]. Settings the camera, engine,
+//! store, bus, logger or limiters were built from at startup keep their
+//! startup values; a change to one of those is logged as needing a restart. The environment is
+//! still the one the daemon started with, so a reload in practice picks up
+//! edits to the config file. Clients watching the D-Bus properties are sent
+//! `PropertiesChanged` for the ones a reload changed.
+
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::dbus_interface::{AppState, VisageService};
+use crate::device_settings;
+use crate::polkit;
+use crate::verify_policy::VerifyPolicy;
+
+/// Which settings a reload changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Reloaded {
+    /// Settings now in effect.
+    pub applied: Vec<&'static str>,
+    /// Settings that changed but keep their startup value until a restart.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Swap the hot-reloadable settings of `fresh` into `state`. Successes
+/// remembered for `VerifyCached` are dropped when anything changed, as they
+/// must not vouch for the new settings.
+pub fn apply(state: &mut AppState, fresh: &Config) -> Reloaded {
+    let config = &mut state.config;
+    let mut reloaded = Reloaded::default();
+
+    macro_rules! classify {
+        (
+            hot: [$($hot:ident),* $(,)?],
+            restart_only: [$($restart:ident),* $(,)?],
+            not_settings: [$($other:ident),* $(,)?] $(,)?
+        ) => {
+            // Names every field once, so a new one does not compile until
+            // it is classified here.
+            let Config { $($hot: _,)* $($restart: _,)* $($other: _,)* } = fresh;
+            $(
+                if config.$hot != fresh.$hot {
+                    config.$hot = fresh.$hot.clone();
+                    reloaded.applied.push(stringify!($hot));
+                }
+            )*
+            $(
+                if config.$restart != fresh.$restart {
+                    reloaded.restart_required.push(stringify!($restart));
+                }
+            )*
+        };
+    }
+
+    classify!(
+        // Read from the state on every request (or self-test).
+        hot: [
+            similarity_threshold,
+            verify_timeout_secs,
+            frames_per_verify,
+            frames_per_enroll,
+            liveness_enabled,
+            liveness_min_displacement,
+            liveness_min_live_pairs,
+            liveness_min_frame_gap_ms,
+            session_bus_hardened,
+            allow_insecure_threshold,
+            enroll_timeout_secs,
+            exposure_report_frames,
+            centroid_matching,
+            centroid_margin,
+            verify_report_timing,
+            verify_require_pose_match,
+            verify_require_enrolled_device,
+            verify_consistency_floor,
+            verify_lighting_tolerance,
+            enroll_min_alignment,
+            enroll_require_liveness,
+            enroll_crop_hash,
+            duplicate_hash_max_distance,
+            enroll_duplicate_threshold,
+            enroll_allow_duplicates,
+            percent_encoded_usernames,
+            verify_schedule,
+            selftest_emitter_schedule,
+        ],
+        // Built into the camera, engine, store, bus, logger or a limiter at
+        // startup. The emitter is probed once when the engine is spawned.
+        restart_only: [
+            camera_device,
+            model_dir,
+            model_manifest,
+            db_path,
+            db_read_connections,
+            encrypt_metadata,
+            emitter_enabled,
+            session_bus,
+            enroll_cooldown_secs,
+            preview_ttl_secs,
+            verify_token_ttl_secs,
+            verify_grace_max_secs,
+            warmup_frames,
+            privacy_mode,
+            startup_selftest,
+            lazy_camera,
+            lazy_camera_keep_open_secs,
+            engine_watchdog_secs,
+            rate_limit_decay_secs,
+            query_requests_per_sec,
+            capture_requests_per_min,
+            client_max_in_flight,
+            selftest_interval_hours,
+            log_format,
+        ],
+        // Where the configuration came from and what loading it noticed.
+        not_settings: [config_file, warnings],
+    );
+
+    if !reloaded.applied.is_empty() {
+        state.verify_grace.clear();
+    }
+    reloaded
+}
+
+/// Read the configuration again and apply it to `state`. An unreadable or
+/// invalid configuration is logged and the running one kept, and `None`
+/// returned.
+pub async fn reload(state: &Mutex<AppState>) -> Option<Reloaded> {
+    let mut fresh = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!(
+                error = %e,
+                "configuration reload failed; keeping the running configuration"
+            );
+            return None;
+        }
+    };
+    for warning in &fresh.warnings {
+        tracing::warn!(%warning, "configuration warning");
+    }
+    let valid = fresh.validate().map_err(|e| e.to_string()).and_then(|()| {
+        VerifyPolicy::from_config(&fresh)
+            .validate(&fresh)
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = valid {
+        tracing::error!(
+            error = %e,
+            "reloaded configuration is invalid; keeping the running configuration"
+        );
+        return None;
+    }
+
+    let mut state = state.lock().await;
+    // Settings calibrated for this camera still win over the file.
+    if let Some(device_id) = visage_hw::quirks::get_device_id(&state.config.camera_device) {
+        if let Err(e) = device_settings::apply_stored(&mut fresh, &state.store, &device_id).await {
+            tracing::warn!(device_id, error = %e, "cannot read stored camera settings");
+        }
+    }
+    let reloaded = apply(&mut state, &fresh);
+    for setting in &reloaded.restart_required {
+        tracing::warn!(
+            setting,
+            "setting changed; restart required for it to take effect"
+        );
+    }
+    tracing::warn!(
+        target: polkit::AUDIT_TARGET,
+        applied = ?reloaded.applied,
+        "configuration reloaded"
+    );
+    Some(reloaded)
+}
+
+/// Reload the configuration on every SIGHUP, for the life of the daemon,
+/// announcing changed properties on `conn`.
+pub async fn run(state: Arc<Mutex<AppState>>, conn: zbus::Connection) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!(
+                error = %e,
+                "cannot listen for SIGHUP; configuration reload disabled"
+            );
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        tracing::info!("SIGHUP received; reloading configuration");
+        let Some(reloaded) = reload(&state).await else {
+            continue;
+        };
+        // The property getters take the state lock, which reload released.
+        if let Err(e) = VisageService::announce_reload(&conn, &reloaded.applied).await {
+            tracing::warn!(error = %e, "failed to emit PropertiesChanged after reload");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::EngineHandle;
+    use crate::polkit::{Authorization, MockAuthority};
+    use crate::preview::PreviewSessions;
+    use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+    use crate::selftest::SelfTestState;
+    use crate::stats::QualityStats;
+    use crate::store::FaceModelStore;
+    use crate::verify_cancel::PendingVerifies;
+    use crate::verify_grace::VerifyGrace;
+    use crate::verify_token::VerifyTokens;
+    use std::path::Path;
+    use std::time::Duration;
+
+    async fn state() -> AppState {
+        AppState {
+            config: Config::from_pairs(&[]).unwrap(),
+            engine: EngineHandle::degraded("test"),
+            store: FaceModelStore::open(Path::new(":memory:"), 1, false)
+                .await
+                .unwrap(),
+            rate_limiter: RateLimiter::new(),
+            enroll_cooldown: EnrollCooldown::new(Duration::ZERO),
+            previews: PreviewSessions::new(Duration::from_secs(30)),
+            verify_tokens: VerifyTokens::new(Duration::ZERO),
+            verify_grace: VerifyGrace::new(Duration::ZERO),
+            pending_verifies: PendingVerifies::default(),
+            stats: QualityStats::new(),
+            selftest: SelfTestState::default(),
+            log_filter: None,
+            authority: Arc::new(MockAuthority::answering(Authorization::NotAuthorized)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_changes_only_hot_settings() {
+        let mut state = state().await;
+        let before = state.config.clone();
+        let fresh = Config::from_pairs(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "0.55"),
+            ("VISAGE_VERIFY_TIMEOUT_SECS", "7"),
+            ("VISAGE_FRAMES_PER_VERIFY", "9"),
+            ("VISAGE_FRAMES_PER_ENROLL", "11"),
+            ("VISAGE_LIVENESS_ENABLED", "0"),
+            ("VISAGE_LIVENESS_MIN_DISPLACEMENT", "2.5"),
+            ("VISAGE_CAMERA_DEVICE", "/dev/video9"),
+            ("VISAGE_DB_PATH", "/tmp/elsewhere.db"),
+            ("VISAGE_EMITTER_ENABLED", "0"),
+            ("VISAGE_ENROLL_COOLDOWN_SECS", "60"),
+            ("VISAGE_VERIFY_CONSISTENCY_FLOOR", "0.6"),
+            ("VISAGE_PRIVACY_MODE", "1"),
+        ])
+        .unwrap();
+
+        let reloaded = apply(&mut state, &fresh);
+
+        assert_eq!(
+            reloaded.applied,
+            [
+                "similarity_threshold",
+                "verify_timeout_secs",
+                "frames_per_verify",
+                "frames_per_enroll",
+                "liveness_enabled",
+                "liveness_min_displacement",
+                "verify_consistency_floor",
+            ]
+        );
+        assert_eq!(
+            reloaded.restart_required,
+            [
+                "camera_device",
+                "db_path",
+                "emitter_enabled",
+                "enroll_cooldown_secs",
+                "privacy_mode",
+            ]
+        );
+        let config = &state.config;
+        assert_eq!(config.similarity_threshold, 0.55);
+        assert_eq!(config.verify_timeout_secs, 7);
+        assert_eq!(config.frames_per_verify, 9);
+        assert_eq!(config.frames_per_enroll, 11);
+        assert!(!config.liveness_enabled);
+        assert_eq!(config.liveness_min_displacement, 2.5);
+        assert_eq!(config.verify_consistency_floor, 0.6);
+        // Startup-only settings keep their running values.
+        assert_eq!(config.camera_device, before.camera_device);
+        assert_eq!(config.db_path, before.db_path);
+        assert_eq!(config.emitter_enabled, before.emitter_enabled);
+        assert_eq!(config.enroll_cooldown_secs, before.enroll_cooldown_secs);
+        assert_eq!(config.privacy_mode, before.privacy_mode);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_the_insecure_threshold_override_with_the_threshold() {
+        let mut state = state().await;
+        let insecure = Config::from_pairs(&[
+            ("VISAGE_SIMILARITY_THRESHOLD", "0.1"),
+            ("VISAGE_ALLOW_INSECURE_THRESHOLD", "1"),
+        ])
+        .unwrap();
+        VerifyPolicy::from_config(&insecure)
+            .validate(&insecure)
+            .unwrap();
+
+        let reloaded = apply(&mut state, &insecure);
+        assert_eq!(
+            reloaded.applied,
+            ["similarity_threshold", "allow_insecure_threshold"]
+        );
+        // The running config validates the threshold it now holds.
+        VerifyPolicy::from_config(&state.config)
+            .validate(&state.config)
+            .unwrap();
+
+        // Dropping the override again takes the threshold back with it.
+        let secure = Config::from_pairs(&[]).unwrap();
+        apply(&mut state, &secure);
+        assert!(!state.config.allow_insecure_threshold);
+        assert!(VerifyPolicy::from_config(&state.config)
+            .merged(r#"{"similarity_threshold": 0.1}"#)
+            .unwrap()
+            .validate(&state.config)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload_of_unchanged_config_applies_nothing() {
+        let mut state = state().await;
+        let fresh = state.config.clone();
+        assert_eq!(apply(&mut state, &fresh), Reloaded::default());
+    }
+}
@@ -0,0 +1,337 @@
+//! Per-client request limits for the D-Bus interface.
+//!
+//! A client calling `Status` thousands of times a second would otherwise keep
+//! the `AppState` mutex busy and starve `Verify`. Handlers are admitted here
+//! before they touch the state: each caller (by unique bus name) gets a token
+//! bucket per [`RequestClass`] and a cap on its handlers in flight, and an
+//! excess request fails at once with `TooManyRequests`. The limiter has its
+//! own lock, held only for the bookkeeping and never across an await.
+//!
+//! This is separate from [`crate::rate_limiter`], which locks a *user* out
+//! after failed matches; these limits bound how often a *connection* may
+//! call at all, whatever the outcome.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::VisageError;
+
+/// Idle clients are forgotten once more than this many are tracked.
+const PRUNE_ABOVE: usize = 256;
+
+/// Which budget a method draws from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestClass {
+    /// Reads and bookkeeping that never reach the camera or the models.
+    Query,
+    /// Requests queued on the engine: captures, enrolls and frame verifies.
+    Capture,
+}
+
+/// Configured limits per client; `0` disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// [`RequestClass::Query`] requests per second, allowed in a burst.
+    pub query_per_sec: u32,
+    /// [`RequestClass::Capture`] requests per minute, allowed in a burst.
+    pub capture_per_min: u32,
+    /// Handlers of any class running at once.
+    pub max_in_flight: u32,
+}
+
+impl RequestLimits {
+    /// Bucket size and refill period for `class`.
+    fn budget(&self, class: RequestClass) -> (u32, Duration) {
+        match class {
+            RequestClass::Query => (self.query_per_sec, Duration::from_secs(1)),
+            RequestClass::Capture => (self.capture_per_min, Duration::from_secs(60)),
+        }
+    }
+}
+
+/// Token bucket holding up to `capacity` requests, refilled evenly over a
+/// period.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn full(capacity: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(capacity),
+            refilled: now,
+        }
+    }
+
+    /// Add the tokens earned since the last refill; returns whether the
+    /// bucket is full.
+    fn refill(&mut self, (capacity, period): (u32, Duration), now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        let capacity = f64::from(capacity);
+        self.tokens = (self.tokens + elapsed * capacity / period.as_secs_f64()).min(capacity);
+        self.refilled = now;
+        self.tokens >= capacity
+    }
+
+    /// Take a token, or return how long until one is earned. A zero
+    /// capacity means unlimited.
+    fn take(&mut self, budget: (u32, Duration), now: Instant) -> Result<(), Duration> {
+        let (capacity, period) = budget;
+        if capacity == 0 {
+            return Ok(());
+        }
+        self.refill(budget, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(period.mul_f64((1.0 - self.tokens) / f64::from(capacity)))
+        }
+    }
+}
+
+struct Client {
+    query: Bucket,
+    capture: Bucket,
+    in_flight: u32,
+}
+
+impl Client {
+    fn new(limits: &RequestLimits, now: Instant) -> Self {
+        Self {
+            query: Bucket::full(limits.query_per_sec, now),
+            capture: Bucket::full(limits.capture_per_min, now),
+            in_flight: 0,
+        }
+    }
+
+    /// Nothing running and both buckets full: forgetting the client changes
+    /// nothing.
+    fn idle(&mut self, limits: &RequestLimits, now: Instant) -> bool {
+        let query_full = self.query.refill(limits.budget(RequestClass::Query), now);
+        let capture_full = self
+            .capture
+            .refill(limits.budget(RequestClass::Capture), now);
+        self.in_flight == 0 && query_full && capture_full
+    }
+}
+
+/// Counters reported under `requests` in `Stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct RequestStats {
+    /// Handlers running now, over all clients.
+    pub in_flight: u64,
+    /// Most handlers ever running at once.
+    pub in_flight_high_water: u64,
+    /// Requests refused for exceeding the query rate.
+    pub refused_query_rate: u64,
+    /// Requests refused for exceeding the capture rate.
+    pub refused_capture_rate: u64,
+    /// Requests refused because the client had too many handlers running.
+    pub refused_in_flight: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    clients: HashMap<String, Client>,
+    stats: RequestStats,
+}
+
+/// Admits D-Bus requests per client under [`RequestLimits`].
+pub struct RequestLimiter {
+    limits: RequestLimits,
+    inner: Mutex<Inner>,
+}
+
+impl RequestLimiter {
+    pub fn new(limits: RequestLimits) -> Self {
+        Self {
+            limits,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Admit a `class` request from `client`, or refuse it with
+    /// `TooManyRequests`. The request counts as in flight until the returned
+    /// guard is dropped.
+    pub fn admit(&self, client: &str, class: RequestClass) -> Result<InFlight<'_>, VisageError> {
+        self.admit_at(client, class, Instant::now())
+    }
+
+    fn admit_at(
+        &self,
+        client: &str,
+        class: RequestClass,
+        now: Instant,
+    ) -> Result<InFlight<'_>, VisageError> {
+        let limits = &self.limits;
+        let mut inner = self.lock();
+        let Inner { clients, stats } = &mut *inner;
+        if clients.len() > PRUNE_ABOVE {
+            clients.retain(|_, c| !c.idle(limits, now));
+        }
+        let record = clients
+            .entry(client.to_string())
+            .or_insert_with(|| Client::new(limits, now));
+
+        if limits.max_in_flight != 0 && record.in_flight >= limits.max_in_flight {
+            stats.refused_in_flight += 1;
+            tracing::debug!(client, in_flight = record.in_flight, "request refused");
+            return Err(VisageError::TooManyRequests(format!(
+                "{} requests from this client are already running",
+                record.in_flight
+            )));
+        }
+        let bucket = match class {
+            RequestClass::Query => &mut record.query,
+            RequestClass::Capture => &mut record.capture,
+        };
+        if let Err(wait) = bucket.take(limits.budget(class), now) {
+            match class {
+                RequestClass::Query => stats.refused_query_rate += 1,
+                RequestClass::Capture => stats.refused_capture_rate += 1,
+            }
+            tracing::debug!(client, ?class, "request refused");
+            return Err(VisageError::TooManyRequests(format!(
+                "too many requests from this client; retry in {} ms",
+                wait.as_millis().max(1)
+            )));
+        }
+
+        record.in_flight += 1;
+        stats.in_flight += 1;
+        stats.in_flight_high_water = stats.in_flight_high_water.max(stats.in_flight);
+        Ok(InFlight {
+            limiter: self,
+            client: client.to_string(),
+        })
+    }
+
+    /// Snapshot of the counters.
+    pub fn stats(&self) -> RequestStats {
+        self.lock().stats.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // The bookkeeping cannot panic halfway; a poisoned lock is still sound.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An admitted request; dropping it ends the request.
+#[must_use = "the request stops counting as in flight when this is dropped"]
+pub struct InFlight<'a> {
+    limiter: &'a RequestLimiter,
+    client: String,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.limiter.lock();
+        inner.stats.in_flight = inner.stats.in_flight.saturating_sub(1);
+        if let Some(client) = inner.clients.get_mut(&self.client) {
+            client.in_flight = client.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(query_per_sec: u32, capture_per_min: u32, max_in_flight: u32) -> RequestLimiter {
+        RequestLimiter::new(RequestLimits {
+            query_per_sec,
+            capture_per_min,
+            max_in_flight,
+        })
+    }
+
+    #[test]
+    fn test_bucket_refuses_a_burst_and_refills_over_time() {
+        let limiter = limiter(4, 2, 0);
+        let t0 = Instant::now();
+        for _ in 0..4 {
+            drop(limiter.admit_at(":1.5", RequestClass::Query, t0).unwrap());
+        }
+        let err = limiter
+            .admit_at(":1.5", RequestClass::Query, t0)
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, VisageError::TooManyRequests(m) if m.contains("250 ms")),
+            "{err:?}"
+        );
+
+        // Other clients and the capture budget are untouched.
+        drop(limiter.admit_at(":1.6", RequestClass::Query, t0).unwrap());
+        drop(limiter.admit_at(":1.5", RequestClass::Capture, t0).unwrap());
+
+        let later = t0 + Duration::from_millis(500);
+        drop(
+            limiter
+                .admit_at(":1.5", RequestClass::Query, later)
+                .unwrap(),
+        );
+        drop(
+            limiter
+                .admit_at(":1.5", RequestClass::Query, later)
+                .unwrap(),
+        );
+        assert!(limiter
+            .admit_at(":1.5", RequestClass::Query, later)
+            .is_err());
+
+        let stats = limiter.stats();
+        assert_eq!(stats.refused_query_rate, 2);
+        assert_eq!(stats.refused_capture_rate, 0);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.in_flight_high_water, 1);
+    }
+
+    #[test]
+    fn test_in_flight_cap_releases_when_requests_finish() {
+        let limiter = limiter(0, 0, 2);
+        let t0 = Instant::now();
+        let first = limiter.admit_at(":1.5", RequestClass::Capture, t0).unwrap();
+        let _second = limiter.admit_at(":1.5", RequestClass::Query, t0).unwrap();
+        assert!(limiter.admit_at(":1.5", RequestClass::Query, t0).is_err());
+        let _other = limiter.admit_at(":1.6", RequestClass::Query, t0).unwrap();
+
+        drop(first);
+        let _third = limiter.admit_at(":1.5", RequestClass::Query, t0).unwrap();
+        let stats = limiter.stats();
+        assert_eq!(stats.refused_in_flight, 1);
+        assert_eq!(stats.in_flight, 3);
+        assert_eq!(stats.in_flight_high_water, 3);
+    }
+
+    #[test]
+    fn test_idle_clients_are_pruned_but_busy_ones_kept() {
+        let limiter = limiter(1, 1, 1);
+        let t0 = Instant::now();
+        let busy = limiter.admit_at(":1.0", RequestClass::Query, t0).unwrap();
+        for i in 1..=PRUNE_ABOVE {
+            drop(
+                limiter
+                    .admit_at(&format!(":1.{i}"), RequestClass::Query, t0)
+                    .unwrap(),
+            );
+        }
+        let later = t0 + Duration::from_secs(2);
+        drop(
+            limiter
+                .admit_at(":2.0", RequestClass::Query, later)
+                .unwrap(),
+        );
+        assert_eq!(limiter.lock().clients.len(), 2);
+
+        // The busy client is still capped after the prune.
+        assert!(limiter
+            .admit_at(":1.0", RequestClass::Query, later)
+            .is_err());
+        drop(busy);
+    }
+}
@@ -0,0 +1,326 @@
+//! Time windows during which face unlock is permitted.
+//!
+//! A window is written `[days] HH:MM-HH:MM`; several are separated by `;`,
+//! e.g. `mon-fri 08:00-18:00; sat,sun 10:00-14:00`. Days are names (`mon`,
+//! `monday`), comma lists, or ranges (`fri-mon` wraps past Sunday); without
+//! them the window applies every day. The end time is exclusive and may be
+//! `24:00`. A window whose end is earlier than its start runs overnight and
+//! belongs to the day it starts on: `fri 22:00-06:00` covers Friday night
+//! into Saturday morning.
+//!
+//! Windows are compared against the daemon's local wall clock, not elapsed
+//! time, so a DST change shifts nothing: on the spring-forward day the
+//! skipped hour is never observed, and on the fall-back day the repeated hour
+//! is evaluated twice with the same answer.
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Timelike, Weekday};
+use thiserror::Error;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const ALL_DAYS: u8 = 0b111_1111;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("window '{0}' must be '[days] HH:MM-HH:MM'")]
+    Malformed(String),
+    #[error("unknown day '{0}' (expected mon … sun)")]
+    UnknownDay(String),
+    #[error("invalid time '{0}' (expected HH:MM, 00:00–24:00)")]
+    InvalidTime(String),
+    #[error("window '{0}' starts and ends at the same time; use 00:00-24:00 for a whole day")]
+    Empty(String),
+}
+
+/// One recurring window: a set of weekdays and a time-of-day range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    /// Bit `n` set for the weekday `n` days from Monday.
+    days: u8,
+    /// Minutes after midnight, inclusive.
+    start: u32,
+    /// Minutes after midnight, exclusive; below `start` for overnight windows.
+    end: u32,
+}
+
+impl Window {
+    /// Whether the wall-clock minute `minute` on `weekday` falls inside.
+    fn contains(&self, weekday: Weekday, minute: u32) -> bool {
+        let on = |day: Weekday| self.days & day_bit(day) != 0;
+        if self.start < self.end {
+            on(weekday) && (self.start..self.end).contains(&minute)
+        } else {
+            // Overnight: the evening of a listed day, or the morning after one.
+            (on(weekday) && minute >= self.start) || (on(weekday.pred()) && minute < self.end)
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days != ALL_DAYS {
+            let names: Vec<String> = (0..7)
+                .filter(|n| self.days & (1 << n) != 0)
+                .map(|n| weekday_from_monday(n).to_string().to_lowercase())
+                .collect();
+            write!(f, "{} ", names.join(","))?;
+        }
+        let hm = |m: u32| format!("{:02}:{:02}", m / 60, m % 60);
+        write!(f, "{}-{}", hm(self.start), hm(self.end))
+    }
+}
+
+/// Include and exclude windows for verification. The default (no windows)
+/// permits every time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    allowed: Vec<Window>,
+    blocked: Vec<Window>,
+}
+
+impl Schedule {
+    /// Permit times inside any `allowed` window (or any time, if there are
+    /// none) that are not inside a `blocked` window.
+    pub fn new(allowed: Vec<Window>, blocked: Vec<Window>) -> Self {
+        Self { allowed, blocked }
+    }
+
+    /// Whether no windows are configured.
+    pub fn is_unrestricted(&self) -> bool {
+        self.allowed.is_empty() && self.blocked.is_empty()
+    }
+
+    /// Whether verification is permitted at the wall-clock time `at`.
+    pub fn permits(&self, at: NaiveDateTime) -> bool {
+        let (weekday, minute) = (at.weekday(), at.hour() * 60 + at.minute());
+        let inside = |windows: &[Window]| windows.iter().any(|w| w.contains(weekday, minute));
+        (self.allowed.is_empty() || inside(&self.allowed)) && !inside(&self.blocked)
+    }
+
+    /// [`permits`](Self::permits) for the local time of `at` in its own zone.
+    pub fn permits_at<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> bool {
+        self.permits(at.naive_local())
+    }
+
+    /// Allowed windows, in their normalized text form (for Status).
+    pub fn allowed(&self) -> Vec<String> {
+        self.allowed.iter().map(Window::to_string).collect()
+    }
+
+    /// Blocked windows, in their normalized text form (for Status).
+    pub fn blocked(&self) -> Vec<String> {
+        self.blocked.iter().map(Window::to_string).collect()
+    }
+}
+
+/// Parse a `;`-separated list of windows. Blank entries are ignored, so an
+/// empty string yields no windows.
+pub fn parse_windows(spec: &str) -> Result<Vec<Window>, ScheduleError> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(parse_window)
+        .collect()
+}
+
+fn parse_window(text: &str) -> Result<Window, ScheduleError> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let (days, range) = match fields.as_slice() {
+        [range] => (ALL_DAYS, *range),
+        [days, range] => (parse_days(days)?, *range),
+        _ => return Err(ScheduleError::Malformed(text.to_string())),
+    };
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| ScheduleError::Malformed(text.to_string()))?;
+    let start = parse_time(start)?;
+    let end = parse_time(end)?;
+    if start == MINUTES_PER_DAY {
+        return Err(ScheduleError::InvalidTime("24:00".to_string()));
+    }
+    if start == end {
+        return Err(ScheduleError::Empty(text.to_string()));
+    }
+    Ok(Window { days, start, end })
+}
+
+fn parse_days(text: &str) -> Result<u8, ScheduleError> {
+    let mut days = 0;
+    for item in text.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (parse_day(first)?, parse_day(last)?),
+            None => (parse_day(item)?, parse_day(item)?),
+        };
+        let mut day = first;
+        loop {
+            days |= day_bit(day);
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+    Ok(days)
+}
+
+fn parse_day(text: &str) -> Result<Weekday, ScheduleError> {
+    text.parse()
+        .map_err(|_| ScheduleError::UnknownDay(text.to_string()))
+}
+
+/// Minutes after midnight for `HH:MM`; `24:00` is accepted as end of day.
+fn parse_time(text: &str) -> Result<u32, ScheduleError> {
+    let invalid = || ScheduleError::InvalidTime(text.to_string());
+    let (h, m) = text.split_once(':').ok_or_else(invalid)?;
+    if h.is_empty() || h.len() > 2 || m.len() != 2 {
+        return Err(invalid());
+    }
+    let h: u32 = h.parse().map_err(|_| invalid())?;
+    let m: u32 = m.parse().map_err(|_| invalid())?;
+    match (h, m) {
+        (24, 0) => Ok(MINUTES_PER_DAY),
+        (0..=23, 0..=59) => Ok(h * 60 + m),
+        _ => Err(invalid()),
+    }
+}
+
+fn day_bit(day: Weekday) -> u8 {
+    1 << day.num_days_from_monday()
+}
+
+fn weekday_from_monday(n: u32) -> Weekday {
+    (0..n).fold(Weekday::Mon, |day, _| day.succ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDate};
+
+    /// 2026-10-12 is a Monday.
+    fn at(day_of_month: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day_of_month)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedule(allowed: &str, blocked: &str) -> Schedule {
+        Schedule::new(
+            parse_windows(allowed).unwrap(),
+            parse_windows(blocked).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_default_permits_any_time() {
+        let s = Schedule::default();
+        assert!(s.is_unrestricted());
+        assert_eq!(schedule("", " ; "), s);
+        for day in 12..19 {
+            for hour in 0..24 {
+                assert!(s.permits(at(day, hour, 0)));
+                assert!(s.permits(at(day, hour, 59)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_window_boundaries() {
+        let s = schedule("mon-fri 08:00-18:00", "");
+        assert!(!s.permits(at(12, 7, 59)));
+        assert!(s.permits(at(12, 8, 0)), "start is inclusive");
+        assert!(s.permits(at(12, 17, 59)));
+        assert!(!s.permits(at(12, 18, 0)), "end is exclusive");
+        assert!(s.permits(at(16, 12, 0)), "friday");
+        assert!(!s.permits(at(17, 12, 0)), "saturday");
+
+        let whole_day = schedule("sun 00:00-24:00", "");
+        assert!(whole_day.permits(at(18, 0, 0)));
+        assert!(whole_day.permits(at(18, 23, 59)));
+        assert!(!whole_day.permits(at(19, 0, 0)), "monday");
+    }
+
+    #[test]
+    fn test_overnight_window_wraps_into_next_day() {
+        let s = schedule("fri 22:00-06:00", "");
+        assert!(!s.permits(at(16, 21, 59)));
+        assert!(s.permits(at(16, 22, 0)), "friday night");
+        assert!(s.permits(at(17, 0, 0)), "saturday midnight");
+        assert!(s.permits(at(17, 5, 59)));
+        assert!(!s.permits(at(17, 6, 0)));
+        assert!(!s.permits(at(17, 22, 30)), "saturday night is not listed");
+        assert!(
+            !s.permits(at(16, 3, 0)),
+            "friday morning belongs to thursday"
+        );
+
+        // Sunday night wraps into Monday of the next week.
+        let s = schedule("sun 23:00-01:00", "");
+        assert!(s.permits(at(19, 0, 30)));
+        assert!(!s.permits(at(18, 0, 30)));
+    }
+
+    #[test]
+    fn test_blocked_windows_override_allowed() {
+        let s = schedule("08:00-18:00", "12:00-13:00; sat,sun 00:00-24:00");
+        assert!(s.permits(at(12, 11, 59)));
+        assert!(!s.permits(at(12, 12, 30)));
+        assert!(s.permits(at(12, 13, 0)));
+        assert!(!s.permits(at(17, 10, 0)), "weekend blocked");
+
+        // Exclude-only: password-only at night, every night.
+        let s = schedule("", "22:00-06:00");
+        assert!(s.permits(at(14, 21, 59)));
+        assert!(!s.permits(at(14, 23, 0)));
+        assert!(!s.permits(at(15, 5, 59)));
+        assert!(s.permits(at(15, 6, 0)));
+    }
+
+    #[test]
+    fn test_evaluates_local_wall_clock_across_dst() {
+        // The same instant on either side of a DST change: only the local
+        // wall-clock reading matters, so 07:30 UTC is inside 08:00-18:00 at
+        // UTC+1 and outside it at UTC+0.
+        let s = schedule("08:00-18:00", "");
+        let instant = at(26, 7, 30).and_utc();
+        let summer = FixedOffset::east_opt(3600).unwrap();
+        let winter = FixedOffset::east_opt(0).unwrap();
+        assert!(s.permits_at(&instant.with_timezone(&summer)));
+        assert!(!s.permits_at(&instant.with_timezone(&winter)));
+    }
+
+    #[test]
+    fn test_parse_day_lists_and_wrapping_ranges() {
+        let w = parse_windows("Fri-Mon,wed 09:30-10:00").unwrap();
+        assert_eq!(w[0].to_string(), "mon,wed,fri,sat,sun 09:30-10:00");
+        let w = parse_windows("monday 00:00-24:00").unwrap();
+        assert_eq!(w[0].to_string(), "mon 00:00-24:00");
+        assert_eq!(
+            parse_windows("mon-sun 1:05-2:00").unwrap()[0].to_string(),
+            "01:05-02:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_windows() {
+        for (spec, err) in [
+            ("08:00", ScheduleError::Malformed("08:00".into())),
+            (
+                "mon fri 08:00-09:00",
+                ScheduleError::Malformed("mon fri 08:00-09:00".into()),
+            ),
+            (
+                "funday 08:00-09:00",
+                ScheduleError::UnknownDay("funday".into()),
+            ),
+            ("08:00-25:00", ScheduleError::InvalidTime("25:00".into())),
+            ("08:60-09:00", ScheduleError::InvalidTime("08:60".into())),
+            ("24:00-06:00", ScheduleError::InvalidTime("24:00".into())),
+            ("08:00-08:00", ScheduleError::Empty("08:00-08:00".into())),
+        ] {
+            assert_eq!(parse_windows(spec), Err(err), "{spec}");
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Scheduled camera self-test (`VISAGE_SELFTEST_INTERVAL_HOURS`).
+//!
+//! Every interval the daemon captures a few raw frames and runs the detector
+//! on them — no embeddings, no user involved — to find a camera that has
+//! stopped delivering frames before someone is locked out at the login
+//! screen. After [`FAILURES_BEFORE_DEGRADED`] consecutive failures Status
+//! reports `camera_degraded` and a `HealthChanged` signal is emitted; the
+//! first passing test clears the flag and signals again.
+//!
+//! A test is skipped, not failed, while the engine is busy (a verify or any
+//! other request is in flight or queued) or the laptop lid is closed. During
+//! the configured quiet hours the emitter stays dark so the sensor does not
+//! flash at night.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::dbus_interface::AppState;
+
+/// Consecutive failed self-tests before the camera is reported degraded.
+pub const FAILURES_BEFORE_DEGRADED: u32 = 2;
+
+/// Raw frames captured per self-test.
+const SELFTEST_FRAMES: usize = 3;
+
+/// Where ACPI exposes lid switches (`<lid>/state` reads `state: open`).
+const ACPI_LID_DIR: &str = "/proc/acpi/button/lid";
+
+/// Called with the new `camera_degraded` value whenever it flips.
+pub type Notify = Arc<dyn Fn(bool) + Send + Sync>;
+
+/// Result of the most recent self-test, as reported in Status.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SelfTestOutcome {
+    /// RFC 3339 timestamp of the test.
+    pub at: String,
+    pub passed: bool,
+    pub frames: usize,
+    pub faces_detected: usize,
+    pub emitter_used: bool,
+    pub error: Option<String>,
+}
+
+/// Self-test history kept in [`AppState`].
+#[derive(Debug, Default)]
+pub struct SelfTestState {
+    consecutive_failures: u32,
+    camera_degraded: bool,
+    last: Option<SelfTestOutcome>,
+}
+
+impl SelfTestState {
+    /// Whether the last [`FAILURES_BEFORE_DEGRADED`] self-tests all failed.
+    pub fn camera_degraded(&self) -> bool {
+        self.camera_degraded
+    }
+
+    pub fn last(&self) -> Option<&SelfTestOutcome> {
+        self.last.as_ref()
+    }
+
+    /// Record `outcome`; returns the new `camera_degraded` value if it flipped.
+    fn record(&mut self, outcome: SelfTestOutcome) -> Option<bool> {
+        self.consecutive_failures = if outcome.passed {
+            0
+        } else {
+            self.consecutive_failures + 1
+        };
+        self.last = Some(outcome);
+        let degraded = self.consecutive_failures >= FAILURES_BEFORE_DEGRADED;
+        if degraded == self.camera_degraded {
+            return None;
+        }
+        self.camera_degraded = degraded;
+        Some(degraded)
+    }
+}
+
+/// Why a scheduled self-test did not run.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Skipped {
+    /// The engine had a request in flight or queued.
+    EngineBusy,
+    LidClosed,
+}
+
+/// Run a self-test every `interval`, forever. The first test runs one
+/// interval after startup, not immediately.
+pub async fn run(state: Arc<Mutex<AppState>>, interval: Duration, notify: Notify) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let now = chrono::Local::now().naive_local();
+        if let Err(reason) = run_once(&state, now, lid_closed(), &notify).await {
+            tracing::debug!(?reason, "camera self-test skipped");
+        }
+    }
+}
+
+/// Run one self-test at local time `now` unless the engine is busy or the
+/// lid is closed. Returns whether the test passed.
+pub async fn run_once(
+    state: &Mutex<AppState>,
+    now: chrono::NaiveDateTime,
+    lid_closed: bool,
+    notify: &Notify,
+) -> Result<bool, Skipped> {
+    let (engine, timeout, use_emitter) = {
+        let state = state.lock().await;
+        (
+            state.engine.clone(),
+            Duration::from_secs(state.config.verify_timeout_secs),
+            state.config.selftest_emitter_schedule.permits(now),
+        )
+    };
+    if lid_closed {
+        return Err(Skipped::LidClosed);
+    }
+    let queue = engine.queue_status();
+    if queue.in_flight.is_some() || queue.queued > 0 {
+        return Err(Skipped::EngineBusy);
+    }
+
+    let result = engine
+        .self_test(SELFTEST_FRAMES, timeout, use_emitter)
+        .await;
+    let outcome = match &result {
+        Ok(report) => {
+            tracing::debug!(
+                frames = report.frames,
+                faces = report.faces_detected,
+                mean_brightness = report.mean_brightness,
+                emitter = report.emitter_used,
+                "camera self-test passed"
+            );
+            SelfTestOutcome {
+                at: chrono::Utc::now().to_rfc3339(),
+                passed: true,
+                frames: report.frames,
+                faces_detected: report.faces_detected,
+                emitter_used: report.emitter_used,
+                error: None,
+            }
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "camera self-test failed");
+            SelfTestOutcome {
+                at: chrono::Utc::now().to_rfc3339(),
+                passed: false,
+                frames: 0,
+                faces_detected: 0,
+                emitter_used: false,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    let passed = outcome.passed;
+    let flipped = state.lock().await.selftest.record(outcome);
+    if let Some(degraded) = flipped {
+        if degraded {
+            tracing::error!(
+                failures = FAILURES_BEFORE_DEGRADED,
+                "camera failed consecutive self-tests — marked degraded"
+            );
+        } else {
+            tracing::info!("camera self-test passed again — degraded flag cleared");
+        }
+        notify(degraded);
+    }
+    Ok(passed)
+}
+
+/// Whether any ACPI lid switch reports closed. Machines without one (or
+/// without ACPI) count as open.
+fn lid_closed() -> bool {
+    lid_closed_in(Path::new(ACPI_LID_DIR))
+}
+
+fn lid_closed_in(dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        std::fs::read_to_string(entry.path().join("state"))
+            .is_ok_and(|state| state.contains("closed"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::{CancelToken, EngineHandle};
+    use crate::polkit::{Authorization, MockAuthority};
+    use crate::preview::PreviewSessions;
+    use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+    use crate::stats::QualityStats;
+    use crate::store::FaceModelStore;
+    use crate::verify_cancel::PendingVerifies;
+    use crate::verify_grace::VerifyGrace;
+    use crate::verify_token::VerifyTokens;
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    async fn state_with(engine: EngineHandle) -> Arc<Mutex<AppState>> {
+        Arc::new(Mutex::new(AppState {
+            config: Config::from_pairs(&[]).unwrap(),
+            engine,
+            store: FaceModelStore::open(Path::new(":memory:"), 1, false)
+                .await
+                .unwrap(),
+            rate_limiter: RateLimiter::new(),
+            enroll_cooldown: EnrollCooldown::new(Duration::ZERO),
+            previews: PreviewSessions::new(Duration::from_secs(30)),
+            verify_tokens: VerifyTokens::new(Duration::ZERO),
+            verify_grace: VerifyGrace::new(Duration::ZERO),
+            pending_verifies: PendingVerifies::default(),
+            stats: QualityStats::new(),
+            selftest: SelfTestState::default(),
+            log_filter: None,
+            authority: Arc::new(MockAuthority::answering(Authorization::NotAuthorized)),
+        }))
+    }
+
+    /// Notify hook that records every flag change it receives.
+    fn recording_notify() -> (Notify, Arc<std::sync::Mutex<Vec<bool>>>) {
+        let signals = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = signals.clone();
+        let notify: Notify = Arc::new(move |degraded| sink.lock().unwrap().push(degraded));
+        (notify, signals)
+    }
+
+    fn at(hour: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, 4)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_consecutive_failures_flag_camera_and_signal() {
+        let broken = Arc::new(AtomicBool::new(false));
+        let state = state_with(EngineHandle::flaky_camera(broken.clone())).await;
+        let (notify, signals) = recording_notify();
+
+        assert_eq!(run_once(&state, at(12), false, &notify).await, Ok(true));
+        broken.store(true, Ordering::SeqCst);
+        assert_eq!(run_once(&state, at(12), false, &notify).await, Ok(false));
+        assert!(!state.lock().await.selftest.camera_degraded());
+        assert!(signals.lock().unwrap().is_empty());
+
+        assert_eq!(run_once(&state, at(12), false, &notify).await, Ok(false));
+        {
+            let state = state.lock().await;
+            assert!(state.selftest.camera_degraded());
+            let last = state.selftest.last().unwrap();
+            assert!(!last.passed);
+            assert!(last.error.as_deref().unwrap().contains("EIO"));
+        }
+        // Further failures do not signal again.
+        run_once(&state, at(12), false, &notify).await.unwrap();
+        assert_eq!(*signals.lock().unwrap(), [true]);
+
+        broken.store(false, Ordering::SeqCst);
+        assert_eq!(run_once(&state, at(12), false, &notify).await, Ok(true));
+        assert!(!state.lock().await.selftest.camera_degraded());
+        assert_eq!(*signals.lock().unwrap(), [true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_skips_when_lid_closed_or_engine_busy() {
+        let (notify, signals) = recording_notify();
+        let state = state_with(EngineHandle::disconnected()).await;
+        assert_eq!(
+            run_once(&state, at(12), true, &notify).await,
+            Err(Skipped::LidClosed)
+        );
+
+        // A verify is waiting on the engine.
+        let engine = EngineHandle::stuck();
+        let state = state_with(engine.clone()).await;
+        let caller = tokio::spawn(async move {
+            engine
+                .verify(
+                    vec![],
+                    None,
+                    0.4,
+                    3,
+                    Duration::from_secs(30),
+                    None,
+                    None,
+                    None,
+                    None,
+                    CancelToken::default(),
+                )
+                .await
+        });
+        while state.lock().await.engine.queue_status().queued == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(
+            run_once(&state, at(12), false, &notify).await,
+            Err(Skipped::EngineBusy)
+        );
+        caller.abort();
+        assert!(state.lock().await.selftest.last().is_none());
+        assert!(signals.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lid_state_read_from_acpi() {
+        let dir = std::env::temp_dir().join(format!("visage-lid-{}", uuid::Uuid::new_v4()));
+        assert!(!lid_closed_in(&dir));
+        std::fs::create_dir_all(dir.join("LID0")).unwrap();
+        std::fs::write(dir.join("LID0/state"), "state:      open\n").unwrap();
+        assert!(!lid_closed_in(&dir));
+        std::fs::write(dir.join("LID0/state"), "state:      closed\n").unwrap();
+        assert!(lid_closed_in(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+//! Aggregate, non-biometric capture and verification statistics.
+//!
+//! Only counters and latencies are kept — never user names, model IDs,
+//! similarity scores, or image data — so the summary is safe to share in
+//! hardware compatibility reports. The last verify's capture context names
+//! only the device, format and processing.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use visage_hw::CaptureContext;
+
+/// Number of recent verify latencies kept for the median.
+const LATENCY_WINDOW: usize = 256;
+/// Detection confidence histogram buckets over [0, 1].
+const CONFIDENCE_BUCKETS: usize = 10;
+
+/// In-memory statistics since daemon start.
+pub struct QualityStats {
+    started: Instant,
+    verify_latencies_ms: VecDeque<u64>,
+    verify_count: u64,
+    confidence_histogram: [u64; CONFIDENCE_BUCKETS],
+    frames_captured: u64,
+    dark_frames: u64,
+    frames_held_high_water: u64,
+    frame_bytes_high_water: u64,
+    last_verify_capture: Option<CaptureContext>,
+}
+
+/// Serializable snapshot of [`QualityStats`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatsSummary {
+    pub verify_count: u64,
+    /// Median latency of the most recent verifies (up to 256), if any.
+    pub median_verify_latency_ms: Option<u64>,
+    /// Best-face detection confidence per verify, in ten 0.1-wide buckets.
+    pub confidence_histogram: [u64; CONFIDENCE_BUCKETS],
+    pub frames_captured: u64,
+    pub dark_frames: u64,
+    /// Fraction of raw frames rejected as dark (0.0 when nothing captured yet).
+    pub dark_frame_rate: f64,
+    /// Most frames any single enroll or verify held in memory at once.
+    pub frames_held_high_water: u64,
+    /// Most frame pixel bytes any single enroll or verify held at once.
+    pub frame_bytes_high_water: u64,
+    /// Camera, format and emitter state of the most recent verify capture.
+    pub last_verify_capture: Option<CaptureContext>,
+}
+
+impl Default for QualityStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            verify_latencies_ms: VecDeque::new(),
+            verify_count: 0,
+            confidence_histogram: [0; CONFIDENCE_BUCKETS],
+            frames_captured: 0,
+            dark_frames: 0,
+            frames_held_high_water: 0,
+            frame_bytes_high_water: 0,
+            last_verify_capture: None,
+        }
+    }
+}
+
+impl QualityStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time since the stats were created at daemon start, on the monotonic
+    /// clock, so it does not advance while the machine is suspended.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Record a completed verify that detected a face.
+    pub fn record_verify(&mut self, latency: Duration, best_confidence: f32) {
+        self.verify_count += 1;
+        if self.verify_latencies_ms.len() == LATENCY_WINDOW {
+            self.verify_latencies_ms.pop_front();
+        }
+        self.verify_latencies_ms
+            .push_back(latency.as_millis().min(u64::MAX as u128) as u64);
+
+        let bucket = (best_confidence.clamp(0.0, 1.0) * CONFIDENCE_BUCKETS as f32) as usize;
+        self.confidence_histogram[bucket.min(CONFIDENCE_BUCKETS - 1)] += 1;
+    }
+
+    /// Record a capture sequence: `captured` usable frames and `dark` rejected ones.
+    pub fn record_capture(&mut self, captured: usize, dark: usize) {
+        self.frames_captured += captured as u64;
+        self.dark_frames += dark as u64;
+    }
+
+    /// Record the peak frame memory of one request, keeping the maximum.
+    pub fn record_frame_memory(&mut self, frames: usize, bytes: usize) {
+        self.frames_held_high_water = self.frames_held_high_water.max(frames as u64);
+        self.frame_bytes_high_water = self.frame_bytes_high_water.max(bytes as u64);
+    }
+
+    /// Remember which camera and processing the latest verify ran on.
+    pub fn record_verify_capture(&mut self, capture: CaptureContext) {
+        self.last_verify_capture = Some(capture);
+    }
+
+    pub fn summary(&self) -> StatsSummary {
+        let mut sorted: Vec<u64> = self.verify_latencies_ms.iter().copied().collect();
+        sorted.sort_unstable();
+        let total_raw = self.frames_captured + self.dark_frames;
+
+        StatsSummary {
+            verify_count: self.verify_count,
+            median_verify_latency_ms: sorted.get(sorted.len() / 2).copied(),
+            confidence_histogram: self.confidence_histogram,
+            frames_captured: self.frames_captured,
+            dark_frames: self.dark_frames,
+            dark_frame_rate: if total_raw == 0 {
+                0.0
+            } else {
+                self.dark_frames as f64 / total_raw as f64
+            },
+            frames_held_high_water: self.frames_held_high_water,
+            frame_bytes_high_water: self.frame_bytes_high_water,
+            last_verify_capture: self.last_verify_capture.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_summary() {
+        let summary = QualityStats::new().summary();
+        assert_eq!(summary.verify_count, 0);
+        assert_eq!(summary.median_verify_latency_ms, None);
+        assert_eq!(summary.dark_frame_rate, 0.0);
+    }
+
+    #[test]
+    fn test_median_and_histogram() {
+        let mut stats = QualityStats::new();
+        for (ms, conf) in [(300, 0.95), (100, 0.72), (200, 0.78), (5000, 1.0)] {
+            stats.record_verify(Duration::from_millis(ms), conf);
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.verify_count, 4);
+        assert_eq!(summary.median_verify_latency_ms, Some(300));
+        assert_eq!(summary.confidence_histogram[7], 2);
+        assert_eq!(summary.confidence_histogram[9], 2);
+    }
+
+    #[test]
+    fn test_latency_window_is_bounded() {
+        let mut stats = QualityStats::new();
+        for _ in 0..LATENCY_WINDOW + 10 {
+            stats.record_verify(Duration::from_millis(10), 0.5);
+        }
+        assert_eq!(stats.verify_latencies_ms.len(), LATENCY_WINDOW);
+        assert_eq!(stats.summary().verify_count, (LATENCY_WINDOW + 10) as u64);
+    }
+
+    #[test]
+    fn test_dark_frame_rate() {
+        let mut stats = QualityStats::new();
+        stats.record_capture(3, 1);
+        stats.record_capture(5, 3);
+        assert_eq!(stats.summary().dark_frame_rate, 4.0 / 12.0);
+    }
+
+    #[test]
+    fn test_last_verify_capture_is_reported() {
+        let mut stats = QualityStats::new();
+        assert!(stats.summary().last_verify_capture.is_none());
+        let capture = CaptureContext {
+            device: "/dev/video2".to_string(),
+            device_id: "usb:04f2:b6d9.2".to_string(),
+            fourcc: "GREY".to_string(),
+            width: 640,
+            height: 360,
+            emitter_active: true,
+            preprocessing: vec!["clahe".to_string()],
+        };
+        stats.record_verify_capture(capture.clone());
+        let json = serde_json::to_value(stats.summary()).unwrap();
+        assert_eq!(json["last_verify_capture"]["device"], "/dev/video2");
+        assert_eq!(json["last_verify_capture"]["emitter_active"], true);
+        assert_eq!(stats.summary().last_verify_capture, Some(capture));
+    }
+
+    #[test]
+    fn test_frame_memory_high_water_mark() {
+        let mut stats = QualityStats::new();
+        stats.record_frame_memory(2, 4_000_000);
+        stats.record_frame_memory(1, 2_000_000);
+        let summary = stats.summary();
+        assert_eq!(summary.frames_held_high_water, 2);
+        assert_eq!(summary.frame_bytes_high_water, 4_000_000);
+    }
+}
@@ -0,0 +1,315 @@
+//! The daemon status behind `Status` and `GetStatus`.
+//!
+//! `Status` replies with a JSON string and `GetStatus` with a typed `a{sv}`
+//! dictionary, for clients that would rather not parse JSON. Both are built
+//! from one [`StatusInfo`]: the dictionary is derived from the struct's
+//! serde output, so the two carry the same keys and values by construction.
+//!
+//! A dictionary value cannot be null, so a key whose JSON value is `null`
+//! is left out of `GetStatus`. Nested objects become `a{sv}` and lists
+//! become `av`, whatever their contents, so a key's signature never depends
+//! on whether its list happens to be empty. Whole numbers are `t` (`x` if
+//! negative), fractions `d`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::capabilities;
+use crate::dbus_interface::AppState;
+use crate::selftest::SelfTestOutcome;
+use crate::store::{ModelVersionCount, UpgradeImpact};
+
+/// Everything `Status` reports. Field names are the JSON and dictionary
+/// keys; renaming or removing one bumps
+/// [`capabilities::STATUS_SCHEMA_VERSION`].
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusInfo {
+    pub status_schema_version: u32,
+    pub version: String,
+    pub capabilities: Vec<String>,
+    pub degraded: bool,
+    /// Why the recognizer could not be loaded, if it could not.
+    pub recognizer_error: Option<String>,
+    /// Seconds since the daemon started, not counting suspend.
+    pub uptime_secs: u64,
+    pub camera: String,
+    pub model_dir: String,
+    pub model_manifest: Option<String>,
+    pub db_path: String,
+    pub models_enrolled: u64,
+    pub models_by_version: Vec<ModelVersionCount>,
+    pub model_upgrade: Option<UpgradeImpact>,
+    pub similarity_threshold: f32,
+    pub insecure_config: bool,
+    pub verify_timeout_secs: u64,
+    pub enroll_timeout_secs: u64,
+    pub privacy_mode: bool,
+    pub warmup_frames: usize,
+    pub startup_selftest: bool,
+    pub frames_per_verify: usize,
+    pub frames_per_enroll: usize,
+    pub exposure_report_frames: usize,
+    pub emitter_enabled: bool,
+    pub lazy_camera: bool,
+    pub engine_watchdog_secs: u64,
+    pub rate_limit_decay_secs: u64,
+    pub centroid_matching: bool,
+    pub centroid_margin: f32,
+    pub verify_report_timing: bool,
+    pub verify_require_pose_match: bool,
+    pub verify_require_enrolled_device: bool,
+    pub liveness_enabled: bool,
+    pub liveness_min_displacement: f32,
+    pub liveness_min_live_pairs: usize,
+    pub enroll_min_alignment: f32,
+    pub enroll_require_liveness: bool,
+    pub enroll_crop_hash: bool,
+    pub verify_allowed_windows: Vec<String>,
+    pub verify_blocked_windows: Vec<String>,
+    pub selftest_interval_hours: u64,
+    pub selftest_quiet_hours: Vec<String>,
+    pub camera_degraded: bool,
+    pub last_selftest: Option<SelfTestOutcome>,
+    pub session_bus: bool,
+    pub config_file: Option<String>,
+    pub config_warnings: Vec<String>,
+}
+
+impl StatusInfo {
+    /// Snapshot the daemon's state. Store counts that cannot be read are
+    /// reported as zero or left out rather than failing the call.
+    pub async fn collect(state: &AppState) -> Self {
+        let config = &state.config;
+        let recognizer_error = state.engine.recognizer_fault().map(|e| e.to_string());
+        Self {
+            status_schema_version: capabilities::STATUS_SCHEMA_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: capabilities::names(config),
+            degraded: recognizer_error.is_some(),
+            recognizer_error,
+            uptime_secs: state.stats.uptime().as_secs(),
+            camera: config.camera_device.clone(),
+            model_dir: config.model_dir.display().to_string(),
+            model_manifest: config
+                .model_manifest
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            db_path: config.db_path.display().to_string(),
+            models_enrolled: state.store.count_all().await.unwrap_or(0),
+            models_by_version: state
+                .store
+                .count_by_model_version()
+                .await
+                .unwrap_or_default(),
+            model_upgrade: state
+                .store
+                .upgrade_impact(visage_models::RECOGNIZER_VERSION)
+                .await
+                .ok(),
+            similarity_threshold: config.similarity_threshold,
+            insecure_config: config.insecure_config(),
+            verify_timeout_secs: config.verify_timeout_secs,
+            enroll_timeout_secs: config.enroll_timeout_secs,
+            privacy_mode: config.privacy_mode,
+            warmup_frames: config.warmup_frames,
+            startup_selftest: config.startup_selftest,
+            frames_per_verify: config.frames_per_verify,
+            frames_per_enroll: config.frames_per_enroll,
+            exposure_report_frames: config.exposure_report_frames,
+            emitter_enabled: config.emitter_enabled,
+            lazy_camera: config.lazy_camera,
+            engine_watchdog_secs: config.engine_watchdog_secs,
+            rate_limit_decay_secs: config.rate_limit_decay_secs,
+            centroid_matching: config.centroid_matching,
+            centroid_margin: config.centroid_margin,
+            verify_report_timing: config.verify_report_timing,
+            verify_require_pose_match: config.verify_require_pose_match,
+            verify_require_enrolled_device: config.verify_require_enrolled_device,
+            liveness_enabled: config.liveness_enabled,
+            liveness_min_displacement: config.liveness_min_displacement,
+            liveness_min_live_pairs: config.liveness_min_live_pairs,
+            enroll_min_alignment: config.enroll_min_alignment,
+            enroll_require_liveness: config.enroll_require_liveness,
+            enroll_crop_hash: config.enroll_crop_hash,
+            verify_allowed_windows: config.verify_schedule.allowed(),
+            verify_blocked_windows: config.verify_schedule.blocked(),
+            selftest_interval_hours: config.selftest_interval_hours,
+            selftest_quiet_hours: config.selftest_emitter_schedule.blocked(),
+            camera_degraded: state.selftest.camera_degraded(),
+            last_selftest: state.selftest.last().cloned(),
+            session_bus: config.session_bus,
+            config_file: config.config_file.as_ref().map(|p| p.display().to_string()),
+            config_warnings: config.warnings.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// The `Status` reply.
+    pub fn to_json(&self) -> serde_json::Value {
+        // Every field serializes infallibly: strings, numbers, lists and
+        // structs of those.
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+impl From<StatusInfo> for HashMap<String, OwnedValue> {
+    /// The `GetStatus` reply: the JSON object's keys, minus nulls.
+    fn from(status: StatusInfo) -> Self {
+        let serde_json::Value::Object(fields) = status.to_json() else {
+            return HashMap::new();
+        };
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = variant(value)?.try_into_owned().ok()?;
+                Some((key, value))
+            })
+            .collect()
+    }
+}
+
+/// The variant for one JSON value, or `None` for null. Nulls inside a list
+/// or object are left out too.
+fn variant(value: serde_json::Value) -> Option<Value<'static>> {
+    use serde_json::Value as Json;
+    Some(match value {
+        Json::Null => return None,
+        Json::Bool(b) => Value::from(b),
+        Json::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => Value::from(u),
+            (None, Some(i)) => Value::from(i),
+            _ => Value::from(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Value::from(s),
+        Json::Array(items) => {
+            Value::from(items.into_iter().filter_map(variant).collect::<Vec<_>>())
+        }
+        Json::Object(fields) => Value::from(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, variant(value)?)))
+                .collect::<HashMap<String, Value<'static>>>(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::EngineHandle;
+    use crate::polkit::{Authorization, MockAuthority};
+    use crate::preview::PreviewSessions;
+    use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+    use crate::selftest::SelfTestState;
+    use crate::stats::QualityStats;
+    use crate::store::{EnrollMeta, FaceModelStore};
+    use crate::verify_cancel::PendingVerifies;
+    use crate::verify_grace::VerifyGrace;
+    use crate::verify_token::VerifyTokens;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// JSON for a dictionary value, inverting [`variant`].
+    fn json(value: &Value<'_>) -> serde_json::Value {
+        match value {
+            Value::Bool(b) => (*b).into(),
+            Value::U64(u) => (*u).into(),
+            Value::I64(i) => (*i).into(),
+            Value::F64(f) => (*f).into(),
+            Value::Str(s) => s.as_str().into(),
+            Value::Value(inner) => json(inner),
+            Value::Array(items) => items.iter().map(json).collect(),
+            Value::Dict(dict) => {
+                let fields: HashMap<String, Value<'_>> =
+                    dict.try_clone().unwrap().try_into().unwrap();
+                fields.iter().map(|(k, v)| (k.clone(), json(v))).collect()
+            }
+            other => panic!("unexpected variant {other:?}"),
+        }
+    }
+
+    /// Drop nulls the way [`variant`] does.
+    fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+        use serde_json::Value as Json;
+        match value {
+            Json::Array(items) => items
+                .into_iter()
+                .filter(|v| !v.is_null())
+                .map(without_nulls)
+                .collect(),
+            Json::Object(fields) => fields
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+            other => other,
+        }
+    }
+
+    async fn state() -> AppState {
+        let config =
+            Config::from_pairs(&[("VISAGE_VERIFY_ALLOWED_WINDOWS", "07:00-19:00")]).unwrap();
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let emb = visage_core::Embedding::new(vec![0.5; 512], Some("arcface-r50".into()));
+        store
+            .insert("alice", "default", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        AppState {
+            config,
+            engine: EngineHandle::degraded("w600k_r50.onnx is missing"),
+            store,
+            rate_limiter: RateLimiter::new(),
+            enroll_cooldown: EnrollCooldown::new(Duration::ZERO),
+            previews: PreviewSessions::new(Duration::from_secs(30)),
+            verify_tokens: VerifyTokens::new(Duration::ZERO),
+            verify_grace: VerifyGrace::new(Duration::ZERO),
+            pending_verifies: PendingVerifies::default(),
+            stats: QualityStats::new(),
+            selftest: SelfTestState::default(),
+            log_filter: None,
+            authority: Arc::new(MockAuthority::answering(Authorization::NotAuthorized)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_and_json_carry_the_same_status() {
+        let status = StatusInfo::collect(&state().await).await;
+        let expected = without_nulls(status.to_json());
+        let dict: HashMap<String, OwnedValue> = status.into();
+
+        let round_trip: serde_json::Map<String, serde_json::Value> = dict
+            .iter()
+            .map(|(key, value)| (key.clone(), json(value)))
+            .collect();
+        assert_eq!(serde_json::Value::Object(round_trip), expected);
+        // The struct's nested objects and lists made it across too.
+        assert_eq!(expected["models_by_version"][0]["templates"], 1);
+        assert_eq!(expected["verify_allowed_windows"][0], "07:00-19:00");
+        assert_eq!(expected["recognizer_error"], "w600k_r50.onnx is missing");
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_types_do_not_depend_on_contents() {
+        let status = StatusInfo::collect(&state().await).await;
+        let dict: HashMap<String, OwnedValue> = status.into();
+        let signature = |key: &str| dict[key].value_signature().to_string();
+
+        assert_eq!(signature("version"), "s");
+        assert_eq!(signature("degraded"), "b");
+        assert_eq!(signature("uptime_secs"), "t");
+        assert_eq!(signature("similarity_threshold"), "d");
+        assert_eq!(signature("models_by_version"), "av");
+        // Empty, yet still a list.
+        assert_eq!(signature("verify_blocked_windows"), "av");
+        assert_eq!(signature("model_upgrade"), "a{sv}");
+        // Null in the JSON, so absent here.
+        assert!(!dict.contains_key("last_selftest"));
+        assert!(!dict.contains_key("config_file"));
+    }
+}
@@ -1,8 +1,16 @@
-use std::path::Path;
+use rusqlite::OptionalExtension;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
 use tokio_rusqlite::Connection;
+use visage_core::alignment::Pose;
+use visage_core::protected::{self, ProtectError};
 use visage_core::{Embedding, FaceModel};
 
+use crate::device_settings::DeviceSettings;
+
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
@@ -13,15 +21,35 @@ use rand::RngCore;
 const EMBEDDING_DIM: usize = 512;
 const EMBEDDING_BYTE_LEN: usize = EMBEDDING_DIM * 4;
 
+/// How far a stored template's L2 norm may stray from 1. Templates are
+/// normalized before they are stored, so anything further off was not
+/// written by enrollment.
+const STORED_NORM_TOLERANCE: f32 = 1e-2;
+
+/// Variance below which a stored template's components count as constant. A
+/// real unit-length 512-d template sits near 1/512 ≈ 2e-3; a constant vector
+/// correlates with every probe alike and could match anyone.
+const MIN_TEMPLATE_VARIANCE: f32 = 1e-5;
+
+/// Leading bytes of a backup's sealed payload.
+const BACKUP_MAGIC: &[u8; 4] = b"VSGB";
+const BACKUP_FORMAT_VERSION: u8 = 1;
+
+/// PBKDF2 iterations for the key sealing a backup.
+#[cfg(not(test))]
+const BACKUP_KDF_ROUNDS: u32 = protected::PBKDF2_ROUNDS;
+#[cfg(test)]
+const BACKUP_KDF_ROUNDS: u32 = 1_000;
+
 #[derive(Error, Debug)]
 pub enum StoreError {
     #[error("database error: {0}")]
     Db(#[from] tokio_rusqlite::Error),
     #[error("rusqlite error: {0}")]
     Rusqlite(#[from] rusqlite::Error),
-    #[error("embedding encryption failed")]
+    #[error("template encryption failed")]
     EncryptionFailed,
-    #[error("embedding decryption failed — key mismatch or corrupted data")]
+    #[error("template decryption failed — key mismatch or corrupted data")]
     DecryptionFailed,
     #[error("invalid embedding blob size: {0} bytes")]
     InvalidBlob(usize),
@@ -31,6 +59,27 @@ pub enum StoreError {
     InvalidEmbeddingValue,
     #[error("encryption key I/O error: {0}")]
     KeyIo(#[source] std::io::Error),
+    #[error("backup I/O error: {0}")]
+    BackupIo(#[source] std::io::Error),
+    #[error("backup bundle: {0}")]
+    Bundle(#[from] ProtectError),
+}
+
+impl StoreError {
+    /// Coarse error class for logs and D-Bus error details: `database`,
+    /// `encryption`, `decryption`, `corrupt_embedding`, `key_io` or `backup`.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::Db(_) | Self::Rusqlite(_) => "database",
+            Self::EncryptionFailed => "encryption",
+            Self::DecryptionFailed => "decryption",
+            Self::InvalidBlob(_) | Self::InvalidEmbeddingDim(_) | Self::InvalidEmbeddingValue => {
+                "corrupt_embedding"
+            }
+            Self::KeyIo(_) => "key_io",
+            Self::BackupIo(_) | Self::Bundle(_) => "backup",
+        }
+    }
 }
 
 /// SQLite-backed face model storage with AES-256-GCM encryption.
@@ -41,15 +90,52 @@ pub enum StoreError {
 ///
 /// Legacy plaintext blobs (2048 bytes) are accepted transparently — they are
 /// migrated to encrypted format on the next enrollment.
+///
+/// Opened with `seal_metadata`, the store encrypts each template's label and
+/// pose under the same key too, into `sealed_meta`, and the
+/// plaintext columns are left empty. `user` stays in plaintext so lookups
+/// can use its index. Rows without `sealed_meta` are read from the
+/// plaintext columns, whichever way the store is opened.
+///
+/// Mutations go through a single writer connection. Reads (gallery fetches,
+/// listings, counts) go through a small pool of read-only connections so a
+/// long write transaction never stalls the verify path; WAL mode lets those
+/// readers proceed concurrently with the writer.
 #[derive(Clone)]
 pub struct FaceModelStore {
     conn: Connection,
+    readers: Arc<ReaderPool>,
     enc_key: [u8; 32],
+    /// Write labels and poses encrypted (see [`open`](Self::open)).
+    seal_meta: bool,
+}
+
+/// Read-only connections handed out round-robin.
+struct ReaderPool {
+    conns: Vec<Connection>,
+    next: AtomicUsize,
+}
+
+impl ReaderPool {
+    fn get(&self) -> &Connection {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.conns[i % self.conns.len()]
+    }
 }
 
 impl FaceModelStore {
-    /// Open (or create) the database at the given path and run migrations.
-    pub async fn open(db_path: &Path) -> Result<Self, StoreError> {
+    /// Open (or create) the database at the given path, run migrations, and
+    /// open `read_connections` read-only connections for queries. With
+    /// `seal_metadata` the label and pose of every template written are
+    /// encrypted, and those already stored in plaintext are sealed now.
+    ///
+    /// An in-memory database cannot be shared between connections, so it
+    /// serves reads from the writer connection instead.
+    pub async fn open(
+        db_path: &Path,
+        read_connections: usize,
+        seal_metadata: bool,
+    ) -> Result<Self, StoreError> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent).ok();
@@ -68,36 +154,116 @@ impl FaceModelStore {
 
         let conn = Connection::open(db_path).await?;
 
-        conn.call(|conn| {
-            conn.execute_batch(
-                "PRAGMA journal_mode = WAL;
-                 PRAGMA foreign_keys = ON;
-                 CREATE TABLE IF NOT EXISTS faces (
-                     id TEXT PRIMARY KEY,
-                     user TEXT NOT NULL,
-                     label TEXT NOT NULL,
-                     embedding BLOB NOT NULL,
-                     model_version TEXT NOT NULL,
-                     quality_score REAL NOT NULL DEFAULT 0.0,
-                     pose_label TEXT NOT NULL DEFAULT 'frontal',
-                     created_at TEXT NOT NULL
-                 );
-                 CREATE INDEX IF NOT EXISTS idx_faces_user ON faces(user);",
-            )?;
-            Ok(())
-        })
-        .await?;
+        conn.call(|conn| Ok(migrate(conn)?)).await?;
+
+        let mut readers = Vec::new();
+        if db_path != Path::new(":memory:") {
+            for _ in 0..read_connections {
+                let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX
+                    | rusqlite::OpenFlags::SQLITE_OPEN_URI;
+                readers.push(Connection::open_with_flags(db_path, flags).await?);
+            }
+        }
+        if readers.is_empty() {
+            readers.push(conn.clone());
+        }
+
+        let store = Self {
+            conn,
+            readers: Arc::new(ReaderPool {
+                conns: readers,
+                next: AtomicUsize::new(0),
+            }),
+            enc_key,
+            seal_meta: seal_metadata,
+        };
+        store.backfill_centroids().await?;
+        if seal_metadata {
+            let sealed = store.seal_plaintext_metadata().await?;
+            if sealed > 0 {
+                tracing::info!(sealed, "sealed stored template labels and poses");
+            }
+        }
+        Ok(store)
+    }
+
+    /// Seal the metadata of every template still stored in plaintext.
+    async fn seal_plaintext_metadata(&self) -> Result<u64, StoreError> {
+        let rows: Vec<(String, String, String)> = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn
+                    .prepare("SELECT id, label, pose_label FROM faces WHERE sealed_meta IS NULL")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        let mut sealed = Vec::with_capacity(rows.len());
+        for (id, label, pose_label) in rows {
+            let blob = encrypt_meta(&self.enc_key, &TemplateMeta { label, pose_label })?;
+            sealed.push((id, blob));
+        }
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut count = 0;
+                for (id, blob) in &sealed {
+                    // A row relabelled meanwhile was sealed by that write.
+                    count += tx.execute(
+                        "UPDATE faces SET label = '', pose_label = '', sealed_meta = ?1
+                         WHERE id = ?2 AND sealed_meta IS NULL",
+                        rusqlite::params![blob, id],
+                    )? as u64;
+                }
+                tx.commit()?;
+                Ok(count)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
+
+    /// The label, pose and sealed metadata to write for a template: the
+    /// plaintext values, or empty columns and their encryption when metadata
+    /// is sealed.
+    fn stored_meta(
+        &self,
+        label: &str,
+        pose_label: &str,
+    ) -> Result<(String, String, Option<Vec<u8>>), StoreError> {
+        if !self.seal_meta {
+            return Ok((label.to_string(), pose_label.to_string(), None));
+        }
+        let meta = TemplateMeta {
+            label: label.to_string(),
+            pose_label: pose_label.to_string(),
+        };
+        let blob = encrypt_meta(&self.enc_key, &meta)?;
+        Ok((String::new(), String::new(), Some(blob)))
+    }
 
-        Ok(Self { conn, enc_key })
+    /// A stored template's metadata: decrypted from `sealed` when the row has
+    /// it, the plaintext columns otherwise.
+    fn read_meta(
+        &self,
+        label: String,
+        pose_label: String,
+        sealed: Option<&[u8]>,
+    ) -> Result<TemplateMeta, StoreError> {
+        match sealed {
+            Some(blob) => decrypt_meta(&self.enc_key, blob),
+            None => Ok(TemplateMeta { label, pose_label }),
+        }
     }
 
-    /// Insert a new face model. Returns the generated UUID.
+    /// Insert a new face model with its enrollment metadata. Returns the generated UUID.
     pub async fn insert(
         &self,
         user: &str,
         label: &str,
         embedding: &Embedding,
         quality_score: f32,
+        meta: EnrollMeta,
     ) -> Result<String, StoreError> {
         let id = uuid::Uuid::new_v4().to_string();
         let model_version = embedding
@@ -111,193 +277,1060 @@ impl FaceModelStore {
         let blob = self.encrypt_embedding(&embedding.values)?;
 
         let id_clone = id.clone();
-        let user = user.to_string();
-        let label = label.to_string();
+        let user_clone = user.to_string();
+        let (label, pose_label, sealed_meta) =
+            self.stored_meta(label, meta.pose.map_or("", Pose::as_str))?;
+        // SQLite integers are signed; store the hash bits unchanged.
+        let crop_hash = meta.crop_hash.map(|h| h as i64);
+        let device_id = meta.device_id;
+        let enroll_brightness = meta.brightness;
+        let duplicate_of = meta.duplicate_of;
 
         self.conn
             .call(move |conn| {
                 conn.execute(
-                    "INSERT INTO faces (id, user, label, embedding, model_version, quality_score, pose_label, created_at)
-                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'frontal', ?7)",
-                    rusqlite::params![id_clone, user, label, blob, model_version, quality_score, created_at],
+                    "INSERT INTO faces (id, user, label, embedding, model_version, quality_score, pose_label, device_id, enroll_brightness, created_at, crop_hash, duplicate_of, sealed_meta)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    rusqlite::params![id_clone, user_clone, label, blob, model_version, quality_score, pose_label, device_id, enroll_brightness, created_at, crop_hash, duplicate_of, sealed_meta],
                 )?;
                 Ok(())
             })
             .await?;
 
+        self.refresh_centroid(user).await;
         Ok(id)
     }
 
     /// Get all face models for a user (the gallery for verification).
     pub async fn get_gallery_for_user(&self, user: &str) -> Result<Vec<FaceModel>, StoreError> {
+        self.fetch_gallery(self.readers.get(), user).await
+    }
+
+    /// Every enrolled user's gallery, keyed by user, for 1:N identification.
+    /// Users left with no valid template are omitted.
+    pub async fn all_galleries(&self) -> Result<BTreeMap<String, Vec<FaceModel>>, StoreError> {
+        let rows: Vec<GalleryRow> = self
+            .readers
+            .get()
+            .call(|conn| {
+                let mut stmt = conn.prepare(&format!("{GALLERY_SELECT} ORDER BY user, rowid"))?;
+                let rows = stmt.query_map([], gallery_row)?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+
+        let mut galleries: BTreeMap<String, Vec<FaceModel>> = BTreeMap::new();
+        for model in self.decode_gallery(rows)? {
+            galleries.entry(model.user.clone()).or_default().push(model);
+        }
+        Ok(galleries)
+    }
+
+    /// Read and decrypt `user`'s gallery through `conn`.
+    async fn fetch_gallery(
+        &self,
+        conn: &Connection,
+        user: &str,
+    ) -> Result<Vec<FaceModel>, StoreError> {
         let user = user.to_string();
 
         // Fetch raw rows from SQLite; decrypt outside the blocking closure
-        let rows: Vec<(String, String, String, Vec<u8>, String, String)> = self
-            .conn
+        let rows: Vec<GalleryRow> = conn
             .call(move |conn| {
-                let mut stmt = conn.prepare(
-                    "SELECT id, user, label, embedding, model_version, created_at
-                     FROM faces WHERE user = ?1",
-                )?;
-                let rows = stmt.query_map([&user], |row| {
-                    Ok((
-                        row.get::<_, String>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, Vec<u8>>(3)?,
-                        row.get::<_, String>(4)?,
-                        row.get::<_, String>(5)?,
-                    ))
-                })?;
+                let mut stmt = conn.prepare(&format!("{GALLERY_SELECT} WHERE user = ?1"))?;
+                let rows = stmt.query_map([&user], gallery_row)?;
                 Ok(rows.collect::<Result<Vec<_>, _>>()?)
             })
             .await?;
+        self.decode_gallery(rows)
+    }
 
+    /// Decrypt gallery rows into models, leaving out (and logging) templates
+    /// unfit to match against.
+    fn decode_gallery(&self, rows: Vec<GalleryRow>) -> Result<Vec<FaceModel>, StoreError> {
         let mut models = Vec::with_capacity(rows.len());
-        for (id, user, label, blob, model_version, created_at) in rows {
-            let values = self.decrypt_embedding(&blob)?;
+        let mut excluded = 0;
+        for (
+            id,
+            user,
+            label,
+            blob,
+            model_version,
+            pose_label,
+            device_id,
+            enroll_brightness,
+            created_at,
+            sealed_meta,
+        ) in rows
+        {
+            let meta = self.read_meta(label, pose_label, sealed_meta.as_deref())?;
+            let values = match self.read_template(&blob)? {
+                Ok(values) => values,
+                Err(reason) => {
+                    tracing::warn!(
+                        user,
+                        model_id = id,
+                        reason,
+                        "invalid stored template excluded"
+                    );
+                    excluded += 1;
+                    continue;
+                }
+            };
             models.push(FaceModel {
                 id,
                 user,
-                label,
-                embedding: Embedding {
-                    values,
-                    model_version: Some(model_version),
-                },
+                label: meta.label,
+                // Normalized once here so every verify compares by dot product.
+                embedding: Embedding::normalized(values, Some(model_version)),
+                pose_label: meta.pose_label,
+                device_id,
+                enroll_brightness,
                 created_at,
             });
         }
+        if excluded > 0 {
+            tracing::warn!(
+                excluded,
+                "invalid models excluded; list them with `visage list` and remove them"
+            );
+        }
         Ok(models)
     }
 
-    /// List face models for a user (metadata only, no embeddings).
-    pub async fn list_by_user(&self, user: &str) -> Result<Vec<ModelInfo>, StoreError> {
+    /// Decrypt a stored template and check it is fit to match against: the
+    /// inner `Err` says why it is not. Blobs that cannot be decrypted or have
+    /// the wrong size fail outright, as they point at the key or the schema
+    /// rather than at one template.
+    fn read_template(&self, blob: &[u8]) -> Result<Result<Vec<f32>, &'static str>, StoreError> {
+        match self.decrypt_embedding(blob) {
+            Ok(values) => Ok(match template_defect(&values) {
+                Some(reason) => Err(reason),
+                None => Ok(values),
+            }),
+            Err(StoreError::InvalidEmbeddingValue) => Ok(Err("non-finite value")),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The user's centroid template (see [`Embedding::centroid`]), if they
+    /// have one. Kept up to date by [`insert`](Self::insert) and
+    /// [`remove`](Self::remove).
+    pub async fn get_centroid(&self, user: &str) -> Result<Option<UserCentroid>, StoreError> {
+        let user = user.to_string();
+        let row: Option<(Vec<u8>, String, f32)> = self
+            .readers
+            .get()
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT embedding, model_version, spread FROM user_centroids
+                         WHERE user = ?1 AND spread IS NOT NULL",
+                        [&user],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                    )
+                    .optional()?)
+            })
+            .await?;
+        row.map(|(blob, model_version, spread)| {
+            let values = self.decrypt_embedding(&blob)?;
+            Ok(UserCentroid {
+                embedding: Embedding::normalized(values, Some(model_version)),
+                spread,
+            })
+        })
+        .transpose()
+    }
+
+    /// Recompute `user`'s centroid from their current templates, or drop it
+    /// when they have none or their templates span model versions.
+    ///
+    /// A template that cannot be decrypted also drops the centroid rather
+    /// than leave a stale one that may still match a removed face.
+    ///
+    /// Runs after the template write has committed, so a failure here is
+    /// logged rather than returned: the caller's change already happened.
+    /// The centroid only ever rejects, so a stale one cannot admit a face.
+    async fn refresh_centroid(&self, user: &str) {
+        if let Err(e) = self.write_centroid(user).await {
+            tracing::warn!(user, error = %e, "centroid not refreshed");
+        }
+    }
+
+    async fn write_centroid(&self, user: &str) -> Result<(), StoreError> {
+        let centroid = match self.compute_centroid(user).await {
+            Ok(centroid) => centroid,
+            Err(e) => {
+                tracing::warn!(user, error = %e, "centroid not recomputed; dropping it");
+                None
+            }
+        };
         let user = user.to_string();
+        let updated_at = chrono::Utc::now().to_rfc3339();
         self.conn
+            .call(move |conn| {
+                match centroid {
+                    Some((blob, model_version, spread)) => conn.execute(
+                        "INSERT INTO user_centroids
+                             (user, embedding, model_version, spread, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5)
+                         ON CONFLICT(user) DO UPDATE SET embedding = excluded.embedding,
+                             model_version = excluded.model_version,
+                             spread = excluded.spread,
+                             updated_at = excluded.updated_at",
+                        rusqlite::params![user, blob, model_version, spread, updated_at],
+                    )?,
+                    None => conn.execute("DELETE FROM user_centroids WHERE user = ?1", [&user])?,
+                };
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Centroid of `user`'s templates, encrypted for storage with its model
+    /// version and spread (see [`UserCentroid::spread`]). Reads through the
+    /// writer connection so it sees the write that triggered it.
+    async fn compute_centroid(
+        &self,
+        user: &str,
+    ) -> Result<Option<(Vec<u8>, String, f32)>, StoreError> {
+        let gallery = self.fetch_gallery(&self.conn, user).await?;
+        let templates: Vec<Embedding> = gallery.into_iter().map(|m| m.embedding).collect();
+        let Some(centroid) = Embedding::centroid(&templates) else {
+            return Ok(None);
+        };
+        let spread = templates
+            .iter()
+            .map(|t| t.similarity(&centroid))
+            .fold(1.0f32, f32::min);
+        let blob = self.encrypt_embedding(&centroid.values)?;
+        let model_version = centroid
+            .model_version
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok(Some((blob, model_version, spread)))
+    }
+
+    /// Compute centroids for users enrolled before centroids, or their
+    /// spread, were stored.
+    async fn backfill_centroids(&self) -> Result<(), StoreError> {
+        let users: Vec<String> = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT DISTINCT user FROM faces
+                     WHERE user NOT IN
+                         (SELECT user FROM user_centroids WHERE spread IS NOT NULL)",
+                )?;
+                let users = stmt.query_map([], |row| row.get(0))?;
+                Ok(users.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        for user in &users {
+            self.refresh_centroid(user).await;
+        }
+        if !users.is_empty() {
+            tracing::debug!(users = users.len(), "centroid backfill complete");
+        }
+        Ok(())
+    }
+
+    /// List face models for a user (metadata only, no embeddings). Models
+    /// verification leaves out carry the reason in `excluded`.
+    pub async fn list_by_user(&self, user: &str) -> Result<Vec<ModelInfo>, StoreError> {
+        let user = user.to_string();
+        let rows: Vec<(ModelInfo, Vec<u8>, Option<Vec<u8>>)> = self
+            .readers
+            .get()
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "SELECT id, label, model_version, quality_score, created_at
+                    "SELECT id, label, model_version, quality_score, pose_label, created_at, embedding, duplicate_of, sealed_meta
                      FROM faces WHERE user = ?1 ORDER BY created_at",
                 )?;
                 let rows = stmt.query_map([&user], |row| {
-                    Ok(ModelInfo {
-                        id: row.get(0)?,
-                        label: row.get(1)?,
-                        model_version: row.get(2)?,
-                        quality_score: row.get(3)?,
-                        created_at: row.get(4)?,
-                    })
+                    Ok((
+                        ModelInfo {
+                            id: row.get(0)?,
+                            label: row.get(1)?,
+                            model_version: row.get(2)?,
+                            quality_score: row.get(3)?,
+                            pose_label: row.get(4)?,
+                            created_at: row.get(5)?,
+                            excluded: None,
+                            duplicate_of: row.get(7)?,
+                        },
+                        row.get(6)?,
+                        row.get(8)?,
+                    ))
                 })?;
                 Ok(rows.collect::<Result<Vec<_>, _>>()?)
             })
+            .await?;
+        rows.into_iter()
+            .map(|(mut info, blob, sealed_meta)| {
+                let meta = self.read_meta(info.label, info.pose_label, sealed_meta.as_deref())?;
+                info.label = meta.label;
+                info.pose_label = meta.pose_label;
+                // An unreadable blob fails verification as a whole; listing
+                // still shows the model.
+                info.excluded = self.read_template(&blob).ok().and_then(Result::err);
+                Ok(info)
+            })
+            .collect()
+    }
+
+    /// Per-user settings; defaults when the user has none stored.
+    pub async fn get_user_settings(&self, user: &str) -> Result<UserSettings, StoreError> {
+        let user = user.to_string();
+        self.readers
+            .get()
+            .call(move |conn| {
+                let model_version = conn
+                    .query_row(
+                        "SELECT model_version FROM user_settings WHERE user = ?1",
+                        [&user],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .flatten();
+                Ok(UserSettings { model_version })
+            })
             .await
             .map_err(StoreError::from)
     }
 
-    /// Remove a face model by ID, scoped to a user for cross-user protection.
-    pub async fn remove(&self, user: &str, model_id: &str) -> Result<bool, StoreError> {
+    /// Pin `user` to a recognizer model version, or clear the pin with `None`.
+    pub async fn set_model_pin(
+        &self,
+        user: &str,
+        model_version: Option<&str>,
+    ) -> Result<(), StoreError> {
         let user = user.to_string();
-        let model_id = model_id.to_string();
+        let model_version = model_version.map(str::to_string);
         self.conn
             .call(move |conn| {
-                let affected = conn.execute(
-                    "DELETE FROM faces WHERE id = ?1 AND user = ?2",
-                    [&model_id, &user],
+                conn.execute(
+                    "INSERT INTO user_settings (user, model_version) VALUES (?1, ?2)
+                     ON CONFLICT(user) DO UPDATE SET model_version = excluded.model_version",
+                    rusqlite::params![user, model_version],
                 )?;
-                Ok(affected > 0)
+                Ok(())
             })
             .await
             .map_err(StoreError::from)
     }
 
-    /// Count total enrolled face models across all users.
-    pub async fn count_all(&self) -> Result<u64, StoreError> {
-        self.conn
-            .call(|conn| {
-                let count: u64 =
-                    conn.query_row("SELECT COUNT(*) FROM faces", [], |row| row.get(0))?;
-                Ok(count)
+    /// Settings stored for the camera `device_id`; defaults when it has none.
+    pub async fn get_device_settings(&self, device_id: &str) -> Result<DeviceSettings, StoreError> {
+        let device_id = device_id.to_string();
+        self.readers
+            .get()
+            .call(move |conn| {
+                let liveness_min_displacement = conn
+                    .query_row(
+                        "SELECT liveness_min_displacement FROM device_settings WHERE device_id = ?1",
+                        [&device_id],
+                        |row| row.get::<_, Option<f64>>(0),
+                    )
+                    .optional()?
+                    .flatten()
+                    .map(|v| v as f32);
+                Ok(DeviceSettings {
+                    liveness_min_displacement,
+                })
             })
             .await
             .map_err(StoreError::from)
     }
 
-    // ── Encryption helpers ────────────────────────────────────────────────────
-
-    /// Encrypt embedding values with AES-256-GCM.
-    ///
-    /// Output: 12-byte random nonce || ciphertext || 16-byte GCM tag.
-    fn encrypt_embedding(&self, values: &[f32]) -> Result<Vec<u8>, StoreError> {
-        validate_embedding_values(values)?;
-        let plaintext = embedding_to_bytes(values);
-
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+    /// Replace the settings stored for the camera `device_id`.
+    pub async fn set_device_settings(
+        &self,
+        device_id: &str,
+        settings: &DeviceSettings,
+    ) -> Result<(), StoreError> {
+        let device_id = device_id.to_string();
+        let liveness_min_displacement = settings.liveness_min_displacement.map(f64::from);
+        let updated_at = chrono::Utc::now().to_rfc3339();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO device_settings (device_id, liveness_min_displacement, updated_at)
+                     VALUES (?1, ?2, ?3)
+                     ON CONFLICT(device_id) DO UPDATE SET
+                         liveness_min_displacement = excluded.liveness_min_displacement,
+                         updated_at = excluded.updated_at",
+                    rusqlite::params![device_id, liveness_min_displacement, updated_at],
+                )?;
+                Ok(())
+            })
+            .await
+            .map_err(StoreError::from)
+    }
 
-        let key = Key::<Aes256Gcm>::from_slice(&self.enc_key);
-        let cipher = Aes256Gcm::new(key);
+    /// Remove a face model by ID, scoped to a user for cross-user protection.
+    pub async fn remove(&self, user: &str, model_id: &str) -> Result<bool, StoreError> {
+        let user_clone = user.to_string();
+        let model_id = model_id.to_string();
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let affected = conn.execute(
+                    "DELETE FROM faces WHERE id = ?1 AND user = ?2",
+                    [&model_id, &user_clone],
+                )?;
+                Ok(affected > 0)
+            })
+            .await?;
+        if removed {
+            self.refresh_centroid(user).await;
+        }
+        Ok(removed)
+    }
 
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext.as_slice())
-            .map_err(|_| StoreError::EncryptionFailed)?;
+    /// Relabel a face model, scoped to a user like [`remove`](Self::remove).
+    /// Returns whether a row changed. The metadata is rewritten sealed or in
+    /// plaintext as the store now writes it.
+    pub async fn update_label(
+        &self,
+        user: &str,
+        model_id: &str,
+        new_label: &str,
+    ) -> Result<bool, StoreError> {
+        let user = user.to_string();
+        let model_id = model_id.to_string();
+        let key = (model_id.clone(), user.clone());
+        let row: Option<(String, String, Option<Vec<u8>>)> = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT label, pose_label, sealed_meta FROM faces
+                         WHERE id = ?1 AND user = ?2",
+                        [&key.0, &key.1],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                    )
+                    .optional()?)
+            })
+            .await?;
+        let Some((label, pose_label, sealed_meta)) = row else {
+            return Ok(false);
+        };
+        let meta = self.read_meta(label, pose_label, sealed_meta.as_deref())?;
+        let (label, pose_label, sealed_meta) = self.stored_meta(new_label, &meta.pose_label)?;
+        self.conn
+            .call(move |conn| {
+                let affected = conn.execute(
+                    "UPDATE faces SET label = ?1, pose_label = ?2, sealed_meta = ?3
+                     WHERE id = ?4 AND user = ?5",
+                    rusqlite::params![label, pose_label, sealed_meta, model_id, user],
+                )?;
+                Ok(affected > 0)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
 
-        let mut blob = Vec::with_capacity(12 + ciphertext.len());
-        blob.extend_from_slice(&nonce_bytes);
-        blob.extend_from_slice(&ciphertext);
-        Ok(blob)
+    /// `user`'s template most similar to `embedding`, with its cosine
+    /// similarity. Templates from another recognizer version are not
+    /// comparable and are skipped.
+    pub async fn nearest_template(
+        &self,
+        user: &str,
+        embedding: &Embedding,
+    ) -> Result<Option<(FaceModel, f32)>, StoreError> {
+        let gallery = self.get_gallery_for_user(user).await?;
+        Ok(gallery
+            .into_iter()
+            .filter(|model| model.embedding.model_version == embedding.model_version)
+            .map(|model| {
+                let similarity = model.embedding.similarity(embedding);
+                (model, similarity)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1)))
     }
 
-    /// Decrypt an embedding blob.
+    /// Remove `user`'s near-duplicate templates and return how many were
+    /// removed.
     ///
-    /// Accepts the legacy plaintext format (512 × 4 = 2048 bytes) and the
-    /// current encrypted format (12-byte nonce + ciphertext + 16-byte GCM tag).
-    fn decrypt_embedding(&self, blob: &[u8]) -> Result<Vec<f32>, StoreError> {
-        const NONCE_LEN: usize = 12;
+    /// Templates are visited best quality first; each is kept unless its
+    /// cosine similarity to an already kept template reaches
+    /// `similarity_threshold`, so every cluster keeps its highest-quality
+    /// member. The best template is always kept, so a user with templates
+    /// never drops to zero.
+    pub async fn dedupe_user(
+        &self,
+        user: &str,
+        similarity_threshold: f32,
+    ) -> Result<u64, StoreError> {
+        let gallery = self.fetch_gallery(&self.conn, user).await?;
+        let owner = user.to_string();
+        let qualities: HashMap<String, f64> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT id, quality_score FROM faces WHERE user = ?1")?;
+                let rows = stmt.query_map([&owner], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                Ok(rows.collect::<Result<HashMap<_, _>, _>>()?)
+            })
+            .await?;
 
-        if blob.len() == EMBEDDING_BYTE_LEN {
-            // Legacy plaintext — accept transparently; re-enrolled next time
-            return bytes_to_embedding_strict(blob);
+        let mut ranked: Vec<(f64, FaceModel)> = gallery
+            .into_iter()
+            .map(|model| (qualities.get(&model.id).copied().unwrap_or(0.0), model))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut kept: Vec<FaceModel> = Vec::new();
+        let mut duplicates = Vec::new();
+        for (_, model) in ranked {
+            if kept
+                .iter()
+                .any(|k| k.embedding.similarity(&model.embedding) >= similarity_threshold)
+            {
+                duplicates.push(model.id);
+            } else {
+                kept.push(model);
+            }
         }
-
-        if blob.len() <= NONCE_LEN {
-            return Err(StoreError::InvalidBlob(blob.len()));
+        if duplicates.is_empty() {
+            return Ok(0);
         }
 
-        let (nonce_bytes, ciphertext) = blob.split_at(NONCE_LEN);
-        let nonce = Nonce::from_slice(nonce_bytes);
-        let key = Key::<Aes256Gcm>::from_slice(&self.enc_key);
-        let cipher = Aes256Gcm::new(key);
+        let owner = user.to_string();
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut removed = 0u64;
+                for id in &duplicates {
+                    removed += tx.execute(
+                        "DELETE FROM faces WHERE id = ?1 AND user = ?2",
+                        [id, &owner],
+                    )? as u64;
+                }
+                tx.commit()?;
+                Ok(removed)
+            })
+            .await?;
+        self.refresh_centroid(user).await;
+        Ok(removed)
+    }
 
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| StoreError::DecryptionFailed)?;
+    /// Remove all of `user`'s templates and their centroid in one
+    /// transaction, keeping the user's settings; returns how many templates
+    /// went.
+    pub async fn remove_all_for_user(&self, user: &str) -> Result<u64, StoreError> {
+        let user = user.to_string();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let removed = tx.execute("DELETE FROM faces WHERE user = ?1", [&user])? as u64;
+                tx.execute("DELETE FROM user_centroids WHERE user = ?1", [&user])?;
+                tx.commit()?;
+                Ok(removed)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
 
-        bytes_to_embedding_strict(&plaintext)
+    /// Remove all of `user`'s templates, centroid and settings; returns how
+    /// many templates went.
+    pub async fn remove_user(&self, user: &str) -> Result<u64, StoreError> {
+        let user = user.to_string();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let removed = tx.execute("DELETE FROM faces WHERE user = ?1", [&user])? as u64;
+                tx.execute("DELETE FROM user_centroids WHERE user = ?1", [&user])?;
+                tx.execute("DELETE FROM user_settings WHERE user = ?1", [&user])?;
+                tx.commit()?;
+                Ok(removed)
+            })
+            .await
+            .map_err(StoreError::from)
     }
-}
 
-// ── Key management ────────────────────────────────────────────────────────────
+    /// Users with at least one enrolled template, in name order.
+    pub async fn list_users(&self) -> Result<Vec<String>, StoreError> {
+        self.readers
+            .get()
+            .call(|conn| {
+                let mut stmt = conn.prepare("SELECT DISTINCT user FROM faces ORDER BY user")?;
+                let rows = stmt.query_map([], |row| row.get(0))?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
 
-/// Load the encryption key from disk, or generate and persist a new one.
-/// Written with mode 0600 (owner-readable only).
-fn load_or_generate_key(key_path: &Path) -> Result<[u8; 32], StoreError> {
-    if key_path.exists() {
-        let bytes = std::fs::read(key_path).map_err(StoreError::KeyIo)?;
-        if bytes.len() != 32 {
-            return Err(StoreError::KeyIo(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "encryption key file has wrong length ({} bytes, expected 32)",
-                    bytes.len()
-                ),
-            )));
-        }
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&bytes);
-        tracing::debug!(path = %key_path.display(), "loaded encryption key");
-        Ok(key)
+    /// Count total enrolled face models across all users.
+    pub async fn count_all(&self) -> Result<u64, StoreError> {
+        self.readers
+            .get()
+            .call(|conn| {
+                let count: u64 =
+                    conn.query_row("SELECT COUNT(*) FROM faces", [], |row| row.get(0))?;
+                Ok(count)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
+
+    /// Enrolled templates and distinct users per recognizer model version,
+    /// ordered by version.
+    pub async fn count_by_model_version(&self) -> Result<Vec<ModelVersionCount>, StoreError> {
+        self.readers
+            .get()
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT model_version, COUNT(*), COUNT(DISTINCT user)
+                     FROM faces GROUP BY model_version ORDER BY model_version",
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(ModelVersionCount {
+                        model_version: row.get(0)?,
+                        templates: row.get(1)?,
+                        users: row.get(2)?,
+                    })
+                })?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+            .map_err(StoreError::from)
+    }
+
+    /// Estimate what upgrading the recognizer to `target_version` would cost:
+    /// enrolled users with no template on that version must re-enroll.
+    pub async fn upgrade_impact(&self, target_version: &str) -> Result<UpgradeImpact, StoreError> {
+        let target = target_version.to_string();
+        let (users_enrolled, users_to_reenroll) = self
+            .readers
+            .get()
+            .call(move |conn| {
+                let counts = conn.query_row(
+                    "SELECT COUNT(*), COALESCE(SUM(NOT has_target), 0)
+                     FROM (SELECT MAX(model_version = ?1) AS has_target
+                           FROM faces GROUP BY user)",
+                    [&target],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+                Ok(counts)
+            })
+            .await?;
+        Ok(UpgradeImpact {
+            target_version: target_version.to_string(),
+            users_enrolled,
+            users_to_reenroll,
+        })
+    }
+
+    /// Find pairs of enrollments belonging to different users whose crop
+    /// hashes differ by at most `max_distance` bits.
+    ///
+    /// Models enrolled without a crop hash are ignored. Pairs are ordered by
+    /// ascending distance.
+    pub async fn find_cross_user_duplicates(
+        &self,
+        max_distance: u32,
+    ) -> Result<Vec<DuplicateEnrollment>, StoreError> {
+        let rows: Vec<HashedRow> = self
+            .readers
+            .get()
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, user, label, pose_label, crop_hash, sealed_meta
+                     FROM faces WHERE crop_hash IS NOT NULL ORDER BY created_at",
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                })?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        let rows = rows
+            .into_iter()
+            .map(|(id, user, label, pose_label, hash, sealed_meta)| {
+                let meta = self.read_meta(label, pose_label, sealed_meta.as_deref())?;
+                Ok((id, user, meta.label, hash))
+            })
+            .collect::<Result<Vec<_>, StoreError>>()?;
+
+        let mut duplicates = Vec::new();
+        for (i, (id_a, user_a, label_a, hash_a)) in rows.iter().enumerate() {
+            for (id_b, user_b, label_b, hash_b) in &rows[i + 1..] {
+                if user_a == user_b {
+                    continue;
+                }
+                let distance = visage_core::phash::hamming_distance(*hash_a as u64, *hash_b as u64);
+                if distance <= max_distance {
+                    duplicates.push(DuplicateEnrollment {
+                        model_a: id_a.clone(),
+                        user_a: user_a.clone(),
+                        label_a: label_a.clone(),
+                        model_b: id_b.clone(),
+                        user_b: user_b.clone(),
+                        label_b: label_b.clone(),
+                        distance,
+                    });
+                }
+            }
+        }
+        duplicates.sort_by_key(|d| d.distance);
+        Ok(duplicates)
+    }
+
+    // ── Backup and restore ────────────────────────────────────────────────────
+
+    /// Copy the whole database into one bundle sealed with `passphrase`
+    /// (see [`visage_core::protected::seal`]). The copy is taken with
+    /// SQLite's online backup API, so enrollments carry on meanwhile. Its
+    /// templates are re-encrypted under a key made for this bundle alone,
+    /// which the bundle carries: [`restore`](Self::restore) on another
+    /// machine needs only the passphrase, and the installation's own key
+    /// never leaves it. Returns the bundle and the number of templates.
+    ///
+    /// The sealed payload is `"VSGB" | version u8 | key [32] | SQLite image`.
+    pub async fn backup(&self, passphrase: &str) -> Result<(Vec<u8>, u64), StoreError> {
+        if passphrase.is_empty() {
+            return Err(ProtectError::EmptyPassphrase.into());
+        }
+        let scratch = ScratchFile::create()?;
+        let path = scratch.path().to_path_buf();
+        self.conn
+            .call(move |conn| Ok(conn.backup(rusqlite::DatabaseName::Main, path, None)?))
+            .await?;
+
+        let mut backup_key = [0u8; 32];
+        OsRng.fill_bytes(&mut backup_key);
+        let copy = Connection::open(scratch.path()).await?;
+        let templates = rekey_copy(&copy, &self.enc_key, &backup_key).await?;
+        copy.close().await?;
+
+        // Reading the image and the PBKDF2 run block, so they get a thread.
+        let path = scratch.path().to_path_buf();
+        let passphrase = passphrase.to_string();
+        let bundle = blocking::unblock(move || {
+            let image = std::fs::read(path).map_err(StoreError::BackupIo)?;
+            let mut payload = Vec::with_capacity(BACKUP_MAGIC.len() + 1 + 32 + image.len());
+            payload.extend_from_slice(BACKUP_MAGIC);
+            payload.push(BACKUP_FORMAT_VERSION);
+            payload.extend_from_slice(&backup_key);
+            payload.extend_from_slice(&image);
+            Ok::<_, StoreError>(protected::seal(&payload, &passphrase, BACKUP_KDF_ROUNDS)?)
+        })
+        .await?;
+        Ok((bundle, templates))
+    }
+
+    /// Replace the whole database with a bundle written by
+    /// [`backup`](Self::backup). Templates are re-encrypted from the
+    /// bundle's key to this installation's key and centroids recomputed
+    /// before the copy goes live, so a wrong passphrase or a corrupted
+    /// bundle leaves the current database untouched. Returns the number of
+    /// templates restored.
+    pub async fn restore(&self, bundle: Vec<u8>, passphrase: &str) -> Result<u64, StoreError> {
+        let scratch = ScratchFile::create()?;
+        let path = scratch.path().to_path_buf();
+        let passphrase = passphrase.to_string();
+        // The PBKDF2 run and writing the image block, so they get a thread.
+        let backup_key = blocking::unblock(move || {
+            let payload = protected::unseal(&bundle, &passphrase)?;
+            let (backup_key, image) = split_backup_payload(&payload)?;
+            std::fs::write(path, image).map_err(StoreError::BackupIo)?;
+            Ok::<_, StoreError>(backup_key)
+        })
+        .await?;
+
+        let copy = Connection::open(scratch.path()).await?;
+        // A backup from an older release gets the current schema first.
+        copy.call(|conn| Ok(migrate(conn)?)).await?;
+        let templates = rekey_copy(&copy, &backup_key, &self.enc_key).await?;
+        copy.close().await?;
+
+        let path = scratch.path().to_path_buf();
+        self.conn
+            .call(move |conn| {
+                conn.restore(
+                    rusqlite::DatabaseName::Main,
+                    path,
+                    None::<fn(rusqlite::backup::Progress)>,
+                )?;
+                Ok(())
+            })
+            .await?;
+        // A bundle from a store that kept its metadata in plaintext is
+        // sealed like the rest.
+        if self.seal_meta {
+            self.seal_plaintext_metadata().await?;
+        }
+        self.backfill_centroids().await?;
+        Ok(templates)
+    }
+
+    // ── Encryption helpers ────────────────────────────────────────────────────
+
+    fn encrypt_embedding(&self, values: &[f32]) -> Result<Vec<u8>, StoreError> {
+        encrypt_embedding(&self.enc_key, values)
+    }
+
+    fn decrypt_embedding(&self, blob: &[u8]) -> Result<Vec<f32>, StoreError> {
+        decrypt_embedding(&self.enc_key, blob)
+    }
+}
+
+// ── Blob encryption ───────────────────────────────────────────────────────────
+
+/// Encrypt embedding values with AES-256-GCM under `key` (see
+/// [`encrypt_blob`]).
+fn encrypt_embedding(key: &[u8; 32], values: &[f32]) -> Result<Vec<u8>, StoreError> {
+    validate_embedding_values(values)?;
+    encrypt_blob(key, &embedding_to_bytes(values))
+}
+
+/// Encrypt a template's label and pose under `key`.
+fn encrypt_meta(key: &[u8; 32], meta: &TemplateMeta) -> Result<Vec<u8>, StoreError> {
+    let plaintext = serde_json::to_vec(meta).map_err(|_| StoreError::EncryptionFailed)?;
+    encrypt_blob(key, &plaintext)
+}
+
+/// Decrypt metadata written by [`encrypt_meta`].
+fn decrypt_meta(key: &[u8; 32], blob: &[u8]) -> Result<TemplateMeta, StoreError> {
+    serde_json::from_slice(&decrypt_blob(key, blob)?).map_err(|_| StoreError::DecryptionFailed)
+}
+
+/// Encrypt `plaintext` with AES-256-GCM under `key`.
+///
+/// Output: 12-byte random nonce || ciphertext || 16-byte GCM tag.
+fn encrypt_blob(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|_| StoreError::EncryptionFailed)?;
+
+    let mut blob = Vec::with_capacity(12 + ciphertext.len());
+    blob.extend_from_slice(&nonce_bytes);
+    blob.extend_from_slice(&ciphertext);
+    Ok(blob)
+}
+
+/// Decrypt an embedding blob written under `key`.
+///
+/// Accepts the legacy plaintext format (512 × 4 = 2048 bytes) and the
+/// current encrypted format (12-byte nonce + ciphertext + 16-byte GCM tag).
+fn decrypt_embedding(key: &[u8; 32], blob: &[u8]) -> Result<Vec<f32>, StoreError> {
+    if blob.len() == EMBEDDING_BYTE_LEN {
+        // Legacy plaintext — accept transparently; re-enrolled next time
+        return bytes_to_embedding_strict(blob);
+    }
+    bytes_to_embedding_strict(&decrypt_blob(key, blob)?)
+}
+
+/// Decrypt a blob written by [`encrypt_blob`] under `key`.
+fn decrypt_blob(key: &[u8; 32], blob: &[u8]) -> Result<Vec<u8>, StoreError> {
+    const NONCE_LEN: usize = 12;
+
+    if blob.len() <= NONCE_LEN {
+        return Err(StoreError::InvalidBlob(blob.len()));
+    }
+
+    let (nonce_bytes, ciphertext) = blob.split_at(NONCE_LEN);
+    let nonce = Nonce::from_slice(nonce_bytes);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|_| StoreError::DecryptionFailed)
+}
+
+// ── Backup helpers ────────────────────────────────────────────────────────────
+
+/// Re-encrypt every template, and any sealed metadata, in the database
+/// `copy` from key `from` to key `to`. Centroids are dropped rather than
+/// carried over; the restoring store recomputes them. Returns the number of
+/// templates.
+async fn rekey_copy(copy: &Connection, from: &[u8; 32], to: &[u8; 32]) -> Result<u64, StoreError> {
+    let rows: Vec<(String, Vec<u8>, Option<Vec<u8>>)> = copy
+        .call(|conn| {
+            let mut stmt = conn.prepare("SELECT id, embedding, sealed_meta FROM faces")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            Ok(rows.collect::<Result<Vec<_>, _>>()?)
+        })
+        .await?;
+    let mut rekeyed = Vec::with_capacity(rows.len());
+    for (id, blob, sealed_meta) in rows {
+        let values = decrypt_embedding(from, &blob)?;
+        let sealed_meta = sealed_meta
+            .map(|blob| encrypt_meta(to, &decrypt_meta(from, &blob)?))
+            .transpose()?;
+        rekeyed.push((id, encrypt_embedding(to, &values)?, sealed_meta));
+    }
+    let templates = rekeyed.len() as u64;
+    copy.call(move |conn| {
+        let tx = conn.transaction()?;
+        for (id, blob, sealed_meta) in &rekeyed {
+            tx.execute(
+                "UPDATE faces SET embedding = ?1, sealed_meta = ?2 WHERE id = ?3",
+                rusqlite::params![blob, sealed_meta, id],
+            )?;
+        }
+        tx.execute("DELETE FROM user_centroids", [])?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await?;
+    Ok(templates)
+}
+
+/// Split a backup payload into the embedding key and the SQLite image.
+fn split_backup_payload(payload: &[u8]) -> Result<([u8; 32], &[u8]), StoreError> {
+    const HEADER_LEN: usize = BACKUP_MAGIC.len() + 1 + 32;
+    if payload.len() < HEADER_LEN || &payload[..BACKUP_MAGIC.len()] != BACKUP_MAGIC {
+        return Err(ProtectError::Malformed("not a store backup").into());
+    }
+    if payload[BACKUP_MAGIC.len()] != BACKUP_FORMAT_VERSION {
+        return Err(ProtectError::Malformed("unsupported backup version").into());
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&payload[BACKUP_MAGIC.len() + 1..HEADER_LEN]);
+    Ok((key, &payload[HEADER_LEN..]))
+}
+
+/// A mode-0600 scratch database in the temporary directory, removed with
+/// its journal files on drop.
+struct ScratchFile(PathBuf);
+
+impl ScratchFile {
+    fn create() -> Result<Self, StoreError> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let path = std::env::temp_dir().join(format!("visage-backup-{}.db", uuid::Uuid::new_v4()));
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(StoreError::BackupIo)?;
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// ── Schema helpers ────────────────────────────────────────────────────────────
+
+/// Create any missing tables, indexes and columns. Run on open and after a
+/// restore, which may bring back a database from an older release.
+fn migrate(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA foreign_keys = ON;
+         CREATE TABLE IF NOT EXISTS faces (
+             id TEXT PRIMARY KEY,
+             user TEXT NOT NULL,
+             label TEXT NOT NULL,
+             embedding BLOB NOT NULL,
+             model_version TEXT NOT NULL,
+             quality_score REAL NOT NULL DEFAULT 0.0,
+             pose_label TEXT NOT NULL DEFAULT 'frontal',
+             created_at TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_faces_user ON faces(user);
+         CREATE TABLE IF NOT EXISTS user_settings (
+             user TEXT PRIMARY KEY,
+             model_version TEXT
+         );
+         CREATE TABLE IF NOT EXISTS user_centroids (
+             user TEXT PRIMARY KEY,
+             embedding BLOB NOT NULL,
+             model_version TEXT NOT NULL,
+             updated_at TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS device_settings (
+             device_id TEXT PRIMARY KEY,
+             liveness_min_displacement REAL,
+             updated_at TEXT NOT NULL
+         );",
+    )?;
+    ensure_column(conn, "faces", "crop_hash", "INTEGER")?;
+    ensure_column(
+        conn,
+        "faces",
+        "pose_label",
+        "TEXT NOT NULL DEFAULT 'frontal'",
+    )?;
+    ensure_column(conn, "faces", "device_id", "TEXT")?;
+    ensure_column(conn, "faces", "enroll_brightness", "REAL")?;
+    ensure_column(conn, "faces", "duplicate_of", "TEXT")?;
+    ensure_column(conn, "faces", "sealed_meta", "BLOB")?;
+    ensure_column(conn, "user_centroids", "spread", "REAL")?;
+    Ok(())
+}
+
+/// Add `column` to `table` if it does not already exist.
+///
+/// Lets databases created by older releases pick up new nullable columns
+/// without a separate migration step.
+fn ensure_column(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+    }
+    Ok(())
+}
+
+// ── Key management ────────────────────────────────────────────────────────────
+
+/// Load the encryption key from disk, or generate and persist a new one.
+/// Written with mode 0600 (owner-readable only).
+fn load_or_generate_key(key_path: &Path) -> Result<[u8; 32], StoreError> {
+    if key_path.exists() {
+        let bytes = std::fs::read(key_path).map_err(StoreError::KeyIo)?;
+        if bytes.len() != 32 {
+            return Err(StoreError::KeyIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "encryption key file has wrong length ({} bytes, expected 32)",
+                    bytes.len()
+                ),
+            )));
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&bytes);
+        tracing::debug!(path = %key_path.display(), "loaded encryption key");
+        Ok(key)
     } else {
         let mut key = [0u8; 32];
         OsRng.fill_bytes(&mut key);
@@ -351,6 +1384,23 @@ fn bytes_to_embedding_strict(bytes: &[u8]) -> Result<Vec<f32>, StoreError> {
     Ok(values)
 }
 
+/// Why a decrypted template cannot be matched against, if it cannot: not
+/// unit length (see [`STORED_NORM_TOLERANCE`]) or near-constant (see
+/// [`MIN_TEMPLATE_VARIANCE`]). Values are already known to be finite.
+fn template_defect(values: &[f32]) -> Option<&'static str> {
+    let n = values.len() as f32;
+    let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if (norm - 1.0).abs() > STORED_NORM_TOLERANCE {
+        return Some("not unit length");
+    }
+    let mean = values.iter().sum::<f32>() / n;
+    let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n;
+    if variance < MIN_TEMPLATE_VARIANCE {
+        return Some("near-constant values");
+    }
+    None
+}
+
 fn validate_embedding_values(values: &[f32]) -> Result<(), StoreError> {
     if values.len() != EMBEDDING_DIM {
         return Err(StoreError::InvalidEmbeddingDim(values.len()));
@@ -370,7 +1420,141 @@ pub struct ModelInfo {
     pub label: String,
     pub model_version: String,
     pub quality_score: f64,
+    /// Head pose the template was enrolled in (`frontal`, `left`, `right`);
+    /// empty when unknown.
+    pub pose_label: String,
     pub created_at: String,
+    /// Why verification ignores this model (not unit length, near-constant
+    /// or non-finite values); absent for usable models.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excluded: Option<&'static str>,
+    /// Template this one was enrolled as a near-duplicate of (see
+    /// `enroll_allow_duplicates`); absent otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+/// A user's centroid template as stored in `user_centroids`.
+#[derive(Debug, Clone)]
+pub struct UserCentroid {
+    pub embedding: Embedding,
+    /// Smallest similarity between any of the user's templates and the
+    /// centroid, which bounds how far the fast path may reject (see
+    /// `CentroidMatcher`).
+    pub spread: f32,
+}
+
+/// Enrollment counts for one recognizer model version.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ModelVersionCount {
+    pub model_version: String,
+    pub templates: u64,
+    pub users: u64,
+}
+
+/// Users an upgrade to `target_version` would invalidate.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct UpgradeImpact {
+    pub target_version: String,
+    pub users_enrolled: u64,
+    /// Enrolled users without a single template on `target_version`.
+    pub users_to_reenroll: u64,
+}
+
+/// Per-user overrides stored in `user_settings`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserSettings {
+    /// Recognizer model version the user must be enrolled and verified with.
+    pub model_version: Option<String>,
+}
+
+/// A `faces` row as read for a gallery, before decryption: id, user, label,
+/// encrypted embedding, model version, pose label, device id, enrollment
+/// brightness, created_at, sealed metadata.
+type GalleryRow = (
+    String,
+    String,
+    String,
+    Vec<u8>,
+    String,
+    String,
+    Option<String>,
+    Option<f32>,
+    String,
+    Option<Vec<u8>>,
+);
+
+/// Selects the [`GalleryRow`] columns; callers append the filter and order.
+const GALLERY_SELECT: &str = "SELECT id, user, label, embedding, model_version, pose_label, \
+     device_id, enroll_brightness, created_at, sealed_meta FROM faces";
+
+fn gallery_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<GalleryRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+        row.get(8)?,
+        row.get(9)?,
+    ))
+}
+
+/// A `faces` row as read for the duplicate report: id, user, label, pose
+/// label, crop hash, sealed metadata.
+type HashedRow = (String, String, String, String, i64, Option<Vec<u8>>);
+
+/// Label and pose of a template, encrypted together into `sealed_meta`
+/// when metadata is sealed.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TemplateMeta {
+    label: String,
+    pose_label: String,
+}
+
+/// Optional metadata recorded alongside a new enrollment.
+#[derive(Debug, Clone, Default)]
+pub struct EnrollMeta {
+    /// Perceptual hash of the aligned enrollment crop (see `visage_core::phash`).
+    pub crop_hash: Option<u64>,
+    /// Head pose of the enrollment frame; stored as an empty label when
+    /// unknown.
+    pub pose: Option<Pose>,
+    /// Camera the enrollment frames came from (`CaptureContext::device_id`).
+    pub device_id: Option<String>,
+    /// Average raw brightness of the enrollment frames (see
+    /// `Frame::raw_brightness`).
+    pub brightness: Option<f32>,
+    /// Existing template of the same user the new one nearly repeats.
+    pub duplicate_of: Option<String>,
+}
+
+/// Two enrollments under different users whose crop hashes nearly match.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateEnrollment {
+    pub model_a: String,
+    pub user_a: String,
+    pub label_a: String,
+    pub model_b: String,
+    pub user_b: String,
+    pub label_b: String,
+    /// Hamming distance between the two crop hashes.
+    pub distance: u32,
+}
+
+#[cfg(test)]
+impl FaceModelStore {
+    /// Make every gallery read fail with a database error, as a corrupted or
+    /// unreadable database would.
+    pub async fn break_gallery_reads(&self) {
+        self.conn
+            .call(|conn| Ok(conn.execute_batch("DROP TABLE faces")?))
+            .await
+            .unwrap();
+    }
 }
 
 // ── Tests ─────────────────────────────────────────────────────────────────────
@@ -378,20 +1562,23 @@ pub struct ModelInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use visage_core::{CosineMatcher, Matcher};
 
     #[tokio::test]
     async fn test_roundtrip() {
-        let store = FaceModelStore::open(Path::new(":memory:")).await.unwrap();
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
 
-        let embedding = Embedding {
-            values: (0..EMBEDDING_DIM)
+        let embedding = Embedding::normalized(
+            (0..EMBEDDING_DIM)
                 .map(|i| i as f32 / EMBEDDING_DIM as f32)
                 .collect(),
-            model_version: Some("w600k_r50".to_string()),
-        };
+            Some("w600k_r50".to_string()),
+        );
 
         let id = store
-            .insert("alice", "default", &embedding, 0.85)
+            .insert("alice", "default", &embedding, 0.85, EnrollMeta::default())
             .await
             .unwrap();
         assert!(!id.is_empty());
@@ -409,15 +1596,78 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_cross_user_protection() {
-        let store = FaceModelStore::open(Path::new(":memory:")).await.unwrap();
+    async fn test_all_galleries_groups_templates_by_user() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert!(store.all_galleries().await.unwrap().is_empty());
+        for (user, label, axis) in [
+            ("bob", "default", 0),
+            ("alice", "default", 1),
+            ("alice", "glasses", 2),
+        ] {
+            store
+                .insert(user, label, &basis(axis), 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+
+        let galleries = store.all_galleries().await.unwrap();
+        assert_eq!(galleries.keys().collect::<Vec<_>>(), ["alice", "bob"]);
+        assert_eq!(galleries["alice"].len(), 2);
+        assert!(galleries["alice"].iter().all(|m| m.user == "alice"));
+        assert_eq!(galleries["bob"].len(), 1);
+        assert_eq!(galleries["bob"][0].embedding.values, basis(0).values);
+    }
 
-        let emb = Embedding {
-            values: vec![1.0; EMBEDDING_DIM],
-            model_version: None,
+    #[tokio::test]
+    async fn test_pose_label_stored_with_template() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let emb = basis(0);
+        let turned = EnrollMeta {
+            pose: Some(Pose::Left),
+            device_id: Some("usb:04f2:b6d9.2".to_string()),
+            brightness: Some(42.5),
+            ..Default::default()
         };
+        store
+            .insert("alice", "left", &emb, 0.9, turned)
+            .await
+            .unwrap();
+        store
+            .insert("alice", "unknown", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        let mut gallery = store.get_gallery_for_user("alice").await.unwrap();
+        gallery.sort_by(|a, b| a.label.cmp(&b.label));
+        assert_eq!(gallery[0].pose_label, "left");
+        assert_eq!(gallery[0].device_id.as_deref(), Some("usb:04f2:b6d9.2"));
+        assert_eq!(gallery[0].enroll_brightness, Some(42.5));
+        assert_eq!(gallery[1].pose_label, "");
+        assert_eq!(gallery[1].device_id, None);
+        assert_eq!(gallery[1].enroll_brightness, None);
+
+        let mut listed = store.list_by_user("alice").await.unwrap();
+        listed.sort_by(|a, b| a.label.cmp(&b.label));
+        assert_eq!(listed[0].pose_label, "left");
+        assert_eq!(listed[1].pose_label, "");
+    }
 
-        let id = store.insert("alice", "default", &emb, 0.9).await.unwrap();
+    #[tokio::test]
+    async fn test_cross_user_protection() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+
+        let emb = basis(0);
+
+        let id = store
+            .insert("alice", "default", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
 
         let bob_gallery = store.get_gallery_for_user("bob").await.unwrap();
         assert!(bob_gallery.is_empty());
@@ -432,6 +1682,124 @@ mod tests {
         assert!(gallery.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_update_label_is_scoped_to_user() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let id = store
+            .insert("alice", "default", &basis(0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        assert!(!store.update_label("bob", &id, "mine").await.unwrap());
+        assert!(!store
+            .update_label("alice", "missing", "mine")
+            .await
+            .unwrap());
+        assert_eq!(
+            store.list_by_user("alice").await.unwrap()[0].label,
+            "default"
+        );
+
+        assert!(store
+            .update_label("alice", &id, "with-glasses")
+            .await
+            .unwrap());
+        let gallery = store.get_gallery_for_user("alice").await.unwrap();
+        assert_eq!(gallery[0].label, "with-glasses");
+    }
+
+    #[tokio::test]
+    async fn test_sealed_metadata_roundtrips_without_plaintext() {
+        let root = std::env::temp_dir().join(format!("visage-store-{}", uuid::Uuid::new_v4()));
+        let db_path = root.join("faces.db");
+        let plain = FaceModelStore::open(&db_path, 1, false).await.unwrap();
+        let left = EnrollMeta {
+            pose: Some(Pose::Left),
+            ..Default::default()
+        };
+        let legacy = plain
+            .insert("alice", "mask", &basis(0), 0.9, left.clone())
+            .await
+            .unwrap();
+
+        // Rows written in plaintext are sealed when a store opens sealing.
+        let store = FaceModelStore::open(&db_path, 1, true).await.unwrap();
+        assert_eq!(store.seal_plaintext_metadata().await.unwrap(), 0);
+        let frontal = EnrollMeta {
+            pose: Some(Pose::Frontal),
+            ..Default::default()
+        };
+        let sealed = store
+            .insert("alice", "glasses", &basis(1), 0.9, frontal)
+            .await
+            .unwrap();
+        assert!(store
+            .update_label("alice", &legacy, "mask-off")
+            .await
+            .unwrap());
+
+        let rows: Vec<(String, String, Vec<u8>)> = store
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare("SELECT label, pose_label, sealed_meta FROM faces")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        for (label, pose_label, blob) in &rows {
+            assert_eq!((label.as_str(), pose_label.as_str()), ("", ""));
+            for word in ["mask", "glasses", "left", "frontal"] {
+                assert!(
+                    !blob.windows(word.len()).any(|w| w == word.as_bytes()),
+                    "{word} readable in the sealed metadata"
+                );
+            }
+        }
+
+        let mut listed = store.list_by_user("alice").await.unwrap();
+        listed.sort_by(|a, b| a.label.cmp(&b.label));
+        let listed: Vec<_> = listed
+            .iter()
+            .map(|m| (m.id.as_str(), m.label.as_str(), m.pose_label.as_str()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (sealed.as_str(), "glasses", "frontal"),
+                (legacy.as_str(), "mask-off", "left"),
+            ]
+        );
+        let mut gallery = store.get_gallery_for_user("alice").await.unwrap();
+        gallery.sort_by(|a, b| a.label.cmp(&b.label));
+        assert_eq!(gallery[1].label, "mask-off");
+        assert_eq!(gallery[1].pose_label, "left");
+
+        // A store that does not seal still reads sealed rows, and writes
+        // the next change in plaintext.
+        assert!(plain
+            .update_label("alice", &sealed, "no-glasses")
+            .await
+            .unwrap());
+        let label: String = store
+            .conn
+            .call(move |conn| {
+                Ok(conn.query_row(
+                    "SELECT label FROM faces WHERE id = ?1 AND sealed_meta IS NULL",
+                    [&sealed],
+                    |row| row.get(0),
+                )?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(label, "no-glasses");
+        assert_eq!(plain.list_by_user("alice").await.unwrap().len(), 2);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
     #[tokio::test]
     async fn test_embedding_byte_fidelity() {
         // Build a 512-dim vector with interesting values at specific positions
@@ -487,16 +1855,19 @@ mod tests {
 
     #[tokio::test]
     async fn test_encryption_roundtrip() {
-        let store = FaceModelStore::open(Path::new(":memory:")).await.unwrap();
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
 
         // Full 512-dim embedding to exercise the real code path
         let values: Vec<f32> = (0..512).map(|i| i as f32 / 512.0).collect();
-        let emb = Embedding {
-            values: values.clone(),
-            model_version: Some("w600k_r50".to_string()),
-        };
+        let emb = Embedding::normalized(values, Some("w600k_r50".to_string()));
+        let values = emb.values.clone();
 
-        let id = store.insert("alice", "test", &emb, 0.95).await.unwrap();
+        let id = store
+            .insert("alice", "test", &emb, 0.95, EnrollMeta::default())
+            .await
+            .unwrap();
         let gallery = store.get_gallery_for_user("alice").await.unwrap();
 
         assert_eq!(gallery.len(), 1);
@@ -506,18 +1877,120 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_gallery_normalized_on_read() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+
+        // Rows may be a rounding error off unit length.
+        let values: Vec<f32> = (0..EMBEDDING_DIM).map(|i| (i % 7) as f32 - 3.0).collect();
+        let scale = 1.005 / values.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let raw = Embedding::new(
+            values.iter().map(|v| v * scale).collect(),
+            Some("w600k_r50".to_string()),
+        );
+        store
+            .insert("alice", "legacy", &raw, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        let gallery = store.get_gallery_for_user("alice").await.unwrap();
+        let stored = &gallery[0].embedding;
+        assert!(stored.is_unit_length());
+        let norm: f32 = stored.values.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5, "norm {norm}");
+
+        let probe = Embedding::new((0..EMBEDDING_DIM).map(|i| (i % 7) as f32).collect(), None);
+        assert!((probe.similarity(stored) - probe.similarity(&raw)).abs() < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn test_degenerate_templates_excluded_from_gallery() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let version = Some("w600k_r50".to_string());
+        let good = store
+            .insert("alice", "good", &basis(0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        let unscaled = Embedding::new(vec![1.0; EMBEDDING_DIM], version.clone());
+        let constant = Embedding::normalized(vec![1.0; EMBEDDING_DIM], version.clone());
+        for (label, emb) in [
+            ("unscaled", &unscaled),
+            ("constant", &constant),
+            ("nan", &basis(1)),
+        ] {
+            store
+                .insert("alice", label, emb, 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+        // insert refuses non-finite values; write one as a legacy plaintext
+        // blob, the way a bad import might.
+        let mut nan = basis(1).values.to_vec();
+        nan[3] = f32::NAN;
+        let blob = embedding_to_bytes(&nan);
+        store
+            .conn
+            .call(move |conn| {
+                Ok(conn.execute(
+                    "UPDATE faces SET embedding = ?1 WHERE label = 'nan'",
+                    [blob],
+                )?)
+            })
+            .await
+            .unwrap();
+
+        let gallery = store.get_gallery_for_user("alice").await.unwrap();
+        assert_eq!(gallery.len(), 1);
+        assert_eq!(gallery[0].id, good);
+
+        let excluded: Vec<_> = store
+            .list_by_user("alice")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.label, m.excluded))
+            .collect();
+        assert_eq!(
+            excluded,
+            [
+                ("good".to_string(), None),
+                ("unscaled".to_string(), Some("not unit length")),
+                ("constant".to_string(), Some("near-constant values")),
+                ("nan".to_string(), Some("non-finite value")),
+            ]
+        );
+
+        // A flat probe would have matched the constant template; only the
+        // real one is left to match against.
+        let flat = Embedding::normalized(
+            (0..EMBEDDING_DIM)
+                .map(|i| 1.0 + (i % 7) as f32 * 0.01)
+                .collect(),
+            None,
+        );
+        assert!(flat.similarity(&constant) > 0.99);
+        assert!(!CosineMatcher.compare(&flat, &gallery, 0.4).matched);
+        let result = CosineMatcher.compare(&basis(0), &gallery, 0.4);
+        assert!(result.matched);
+        assert_eq!(result.model_id.as_deref(), Some(good.as_str()));
+    }
+
     #[tokio::test]
     async fn test_wrong_key_fails() {
         // Encrypt with one key, try to decrypt with another — must fail
         let store1 = FaceModelStore {
-            conn: tokio_rusqlite::Connection::open(Path::new(":memory:"))
-                .await
-                .unwrap(),
             enc_key: [1u8; 32],
+            ..FaceModelStore::open(Path::new(":memory:"), 1, false)
+                .await
+                .unwrap()
         };
         let store2 = FaceModelStore {
-            conn: store1.conn.clone(),
             enc_key: [2u8; 32],
+            ..store1.clone()
         };
 
         let values: Vec<f32> = (0..EMBEDDING_DIM)
@@ -529,16 +2002,24 @@ mod tests {
 
     #[tokio::test]
     async fn test_list_by_user() {
-        let store = FaceModelStore::open(Path::new(":memory:")).await.unwrap();
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
 
-        let emb = Embedding {
-            values: vec![1.0; EMBEDDING_DIM],
-            model_version: Some("v1".to_string()),
-        };
+        let emb = Embedding::new(vec![1.0; EMBEDDING_DIM], Some("v1".to_string()));
 
-        store.insert("alice", "normal", &emb, 0.9).await.unwrap();
-        store.insert("alice", "glasses", &emb, 0.8).await.unwrap();
-        store.insert("bob", "default", &emb, 0.7).await.unwrap();
+        store
+            .insert("alice", "normal", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        store
+            .insert("alice", "glasses", &emb, 0.8, EnrollMeta::default())
+            .await
+            .unwrap();
+        store
+            .insert("bob", "default", &emb, 0.7, EnrollMeta::default())
+            .await
+            .unwrap();
 
         let alice_models = store.list_by_user("alice").await.unwrap();
         assert_eq!(alice_models.len(), 2);
@@ -548,4 +2029,665 @@ mod tests {
         let count = store.count_all().await.unwrap();
         assert_eq!(count, 3);
     }
+
+    #[tokio::test]
+    async fn test_cross_user_duplicates_flagged() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+
+        let emb = Embedding::new(vec![1.0; EMBEDDING_DIM], None);
+        let meta = |hash| EnrollMeta {
+            crop_hash: Some(hash),
+            ..Default::default()
+        };
+
+        let hash = 0xF0F0_1234_ABCD_5678u64;
+        let alice = store
+            .insert("alice", "default", &emb, 0.9, meta(hash))
+            .await
+            .unwrap();
+        // Two bits away from alice's hash
+        let mallory = store
+            .insert("mallory", "default", &emb, 0.9, meta(hash ^ 0b101))
+            .await
+            .unwrap();
+        // Same user near-duplicates and distant hashes are not reported
+        store
+            .insert("alice", "glasses", &emb, 0.9, meta(hash ^ 0b1))
+            .await
+            .unwrap();
+        store
+            .insert("bob", "default", &emb, 0.9, meta(!hash))
+            .await
+            .unwrap();
+        store
+            .insert("carol", "default", &emb, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        let dups = store.find_cross_user_duplicates(4).await.unwrap();
+        let pairs: Vec<_> = dups
+            .iter()
+            .map(|d| (d.user_a.as_str(), d.user_b.as_str(), d.distance))
+            .collect();
+        assert!(dups.iter().any(|d| {
+            let ids = [d.model_a.as_str(), d.model_b.as_str()];
+            ids.contains(&alice.as_str()) && ids.contains(&mallory.as_str()) && d.distance == 2
+        }));
+        assert!(pairs.iter().all(|(a, b, _)| a != b));
+        assert!(pairs.iter().all(|(a, b, _)| *a != "bob" && *b != "bob"));
+        assert!(pairs.iter().all(|(a, b, _)| *a != "carol" && *b != "carol"));
+    }
+
+    /// alice: old + new, bob: old only, carol: new only, dave: old twice.
+    async fn mixed_version_store() -> FaceModelStore {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let old = Embedding::new(vec![1.0; EMBEDDING_DIM], Some("w600k_r50".into()));
+        let new = Embedding::new(vec![1.0; EMBEDDING_DIM], Some("w800k_r100".into()));
+        for (user, emb) in [
+            ("alice", &old),
+            ("alice", &new),
+            ("bob", &old),
+            ("carol", &new),
+            ("dave", &old),
+            ("dave", &old),
+        ] {
+            store
+                .insert(user, "default", emb, 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn test_remove_user_clears_templates_centroid_and_settings() {
+        let store = mixed_version_store().await;
+        assert_eq!(
+            store.list_users().await.unwrap(),
+            ["alice", "bob", "carol", "dave"]
+        );
+        store
+            .set_model_pin("dave", Some("w600k_r50"))
+            .await
+            .unwrap();
+
+        assert_eq!(store.remove_user("dave").await.unwrap(), 2);
+        assert_eq!(store.list_users().await.unwrap(), ["alice", "bob", "carol"]);
+        assert!(store.get_centroid("dave").await.unwrap().is_none());
+        assert_eq!(
+            store.get_user_settings("dave").await.unwrap(),
+            UserSettings::default()
+        );
+        assert_eq!(store.remove_user("dave").await.unwrap(), 0);
+        assert_eq!(store.count_all().await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_remove_all_for_user_leaves_other_users_and_settings() {
+        let store = mixed_version_store().await;
+        store
+            .set_model_pin("dave", Some("w600k_r50"))
+            .await
+            .unwrap();
+
+        assert_eq!(store.remove_all_for_user("dave").await.unwrap(), 2);
+        assert!(store.list_by_user("dave").await.unwrap().is_empty());
+        assert!(store.get_centroid("dave").await.unwrap().is_none());
+        assert_eq!(
+            store
+                .get_user_settings("dave")
+                .await
+                .unwrap()
+                .model_version
+                .as_deref(),
+            Some("w600k_r50")
+        );
+        assert_eq!(store.list_users().await.unwrap(), ["alice", "bob", "carol"]);
+        assert_eq!(store.count_all().await.unwrap(), 4);
+
+        // Nothing left to remove is not an error.
+        assert_eq!(store.remove_all_for_user("dave").await.unwrap(), 0);
+        assert_eq!(store.remove_all_for_user("nobody").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_count_by_model_version_over_mixed_gallery() {
+        let store = mixed_version_store().await;
+        let counts = store.count_by_model_version().await.unwrap();
+        assert_eq!(
+            counts,
+            vec![
+                ModelVersionCount {
+                    model_version: "w600k_r50".into(),
+                    templates: 4,
+                    users: 3,
+                },
+                ModelVersionCount {
+                    model_version: "w800k_r100".into(),
+                    templates: 2,
+                    users: 2,
+                },
+            ]
+        );
+
+        let empty = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert!(empty.count_by_model_version().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_impact_counts_users_without_target_template() {
+        let store = mixed_version_store().await;
+
+        // bob and dave have nothing on the new version.
+        let upgrade = store.upgrade_impact("w800k_r100").await.unwrap();
+        assert_eq!(upgrade.users_enrolled, 4);
+        assert_eq!(upgrade.users_to_reenroll, 2);
+        // carol alone lacks an old template.
+        let current = store.upgrade_impact("w600k_r50").await.unwrap();
+        assert_eq!(current.users_to_reenroll, 1);
+        // Nobody is on an unreleased version.
+        let unknown = store.upgrade_impact("next").await.unwrap();
+        assert_eq!(unknown.users_to_reenroll, 4);
+
+        let empty = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let none = empty.upgrade_impact("w800k_r100").await.unwrap();
+        assert_eq!((none.users_enrolled, none.users_to_reenroll), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_pose_label_added_to_existing_database() {
+        let dir = std::env::temp_dir().join(format!("visage-store-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("faces.db");
+        // A v0.3 database: no pose_label column.
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE faces (
+                     id TEXT PRIMARY KEY,
+                     user TEXT NOT NULL,
+                     label TEXT NOT NULL,
+                     embedding BLOB NOT NULL,
+                     model_version TEXT NOT NULL,
+                     quality_score REAL NOT NULL DEFAULT 0.0,
+                     created_at TEXT NOT NULL
+                 );",
+            )
+            .unwrap();
+
+        let store = FaceModelStore::open(&db_path, 1, false).await.unwrap();
+        let embedding = basis(0);
+        let left = EnrollMeta {
+            pose: Some(Pose::Left),
+            ..Default::default()
+        };
+        store
+            .insert("alice", "default", &embedding, 0.9, left)
+            .await
+            .unwrap();
+        let gallery = store.get_gallery_for_user("alice").await.unwrap();
+        assert_eq!(gallery[0].pose_label, "left");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_reads_not_blocked_by_long_write() {
+        let dir = std::env::temp_dir().join(format!("visage-store-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = FaceModelStore::open(&dir.join("faces.db"), 2, false)
+            .await
+            .unwrap();
+
+        let embedding = basis(0);
+        store
+            .insert("alice", "default", &embedding, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        // Hold a write transaction open on the writer connection.
+        let writer = store.conn.clone();
+        let long_write = tokio::spawn(async move {
+            writer
+                .call(|conn| {
+                    let tx = conn.transaction()?;
+                    tx.execute("UPDATE faces SET label = 'renamed'", [])?;
+                    std::thread::sleep(std::time::Duration::from_millis(800));
+                    tx.commit()?;
+                    Ok(())
+                })
+                .await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let started = std::time::Instant::now();
+        let gallery = store.get_gallery_for_user("alice").await.unwrap();
+        let models = store.list_by_user("alice").await.unwrap();
+        let count = store.count_all().await.unwrap();
+        let elapsed = started.elapsed();
+
+        assert!(
+            elapsed < std::time::Duration::from_millis(400),
+            "reads waited {elapsed:?} for the writer"
+        );
+        // Readers see the last committed snapshot.
+        assert_eq!(gallery.len(), 1);
+        assert_eq!(models[0].label, "default");
+        assert_eq!(count, 1);
+
+        long_write.await.unwrap().unwrap();
+        assert_eq!(
+            store.list_by_user("alice").await.unwrap()[0].label,
+            "renamed"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_model_pin_roundtrip() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user_settings("alice").await.unwrap(),
+            UserSettings::default()
+        );
+
+        store
+            .set_model_pin("alice", Some("w600k_r50"))
+            .await
+            .unwrap();
+        store
+            .set_model_pin("alice", Some("antelopev2"))
+            .await
+            .unwrap();
+        let settings = store.get_user_settings("alice").await.unwrap();
+        assert_eq!(settings.model_version.as_deref(), Some("antelopev2"));
+        assert_eq!(
+            store.get_user_settings("bob").await.unwrap(),
+            UserSettings::default()
+        );
+
+        store.set_model_pin("alice", None).await.unwrap();
+        assert_eq!(
+            store.get_user_settings("alice").await.unwrap(),
+            UserSettings::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_settings_roundtrip() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let docked = "usb:046d:085e.2";
+        let built_in = "usb:04f2:b6d9.2";
+        assert_eq!(
+            store.get_device_settings(docked).await.unwrap(),
+            DeviceSettings::default()
+        );
+
+        let calibrated = |px| DeviceSettings {
+            liveness_min_displacement: Some(px),
+        };
+        store
+            .set_device_settings(docked, &calibrated(0.8))
+            .await
+            .unwrap();
+        store
+            .set_device_settings(built_in, &calibrated(1.5))
+            .await
+            .unwrap();
+        store
+            .set_device_settings(docked, &calibrated(0.6))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_device_settings(docked).await.unwrap(),
+            calibrated(0.6)
+        );
+        assert_eq!(
+            store.get_device_settings(built_in).await.unwrap(),
+            calibrated(1.5)
+        );
+
+        store
+            .set_device_settings(built_in, &DeviceSettings::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_device_settings(built_in).await.unwrap(),
+            DeviceSettings::default()
+        );
+    }
+
+    fn basis(axis: usize) -> Embedding {
+        let mut values = vec![0.0; EMBEDDING_DIM];
+        values[axis] = 1.0;
+        Embedding::normalized(values, Some("w600k_r50".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_collapses_near_duplicates_and_keeps_best() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        let near = |wobble: f32| {
+            let mut values = vec![0.0; EMBEDDING_DIM];
+            values[0] = 1.0;
+            values[1] = wobble;
+            Embedding::normalized(values, Some("w600k_r50".to_string()))
+        };
+        let mut ids = Vec::new();
+        for (wobble, quality) in [(0.00, 0.7), (0.05, 0.9), (0.10, 0.8)] {
+            let id = store
+                .insert(
+                    "alice",
+                    "desk",
+                    &near(wobble),
+                    quality,
+                    EnrollMeta::default(),
+                )
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        let distinct = store
+            .insert("alice", "glasses", &basis(5), 0.6, EnrollMeta::default())
+            .await
+            .unwrap();
+        store
+            .insert("bob", "desk", &near(0.0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.dedupe_user("alice", 0.95).await.unwrap(), 2);
+        let mut left: Vec<String> = store
+            .list_by_user("alice")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        left.sort();
+        let mut expected = vec![ids[1].clone(), distinct];
+        expected.sort();
+        assert_eq!(left, expected);
+        assert_eq!(store.list_by_user("bob").await.unwrap().len(), 1);
+        // Already clean: nothing more to remove.
+        assert_eq!(store.dedupe_user("alice", 0.95).await.unwrap(), 0);
+
+        // Even a threshold that matches everything keeps one template.
+        assert_eq!(store.dedupe_user("alice", -1.0).await.unwrap(), 1);
+        assert_eq!(store.list_by_user("alice").await.unwrap().len(), 1);
+        assert_eq!(store.dedupe_user("nobody", 0.95).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_nearest_template_and_duplicate_marker() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert!(store
+            .nearest_template("alice", &basis(0))
+            .await
+            .unwrap()
+            .is_none());
+
+        let desk = store
+            .insert("alice", "desk", &basis(0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        store
+            .insert("alice", "glasses", &basis(1), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        let legacy =
+            Embedding::normalized(basis(2).values.to_vec(), Some("r34_legacy".to_string()));
+        store
+            .insert("alice", "old", &legacy, 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+
+        let mut values = vec![0.0; EMBEDDING_DIM];
+        values[0] = 1.0;
+        values[1] = 0.1;
+        let probe = Embedding::normalized(values, Some("w600k_r50".to_string()));
+        let (model, similarity) = store
+            .nearest_template("alice", &probe)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(model.id, desk);
+        assert!(similarity > 0.99, "{similarity}");
+        // Only the other recognizer's template lies along axis 2.
+        let (model, similarity) = store
+            .nearest_template("alice", &basis(2))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(model.label, "old");
+        assert!(similarity.abs() < 1e-6, "{similarity}");
+
+        let meta = EnrollMeta {
+            duplicate_of: Some(desk.clone()),
+            ..Default::default()
+        };
+        let again = store
+            .insert("alice", "desk2", &probe, 0.9, meta)
+            .await
+            .unwrap();
+        let models = store.list_by_user("alice").await.unwrap();
+        let marked: Vec<_> = models
+            .iter()
+            .filter_map(|m| Some((m.id.as_str(), m.duplicate_of.as_deref()?)))
+            .collect();
+        assert_eq!(marked, [(again.as_str(), desk.as_str())]);
+    }
+
+    #[tokio::test]
+    async fn test_centroid_follows_enroll_and_remove() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert!(store.get_centroid("alice").await.unwrap().is_none());
+
+        let first = store
+            .insert("alice", "a", &basis(0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        let centroid = store.get_centroid("alice").await.unwrap().unwrap();
+        assert_eq!(centroid.embedding.values, basis(0).values);
+        assert_eq!(
+            centroid.embedding.model_version.as_deref(),
+            Some("w600k_r50")
+        );
+        assert_eq!(centroid.spread, 1.0);
+
+        let second = store
+            .insert("alice", "b", &basis(1), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        let centroid = store.get_centroid("alice").await.unwrap().unwrap();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((centroid.embedding.values[0] - half).abs() < 1e-6);
+        assert!((centroid.embedding.values[1] - half).abs() < 1e-6);
+        assert!(centroid.embedding.is_unit_length());
+        // Each template sits 45° from the centroid.
+        assert!((centroid.spread - half).abs() < 1e-6);
+
+        assert!(store.remove("alice", &first).await.unwrap());
+        let centroid = store.get_centroid("alice").await.unwrap().unwrap();
+        assert_eq!(centroid.embedding.values, basis(1).values);
+        // Another user's enrollment leaves alice's centroid alone.
+        store
+            .insert("bob", "a", &basis(2), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .get_centroid("alice")
+                .await
+                .unwrap()
+                .unwrap()
+                .embedding
+                .values,
+            basis(1).values
+        );
+
+        assert!(store.remove("alice", &second).await.unwrap());
+        assert!(store.get_centroid("alice").await.unwrap().is_none());
+        assert!(store.get_centroid("bob").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_centroid_failure_does_not_fail_a_committed_write() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        // Every centroid write now fails after the template write commits.
+        store
+            .conn
+            .call(|conn| Ok(conn.execute("DROP TABLE user_centroids", [])?))
+            .await
+            .unwrap();
+
+        let id = store
+            .insert("alice", "a", &basis(0), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        assert_eq!(store.list_by_user("alice").await.unwrap().len(), 1);
+        assert!(store.remove("alice", &id).await.unwrap());
+        assert!(store.list_by_user("alice").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_centroids_are_backfilled() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        store
+            .insert("alice", "a", &basis(3), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        // As if enrolled by a release that did not store centroids.
+        store
+            .conn
+            .call(|conn| Ok(conn.execute("DELETE FROM user_centroids", [])?))
+            .await
+            .unwrap();
+        assert!(store.get_centroid("alice").await.unwrap().is_none());
+
+        store.backfill_centroids().await.unwrap();
+        let centroid = store.get_centroid("alice").await.unwrap().unwrap();
+        assert_eq!(centroid.embedding.values, basis(3).values);
+
+        // As if stored by a release that did not record the spread.
+        store
+            .conn
+            .call(|conn| Ok(conn.execute("UPDATE user_centroids SET spread = NULL", [])?))
+            .await
+            .unwrap();
+        assert!(store.get_centroid("alice").await.unwrap().is_none());
+        store.backfill_centroids().await.unwrap();
+        assert_eq!(
+            store.get_centroid("alice").await.unwrap().unwrap().spread,
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backup_restores_into_another_installation() {
+        let root = std::env::temp_dir().join(format!("visage-store-{}", uuid::Uuid::new_v4()));
+        // Sealed labels are re-encrypted for the target like the templates.
+        let source = FaceModelStore::open(&root.join("a/faces.db"), 1, true)
+            .await
+            .unwrap();
+        for (user, label, axis) in [
+            ("alice", "desk", 0),
+            ("alice", "glasses", 1),
+            ("bob", "a", 2),
+        ] {
+            source
+                .insert(user, label, &basis(axis), 0.9, EnrollMeta::default())
+                .await
+                .unwrap();
+        }
+        source
+            .set_model_pin("alice", Some("w600k_r50"))
+            .await
+            .unwrap();
+        let (bundle, templates) = source.backup("hunter2").await.unwrap();
+        assert_eq!(templates, 3);
+        // The bundle carries a key of its own, not the installation's.
+        let payload = protected::unseal(&bundle, "hunter2").unwrap();
+        let (bundle_key, _) = split_backup_payload(&payload).unwrap();
+        assert_ne!(bundle_key, source.enc_key);
+
+        // A separate installation: its own key and its own enrollments.
+        let target = FaceModelStore::open(&root.join("b/faces.db"), 2, false)
+            .await
+            .unwrap();
+        target
+            .insert("carol", "a", &basis(3), 0.9, EnrollMeta::default())
+            .await
+            .unwrap();
+        assert_ne!(source.enc_key, target.enc_key);
+
+        let wrong = target.restore(bundle.clone(), "hunter3").await;
+        assert!(matches!(
+            wrong,
+            Err(StoreError::Bundle(ProtectError::DecryptionFailed))
+        ));
+        assert_eq!(target.list_users().await.unwrap(), ["carol"]);
+
+        assert_eq!(target.restore(bundle, "hunter2").await.unwrap(), 3);
+        assert_eq!(target.list_users().await.unwrap(), ["alice", "bob"]);
+        for user in ["alice", "bob"] {
+            let mut expected = source.get_gallery_for_user(user).await.unwrap();
+            let mut restored = target.get_gallery_for_user(user).await.unwrap();
+            expected.sort_by(|a, b| a.id.cmp(&b.id));
+            restored.sort_by(|a, b| a.id.cmp(&b.id));
+            assert_eq!(restored.len(), expected.len());
+            for (r, e) in restored.iter().zip(&expected) {
+                assert_eq!((&r.id, &r.label), (&e.id, &e.label));
+                assert_eq!(r.embedding.values, e.embedding.values);
+            }
+            assert!(target.get_centroid(user).await.unwrap().is_some());
+        }
+        assert_eq!(
+            target
+                .get_user_settings("alice")
+                .await
+                .unwrap()
+                .model_version
+                .as_deref(),
+            Some("w600k_r50")
+        );
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_foreign_bundles() {
+        let store = FaceModelStore::open(Path::new(":memory:"), 1, false)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.backup("").await,
+            Err(StoreError::Bundle(ProtectError::EmptyPassphrase))
+        ));
+
+        let sealed = protected::seal(b"VSGE not a database", "pw", 1_000).unwrap();
+        assert!(matches!(
+            store.restore(sealed, "pw").await,
+            Err(StoreError::Bundle(ProtectError::Malformed(_)))
+        ));
+    }
 }
@@ -0,0 +1,188 @@
+//! Canonical form of usernames received over D-Bus.
+//!
+//! D-Bus strings must be UTF-8, so an account whose name is not valid UTF-8
+//! cannot be named directly. With `VISAGE_PERCENT_ENCODED_USERNAMES=1` clients
+//! send such names percent-encoded (`%XX` per byte, `%25` for a literal `%`),
+//! and every spelling of the same byte string maps to one canonical key: bytes
+//! that form valid UTF-8 stay literal, everything else — and `%` itself —
+//! becomes `%XX` with uppercase hex. Enroll, verify, list and remove all key
+//! on that form, so galleries line up however a client chose to encode.
+//!
+//! Without the option names are taken literally, after basic validation.
+
+use thiserror::Error;
+
+/// Longest accepted username, in bytes (after decoding).
+pub const MAX_USERNAME_LEN: usize = 256;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum UsernameError {
+    #[error("username is empty")]
+    Empty,
+    #[error("username exceeds {MAX_USERNAME_LEN} bytes")]
+    TooLong,
+    #[error("username contains forbidden byte {0:#04x}")]
+    ForbiddenByte(u8),
+    #[error("malformed percent escape at byte {0}")]
+    InvalidEscape(usize),
+}
+
+/// Validate `raw` and return its canonical store key.
+pub fn canonicalize(raw: &str, percent_encoded: bool) -> Result<String, UsernameError> {
+    if !percent_encoded {
+        validate(raw.as_bytes())?;
+        return Ok(raw.to_string());
+    }
+    let bytes = decode(raw)?;
+    validate(&bytes)?;
+    Ok(encode(&bytes))
+}
+
+/// The name to look up via NSS for a canonical key, or `None` when the
+/// account name is not valid UTF-8 (NSS lookups here are `&str`-only).
+pub fn nss_name(canonical: &str, percent_encoded: bool) -> Option<String> {
+    if !percent_encoded {
+        return Some(canonical.to_string());
+    }
+    decode(canonical)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// Percent-encode raw username bytes into canonical form.
+///
+/// `pam_visage` applies the same encoding when its `percent_encode_usernames`
+/// option is set; the two must stay in sync.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                push_escaped(&mut out, valid);
+                return out;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                let valid = std::str::from_utf8(valid).expect("prefix is valid UTF-8");
+                push_escaped(&mut out, valid);
+                let bad = e.error_len().unwrap_or(after.len());
+                for b in &after[..bad] {
+                    out.push_str(&format!("%{b:02X}"));
+                }
+                rest = &after[bad..];
+            }
+        }
+    }
+}
+
+fn push_escaped(out: &mut String, s: &str) {
+    for c in s.chars() {
+        if c == '%' {
+            out.push_str("%25");
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+/// Decode `%XX` escapes into raw bytes. Other characters pass through.
+fn decode(raw: &str) -> Result<Vec<u8>, UsernameError> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or(UsernameError::InvalidEscape(i))?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+fn validate(bytes: &[u8]) -> Result<(), UsernameError> {
+    if bytes.is_empty() {
+        return Err(UsernameError::Empty);
+    }
+    if bytes.len() > MAX_USERNAME_LEN {
+        return Err(UsernameError::TooLong);
+    }
+    match bytes.iter().find(|&&b| b < 0x20 || b == 0x7f || b == b'/') {
+        Some(&b) => Err(UsernameError::ForbiddenByte(b)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_utf8_username_round_trip() {
+        // Latin-1 "josé" as an account name on a non-UTF-8 system.
+        let raw = b"jos\xe9";
+        let sent = encode(raw);
+        assert_eq!(sent, "jos%E9");
+
+        // Lower-case hex from a different client canonicalizes identically.
+        let enrolled = canonicalize(&sent, true).unwrap();
+        let verified = canonicalize("jos%e9", true).unwrap();
+        assert_eq!(enrolled, verified);
+        assert_eq!(decode(&enrolled).unwrap(), raw);
+        assert_eq!(nss_name(&enrolled, true), None);
+    }
+
+    #[test]
+    fn test_utf8_names_stay_literal() {
+        assert_eq!(encode("josé".as_bytes()), "josé");
+        assert_eq!(canonicalize("jos%C3%A9", true).unwrap(), "josé");
+        assert_eq!(nss_name("josé", true).as_deref(), Some("josé"));
+    }
+
+    #[test]
+    fn test_literal_percent_is_escaped() {
+        assert_eq!(encode(b"a%b"), "a%25b");
+        assert_eq!(canonicalize("a%25b", true).unwrap(), "a%25b");
+        assert_eq!(nss_name("a%25b", true).as_deref(), Some("a%b"));
+    }
+
+    #[test]
+    fn test_strict_mode_is_literal() {
+        assert_eq!(canonicalize("alice", false).unwrap(), "alice");
+        assert_eq!(canonicalize("a%41", false).unwrap(), "a%41");
+    }
+
+    #[test]
+    fn test_rejects_malformed_names() {
+        assert_eq!(canonicalize("", false), Err(UsernameError::Empty));
+        assert_eq!(
+            canonicalize("../etc", false),
+            Err(UsernameError::ForbiddenByte(b'/'))
+        );
+        assert_eq!(
+            canonicalize("bob%00", true),
+            Err(UsernameError::ForbiddenByte(0))
+        );
+        assert_eq!(
+            canonicalize("bob%4", true),
+            Err(UsernameError::InvalidEscape(3))
+        );
+        assert_eq!(
+            canonicalize("bob%+1", true),
+            Err(UsernameError::InvalidEscape(3))
+        );
+        assert_eq!(
+            canonicalize(&"x".repeat(MAX_USERNAME_LEN + 1), false),
+            Err(UsernameError::TooLong)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Captures in flight per D-Bus connection, backing `Cancel` and
+//! `CancelUser`.
+//!
+//! On GDM the face prompt and the password field are live together, so a
+//! user who starts typing wants the capture to stop instead of finishing
+//! seconds later. Each verify or enrollment registers a [`CancelToken`]
+//! under the caller's unique bus name and the user it captures for; `Cancel`
+//! sets the tokens of that connection's captures only. The bus vouches for
+//! the sender name, so no caller can cancel another's capture and the method
+//! needs no further access check. `CancelUser` sets every token registered
+//! for one user, from any connection, and is checked like the user's own
+//! templates.
+
+use std::collections::HashMap;
+
+use crate::engine::CancelToken;
+
+/// Identifies one registered capture, for [`PendingVerifies::finish`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ticket(u64);
+
+/// A registered capture: the connection that asked for it, the user it
+/// captures for (none for `Identify`) and its cancel token.
+struct Pending {
+    client: String,
+    user: Option<String>,
+    token: CancelToken,
+}
+
+/// Cancel tokens of the verifies and enrollments waiting on the engine.
+#[derive(Default)]
+pub struct PendingVerifies {
+    next: u64,
+    pending: HashMap<Ticket, Pending>,
+}
+
+impl PendingVerifies {
+    /// Register a capture for `user` from the connection `client`.
+    pub fn register(&mut self, client: &str, user: Option<&str>) -> (Ticket, CancelToken) {
+        let ticket = Ticket(self.next);
+        self.next += 1;
+        let token = CancelToken::default();
+        self.pending.insert(
+            ticket,
+            Pending {
+                client: client.to_string(),
+                user: user.map(str::to_string),
+                token: token.clone(),
+            },
+        );
+        (ticket, token)
+    }
+
+    /// Drop a capture once the engine has answered it.
+    pub fn finish(&mut self, ticket: Ticket) {
+        self.pending.remove(&ticket);
+    }
+
+    /// Cancel every capture `client` has registered; returns how many.
+    pub fn cancel(&mut self, client: &str) -> usize {
+        self.cancel_where(|pending| pending.client == client)
+    }
+
+    /// Cancel every capture registered for `user`, whichever connection
+    /// asked for it; returns how many.
+    pub fn cancel_user(&mut self, user: &str) -> usize {
+        self.cancel_where(|pending| pending.user.as_deref() == Some(user))
+    }
+
+    fn cancel_where(&self, matches: impl Fn(&Pending) -> bool) -> usize {
+        let mut cancelled = 0;
+        for pending in self.pending.values().filter(|pending| matches(pending)) {
+            pending.token.cancel();
+            cancelled += 1;
+        }
+        cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_reaches_only_the_callers_verifies() {
+        let mut pending = PendingVerifies::default();
+        let (first, first_token) = pending.register(":1.7", Some("alice"));
+        let (_, second_token) = pending.register(":1.7", None);
+        let (_, other_token) = pending.register(":1.8", Some("alice"));
+
+        assert_eq!(pending.cancel(":1.9"), 0);
+        assert_eq!(pending.cancel(":1.7"), 2);
+        assert!(first_token.is_cancelled());
+        assert!(second_token.is_cancelled());
+        assert!(!other_token.is_cancelled());
+
+        pending.finish(first);
+        assert_eq!(pending.cancel(":1.7"), 1);
+    }
+    #[test]
+    fn test_cancel_user_reaches_that_users_captures_from_any_connection() {
+        let mut pending = PendingVerifies::default();
+        let (_, alice_gdm) = pending.register(":1.7", Some("alice"));
+        let (_, alice_cli) = pending.register(":1.8", Some("alice"));
+        let (_, bob) = pending.register(":1.7", Some("bob"));
+        let (_, identify) = pending.register(":1.9", None);
+
+        assert_eq!(pending.cancel_user("carol"), 0);
+        assert_eq!(pending.cancel_user("alice"), 2);
+        assert!(alice_gdm.is_cancelled());
+        assert!(alice_cli.is_cancelled());
+        assert!(!bob.is_cancelled());
+        assert!(!identify.is_cancelled());
+    }
+}
@@ -0,0 +1,145 @@
+//! Recent verify successes backing `VerifyCached`.
+//!
+//! A screen unlock followed straight away by `sudo` would otherwise capture
+//! twice. After a match the daemon remembers when `user` last verified in
+//! the caller's logind session; `VerifyCached` answers from that without the
+//! camera while the success is younger than both the caller's `max_age` and
+//! the daemon's cap. Sessions, not caller UIDs, key the entries: every setuid
+//! PAM caller runs as root, so a UID would let any other login reuse them. Any verify for the user that does not end in a match forgets every
+//! entry for them. Nothing is persisted, so a restart starts empty.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Last verify success per (user, caller's logind session object path).
+pub struct VerifyGrace {
+    max_age: Duration,
+    successes: HashMap<(String, String), Instant>,
+}
+
+impl VerifyGrace {
+    /// `max_age` caps what any caller may ask for; zero disables the cache.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            successes: HashMap::new(),
+        }
+    }
+
+    /// Whether successes are remembered at all.
+    pub fn enabled(&self) -> bool {
+        !self.max_age.is_zero()
+    }
+
+    /// Remember that `user` just verified in `session`.
+    pub fn record_success(&mut self, user: &str, session: &str) {
+        self.record_success_at(user, session, Instant::now());
+    }
+
+    fn record_success_at(&mut self, user: &str, session: &str, now: Instant) {
+        if self.max_age.is_zero() {
+            return;
+        }
+        let max_age = self.max_age;
+        self.successes
+            .retain(|_, at| now.duration_since(*at) <= max_age);
+        self.successes
+            .insert((user.to_string(), session.to_string()), now);
+    }
+
+    /// Forget `user`'s successes in every session, after a failed verify.
+    pub fn forget_user(&mut self, user: &str) {
+        self.successes.retain(|(u, _), _| u != user);
+    }
+
+    /// Forget everyone's successes, e.g. when the verification policy changes.
+    pub fn clear(&mut self) {
+        self.successes.clear();
+    }
+
+    /// Whether `user` verified in `session` within `max_age`, itself capped
+    /// at the daemon's limit.
+    pub fn is_fresh(&self, user: &str, session: &str, max_age: Duration) -> bool {
+        self.is_fresh_at(user, session, max_age, Instant::now())
+    }
+
+    fn is_fresh_at(&self, user: &str, session: &str, max_age: Duration, now: Instant) -> bool {
+        let max_age = max_age.min(self.max_age);
+        self.successes
+            .get(&(user.to_string(), session.to_string()))
+            .is_some_and(|at| now.duration_since(*at) <= max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAP: Duration = Duration::from_secs(60);
+    const TTY1: &str = "/org/freedesktop/login1/session/_31";
+    const TTY2: &str = "/org/freedesktop/login1/session/_32";
+    const SEAT: &str = "/org/freedesktop/login1/session/c1";
+
+    #[test]
+    fn test_success_expires_at_the_smaller_of_request_and_cap() {
+        let mut grace = VerifyGrace::new(CAP);
+        let t0 = Instant::now();
+        grace.record_success_at("alice", TTY1, t0);
+
+        let ten = Duration::from_secs(10);
+        assert!(grace.is_fresh_at("alice", TTY1, ten, t0 + ten));
+        assert!(!grace.is_fresh_at("alice", TTY1, ten, t0 + ten + Duration::from_secs(1)));
+        // Asking for longer than the cap gets the cap.
+        let hour = Duration::from_secs(3600);
+        assert!(grace.is_fresh_at("alice", TTY1, hour, t0 + CAP));
+        assert!(!grace.is_fresh_at("alice", TTY1, hour, t0 + CAP + Duration::from_secs(1)));
+        assert!(!grace.is_fresh_at("alice", TTY1, Duration::ZERO, t0 + Duration::from_millis(1)));
+
+        let mut disabled = VerifyGrace::new(Duration::ZERO);
+        disabled.record_success_at("alice", TTY1, t0);
+        assert!(!disabled.is_fresh_at("alice", TTY1, hour, t0));
+    }
+
+    #[test]
+    fn test_failure_forgets_the_user_in_every_session() {
+        let mut grace = VerifyGrace::new(CAP);
+        let t0 = Instant::now();
+        grace.record_success_at("alice", TTY1, t0);
+        grace.record_success_at("alice", SEAT, t0);
+        grace.record_success_at("bob", TTY2, t0);
+
+        grace.forget_user("alice");
+        assert!(!grace.is_fresh_at("alice", TTY1, CAP, t0));
+        assert!(!grace.is_fresh_at("alice", SEAT, CAP, t0));
+        assert!(grace.is_fresh_at("bob", TTY2, CAP, t0));
+
+        grace.clear();
+        assert!(!grace.is_fresh_at("bob", TTY2, CAP, t0));
+    }
+
+    #[test]
+    fn test_success_is_per_user_and_session() {
+        let mut grace = VerifyGrace::new(CAP);
+        let t0 = Instant::now();
+        grace.record_success_at("alice", TTY1, t0);
+
+        assert!(grace.is_fresh_at("alice", TTY1, CAP, t0));
+        assert!(!grace.is_fresh_at("bob", TTY1, CAP, t0));
+        assert!(!grace.is_fresh_at("alice", TTY2, CAP, t0));
+        assert!(!grace.is_fresh_at("alice", SEAT, CAP, t0));
+    }
+
+    #[test]
+    fn test_root_callers_in_other_sessions_do_not_share_a_success() {
+        // sudo in one terminal and su in another both reach the daemon as
+        // UID 0; the match in the first must not answer for the second.
+        let mut grace = VerifyGrace::new(CAP);
+        let t0 = Instant::now();
+        grace.record_success_at("alice", TTY1, t0);
+
+        assert!(grace.is_fresh_at("alice", TTY1, CAP, t0));
+        assert!(!grace.is_fresh_at("alice", TTY2, CAP, t0));
+        assert!(!VerifyGrace::new(Duration::ZERO).enabled());
+        assert!(grace.enabled());
+    }
+}
@@ -0,0 +1,192 @@
+//! The verification policy bundle behind `GetPolicy` / `SetPolicy`.
+//!
+//! Collects the settings that decide whether a verify passes (threshold,
+//! liveness, matcher choice and the pose and camera requirements) so an
+//! administrator can tighten them at runtime, e.g. after a suspected spoof,
+//! without restarting the daemon. The JSON keys are the field names `Status`
+//! reports. An update names only the fields it changes; the merged bundle is
+//! validated as a whole and applied in one step or not at all.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::{self, Config, ConfigError};
+
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("invalid policy: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("liveness_min_displacement {0} must be a non-negative number of pixels")]
+    InvalidDisplacement(f32),
+    #[error(
+        "liveness_min_live_pairs {pairs} needs at least {needed} frames per verify \
+         (VISAGE_FRAMES_PER_VERIFY is {frames})"
+    )]
+    UnreachableLivePairs {
+        pairs: usize,
+        needed: usize,
+        frames: usize,
+    },
+    #[error("centroid_matching has no effect with verify_require_pose_match; disable one")]
+    CentroidWithPoseMatch,
+}
+
+/// The verify-time settings `SetPolicy` may change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyPolicy {
+    pub similarity_threshold: f32,
+    pub liveness_enabled: bool,
+    pub liveness_min_displacement: f32,
+    pub liveness_min_live_pairs: usize,
+    pub liveness_min_frame_gap_ms: u64,
+    pub centroid_matching: bool,
+    pub centroid_margin: f32,
+    pub verify_require_pose_match: bool,
+    pub verify_require_enrolled_device: bool,
+}
+
+impl VerifyPolicy {
+    /// The policy `config` currently applies.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            similarity_threshold: config.similarity_threshold,
+            liveness_enabled: config.liveness_enabled,
+            liveness_min_displacement: config.liveness_min_displacement,
+            liveness_min_live_pairs: config.liveness_min_live_pairs,
+            liveness_min_frame_gap_ms: config.liveness_min_frame_gap_ms,
+            centroid_matching: config.centroid_matching,
+            centroid_margin: config.centroid_margin,
+            verify_require_pose_match: config.verify_require_pose_match,
+            verify_require_enrolled_device: config.verify_require_enrolled_device,
+        }
+    }
+
+    /// This policy with the fields of the JSON object `update` replaced.
+    /// Unknown fields and values of the wrong type are rejected.
+    pub fn merged(&self, update: &str) -> Result<Self, PolicyError> {
+        let update: serde_json::Map<String, serde_json::Value> = serde_json::from_str(update)?;
+        let mut merged = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(fields) = &mut merged {
+            fields.extend(update);
+        }
+        Ok(serde_json::from_value(merged)?)
+    }
+
+    /// Check the bundle against itself and the parts of `config` it cannot
+    /// change: the insecure-threshold override and the frames per verify.
+    pub fn validate(&self, config: &Config) -> Result<(), PolicyError> {
+        config::check_threshold(self.similarity_threshold, config.allow_insecure_threshold)?;
+        if !(0.0..=1.0).contains(&self.centroid_margin) {
+            return Err(ConfigError::InvalidCentroidMargin(self.centroid_margin).into());
+        }
+        if self.liveness_min_displacement < 0.0 {
+            return Err(PolicyError::InvalidDisplacement(
+                self.liveness_min_displacement,
+            ));
+        }
+        // N pairs take N + 1 frames with a face.
+        let needed = self.liveness_min_live_pairs + 1;
+        if self.liveness_enabled
+            && self.liveness_min_live_pairs > 0
+            && needed > config.frames_per_verify
+        {
+            return Err(PolicyError::UnreachableLivePairs {
+                pairs: self.liveness_min_live_pairs,
+                needed,
+                frames: config.frames_per_verify,
+            });
+        }
+        if self.centroid_matching && self.verify_require_pose_match {
+            return Err(PolicyError::CentroidWithPoseMatch);
+        }
+        Ok(())
+    }
+
+    /// Make this policy the one `config` applies.
+    pub fn apply(&self, config: &mut Config) {
+        config.similarity_threshold = self.similarity_threshold;
+        config.liveness_enabled = self.liveness_enabled;
+        config.liveness_min_displacement = self.liveness_min_displacement;
+        config.liveness_min_live_pairs = self.liveness_min_live_pairs;
+        config.liveness_min_frame_gap_ms = self.liveness_min_frame_gap_ms;
+        config.centroid_matching = self.centroid_matching;
+        config.centroid_margin = self.centroid_margin;
+        config.verify_require_pose_match = self.verify_require_pose_match;
+        config.verify_require_enrolled_device = self.verify_require_enrolled_device;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_replaces_only_named_fields() {
+        let config = Config::from_pairs(&[]).unwrap();
+        let current = VerifyPolicy::from_config(&config);
+        let strict = current
+            .merged(r#"{"similarity_threshold": 0.6, "verify_require_enrolled_device": true}"#)
+            .unwrap();
+        assert_eq!(strict.similarity_threshold, 0.6);
+        assert!(strict.verify_require_enrolled_device);
+        assert_eq!(
+            VerifyPolicy {
+                similarity_threshold: current.similarity_threshold,
+                verify_require_enrolled_device: false,
+                ..strict.clone()
+            },
+            current
+        );
+        strict.validate(&config).unwrap();
+
+        let mut applied = config.clone();
+        strict.apply(&mut applied);
+        assert_eq!(VerifyPolicy::from_config(&applied), strict);
+
+        for bad in [
+            r#"{"similarity_treshold": 0.6}"#,
+            r#"{"liveness_enabled": "yes"}"#,
+            r#"[0.6]"#,
+            "",
+        ] {
+            assert!(
+                matches!(current.merged(bad), Err(PolicyError::Parse(_))),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_bundles_rejected() {
+        let config = Config::from_pairs(&[("VISAGE_FRAMES_PER_VERIFY", "3")]).unwrap();
+        let current = VerifyPolicy::from_config(&config);
+        let check = |update: &str| current.merged(update).unwrap().validate(&config);
+
+        assert!(matches!(
+            check(r#"{"similarity_threshold": 0.1}"#),
+            Err(PolicyError::Config(ConfigError::InsecureThreshold(_)))
+        ));
+        assert!(matches!(
+            check(r#"{"centroid_margin": 1.5}"#),
+            Err(PolicyError::Config(ConfigError::InvalidCentroidMargin(_)))
+        ));
+        assert!(matches!(
+            check(r#"{"liveness_min_displacement": -1.0}"#),
+            Err(PolicyError::InvalidDisplacement(_))
+        ));
+        assert!(matches!(
+            check(r#"{"liveness_min_live_pairs": 3}"#),
+            Err(PolicyError::UnreachableLivePairs { needed: 4, .. })
+        ));
+        check(r#"{"liveness_min_live_pairs": 2}"#).unwrap();
+        // Without liveness the pair count is not used by verify.
+        check(r#"{"liveness_enabled": false, "liveness_min_live_pairs": 3}"#).unwrap();
+        assert!(matches!(
+            check(r#"{"centroid_matching": true, "verify_require_pose_match": true}"#),
+            Err(PolicyError::CentroidWithPoseMatch)
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! One-time verification tokens backing `VerifyWithToken` / `RedeemToken`.
+//!
+//! A successful `VerifyWithToken` may hand back a random 128-bit token bound
+//! to the user and the caller's logind session. `RedeemToken` accepts it once,
+//! within the TTL, from a caller in that same session — so a polkit prompt
+//! straight after a screen unlock need not capture again, while a token
+//! copied to another session is worthless. Each user holds at most one token:
+//! a new verify replaces the previous one.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("unknown or already used verification token")]
+    UnknownToken,
+    #[error("verification token expired")]
+    Expired,
+    #[error("verification token was issued for another user")]
+    WrongUser,
+    #[error("verification token was issued to another session")]
+    WrongSession,
+}
+
+struct Grant {
+    user: String,
+    session: String,
+    issued: Instant,
+}
+
+/// Outstanding tokens keyed by token.
+pub struct VerifyTokens {
+    ttl: Duration,
+    grants: HashMap<String, Grant>,
+}
+
+impl VerifyTokens {
+    /// A zero `ttl` disables tokens: [`issue`](Self::issue) returns `None`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            grants: HashMap::new(),
+        }
+    }
+
+    /// Issue a token for `user` in logind session `session`.
+    pub fn issue(&mut self, user: &str, session: &str) -> Option<String> {
+        self.issue_at(user, session, Instant::now())
+    }
+
+    fn issue_at(&mut self, user: &str, session: &str, now: Instant) -> Option<String> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let ttl = self.ttl;
+        self.grants
+            .retain(|_, g| g.user != user && now.duration_since(g.issued) <= ttl);
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        self.grants.insert(
+            token.clone(),
+            Grant {
+                user: user.to_string(),
+                session: session.to_string(),
+                issued: now,
+            },
+        );
+        Some(token)
+    }
+
+    /// Redeem `token` for `user` from `session`, consuming it.
+    ///
+    /// A token presented for the wrong user or from another session is left
+    /// in place so its rightful holder can still redeem it.
+    pub fn redeem(&mut self, token: &str, user: &str, session: &str) -> Result<(), TokenError> {
+        self.redeem_at(token, user, session, Instant::now())
+    }
+
+    fn redeem_at(
+        &mut self,
+        token: &str,
+        user: &str,
+        session: &str,
+        now: Instant,
+    ) -> Result<(), TokenError> {
+        let grant = self.grants.get(token).ok_or(TokenError::UnknownToken)?;
+        if now.duration_since(grant.issued) > self.ttl {
+            self.grants.remove(token);
+            return Err(TokenError::Expired);
+        }
+        if grant.user != user {
+            return Err(TokenError::WrongUser);
+        }
+        if grant.session != session {
+            return Err(TokenError::WrongSession);
+        }
+        self.grants.remove(token);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_token_redeems_once() {
+        let mut tokens = VerifyTokens::new(TTL);
+        let t0 = Instant::now();
+        let token = tokens.issue_at("alice", "c2", t0).unwrap();
+        assert_eq!(token.len(), 32);
+
+        assert_eq!(tokens.redeem_at(&token, "alice", "c2", t0 + TTL), Ok(()));
+        assert_eq!(
+            tokens.redeem_at(&token, "alice", "c2", t0),
+            Err(TokenError::UnknownToken)
+        );
+        assert_eq!(
+            tokens.redeem_at("bogus", "alice", "c2", t0),
+            Err(TokenError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn test_token_rejected_from_another_session_or_user() {
+        let mut tokens = VerifyTokens::new(TTL);
+        let t0 = Instant::now();
+        let token = tokens.issue_at("alice", "c2", t0).unwrap();
+
+        assert_eq!(
+            tokens.redeem_at(&token, "alice", "c7", t0),
+            Err(TokenError::WrongSession)
+        );
+        assert_eq!(
+            tokens.redeem_at(&token, "bob", "c2", t0),
+            Err(TokenError::WrongUser)
+        );
+        // Neither attempt used it up.
+        assert_eq!(tokens.redeem_at(&token, "alice", "c2", t0), Ok(()));
+    }
+
+    #[test]
+    fn test_token_expires_and_is_replaced_by_the_next_verify() {
+        let mut tokens = VerifyTokens::new(TTL);
+        let t0 = Instant::now();
+        let stale = tokens.issue_at("alice", "c2", t0).unwrap();
+        let late = t0 + TTL + Duration::from_secs(1);
+        assert_eq!(
+            tokens.redeem_at(&stale, "alice", "c2", late),
+            Err(TokenError::Expired)
+        );
+        assert!(tokens.grants.is_empty());
+
+        let first = tokens.issue_at("alice", "c2", t0).unwrap();
+        let second = tokens.issue_at("alice", "c2", t0).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            tokens.redeem_at(&first, "alice", "c2", t0),
+            Err(TokenError::UnknownToken)
+        );
+        assert_eq!(tokens.redeem_at(&second, "alice", "c2", t0), Ok(()));
+
+        assert_eq!(VerifyTokens::new(Duration::ZERO).issue("alice", "c2"), None);
+    }
+}
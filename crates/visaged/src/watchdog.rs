@@ -0,0 +1,213 @@
+//! Engine watchdog (`VISAGE_ENGINE_WATCHDOG_SECS`).
+//!
+//! Some V4L2 ioctls block uninterruptibly, so deadline checks inside the
+//! engine thread cannot rescue a wedged capture. The watchdog polls the
+//! engine's [`Heartbeat`](crate::engine::Heartbeat); once a request has gone
+//! `stale_after` without progress it abandons the engine — its callers get
+//! `EngineRestarted` — and installs a freshly spawned one.
+//!
+//! The wedged thread is not killed: there is no safe way to stop a thread
+//! blocked in the kernel, so it is left behind, still holding its camera
+//! handle. On devices that allow a single streamer the replacement may fail
+//! to open the camera; the watchdog then retries on every poll.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::dbus_interface::AppState;
+use crate::engine::{EngineError, EngineHandle};
+
+/// Spawns a replacement engine (opens the camera, reloads models).
+pub type Respawn = Arc<dyn Fn() -> Result<EngineHandle, EngineError> + Send + Sync>;
+
+/// Poll the engine heartbeat forever, replacing the engine whenever it stalls.
+pub async fn run(state: Arc<Mutex<AppState>>, stale_after: Duration, respawn: Respawn) {
+    let mut ticker = tokio::time::interval((stale_after / 4).max(Duration::from_millis(250)));
+    loop {
+        ticker.tick().await;
+        check(&state, stale_after, &respawn).await;
+    }
+}
+
+/// Replace the engine if its in-flight request has stalled for `stale_after`.
+/// Returns whether a replacement was installed.
+pub async fn check(state: &Mutex<AppState>, stale_after: Duration, respawn: &Respawn) -> bool {
+    let engine = state.lock().await.engine.clone();
+    let Some(stalled) = engine.stalled_for().filter(|s| *s >= stale_after) else {
+        return false;
+    };
+
+    tracing::error!(
+        stalled_secs = stalled.as_secs_f32(),
+        "engine thread unresponsive — abandoning it and starting a replacement"
+    );
+    engine.abandon();
+
+    let respawn = respawn.clone();
+    match tokio::task::spawn_blocking(move || respawn()).await {
+        Ok(Ok(replacement)) => {
+            state.lock().await.engine = replacement;
+            tracing::warn!("engine restarted by watchdog");
+            true
+        }
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "engine restart failed; retrying on next poll");
+            false
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "engine restart panicked; retrying on next poll");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::CancelToken;
+    use crate::polkit::{Authorization, MockAuthority};
+    use crate::preview::PreviewSessions;
+    use crate::rate_limiter::{EnrollCooldown, RateLimiter};
+    use crate::selftest::SelfTestState;
+    use crate::stats::QualityStats;
+    use crate::store::FaceModelStore;
+    use crate::verify_cancel::PendingVerifies;
+    use crate::verify_grace::VerifyGrace;
+    use crate::verify_token::VerifyTokens;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn state_with(engine: EngineHandle) -> Arc<Mutex<AppState>> {
+        Arc::new(Mutex::new(AppState {
+            config: Config::from_pairs(&[]).unwrap(),
+            engine,
+            store: FaceModelStore::open(Path::new(":memory:"), 1, false)
+                .await
+                .unwrap(),
+            rate_limiter: RateLimiter::new(),
+            enroll_cooldown: EnrollCooldown::new(Duration::ZERO),
+            previews: PreviewSessions::new(std::time::Duration::from_secs(30)),
+            verify_tokens: VerifyTokens::new(Duration::ZERO),
+            verify_grace: VerifyGrace::new(Duration::ZERO),
+            pending_verifies: PendingVerifies::default(),
+            stats: QualityStats::new(),
+            selftest: SelfTestState::default(),
+            log_filter: None,
+            authority: Arc::new(MockAuthority::answering(Authorization::NotAuthorized)),
+        }))
+    }
+
+    /// Respawn hook that counts calls and hands out identifiable handles.
+    fn counting_respawn() -> (Respawn, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let respawn: Respawn = Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(EngineHandle::degraded("replacement"))
+        });
+        (respawn, calls)
+    }
+
+    #[tokio::test]
+    async fn test_stale_heartbeat_replaces_engine_and_fails_callers() {
+        let engine = EngineHandle::stuck();
+        let state = state_with(engine.clone()).await;
+        let (respawn, calls) = counting_respawn();
+        let stale_after = Duration::from_millis(50);
+
+        // A caller waiting on the engine, which picked up the request and stalled.
+        let caller = {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                engine
+                    .verify(
+                        vec![],
+                        None,
+                        0.4,
+                        3,
+                        Duration::from_secs(30),
+                        None,
+                        None,
+                        None,
+                        None,
+                        CancelToken::default(),
+                    )
+                    .await
+            })
+        };
+        engine.heartbeat().beat();
+
+        assert!(!check(&state, stale_after, &respawn).await);
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(check(&state, stale_after, &respawn).await);
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            state.lock().await.engine.recognizer_fault().as_deref(),
+            Some("replacement")
+        );
+        let result = caller.await.unwrap();
+        assert!(matches!(result, Err(EngineError::EngineRestarted)));
+
+        // New requests through a stale clone of the old handle fail the same way.
+        let result = engine
+            .enroll(
+                3,
+                false,
+                0.0,
+                Duration::from_secs(1),
+                None,
+                None,
+                CancelToken::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EngineError::EngineRestarted)));
+    }
+
+    #[tokio::test]
+    async fn test_idle_engine_is_never_restarted() {
+        let engine = EngineHandle::stuck();
+        let state = state_with(engine.clone()).await;
+        let (respawn, calls) = counting_respawn();
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(!check(&state, Duration::from_millis(50), &respawn).await);
+
+        // Progress keeps resetting the clock.
+        for _ in 0..3 {
+            engine.heartbeat().beat();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(!check(&state, Duration::from_millis(50), &respawn).await);
+        }
+        engine.heartbeat().idle();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(!check(&state, Duration::from_millis(50), &respawn).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_respawn_retries_on_next_poll() {
+        let engine = EngineHandle::stuck();
+        let state = state_with(engine.clone()).await;
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let respawn: Respawn = {
+            let attempts = attempts.clone();
+            Arc::new(move || {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(EngineError::ChannelClosed)
+                } else {
+                    Ok(EngineHandle::degraded("replacement"))
+                }
+            })
+        };
+
+        engine.heartbeat().beat();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        let stale_after = Duration::from_millis(50);
+        assert!(!check(&state, stale_after, &respawn).await);
+        assert!(check(&state, stale_after, &respawn).await);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
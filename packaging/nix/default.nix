@@ -45,6 +45,16 @@ rustPlatform.buildRustPackage {
     install -Dm644 packaging/dbus/org.freedesktop.Visage1.conf \
       $out/share/dbus-1/system.d/org.freedesktop.Visage1.conf
 
+    # D-Bus interface contract, for client code generation
+    install -Dm644 packaging/dbus/org.freedesktop.Visage1.xml \
+      $out/share/dbus-1/interfaces/org.freedesktop.Visage1.xml
+
+    # polkit action and group rule
+    install -Dm644 packaging/polkit/org.freedesktop.Visage1.policy \
+      $out/share/polkit-1/actions/org.freedesktop.Visage1.policy
+    install -Dm644 packaging/polkit/49-visage.rules \
+      $out/share/polkit-1/rules.d/49-visage.rules
+
     # systemd units — patch ExecStart to reference the Nix store path
     install -Dm644 packaging/systemd/visaged.service \
       $out/lib/systemd/system/visaged.service